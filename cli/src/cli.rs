@@ -60,8 +60,8 @@ pub enum Subcommand {
 #[allow(missing_docs)]
 #[derive(Debug, StructOpt)]
 pub struct ValidationWorkerCommand {
-	#[allow(missing_docs)]
-	pub mem_id: String,
+	/// The file descriptor of the socket connected to the validation host.
+	pub socket_fd: String,
 }
 
 #[allow(missing_docs)]
@@ -101,6 +101,18 @@ pub struct RunCmd {
 	/// elapsed (i.e. until a block at height `pause_block + delay` is imported).
 	#[structopt(long = "grandpa-pause", number_of_values(2))]
 	pub grandpa_pause: Vec<u32>,
+
+	/// Run the parachain subsystems instead of the dummy ones in the overseer.
+	///
+	/// Only supported by the new service.
+	#[structopt(long = "real-overseer")]
+	pub real_overseer: bool,
+
+	/// The number of workers validating parachain candidates in parallel.
+	///
+	/// Defaults to the number of CPUs. Only supported by the new service with `--real-overseer`.
+	#[structopt(long = "validation-workers")]
+	pub validation_workers: Option<usize>,
 }
 
 #[allow(missing_docs)]
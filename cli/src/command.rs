@@ -128,6 +128,18 @@ pub fn run() -> Result<()> {
 			} else {
 				Some((cli.run.grandpa_pause[0], cli.run.grandpa_pause[1]))
 			};
+			// only the new service runs the overseer, and only its real subsystems validate candidates
+			// in a pool of workers.
+			if cli.run.real_overseer && !cfg!(feature = "service-rewr") {
+				return Err(sc_cli::Error::Input("--real-overseer requires the `service-rewr` feature".into()));
+			}
+			if cli.run.validation_workers.is_some() && !cli.run.real_overseer {
+				return Err(sc_cli::Error::Input("--validation-workers requires --real-overseer".into()));
+			}
+			#[cfg(feature = "service-rewr")]
+			let real_overseer = cli.run.real_overseer;
+			#[cfg(feature = "service-rewr")]
+			let validation_workers = cli.run.validation_workers;
 
 			if chain_spec.is_kusama() {
 				info!("----------------------------");
@@ -142,11 +154,21 @@ pub fn run() -> Result<()> {
 
 				match role {
 					Role::Light => service::build_light(config).map(|(task_manager, _)| task_manager),
+					#[cfg(not(feature = "service-rewr"))]
+					_ => service::build_full(
+						config,
+						None,
+						authority_discovery_enabled,
+						grandpa_pause,
+					).map(|r| r.0),
+					#[cfg(feature = "service-rewr")]
 					_ => service::build_full(
 						config,
 						None,
 						authority_discovery_enabled,
 						grandpa_pause,
+						real_overseer,
+						validation_workers,
 					).map(|r| r.0),
 				}
 			})
@@ -253,8 +275,8 @@ pub fn run() -> Result<()> {
 			if cfg!(feature = "browser") {
 				Err(sc_cli::Error::Input("Cannot run validation worker in browser".into()))
 			} else {
-				#[cfg(all(not(feature = "browser"), not(feature = "service-rewr")))]
-				service::run_validation_worker(&cmd.mem_id)?;
+				#[cfg(not(feature = "browser"))]
+				service::run_validation_worker(&cmd.socket_fd)?;
 				Ok(())
 			}
 		},
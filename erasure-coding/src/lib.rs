@@ -124,6 +124,13 @@ fn code_params(n_validators: usize) -> Result<CodeParams, Error> {
 	})
 }
 
+/// Get the minimum number of chunks needed to reconstruct the data for the given number of
+/// validators.
+pub fn recovery_threshold(n_validators: usize) -> Result<usize, Error> {
+	let params = code_params(n_validators)?;
+	Ok(params.data_shards)
+}
+
 /// Obtain erasure-coded chunks for v0 `AvailableData`, one for each validator.
 ///
 /// Works only up to 65536 validators, and `n_validators` must be non-zero.
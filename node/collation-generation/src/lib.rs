@@ -211,9 +211,18 @@ async fn handle_new_activations<Context: SubsystemContext>(
 				CoreState::Scheduled(scheduled_core) => {
 					(scheduled_core, OccupiedCoreAssumption::Free)
 				}
-				CoreState::Occupied(_occupied_core) => {
-					// TODO: https://github.com/paritytech/polkadot/issues/1573
-					continue;
+				CoreState::Occupied(occupied_core) => {
+					// the core frees up as soon as the candidate pending availability on it is
+					// included or times out, so we build on the assumption under which our para is
+					// next up on the core. Inclusion is the more likely of the two.
+					let next_up_on_available = occupied_core.next_up_on_available
+						.filter(|core| core.para_id == config.para_id);
+
+					match (next_up_on_available, occupied_core.next_up_on_time_out) {
+						(Some(next_up), _) => (next_up, OccupiedCoreAssumption::Included),
+						(None, Some(next_up)) => (next_up, OccupiedCoreAssumption::TimedOut),
+						(None, None) => continue,
+					}
 				}
 				_ => continue,
 			};
@@ -273,6 +282,9 @@ async fn handle_new_activations<Context: SubsystemContext>(
 					new_validation_code: collation.new_validation_code,
 					head_data: collation.head_data,
 					erasure_root,
+					processed_downward_messages: collation.processed_downward_messages,
+					horizontal_messages: collation.horizontal_messages,
+					hrmp_watermark: collation.hrmp_watermark,
 				};
 
 				let ccr = CandidateReceipt {
@@ -284,6 +296,7 @@ async fn handle_new_activations<Context: SubsystemContext>(
 						collator: task_config.key.public(),
 						persisted_validation_data_hash,
 						pov_hash,
+						erasure_root,
 					},
 				};
 
@@ -364,7 +377,7 @@ mod tests {
 			subsystem_test_harness, TestSubsystemContextHandle,
 		};
 		use polkadot_primitives::v1::{
-			BlockData, BlockNumber, CollatorPair, Id as ParaId,
+			BlockData, BlockNumber, CollatorPair, Id as ParaId, OccupiedCore,
 			PersistedValidationData, PoV, ScheduledCore, ValidationData,
 		};
 		use std::pin::Pin;
@@ -378,6 +391,9 @@ mod tests {
 				proof_of_validity: PoV {
 					block_data: BlockData(Vec::new()),
 				},
+				processed_downward_messages: Default::default(),
+				horizontal_messages: Default::default(),
+				hrmp_watermark: Default::default(),
 			}
 		}
 
@@ -411,6 +427,21 @@ mod tests {
 			}
 		}
 
+		fn occupied_core_for(
+			next_up_on_available: Option<ScheduledCore>,
+			next_up_on_time_out: Option<ScheduledCore>,
+		) -> CoreState {
+			CoreState::Occupied(OccupiedCore {
+				para_id: ParaId::from(1),
+				next_up_on_available,
+				occupied_since: 1,
+				time_out_at: 10,
+				next_up_on_time_out,
+				availability: Default::default(),
+				group_responsible: Default::default(),
+			})
+		}
+
 		#[test]
 		fn requests_availability_per_relay_parent() {
 			let activated_hashes: Vec<Hash> = vec![
@@ -540,6 +571,80 @@ mod tests {
 			assert_eq!(requested_full_validation_data, vec![[4; 32].into()]);
 		}
 
+		#[test]
+		fn requests_validation_data_for_occupied_cores_next_up() {
+			let activated_hashes: Vec<Hash> = vec![Hash::repeat_byte(1)];
+
+			let requested_full_validation_data = Arc::new(Mutex::new(Vec::new()));
+
+			let overseer_requested_full_validation_data = requested_full_validation_data.clone();
+			let overseer = |mut handle: TestSubsystemContextHandle<CollationGenerationMessage>| async move {
+				loop {
+					match handle.try_recv().await {
+						None => break,
+						Some(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+							_hash,
+							RuntimeApiRequest::AvailabilityCores(tx),
+						))) => {
+							tx.send(Ok(vec![
+								// next up on availability.
+								occupied_core_for(Some(scheduled_core_for(16)), Some(scheduled_core_for(16))),
+								// next up on time-out only.
+								occupied_core_for(Some(scheduled_core_for(17)), Some(scheduled_core_for(16))),
+								// not next up at all.
+								occupied_core_for(Some(scheduled_core_for(17)), None),
+								occupied_core_for(None, Some(scheduled_core_for(17))),
+							]))
+							.unwrap();
+						}
+						Some(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+							_hash,
+							RuntimeApiRequest::FullValidationData(
+								para_id,
+								occupied_core_assumption,
+								tx,
+							),
+						))) => {
+							overseer_requested_full_validation_data
+								.lock()
+								.await
+								.push((para_id, occupied_core_assumption));
+							tx.send(Ok(Default::default())).unwrap();
+						}
+						Some(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+							_hash,
+							RuntimeApiRequest::Validators(tx),
+						))) => {
+							tx.send(Ok(vec![Default::default(); 3])).unwrap();
+						}
+						Some(msg) => {
+							panic!("didn't expect any other overseer requests; got {:?}", msg)
+						}
+					}
+				}
+			};
+
+			let (tx, _rx) = mpsc::channel(0);
+
+			subsystem_test_harness(overseer, |mut ctx| async move {
+				handle_new_activations(test_config(16), &activated_hashes, &mut ctx, Metrics(None), &tx)
+					.await
+					.unwrap();
+			});
+
+			let requested_full_validation_data = Arc::try_unwrap(requested_full_validation_data)
+				.expect("overseer should have shut down by now")
+				.into_inner();
+
+			assert_eq!(
+				requested_full_validation_data,
+				vec![
+					(ParaId::from(16), OccupiedCoreAssumption::Included),
+					(ParaId::from(16), OccupiedCoreAssumption::TimedOut),
+				],
+			);
+		}
+
 		#[test]
 		fn sends_distribute_collation_message() {
 			let activated_hashes: Vec<Hash> = vec![
@@ -622,6 +727,11 @@ mod tests {
 			let expect_validation_data_hash
 				= PersistedValidationData::<BlockNumber>::default().hash();
 			let expect_relay_parent = Hash::repeat_byte(4);
+			let expect_erasure_root = erasure_root(
+				3,
+				Default::default(),
+				test_collation().proof_of_validity,
+			).unwrap();
 			let expect_payload = collator_signature_payload(
 				&expect_relay_parent,
 				&config.para_id,
@@ -635,6 +745,7 @@ mod tests {
 				collator: config.key.public(),
 				persisted_validation_data_hash: expect_validation_data_hash,
 				pov_hash: expect_pov_hash,
+				erasure_root: expect_erasure_root,
 			};
 
 			assert_eq!(sent_messages.len(), 1);
@@ -0,0 +1,250 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Utilities for checking whether a candidate has been approved under a given block.
+
+use std::collections::{BTreeMap, HashSet};
+
+use polkadot_node_primitives::approval::DelayTranche;
+use polkadot_primitives::v1::ValidatorIndex;
+
+use crate::time::Tick;
+
+/// The assignments and approvals of the validators checking a candidate under a particular
+/// block.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ApprovalEntry {
+	/// The delay tranche of every assigned validator along with the tick at which we
+	/// received the assignment.
+	assignments: BTreeMap<ValidatorIndex, (DelayTranche, Tick)>,
+	approvals: HashSet<ValidatorIndex>,
+}
+
+impl ApprovalEntry {
+	/// Import an assignment of the given validator. Returns `false` if the validator was
+	/// already assigned.
+	pub(crate) fn import_assignment(
+		&mut self,
+		validator: ValidatorIndex,
+		tranche: DelayTranche,
+		tick_now: Tick,
+	) -> bool {
+		if self.assignments.contains_key(&validator) {
+			return false;
+		}
+
+		self.assignments.insert(validator, (tranche, tick_now));
+		true
+	}
+
+	/// Whether the given validator is assigned to check the candidate.
+	pub(crate) fn is_assigned(&self, validator: ValidatorIndex) -> bool {
+		self.assignments.contains_key(&validator)
+	}
+
+	/// Import an approval of the given validator. Returns `false` if the validator had already
+	/// approved.
+	pub(crate) fn import_approval(&mut self, validator: ValidatorIndex) -> bool {
+		self.approvals.insert(validator)
+	}
+
+	fn is_no_show(&self, validator: &ValidatorIndex, assigned_at: Tick, now: Tick, no_show_duration: Tick) -> bool {
+		!self.approvals.contains(validator) && assigned_at + no_show_duration <= now
+	}
+}
+
+/// The tranches of assignments required for approving a candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RequiredTranches {
+	/// There aren't enough assignments yet or there are uncovered no-shows; every tranche up
+	/// to and including the current one is required and more may follow.
+	Pending,
+	/// The assignments of all tranches up to and including `needed` suffice for approval.
+	Exact {
+		/// The last tranche whose assignments are required.
+		needed: DelayTranche,
+	},
+}
+
+/// Determine the tranches of assignments which are required for approving a candidate, as of
+/// tranche `tranche_now` and tick `now`.
+///
+/// Tranches are taken until there are at least `needed_approvals` assignments. Validators who
+/// didn't approve within `no_show_duration` of us receiving their assignment are no-shows, and
+/// every no-show needs to be covered by taking one more non-empty tranche.
+pub(crate) fn tranches_to_approve(
+	entry: &ApprovalEntry,
+	tranche_now: DelayTranche,
+	now: Tick,
+	no_show_duration: Tick,
+	needed_approvals: usize,
+) -> RequiredTranches {
+	// the number of assignments and no-shows per tranche.
+	let mut tranches: BTreeMap<DelayTranche, (usize, usize)> = BTreeMap::new();
+	for (validator, &(tranche, assigned_at)) in &entry.assignments {
+		if tranche > tranche_now {
+			continue;
+		}
+
+		let counts = tranches.entry(tranche).or_default();
+		counts.0 += 1;
+		if entry.is_no_show(validator, assigned_at, now, no_show_duration) {
+			counts.1 += 1;
+		}
+	}
+
+	let mut assigned = 0;
+	let mut uncovered_no_shows = 0;
+	let mut last_taken = None;
+
+	for tranche in 0..=tranche_now {
+		if assigned >= needed_approvals && uncovered_no_shows == 0 {
+			break;
+		}
+
+		let (n_assignments, n_no_shows) = tranches.get(&tranche).cloned().unwrap_or_default();
+
+		// every non-empty tranche beyond the ones we need covers one no-show.
+		if assigned >= needed_approvals && n_assignments > 0 {
+			uncovered_no_shows -= 1;
+		}
+
+		assigned += n_assignments;
+		uncovered_no_shows += n_no_shows;
+		last_taken = Some(tranche);
+	}
+
+	if assigned >= needed_approvals && uncovered_no_shows == 0 {
+		RequiredTranches::Exact { needed: last_taken.unwrap_or(0) }
+	} else {
+		RequiredTranches::Pending
+	}
+}
+
+/// Check whether a candidate is approved, i.e. all validators assigned in the required tranches
+/// approved it, except for the no-shows which have been covered.
+pub(crate) fn check_approval(
+	entry: &ApprovalEntry,
+	required: &RequiredTranches,
+	now: Tick,
+	no_show_duration: Tick,
+) -> bool {
+	let needed = match *required {
+		RequiredTranches::Pending => return false,
+		RequiredTranches::Exact { needed } => needed,
+	};
+
+	entry.assignments.iter()
+		.filter(|(_, &(tranche, _))| tranche <= needed)
+		.all(|(validator, &(_, assigned_at))| {
+			entry.approvals.contains(validator)
+				|| entry.is_no_show(validator, assigned_at, now, no_show_duration)
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const NO_SHOW_DURATION: Tick = 24;
+
+	fn entry(assignments: &[(ValidatorIndex, DelayTranche, Tick)]) -> ApprovalEntry {
+		let mut entry = ApprovalEntry::default();
+		for &(validator, tranche, tick) in assignments {
+			assert!(entry.import_assignment(validator, tranche, tick));
+		}
+		entry
+	}
+
+	#[test]
+	fn duplicate_assignments_and_approvals_are_detected() {
+		let mut entry = entry(&[(0, 0, 0)]);
+
+		assert!(!entry.import_assignment(0, 1, 5));
+		assert!(entry.is_assigned(0));
+		assert!(!entry.is_assigned(1));
+
+		assert!(entry.import_approval(0));
+		assert!(!entry.import_approval(0));
+	}
+
+	#[test]
+	fn pending_until_enough_assignments() {
+		let entry = entry(&[(0, 0, 0), (1, 2, 2)]);
+
+		assert_eq!(tranches_to_approve(&entry, 1, 1, NO_SHOW_DURATION, 2), RequiredTranches::Pending);
+		assert_eq!(
+			tranches_to_approve(&entry, 5, 5, NO_SHOW_DURATION, 2),
+			RequiredTranches::Exact { needed: 2 },
+		);
+	}
+
+	#[test]
+	fn assignments_from_future_tranches_are_ignored() {
+		let entry = entry(&[(0, 0, 0), (1, 3, 0)]);
+
+		assert_eq!(tranches_to_approve(&entry, 2, 2, NO_SHOW_DURATION, 2), RequiredTranches::Pending);
+	}
+
+	#[test]
+	fn no_shows_are_covered_by_an_extra_tranche_each() {
+		let mut entry = entry(&[(0, 0, 0), (1, 0, 0), (2, 1, 20), (3, 1, 20), (4, 3, 20)]);
+		entry.import_approval(1);
+
+		// before validator 0 becomes a no-show, tranche zero suffices.
+		let required = tranches_to_approve(&entry, 10, 10, NO_SHOW_DURATION, 2);
+		assert_eq!(required, RequiredTranches::Exact { needed: 0 });
+		assert!(!check_approval(&entry, &required, 10, NO_SHOW_DURATION));
+
+		// validator 0 is a no-show, so the next non-empty tranche is needed.
+		let required = tranches_to_approve(&entry, 30, 30, NO_SHOW_DURATION, 2);
+		assert_eq!(required, RequiredTranches::Exact { needed: 1 });
+		assert!(!check_approval(&entry, &required, 30, NO_SHOW_DURATION));
+
+		entry.import_approval(2);
+		entry.import_approval(3);
+		assert!(check_approval(&entry, &required, 30, NO_SHOW_DURATION));
+
+		// a late approval of the no-show reduces the required tranches again.
+		entry.import_approval(0);
+		let required = tranches_to_approve(&entry, 30, 30, NO_SHOW_DURATION, 2);
+		assert_eq!(required, RequiredTranches::Exact { needed: 0 });
+		assert!(check_approval(&entry, &required, 30, NO_SHOW_DURATION));
+	}
+
+	#[test]
+	fn no_shows_of_covering_tranches_need_to_be_covered_as_well() {
+		let mut entry = entry(&[(0, 0, 0), (1, 0, 0), (2, 1, 1), (3, 4, 30)]);
+		entry.import_approval(1);
+
+		// both 0 and the covering 2 are no-shows, so tranche 4 is needed.
+		let required = tranches_to_approve(&entry, 40, 40, NO_SHOW_DURATION, 2);
+		assert_eq!(required, RequiredTranches::Exact { needed: 4 });
+		assert!(!check_approval(&entry, &required, 40, NO_SHOW_DURATION));
+
+		entry.import_approval(3);
+		assert!(check_approval(&entry, &required, 40, NO_SHOW_DURATION));
+
+		// without any further tranche the candidate can't be approved.
+		let entry = {
+			let mut entry = self::entry(&[(0, 0, 0), (1, 0, 0)]);
+			entry.import_approval(1);
+			entry
+		};
+		assert_eq!(tranches_to_approve(&entry, 40, 40, NO_SHOW_DURATION, 2), RequiredTranches::Pending);
+		assert!(!check_approval(&entry, &RequiredTranches::Pending, 40, NO_SHOW_DURATION));
+	}
+}
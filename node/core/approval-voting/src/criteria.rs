@@ -0,0 +1,380 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Assignment criteria VRF generation and checking.
+
+use std::collections::btree_map::{BTreeMap, Entry};
+
+use keystore::KeyStorePtr;
+use merlin::Transcript;
+use parity_scale_codec::Encode;
+use schnorrkel::vrf::VRFInOut;
+use sp_core::crypto::IsWrappedBy;
+
+use polkadot_node_primitives::approval::{
+	AssignmentCert, AssignmentCertKind, DelayTranche, RelayVRFStory, VRFOutput, VRFProof,
+	ASSIGNED_CORE_CONTEXT, RELAY_VRF_DELAY_CONTEXT, RELAY_VRF_MODULO_CONTEXT,
+	TRANCHE_RANDOMNESS_CONTEXT,
+};
+use polkadot_primitives::v1::{CoreIndex, ValidatorId, ValidatorIndex, ValidatorPair};
+
+/// The parameters of the assignment criteria.
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+	/// The number of availability cores at the block which declared the candidates available.
+	pub(crate) n_cores: u32,
+	/// The number of `RelayVRFModulo` samples each validator takes.
+	pub(crate) relay_vrf_modulo_samples: u32,
+	/// The number of delay tranches in total.
+	pub(crate) n_delay_tranches: u32,
+	/// The width of the zeroth delay tranche of `RelayVRFDelay` assignments.
+	pub(crate) zeroth_delay_tranche_width: u32,
+}
+
+/// An assignment of the local validator to check a candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OurAssignment {
+	pub(crate) cert: AssignmentCert,
+	pub(crate) tranche: DelayTranche,
+	pub(crate) validator_index: ValidatorIndex,
+	/// Whether the assignment has been triggered, i.e. announced and acted upon.
+	pub(crate) triggered: bool,
+}
+
+/// The assignment cert was invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum InvalidAssignment {
+	/// The validator key is not a valid sr25519 public key.
+	BadValidatorKey,
+	/// The sample number exceeds the number of samples taken by every validator.
+	SampleOutOfBounds,
+	/// The VRF proof doesn't match the story.
+	BadProof,
+	/// The cert doesn't assign the validator to the core of the candidate.
+	WrongCore,
+}
+
+fn relay_vrf_modulo_transcript(relay_vrf_story: &RelayVRFStory, sample: u32) -> Transcript {
+	// combine the relay VRF story with a sample number.
+	let mut t = Transcript::new(RELAY_VRF_MODULO_CONTEXT);
+	t.append_message(b"RC-VRF", &relay_vrf_story.0);
+	sample.using_encoded(|s| t.append_message(b"sample", s));
+
+	t
+}
+
+fn relay_vrf_modulo_core(vrf_in_out: &VRFInOut, n_cores: u32) -> CoreIndex {
+	let bytes: [u8; 4] = vrf_in_out.make_bytes(ASSIGNED_CORE_CONTEXT);
+
+	// interpret as little-endian u32.
+	let random_core = u32::from_le_bytes(bytes) % n_cores;
+	CoreIndex(random_core)
+}
+
+fn relay_vrf_delay_transcript(relay_vrf_story: &RelayVRFStory, core_index: CoreIndex) -> Transcript {
+	let mut t = Transcript::new(RELAY_VRF_DELAY_CONTEXT);
+	t.append_message(b"RC-VRF", &relay_vrf_story.0);
+	core_index.0.using_encoded(|s| t.append_message(b"core", s));
+	t
+}
+
+fn relay_vrf_delay_tranche(
+	vrf_in_out: &VRFInOut,
+	n_delay_tranches: u32,
+	zeroth_delay_tranche_width: u32,
+) -> DelayTranche {
+	let bytes: [u8; 4] = vrf_in_out.make_bytes(TRANCHE_RANDOMNESS_CONTEXT);
+
+	// interpret as little-endian u32 and reduce by the number of tranches.
+	let wide_tranche = u32::from_le_bytes(bytes) % (n_delay_tranches + zeroth_delay_tranche_width);
+
+	// Consolidate early results to tranche zero so tranche zero is extra wide.
+	wide_tranche.saturating_sub(zeroth_delay_tranche_width)
+}
+
+/// Compute the assignments of the local validator to the candidates leaving the given cores,
+/// if the local validator is among `validators`.
+///
+/// Every core gets at most one assignment, the one of the lowest tranche.
+pub(crate) fn compute_assignments(
+	keystore: &KeyStorePtr,
+	relay_vrf_story: &RelayVRFStory,
+	config: &Config,
+	validators: &[ValidatorId],
+	leaving_cores: &[CoreIndex],
+) -> BTreeMap<CoreIndex, OurAssignment> {
+	let (validator_index, pair) = {
+		let keystore = keystore.read();
+		let found = validators.iter().enumerate().find_map(|(i, v)| {
+			keystore.key_pair::<ValidatorPair>(v).ok().map(|pair| (i as ValidatorIndex, pair))
+		});
+
+		match found {
+			Some(found) => found,
+			None => return BTreeMap::new(),
+		}
+	};
+
+	let keypair: &schnorrkel::Keypair = sp_core::sr25519::Pair::from_ref(&pair).as_ref();
+	let mut assignments = BTreeMap::new();
+
+	compute_relay_vrf_modulo_assignments(
+		keypair,
+		validator_index,
+		config,
+		relay_vrf_story,
+		leaving_cores,
+		&mut assignments,
+	);
+
+	compute_relay_vrf_delay_assignments(
+		keypair,
+		validator_index,
+		config,
+		relay_vrf_story,
+		leaving_cores,
+		&mut assignments,
+	);
+
+	assignments
+}
+
+fn compute_relay_vrf_modulo_assignments(
+	keypair: &schnorrkel::Keypair,
+	validator_index: ValidatorIndex,
+	config: &Config,
+	relay_vrf_story: &RelayVRFStory,
+	leaving_cores: &[CoreIndex],
+	assignments: &mut BTreeMap<CoreIndex, OurAssignment>,
+) {
+	if config.n_cores == 0 {
+		return;
+	}
+
+	for sample in 0..config.relay_vrf_modulo_samples {
+		let (vrf_in_out, proof, _) = keypair.vrf_sign(
+			relay_vrf_modulo_transcript(relay_vrf_story, sample),
+		);
+
+		// samples landing on cores without a leaving candidate are dropped.
+		let core = relay_vrf_modulo_core(&vrf_in_out, config.n_cores);
+		if !leaving_cores.contains(&core) || assignments.contains_key(&core) {
+			continue;
+		}
+
+		assignments.insert(core, OurAssignment {
+			cert: AssignmentCert {
+				kind: AssignmentCertKind::RelayVRFModulo { sample },
+				vrf: (VRFOutput(vrf_in_out.to_output()), VRFProof(proof)),
+			},
+			tranche: 0,
+			validator_index,
+			triggered: false,
+		});
+	}
+}
+
+fn compute_relay_vrf_delay_assignments(
+	keypair: &schnorrkel::Keypair,
+	validator_index: ValidatorIndex,
+	config: &Config,
+	relay_vrf_story: &RelayVRFStory,
+	leaving_cores: &[CoreIndex],
+	assignments: &mut BTreeMap<CoreIndex, OurAssignment>,
+) {
+	for &core in leaving_cores {
+		let (vrf_in_out, proof, _) = keypair.vrf_sign(
+			relay_vrf_delay_transcript(relay_vrf_story, core),
+		);
+
+		let tranche = relay_vrf_delay_tranche(
+			&vrf_in_out,
+			config.n_delay_tranches,
+			config.zeroth_delay_tranche_width,
+		);
+
+		let our_assignment = OurAssignment {
+			cert: AssignmentCert {
+				kind: AssignmentCertKind::RelayVRFDelay { core_index: core },
+				vrf: (VRFOutput(vrf_in_out.to_output()), VRFProof(proof)),
+			},
+			tranche,
+			validator_index,
+			triggered: false,
+		};
+
+		match assignments.entry(core) {
+			Entry::Vacant(entry) => {
+				entry.insert(our_assignment);
+			}
+			Entry::Occupied(mut entry) => if entry.get().tranche > tranche {
+				entry.insert(our_assignment);
+			},
+		}
+	}
+}
+
+/// Check the assignment cert of the given validator to the candidate leaving `claimed_core_index`,
+/// returning the delay tranche of the assignment.
+pub(crate) fn check_assignment_cert(
+	claimed_core_index: CoreIndex,
+	config: &Config,
+	relay_vrf_story: &RelayVRFStory,
+	assignment: &AssignmentCert,
+	validator_key: &ValidatorId,
+) -> Result<DelayTranche, InvalidAssignment> {
+	let key_bytes: &[u8] = validator_key.as_ref();
+	let public = schnorrkel::PublicKey::from_bytes(key_bytes)
+		.map_err(|_| InvalidAssignment::BadValidatorKey)?;
+
+	let (vrf_output, vrf_proof) = &assignment.vrf;
+	match assignment.kind {
+		AssignmentCertKind::RelayVRFModulo { sample } => {
+			if sample >= config.relay_vrf_modulo_samples {
+				return Err(InvalidAssignment::SampleOutOfBounds);
+			}
+
+			let (vrf_in_out, _) = public.vrf_verify(
+				relay_vrf_modulo_transcript(relay_vrf_story, sample),
+				&vrf_output.0,
+				&vrf_proof.0,
+			).map_err(|_| InvalidAssignment::BadProof)?;
+
+			if config.n_cores == 0
+				|| relay_vrf_modulo_core(&vrf_in_out, config.n_cores) != claimed_core_index
+			{
+				return Err(InvalidAssignment::WrongCore);
+			}
+
+			Ok(0)
+		}
+		AssignmentCertKind::RelayVRFDelay { core_index } => {
+			if core_index != claimed_core_index {
+				return Err(InvalidAssignment::WrongCore);
+			}
+
+			let (vrf_in_out, _) = public.vrf_verify(
+				relay_vrf_delay_transcript(relay_vrf_story, core_index),
+				&vrf_output.0,
+				&vrf_proof.0,
+			).map_err(|_| InvalidAssignment::BadProof)?;
+
+			Ok(relay_vrf_delay_tranche(
+				&vrf_in_out,
+				config.n_delay_tranches,
+				config.zeroth_delay_tranche_width,
+			))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_keyring::Sr25519Keyring;
+
+	fn config(n_cores: u32) -> Config {
+		Config {
+			n_cores,
+			relay_vrf_modulo_samples: 3,
+			n_delay_tranches: 40,
+			zeroth_delay_tranche_width: 1,
+		}
+	}
+
+	fn keystore_with(keyring: Sr25519Keyring) -> KeyStorePtr {
+		let keystore = keystore::Store::new_in_memory();
+		keystore.write().insert_ephemeral_from_seed::<ValidatorPair>(&keyring.to_seed())
+			.expect("Insert key into keystore");
+		keystore
+	}
+
+	fn validators() -> Vec<ValidatorId> {
+		vec![
+			Sr25519Keyring::Alice.public().into(),
+			Sr25519Keyring::Bob.public().into(),
+			Sr25519Keyring::Charlie.public().into(),
+		]
+	}
+
+	#[test]
+	fn no_assignments_without_validator_key() {
+		let keystore = keystore_with(Sr25519Keyring::Ferdie);
+		let story = RelayVRFStory([42; 32]);
+		let cores: Vec<_> = (0..10).map(CoreIndex).collect();
+
+		let assignments = compute_assignments(&keystore, &story, &config(10), &validators(), &cores);
+		assert!(assignments.is_empty());
+	}
+
+	#[test]
+	fn assignments_are_computed_for_leaving_cores_and_check_out() {
+		let keystore = keystore_with(Sr25519Keyring::Bob);
+		let story = RelayVRFStory([42; 32]);
+		let config = config(10);
+		let leaving_cores = vec![CoreIndex(1), CoreIndex(4), CoreIndex(7)];
+		let validators = validators();
+
+		let assignments = compute_assignments(&keystore, &story, &config, &validators, &leaving_cores);
+
+		// delay assignments cover all leaving cores.
+		assert_eq!(assignments.keys().cloned().collect::<Vec<_>>(), leaving_cores);
+
+		for (core, assignment) in assignments {
+			assert_eq!(assignment.validator_index, 1);
+			assert!(assignment.tranche < config.n_delay_tranches);
+			assert_eq!(
+				check_assignment_cert(core, &config, &story, &assignment.cert, &validators[1]),
+				Ok(assignment.tranche),
+			);
+
+			// the cert doesn't apply to other validators, cores or stories.
+			assert!(
+				check_assignment_cert(core, &config, &story, &assignment.cert, &validators[0]).is_err()
+			);
+			assert_eq!(
+				check_assignment_cert(CoreIndex(core.0 + 1), &config, &story, &assignment.cert, &validators[1]),
+				Err(InvalidAssignment::WrongCore),
+			);
+			assert!(
+				check_assignment_cert(core, &config, &RelayVRFStory([43; 32]), &assignment.cert, &validators[1])
+					.is_err()
+			);
+		}
+	}
+
+	#[test]
+	fn modulo_samples_are_bounded() {
+		let keystore = keystore_with(Sr25519Keyring::Alice);
+		let story = RelayVRFStory([7; 32]);
+		let config = config(1);
+		let validators = validators();
+
+		// with a single core all modulo samples land on it.
+		let assignments = compute_assignments(&keystore, &story, &config, &validators, &[CoreIndex(0)]);
+		let assignment = &assignments[&CoreIndex(0)];
+		assert_eq!(assignment.tranche, 0);
+		assert_eq!(assignment.cert.kind, AssignmentCertKind::RelayVRFModulo { sample: 0 });
+
+		let mut config = config;
+		config.relay_vrf_modulo_samples = 0;
+		assert_eq!(
+			check_assignment_cert(CoreIndex(0), &config, &story, &assignment.cert, &validators[0]),
+			Err(InvalidAssignment::SampleOutOfBounds),
+		);
+	}
+}
@@ -0,0 +1,969 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Approval Voting Subsystem.
+//!
+//! This subsystem is responsible for determining candidates to do approval checks
+//! on, performing those approval checks, and tracking the assignments and approvals
+//! of others. It uses this information to determine when candidates and blocks have
+//! been sufficiently approved.
+
+#![deny(missing_docs)]
+
+use std::collections::{HashMap, hash_map::Entry};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::channel::{mpsc, oneshot};
+use futures::prelude::*;
+use futures::select;
+
+use keystore::KeyStorePtr;
+use sp_consensus_babe::digests::{CompatibleDigestItem, PreDigest};
+
+use polkadot_node_primitives::ValidationResult;
+use polkadot_node_primitives::approval::{
+	ApprovalVote, CandidateIndex, IndirectAssignmentCert, IndirectSignedApprovalVote,
+	RelayVRFStory, RELAY_VRF_STORY_CONTEXT,
+};
+use polkadot_primitives::v1::{
+	BlockNumber, CandidateCommitments, CandidateEvent, CandidateReceipt, CoreIndex,
+	CoreState, Hash, Header, OccupiedCoreAssumption, SessionIndex, Signed, ValidatorId,
+	ValidatorPair,
+};
+use polkadot_subsystem::{
+	FromOverseer, OverseerSignal, SpawnedSubsystem, Subsystem, SubsystemContext, SubsystemError,
+	SubsystemResult, ActiveLeavesUpdate,
+	errors::{ChainApiError, RecoveryError, RuntimeApiError},
+	messages::{
+		AllMessages, ApprovalCheckResult, ApprovalDistributionMessage, ApprovalVotingMessage,
+		AssignmentCheckResult, AvailabilityRecoveryMessage, CandidateValidationMessage, ChainApiMessage,
+		RuntimeApiMessage, RuntimeApiRequest, RuntimeApiSender,
+	},
+	metrics::{self, prometheus},
+};
+
+use approval_checking::{ApprovalEntry, RequiredTranches};
+use criteria::OurAssignment;
+use time::{Clock, SystemClock, Tick};
+
+mod approval_checking;
+mod criteria;
+mod time;
+
+#[cfg(test)]
+mod tests;
+
+const LOG_TARGET: &str = "approval_voting";
+
+/// Assignments whose tranche starts this many ticks after the current one are rejected.
+const TICK_TOO_FAR_IN_FUTURE: Tick = 20; // 10 seconds.
+
+const BACKGROUND_CHANNEL_CAPACITY: usize = 64;
+
+/// Configuration for the approval voting subsystem.
+#[derive(Debug, Clone)]
+pub struct Config {
+	/// The duration of a relay-chain slot in milliseconds.
+	pub slot_duration_millis: u64,
+	/// The number of validators which need to approve a candidate.
+	pub needed_approvals: u32,
+	/// The number of `RelayVRFModulo` samples each validator takes.
+	pub relay_vrf_modulo_samples: u32,
+	/// The number of delay tranches in total.
+	pub n_delay_tranches: u32,
+	/// The width of the zeroth delay tranche of `RelayVRFDelay` assignments.
+	pub zeroth_delay_tranche_width: u32,
+	/// The number of slots after receiving their assignment that validators are given to
+	/// approve a candidate before they are considered no-shows.
+	pub no_show_slots: u32,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			slot_duration_millis: 6_000,
+			needed_approvals: 10,
+			relay_vrf_modulo_samples: 3,
+			n_delay_tranches: 40,
+			zeroth_delay_tranche_width: 1,
+			no_show_slots: 2,
+		}
+	}
+}
+
+#[derive(Debug, derive_more::From)]
+enum Error {
+	#[from]
+	ChainApi(ChainApiError),
+	#[from]
+	Erasure(erasure::Error),
+	#[from]
+	Oneshot(oneshot::Canceled),
+	#[from]
+	RuntimeApi(RuntimeApiError),
+	#[from]
+	Send(mpsc::SendError),
+	#[from]
+	Subsystem(SubsystemError),
+}
+
+/// The approval voting subsystem.
+pub struct ApprovalVotingSubsystem {
+	keystore: KeyStorePtr,
+	config: Config,
+	metrics: Metrics,
+}
+
+impl ApprovalVotingSubsystem {
+	/// Create a new approval voting subsystem with the given keystore and configuration.
+	pub fn new(keystore: KeyStorePtr, config: Config, metrics: Metrics) -> Self {
+		ApprovalVotingSubsystem {
+			keystore,
+			config,
+			metrics,
+		}
+	}
+}
+
+impl<C> Subsystem<C> for ApprovalVotingSubsystem
+	where C: SubsystemContext<Message = ApprovalVotingMessage>
+{
+	type Metrics = Metrics;
+
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		let future = Box::pin(async move {
+			if let Err(e) = run(ctx, self, Box::new(SystemClock)).await {
+				log::error!(target: LOG_TARGET, "Subsystem exited with an error {:?}", e);
+			}
+		});
+
+		SpawnedSubsystem {
+			name: "approval-voting-subsystem",
+			future,
+		}
+	}
+}
+
+/// Messages sent to the main loop by the tasks running approval checks.
+enum BackgroundMessage {
+	/// A message to be forwarded to the overseer.
+	Message(AllMessages),
+	/// A candidate was checked, `valid` tells whether it can be approved.
+	CandidateChecked {
+		candidate_hash: Hash,
+		valid: bool,
+	},
+}
+
+/// The status of our own check of a candidate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CheckStatus {
+	InProgress,
+	Valid,
+	Failed,
+}
+
+/// A candidate included by a relay-chain block.
+struct CandidateEntry {
+	receipt: CandidateReceipt,
+	hash: Hash,
+	/// The core the candidate was leaving when it got included.
+	core: CoreIndex,
+	approval_entry: ApprovalEntry,
+	our_assignment: Option<OurAssignment>,
+	approved: bool,
+}
+
+/// A relay-chain block including candidates that need to be approved.
+struct BlockEntry {
+	number: BlockNumber,
+	session: SessionIndex,
+	/// The first tick of the slot the block was authored in.
+	tick: Tick,
+	relay_vrf_story: RelayVRFStory,
+	n_cores: u32,
+	validators: Vec<ValidatorId>,
+	candidates: Vec<CandidateEntry>,
+	approved: bool,
+}
+
+/// A check to run on a candidate.
+struct ApprovalCheck {
+	receipt: CandidateReceipt,
+	session: SessionIndex,
+	n_validators: usize,
+}
+
+/// Work resulting from state updates, to be carried out by the main loop.
+enum Action {
+	/// Launch an approval check of a candidate.
+	LaunchApprovalCheck(ApprovalCheck),
+	/// Gossip one of our assignments.
+	DistributeAssignment(IndirectAssignmentCert, CandidateIndex),
+	/// Gossip one of our approval votes.
+	DistributeApproval(IndirectSignedApprovalVote),
+}
+
+struct State {
+	keystore: KeyStorePtr,
+	config: Config,
+	clock: Box<dyn Clock + Send + Sync>,
+	metrics: Metrics,
+	blocks: HashMap<Hash, BlockEntry>,
+	candidate_checks: HashMap<Hash, CheckStatus>,
+}
+
+impl State {
+	fn criteria_config(&self, n_cores: u32) -> criteria::Config {
+		criteria::Config {
+			n_cores,
+			relay_vrf_modulo_samples: self.config.relay_vrf_modulo_samples,
+			n_delay_tranches: self.config.n_delay_tranches,
+			zeroth_delay_tranche_width: self.config.zeroth_delay_tranche_width,
+		}
+	}
+
+	fn no_show_duration(&self) -> Tick {
+		time::slot_number_to_tick(self.config.slot_duration_millis, self.config.no_show_slots as u64)
+	}
+
+	fn check_and_import_assignment(
+		&mut self,
+		assignment: IndirectAssignmentCert,
+		candidate_index: CandidateIndex,
+	) -> AssignmentCheckResult {
+		let tick_now = self.clock.tick_now();
+		let criteria_config = match self.blocks.get(&assignment.block_hash) {
+			Some(block) => self.criteria_config(block.n_cores),
+			None => return AssignmentCheckResult::Bad,
+		};
+		let block = match self.blocks.get_mut(&assignment.block_hash) {
+			Some(block) => block,
+			None => return AssignmentCheckResult::Bad,
+		};
+
+		let validator_key = match block.validators.get(assignment.validator as usize) {
+			Some(key) => key,
+			None => return AssignmentCheckResult::Bad,
+		};
+
+		let candidate = match block.candidates.get_mut(candidate_index as usize) {
+			Some(candidate) => candidate,
+			None => return AssignmentCheckResult::Bad,
+		};
+
+		let tranche = match criteria::check_assignment_cert(
+			candidate.core,
+			&criteria_config,
+			&block.relay_vrf_story,
+			&assignment.cert,
+			validator_key,
+		) {
+			Ok(tranche) => tranche,
+			Err(e) => {
+				log::debug!(
+					target: LOG_TARGET,
+					"Assignment of validator {} to {:?} is invalid: {:?}",
+					assignment.validator,
+					candidate.hash,
+					e,
+				);
+				return AssignmentCheckResult::Bad;
+			}
+		};
+
+		if block.tick + tranche as Tick >= tick_now + TICK_TOO_FAR_IN_FUTURE {
+			return AssignmentCheckResult::TooFarInFuture;
+		}
+
+		if candidate.approval_entry.import_assignment(assignment.validator, tranche, tick_now) {
+			AssignmentCheckResult::Accepted
+		} else {
+			AssignmentCheckResult::AcceptedDuplicate
+		}
+	}
+
+	fn check_and_import_approval(&mut self, approval: IndirectSignedApprovalVote) -> ApprovalCheckResult {
+		let block = match self.blocks.get_mut(&approval.block_hash) {
+			Some(block) => block,
+			None => return ApprovalCheckResult::Bad,
+		};
+
+		let validator_key = match block.validators.get(approval.validator as usize) {
+			Some(key) => key,
+			None => return ApprovalCheckResult::Bad,
+		};
+
+		let candidate = match block.candidates.get_mut(approval.candidate_index as usize) {
+			Some(candidate) => candidate,
+			None => return ApprovalCheckResult::Bad,
+		};
+
+		// approvals are only accepted from assigned validators.
+		if !candidate.approval_entry.is_assigned(approval.validator) {
+			return ApprovalCheckResult::Bad;
+		}
+
+		let signing_context = ApprovalVote::signing_context(block.session, approval.block_hash);
+		let signed = Signed::<ApprovalVote>::new(
+			ApprovalVote(candidate.hash),
+			approval.validator,
+			approval.signature,
+			&signing_context,
+			validator_key,
+		);
+
+		if signed.is_none() {
+			return ApprovalCheckResult::Bad;
+		}
+
+		candidate.approval_entry.import_approval(approval.validator);
+		ApprovalCheckResult::Accepted
+	}
+
+	/// Update the approval status of all candidates and trigger our own assignments if they're
+	/// needed.
+	fn process_tick(&mut self) -> Vec<Action> {
+		let tick_now = self.clock.tick_now();
+		let no_show_duration = self.no_show_duration();
+		let needed_approvals = self.config.needed_approvals as usize;
+		let metrics = &self.metrics;
+
+		let mut actions = Vec::new();
+		let mut triggered = Vec::new();
+		for (block_hash, block) in self.blocks.iter_mut().filter(|(_, b)| !b.approved) {
+			let tranche_now = time::tranche_now(block.tick, tick_now);
+
+			// there can't be more approvals than validators.
+			let needed_approvals = std::cmp::min(needed_approvals, block.validators.len());
+
+			for (index, candidate) in block.candidates.iter_mut().enumerate() {
+				if candidate.approved {
+					continue;
+				}
+
+				let required = approval_checking::tranches_to_approve(
+					&candidate.approval_entry,
+					tranche_now,
+					tick_now,
+					no_show_duration,
+					needed_approvals,
+				);
+
+				if approval_checking::check_approval(
+					&candidate.approval_entry,
+					&required,
+					tick_now,
+					no_show_duration,
+				) {
+					log::debug!(target: LOG_TARGET, "Candidate {:?} approved", candidate.hash);
+					candidate.approved = true;
+					metrics.on_candidate_approved();
+					continue;
+				}
+
+				// our assignment is only announced if the candidate needs more checkers.
+				let our_assignment = match candidate.our_assignment {
+					Some(ref mut our_assignment) if !our_assignment.triggered => our_assignment,
+					_ => continue,
+				};
+
+				let should_trigger = our_assignment.tranche <= tranche_now && match required {
+					RequiredTranches::Pending => true,
+					RequiredTranches::Exact { needed } => our_assignment.tranche <= needed,
+				};
+
+				if should_trigger {
+					our_assignment.triggered = true;
+					candidate.approval_entry.import_assignment(
+						our_assignment.validator_index,
+						our_assignment.tranche,
+						tick_now,
+					);
+					metrics.on_assignment_produced();
+
+					let cert = IndirectAssignmentCert {
+						block_hash: *block_hash,
+						validator: our_assignment.validator_index,
+						cert: our_assignment.cert.clone(),
+					};
+					actions.push(Action::DistributeAssignment(cert, index as CandidateIndex));
+					triggered.push((*block_hash, index as CandidateIndex));
+				}
+			}
+
+			if block.candidates.iter().all(|c| c.approved) {
+				log::debug!(target: LOG_TARGET, "Block {:?} approved", block_hash);
+				block.approved = true;
+			}
+		}
+
+		for (block_hash, candidate_index) in triggered {
+			let (receipt, session, n_validators) = {
+				let block = &self.blocks[&block_hash];
+				let candidate = &block.candidates[candidate_index as usize];
+				(candidate.receipt.clone(), block.session, block.validators.len())
+			};
+
+			match self.candidate_checks.entry(receipt.hash()) {
+				Entry::Vacant(entry) => {
+					entry.insert(CheckStatus::InProgress);
+					actions.push(Action::LaunchApprovalCheck(ApprovalCheck { receipt, session, n_validators }));
+				}
+				Entry::Occupied(entry) => if *entry.get() == CheckStatus::Valid {
+					actions.extend(self.issue_approval(block_hash, candidate_index));
+				},
+			}
+		}
+
+		actions
+	}
+
+	/// Note the outcome of our check of a candidate, approving it under all blocks in which we
+	/// triggered our assignment to it.
+	fn note_candidate_checked(&mut self, candidate_hash: Hash, valid: bool) -> Vec<Action> {
+		let status = if valid { CheckStatus::Valid } else { CheckStatus::Failed };
+		self.candidate_checks.insert(candidate_hash, status);
+
+		if !valid {
+			return Vec::new();
+		}
+
+		let to_approve: Vec<_> = self.blocks.iter()
+			.flat_map(|(block_hash, block)| block.candidates.iter()
+				.enumerate()
+				.filter(move |(_, c)| c.hash == candidate_hash)
+				.filter(|(_, c)| c.our_assignment.as_ref().map_or(false, |a| a.triggered))
+				.map(move |(index, _)| (*block_hash, index as CandidateIndex))
+			)
+			.collect();
+
+		to_approve.into_iter()
+			.filter_map(|(block_hash, candidate_index)| self.issue_approval(block_hash, candidate_index))
+			.collect()
+	}
+
+	fn issue_approval(&mut self, block_hash: Hash, candidate_index: CandidateIndex) -> Option<Action> {
+		let vote = {
+			let block = &self.blocks[&block_hash];
+			let candidate = &block.candidates[candidate_index as usize];
+			let validator_index = match candidate.our_assignment {
+				Some(ref our_assignment) => our_assignment.validator_index,
+				None => return None,
+			};
+
+			let validator_id = &block.validators[validator_index as usize];
+			let key = match self.keystore.read().key_pair::<ValidatorPair>(validator_id) {
+				Ok(key) => key,
+				Err(e) => {
+					log::warn!(target: LOG_TARGET, "Validator key not found: {:?}", e);
+					return None;
+				}
+			};
+
+			let signing_context = ApprovalVote::signing_context(block.session, block_hash);
+			let signed = Signed::<ApprovalVote>::sign(
+				ApprovalVote(candidate.hash),
+				&signing_context,
+				validator_index,
+				&key,
+			);
+
+			IndirectSignedApprovalVote {
+				block_hash,
+				candidate_index,
+				validator: validator_index,
+				signature: signed.signature().clone(),
+			}
+		};
+
+		// our own votes are imported the same way as the ones of everybody else.
+		match self.check_and_import_approval(vote.clone()) {
+			ApprovalCheckResult::Accepted => {
+				self.metrics.on_approval_produced();
+				Some(Action::DistributeApproval(vote))
+			}
+			ApprovalCheckResult::Bad => {
+				log::warn!(
+					target: LOG_TARGET,
+					"Failed to import our own approval of candidate {} in {:?}",
+					candidate_index,
+					block_hash,
+				);
+				None
+			}
+		}
+	}
+
+	/// Forget about all blocks up to and including the finalized one along with our checks of
+	/// the candidates which aren't included by any of the remaining blocks.
+	fn note_block_finalized(&mut self, finalized_number: BlockNumber) {
+		self.blocks.retain(|_, block| block.number > finalized_number);
+
+		let blocks = &self.blocks;
+		self.candidate_checks.retain(|candidate_hash, _| blocks.values()
+			.any(|block| block.candidates.iter().any(|c| &c.hash == candidate_hash))
+		);
+	}
+}
+
+async fn run<Context>(
+	mut ctx: Context,
+	subsystem: ApprovalVotingSubsystem,
+	clock: Box<dyn Clock + Send + Sync>,
+) -> Result<(), Error>
+where
+	Context: SubsystemContext<Message = ApprovalVotingMessage>,
+{
+	let (background_tx, mut background_rx) = mpsc::channel(BACKGROUND_CHANNEL_CAPACITY);
+
+	let mut state = State {
+		keystore: subsystem.keystore,
+		config: subsystem.config,
+		clock,
+		metrics: subsystem.metrics,
+		blocks: HashMap::new(),
+		candidate_checks: HashMap::new(),
+	};
+
+	let mut wakeup = state.clock.wait(state.clock.tick_now() + 1).fuse();
+
+	loop {
+		select! {
+			incoming = ctx.recv().fuse() => {
+				match incoming? {
+					FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+					FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+						ActiveLeavesUpdate { activated, .. })
+					) => {
+						for head in activated {
+							if let Err(e) = handle_new_head(&mut ctx, &mut state, head).await {
+								log::warn!(
+									target: LOG_TARGET,
+									"Failed to import the candidates included in {:?}: {:?}",
+									head,
+									e,
+								);
+							}
+						}
+					}
+					FromOverseer::Signal(OverseerSignal::BlockFinalized(hash)) => {
+						let number = match state.blocks.get(&hash) {
+							Some(block) => Some(block.number),
+							None => request_block_number(&mut ctx, hash).await?,
+						};
+
+						if let Some(number) = number {
+							state.note_block_finalized(number);
+						}
+					}
+					FromOverseer::Communication { msg } => handle_message(&mut state, msg),
+				}
+			}
+			background = background_rx.next() => {
+				match background {
+					Some(BackgroundMessage::Message(msg)) => ctx.send_message(msg).await?,
+					Some(BackgroundMessage::CandidateChecked { candidate_hash, valid }) => {
+						let actions = state.note_candidate_checked(candidate_hash, valid);
+						handle_actions(&mut ctx, &background_tx, actions).await?;
+					}
+					None => return Ok(()),
+				}
+			}
+			_ = wakeup => {
+				let actions = state.process_tick();
+				handle_actions(&mut ctx, &background_tx, actions).await?;
+
+				wakeup = state.clock.wait(state.clock.tick_now() + 1).fuse();
+			}
+		}
+	}
+}
+
+async fn handle_actions<Context>(
+	ctx: &mut Context,
+	background_tx: &mpsc::Sender<BackgroundMessage>,
+	actions: Vec<Action>,
+) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message = ApprovalVotingMessage>,
+{
+	for action in actions {
+		match action {
+			Action::LaunchApprovalCheck(check) => {
+				launch_approval_check(ctx, background_tx, check).await?;
+			}
+			Action::DistributeAssignment(cert, candidate_index) => {
+				ctx.send_message(AllMessages::ApprovalDistribution(
+					ApprovalDistributionMessage::DistributeAssignment(cert, candidate_index),
+				)).await?;
+			}
+			Action::DistributeApproval(vote) => {
+				ctx.send_message(AllMessages::ApprovalDistribution(
+					ApprovalDistributionMessage::DistributeApproval(vote),
+				)).await?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn handle_message(state: &mut State, msg: ApprovalVotingMessage) {
+	match msg {
+		ApprovalVotingMessage::CheckAndImportAssignment(assignment, candidate_index, tx) => {
+			let _ = tx.send(state.check_and_import_assignment(assignment, candidate_index));
+		}
+		ApprovalVotingMessage::CheckAndImportApproval(approval, tx) => {
+			let _ = tx.send(state.check_and_import_approval(approval));
+		}
+	}
+}
+
+/// Extract the BABE slot number and the relay VRF story from the header of a block authored in
+/// a primary slot.
+///
+/// The story is the hash of the VRF output that authorized the block, so that nobody but the
+/// block author knew it in advance. Blocks authored in secondary slots carry no VRF output.
+fn babe_slot_and_story(header: &Header) -> Option<(u64, RelayVRFStory)> {
+	let pre_digest = header.digest.logs().iter().find_map(|log| log.as_babe_pre_digest())?;
+
+	match pre_digest {
+		PreDigest::Primary(primary) => {
+			let mut input = RELAY_VRF_STORY_CONTEXT.to_vec();
+			input.extend_from_slice(primary.vrf_output.as_bytes());
+
+			Some((primary.slot_number, RelayVRFStory(sp_core::blake2_256(&input))))
+		}
+		_ => None,
+	}
+}
+
+async fn handle_new_head<Context>(
+	ctx: &mut Context,
+	state: &mut State,
+	head: Hash,
+) -> Result<(), Error>
+where
+	Context: SubsystemContext<Message = ApprovalVotingMessage>,
+{
+	if state.blocks.contains_key(&head) {
+		return Ok(());
+	}
+
+	let header = match request_block_header(ctx, head).await? {
+		Some(header) => header,
+		None => return Ok(()),
+	};
+
+	let (slot, relay_vrf_story) = match babe_slot_and_story(&header) {
+		Some(slot_and_story) => slot_and_story,
+		None => {
+			log::debug!(target: LOG_TARGET, "No relay VRF story in {:?}, skipping", head);
+			return Ok(());
+		}
+	};
+
+	let included: Vec<CandidateReceipt> = request_runtime(ctx, head, RuntimeApiRequest::CandidateEvents)
+		.await?
+		.into_iter()
+		.filter_map(|event| match event {
+			CandidateEvent::CandidateIncluded(receipt, _, _) => Some(receipt),
+			_ => None,
+		})
+		.collect();
+
+	if included.is_empty() {
+		return Ok(());
+	}
+
+	// the candidates were declared available in the context of the parent.
+	let parent = header.parent_hash;
+	let session = request_runtime(ctx, parent, RuntimeApiRequest::SessionIndexForChild).await?;
+	let validators = request_runtime(ctx, parent, RuntimeApiRequest::Validators).await?;
+	let cores = request_runtime(ctx, parent, RuntimeApiRequest::AvailabilityCores).await?;
+
+	let candidates: Vec<CandidateEntry> = included.into_iter()
+		.filter_map(|receipt| {
+			let core = cores.iter().position(|core| match core {
+				CoreState::Occupied(occupied) => occupied.para_id == receipt.descriptor.para_id,
+				_ => false,
+			})?;
+
+			Some(CandidateEntry {
+				hash: receipt.hash(),
+				receipt,
+				core: CoreIndex(core as u32),
+				approval_entry: ApprovalEntry::default(),
+				our_assignment: None,
+				approved: false,
+			})
+		})
+		.collect();
+
+	let n_cores = cores.len() as u32;
+	let leaving_cores: Vec<_> = candidates.iter().map(|c| c.core).collect();
+	let mut our_assignments = criteria::compute_assignments(
+		&state.keystore,
+		&relay_vrf_story,
+		&state.criteria_config(n_cores),
+		&validators,
+		&leaving_cores,
+	);
+
+	let candidates = candidates.into_iter()
+		.map(|mut candidate| {
+			candidate.our_assignment = our_assignments.remove(&candidate.core);
+			candidate
+		})
+		.collect();
+
+	state.blocks.insert(head, BlockEntry {
+		number: header.number,
+		session,
+		tick: time::slot_number_to_tick(state.config.slot_duration_millis, slot),
+		relay_vrf_story,
+		n_cores,
+		validators,
+		candidates,
+		approved: false,
+	});
+
+	Ok(())
+}
+
+async fn launch_approval_check<Context>(
+	ctx: &mut Context,
+	background_tx: &mpsc::Sender<BackgroundMessage>,
+	check: ApprovalCheck,
+) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message = ApprovalVotingMessage>,
+{
+	let mut sender = background_tx.clone();
+	let candidate_hash = check.receipt.hash();
+
+	let future = async move {
+		let valid = match check_candidate(&mut sender, &check.receipt, check.session, check.n_validators).await {
+			Ok(valid) => valid,
+			Err(e) => {
+				log::warn!(target: LOG_TARGET, "Failed to check candidate {:?}: {:?}", candidate_hash, e);
+				false
+			}
+		};
+
+		let _ = sender.send(BackgroundMessage::CandidateChecked { candidate_hash, valid }).await;
+	};
+
+	ctx.spawn("approval-check", future.boxed()).await
+}
+
+/// Recover the available data of the candidate, re-validate it and check that the outputs
+/// match the commitments of the receipt.
+async fn check_candidate(
+	sender: &mut mpsc::Sender<BackgroundMessage>,
+	receipt: &CandidateReceipt,
+	session: SessionIndex,
+	n_validators: usize,
+) -> Result<bool, Error> {
+	let candidate_hash = receipt.hash();
+
+	let (tx, rx) = oneshot::channel();
+	sender.send(BackgroundMessage::Message(AllMessages::AvailabilityRecovery(
+		AvailabilityRecoveryMessage::RecoverAvailableData(receipt.clone(), session, tx),
+	))).await?;
+
+	let available_data = match rx.await? {
+		Ok(available_data) => available_data,
+		Err(RecoveryError::Unavailable) => {
+			log::debug!(target: LOG_TARGET, "Data of candidate {:?} is unavailable", candidate_hash);
+			return Ok(false);
+		}
+		Err(RecoveryError::Invalid) => {
+			log::warn!(target: LOG_TARGET, "Data of candidate {:?} is invalidly encoded", candidate_hash);
+			return Ok(false);
+		}
+	};
+
+	// the erasure root is part of the commitments, so the data is encoded again.
+	let chunks = erasure::obtain_chunks_v1(n_validators, &available_data)?;
+	let erasure_root = erasure::branches(&chunks).root();
+
+	let relay_parent = receipt.descriptor.relay_parent;
+
+	let (tx, rx) = oneshot::channel();
+	sender.send(BackgroundMessage::Message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		relay_parent,
+		RuntimeApiRequest::ValidationCode(
+			receipt.descriptor.para_id,
+			OccupiedCoreAssumption::Included,
+			tx,
+		),
+	)))).await?;
+
+	let validation_code = match rx.await?? {
+		Some(code) => code,
+		None => {
+			log::warn!(target: LOG_TARGET, "No validation code for candidate {:?}", candidate_hash);
+			return Ok(false);
+		}
+	};
+
+	// approval checks are allowed more time than backing, as configured on-chain.
+	let (tx, rx) = oneshot::channel();
+	sender.send(BackgroundMessage::Message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		relay_parent,
+		RuntimeApiRequest::ExecutionTimeouts(tx),
+	)))).await?;
+
+	let timeout = Duration::from_millis(rx.await??.approval as u64);
+
+	let (tx, rx) = oneshot::channel();
+	sender.send(BackgroundMessage::Message(AllMessages::CandidateValidation(
+		CandidateValidationMessage::ValidateFromExhaustive(
+			available_data.validation_data,
+			None,
+			validation_code,
+			receipt.descriptor.clone(),
+			Arc::new(available_data.pov),
+			Some(timeout),
+			tx,
+		),
+	))).await?;
+
+	match rx.await? {
+		Ok(ValidationResult::Valid(outputs)) => {
+			let commitments = CandidateCommitments {
+				fees: outputs.fees,
+				upward_messages: outputs.upward_messages,
+				erasure_root,
+				new_validation_code: outputs.new_validation_code,
+				head_data: outputs.head_data,
+				processed_downward_messages: outputs.processed_downward_messages,
+				horizontal_messages: outputs.horizontal_messages,
+				hrmp_watermark: outputs.hrmp_watermark,
+			};
+
+			Ok(commitments.hash() == receipt.commitments_hash)
+		}
+		Ok(ValidationResult::Invalid(reason)) => {
+			log::warn!(target: LOG_TARGET, "Candidate {:?} is invalid: {:?}", candidate_hash, reason);
+			Ok(false)
+		}
+		Err(e) => {
+			log::warn!(target: LOG_TARGET, "Failed to validate candidate {:?}: {:?}", candidate_hash, e);
+			Ok(false)
+		}
+	}
+}
+
+async fn request_block_header<Context>(ctx: &mut Context, hash: Hash) -> Result<Option<Header>, Error>
+where
+	Context: SubsystemContext<Message = ApprovalVotingMessage>,
+{
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::ChainApi(ChainApiMessage::BlockHeader(hash, tx))).await?;
+
+	Ok(rx.await??)
+}
+
+async fn request_block_number<Context>(ctx: &mut Context, hash: Hash) -> Result<Option<BlockNumber>, Error>
+where
+	Context: SubsystemContext<Message = ApprovalVotingMessage>,
+{
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::ChainApi(ChainApiMessage::BlockNumber(hash, tx))).await?;
+
+	Ok(rx.await??)
+}
+
+async fn request_runtime<Context, T>(
+	ctx: &mut Context,
+	relay_parent: Hash,
+	request: impl FnOnce(RuntimeApiSender<T>) -> RuntimeApiRequest,
+) -> Result<T, Error>
+where
+	Context: SubsystemContext<Message = ApprovalVotingMessage>,
+{
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		relay_parent,
+		request(tx),
+	))).await?;
+
+	Ok(rx.await??)
+}
+
+#[derive(Clone)]
+struct MetricsInner {
+	assignments_produced: prometheus::Counter<prometheus::U64>,
+	approvals_produced: prometheus::Counter<prometheus::U64>,
+	candidates_approved: prometheus::Counter<prometheus::U64>,
+}
+
+/// Approval Voting metrics.
+#[derive(Default, Clone)]
+pub struct Metrics(Option<MetricsInner>);
+
+impl Metrics {
+	fn on_assignment_produced(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.assignments_produced.inc();
+		}
+	}
+
+	fn on_approval_produced(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.approvals_produced.inc();
+		}
+	}
+
+	fn on_candidate_approved(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.candidates_approved.inc();
+		}
+	}
+}
+
+impl metrics::Metrics for Metrics {
+	fn try_register(registry: &prometheus::Registry) -> Result<Self, prometheus::PrometheusError> {
+		let metrics = MetricsInner {
+			assignments_produced: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_approval_assignments_produced_total",
+					"Number of assignments of ours which were triggered.",
+				)?,
+				registry,
+			)?,
+			approvals_produced: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_approvals_produced_total",
+					"Number of approval votes we issued.",
+				)?,
+				registry,
+			)?,
+			candidates_approved: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_approval_candidates_approved_total",
+					"Number of candidates which were approved under a relay-chain block.",
+				)?,
+				registry,
+			)?,
+		};
+		Ok(Metrics(Some(metrics)))
+	}
+}
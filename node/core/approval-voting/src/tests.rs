@@ -0,0 +1,597 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::*;
+
+use std::pin::Pin;
+
+use assert_matches::assert_matches;
+use futures::{executor, future};
+use merlin::Transcript;
+use parking_lot::Mutex;
+use sp_consensus_babe::digests::PrimaryPreDigest;
+use sp_keyring::Sr25519Keyring;
+use sp_runtime::generic::{Digest, DigestItem};
+use sp_runtime::traits::Header as HeaderT;
+
+use polkadot_node_primitives::ValidationOutputs;
+use polkadot_node_primitives::approval::{VRFOutput, VRFProof};
+use polkadot_node_subsystem_test_helpers as test_helpers;
+use polkadot_primitives::v1::{
+	AvailableData, BlockData, CandidateDescriptor, ExecutionTimeouts, HeadData, Id as ParaId, OccupiedCore,
+	PersistedValidationData, PoV, ValidationCode, ValidatorIndex,
+};
+
+const SLOT: u64 = 10;
+
+#[derive(Default)]
+struct MockClockInner {
+	tick: Tick,
+	wakeups: Vec<(Tick, oneshot::Sender<()>)>,
+}
+
+/// A clock which only advances when told so.
+#[derive(Clone, Default)]
+struct MockClock(Arc<Mutex<MockClockInner>>);
+
+impl MockClock {
+	fn new(tick: Tick) -> Self {
+		let clock = MockClock::default();
+		clock.0.lock().tick = tick;
+		clock
+	}
+
+	fn set_tick(&self, tick: Tick) {
+		let mut inner = self.0.lock();
+		inner.tick = tick;
+
+		let (ready, pending): (Vec<_>, Vec<_>) = inner.wakeups.drain(..).partition(|(t, _)| *t <= tick);
+		inner.wakeups = pending;
+
+		for (_, tx) in ready {
+			let _ = tx.send(());
+		}
+	}
+}
+
+impl Clock for MockClock {
+	fn tick_now(&self) -> Tick {
+		self.0.lock().tick
+	}
+
+	fn wait(&self, tick: Tick) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> {
+		let (tx, rx) = oneshot::channel();
+		self.0.lock().wakeups.push((tick, tx));
+
+		Box::pin(rx.map(|_| ()))
+	}
+}
+
+fn keystore_with(keyring: Option<Sr25519Keyring>) -> KeyStorePtr {
+	let keystore = keystore::Store::new_in_memory();
+	if let Some(keyring) = keyring {
+		keystore.write().insert_ephemeral_from_seed::<ValidatorPair>(&keyring.to_seed())
+			.expect("Insert key into keystore");
+	}
+	keystore
+}
+
+fn validators() -> Vec<ValidatorId> {
+	vec![
+		Sr25519Keyring::Alice.public().into(),
+		Sr25519Keyring::Bob.public().into(),
+		Sr25519Keyring::Charlie.public().into(),
+	]
+}
+
+fn block_tick() -> Tick {
+	time::slot_number_to_tick(Config::default().slot_duration_millis, SLOT)
+}
+
+/// A header authored in a primary slot by Alice.
+fn make_header(parent_hash: Hash, number: BlockNumber) -> Header {
+	let pair = Sr25519Keyring::Alice.pair();
+	let keypair: &schnorrkel::Keypair = pair.as_ref();
+	let (inout, proof, _) = keypair.vrf_sign(Transcript::new(b"test"));
+
+	let pre_digest = PreDigest::Primary(PrimaryPreDigest {
+		authority_index: 0,
+		slot_number: SLOT,
+		vrf_output: VRFOutput(inout.to_output()),
+		vrf_proof: VRFProof(proof),
+	});
+
+	Header {
+		parent_hash,
+		number,
+		state_root: Default::default(),
+		extrinsics_root: Default::default(),
+		digest: Digest { logs: vec![DigestItem::babe_pre_digest(pre_digest)] },
+	}
+}
+
+fn available_data() -> AvailableData {
+	AvailableData {
+		pov: PoV { block_data: BlockData(vec![1, 2, 3]) },
+		validation_data: PersistedValidationData::default(),
+	}
+}
+
+fn validation_outputs() -> ValidationOutputs {
+	ValidationOutputs {
+		head_data: HeadData(vec![4, 5, 6]),
+		validation_data: PersistedValidationData::default(),
+		upward_messages: Vec::new(),
+		fees: 0,
+		new_validation_code: None,
+		processed_downward_messages: 0,
+		horizontal_messages: Vec::new(),
+		hrmp_watermark: 0,
+	}
+}
+
+fn make_receipt() -> CandidateReceipt {
+	let chunks = erasure::obtain_chunks_v1(validators().len(), &available_data()).unwrap();
+	let erasure_root = erasure::branches(&chunks).root();
+	let outputs = validation_outputs();
+	let commitments = CandidateCommitments {
+		fees: outputs.fees,
+		upward_messages: outputs.upward_messages,
+		erasure_root,
+		new_validation_code: outputs.new_validation_code,
+		head_data: outputs.head_data,
+		processed_downward_messages: outputs.processed_downward_messages,
+		horizontal_messages: outputs.horizontal_messages,
+		hrmp_watermark: outputs.hrmp_watermark,
+	};
+
+	CandidateReceipt {
+		descriptor: CandidateDescriptor {
+			para_id: ParaId::from(1),
+			erasure_root,
+			..Default::default()
+		},
+		commitments_hash: commitments.hash(),
+	}
+}
+
+fn occupied_core(para_id: ParaId) -> CoreState {
+	CoreState::Occupied(OccupiedCore {
+		para_id,
+		next_up_on_available: None,
+		occupied_since: 0,
+		time_out_at: 10,
+		next_up_on_time_out: None,
+		availability: Default::default(),
+		group_responsible: 0,
+	})
+}
+
+fn sign_approval(
+	keyring: Sr25519Keyring,
+	validator: ValidatorIndex,
+	block_hash: Hash,
+	candidate_hash: Hash,
+) -> IndirectSignedApprovalVote {
+	let signed = Signed::<ApprovalVote>::sign(
+		ApprovalVote(candidate_hash),
+		&ApprovalVote::signing_context(1, block_hash),
+		validator,
+		&keyring.pair().into(),
+	);
+
+	IndirectSignedApprovalVote {
+		block_hash,
+		candidate_index: 0,
+		validator,
+		signature: signed.signature().clone(),
+	}
+}
+
+/// Compute the assignment of the given validator to the first core.
+fn assignment_for(keyring: Sr25519Keyring, story: &RelayVRFStory) -> OurAssignment {
+	let config = criteria::Config {
+		n_cores: 1,
+		relay_vrf_modulo_samples: Config::default().relay_vrf_modulo_samples,
+		n_delay_tranches: Config::default().n_delay_tranches,
+		zeroth_delay_tranche_width: Config::default().zeroth_delay_tranche_width,
+	};
+
+	criteria::compute_assignments(&keystore_with(Some(keyring)), story, &config, &validators(), &[CoreIndex(0)])
+		.remove(&CoreIndex(0))
+		.expect("single core is always assigned")
+}
+
+struct TestHarness {
+	virtual_overseer: test_helpers::TestSubsystemContextHandle<ApprovalVotingMessage>,
+	clock: MockClock,
+}
+
+fn test_harness<T: Future<Output = ()>>(
+	local_key: Option<Sr25519Keyring>,
+	test: impl FnOnce(TestHarness) -> T,
+) {
+	let pool = sp_core::testing::TaskExecutor::new();
+	let (context, virtual_overseer) = test_helpers::make_subsystem_context(pool.clone());
+
+	let clock = MockClock::new(block_tick());
+	let subsystem = ApprovalVotingSubsystem::new(
+		keystore_with(local_key),
+		Config::default(),
+		Metrics::default(),
+	);
+	let subsystem = run(context, subsystem, Box::new(clock.clone())).map(|_| ());
+
+	let test_fut = test(TestHarness { virtual_overseer, clock });
+
+	futures::pin_mut!(test_fut);
+	futures::pin_mut!(subsystem);
+
+	executor::block_on(future::select(test_fut, subsystem));
+}
+
+/// Activate a leaf including a single candidate and answer the requests of the subsystem.
+async fn import_block(
+	virtual_overseer: &mut test_helpers::TestSubsystemContextHandle<ApprovalVotingMessage>,
+	header: &Header,
+	receipt: &CandidateReceipt,
+) {
+	let head = header.hash();
+	virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+		ActiveLeavesUpdate::start_work(head),
+	))).await;
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+			assert_eq!(h, head);
+			tx.send(Ok(Some(header.clone()))).unwrap();
+		}
+	);
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			h,
+			RuntimeApiRequest::CandidateEvents(tx),
+		)) => {
+			assert_eq!(h, head);
+			tx.send(Ok(vec![CandidateEvent::CandidateIncluded(receipt.clone(), HeadData::default(), CoreIndex(0))]))
+				.unwrap();
+		}
+	);
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			h,
+			RuntimeApiRequest::SessionIndexForChild(tx),
+		)) => {
+			assert_eq!(h, header.parent_hash);
+			tx.send(Ok(1)).unwrap();
+		}
+	);
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			h,
+			RuntimeApiRequest::Validators(tx),
+		)) => {
+			assert_eq!(h, header.parent_hash);
+			tx.send(Ok(validators())).unwrap();
+		}
+	);
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			h,
+			RuntimeApiRequest::AvailabilityCores(tx),
+		)) => {
+			assert_eq!(h, header.parent_hash);
+			tx.send(Ok(vec![occupied_core(receipt.descriptor.para_id)])).unwrap();
+		}
+	);
+}
+
+async fn check_assignment(
+	virtual_overseer: &mut test_helpers::TestSubsystemContextHandle<ApprovalVotingMessage>,
+	assignment: IndirectAssignmentCert,
+	candidate_index: CandidateIndex,
+) -> AssignmentCheckResult {
+	let (tx, rx) = oneshot::channel();
+	virtual_overseer.send(FromOverseer::Communication {
+		msg: ApprovalVotingMessage::CheckAndImportAssignment(assignment, candidate_index, tx),
+	}).await;
+
+	rx.await.unwrap()
+}
+
+async fn check_approval(
+	virtual_overseer: &mut test_helpers::TestSubsystemContextHandle<ApprovalVotingMessage>,
+	approval: IndirectSignedApprovalVote,
+) -> ApprovalCheckResult {
+	let (tx, rx) = oneshot::channel();
+	virtual_overseer.send(FromOverseer::Communication {
+		msg: ApprovalVotingMessage::CheckAndImportApproval(approval, tx),
+	}).await;
+
+	rx.await.unwrap()
+}
+
+#[test]
+fn assignments_are_checked_against_imported_blocks() {
+	test_harness(None, |test_harness| async move {
+		let TestHarness { mut virtual_overseer, .. } = test_harness;
+
+		let header = make_header(Hash::repeat_byte(1), 5);
+		let head = header.hash();
+		let (_, story) = babe_slot_and_story(&header).unwrap();
+		let cert = assignment_for(Sr25519Keyring::Bob, &story).cert;
+		let assignment = IndirectAssignmentCert { block_hash: head, validator: 1, cert };
+
+		// the block isn't known yet.
+		assert_eq!(
+			check_assignment(&mut virtual_overseer, assignment.clone(), 0).await,
+			AssignmentCheckResult::Bad,
+		);
+
+		import_block(&mut virtual_overseer, &header, &make_receipt()).await;
+
+		assert_eq!(
+			check_assignment(&mut virtual_overseer, assignment.clone(), 0).await,
+			AssignmentCheckResult::Accepted,
+		);
+		assert_eq!(
+			check_assignment(&mut virtual_overseer, assignment.clone(), 0).await,
+			AssignmentCheckResult::AcceptedDuplicate,
+		);
+
+		// there is no second candidate.
+		assert_eq!(
+			check_assignment(&mut virtual_overseer, assignment.clone(), 1).await,
+			AssignmentCheckResult::Bad,
+		);
+
+		// the cert was produced by Bob's key.
+		let wrong_validator = IndirectAssignmentCert { validator: 2, ..assignment };
+		assert_eq!(
+			check_assignment(&mut virtual_overseer, wrong_validator, 0).await,
+			AssignmentCheckResult::Bad,
+		);
+
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+	});
+}
+
+#[test]
+fn approvals_require_an_assignment_and_a_valid_signature() {
+	test_harness(None, |test_harness| async move {
+		let TestHarness { mut virtual_overseer, .. } = test_harness;
+
+		let header = make_header(Hash::repeat_byte(1), 5);
+		let head = header.hash();
+		let receipt = make_receipt();
+		let (_, story) = babe_slot_and_story(&header).unwrap();
+
+		import_block(&mut virtual_overseer, &header, &receipt).await;
+
+		let approval = sign_approval(Sr25519Keyring::Bob, 1, head, receipt.hash());
+		assert_eq!(
+			check_approval(&mut virtual_overseer, approval.clone()).await,
+			ApprovalCheckResult::Bad,
+		);
+
+		let cert = assignment_for(Sr25519Keyring::Bob, &story).cert;
+		assert_eq!(
+			check_assignment(
+				&mut virtual_overseer,
+				IndirectAssignmentCert { block_hash: head, validator: 1, cert },
+				0,
+			).await,
+			AssignmentCheckResult::Accepted,
+		);
+
+		assert_eq!(
+			check_approval(&mut virtual_overseer, approval).await,
+			ApprovalCheckResult::Accepted,
+		);
+
+		let bad_signature = sign_approval(Sr25519Keyring::Charlie, 1, head, receipt.hash());
+		assert_eq!(
+			check_approval(&mut virtual_overseer, bad_signature).await,
+			ApprovalCheckResult::Bad,
+		);
+
+		let wrong_candidate = sign_approval(Sr25519Keyring::Bob, 1, head, Hash::repeat_byte(42));
+		assert_eq!(
+			check_approval(&mut virtual_overseer, wrong_candidate).await,
+			ApprovalCheckResult::Bad,
+		);
+
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+	});
+}
+
+#[test]
+fn triggered_assignment_is_distributed_and_checked() {
+	test_harness(Some(Sr25519Keyring::Alice), |test_harness| async move {
+		let TestHarness { mut virtual_overseer, clock } = test_harness;
+
+		let header = make_header(Hash::repeat_byte(1), 5);
+		let receipt = make_receipt();
+		let candidate_hash = receipt.hash();
+
+		import_block(&mut virtual_overseer, &header, &receipt).await;
+
+		clock.set_tick(block_tick() + 1);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::ApprovalDistribution(ApprovalDistributionMessage::DistributeAssignment(
+				cert,
+				0,
+			)) => {
+				assert_eq!(cert.block_hash, header.hash());
+				assert_eq!(cert.validator, 0);
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::AvailabilityRecovery(AvailabilityRecoveryMessage::RecoverAvailableData(
+				r,
+				session,
+				tx,
+			)) => {
+				assert_eq!(r.hash(), candidate_hash);
+				assert_eq!(session, 1);
+				tx.send(Ok(available_data())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				h,
+				RuntimeApiRequest::ValidationCode(para_id, OccupiedCoreAssumption::Included, tx),
+			)) => {
+				assert_eq!(h, receipt.descriptor.relay_parent);
+				assert_eq!(para_id, receipt.descriptor.para_id);
+				tx.send(Ok(Some(ValidationCode(vec![1, 2, 3])))).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				h,
+				RuntimeApiRequest::ExecutionTimeouts(tx),
+			)) => {
+				assert_eq!(h, receipt.descriptor.relay_parent);
+				tx.send(Ok(ExecutionTimeouts { backing: 1_000, approval: 3_000 })).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::CandidateValidation(CandidateValidationMessage::ValidateFromExhaustive(
+				validation_data,
+				transient_validation_data,
+				validation_code,
+				descriptor,
+				pov,
+				timeout,
+				tx,
+			)) => {
+				assert_eq!(validation_data, available_data().validation_data);
+				assert!(transient_validation_data.is_none());
+				assert_eq!(validation_code, ValidationCode(vec![1, 2, 3]));
+				assert_eq!(descriptor, receipt.descriptor);
+				assert_eq!(*pov, available_data().pov);
+				assert_eq!(timeout, Some(Duration::from_millis(3_000)));
+				tx.send(Ok(ValidationResult::Valid(validation_outputs()))).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::ApprovalDistribution(ApprovalDistributionMessage::DistributeApproval(vote)) => {
+				assert_eq!(vote.block_hash, header.hash());
+				assert_eq!(vote.candidate_index, 0);
+				assert_eq!(vote.validator, 0);
+			}
+		);
+
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+	});
+}
+
+#[test]
+fn candidates_are_approved_after_our_check() {
+	let receipt = make_receipt();
+	let candidate_hash = receipt.hash();
+	let block_hash = Hash::repeat_byte(1);
+	let story = RelayVRFStory([42; 32]);
+	let clock = MockClock::new(block_tick() + 1);
+
+	// we are the only validator, so our approval suffices.
+	let mut state = State {
+		keystore: keystore_with(Some(Sr25519Keyring::Alice)),
+		config: Config::default(),
+		clock: Box::new(clock),
+		metrics: Metrics::default(),
+		blocks: HashMap::new(),
+		candidate_checks: HashMap::new(),
+	};
+
+	state.blocks.insert(block_hash, BlockEntry {
+		number: 5,
+		session: 1,
+		tick: block_tick(),
+		relay_vrf_story: story,
+		n_cores: 1,
+		validators: vec![Sr25519Keyring::Alice.public().into()],
+		candidates: vec![CandidateEntry {
+			hash: candidate_hash,
+			receipt,
+			core: CoreIndex(0),
+			approval_entry: ApprovalEntry::default(),
+			our_assignment: Some(assignment_for(Sr25519Keyring::Alice, &story)),
+			approved: false,
+		}],
+		approved: false,
+	});
+
+	let actions = state.process_tick();
+	assert_eq!(actions.len(), 2);
+	assert_matches!(
+		&actions[0],
+		Action::DistributeAssignment(cert, 0) => {
+			assert_eq!(cert.block_hash, block_hash);
+			assert_eq!(cert.validator, 0);
+		}
+	);
+	assert_matches!(
+		&actions[1],
+		Action::LaunchApprovalCheck(check) => assert_eq!(check.receipt.hash(), candidate_hash)
+	);
+	assert_eq!(state.candidate_checks.get(&candidate_hash), Some(&CheckStatus::InProgress));
+
+	// the check is in progress, so it isn't launched again.
+	assert!(state.process_tick().is_empty());
+	assert!(!state.blocks[&block_hash].approved);
+
+	let actions = state.note_candidate_checked(candidate_hash, true);
+	assert_matches!(
+		&actions[..],
+		[Action::DistributeApproval(vote)] => {
+			assert_eq!(vote.block_hash, block_hash);
+			assert_eq!(vote.candidate_index, 0);
+			assert_eq!(vote.validator, 0);
+		}
+	);
+	assert!(state.process_tick().is_empty());
+
+	let block = &state.blocks[&block_hash];
+	assert!(block.candidates[0].approved);
+	assert!(block.approved);
+
+	state.note_block_finalized(5);
+	assert!(state.blocks.is_empty());
+	assert!(state.candidate_checks.is_empty());
+}
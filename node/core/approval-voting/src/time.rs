@@ -0,0 +1,76 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Time utilities for approval voting.
+
+use std::pin::Pin;
+use std::time::{Duration, SystemTime};
+
+use futures::prelude::*;
+use polkadot_node_primitives::approval::DelayTranche;
+
+/// A unit of time, measured in multiples of [`TICK_DURATION_MILLIS`] since the unix epoch.
+pub(crate) type Tick = u64;
+
+/// The duration of a single tick in milliseconds. Every tick is one delay tranche.
+pub(crate) const TICK_DURATION_MILLIS: u64 = 500;
+
+/// A clock which allows querying of the current tick as well as waiting for a tick to be
+/// reached.
+pub(crate) trait Clock {
+	/// Yields the current tick.
+	fn tick_now(&self) -> Tick;
+
+	/// Yields a future which concludes when the given tick is reached.
+	fn wait(&self, tick: Tick) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+}
+
+/// A clock which uses the system time.
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+	fn tick_now(&self) -> Tick {
+		time_to_tick(SystemTime::now())
+	}
+
+	fn wait(&self, tick: Tick) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> {
+		let wait_for = tick_to_time(tick)
+			.duration_since(SystemTime::now())
+			.unwrap_or_default();
+
+		Box::pin(futures_timer::Delay::new(wait_for))
+	}
+}
+
+fn time_to_tick(time: SystemTime) -> Tick {
+	let since_epoch = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+	since_epoch.as_millis() as u64 / TICK_DURATION_MILLIS
+}
+
+fn tick_to_time(tick: Tick) -> SystemTime {
+	SystemTime::UNIX_EPOCH + Duration::from_millis(TICK_DURATION_MILLIS * tick)
+}
+
+/// The first tick of the given BABE slot.
+pub(crate) fn slot_number_to_tick(slot_duration_millis: u64, slot: u64) -> Tick {
+	slot * slot_duration_millis / TICK_DURATION_MILLIS
+}
+
+/// The delay tranche which is current at `now` for a block whose slot starts at `block_tick`.
+pub(crate) fn tranche_now(block_tick: Tick, now: Tick) -> DelayTranche {
+	let elapsed = now.saturating_sub(block_tick);
+	std::cmp::min(elapsed, DelayTranche::max_value() as u64) as DelayTranche
+}
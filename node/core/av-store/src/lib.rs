@@ -23,30 +23,54 @@ use std::collections::HashMap;
 use std::io;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use codec::{Encode, Decode};
 use futures::{select, channel::oneshot, FutureExt};
+use futures_timer::Delay;
 use kvdb_rocksdb::{Database, DatabaseConfig};
 use kvdb::{KeyValueDB, DBTransaction};
 
 use polkadot_primitives::v1::{
-	Hash, AvailableData, ErasureChunk, ValidatorIndex,
+	Hash, AvailableData, BlockNumber, CandidateEvent, ErasureChunk, ValidatorIndex,
 };
 use polkadot_subsystem::{
-	FromOverseer, SubsystemError, Subsystem, SubsystemContext, SpawnedSubsystem,
+	ActiveLeavesUpdate, FromOverseer, OverseerSignal, SubsystemError, Subsystem, SubsystemContext,
+	SpawnedSubsystem,
+	errors::{ChainApiError, RuntimeApiError},
 	metrics::{self, prometheus},
 };
-use polkadot_subsystem::messages::AvailabilityStoreMessage;
+use polkadot_subsystem::messages::{
+	AllMessages, AvailabilityStoreMessage, ChainApiMessage, RuntimeApiMessage, RuntimeApiRequest,
+};
 
 const LOG_TARGET: &str = "availability";
 
 mod columns {
 	pub const DATA: u32 = 0;
-	pub const NUM_COLUMNS: u32 = 1;
+	pub const META: u32 = 1;
+	pub const NUM_COLUMNS: u32 = 2;
 }
 
+/// The key under which the pruning records are stored in the `META` column.
+const PRUNING_KEY: &[u8] = b"av_pruning_records";
+
+/// Keep data of candidates that were never seen included for 1 hour.
+const KEEP_STORED_BLOCK_FOR: Duration = Duration::from_secs(60 * 60);
+
+/// Keep available data for 1 day after the block that included it was finalized.
+const KEEP_FINALIZED_BLOCK_FOR: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Keep chunks for 1 day and 1 hour after the block that included them was finalized.
+const KEEP_FINALIZED_CHUNK_FOR: Duration = Duration::from_secs(25 * 60 * 60);
+
+/// Run the pruning job every 5 minutes.
+const PRUNING_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Debug, derive_more::From)]
 enum Error {
+	#[from]
+	ChainApi(ChainApiError),
 	#[from]
 	Erasure(erasure::Error),
 	#[from]
@@ -54,11 +78,14 @@ enum Error {
 	#[from]
 	Oneshot(oneshot::Canceled),
 	#[from]
+	RuntimeApi(RuntimeApiError),
+	#[from]
 	Subsystem(SubsystemError),
 }
 
 /// An implementation of the Availability Store subsystem.
 pub struct AvailabilityStoreSubsystem {
+	pruning_config: PruningConfig,
 	inner: Arc<dyn KeyValueDB>,
 	metrics: Metrics,
 }
@@ -77,12 +104,120 @@ struct StoredAvailableData {
 	n_validators: u32,
 }
 
+/// The state of a candidate whose data we keep.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq)]
+enum CandidateState {
+	/// The data was stored, but the candidate was not seen included yet.
+	Stored,
+	/// The candidate was included in the given relay-chain blocks, none of which is finalized yet.
+	Included(Vec<(BlockNumber, Hash)>),
+	/// A block including the candidate was finalized.
+	Finalized,
+}
+
+/// The kind of data a pruning record refers to.
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq)]
+enum StoredData {
+	/// The full `AvailableData` of the candidate.
+	AvailableData,
+	/// The erasure chunk with the given index.
+	Chunk(ValidatorIndex),
+}
+
+/// The time after which a piece of data may be pruned.
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq)]
+enum PruningDelay {
+	/// Prune at the given UNIX time in seconds.
+	At(u64),
+	/// Keep until the state of the candidate changes.
+	Indefinite,
+}
+
+/// A record tracking the lifetime of a piece of data stored for a candidate.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq)]
+struct PruningRecord {
+	candidate_hash: Hash,
+	data: StoredData,
+	candidate_state: CandidateState,
+	prune_at: PruningDelay,
+}
+
+impl PruningRecord {
+	fn key(&self) -> Vec<u8> {
+		match self.data {
+			StoredData::AvailableData => available_data_key(&self.candidate_hash),
+			StoredData::Chunk(index) => erasure_chunk_key(&self.candidate_hash, index),
+		}
+	}
+
+	fn should_prune(&self, now: u64) -> bool {
+		match self.prune_at {
+			PruningDelay::At(at) => at <= now,
+			PruningDelay::Indefinite => false,
+		}
+	}
+}
+
+/// Configuration of how long the availability store keeps data.
+#[derive(Debug, Clone)]
+pub struct PruningConfig {
+	/// How long to keep data of candidates that were never seen included.
+	pub keep_stored_block_for: Duration,
+	/// How long to keep available data after the block that included the candidate was finalized.
+	pub keep_finalized_block_for: Duration,
+	/// How long to keep chunks after the block that included the candidate was finalized.
+	pub keep_finalized_chunk_for: Duration,
+	/// How often to run the pruning job.
+	pub pruning_interval: Duration,
+}
+
+impl Default for PruningConfig {
+	fn default() -> Self {
+		Self {
+			keep_stored_block_for: KEEP_STORED_BLOCK_FOR,
+			keep_finalized_block_for: KEEP_FINALIZED_BLOCK_FOR,
+			keep_finalized_chunk_for: KEEP_FINALIZED_CHUNK_FOR,
+			pruning_interval: PRUNING_INTERVAL,
+		}
+	}
+}
+
+impl PruningConfig {
+	/// The time after which data in the given candidate state may be pruned.
+	fn prune_at(&self, state: &CandidateState, data: StoredData, now: u64) -> PruningDelay {
+		let keep_for = match (state, data) {
+			(CandidateState::Stored, _) => self.keep_stored_block_for,
+			(CandidateState::Included(_), _) => return PruningDelay::Indefinite,
+			(CandidateState::Finalized, StoredData::AvailableData) => self.keep_finalized_block_for,
+			(CandidateState::Finalized, StoredData::Chunk(_)) => self.keep_finalized_chunk_for,
+		};
+
+		PruningDelay::At(now.saturating_add(keep_for.as_secs()))
+	}
+}
+
 /// Configuration for the availability store.
 pub struct Config {
 	/// Total cache size in megabytes. If `None` the default (128 MiB per column) is used.
 	pub cache_size: Option<usize>,
 	/// Path to the database.
 	pub path: PathBuf,
+	/// The retention policy of the stored data.
+	pub pruning: PruningConfig,
+}
+
+/// The current UNIX time in seconds.
+#[cfg(not(test))]
+fn time_now() -> u64 {
+	std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
+
+#[cfg(test)]
+fn time_now() -> u64 {
+	tests::TIME_NOW.with(|now| now.borrow().unwrap_or_default())
 }
 
 impl AvailabilityStoreSubsystem {
@@ -107,14 +242,16 @@ impl AvailabilityStoreSubsystem {
 		let db = Database::open(&db_config, &path)?;
 
 		Ok(Self {
+			pruning_config: config.pruning,
 			inner: Arc::new(db),
 			metrics,
 		})
 	}
 
 	#[cfg(test)]
-	fn new_in_memory(inner: Arc<dyn KeyValueDB>) -> Self {
+	fn new_in_memory(inner: Arc<dyn KeyValueDB>, pruning_config: PruningConfig) -> Self {
 		Self {
+			pruning_config,
 			inner,
 			metrics: Metrics(None),
 		}
@@ -127,18 +264,47 @@ where
 	Context: SubsystemContext<Message=AvailabilityStoreMessage>,
 {
 	let ctx = &mut ctx;
+	let mut next_pruning = Delay::new(subsystem.pruning_config.pruning_interval).fuse();
+
 	loop {
 		select! {
 			incoming = ctx.recv().fuse() => {
 				match incoming {
-					Ok(FromOverseer::Signal(Conclude)) => break,
-					Ok(FromOverseer::Signal(_)) => (),
+					Ok(FromOverseer::Signal(OverseerSignal::Conclude)) => break,
+					Ok(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+						ActiveLeavesUpdate { activated, .. })
+					)) => {
+						for activated in activated.into_iter() {
+							if let Err(e) = process_block_activated(ctx, &subsystem, activated).await {
+								log::warn!(
+									target: LOG_TARGET,
+									"Failed to note the candidates included in {:?}: {:?}",
+									activated,
+									e,
+								);
+							}
+						}
+					}
+					Ok(FromOverseer::Signal(OverseerSignal::BlockFinalized(hash))) => {
+						if let Err(e) = process_block_finalized(ctx, &subsystem, hash).await {
+							log::warn!(
+								target: LOG_TARGET,
+								"Failed to process the finalization of {:?}: {:?}",
+								hash,
+								e,
+							);
+						}
+					}
 					Ok(FromOverseer::Communication { msg }) => {
-						process_message(&subsystem.inner, &subsystem.metrics, msg)?;
+						process_message(&subsystem, msg)?;
 					}
 					Err(_) => break,
 				}
 			}
+			_ = next_pruning => {
+				prune(&subsystem)?;
+				next_pruning = Delay::new(subsystem.pruning_config.pruning_interval).fuse();
+			}
 			complete => break,
 		}
 	}
@@ -146,8 +312,248 @@ where
 	Ok(())
 }
 
-fn process_message(db: &Arc<dyn KeyValueDB>, metrics: &Metrics, msg: AvailabilityStoreMessage) -> Result<(), Error> {
+async fn request_block_number<Context>(ctx: &mut Context, hash: Hash)
+	-> Result<Option<BlockNumber>, Error>
+where
+	Context: SubsystemContext<Message=AvailabilityStoreMessage>,
+{
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::ChainApi(ChainApiMessage::BlockNumber(hash, tx))).await?;
+
+	Ok(rx.await??)
+}
+
+async fn request_finalized_block_hash<Context>(ctx: &mut Context, number: BlockNumber)
+	-> Result<Option<Hash>, Error>
+where
+	Context: SubsystemContext<Message=AvailabilityStoreMessage>,
+{
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::ChainApi(ChainApiMessage::FinalizedBlockHash(number, tx))).await?;
+
+	Ok(rx.await??)
+}
+
+/// Note the candidates included and timed out in a newly activated block and update the pruning
+/// records of their data accordingly.
+async fn process_block_activated<Context>(
+	ctx: &mut Context,
+	subsystem: &AvailabilityStoreSubsystem,
+	hash: Hash,
+) -> Result<(), Error>
+where
+	Context: SubsystemContext<Message=AvailabilityStoreMessage>,
+{
+	let number = match request_block_number(ctx, hash).await? {
+		Some(number) => number,
+		None => return Ok(()),
+	};
+
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		hash,
+		RuntimeApiRequest::CandidateEvents(tx),
+	))).await?;
+	let events = rx.await??;
+
+	let db = &subsystem.inner;
+	let now = time_now();
+	let mut records = pruning_records(db);
+
+	for event in events {
+		match event {
+			CandidateEvent::CandidateIncluded(receipt, _, _) => {
+				let candidate_hash = receipt.hash();
+				for record in records.iter_mut().filter(|r| r.candidate_hash == candidate_hash) {
+					match record.candidate_state {
+						CandidateState::Stored => {
+							record.candidate_state = CandidateState::Included(vec![(number, hash)]);
+						}
+						CandidateState::Included(ref mut blocks) => if !blocks.contains(&(number, hash)) {
+							blocks.push((number, hash));
+						},
+						CandidateState::Finalized => continue,
+					}
+
+					record.prune_at = subsystem.pruning_config.prune_at(
+						&record.candidate_state,
+						record.data,
+						now,
+					);
+				}
+			}
+			CandidateEvent::CandidateTimedOut(receipt, _, _) => {
+				// the data of a candidate that didn't become available is of no use anymore,
+				// unless the candidate got included elsewhere.
+				let candidate_hash = receipt.hash();
+				for record in records.iter_mut().filter(|r| r.candidate_hash == candidate_hash) {
+					if record.candidate_state == CandidateState::Stored {
+						record.prune_at = PruningDelay::At(now);
+					}
+				}
+			}
+			CandidateEvent::CandidateBacked(..) => {}
+		}
+	}
+
+	let mut tx = DBTransaction::new();
+	write_pruning_records(&mut tx, &records);
+	db.write(tx)?;
+
+	Ok(())
+}
+
+/// Update the pruning records of the candidates included in the newly finalized block and the
+/// blocks preceding it.
+///
+/// Candidates that were only included in blocks which got abandoned by finality are scheduled
+/// for pruning right away.
+async fn process_block_finalized<Context>(
+	ctx: &mut Context,
+	subsystem: &AvailabilityStoreSubsystem,
+	hash: Hash,
+) -> Result<(), Error>
+where
+	Context: SubsystemContext<Message=AvailabilityStoreMessage>,
+{
+	let finalized_number = match request_block_number(ctx, hash).await? {
+		Some(number) => number,
+		None => return Ok(()),
+	};
+
+	let db = &subsystem.inner;
+	let now = time_now();
+	let mut records = pruning_records(db);
+
+	// the finalized block hashes at every height a candidate was included at.
+	let mut finalized_hashes: HashMap<BlockNumber, Option<Hash>> = HashMap::new();
+	for record in records.iter() {
+		if let CandidateState::Included(ref blocks) = record.candidate_state {
+			for (number, _) in blocks.iter().filter(|(n, _)| *n <= finalized_number) {
+				if !finalized_hashes.contains_key(number) {
+					let finalized_hash = request_finalized_block_hash(ctx, *number).await?;
+					finalized_hashes.insert(*number, finalized_hash);
+				}
+			}
+		}
+	}
+
+	for record in records.iter_mut() {
+		let finalized = match record.candidate_state {
+			CandidateState::Included(ref mut blocks) => {
+				if blocks.iter().any(|(n, h)| finalized_hashes.get(n) == Some(&Some(*h))) {
+					true
+				} else {
+					blocks.retain(|(n, _)| *n > finalized_number);
+					if !blocks.is_empty() {
+						continue
+					}
+
+					false
+				}
+			}
+			_ => continue,
+		};
+
+		if finalized {
+			record.candidate_state = CandidateState::Finalized;
+			record.prune_at = subsystem.pruning_config.prune_at(
+				&record.candidate_state,
+				record.data,
+				now,
+			);
+		} else {
+			record.candidate_state = CandidateState::Stored;
+			record.prune_at = PruningDelay::At(now);
+		}
+	}
+
+	let mut tx = DBTransaction::new();
+	write_pruning_records(&mut tx, &records);
+	db.write(tx)?;
+
+	Ok(())
+}
+
+/// Remove all data whose pruning time has come along with its pruning records.
+fn prune(subsystem: &AvailabilityStoreSubsystem) -> Result<(), Error> {
+	let db = &subsystem.inner;
+	let now = time_now();
+
+	let (to_prune, to_keep): (Vec<_>, Vec<_>) = pruning_records(db)
+		.into_iter()
+		.partition(|r| r.should_prune(now));
+
+	if to_prune.is_empty() {
+		return Ok(());
+	}
+
+	let mut tx = DBTransaction::new();
+	let mut pruned_data = 0;
+	let mut pruned_chunks = 0;
+
+	for record in to_prune.iter() {
+		tx.delete(columns::DATA, record.key().as_slice());
+		match record.data {
+			StoredData::AvailableData => pruned_data += 1,
+			StoredData::Chunk(_) => pruned_chunks += 1,
+		}
+	}
+
+	write_pruning_records(&mut tx, &to_keep);
+	db.write(tx)?;
+
+	subsystem.metrics.on_pruned(pruned_data, pruned_chunks);
+
+	Ok(())
+}
+
+fn pruning_records(db: &Arc<dyn KeyValueDB>) -> Vec<PruningRecord> {
+	query_inner(db, columns::META, PRUNING_KEY).unwrap_or_default()
+}
+
+fn write_pruning_records(tx: &mut DBTransaction, records: &[PruningRecord]) {
+	tx.put_vec(columns::META, PRUNING_KEY, records.encode());
+}
+
+/// Note that the given data is stored for a candidate, unless there is a record for it already.
+///
+/// New records inherit the state of the other data we keep for the same candidate.
+fn note_stored(
+	tx: &mut DBTransaction,
+	db: &Arc<dyn KeyValueDB>,
+	pruning_config: &PruningConfig,
+	candidate_hash: &Hash,
+	data: StoredData,
+) {
+	let mut records = pruning_records(db);
+
+	if records.iter().any(|r| &r.candidate_hash == candidate_hash && r.data == data) {
+		return;
+	}
+
+	let candidate_state = records.iter()
+		.find(|r| &r.candidate_hash == candidate_hash)
+		.map(|r| r.candidate_state.clone())
+		.unwrap_or(CandidateState::Stored);
+
+	records.push(PruningRecord {
+		candidate_hash: *candidate_hash,
+		data,
+		prune_at: pruning_config.prune_at(&candidate_state, data, time_now()),
+		candidate_state,
+	});
+
+	write_pruning_records(tx, &records);
+}
+
+fn process_message(subsystem: &AvailabilityStoreSubsystem, msg: AvailabilityStoreMessage) -> Result<(), Error> {
 	use AvailabilityStoreMessage::*;
+
+	let db = &subsystem.inner;
+	let metrics = &subsystem.metrics;
+	let pruning_config = &subsystem.pruning_config;
+
 	match msg {
 		QueryAvailableData(hash, tx) => {
 			tx.send(available_data(db, &hash).map(|d| d.data)).map_err(|_| oneshot::Canceled)?;
@@ -156,13 +562,14 @@ fn process_message(db: &Arc<dyn KeyValueDB>, metrics: &Metrics, msg: Availabilit
 			tx.send(available_data(db, &hash).is_some()).map_err(|_| oneshot::Canceled)?;
 		}
 		QueryChunk(hash, id, tx) => {
-			tx.send(get_chunk(db, &hash, id, metrics)?).map_err(|_| oneshot::Canceled)?;
+			tx.send(get_chunk(db, pruning_config, &hash, id, metrics)?).map_err(|_| oneshot::Canceled)?;
 		}
 		QueryChunkAvailability(hash, id, tx) => {
-			tx.send(get_chunk(db, &hash, id, metrics)?.is_some()).map_err(|_| oneshot::Canceled)?;
+			tx.send(get_chunk(db, pruning_config, &hash, id, metrics)?.is_some())
+				.map_err(|_| oneshot::Canceled)?;
 		}
 		StoreChunk(hash, id, chunk, tx) => {
-			match store_chunk(db, &hash, id, chunk) {
+			match store_chunk(db, pruning_config, &hash, id, chunk) {
 				Err(e) => {
 					tx.send(Err(())).map_err(|_| oneshot::Canceled)?;
 					return Err(e);
@@ -173,7 +580,7 @@ fn process_message(db: &Arc<dyn KeyValueDB>, metrics: &Metrics, msg: Availabilit
 			}
 		}
 		StoreAvailableData(hash, id, n_validators, av_data, tx) => {
-			match store_available_data(db, &hash, id, n_validators, av_data, metrics) {
+			match store_available_data(db, pruning_config, &hash, id, n_validators, av_data, metrics) {
 				Err(e) => {
 					tx.send(Err(())).map_err(|_| oneshot::Canceled)?;
 					return Err(e);
@@ -194,6 +601,7 @@ fn available_data(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash) -> Option<Sto
 
 fn store_available_data(
 	db: &Arc<dyn KeyValueDB>,
+	pruning_config: &PruningConfig,
 	candidate_hash: &Hash,
 	id: Option<ValidatorIndex>,
 	n_validators: u32,
@@ -204,7 +612,7 @@ fn store_available_data(
 
 	if let Some(index) = id {
 		let chunks = get_chunks(&available_data, n_validators as usize, metrics)?;
-		store_chunk(db, candidate_hash, n_validators, chunks[index as usize].clone())?;
+		store_chunk(db, pruning_config, candidate_hash, n_validators, chunks[index as usize].clone())?;
 	}
 
 	let stored_data = StoredAvailableData {
@@ -217,28 +625,38 @@ fn store_available_data(
 		available_data_key(&candidate_hash).as_slice(),
 		stored_data.encode(),
 	);
+	note_stored(&mut tx, db, pruning_config, candidate_hash, StoredData::AvailableData);
 
 	db.write(tx)?;
 
 	Ok(())
 }
 
-fn store_chunk(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash, _n_validators: u32, chunk: ErasureChunk)
-	-> Result<(), Error>
-{
+fn store_chunk(
+	db: &Arc<dyn KeyValueDB>,
+	pruning_config: &PruningConfig,
+	candidate_hash: &Hash,
+	_n_validators: u32,
+	chunk: ErasureChunk,
+) -> Result<(), Error> {
 	let mut tx = DBTransaction::new();
 
 	let dbkey = erasure_chunk_key(candidate_hash, chunk.index);
 
 	tx.put_vec(columns::DATA, &dbkey, chunk.encode());
+	note_stored(&mut tx, db, pruning_config, candidate_hash, StoredData::Chunk(chunk.index));
 	db.write(tx)?;
 
 	Ok(())
 }
 
-fn get_chunk(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash, index: u32, metrics: &Metrics)
-	-> Result<Option<ErasureChunk>, Error>
-{
+fn get_chunk(
+	db: &Arc<dyn KeyValueDB>,
+	pruning_config: &PruningConfig,
+	candidate_hash: &Hash,
+	index: u32,
+	metrics: &Metrics,
+) -> Result<Option<ErasureChunk>, Error> {
 	if let Some(chunk) = query_inner(
 		db,
 		columns::DATA,
@@ -250,7 +668,7 @@ fn get_chunk(db: &Arc<dyn KeyValueDB>, candidate_hash: &Hash, index: u32, metric
 		let mut chunks = get_chunks(&data.data, data.n_validators as usize, metrics)?;
 		let desired_chunk = chunks.get(index as usize).cloned();
 		for chunk in chunks.drain(..) {
-			store_chunk(db, candidate_hash, data.n_validators, chunk)?;
+			store_chunk(db, pruning_config, candidate_hash, data.n_validators, chunk)?;
 		}
 		return Ok(desired_chunk);
 	}
@@ -313,6 +731,8 @@ fn get_chunks(data: &AvailableData, n_validators: usize, metrics: &Metrics) -> R
 #[derive(Clone)]
 struct MetricsInner {
 	received_availability_chunks_total: prometheus::Counter<prometheus::U64>,
+	pruned_availability_data_total: prometheus::Counter<prometheus::U64>,
+	pruned_availability_chunks_total: prometheus::Counter<prometheus::U64>,
 }
 
 /// Availability metrics.
@@ -328,6 +748,13 @@ impl Metrics {
 			metrics.received_availability_chunks_total.inc_by(by);
 		}
 	}
+
+	fn on_pruned(&self, data: u64, chunks: u64) {
+		if let Some(metrics) = &self.0 {
+			metrics.pruned_availability_data_total.inc_by(data);
+			metrics.pruned_availability_chunks_total.inc_by(chunks);
+		}
+	}
 }
 
 impl metrics::Metrics for Metrics {
@@ -340,6 +767,20 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			pruned_availability_data_total: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_pruned_availability_data_total",
+					"Number of available data entries pruned from the availability store.",
+				)?,
+				registry,
+			)?,
+			pruned_availability_chunks_total: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_pruned_availability_chunks_total",
+					"Number of availability chunks pruned from the availability store.",
+				)?,
+				registry,
+			)?,
 		};
 		Ok(Metrics(Some(metrics)))
 	}
@@ -355,8 +796,10 @@ mod tests {
 		Future,
 	};
 	use std::cell::RefCell;
+	use assert_matches::assert_matches;
 	use polkadot_primitives::v1::{
-		AvailableData, BlockData, HeadData, PersistedValidationData, PoV,
+		AvailableData, BlockData, CandidateReceipt, CoreIndex, HeadData, Id as ParaId,
+		PersistedValidationData, PoV,
 	};
 	use polkadot_node_subsystem_test_helpers as test_helpers;
 
@@ -365,7 +808,7 @@ mod tests {
 	}
 
 	thread_local! {
-		static TIME_NOW: RefCell<Option<u64>> = RefCell::new(None);
+		pub(super) static TIME_NOW: RefCell<Option<u64>> = RefCell::new(None);
 	}
 
 	struct TestState {
@@ -379,6 +822,7 @@ mod tests {
 				parent_head: HeadData(vec![7, 8, 9]),
 				block_number: Default::default(),
 				hrmp_mqc_heads: Vec::new(),
+				dmq_mqc_head: Default::default(),
 			};
 			Self {
 				persisted_validation_data,
@@ -387,13 +831,14 @@ mod tests {
 	}
 
 	fn test_harness<T: Future<Output=()>>(
+		pruning_config: PruningConfig,
 		store: Arc<dyn KeyValueDB>,
 		test: impl FnOnce(TestHarness) -> T,
 	) {
 		let pool = sp_core::testing::TaskExecutor::new();
 		let (context, virtual_overseer) = test_helpers::make_subsystem_context(pool.clone());
 
-		let subsystem = AvailabilityStoreSubsystem::new_in_memory(store);
+		let subsystem = AvailabilityStoreSubsystem::new_in_memory(store, pruning_config);
 		let subsystem = run(subsystem, context);
 
 		let test_fut = test(TestHarness {
@@ -409,7 +854,7 @@ mod tests {
 	#[test]
 	fn store_chunk_works() {
 		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
-		test_harness(store.clone(), |test_harness| async move {
+		test_harness(PruningConfig::default(), store.clone(), |test_harness| async move {
 			let TestHarness { mut virtual_overseer } = test_harness;
 			let relay_parent = Hash::from([1; 32]);
 			let validator_index = 5;
@@ -449,7 +894,7 @@ mod tests {
 	fn store_block_works() {
 		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
 		let test_state = TestState::default();
-		test_harness(store.clone(), |test_harness| async move {
+		test_harness(PruningConfig::default(), store.clone(), |test_harness| async move {
 			let TestHarness { mut virtual_overseer } = test_harness;
 			let candidate_hash = Hash::from([1; 32]);
 			let validator_index = 5;
@@ -503,7 +948,7 @@ mod tests {
 		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
 		let test_state = TestState::default();
 
-		test_harness(store.clone(), |test_harness| async move {
+		test_harness(PruningConfig::default(), store.clone(), |test_harness| async move {
 			let TestHarness { mut virtual_overseer } = test_harness;
 			let candidate_hash = Hash::from([1; 32]);
 			let n_validators = 10;
@@ -565,4 +1010,247 @@ mod tests {
 
 		rx.await.unwrap()
 	}
+
+	fn set_time(now: u64) {
+		TIME_NOW.with(|t| *t.borrow_mut() = Some(now));
+	}
+
+	// Gives the pruning job the chance to run at least once.
+	async fn wait_for_pruning(pruning_config: &PruningConfig) {
+		Delay::new(pruning_config.pruning_interval * 5).await;
+	}
+
+	fn test_pruning_config() -> PruningConfig {
+		PruningConfig {
+			keep_stored_block_for: Duration::from_secs(10),
+			keep_finalized_block_for: Duration::from_secs(100),
+			keep_finalized_chunk_for: Duration::from_secs(200),
+			pruning_interval: Duration::from_millis(10),
+		}
+	}
+
+	fn test_candidate(para_id: u32) -> CandidateReceipt {
+		let mut receipt = CandidateReceipt::default();
+		receipt.descriptor.para_id = ParaId::from(para_id);
+		receipt
+	}
+
+	async fn store_available_data(
+		virtual_overseer: &mut test_helpers::TestSubsystemContextHandle<AvailabilityStoreMessage>,
+		candidate_hash: Hash,
+		validator_index: ValidatorIndex,
+		available_data: AvailableData,
+	) {
+		let (tx, rx) = oneshot::channel();
+		let block_msg = AvailabilityStoreMessage::StoreAvailableData(
+			candidate_hash,
+			Some(validator_index),
+			10,
+			available_data,
+			tx,
+		);
+
+		virtual_overseer.send(FromOverseer::Communication{ msg: block_msg }).await;
+		assert_eq!(rx.await.unwrap(), Ok(()));
+	}
+
+	async fn activate_leaf(
+		virtual_overseer: &mut test_helpers::TestSubsystemContextHandle<AvailabilityStoreMessage>,
+		hash: Hash,
+		number: BlockNumber,
+		events: Vec<CandidateEvent>,
+	) {
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+			ActiveLeavesUpdate::start_work(hash),
+		))).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::ChainApi(ChainApiMessage::BlockNumber(h, tx)) => {
+				assert_eq!(h, hash);
+				tx.send(Ok(Some(number))).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				relay_parent,
+				RuntimeApiRequest::CandidateEvents(tx),
+			)) => {
+				assert_eq!(relay_parent, hash);
+				tx.send(Ok(events)).unwrap();
+			}
+		);
+	}
+
+	async fn finalize_block(
+		virtual_overseer: &mut test_helpers::TestSubsystemContextHandle<AvailabilityStoreMessage>,
+		hash: Hash,
+		number: BlockNumber,
+	) {
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::BlockFinalized(hash))).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::ChainApi(ChainApiMessage::BlockNumber(h, tx)) => {
+				assert_eq!(h, hash);
+				tx.send(Ok(Some(number))).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::ChainApi(ChainApiMessage::FinalizedBlockHash(n, tx)) => {
+				assert_eq!(n, number);
+				tx.send(Ok(Some(hash))).unwrap();
+			}
+		);
+	}
+
+	#[test]
+	fn stored_data_is_pruned_if_never_included() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let test_state = TestState::default();
+		let pruning_config = test_pruning_config();
+
+		test_harness(pruning_config.clone(), store.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+			let candidate_hash = test_candidate(1).hash();
+			let validator_index = 5;
+
+			let available_data = AvailableData {
+				pov: PoV { block_data: BlockData(vec![4, 5, 6]) },
+				validation_data: test_state.persisted_validation_data,
+			};
+
+			set_time(1000);
+			store_available_data(
+				&mut virtual_overseer,
+				candidate_hash,
+				validator_index,
+				available_data.clone(),
+			).await;
+
+			// still within the timeout.
+			set_time(1005);
+			wait_for_pruning(&pruning_config).await;
+			assert_eq!(
+				query_available_data(&mut virtual_overseer, candidate_hash).await,
+				Some(available_data),
+			);
+
+			set_time(1011);
+			wait_for_pruning(&pruning_config).await;
+			assert!(query_available_data(&mut virtual_overseer, candidate_hash).await.is_none());
+			assert!(query_chunk(&mut virtual_overseer, candidate_hash, validator_index).await.is_none());
+		});
+	}
+
+	#[test]
+	fn included_data_is_kept_until_finalized() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let test_state = TestState::default();
+		let pruning_config = test_pruning_config();
+
+		test_harness(pruning_config.clone(), store.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+			let candidate = test_candidate(1);
+			let candidate_hash = candidate.hash();
+			let block_hash = Hash::from([1; 32]);
+			let validator_index = 5;
+
+			let available_data = AvailableData {
+				pov: PoV { block_data: BlockData(vec![4, 5, 6]) },
+				validation_data: test_state.persisted_validation_data,
+			};
+
+			set_time(1000);
+			store_available_data(
+				&mut virtual_overseer,
+				candidate_hash,
+				validator_index,
+				available_data.clone(),
+			).await;
+
+			activate_leaf(
+				&mut virtual_overseer,
+				block_hash,
+				1,
+				vec![CandidateEvent::CandidateIncluded(candidate, HeadData::default(), CoreIndex(0))],
+			).await;
+
+			// included data is kept for as long as it's not finalized.
+			set_time(1_000_000);
+			wait_for_pruning(&pruning_config).await;
+			assert!(query_available_data(&mut virtual_overseer, candidate_hash).await.is_some());
+
+			finalize_block(&mut virtual_overseer, block_hash, 1).await;
+
+			set_time(1_000_050);
+			wait_for_pruning(&pruning_config).await;
+			assert!(query_available_data(&mut virtual_overseer, candidate_hash).await.is_some());
+
+			// the available data is gone, but our chunk is kept for a bit longer.
+			set_time(1_000_150);
+			wait_for_pruning(&pruning_config).await;
+			assert!(query_available_data(&mut virtual_overseer, candidate_hash).await.is_none());
+			assert!(query_chunk(&mut virtual_overseer, candidate_hash, validator_index).await.is_some());
+
+			set_time(1_000_250);
+			wait_for_pruning(&pruning_config).await;
+			assert!(query_chunk(&mut virtual_overseer, candidate_hash, validator_index).await.is_none());
+		});
+	}
+
+	#[test]
+	fn data_included_in_abandoned_fork_is_pruned_on_finality() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let test_state = TestState::default();
+		let pruning_config = test_pruning_config();
+
+		test_harness(pruning_config.clone(), store.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+			let candidate_1 = test_candidate(1);
+			let candidate_2 = test_candidate(2);
+			let candidate_1_hash = candidate_1.hash();
+			let candidate_2_hash = candidate_2.hash();
+			let leaf_1 = Hash::from([1; 32]);
+			let leaf_2 = Hash::from([2; 32]);
+
+			let available_data = AvailableData {
+				pov: PoV { block_data: BlockData(vec![4, 5, 6]) },
+				validation_data: test_state.persisted_validation_data,
+			};
+
+			set_time(1000);
+			store_available_data(&mut virtual_overseer, candidate_1_hash, 5, available_data.clone()).await;
+			store_available_data(&mut virtual_overseer, candidate_2_hash, 5, available_data.clone()).await;
+
+			activate_leaf(
+				&mut virtual_overseer,
+				leaf_1,
+				1,
+				vec![CandidateEvent::CandidateIncluded(candidate_1, HeadData::default(), CoreIndex(0))],
+			).await;
+			activate_leaf(
+				&mut virtual_overseer,
+				leaf_2,
+				1,
+				vec![CandidateEvent::CandidateIncluded(candidate_2, HeadData::default(), CoreIndex(0))],
+			).await;
+
+			set_time(1005);
+			finalize_block(&mut virtual_overseer, leaf_1, 1).await;
+
+			// the candidate included only in the abandoned fork is pruned right away.
+			wait_for_pruning(&pruning_config).await;
+			assert!(query_available_data(&mut virtual_overseer, candidate_1_hash).await.is_some());
+			assert!(query_available_data(&mut virtual_overseer, candidate_2_hash).await.is_none());
+
+			set_time(1106);
+			wait_for_pruning(&pruning_config).await;
+			assert!(query_available_data(&mut virtual_overseer, candidate_1_hash).await.is_none());
+		});
+	}
 }
@@ -292,11 +292,14 @@ impl CandidateBackingJob {
 				// have not seconded the given candidate.
 				//
 				// If the commitments hash produced by validation is not the same as given by
-				// the collator, do not make available and report the collator.
+				// the collator, or the erasure root in the descriptor doesn't match the one of
+				// the data, do not make available and report the collator.
 				let commitments_check = self.make_pov_available(
 					pov,
 					outputs,
-					|commitments| if commitments.hash() == candidate.commitments_hash {
+					|commitments| if commitments.hash() == candidate.commitments_hash
+						&& commitments.erasure_root == candidate.descriptor().erasure_root
+					{
 						Ok(CommittedCandidateReceipt {
 							descriptor: candidate.descriptor().clone(),
 							commitments,
@@ -489,6 +492,7 @@ impl CandidateBackingJob {
 		let expected_commitments = candidate.commitments.clone();
 
 		let descriptor = candidate.descriptor().clone();
+		let expected_erasure_root = descriptor.erasure_root;
 
 		// Check that candidate is collated by the right collator.
 		if self.required_collator.as_ref()
@@ -507,11 +511,14 @@ impl CandidateBackingJob {
 
 		let statement = match v {
 			ValidationResult::Valid(outputs) => {
-				// If validation produces a new set of commitments, we vote the candidate as invalid.
+				// If validation produces a new set of commitments or the erasure root in the
+				// descriptor is wrong, we vote the candidate as invalid.
 				let commitments_check = self.make_pov_available(
 					(&*pov).clone(),
 					outputs,
-					|commitments| if commitments == expected_commitments {
+					|commitments| if commitments == expected_commitments
+						&& commitments.erasure_root == expected_erasure_root
+					{
 						Ok(())
 					} else {
 						Err(())
@@ -665,6 +672,9 @@ impl CandidateBackingJob {
 			erasure_root,
 			new_validation_code: outputs.new_validation_code,
 			head_data: outputs.head_data,
+			processed_downward_messages: outputs.processed_downward_messages,
+			horizontal_messages: outputs.horizontal_messages,
+			hrmp_watermark: outputs.hrmp_watermark,
 		};
 
 		let res = match with_commitments(commitments) {
@@ -957,6 +967,7 @@ mod tests {
 					parent_head: HeadData(vec![7, 8, 9]),
 					block_number: Default::default(),
 					hrmp_mqc_heads: Vec::new(),
+					dmq_mqc_head: Default::default(),
 				},
 				transient: TransientValidationData {
 					max_code_size: 1000,
@@ -1028,6 +1039,7 @@ mod tests {
 					para_id: self.para_id,
 					pov_hash: self.pov_hash,
 					relay_parent: self.relay_parent,
+					erasure_root: self.erasure_root,
 					..Default::default()
 				},
 				commitments: CandidateCommitments {
@@ -1141,6 +1153,9 @@ mod tests {
 							upward_messages: Vec::new(),
 							fees: Default::default(),
 							new_validation_code: None,
+							processed_downward_messages: 0,
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
 						}),
 					)).unwrap();
 				}
@@ -1252,6 +1267,9 @@ mod tests {
 							upward_messages: Vec::new(),
 							fees: Default::default(),
 							new_validation_code: None,
+							processed_downward_messages: 0,
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
 						}),
 					)).unwrap();
 				}
@@ -1381,6 +1399,9 @@ mod tests {
 							upward_messages: Vec::new(),
 							fees: Default::default(),
 							new_validation_code: None,
+							processed_downward_messages: 0,
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
 						}),
 					)).unwrap();
 				}
@@ -1537,6 +1558,9 @@ mod tests {
 							upward_messages: Vec::new(),
 							fees: Default::default(),
 							new_validation_code: None,
+							processed_downward_messages: 0,
+							horizontal_messages: Vec::new(),
+							hrmp_watermark: 0,
 						}),
 					)).unwrap();
 				}
@@ -21,29 +21,54 @@
 
 use futures::{
 	channel::{mpsc, oneshot},
+	future::FusedFuture,
 	prelude::*,
 };
+use futures_timer::Delay;
 use polkadot_node_primitives::ValidationResult;
 use polkadot_node_subsystem::{
 	errors::{ChainApiError, RuntimeApiError},
 	messages::{
 		AllMessages, CandidateBackingMessage, CandidateSelectionMessage,
-		CandidateValidationMessage, CollatorProtocolMessage,
+		CandidateValidationMessage, CollatorProtocolMessage, PeerSetManagerMessage,
 	},
 	metrics::{self, prometheus},
 };
-use polkadot_node_subsystem_util::{self as util, delegated_subsystem, JobTrait, ToJobTrait};
+use polkadot_node_subsystem_util::{
+	self as util, delegated_subsystem, JobTrait, TimeoutExt, ToJobTrait,
+};
 use polkadot_primitives::v1::{
 	CandidateDescriptor, CandidateReceipt, CollatorId, Hash, Id as ParaId, PoV,
 };
-use std::{convert::TryFrom, pin::Pin, sync::Arc};
+use std::{cmp::Reverse, convert::TryFrom, pin::Pin, sync::Arc, time::Duration};
 
 const TARGET: &'static str = "candidate_selection";
 
+/// How long advertisements are collected after the first one, before a collation is selected.
+const COLLATION_WINDOW: Duration = Duration::from_millis(500);
+
+/// How long to wait for a selected collation to be fetched before falling back to the next one.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A collation advertised to us by a collator.
+#[derive(Debug, Clone, PartialEq)]
+struct Advertisement {
+	relay_parent: Hash,
+	para_id: ParaId,
+	collator_id: CollatorId,
+	pov_size: u32,
+}
+
 struct CandidateSelectionJob {
 	sender: mpsc::Sender<FromJob>,
 	receiver: mpsc::Receiver<ToJob>,
 	metrics: Metrics,
+	collation_window: Duration,
+	fetch_timeout: Duration,
+	/// Advertised collations which haven't been tried yet.
+	advertisements: Vec<Advertisement>,
+	/// Once the collection window has ended, collations are selected as soon as they are advertised.
+	window_ended: bool,
 	seconded_candidate: Option<CollatorId>,
 }
 
@@ -89,6 +114,7 @@ enum FromJob {
 	Validation(CandidateValidationMessage),
 	Backing(CandidateBackingMessage),
 	Collator(CollatorProtocolMessage),
+	PeerSetManager(PeerSetManagerMessage),
 }
 
 impl From<FromJob> for AllMessages {
@@ -97,6 +123,7 @@ impl From<FromJob> for AllMessages {
 			FromJob::Validation(msg) => AllMessages::CandidateValidation(msg),
 			FromJob::Backing(msg) => AllMessages::CandidateBacking(msg),
 			FromJob::Collator(msg) => AllMessages::CollatorProtocol(msg),
+			FromJob::PeerSetManager(msg) => AllMessages::PeerSetManager(msg),
 		}
 	}
 }
@@ -109,6 +136,7 @@ impl TryFrom<AllMessages> for FromJob {
 			AllMessages::CandidateValidation(msg) => Ok(FromJob::Validation(msg)),
 			AllMessages::CandidateBacking(msg) => Ok(FromJob::Backing(msg)),
 			AllMessages::CollatorProtocol(msg) => Ok(FromJob::Collator(msg)),
+			AllMessages::PeerSetManager(msg) => Ok(FromJob::PeerSetManager(msg)),
 			_ => Err(()),
 		}
 	}
@@ -168,6 +196,10 @@ impl CandidateSelectionJob {
 			sender,
 			receiver,
 			metrics,
+			collation_window: COLLATION_WINDOW,
+			fetch_timeout: FETCH_TIMEOUT,
+			advertisements: Vec::new(),
+			window_ended: false,
 			seconded_candidate: None,
 		}
 	}
@@ -178,23 +210,41 @@ impl CandidateSelectionJob {
 
 	/// this function exists for testing and should not generally be used; use `run_loop` instead.
 	async fn run_loop_borrowed(&mut self) -> Result<(), Error> {
-		while let Some(msg) = self.receiver.next().await {
-			match msg {
-				ToJob::CandidateSelection(CandidateSelectionMessage::Collation(
-					relay_parent,
-					para_id,
-					collator_id,
-				)) => {
-					self.handle_collation(relay_parent, para_id, collator_id)
-						.await;
-				}
-				ToJob::CandidateSelection(CandidateSelectionMessage::Invalid(
-					_,
-					candidate_receipt,
-				)) => {
-					self.handle_invalid(candidate_receipt).await;
+		let mut window = future::Fuse::<Delay>::terminated();
+
+		loop {
+			futures::select! {
+				msg = self.receiver.next() => match msg {
+					Some(ToJob::CandidateSelection(CandidateSelectionMessage::Collation(
+						relay_parent,
+						para_id,
+						collator_id,
+						pov_size,
+					))) => {
+						// the first advertisement opens the collection window
+						if !self.window_ended && window.is_terminated() {
+							window = Delay::new(self.collation_window).fuse();
+						}
+
+						self.handle_collation(Advertisement {
+							relay_parent,
+							para_id,
+							collator_id,
+							pov_size,
+						}).await;
+					}
+					Some(ToJob::CandidateSelection(CandidateSelectionMessage::Invalid(
+						_,
+						candidate_receipt,
+					))) => {
+						self.handle_invalid(candidate_receipt).await;
+					}
+					Some(ToJob::Stop) | None => break,
+				},
+				_ = window => {
+					self.window_ended = true;
+					self.select_collation().await;
 				}
-				ToJob::Stop => break,
 			}
 		}
 
@@ -204,62 +254,127 @@ impl CandidateSelectionJob {
 		Ok(())
 	}
 
-	async fn handle_collation(
-		&mut self,
-		relay_parent: Hash,
-		para_id: ParaId,
-		collator_id: CollatorId,
-	) {
-		if self.seconded_candidate.is_none() {
-			let (candidate_receipt, pov) =
-				match get_collation(
-					relay_parent,
-					para_id,
-					collator_id.clone(),
-					self.sender.clone(),
-				).await {
-					Ok(response) => response,
-					Err(err) => {
-						log::warn!(
-							target: TARGET,
-							"failed to get collation from collator protocol subsystem: {:?}",
-							err
-						);
-						return;
-					}
-				};
+	async fn handle_collation(&mut self, advertisement: Advertisement) {
+		if self.advertisements.iter().any(|a| a.collator_id == advertisement.collator_id) {
+			return;
+		}
 
-			let pov = Arc::new(pov);
+		self.advertisements.push(advertisement);
 
-			if !candidate_is_valid(
-				candidate_receipt.descriptor.clone(),
-				pov.clone(),
-				self.sender.clone(),
-			)
-			.await
-			{
-				return;
+		if self.window_ended {
+			self.select_collation().await;
+		}
+	}
+
+	/// Try the advertised collations from the best to the worst, until one of them is seconded.
+	async fn select_collation(&mut self) {
+		if self.seconded_candidate.is_some() || self.advertisements.is_empty() {
+			return;
+		}
+
+		self.rank_advertisements().await;
+
+		while self.seconded_candidate.is_none() {
+			match self.advertisements.pop() {
+				Some(advertisement) => self.try_collation(advertisement).await,
+				None => break,
 			}
+		}
+	}
 
-			let pov = if let Ok(pov) = Arc::try_unwrap(pov) {
-				pov
-			} else {
-				log::warn!(target: TARGET, "Arc unwrapping is expected to succeed, the other fns should have already run to completion by now.");
-				return;
-			};
+	/// Sort the advertisements such that the best one comes last.
+	///
+	/// Collators with a better reputation are preferred. Among equally reputable collators, we
+	/// prefer the smaller PoV, which is quicker to fetch and validate, and then the earlier
+	/// advertisement.
+	async fn rank_advertisements(&mut self) {
+		let collators = self.advertisements.iter().map(|a| a.collator_id.clone()).collect();
+		let scores = match get_collator_scores(collators, &mut self.sender).await {
+			Ok(scores) => scores,
+			Err(err) => {
+				log::warn!(
+					target: TARGET,
+					"failed to get collator scores from peer set manager subsystem: {:?}",
+					err
+				);
+				vec![0; self.advertisements.len()]
+			}
+		};
 
-			match second_candidate(
+		let mut ranked: Vec<_> = scores.into_iter()
+			.zip(self.advertisements.drain(..).enumerate())
+			.collect();
+		ranked.sort_by_key(|(score, (index, advertisement))| {
+			(*score, Reverse(advertisement.pov_size), Reverse(*index))
+		});
+
+		self.advertisements = ranked.into_iter()
+			.map(|(_, (_, advertisement))| advertisement)
+			.collect();
+	}
+
+	/// Fetch, validate and second an advertised collation.
+	///
+	/// Nothing is seconded if the collation can't be fetched in time or turns out to be invalid.
+	async fn try_collation(&mut self, advertisement: Advertisement) {
+		let Advertisement { relay_parent, para_id, collator_id, .. } = advertisement;
+
+		let (candidate_receipt, pov) =
+			match get_collation(
 				relay_parent,
-				candidate_receipt,
-				pov,
-				&mut self.sender,
-				&self.metrics,
-			)
-			.await
-			{
-				Err(err) => log::warn!(target: TARGET, "failed to second a candidate: {:?}", err),
-				Ok(()) => self.seconded_candidate = Some(collator_id),
-			}
+				para_id,
+				collator_id.clone(),
+				self.sender.clone(),
+			).timeout(self.fetch_timeout).await {
+				Some(Ok(response)) => response,
+				Some(Err(err)) => {
+					log::warn!(
+						target: TARGET,
+						"failed to get collation from collator protocol subsystem: {:?}",
+						err
+					);
+					return;
+				}
+				None => {
+					log::debug!(
+						target: TARGET,
+						"fetching the collation of {:?} timed out",
+						collator_id,
+					);
+					return;
+				}
+			};
+
+		let pov = Arc::new(pov);
+
+		if !candidate_is_valid(
+			candidate_receipt.descriptor.clone(),
+			pov.clone(),
+			self.sender.clone(),
+		)
+		.await
+		{
+			return;
+		}
+
+		let pov = if let Ok(pov) = Arc::try_unwrap(pov) {
+			pov
+		} else {
+			log::warn!(target: TARGET, "Arc unwrapping is expected to succeed, the other fns should have already run to completion by now.");
+			return;
+		};
+
+		match second_candidate(
+			relay_parent,
+			candidate_receipt,
+			pov,
+			&mut self.sender,
+			&self.metrics,
+		)
+		.await
+		{
+			Err(err) => log::warn!(target: TARGET, "failed to second a candidate: {:?}", err),
+			Ok(()) => self.seconded_candidate = Some(collator_id),
 		}
 	}
 
@@ -292,6 +407,10 @@ impl CandidateSelectionJob {
 				true
 			};
 		self.metrics.on_invalid_selection(succeeded);
+
+		// fall back to the next best collation, so that the core doesn't stay idle
+		self.seconded_candidate = None;
+		self.select_collation().await;
 	}
 }
 
@@ -316,6 +435,21 @@ async fn get_collation(
 	rx.await.map_err(Into::into)
 }
 
+// get the scores of the collators from the Peer Set Manager subsystem
+async fn get_collator_scores(
+	collators: Vec<CollatorId>,
+	sender: &mut mpsc::Sender<FromJob>,
+) -> Result<Vec<i32>, Error> {
+	let (tx, rx) = oneshot::channel();
+	sender
+		.send(FromJob::PeerSetManager(PeerSetManagerMessage::CollatorScores(
+			collators,
+			tx,
+		)))
+		.await?;
+	rx.await.map_err(Into::into)
+}
+
 // find out whether a candidate is valid or not
 async fn candidate_is_valid(
 	candidate_descriptor: CandidateDescriptor,
@@ -463,6 +597,10 @@ mod tests {
 			sender: from_job_tx,
 			receiver: to_job_rx,
 			metrics: Default::default(),
+			collation_window: Duration::from_millis(50),
+			fetch_timeout: Duration::from_millis(100),
+			advertisements: Vec::new(),
+			window_ended: false,
 			seconded_candidate: None,
 		};
 
@@ -490,13 +628,26 @@ mod tests {
 				parent_head: HeadData(parent_head_data),
 				block_number: 123,
 				hrmp_mqc_heads: Vec::new(),
+				dmq_mqc_head: Default::default(),
 			},
 			upward_messages: Vec::new(),
 			fees: 0,
 			new_validation_code: None,
+			processed_downward_messages: 0,
+			horizontal_messages: Vec::new(),
+			hrmp_watermark: 0,
 		}
 	}
 
+	fn collation(relay_parent: Hash, para_id: ParaId, collator_id: &CollatorId, pov_size: u32) -> ToJob {
+		ToJob::CandidateSelection(CandidateSelectionMessage::Collation(
+			relay_parent,
+			para_id,
+			collator_id.clone(),
+			pov_size,
+		))
+	}
+
 	/// when nothing is seconded so far, the collation is fetched and seconded
 	#[test]
 	fn fetches_and_seconds_a_collation() {
@@ -517,19 +668,20 @@ mod tests {
 			|_job| {},
 			|mut to_job, mut from_job| async move {
 				to_job
-					.send(ToJob::CandidateSelection(
-						CandidateSelectionMessage::Collation(
-							relay_parent,
-							para_id,
-							collator_id_clone.clone(),
-						),
-					))
+					.send(collation(relay_parent, para_id, &collator_id_clone, 256))
 					.await
 					.unwrap();
-				std::mem::drop(to_job);
 
 				while let Some(msg) = from_job.next().await {
 					match msg {
+						FromJob::PeerSetManager(PeerSetManagerMessage::CollatorScores(
+							collators,
+							return_sender,
+						)) => {
+							assert_eq!(collators, vec![collator_id_clone.clone()]);
+
+							return_sender.send(vec![0]).unwrap();
+						}
 						FromJob::Collator(CollatorProtocolMessage::FetchCollation(
 							got_relay_parent,
 							collator_id,
@@ -568,6 +720,7 @@ mod tests {
 							assert_eq!(got_pov, pov);
 
 							*was_seconded_clone.lock().await = true;
+							to_job.send(ToJob::Stop).await.unwrap();
 						}
 						other => panic!("unexpected message from job: {:?}", other),
 					}
@@ -582,6 +735,134 @@ mod tests {
 		assert!(Arc::try_unwrap(was_seconded).unwrap().into_inner());
 	}
 
+	/// advertisements are collected for a while and the best one is seconded
+	#[test]
+	fn prefers_reputable_collators_and_small_povs() {
+		let relay_parent = Hash::random();
+		let para_id: ParaId = 123.into();
+		let fresh_collator = CollatorId::from_slice(&[1; 32]);
+		let large_pov_collator = CollatorId::from_slice(&[2; 32]);
+		let best_collator = CollatorId::from_slice(&[3; 32]);
+		let best_collator_clone = best_collator.clone();
+
+		test_harness(
+			|_job| {},
+			|mut to_job, mut from_job| async move {
+				to_job.send(collation(relay_parent, para_id, &fresh_collator, 100)).await.unwrap();
+				to_job.send(collation(relay_parent, para_id, &large_pov_collator, 1000)).await.unwrap();
+				to_job.send(collation(relay_parent, para_id, &best_collator, 500)).await.unwrap();
+
+				while let Some(msg) = from_job.next().await {
+					match msg {
+						FromJob::PeerSetManager(PeerSetManagerMessage::CollatorScores(
+							collators,
+							return_sender,
+						)) => {
+							assert_eq!(
+								collators,
+								vec![fresh_collator.clone(), large_pov_collator.clone(), best_collator.clone()],
+							);
+
+							return_sender.send(vec![0, 100, 100]).unwrap();
+						}
+						FromJob::Collator(CollatorProtocolMessage::FetchCollation(
+							_,
+							collator_id,
+							_,
+							return_sender,
+						)) => {
+							assert_eq!(collator_id, best_collator);
+
+							return_sender
+								.send((CandidateReceipt::default(), PoV { block_data: BlockData(vec![]) }))
+								.unwrap();
+						}
+						FromJob::Validation(
+							CandidateValidationMessage::ValidateFromChainState(_, _, return_sender),
+						) => {
+							return_sender
+								.send(Ok(ValidationResult::Valid(default_validation_outputs())))
+								.unwrap();
+						}
+						FromJob::Backing(CandidateBackingMessage::Second(..)) => {
+							to_job.send(ToJob::Stop).await.unwrap();
+						}
+						other => panic!("unexpected message from job: {:?}", other),
+					}
+				}
+			},
+			|job, job_result| {
+				assert!(job_result.is_ok());
+				assert_eq!(job.seconded_candidate.unwrap(), best_collator_clone);
+				assert_eq!(job.advertisements.len(), 2);
+			},
+		);
+	}
+
+	/// when fetching the best collation times out, the next best one is seconded instead
+	#[test]
+	fn falls_back_to_the_next_collator_on_fetch_timeout() {
+		let relay_parent = Hash::random();
+		let para_id: ParaId = 123.into();
+		let slow_collator = CollatorId::from_slice(&[1; 32]);
+		let other_collator = CollatorId::from_slice(&[2; 32]);
+		let other_collator_clone = other_collator.clone();
+
+		test_harness(
+			|_job| {},
+			|mut to_job, mut from_job| async move {
+				to_job.send(collation(relay_parent, para_id, &slow_collator, 100)).await.unwrap();
+				to_job.send(collation(relay_parent, para_id, &other_collator, 100)).await.unwrap();
+
+				// keep the request to the slow collator pending
+				let mut pending = Vec::new();
+
+				while let Some(msg) = from_job.next().await {
+					match msg {
+						FromJob::PeerSetManager(PeerSetManagerMessage::CollatorScores(
+							_,
+							return_sender,
+						)) => {
+							return_sender.send(vec![100, 0]).unwrap();
+						}
+						FromJob::Collator(CollatorProtocolMessage::FetchCollation(
+							_,
+							collator_id,
+							_,
+							return_sender,
+						)) => {
+							if collator_id == slow_collator {
+								pending.push(return_sender);
+							} else {
+								assert_eq!(pending.len(), 1);
+								assert_eq!(collator_id, other_collator);
+
+								return_sender
+									.send((CandidateReceipt::default(), PoV { block_data: BlockData(vec![]) }))
+									.unwrap();
+							}
+						}
+						FromJob::Validation(
+							CandidateValidationMessage::ValidateFromChainState(_, _, return_sender),
+						) => {
+							return_sender
+								.send(Ok(ValidationResult::Valid(default_validation_outputs())))
+								.unwrap();
+						}
+						FromJob::Backing(CandidateBackingMessage::Second(..)) => {
+							to_job.send(ToJob::Stop).await.unwrap();
+						}
+						other => panic!("unexpected message from job: {:?}", other),
+					}
+				}
+			},
+			|job, job_result| {
+				assert!(job_result.is_ok());
+				assert_eq!(job.seconded_candidate.unwrap(), other_collator_clone);
+			},
+		);
+	}
+
 	/// when something has been seconded, further collation notifications are ignored
 	#[test]
 	fn ignores_collation_notifications_after_the_first() {
@@ -595,16 +876,13 @@ mod tests {
 		let was_seconded_clone = was_seconded.clone();
 
 		test_harness(
-			|job| job.seconded_candidate = Some(prev_collator_id.clone()),
+			|job| {
+				job.window_ended = true;
+				job.seconded_candidate = Some(prev_collator_id.clone());
+			},
 			|mut to_job, mut from_job| async move {
 				to_job
-					.send(ToJob::CandidateSelection(
-						CandidateSelectionMessage::Collation(
-							relay_parent,
-							para_id,
-							collator_id_clone,
-						),
-					))
+					.send(collation(relay_parent, para_id, &collator_id_clone, 256))
 					.await
 					.unwrap();
 				std::mem::drop(to_job);
@@ -669,10 +947,87 @@ mod tests {
 			},
 			|job, job_result| {
 				assert!(job_result.is_ok());
-				assert_eq!(job.seconded_candidate.unwrap(), collator_id);
+				assert!(job.seconded_candidate.is_none());
 			},
 		);
 
 		assert!(Arc::try_unwrap(sent_report).unwrap().into_inner());
 	}
+
+	/// when the seconded candidate turns out invalid, the next best collation is seconded
+	#[test]
+	fn falls_back_to_the_next_collator_on_invalidity() {
+		let relay_parent = Hash::random();
+		let para_id: ParaId = 123.into();
+		let bad_collator = CollatorId::from_slice(&[1; 32]);
+		let next_collator = CollatorId::from_slice(&[2; 32]);
+		let next_collator_clone = next_collator.clone();
+
+		test_harness(
+			|job| {
+				job.window_ended = true;
+				job.seconded_candidate = Some(bad_collator.clone());
+				job.advertisements.push(Advertisement {
+					relay_parent,
+					para_id,
+					collator_id: next_collator.clone(),
+					pov_size: 100,
+				});
+			},
+			|mut to_job, mut from_job| async move {
+				to_job
+					.send(ToJob::CandidateSelection(
+						CandidateSelectionMessage::Invalid(relay_parent, CandidateReceipt::default()),
+					))
+					.await
+					.unwrap();
+
+				match from_job.next().await {
+					Some(FromJob::Collator(CollatorProtocolMessage::ReportCollator(_))) => {}
+					other => panic!("unexpected message from job: {:?}", other),
+				}
+
+				while let Some(msg) = from_job.next().await {
+					match msg {
+						FromJob::PeerSetManager(PeerSetManagerMessage::CollatorScores(
+							collators,
+							return_sender,
+						)) => {
+							assert_eq!(collators, vec![next_collator_clone.clone()]);
+
+							return_sender.send(vec![0]).unwrap();
+						}
+						FromJob::Collator(CollatorProtocolMessage::FetchCollation(
+							got_relay_parent,
+							collator_id,
+							_,
+							return_sender,
+						)) => {
+							assert_eq!(got_relay_parent, relay_parent);
+							assert_eq!(collator_id, next_collator_clone);
+
+							return_sender
+								.send((CandidateReceipt::default(), PoV { block_data: BlockData(vec![]) }))
+								.unwrap();
+						}
+						FromJob::Validation(
+							CandidateValidationMessage::ValidateFromChainState(_, _, return_sender),
+						) => {
+							return_sender
+								.send(Ok(ValidationResult::Valid(default_validation_outputs())))
+								.unwrap();
+						}
+						FromJob::Backing(CandidateBackingMessage::Second(..)) => {
+							to_job.send(ToJob::Stop).await.unwrap();
+						}
+						other => panic!("unexpected message from job: {:?}", other),
+					}
+				}
+			},
+			|job, job_result| {
+				assert!(job_result.is_ok());
+				assert_eq!(job.seconded_candidate.unwrap(), next_collator);
+			},
+		);
+	}
 }
@@ -19,9 +19,15 @@
 //! This handles incoming requests from other subsystems to validate candidates
 //! according to a validation function. This delegates validation to an underlying
 //! pool of processes used for execution of the Wasm.
+//!
+//! The processes execute the Wasm from an on-disk cache of prepared PVF artifacts: validation code
+//! which has been checked, instrumented with the execution limits and compiled. Artifacts of new
+//! validation code are prepared ahead of time, as soon as an upgrade of the code of a para is
+//! scheduled in the state of an active leaf or a candidate upgrading the code is found valid, and
+//! artifacts which are no longer referenced are pruned on finality.
 
 use polkadot_subsystem::{
-	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult, SubsystemError,
 	FromOverseer, OverseerSignal,
 	messages::{
 		AllMessages, CandidateValidationMessage, RuntimeApiMessage,
@@ -33,33 +39,55 @@ use polkadot_subsystem::errors::RuntimeApiError;
 use polkadot_node_primitives::{ValidationResult, ValidationOutputs, InvalidCandidate};
 use polkadot_primitives::v1::{
 	ValidationCode, PoV, CandidateDescriptor, ValidationData, PersistedValidationData,
-	TransientValidationData, OccupiedCoreAssumption, Hash,
+	TransientValidationData, OccupiedCoreAssumption, Hash, CoreState, ExecutionLimits,
+	ExecutionTimeouts,
 };
 use polkadot_parachain::wasm_executor::{
-	self, ValidationPool, ExecutionMode, ValidationError,
+	self, ValidationPool, ExecutionMode, ValidationError, ArtifactCache,
 	InvalidCandidate as WasmInvalidCandidate,
 };
 use polkadot_parachain::primitives::{ValidationResult as WasmValidationResult, ValidationParams};
 
 use parity_scale_codec::Encode;
-use sp_core::traits::SpawnNamed;
+use sp_core::{hashing::blake2_256, traits::SpawnNamed};
 
 use futures::channel::oneshot;
 use futures::prelude::*;
 
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 const LOG_TARGET: &'static str = "candidate_validation";
 
+/// The minimum interval between two prunings of the artifact cache.
+const ARTIFACT_PRUNING_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How long an artifact is kept after it was last used or prepared, even if the code is not
+/// the current code of any para. This keeps the artifacts of upgrades which are not applied yet.
+const ARTIFACT_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Configuration for the candidate validation subsystem.
+#[derive(Clone, Debug)]
+pub struct Config {
+	/// The directory the prepared PVF artifacts are kept in.
+	pub artifact_cache_path: PathBuf,
+	/// The number of workers validating candidates in parallel, or `None` for one per CPU.
+	pub validation_workers: Option<usize>,
+}
+
 /// The candidate validation subsystem.
 pub struct CandidateValidationSubsystem<S> {
 	spawn: S,
+	config: Config,
 	metrics: Metrics,
 }
 
 #[derive(Clone)]
 struct MetricsInner {
 	validation_requests: prometheus::CounterVec<prometheus::U64>,
+	artifact_cache_lookups: prometheus::CounterVec<prometheus::U64>,
+	pruned_artifacts: prometheus::Counter<prometheus::U64>,
 }
 
 /// Candidate validation metrics.
@@ -82,6 +110,19 @@ impl Metrics {
 			}
 		}
 	}
+
+	fn on_artifact_cache_lookup(&self, hit: bool) {
+		if let Some(metrics) = &self.0 {
+			let label = if hit { "hit" } else { "miss" };
+			metrics.artifact_cache_lookups.with_label_values(&[label]).inc();
+		}
+	}
+
+	fn on_artifacts_pruned(&self, count: usize) {
+		if let Some(metrics) = &self.0 {
+			metrics.pruned_artifacts.inc_by(count as u64);
+		}
+	}
 }
 
 impl metrics::Metrics for Metrics {
@@ -97,15 +138,32 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			artifact_cache_lookups: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"parachain_pvf_artifact_cache_lookups_total",
+						"Number of lookups of PVF artifacts, by whether the artifact was prepared already.",
+					),
+					&["outcome"],
+				)?,
+				registry,
+			)?,
+			pruned_artifacts: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_pvf_artifacts_pruned_total",
+					"Number of PVF artifacts pruned from the cache.",
+				)?,
+				registry,
+			)?,
 		};
 		Ok(Metrics(Some(metrics)))
 	}
 }
 
 impl<S> CandidateValidationSubsystem<S> {
-	/// Create a new `CandidateValidationSubsystem` with the given task spawner.
-	pub fn new(spawn: S, metrics: Metrics) -> Self {
-		CandidateValidationSubsystem { spawn, metrics }
+	/// Create a new `CandidateValidationSubsystem` with the given task spawner and configuration.
+	pub fn new(spawn: S, config: Config, metrics: Metrics) -> Self {
+		CandidateValidationSubsystem { spawn, config, metrics }
 	}
 }
 
@@ -118,24 +176,97 @@ impl<S, C> Subsystem<C> for CandidateValidationSubsystem<S> where
 	fn start(self, ctx: C) -> SpawnedSubsystem {
 		SpawnedSubsystem {
 			name: "candidate-validation-subsystem",
-			future: run(ctx, self.spawn, self.metrics).map(|_| ()).boxed(),
+			future: run(ctx, self.spawn, self.config, self.metrics).map(|_| ()).boxed(),
 		}
 	}
 }
 
+/// Keeps track of the PVF artifacts which were recently used or prepared, and of when the
+/// artifact cache was last pruned.
+struct Artifacts {
+	cache: ArtifactCache,
+	recently_used: HashMap<Hash, Instant>,
+	last_pruned: Instant,
+}
+
+impl Artifacts {
+	fn new(cache: ArtifactCache) -> Self {
+		Artifacts {
+			cache,
+			recently_used: HashMap::new(),
+			// give the artifacts prepared since the start the chance to be noted as used before
+			// pruning for the first time.
+			last_pruned: Instant::now(),
+		}
+	}
+
+	fn note_used(&mut self, code_hash: Hash) {
+		self.recently_used.insert(code_hash, Instant::now());
+	}
+
+	/// Whether the cache is due to be pruned, noting it as pruned now if so.
+	fn due_for_pruning(&mut self, now: Instant) -> bool {
+		let due = now.duration_since(self.last_pruned) >= ARTIFACT_PRUNING_INTERVAL;
+		if due {
+			self.last_pruned = now;
+		}
+
+		due
+	}
+
+	/// The hashes of all code whose artifacts were used or prepared within the retention period.
+	fn retained(&mut self, now: Instant) -> HashSet<Hash> {
+		self.recently_used.retain(|_, used| now.duration_since(*used) < ARTIFACT_RETENTION);
+		self.recently_used.keys().cloned().collect()
+	}
+}
+
 async fn run(
 	mut ctx: impl SubsystemContext<Message = CandidateValidationMessage>,
 	spawn: impl SpawnNamed + Clone + 'static,
+	config: Config,
 	metrics: Metrics,
 )
 	-> SubsystemResult<()>
 {
-	let execution_mode = ExecutionMode::ExternalProcessSelfHost(ValidationPool::new());
+	let artifact_cache = match ArtifactCache::new(config.artifact_cache_path) {
+		Ok(artifact_cache) => artifact_cache,
+		Err(e) => {
+			log::error!(target: LOG_TARGET, "Failed to open the PVF artifact cache: {:?}", e);
+			return Err(SubsystemError);
+		}
+	};
+	// the artifacts are compiled, and those compiled by another version of the node cannot be
+	// loaded.
+	if let Err(e) = artifact_cache.prune(|_| false) {
+		log::warn!(target: LOG_TARGET, "Failed to purge the PVF artifact cache: {:?}", e);
+	}
+	let pool = match config.validation_workers {
+		Some(num_workers) => ValidationPool::with_num_workers(artifact_cache.clone(), num_workers),
+		None => ValidationPool::with_artifact_cache(artifact_cache.clone()),
+	};
+	let execution_mode = ExecutionMode::ExternalProcessSelfHost(pool);
+	let mut artifacts = Artifacts::new(artifact_cache);
 
 	loop {
 		match ctx.recv().await? {
-			FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {}
-			FromOverseer::Signal(OverseerSignal::BlockFinalized(_)) => {}
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(update)) => {
+				for leaf in update.activated {
+					let codes = scheduled_upgrades(&mut ctx, leaf).await?;
+					spawn_prepare_artifacts(&mut ctx, &mut artifacts, leaf, codes).await?;
+				}
+			}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(hash)) => {
+				let now = Instant::now();
+				if artifacts.due_for_pruning(now) {
+					if let Some(referenced) = referenced_code_hashes(&mut ctx, hash).await? {
+						let mut keep = artifacts.retained(now);
+						keep.extend(referenced);
+
+						spawn_prune_artifacts(&mut ctx, artifacts.cache.clone(), keep, metrics.clone()).await?;
+					}
+				}
+			}
 			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
 			FromOverseer::Communication { msg } => match msg {
 				CandidateValidationMessage::ValidateFromChainState(
@@ -143,17 +274,21 @@ async fn run(
 					pov,
 					response_sender,
 				) => {
+					let relay_parent = descriptor.relay_parent;
 					let res = spawn_validate_from_chain_state(
 						&mut ctx,
 						execution_mode.clone(),
+						&mut artifacts,
 						descriptor,
 						pov,
 						spawn.clone(),
+						&metrics,
 					).await;
 
 					match res {
 						Ok(x) => {
 							metrics.on_validation_event(&x);
+							spawn_prepare_new_validation_code(&mut ctx, &mut artifacts, relay_parent, &x).await?;
 							let _ = response_sender.send(x);
 						}
 						Err(e) => return Err(e),
@@ -165,22 +300,28 @@ async fn run(
 					validation_code,
 					descriptor,
 					pov,
+					timeout,
 					response_sender,
 				) => {
+					let relay_parent = descriptor.relay_parent;
 					let res = spawn_validate_exhaustive(
 						&mut ctx,
 						execution_mode.clone(),
+						&mut artifacts,
 						persisted_validation_data,
 						transient_validation_data,
 						validation_code,
 						descriptor,
 						pov,
+						timeout,
 						spawn.clone(),
+						&metrics,
 					).await;
 
 					match res {
 						Ok(x) => {
 							metrics.on_validation_event(&x);
+							spawn_prepare_new_validation_code(&mut ctx, &mut artifacts, relay_parent, &x).await?;
 							if let Err(_e) = response_sender.send(x) {
 								log::warn!(
 									target: LOG_TARGET,
@@ -196,6 +337,177 @@ async fn run(
 	}
 }
 
+/// Prepare the artifact of the new validation code of a valid candidate ahead of time, so that
+/// it is ready once the code upgrade of the para is applied.
+///
+/// The artifact is prepared for the execution limits as of the relay-parent of the candidate.
+async fn spawn_prepare_new_validation_code(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	artifacts: &mut Artifacts,
+	relay_parent: Hash,
+	result: &Result<ValidationResult, ValidationFailed>,
+) -> SubsystemResult<()> {
+	let code = match result {
+		Ok(ValidationResult::Valid(ValidationOutputs { new_validation_code: Some(code), .. })) => code.clone(),
+		_ => return Ok(()),
+	};
+
+	spawn_prepare_artifacts(ctx, artifacts, relay_parent, vec![code]).await
+}
+
+/// Fetch the validation code which the paras assigned to an availability core are scheduled to
+/// upgrade to as of the given block.
+///
+/// Paras whose code can't be fetched are skipped.
+async fn scheduled_upgrades(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	block_hash: Hash,
+) -> SubsystemResult<Vec<ValidationCode>> {
+	let (tx, rx) = oneshot::channel();
+	let cores = match runtime_api_request(ctx, block_hash, RuntimeApiRequest::AvailabilityCores(tx), rx).await? {
+		Ok(cores) => cores,
+		Err(e) => {
+			log::debug!(target: LOG_TARGET, "Failed to fetch the availability cores: {:?}", e);
+			return Ok(Vec::new());
+		}
+	};
+
+	let mut codes = Vec::new();
+	for core in cores {
+		let para_id = match core {
+			CoreState::Occupied(core) => core.para_id,
+			CoreState::Scheduled(core) => core.para_id,
+			CoreState::Free => continue,
+		};
+
+		let (tx, rx) = oneshot::channel();
+		let request = RuntimeApiRequest::FutureValidationCode(para_id, tx);
+		match runtime_api_request(ctx, block_hash, request, rx).await? {
+			Ok(Some(code)) => codes.push(code),
+			Ok(None) => {}
+			Err(e) => {
+				log::debug!(
+					target: LOG_TARGET,
+					"Failed to fetch the future validation code of {:?}: {:?}",
+					para_id,
+					e,
+				);
+			}
+		}
+	}
+
+	Ok(codes)
+}
+
+/// Prepare the artifacts of the given validation code ahead of time, noting them as used so
+/// that they are retained until the code is referenced by a para.
+///
+/// The artifacts are prepared for the execution limits as of the given relay-parent.
+async fn spawn_prepare_artifacts(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	artifacts: &mut Artifacts,
+	relay_parent: Hash,
+	codes: Vec<ValidationCode>,
+) -> SubsystemResult<()> {
+	if codes.is_empty() {
+		return Ok(());
+	}
+
+	for code in &codes {
+		artifacts.note_used(Hash::from(blake2_256(&code.0)));
+	}
+
+	let limits = match request_execution_limits(ctx, relay_parent).await? {
+		Ok(limits) => limits,
+		Err(e) => {
+			log::debug!(target: LOG_TARGET, "Failed to fetch the execution limits: {:?}", e);
+			return Ok(());
+		}
+	};
+
+	let cache = artifacts.cache.clone();
+	let fut = async move {
+		for code in codes {
+			let code_hash = Hash::from(blake2_256(&code.0));
+			if cache.contains(&code_hash, &limits) {
+				continue;
+			}
+
+			if let Err(e) = cache.prepare(&code.0, &limits) {
+				log::warn!(
+					target: LOG_TARGET,
+					"Failed to prepare the artifact of new validation code {}: {:?}",
+					code_hash,
+					e,
+				);
+			}
+		}
+	};
+
+	ctx.spawn_blocking("pvf-artifact-preparation", fut.boxed()).await
+}
+
+/// Fetch the hashes of the current validation code of all paras assigned to an availability core
+/// as of the given block.
+///
+/// Returns `None` if any of the runtime API requests fails.
+async fn referenced_code_hashes(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	block_hash: Hash,
+) -> SubsystemResult<Option<HashSet<Hash>>> {
+	let (tx, rx) = oneshot::channel();
+	let cores = match runtime_api_request(ctx, block_hash, RuntimeApiRequest::AvailabilityCores(tx), rx).await? {
+		Ok(cores) => cores,
+		Err(e) => {
+			log::debug!(target: LOG_TARGET, "Failed to fetch the availability cores: {:?}", e);
+			return Ok(None);
+		}
+	};
+
+	let mut code_hashes = HashSet::new();
+	for core in cores {
+		let (para_id, assumption) = match core {
+			CoreState::Occupied(core) => (core.para_id, OccupiedCoreAssumption::TimedOut),
+			CoreState::Scheduled(core) => (core.para_id, OccupiedCoreAssumption::Free),
+			CoreState::Free => continue,
+		};
+
+		let (tx, rx) = oneshot::channel();
+		let request = RuntimeApiRequest::ValidationCode(para_id, assumption, tx);
+		match runtime_api_request(ctx, block_hash, request, rx).await? {
+			Ok(Some(code)) => {
+				code_hashes.insert(Hash::from(blake2_256(&code.0)));
+			}
+			Ok(None) => {}
+			Err(e) => {
+				log::debug!(target: LOG_TARGET, "Failed to fetch the validation code of {:?}: {:?}", para_id, e);
+				return Ok(None);
+			}
+		}
+	}
+
+	Ok(Some(code_hashes))
+}
+
+async fn spawn_prune_artifacts(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	cache: ArtifactCache,
+	keep: HashSet<Hash>,
+	metrics: Metrics,
+) -> SubsystemResult<()> {
+	let fut = async move {
+		match cache.prune(|code_hash| keep.contains(code_hash)) {
+			Ok(pruned) => {
+				log::debug!(target: LOG_TARGET, "Pruned {} PVF artifacts", pruned.len());
+				metrics.on_artifacts_pruned(pruned.len());
+			}
+			Err(e) => log::warn!(target: LOG_TARGET, "Failed to prune PVF artifacts: {:?}", e),
+		}
+	};
+
+	ctx.spawn_blocking("pvf-artifact-pruning", fut.boxed()).await
+}
+
 async fn runtime_api_request<T>(
 	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
 	relay_parent: Hash,
@@ -212,6 +524,22 @@ async fn runtime_api_request<T>(
 	receiver.await.map_err(Into::into)
 }
 
+async fn request_execution_limits(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	relay_parent: Hash,
+) -> SubsystemResult<Result<ExecutionLimits, RuntimeApiError>> {
+	let (tx, rx) = oneshot::channel();
+	runtime_api_request(ctx, relay_parent, RuntimeApiRequest::ExecutionLimits(tx), rx).await
+}
+
+async fn request_execution_timeouts(
+	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
+	relay_parent: Hash,
+) -> SubsystemResult<Result<ExecutionTimeouts, RuntimeApiError>> {
+	let (tx, rx) = oneshot::channel();
+	runtime_api_request(ctx, relay_parent, RuntimeApiRequest::ExecutionTimeouts(tx), rx).await
+}
+
 #[derive(Debug)]
 enum AssumptionCheckOutcome {
 	Matches(ValidationData, ValidationCode),
@@ -272,9 +600,11 @@ async fn check_assumption_validation_data(
 async fn spawn_validate_from_chain_state(
 	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
 	execution_mode: ExecutionMode,
+	artifacts: &mut Artifacts,
 	descriptor: CandidateDescriptor,
 	pov: Arc<PoV>,
 	spawn: impl SpawnNamed + 'static,
+	metrics: &Metrics,
 ) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
 	// The candidate descriptor has a `persisted_validation_data_hash` which corresponds to
 	// one of up to two possible values that we can derive from the state of the
@@ -289,12 +619,15 @@ async fn spawn_validate_from_chain_state(
 			return spawn_validate_exhaustive(
 				ctx,
 				execution_mode,
+				artifacts,
 				validation_data.persisted,
 				Some(validation_data.transient),
 				validation_code,
 				descriptor,
 				pov,
+				None,
 				spawn,
+				metrics,
 			).await;
 		}
 		AssumptionCheckOutcome::DoesNotMatch => {},
@@ -310,12 +643,15 @@ async fn spawn_validate_from_chain_state(
 			return spawn_validate_exhaustive(
 				ctx,
 				execution_mode,
+				artifacts,
 				validation_data.persisted,
 				Some(validation_data.transient),
 				validation_code,
 				descriptor,
 				pov,
+				None,
 				spawn,
+				metrics,
 			).await;
 		}
 		AssumptionCheckOutcome::DoesNotMatch => {},
@@ -331,17 +667,42 @@ async fn spawn_validate_from_chain_state(
 async fn spawn_validate_exhaustive(
 	ctx: &mut impl SubsystemContext<Message = CandidateValidationMessage>,
 	execution_mode: ExecutionMode,
+	artifacts: &mut Artifacts,
 	persisted_validation_data: PersistedValidationData,
 	transient_validation_data: Option<TransientValidationData>,
 	validation_code: ValidationCode,
 	descriptor: CandidateDescriptor,
 	pov: Arc<PoV>,
+	timeout: Option<Duration>,
 	spawn: impl SpawnNamed + 'static,
+	metrics: &Metrics,
 ) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	// all validators execute the candidate under the limits as of its relay-parent.
+	let limits = match request_execution_limits(ctx, descriptor.relay_parent).await? {
+		Ok(limits) => limits,
+		Err(e) => return Ok(Err(ValidationFailed(format!("Failed to fetch the execution limits: {:?}", e)))),
+	};
+
+	let timeout = match timeout {
+		Some(timeout) => timeout,
+		None => match request_execution_timeouts(ctx, descriptor.relay_parent).await? {
+			Ok(timeouts) => Duration::from_millis(timeouts.backing as u64),
+			Err(e) => return Ok(Err(ValidationFailed(format!("Failed to fetch the execution timeouts: {:?}", e)))),
+		},
+	};
+
+	let code_hash = Hash::from(blake2_256(&validation_code.0));
+	artifacts.note_used(code_hash);
+
+	let artifact_cache = artifacts.cache.clone();
+	let metrics = metrics.clone();
 	let (tx, rx) = oneshot::channel();
 	let fut = async move {
+		// the artifact is prepared by the validation pool on a miss.
+		metrics.on_artifact_cache_lookup(artifact_cache.contains(&code_hash, &limits));
+
 		let res = validate_candidate_exhaustive::<RealValidationBackend, _>(
-			execution_mode,
+			(execution_mode, limits, timeout),
 			persisted_validation_data,
 			transient_validation_data,
 			validation_code,
@@ -422,10 +783,10 @@ trait ValidationBackend {
 struct RealValidationBackend;
 
 impl ValidationBackend for RealValidationBackend {
-	type Arg = ExecutionMode;
+	type Arg = (ExecutionMode, ExecutionLimits, Duration);
 
 	fn validate<S: SpawnNamed + 'static>(
-		execution_mode: ExecutionMode,
+		(execution_mode, limits, timeout): (ExecutionMode, ExecutionLimits, Duration),
 		validation_code: &ValidationCode,
 		params: ValidationParams,
 		spawn: S,
@@ -433,6 +794,8 @@ impl ValidationBackend for RealValidationBackend {
 		wasm_executor::validate_candidate(
 			&validation_code.0,
 			params,
+			&limits,
+			timeout,
 			&execution_mode,
 			spawn,
 		)
@@ -460,6 +823,7 @@ fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
 		block_data: pov.block_data.clone(),
 		relay_chain_height: persisted_validation_data.block_number,
 		hrmp_mqc_heads: persisted_validation_data.hrmp_mqc_heads.clone(),
+		dmq_mqc_head: persisted_validation_data.dmq_mqc_head,
 	};
 
 	match B::validate(backend_arg, &validation_code, params, spawn) {
@@ -475,6 +839,14 @@ fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
 			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e.to_string()))),
 		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::ExternalWasmExecutor(e))) =>
 			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e.to_string()))),
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::InvalidCode(e))) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e))),
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::MemoryLimitExceeded)) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::MemoryLimitExceeded)),
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::StackLimitExceeded)) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::StackLimitExceeded)),
+		Err(ValidationError::InvalidCandidate(WasmInvalidCandidate::FuelExhausted)) =>
+			Ok(ValidationResult::Invalid(InvalidCandidate::FuelExhausted)),
 		Err(ValidationError::Internal(e)) => Err(ValidationFailed(e.to_string())),
 		Ok(res) => {
 			let post_check_result = if let Some(transient) = transient_validation_data {
@@ -493,6 +865,9 @@ fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
 					upward_messages: res.upward_messages,
 					fees: 0,
 					new_validation_code: res.new_validation_code,
+					processed_downward_messages: res.processed_downward_messages,
+					horizontal_messages: res.horizontal_messages,
+					hrmp_watermark: res.hrmp_watermark,
 				}),
 				Err(e) => ValidationResult::Invalid(e),
 			})
@@ -504,7 +879,7 @@ fn validate_candidate_exhaustive<B: ValidationBackend, S: SpawnNamed + 'static>(
 mod tests {
 	use super::*;
 	use polkadot_node_subsystem_test_helpers as test_helpers;
-	use polkadot_primitives::v1::{HeadData, BlockData};
+	use polkadot_primitives::v1::{HeadData, BlockData, OccupiedCore, ScheduledCore};
 	use sp_core::testing::TaskExecutor;
 	use futures::executor;
 	use assert_matches::assert_matches;
@@ -662,6 +1037,150 @@ mod tests {
 		executor::block_on(test_fut);
 	}
 
+	#[test]
+	fn referenced_code_hashes_are_fetched_for_occupied_and_scheduled_cores() {
+		let relay_parent = [2; 32].into();
+		let occupied_para = 5.into();
+		let scheduled_para = 6.into();
+		let occupied_code: ValidationCode = vec![1, 2, 3].into();
+		let scheduled_code: ValidationCode = vec![4, 5, 6].into();
+
+		let cores = vec![
+			CoreState::Occupied(OccupiedCore {
+				para_id: occupied_para,
+				next_up_on_available: None,
+				occupied_since: 1,
+				time_out_at: 10,
+				next_up_on_time_out: None,
+				availability: Default::default(),
+				group_responsible: Default::default(),
+			}),
+			CoreState::Scheduled(ScheduledCore { para_id: scheduled_para, collator: None }),
+			CoreState::Free,
+		];
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (fetch_fut, fetch_result) = referenced_code_hashes(&mut ctx, relay_parent).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::AvailabilityCores(tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					let _ = tx.send(Ok(cores));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::TimedOut, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, occupied_para);
+					let _ = tx.send(Ok(Some(occupied_code.clone())));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::ValidationCode(p, OccupiedCoreAssumption::Free, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, scheduled_para);
+					let _ = tx.send(Ok(Some(scheduled_code.clone())));
+				}
+			);
+
+			let expected: HashSet<Hash> = vec![
+				Hash::from(blake2_256(&occupied_code.0)),
+				Hash::from(blake2_256(&scheduled_code.0)),
+			].into_iter().collect();
+
+			assert_eq!(fetch_result.await.unwrap(), Some(expected));
+		};
+
+		let test_fut = future::join(test_fut, fetch_fut);
+		executor::block_on(test_fut);
+	}
+
+	#[test]
+	fn scheduled_upgrades_are_fetched_for_occupied_and_scheduled_cores() {
+		let relay_parent = [2; 32].into();
+		let occupied_para = 5.into();
+		let scheduled_para = 6.into();
+		let future_code: ValidationCode = vec![1, 2, 3].into();
+
+		let cores = vec![
+			CoreState::Occupied(OccupiedCore {
+				para_id: occupied_para,
+				next_up_on_available: None,
+				occupied_since: 1,
+				time_out_at: 10,
+				next_up_on_time_out: None,
+				availability: Default::default(),
+				group_responsible: Default::default(),
+			}),
+			CoreState::Scheduled(ScheduledCore { para_id: scheduled_para, collator: None }),
+			CoreState::Free,
+		];
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut ctx_handle) = test_helpers::make_subsystem_context(pool.clone());
+
+		let (fetch_fut, fetch_result) = scheduled_upgrades(&mut ctx, relay_parent).remote_handle();
+
+		let test_fut = async move {
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::AvailabilityCores(tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					let _ = tx.send(Ok(cores));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::FutureValidationCode(p, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, occupied_para);
+					let _ = tx.send(Ok(None));
+				}
+			);
+
+			assert_matches!(
+				ctx_handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					rp,
+					RuntimeApiRequest::FutureValidationCode(p, tx)
+				)) => {
+					assert_eq!(rp, relay_parent);
+					assert_eq!(p, scheduled_para);
+					let _ = tx.send(Ok(Some(future_code.clone())));
+				}
+			);
+
+			assert_eq!(fetch_result.await.unwrap(), vec![future_code]);
+		};
+
+		let test_fut = future::join(test_fut, fetch_fut);
+		executor::block_on(test_fut);
+	}
+
 	#[test]
 	fn check_is_bad_request_if_no_validation_data() {
 		let validation_data: ValidationData = Default::default();
@@ -820,6 +1339,8 @@ mod tests {
 			new_validation_code: Some(vec![2, 2, 2].into()),
 			upward_messages: Vec::new(),
 			processed_downward_messages: 0,
+			horizontal_messages: Vec::new(),
+			hrmp_watermark: 0,
 		};
 
 		assert!(check_wasm_result_against_constraints(
@@ -867,6 +1388,8 @@ mod tests {
 			new_validation_code: Some(vec![2, 2, 2].into()),
 			upward_messages: Vec::new(),
 			processed_downward_messages: 0,
+			horizontal_messages: Vec::new(),
+			hrmp_watermark: 0,
 		};
 
 		assert!(check_wasm_result_against_constraints(
@@ -891,6 +1414,39 @@ mod tests {
 		assert_matches!(v, ValidationResult::Invalid(InvalidCandidate::BadReturn));
 	}
 
+	#[test]
+	fn candidate_validation_exceeding_limits_is_invalid() {
+		let validation_data: ValidationData = Default::default();
+		let pov = PoV { block_data: BlockData(vec![1; 32]) };
+
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov.hash();
+		collator_sign(&mut descriptor, Sr25519Keyring::Alice);
+
+		let validate = |err| validate_candidate_exhaustive::<MockValidationBackend, _>(
+			MockValidationArg { result: Err(ValidationError::InvalidCandidate(err)) },
+			validation_data.persisted.clone(),
+			None,
+			vec![1, 2, 3].into(),
+			descriptor.clone(),
+			Arc::new(pov.clone()),
+			TaskExecutor::new(),
+		).unwrap();
+
+		assert_matches!(
+			validate(WasmInvalidCandidate::MemoryLimitExceeded),
+			ValidationResult::Invalid(InvalidCandidate::MemoryLimitExceeded)
+		);
+		assert_matches!(
+			validate(WasmInvalidCandidate::StackLimitExceeded),
+			ValidationResult::Invalid(InvalidCandidate::StackLimitExceeded)
+		);
+		assert_matches!(
+			validate(WasmInvalidCandidate::FuelExhausted),
+			ValidationResult::Invalid(InvalidCandidate::FuelExhausted)
+		);
+	}
+
 
 	#[test]
 	fn candidate_validation_timeout_is_internal_error() {
@@ -913,6 +1469,8 @@ mod tests {
 			new_validation_code: Some(vec![2, 2, 2].into()),
 			upward_messages: Vec::new(),
 			processed_downward_messages: 0,
+			horizontal_messages: Vec::new(),
+			hrmp_watermark: 0,
 		};
 
 		assert!(check_wasm_result_against_constraints(
@@ -956,6 +1514,8 @@ mod tests {
 			new_validation_code: Some(vec![2, 2, 2].into()),
 			upward_messages: Vec::new(),
 			processed_downward_messages: 0,
+			horizontal_messages: Vec::new(),
+			hrmp_watermark: 0,
 		};
 
 		assert!(check_wasm_result_against_constraints(
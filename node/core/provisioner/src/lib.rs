@@ -38,8 +38,8 @@ use polkadot_node_subsystem_util::{
 	request_availability_cores, request_persisted_validation_data, JobTrait, ToJobTrait,
 };
 use polkadot_primitives::v1::{
-	BackedCandidate, BlockNumber, CoreState, Hash, OccupiedCoreAssumption,
-	SignedAvailabilityBitfield,
+	BackedCandidate, BlockNumber, CoreState, DisputeStatementSet, Hash, OccupiedCoreAssumption,
+	SessionIndex, SignedAvailabilityBitfield,
 };
 use std::{collections::HashMap, convert::TryFrom, pin::Pin};
 
@@ -50,6 +50,7 @@ struct ProvisioningJob {
 	provisionable_data_channels: Vec<mpsc::Sender<ProvisionableData>>,
 	backed_candidates: Vec<BackedCandidate>,
 	signed_bitfields: Vec<SignedAvailabilityBitfield>,
+	dispute_statements: Vec<DisputeStatementSet>,
 	metrics: Metrics,
 }
 
@@ -174,6 +175,7 @@ impl ProvisioningJob {
 			provisionable_data_channels: Vec::new(),
 			backed_candidates: Vec::new(),
 			signed_bitfields: Vec::new(),
+			dispute_statements: Vec::new(),
 			metrics,
 		}
 	}
@@ -190,6 +192,7 @@ impl ProvisioningJob {
 						self.relay_parent,
 						&self.signed_bitfields,
 						&self.backed_candidates,
+						&self.dispute_statements,
 						return_sender,
 						self.sender.clone(),
 					)
@@ -252,6 +255,9 @@ impl ProvisioningJob {
 			ProvisionableData::BackedCandidate(backed_candidate) => {
 				self.backed_candidates.push(backed_candidate)
 			}
+			ProvisionableData::Dispute(_, statement_set) => {
+				self.dispute_statements.push(statement_set)
+			}
 			_ => {}
 		}
 	}
@@ -280,6 +286,7 @@ async fn send_inherent_data(
 	relay_parent: Hash,
 	bitfields: &[SignedAvailabilityBitfield],
 	candidates: &[BackedCandidate],
+	dispute_statements: &[DisputeStatementSet],
 	return_sender: oneshot::Sender<ProvisionerInherentData>,
 	mut from_job: mpsc::Sender<FromJob>,
 ) -> Result<(), Error> {
@@ -297,12 +304,42 @@ async fn send_inherent_data(
 	)
 	.await?;
 
+	let dispute_statements = select_dispute_statements(dispute_statements);
+
 	return_sender
-		.send((bitfields, candidates))
+		.send((bitfields, candidates, dispute_statements))
 		.map_err(|_| Error::OneshotSend)?;
 	Ok(())
 }
 
+// The runtime accepts at most one dispute statement set per candidate, so all statements about the
+// same candidate are merged into a single set, dropping duplicate statements of the same validator.
+fn select_dispute_statements(statement_sets: &[DisputeStatementSet]) -> Vec<DisputeStatementSet> {
+	let mut merged: HashMap<(SessionIndex, Hash), DisputeStatementSet> = HashMap::new();
+	for set in statement_sets {
+		let entry = merged
+			.entry((set.session, set.candidate_hash))
+			.or_insert_with(|| DisputeStatementSet {
+				candidate_hash: set.candidate_hash,
+				session: set.session,
+				statements: Vec::new(),
+			});
+
+		for statement in &set.statements {
+			let known = entry.statements.iter().any(|known| {
+				known.validator_index() == statement.validator_index()
+					&& known.payload() == statement.payload()
+			});
+
+			if !known {
+				entry.statements.push(statement.clone());
+			}
+		}
+	}
+
+	merged.into_iter().map(|(_, set)| set).collect()
+}
+
 // in general, we want to pick all the bitfields. However, we have the following constraints:
 //
 // - not more than one per validator
@@ -647,6 +684,59 @@ mod tests {
 		}
 	}
 
+	mod select_dispute_statements {
+		use super::super::*;
+		use polkadot_primitives::v1::{CompactStatement, SignedStatement, ValidatorPair};
+		use sp_core::crypto::Pair;
+
+		fn statement_set(
+			candidate_hash: Hash,
+			statements: &[(&ValidatorPair, u32, bool)],
+		) -> DisputeStatementSet {
+			let mut set = DisputeStatementSet { candidate_hash, session: 1, statements: Vec::new() };
+			let signing_context = set.signing_context();
+
+			set.statements = statements.iter().map(|&(pair, validator_index, valid)| {
+				let statement = if valid {
+					CompactStatement::Valid(candidate_hash)
+				} else {
+					CompactStatement::Invalid(candidate_hash)
+				};
+
+				SignedStatement::sign(statement, &signing_context, validator_index, pair)
+			}).collect();
+
+			set
+		}
+
+		#[test]
+		fn merges_sets_about_the_same_candidate() {
+			let (alice, bob) = (ValidatorPair::generate().0, ValidatorPair::generate().0);
+			let (candidate_a, candidate_b) = (Hash::repeat_byte(1), Hash::repeat_byte(2));
+
+			let sets = vec![
+				statement_set(candidate_a, &[(&alice, 0, true), (&bob, 1, false)]),
+				statement_set(candidate_b, &[(&alice, 0, false)]),
+				statement_set(candidate_a, &[(&bob, 1, false), (&bob, 1, true)]),
+			];
+
+			let mut selected = select_dispute_statements(&sets);
+			selected.sort_by_key(|set| set.candidate_hash);
+
+			assert_eq!(selected.len(), 2);
+			assert_eq!(selected[0].candidate_hash, candidate_a);
+			assert_eq!(
+				selected[0].statements,
+				vec![
+					sets[0].statements[0].clone(),
+					sets[0].statements[1].clone(),
+					sets[2].statements[1].clone(),
+				],
+			);
+			assert_eq!(selected[1], sets[1]);
+		}
+	}
+
 	mod select_candidates {
 		use super::super::*;
 		use super::{build_occupied_core, default_bitvec, occupied_core, scheduled_core};
@@ -0,0 +1,192 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A memory-weighted LRU cache of Runtime API responses.
+//!
+//! Responses are kept in their encoded form, which also determines their weight.
+
+use std::collections::{BTreeMap, HashMap};
+
+use parity_scale_codec::{Encode, Decode};
+use polkadot_primitives::v1::Hash;
+
+/// The key of a cached response: the relay-parent the request was made at, the name of the
+/// runtime API and its encoded parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct RequestKey {
+	relay_parent: Hash,
+	api: &'static str,
+	params: Vec<u8>,
+}
+
+impl RequestKey {
+	pub(crate) fn new(relay_parent: Hash, api: &'static str, params: Vec<u8>) -> Self {
+		RequestKey { relay_parent, api, params }
+	}
+
+	fn weight(&self) -> usize {
+		std::mem::size_of::<Self>() + self.params.len()
+	}
+}
+
+struct Entry {
+	response: Vec<u8>,
+	last_used: u64,
+}
+
+/// A cache of encoded responses bounded by their total weight in bytes.
+///
+/// When the capacity is exceeded, the least recently used responses are evicted first.
+pub(crate) struct ResponseCache {
+	capacity: usize,
+	weight: usize,
+	tick: u64,
+	entries: HashMap<RequestKey, Entry>,
+	// the keys of all entries, ordered by their last use.
+	lru: BTreeMap<u64, RequestKey>,
+}
+
+impl ResponseCache {
+	/// Create a new cache holding responses of up to `capacity` bytes in total.
+	pub(crate) fn new(capacity: usize) -> Self {
+		ResponseCache {
+			capacity,
+			weight: 0,
+			tick: 0,
+			entries: HashMap::new(),
+			lru: BTreeMap::new(),
+		}
+	}
+
+	/// Get the cached response for the given request, if any, marking it as recently used.
+	pub(crate) fn get<T: Decode>(&mut self, key: &RequestKey) -> Option<T> {
+		let tick = self.next_tick();
+		let entry = self.entries.get_mut(key)?;
+
+		self.lru.remove(&entry.last_used);
+		self.lru.insert(tick, key.clone());
+		entry.last_used = tick;
+
+		T::decode(&mut &entry.response[..]).ok()
+	}
+
+	/// Cache the response to the given request, evicting the least recently used responses
+	/// if the capacity is exceeded.
+	///
+	/// Responses weighing more than the whole capacity are not cached.
+	pub(crate) fn insert<T: Encode>(&mut self, key: RequestKey, response: &T) {
+		let response = response.encode();
+		let weight = key.weight() + response.len();
+		if weight > self.capacity {
+			return;
+		}
+
+		self.remove(&key);
+
+		while self.weight + weight > self.capacity {
+			let oldest = match self.lru.keys().next() {
+				Some(tick) => *tick,
+				None => break,
+			};
+			if let Some(key) = self.lru.remove(&oldest) {
+				self.remove(&key);
+			}
+		}
+
+		let tick = self.next_tick();
+		self.lru.insert(tick, key.clone());
+		self.entries.insert(key, Entry { response, last_used: tick });
+		self.weight += weight;
+	}
+
+	/// Remove all responses to requests made at the given relay-parent.
+	pub(crate) fn invalidate(&mut self, relay_parent: &Hash) {
+		let keys: Vec<_> = self.entries.keys()
+			.filter(|k| &k.relay_parent == relay_parent)
+			.cloned()
+			.collect();
+
+		for key in keys {
+			self.remove(&key);
+		}
+	}
+
+	fn remove(&mut self, key: &RequestKey) {
+		if let Some(entry) = self.entries.remove(key) {
+			self.lru.remove(&entry.last_used);
+			self.weight -= key.weight() + entry.response.len();
+		}
+	}
+
+	fn next_tick(&mut self) -> u64 {
+		self.tick += 1;
+		self.tick
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key(relay_parent: u8, api: &'static str) -> RequestKey {
+		RequestKey::new([relay_parent; 32].into(), api, Vec::new())
+	}
+
+	#[test]
+	fn evicts_least_recently_used() {
+		let entry_weight = key(1, "a").weight() + vec![0u8; 100].encode().len();
+		let mut cache = ResponseCache::new(entry_weight * 2);
+
+		cache.insert(key(1, "a"), &vec![1u8; 100]);
+		cache.insert(key(1, "b"), &vec![2u8; 100]);
+
+		// touch `a`, so that `b` gets evicted first.
+		assert_eq!(cache.get::<Vec<u8>>(&key(1, "a")), Some(vec![1u8; 100]));
+
+		cache.insert(key(1, "c"), &vec![3u8; 100]);
+
+		assert_eq!(cache.get::<Vec<u8>>(&key(1, "a")), Some(vec![1u8; 100]));
+		assert_eq!(cache.get::<Vec<u8>>(&key(1, "b")), None);
+		assert_eq!(cache.get::<Vec<u8>>(&key(1, "c")), Some(vec![3u8; 100]));
+		assert_eq!(cache.weight, entry_weight * 2);
+	}
+
+	#[test]
+	fn does_not_cache_responses_exceeding_capacity() {
+		let mut cache = ResponseCache::new(64);
+
+		cache.insert(key(1, "a"), &vec![1u8; 100]);
+
+		assert_eq!(cache.get::<Vec<u8>>(&key(1, "a")), None);
+		assert_eq!(cache.weight, 0);
+	}
+
+	#[test]
+	fn invalidates_by_relay_parent() {
+		let mut cache = ResponseCache::new(1024 * 1024);
+
+		cache.insert(key(1, "a"), &1u32);
+		cache.insert(key(1, "b"), &2u32);
+		cache.insert(key(2, "a"), &3u32);
+
+		cache.invalidate(&[1; 32].into());
+
+		assert_eq!(cache.get::<u32>(&key(1, "a")), None);
+		assert_eq!(cache.get::<u32>(&key(1, "b")), None);
+		assert_eq!(cache.get::<u32>(&key(2, "a")), Some(3));
+		assert_eq!(cache.weight, key(2, "a").weight() + 3u32.encode().len());
+	}
+}
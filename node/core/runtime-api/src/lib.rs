@@ -16,12 +16,13 @@
 
 //! Implements the Runtime API Subsystem
 //!
-//! This provides a clean, ownerless wrapper around the parachain-related runtime APIs. This crate
-//! can also be used to cache responses from heavy runtime APIs.
+//! This provides a clean, ownerless wrapper around the parachain-related runtime APIs. Responses
+//! are cached per relay-parent, so that subsystems asking for the same data at the same block
+//! only lead to a single runtime call.
 
 use polkadot_subsystem::{
 	Subsystem, SpawnedSubsystem, SubsystemResult, SubsystemContext,
-	FromOverseer, OverseerSignal,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate,
 	metrics::{self, prometheus},
 };
 use polkadot_subsystem::messages::{
@@ -33,22 +34,43 @@ use polkadot_primitives::v1::{Block, BlockId, Hash, ParachainHost};
 use sp_api::{ProvideRuntimeApi};
 
 use futures::prelude::*;
+use parity_scale_codec::Encode;
+
+use std::sync::Arc;
+
+mod cache;
+
+use cache::{RequestKey, ResponseCache};
+
+/// The default capacity of the response cache in bytes.
+const DEFAULT_CACHE_CAPACITY: usize = 64 * 1024 * 1024;
 
 /// The `RuntimeApiSubsystem`. See module docs for more details.
 pub struct RuntimeApiSubsystem<Client> {
-	client: Client,
+	client: Arc<Client>,
 	metrics: Metrics,
+	cache: ResponseCache,
 }
 
 impl<Client> RuntimeApiSubsystem<Client> {
 	/// Create a new Runtime API subsystem wrapping the given client and metrics.
-	pub fn new(client: Client, metrics: Metrics) -> Self {
-		RuntimeApiSubsystem { client, metrics }
+	pub fn new(client: Arc<Client>, metrics: Metrics) -> Self {
+		Self::with_cache_capacity(client, metrics, DEFAULT_CACHE_CAPACITY)
+	}
+
+	/// Create a new Runtime API subsystem caching responses of up to `cache_capacity` bytes
+	/// in total.
+	pub fn with_cache_capacity(client: Arc<Client>, metrics: Metrics, cache_capacity: usize) -> Self {
+		RuntimeApiSubsystem {
+			client,
+			metrics,
+			cache: ResponseCache::new(cache_capacity),
+		}
 	}
 }
 
 impl<Client, Context> Subsystem<Context> for RuntimeApiSubsystem<Client> where
-	Client: ProvideRuntimeApi<Block> + Send + 'static,
+	Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
 	Client::Api: ParachainHost<Block>,
 	Context: SubsystemContext<Message = RuntimeApiMessage>
 {
@@ -64,7 +86,7 @@ impl<Client, Context> Subsystem<Context> for RuntimeApiSubsystem<Client> where
 
 async fn run<Client>(
 	mut ctx: impl SubsystemContext<Message = RuntimeApiMessage>,
-	subsystem: RuntimeApiSubsystem<Client>,
+	mut subsystem: RuntimeApiSubsystem<Client>,
 ) -> SubsystemResult<()> where
 	Client: ProvideRuntimeApi<Block>,
 	Client::Api: ParachainHost<Block>,
@@ -72,12 +94,17 @@ async fn run<Client>(
 	loop {
 		match ctx.recv().await? {
 			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
-			FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {},
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { deactivated, .. })) => {
+				for relay_parent in deactivated {
+					subsystem.cache.invalidate(&relay_parent);
+				}
+			},
 			FromOverseer::Signal(OverseerSignal::BlockFinalized(_)) => {},
 			FromOverseer::Communication { msg } => match msg {
 				RuntimeApiMessage::Request(relay_parent, request) => make_runtime_api_request(
-					&subsystem.client,
+					&*subsystem.client,
 					&subsystem.metrics,
+					&mut subsystem.cache,
 					relay_parent,
 					request,
 				),
@@ -89,6 +116,7 @@ async fn run<Client>(
 fn make_runtime_api_request<Client>(
 	client: &Client,
 	metrics: &Metrics,
+	cache: &mut ResponseCache,
 	relay_parent: Hash,
 	request: Request,
 ) where
@@ -98,11 +126,31 @@ fn make_runtime_api_request<Client>(
 	macro_rules! query {
 		($api_name:ident ($($param:expr),*), $sender:expr) => {{
 			let sender = $sender;
-			let api = client.runtime_api();
-			let res = api.$api_name(&BlockId::Hash(relay_parent), $($param),*)
-				.map_err(|e| RuntimeApiError::from(format!("{:?}", e)));
-			metrics.on_request(res.is_ok());
-			let _ = sender.send(res);
+			let key = RequestKey::new(
+				relay_parent,
+				stringify!($api_name),
+				($(&$param,)*).encode(),
+			);
+
+			match cache.get(&key) {
+				Some(cached) => {
+					metrics.on_cached_request(true);
+					let _ = sender.send(Ok(cached));
+				}
+				None => {
+					metrics.on_cached_request(false);
+
+					let api = client.runtime_api();
+					let res = api.$api_name(&BlockId::Hash(relay_parent), $($param),*)
+						.map_err(|e| RuntimeApiError::from(format!("{:?}", e)));
+					metrics.on_request(res.is_ok());
+
+					if let Ok(ref response) = res {
+						cache.insert(key, response);
+					}
+					let _ = sender.send(res);
+				}
+			}
 		}}
 	}
 
@@ -115,17 +163,28 @@ fn make_runtime_api_request<Client>(
 		Request::FullValidationData(para, assumption, sender) =>
 			query!(full_validation_data(para, assumption), sender),
 		Request::SessionIndexForChild(sender) => query!(session_index_for_child(), sender),
+		Request::SessionInfo(index, sender) => query!(session_info(index), sender),
 		Request::ValidationCode(para, assumption, sender) =>
 			query!(validation_code(para, assumption), sender),
+		Request::FutureValidationCode(para, sender) =>
+			query!(future_validation_code(para), sender),
 		Request::CandidatePendingAvailability(para, sender) =>
 			query!(candidate_pending_availability(para), sender),
 		Request::CandidateEvents(sender) => query!(candidate_events(), sender),
+		Request::DmqContents(id, sender) => query!(dmq_contents(id), sender),
+		Request::InboundHrmpChannelsContents(id, sender) =>
+			query!(inbound_hrmp_channels_contents(id), sender),
+		Request::ValidatorDiscovery(ids, sender) => query!(validator_discovery(ids), sender),
+		Request::ExecutionLimits(sender) => query!(execution_limits(), sender),
+		Request::ExecutionTimeouts(sender) => query!(execution_timeouts(), sender),
 	}
 }
 
 #[derive(Clone)]
 struct MetricsInner {
 	chain_api_requests: prometheus::CounterVec<prometheus::U64>,
+	cache_hits: prometheus::Counter<prometheus::U64>,
+	cache_misses: prometheus::Counter<prometheus::U64>,
 }
 
 /// Runtime API metrics.
@@ -142,6 +201,16 @@ impl Metrics {
 			}
 		}
 	}
+
+	fn on_cached_request(&self, hit: bool) {
+		if let Some(metrics) = &self.0 {
+			if hit {
+				metrics.cache_hits.inc();
+			} else {
+				metrics.cache_misses.inc();
+			}
+		}
+	}
 }
 
 impl metrics::Metrics for Metrics {
@@ -157,6 +226,20 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			cache_hits: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_runtime_api_cache_hits_total",
+					"Number of Runtime API requests served from the cache.",
+				)?,
+				registry,
+			)?,
+			cache_misses: prometheus::register(
+				prometheus::Counter::new(
+					"parachain_runtime_api_cache_misses_total",
+					"Number of Runtime API requests not found in the cache.",
+				)?,
+				registry,
+			)?,
 		};
 		Ok(Metrics(Some(metrics)))
 	}
@@ -169,12 +252,15 @@ mod tests {
 	use polkadot_primitives::v1::{
 		ValidatorId, ValidatorIndex, GroupRotationInfo, CoreState, PersistedValidationData,
 		Id as ParaId, OccupiedCoreAssumption, ValidationData, SessionIndex, ValidationCode,
-		CommittedCandidateReceipt, CandidateEvent,
+		CommittedCandidateReceipt, CandidateEvent, InboundDownwardMessage, BlockNumber,
+		DownwardMessage, InboundHrmpMessage, AuthorityDiscoveryId, SessionInfo, ExecutionLimits,
+		ExecutionTimeouts,
 	};
 	use polkadot_node_subsystem_test_helpers as test_helpers;
-	use sp_core::testing::TaskExecutor;
+	use sp_core::{sr25519, testing::TaskExecutor};
 
-	use std::collections::HashMap;
+	use std::collections::{HashMap, BTreeMap};
+	use std::sync::atomic::{AtomicUsize, Ordering};
 	use futures::channel::oneshot;
 
 	#[derive(Default, Clone)]
@@ -184,9 +270,17 @@ mod tests {
 		availability_cores: Vec<CoreState>,
 		validation_data: HashMap<ParaId, ValidationData>,
 		session_index_for_child: SessionIndex,
+		session_info: HashMap<SessionIndex, SessionInfo>,
 		validation_code: HashMap<ParaId, ValidationCode>,
+		future_validation_code: HashMap<ParaId, ValidationCode>,
 		candidate_pending_availability: HashMap<ParaId, CommittedCandidateReceipt>,
 		candidate_events: Vec<CandidateEvent>,
+		dmq_contents: HashMap<ParaId, Vec<InboundDownwardMessage>>,
+		hrmp_channels: HashMap<ParaId, BTreeMap<ParaId, Vec<InboundHrmpMessage>>>,
+		authority_discovery_keys: HashMap<ValidatorId, AuthorityDiscoveryId>,
+		execution_limits: ExecutionLimits,
+		execution_timeouts: ExecutionTimeouts,
+		validators_calls: Arc<AtomicUsize>,
 	}
 
 	impl ProvideRuntimeApi<Block> for MockRuntimeApi {
@@ -202,6 +296,7 @@ mod tests {
 			type Error = String;
 
 			fn validators(&self) -> Vec<ValidatorId> {
+				self.validators_calls.fetch_add(1, Ordering::SeqCst);
 				self.validators.clone()
 			}
 
@@ -240,6 +335,10 @@ mod tests {
 				self.session_index_for_child.clone()
 			}
 
+			fn session_info(&self, index: SessionIndex) -> Option<SessionInfo> {
+				self.session_info.get(&index).cloned()
+			}
+
 			fn validation_code(
 				&self,
 				para: ParaId,
@@ -248,6 +347,10 @@ mod tests {
 				self.validation_code.get(&para).map(|c| c.clone())
 			}
 
+			fn future_validation_code(&self, para: ParaId) -> Option<ValidationCode> {
+				self.future_validation_code.get(&para).cloned()
+			}
+
 			fn candidate_pending_availability(
 				&self,
 				para: ParaId,
@@ -258,6 +361,35 @@ mod tests {
 			fn candidate_events(&self) -> Vec<CandidateEvent> {
 				self.candidate_events.clone()
 			}
+
+			fn dmq_contents(
+				&self,
+				recipient: ParaId,
+			) -> Vec<InboundDownwardMessage<BlockNumber>> {
+				self.dmq_contents.get(&recipient).cloned().unwrap_or_default()
+			}
+
+			fn inbound_hrmp_channels_contents(
+				&self,
+				recipient: ParaId
+			) -> BTreeMap<ParaId, Vec<InboundHrmpMessage>> {
+				self.hrmp_channels.get(&recipient).cloned().unwrap_or_default()
+			}
+
+			fn validator_discovery(
+				&self,
+				validators: Vec<ValidatorId>,
+			) -> Vec<Option<AuthorityDiscoveryId>> {
+				validators.iter().map(|v| self.authority_discovery_keys.get(v).cloned()).collect()
+			}
+
+			fn execution_limits(&self) -> ExecutionLimits {
+				self.execution_limits
+			}
+
+			fn execution_timeouts(&self) -> ExecutionTimeouts {
+				self.execution_timeouts
+			}
 		}
 	}
 
@@ -267,7 +399,7 @@ mod tests {
 		let runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -290,7 +422,7 @@ mod tests {
 		let runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -313,7 +445,7 @@ mod tests {
 		let runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -340,7 +472,7 @@ mod tests {
 
 		runtime_api.validation_data.insert(para_a, Default::default());
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -380,7 +512,7 @@ mod tests {
 
 		runtime_api.validation_data.insert(para_a, Default::default());
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -416,7 +548,7 @@ mod tests {
 		let runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -433,6 +565,39 @@ mod tests {
 		futures::executor::block_on(future::join(subsystem_task, test_task));
 	}
 
+	#[test]
+	fn requests_session_info() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.session_info.insert(1, SessionInfo { n_cores: 5, ..Default::default() });
+
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::SessionInfo(1, tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.session_info.get(&1).cloned());
+
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::SessionInfo(2, tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
 	#[test]
 	fn requests_validation_code() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
@@ -443,7 +608,7 @@ mod tests {
 
 		runtime_api.validation_code.insert(para_a, Default::default());
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -473,6 +638,40 @@ mod tests {
 		futures::executor::block_on(future::join(subsystem_task, test_task));
 	}
 
+	#[test]
+	fn requests_future_validation_code() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+		let para_a = 5.into();
+		let para_b = 6.into();
+
+		runtime_api.future_validation_code.insert(para_a, ValidationCode(vec![1, 2, 3]));
+
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::FutureValidationCode(para_a, tx)),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), Some(ValidationCode(vec![1, 2, 3])));
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::FutureValidationCode(para_b, tx)),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
 	#[test]
 	fn requests_candidate_pending_availability() {
 		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
@@ -483,7 +682,7 @@ mod tests {
 
 		runtime_api.candidate_pending_availability.insert(para_a, Default::default());
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -520,7 +719,7 @@ mod tests {
 		let runtime_api = MockRuntimeApi::default();
 		let relay_parent = [1; 32].into();
 
-		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
 		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
 		let test_task = async move {
 			let (tx, rx) = oneshot::channel();
@@ -536,4 +735,238 @@ mod tests {
 
 		futures::executor::block_on(future::join(subsystem_task, test_task));
 	}
+
+	#[test]
+	fn requests_dmq_contents() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+		let para_a = 5.into();
+		let para_b = 6.into();
+
+		runtime_api.dmq_contents.insert(para_a, vec![]);
+		runtime_api.dmq_contents.insert(
+			para_b,
+			vec![InboundDownwardMessage {
+				sent_at: 228,
+				msg: DownwardMessage::Opaque(b"Novus Ordo Seclorum".to_vec()),
+			}],
+		);
+
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::DmqContents(para_a, tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), vec![]);
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::DmqContents(para_b, tx))
+			}).await;
+
+			assert_eq!(
+				rx.await.unwrap().unwrap(),
+				vec![InboundDownwardMessage {
+					sent_at: 228,
+					msg: DownwardMessage::Opaque(b"Novus Ordo Seclorum".to_vec()),
+				}]
+			);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_inbound_hrmp_channels_contents() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+
+		let relay_parent = [1; 32].into();
+		let para_a = 99.into();
+		let para_b = 66.into();
+		let para_c = 33.into();
+
+		let para_b_inbound_channels = [
+			(para_a, vec![]),
+			(
+				para_c,
+				vec![InboundHrmpMessage {
+					sent_at: 1,
+					data: "𝙀=𝙈𝘾²".as_bytes().to_owned(),
+				}],
+			),
+		]
+		.iter()
+		.cloned()
+		.collect::<BTreeMap<_, _>>();
+
+		let runtime_api = {
+			let mut runtime_api = MockRuntimeApi::default();
+
+			runtime_api.hrmp_channels.insert(para_a, BTreeMap::new());
+			runtime_api.hrmp_channels.insert(para_b, para_b_inbound_channels.clone());
+
+			Arc::new(runtime_api)
+		};
+
+		let subsystem = RuntimeApiSubsystem::new(runtime_api.clone(), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::InboundHrmpChannelsContents(para_a, tx),
+				),
+			}).await;
+			assert_eq!(rx.await.unwrap().unwrap(), BTreeMap::new());
+
+			let (tx, rx) = oneshot::channel();
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::InboundHrmpChannelsContents(para_b, tx),
+				),
+			}).await;
+			assert_eq!(rx.await.unwrap().unwrap(), para_b_inbound_channels);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_validator_discovery() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		let alice = ValidatorId::from(sr25519::Public::from_raw([1; 32]));
+		let bob = ValidatorId::from(sr25519::Public::from_raw([2; 32]));
+		let alice_discovery = AuthorityDiscoveryId::from(sr25519::Public::from_raw([3; 32]));
+
+		runtime_api.authority_discovery_keys.insert(alice.clone(), alice_discovery.clone());
+
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					Request::ValidatorDiscovery(vec![alice, bob], tx),
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), vec![Some(alice_discovery), None]);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_execution_limits() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.execution_limits.max_fuel = Some(1_000_000);
+
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::ExecutionLimits(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.execution_limits);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	#[test]
+	fn requests_execution_timeouts() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+
+		runtime_api.execution_timeouts.approval = 30_000;
+
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			let (tx, rx) = oneshot::channel();
+
+			ctx_handle.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, Request::ExecutionTimeouts(tx))
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), runtime_api.execution_timeouts);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
+
+	async fn request_validators(
+		ctx_handle: &mut test_helpers::TestSubsystemContextHandle<RuntimeApiMessage>,
+		relay_parent: Hash,
+	) -> Vec<ValidatorId> {
+		let (tx, rx) = oneshot::channel();
+
+		ctx_handle.send(FromOverseer::Communication {
+			msg: RuntimeApiMessage::Request(relay_parent, Request::Validators(tx))
+		}).await;
+
+		rx.await.unwrap().unwrap()
+	}
+
+	#[test]
+	fn requests_are_cached_until_relay_parent_is_deactivated() {
+		let (ctx, mut ctx_handle) = test_helpers::make_subsystem_context(TaskExecutor::new());
+		let mut runtime_api = MockRuntimeApi::default();
+		let relay_parent = [1; 32].into();
+		let other_relay_parent = [2; 32].into();
+
+		runtime_api.validators = vec![sr25519::Public::from_raw([1; 32]).into()];
+
+		let subsystem = RuntimeApiSubsystem::new(Arc::new(runtime_api.clone()), Metrics(None));
+		let subsystem_task = run(ctx, subsystem).map(|x| x.unwrap());
+		let test_task = async move {
+			assert_eq!(request_validators(&mut ctx_handle, relay_parent).await, runtime_api.validators);
+			assert_eq!(request_validators(&mut ctx_handle, relay_parent).await, runtime_api.validators);
+			assert_eq!(runtime_api.validators_calls.load(Ordering::SeqCst), 1);
+
+			// other relay-parents are cached separately.
+			assert_eq!(request_validators(&mut ctx_handle, other_relay_parent).await, runtime_api.validators);
+			assert_eq!(runtime_api.validators_calls.load(Ordering::SeqCst), 2);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::stop_work(relay_parent),
+			))).await;
+
+			assert_eq!(request_validators(&mut ctx_handle, relay_parent).await, runtime_api.validators);
+			assert_eq!(request_validators(&mut ctx_handle, other_relay_parent).await, runtime_api.validators);
+			assert_eq!(runtime_api.validators_calls.load(Ordering::SeqCst), 3);
+
+			ctx_handle.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		};
+
+		futures::executor::block_on(future::join(subsystem_task, test_task));
+	}
 }
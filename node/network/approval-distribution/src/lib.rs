@@ -0,0 +1,743 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The approval distribution
+//!
+//! Gossips the assignments and approval votes of the local validator, as produced by the
+//! approval voting subsystem, and passes on valid assignments and approvals received from
+//! peers to other interested peers.
+
+#![deny(missing_docs)]
+
+use futures::{channel::oneshot, FutureExt};
+
+use log::{trace, warn};
+use polkadot_subsystem::messages::*;
+use polkadot_subsystem::{
+	FromOverseer, OverseerSignal, SpawnedSubsystem, Subsystem, SubsystemContext, SubsystemResult,
+};
+use node_primitives::approval::{CandidateIndex, IndirectAssignmentCert, IndirectSignedApprovalVote};
+use polkadot_primitives::v1::{Hash, ValidatorIndex};
+use polkadot_node_network_protocol::{v1 as protocol_v1, PeerId, NetworkBridgeEvent, View, ReputationChange};
+use std::collections::{HashMap, HashSet};
+
+const COST_UNEXPECTED_MESSAGE: ReputationChange =
+	ReputationChange::new(-100, "Peer sent an out-of-view assignment or approval");
+const COST_PEER_DUPLICATE_MESSAGE: ReputationChange =
+	ReputationChange::new(-500, "Peer sent the same message multiple times");
+const COST_INVALID_MESSAGE: ReputationChange =
+	ReputationChange::new(-500, "Peer sent an invalid assignment or approval");
+const COST_ASSIGNMENT_TOO_FAR_IN_THE_FUTURE: ReputationChange =
+	ReputationChange::new(-20, "The vote was valid but too far in the future");
+const BENEFIT_VALID_MESSAGE_FIRST: ReputationChange =
+	ReputationChange::new(15, "Valid message with new information");
+const BENEFIT_VALID_MESSAGE: ReputationChange =
+	ReputationChange::new(10, "Valid message");
+
+const TARGET: &'static str = "approval_distribution";
+
+/// Identifies a message within a block: assignments and approvals are unique per candidate
+/// and validator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum MessageFingerprint {
+	Assignment(CandidateIndex, ValidatorIndex),
+	Approval(CandidateIndex, ValidatorIndex),
+}
+
+/// Where a message originates from.
+#[derive(Debug, Clone, PartialEq)]
+enum MessageSource {
+	Peer(PeerId),
+	Local,
+}
+
+/// Data for a particular block in our view.
+#[derive(Debug, Clone, Default)]
+struct BlockEntry {
+	/// The valid assignments we know of, which also serve as the messages to send to
+	/// peers adding the block to their view later on.
+	assignments: HashMap<(CandidateIndex, ValidatorIndex), IndirectAssignmentCert>,
+
+	/// The valid approvals we know of.
+	approvals: HashMap<(CandidateIndex, ValidatorIndex), IndirectSignedApprovalVote>,
+
+	/// The messages known by a peer, either because we sent it to them or because we received
+	/// it from them. Avoids duplicate message transmission to our peers.
+	known_by: HashMap<PeerId, HashSet<MessageFingerprint>>,
+
+	/// Track messages that were already received by a peer to prevent flooding.
+	received_from: HashMap<PeerId, HashSet<MessageFingerprint>>,
+}
+
+impl BlockEntry {
+	/// Note that the peer sent us the message. Returns `false` if it had done so before.
+	fn note_received(&mut self, peer: &PeerId, fingerprint: MessageFingerprint) -> bool {
+		self.known_by.entry(peer.clone()).or_default().insert(fingerprint);
+		self.received_from.entry(peer.clone()).or_default().insert(fingerprint)
+	}
+
+	/// Determines if the message is needed by the given peer.
+	fn message_needed_by_peer(&self, peer: &PeerId, fingerprint: &MessageFingerprint) -> bool {
+		self.known_by.get(peer).map_or(true, |known| !known.contains(fingerprint))
+	}
+}
+
+/// Data used to track information of peers and blocks.
+#[derive(Default, Clone)]
+struct State {
+	/// Track all active peers and their views to determine what is relevant to them.
+	peer_views: HashMap<PeerId, View>,
+
+	/// Our current view.
+	view: View,
+
+	/// Assignments and approvals of the blocks in our view.
+	blocks: HashMap<Hash, BlockEntry>,
+}
+
+/// The approval distribution subsystem.
+pub struct ApprovalDistribution;
+
+impl ApprovalDistribution {
+	/// Start processing work as passed on from the Overseer.
+	async fn run<Context>(mut ctx: Context) -> SubsystemResult<()>
+	where
+		Context: SubsystemContext<Message = ApprovalDistributionMessage>,
+	{
+		let mut state = State::default();
+		loop {
+			let message = ctx.recv().await?;
+			match message {
+				FromOverseer::Communication {
+					msg: ApprovalDistributionMessage::DistributeAssignment(cert, candidate_index),
+				} => {
+					trace!(target: TARGET, "Processing DistributeAssignment");
+					import_and_circulate_assignment(
+						&mut ctx,
+						&mut state,
+						MessageSource::Local,
+						cert,
+						candidate_index,
+					).await?;
+				}
+				FromOverseer::Communication {
+					msg: ApprovalDistributionMessage::DistributeApproval(vote),
+				} => {
+					trace!(target: TARGET, "Processing DistributeApproval");
+					import_and_circulate_approval(&mut ctx, &mut state, MessageSource::Local, vote).await?;
+				}
+				FromOverseer::Communication {
+					msg: ApprovalDistributionMessage::NetworkBridgeUpdateV1(event),
+				} => {
+					trace!(target: TARGET, "Processing NetworkMessage");
+					if let Err(e) = handle_network_msg(&mut ctx, &mut state, event).await {
+						warn!(target: TARGET, "Failed to handle incoming network messages: {:?}", e);
+					}
+				}
+				FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {
+					// the blocks we work on are determined by our view.
+				}
+				FromOverseer::Signal(OverseerSignal::BlockFinalized(hash)) => {
+					trace!(target: TARGET, "Block finalized {:?}", hash);
+				}
+				FromOverseer::Signal(OverseerSignal::Conclude) => {
+					trace!(target: TARGET, "Conclude");
+					return Ok(());
+				}
+			}
+		}
+	}
+}
+
+/// Modify the reputation of a peer based on its behaviour.
+async fn modify_reputation<Context>(
+	ctx: &mut Context,
+	peer: PeerId,
+	rep: ReputationChange,
+) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message = ApprovalDistributionMessage>,
+{
+	trace!(target: TARGET, "Reputation change of {:?} for peer {:?}", rep, peer);
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::ReportPeer(peer, rep),
+	))
+	.await
+}
+
+/// Import an assignment, checking it with the approval voting subsystem unless it is our own,
+/// and pass it on to all interested peers if it is new and valid.
+async fn import_and_circulate_assignment<Context>(
+	ctx: &mut Context,
+	state: &mut State,
+	source: MessageSource,
+	assignment: IndirectAssignmentCert,
+	candidate_index: CandidateIndex,
+) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message = ApprovalDistributionMessage>,
+{
+	let block_hash = assignment.block_hash;
+	let key = (candidate_index, assignment.validator);
+	let fingerprint = MessageFingerprint::Assignment(candidate_index, assignment.validator);
+
+	let block_entry = match state.blocks.get_mut(&block_hash) {
+		Some(block_entry) => block_entry,
+		None => {
+			trace!(target: TARGET, "Assignment for {:?}, which is not in our view", block_hash);
+			if let MessageSource::Peer(peer) = source {
+				modify_reputation(ctx, peer, COST_UNEXPECTED_MESSAGE).await?;
+			}
+			return Ok(());
+		}
+	};
+
+	if let MessageSource::Peer(ref peer) = source {
+		if !block_entry.note_received(peer, fingerprint) {
+			return modify_reputation(ctx, peer.clone(), COST_PEER_DUPLICATE_MESSAGE).await;
+		}
+
+		if block_entry.assignments.get(&key) == Some(&assignment) {
+			return modify_reputation(ctx, peer.clone(), BENEFIT_VALID_MESSAGE).await;
+		}
+
+		let (tx, rx) = oneshot::channel();
+		ctx.send_message(AllMessages::ApprovalVoting(
+			ApprovalVotingMessage::CheckAndImportAssignment(assignment.clone(), candidate_index, tx),
+		)).await?;
+
+		match rx.await? {
+			AssignmentCheckResult::Accepted => {
+				modify_reputation(ctx, peer.clone(), BENEFIT_VALID_MESSAGE_FIRST).await?;
+			}
+			AssignmentCheckResult::AcceptedDuplicate => {
+				return modify_reputation(ctx, peer.clone(), BENEFIT_VALID_MESSAGE).await;
+			}
+			AssignmentCheckResult::TooFarInFuture => {
+				return modify_reputation(ctx, peer.clone(), COST_ASSIGNMENT_TOO_FAR_IN_THE_FUTURE).await;
+			}
+			AssignmentCheckResult::Bad => {
+				return modify_reputation(ctx, peer.clone(), COST_INVALID_MESSAGE).await;
+			}
+		}
+	}
+
+	block_entry.assignments.insert(key, assignment.clone());
+
+	let peers = interested_peers(&state.peer_views, block_entry, block_hash, fingerprint);
+	if !peers.is_empty() {
+		ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+			peers,
+			protocol_v1::ValidationProtocol::ApprovalDistribution(
+				protocol_v1::ApprovalDistributionMessage::Assignments(vec![(assignment, candidate_index)]),
+			),
+		))).await?;
+	}
+
+	Ok(())
+}
+
+/// Import an approval, checking it with the approval voting subsystem unless it is our own,
+/// and pass it on to all interested peers if it is new and valid.
+async fn import_and_circulate_approval<Context>(
+	ctx: &mut Context,
+	state: &mut State,
+	source: MessageSource,
+	vote: IndirectSignedApprovalVote,
+) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message = ApprovalDistributionMessage>,
+{
+	let block_hash = vote.block_hash;
+	let key = (vote.candidate_index, vote.validator);
+	let fingerprint = MessageFingerprint::Approval(vote.candidate_index, vote.validator);
+
+	let block_entry = match state.blocks.get_mut(&block_hash) {
+		Some(block_entry) => block_entry,
+		None => {
+			trace!(target: TARGET, "Approval for {:?}, which is not in our view", block_hash);
+			if let MessageSource::Peer(peer) = source {
+				modify_reputation(ctx, peer, COST_UNEXPECTED_MESSAGE).await?;
+			}
+			return Ok(());
+		}
+	};
+
+	if let MessageSource::Peer(ref peer) = source {
+		if !block_entry.note_received(peer, fingerprint) {
+			return modify_reputation(ctx, peer.clone(), COST_PEER_DUPLICATE_MESSAGE).await;
+		}
+
+		if block_entry.approvals.get(&key) == Some(&vote) {
+			return modify_reputation(ctx, peer.clone(), BENEFIT_VALID_MESSAGE).await;
+		}
+
+		let (tx, rx) = oneshot::channel();
+		ctx.send_message(AllMessages::ApprovalVoting(
+			ApprovalVotingMessage::CheckAndImportApproval(vote.clone(), tx),
+		)).await?;
+
+		match rx.await? {
+			ApprovalCheckResult::Accepted => {
+				modify_reputation(ctx, peer.clone(), BENEFIT_VALID_MESSAGE_FIRST).await?;
+			}
+			ApprovalCheckResult::Bad => {
+				return modify_reputation(ctx, peer.clone(), COST_INVALID_MESSAGE).await;
+			}
+		}
+	}
+
+	block_entry.approvals.insert(key, vote.clone());
+
+	let peers = interested_peers(&state.peer_views, block_entry, block_hash, fingerprint);
+	if !peers.is_empty() {
+		ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+			peers,
+			protocol_v1::ValidationProtocol::ApprovalDistribution(
+				protocol_v1::ApprovalDistributionMessage::Approvals(vec![vote]),
+			),
+		))).await?;
+	}
+
+	Ok(())
+}
+
+/// Determine the peers which have the block in their view but don't know the message yet,
+/// tracking the message as sent to them.
+fn interested_peers(
+	peer_views: &HashMap<PeerId, View>,
+	block_entry: &mut BlockEntry,
+	block_hash: Hash,
+	fingerprint: MessageFingerprint,
+) -> Vec<PeerId> {
+	let peers: Vec<PeerId> = peer_views.iter()
+		.filter(|(peer, view)| {
+			view.contains(&block_hash) && block_entry.message_needed_by_peer(peer, &fingerprint)
+		})
+		.map(|(peer, _)| peer.clone())
+		.collect();
+
+	for peer in &peers {
+		block_entry.known_by.entry(peer.clone()).or_default().insert(fingerprint);
+	}
+
+	if peers.is_empty() {
+		trace!(target: TARGET, "No peers are interested in gossip for block {:?}", block_hash);
+	}
+
+	peers
+}
+
+/// Deal with network bridge updates and track what needs to be tracked
+/// which depends on the message type received.
+async fn handle_network_msg<Context>(
+	ctx: &mut Context,
+	state: &mut State,
+	bridge_message: NetworkBridgeEvent<protocol_v1::ApprovalDistributionMessage>,
+) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message = ApprovalDistributionMessage>,
+{
+	match bridge_message {
+		NetworkBridgeEvent::PeerConnected(peer, _role) => {
+			// insert if none already present
+			state.peer_views.entry(peer).or_default();
+		}
+		NetworkBridgeEvent::PeerDisconnected(peer) => {
+			// get rid of superfluous data
+			state.peer_views.remove(&peer);
+			for block_entry in state.blocks.values_mut() {
+				block_entry.known_by.remove(&peer);
+				block_entry.received_from.remove(&peer);
+			}
+		}
+		NetworkBridgeEvent::PeerViewChange(peer, view) => {
+			handle_peer_view_change(ctx, state, peer, view).await?;
+		}
+		NetworkBridgeEvent::OurViewChange(view) => {
+			handle_our_view_change(state, view);
+		}
+		NetworkBridgeEvent::PeerMessage(peer, message) => {
+			match message {
+				protocol_v1::ApprovalDistributionMessage::Assignments(assignments) => {
+					trace!(target: TARGET, "Received {} assignments from peer {:?}", assignments.len(), peer);
+					for (assignment, candidate_index) in assignments {
+						import_and_circulate_assignment(
+							ctx,
+							state,
+							MessageSource::Peer(peer.clone()),
+							assignment,
+							candidate_index,
+						).await?;
+					}
+				}
+				protocol_v1::ApprovalDistributionMessage::Approvals(approvals) => {
+					trace!(target: TARGET, "Received {} approvals from peer {:?}", approvals.len(), peer);
+					for vote in approvals {
+						import_and_circulate_approval(ctx, state, MessageSource::Peer(peer.clone()), vote)
+							.await?;
+					}
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Handle the changes necessary when our view changes.
+fn handle_our_view_change(state: &mut State, view: View) {
+	let old_view = std::mem::replace(&mut state.view, view);
+
+	for added in state.view.difference(&old_view) {
+		state.blocks.entry(*added).or_default();
+	}
+
+	for removed in old_view.difference(&state.view) {
+		// cleanup blocks we are not interested in any more
+		state.blocks.remove(removed);
+	}
+}
+
+/// Send all messages of the blocks the peer newly added to its view, except for the ones
+/// the peer already knows.
+async fn handle_peer_view_change<Context>(
+	ctx: &mut Context,
+	state: &mut State,
+	peer: PeerId,
+	view: View,
+) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message = ApprovalDistributionMessage>,
+{
+	let current = state.peer_views.entry(peer.clone()).or_default();
+	let added: Vec<Hash> = view.difference(&*current).cloned().collect();
+	*current = view;
+
+	let mut assignments = Vec::new();
+	let mut approvals = Vec::new();
+
+	for block_hash in added {
+		// A block is in the peers view, which is not in ours, ignore those.
+		let block_entry = match state.blocks.get_mut(&block_hash) {
+			Some(block_entry) => block_entry,
+			None => continue,
+		};
+
+		let known = block_entry.known_by.entry(peer.clone()).or_default();
+
+		for (&(candidate_index, validator), assignment) in &block_entry.assignments {
+			if known.insert(MessageFingerprint::Assignment(candidate_index, validator)) {
+				assignments.push((assignment.clone(), candidate_index));
+			}
+		}
+
+		for (&(candidate_index, validator), vote) in &block_entry.approvals {
+			if known.insert(MessageFingerprint::Approval(candidate_index, validator)) {
+				approvals.push(vote.clone());
+			}
+		}
+	}
+
+	// assignments go first, as approvals without an assignment are rejected.
+	if !assignments.is_empty() {
+		ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+			vec![peer.clone()],
+			protocol_v1::ValidationProtocol::ApprovalDistribution(
+				protocol_v1::ApprovalDistributionMessage::Assignments(assignments),
+			),
+		))).await?;
+	}
+
+	if !approvals.is_empty() {
+		ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+			vec![peer],
+			protocol_v1::ValidationProtocol::ApprovalDistribution(
+				protocol_v1::ApprovalDistributionMessage::Approvals(approvals),
+			),
+		))).await?;
+	}
+
+	Ok(())
+}
+
+impl<C> Subsystem<C> for ApprovalDistribution
+where
+	C: SubsystemContext<Message = ApprovalDistributionMessage> + Sync + Send,
+{
+	type Metrics = ();
+
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem {
+			name: "approval-distribution-subsystem",
+			future: Box::pin(Self::run(ctx).map(|_| ())),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{executor, future, Future};
+	use assert_matches::assert_matches;
+	use node_primitives::approval::{AssignmentCert, AssignmentCertKind, VRFOutput, VRFProof};
+	use polkadot_node_subsystem_test_helpers as test_helpers;
+	use polkadot_primitives::v1::CoreIndex;
+	use sp_keyring::Sr25519Keyring;
+
+	macro_rules! view {
+		( $( $hash:expr ),* $(,)? ) => [
+			View(vec![ $( $hash.clone() ),* ])
+		];
+	}
+
+	type VirtualOverseer = test_helpers::TestSubsystemContextHandle<ApprovalDistributionMessage>;
+
+	fn test_harness<T: Future<Output = ()>>(test: impl FnOnce(VirtualOverseer) -> T) {
+		let _ = env_logger::builder()
+			.filter(None, log::LevelFilter::Trace)
+			.is_test(true)
+			.try_init();
+
+		let pool = sp_core::testing::TaskExecutor::new();
+		let (context, virtual_overseer) = test_helpers::make_subsystem_context(pool);
+
+		let subsystem = ApprovalDistribution::run(context);
+		let test_fut = test(virtual_overseer);
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn fake_assignment(block_hash: Hash, validator: ValidatorIndex) -> IndirectAssignmentCert {
+		let pair = Sr25519Keyring::Alice.pair();
+		let keypair: &schnorrkel::Keypair = pair.as_ref();
+		let (inout, proof, _) = keypair.vrf_sign(merlin::Transcript::new(b"test"));
+
+		IndirectAssignmentCert {
+			block_hash,
+			validator,
+			cert: AssignmentCert {
+				kind: AssignmentCertKind::RelayVRFDelay { core_index: CoreIndex(0) },
+				vrf: (VRFOutput(inout.to_output()), VRFProof(proof)),
+			},
+		}
+	}
+
+	fn fake_approval(block_hash: Hash, validator: ValidatorIndex) -> IndirectSignedApprovalVote {
+		IndirectSignedApprovalVote {
+			block_hash,
+			candidate_index: 0,
+			validator,
+			signature: sp_core::sr25519::Signature([0u8; 64]).into(),
+		}
+	}
+
+	async fn network_event(
+		virtual_overseer: &mut VirtualOverseer,
+		event: NetworkBridgeEvent<protocol_v1::ApprovalDistributionMessage>,
+	) {
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: ApprovalDistributionMessage::NetworkBridgeUpdateV1(event),
+		}).await;
+	}
+
+	/// Make sure that the subsystem sent nothing else, relying on messages being handled in order:
+	/// an unexpected message from a peer is the next thing to be reported.
+	async fn expect_nothing_sent(virtual_overseer: &mut VirtualOverseer) {
+		let peer = PeerId::random();
+		let message = protocol_v1::ApprovalDistributionMessage::Approvals(
+			vec![fake_approval(Hash::repeat_byte(0xFF), 0)],
+		);
+		network_event(virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), message)).await;
+		expect_reputation_change(virtual_overseer, &peer, COST_UNEXPECTED_MESSAGE).await;
+	}
+
+	async fn expect_reputation_change(
+		virtual_overseer: &mut VirtualOverseer,
+		peer: &PeerId,
+		expected: ReputationChange,
+	) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep)) => {
+				assert_eq!(&p, peer);
+				assert_eq!(rep, expected);
+			}
+		);
+	}
+
+	#[test]
+	fn valid_assignment_is_forwarded_to_interested_peers() {
+		let hash_a = Hash::repeat_byte(0xAA);
+		let hash_b = Hash::repeat_byte(0xBB);
+		let peer_a = PeerId::random();
+		let peer_b = PeerId::random();
+		let peer_c = PeerId::random();
+
+		test_harness(|mut virtual_overseer| async move {
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::OurViewChange(view![hash_a])).await;
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_a.clone(), view![hash_a])).await;
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_b.clone(), view![hash_a])).await;
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_c.clone(), view![hash_b])).await;
+
+			let assignment = fake_assignment(hash_a, 1);
+			let message = protocol_v1::ApprovalDistributionMessage::Assignments(vec![(assignment.clone(), 0)]);
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_a.clone(), message.clone())).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::ApprovalVoting(ApprovalVotingMessage::CheckAndImportAssignment(a, 0, tx)) => {
+					assert_eq!(a, assignment);
+					tx.send(AssignmentCheckResult::Accepted).unwrap();
+				}
+			);
+
+			expect_reputation_change(&mut virtual_overseer, &peer_a, BENEFIT_VALID_MESSAGE_FIRST).await;
+
+			// peer_c isn't interested in the block and peer_a sent the assignment.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+					peers,
+					protocol_v1::ValidationProtocol::ApprovalDistribution(
+						protocol_v1::ApprovalDistributionMessage::Assignments(assignments),
+					),
+				)) => {
+					assert_eq!(peers, vec![peer_b.clone()]);
+					assert_eq!(assignments, vec![(assignment.clone(), 0)]);
+				}
+			);
+
+			// sending the same message twice is penalized.
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_a.clone(), message.clone())).await;
+			expect_reputation_change(&mut virtual_overseer, &peer_a, COST_PEER_DUPLICATE_MESSAGE).await;
+
+			// a message we already know is not checked again.
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_b.clone(), message)).await;
+			expect_reputation_change(&mut virtual_overseer, &peer_b, BENEFIT_VALID_MESSAGE).await;
+
+			// messages for blocks outside of our view are unexpected.
+			let message = protocol_v1::ApprovalDistributionMessage::Assignments(vec![(fake_assignment(hash_b, 1), 0)]);
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_c.clone(), message)).await;
+			expect_reputation_change(&mut virtual_overseer, &peer_c, COST_UNEXPECTED_MESSAGE).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn invalid_messages_are_penalized_and_not_forwarded() {
+		let hash_a = Hash::repeat_byte(0xAA);
+		let peer_a = PeerId::random();
+		let peer_b = PeerId::random();
+
+		test_harness(|mut virtual_overseer| async move {
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::OurViewChange(view![hash_a])).await;
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_a.clone(), view![hash_a])).await;
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_b.clone(), view![hash_a])).await;
+
+			let message = protocol_v1::ApprovalDistributionMessage::Assignments(vec![(fake_assignment(hash_a, 1), 0)]);
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_a.clone(), message)).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::ApprovalVoting(ApprovalVotingMessage::CheckAndImportAssignment(_, _, tx)) => {
+					tx.send(AssignmentCheckResult::TooFarInFuture).unwrap();
+				}
+			);
+			expect_reputation_change(&mut virtual_overseer, &peer_a, COST_ASSIGNMENT_TOO_FAR_IN_THE_FUTURE).await;
+
+			let message = protocol_v1::ApprovalDistributionMessage::Approvals(vec![fake_approval(hash_a, 1)]);
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_a.clone(), message)).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::ApprovalVoting(ApprovalVotingMessage::CheckAndImportApproval(_, tx)) => {
+					tx.send(ApprovalCheckResult::Bad).unwrap();
+				}
+			);
+			expect_reputation_change(&mut virtual_overseer, &peer_a, COST_INVALID_MESSAGE).await;
+
+			// nothing was forwarded to peer_b.
+			expect_nothing_sent(&mut virtual_overseer).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn local_messages_are_sent_to_peers_changing_their_view() {
+		let hash_a = Hash::repeat_byte(0xAA);
+		let peer_a = PeerId::random();
+
+		test_harness(|mut virtual_overseer| async move {
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::OurViewChange(view![hash_a])).await;
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerConnected(
+				peer_a.clone(),
+				polkadot_node_network_protocol::ObservedRole::Full,
+			)).await;
+
+			let assignment = fake_assignment(hash_a, 0);
+			let approval = fake_approval(hash_a, 0);
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: ApprovalDistributionMessage::DistributeAssignment(assignment.clone(), 0),
+			}).await;
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: ApprovalDistributionMessage::DistributeApproval(approval.clone()),
+			}).await;
+
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_a.clone(), view![hash_a])).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+					peers,
+					protocol_v1::ValidationProtocol::ApprovalDistribution(
+						protocol_v1::ApprovalDistributionMessage::Assignments(assignments),
+					),
+				)) => {
+					assert_eq!(peers, vec![peer_a.clone()]);
+					assert_eq!(assignments, vec![(assignment, 0)]);
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+					peers,
+					protocol_v1::ValidationProtocol::ApprovalDistribution(
+						protocol_v1::ApprovalDistributionMessage::Approvals(approvals),
+					),
+				)) => {
+					assert_eq!(peers, vec![peer_a.clone()]);
+					assert_eq!(approvals, vec![approval.clone()]);
+				}
+			);
+
+			// the peer already knows about the approval.
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_a.clone(), view![])).await;
+			network_event(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_a.clone(), view![hash_a])).await;
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: ApprovalDistributionMessage::DistributeApproval(approval),
+			}).await;
+			expect_nothing_sent(&mut virtual_overseer).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
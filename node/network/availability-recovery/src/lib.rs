@@ -0,0 +1,576 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Availability Recovery Subsystem of Polkadot.
+//!
+//! Recovers the `AvailableData` of candidates by requesting erasure chunks from the
+//! validators of the session the candidate was backed in, and serves the chunks held in
+//! the local availability store to other validators doing the same.
+
+#![deny(missing_docs)]
+
+use std::collections::{HashMap, HashSet, VecDeque, hash_map::Entry};
+use std::time::Duration;
+
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, BoxFuture};
+use futures::prelude::*;
+use futures::select;
+use futures::stream::FuturesUnordered;
+use futures_timer::Delay;
+
+use polkadot_erasure_coding::{branch_hash, branches, obtain_chunks_v1, reconstruct_v1, recovery_threshold};
+use polkadot_primitives::v1::{
+	AvailableData, BlakeTwo256, CandidateReceipt, ErasureChunk, Hash, HashT, SessionIndex,
+	ValidatorId, ValidatorIndex,
+};
+use polkadot_subsystem::{
+	FromOverseer, OverseerSignal, SpawnedSubsystem, Subsystem, SubsystemContext, SubsystemError,
+	errors::{RecoveryError, RuntimeApiError},
+	messages::{
+		AllMessages, AvailabilityRecoveryMessage, AvailabilityStoreMessage, NetworkBridgeMessage,
+		RuntimeApiMessage, RuntimeApiRequest, RuntimeApiSender,
+	},
+};
+use polkadot_node_network_protocol::{
+	v1 as protocol_v1, NetworkBridgeEvent, PeerId, PeerSet, ReputationChange as Rep, RequestId,
+};
+
+#[cfg(test)]
+mod tests;
+
+const LOG_TARGET: &str = "availability_recovery";
+
+const COST_MERKLE_PROOF_INVALID: Rep = Rep::new(-100, "Merkle proof was invalid");
+const COST_UNEXPECTED_CHUNK: Rep = Rep::new(-100, "Peer sent an unrequested chunk");
+
+/// The number of chunk requests an interaction keeps in flight at once.
+const N_PARALLEL: usize = 50;
+
+/// The time to wait for the response to a chunk request. Afterwards, the chunk is considered
+/// unavailable from that validator.
+const CHUNK_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The time after which an interaction gives up on recovering the data.
+const RECOVERY_TIMEOUT: Duration = Duration::from_secs(60);
+
+const INTERACTION_CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Debug, derive_more::From)]
+enum Error {
+	#[from]
+	Oneshot(oneshot::Canceled),
+	#[from]
+	RuntimeApi(RuntimeApiError),
+	#[from]
+	Subsystem(SubsystemError),
+}
+
+/// The availability recovery subsystem.
+pub struct AvailabilityRecoverySubsystem;
+
+impl<C> Subsystem<C> for AvailabilityRecoverySubsystem
+	where C: SubsystemContext<Message = AvailabilityRecoveryMessage>
+{
+	type Metrics = ();
+
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		let future = Box::pin(async move {
+			if let Err(e) = run(ctx).await {
+				log::error!(target: LOG_TARGET, "Subsystem exited with an error {:?}", e);
+			}
+		});
+
+		SpawnedSubsystem {
+			name: "availability-recovery-subsystem",
+			future,
+		}
+	}
+}
+
+/// Messages sent to the main loop by the interactions.
+enum FromInteraction {
+	/// A message to send to another subsystem.
+	Message(AllMessages),
+	/// Request the chunk of a candidate held by the validator with the given index from the
+	/// given peer. The response is `None` if the peer doesn't have the chunk.
+	MakeRequest(PeerId, Hash, ValidatorIndex, oneshot::Sender<Option<ErasureChunk>>),
+	/// The interaction recovering the data of a candidate concluded.
+	Concluded(Hash, Result<AvailableData, RecoveryError>),
+}
+
+/// A chunk request awaiting the response of a peer.
+struct AwaitedChunk {
+	peer: PeerId,
+	response: oneshot::Sender<Option<ErasureChunk>>,
+}
+
+#[derive(Default)]
+struct State {
+	/// The ongoing recoveries along with everybody waiting for their result, by candidate hash.
+	interactions: HashMap<Hash, Vec<oneshot::Sender<Result<AvailableData, RecoveryError>>>>,
+	/// The chunk requests made on behalf of the interactions, by request ID.
+	live_requests: HashMap<RequestId, AwaitedChunk>,
+	next_request_id: RequestId,
+}
+
+/// The recovery of the available data of a single candidate, running as a separate task.
+struct Interaction {
+	to_state: mpsc::Sender<FromInteraction>,
+	candidate_hash: Hash,
+	erasure_root: Hash,
+	/// The validators of the session the candidate was backed in.
+	validators: Vec<ValidatorId>,
+	/// The number of chunks needed to reconstruct the data.
+	threshold: usize,
+	/// The validators we connected to, whether their chunk was requested yet or not.
+	connected: HashSet<ValidatorIndex>,
+	/// The validators we connected to but haven't requested the chunk from yet.
+	to_request: VecDeque<(ValidatorIndex, PeerId)>,
+	received_chunks: HashMap<ValidatorIndex, ErasureChunk>,
+}
+
+impl Interaction {
+	async fn run(mut self) {
+		let result = self.recover().await;
+
+		let _ = self.to_state.send(FromInteraction::Concluded(self.candidate_hash, result)).await;
+	}
+
+	async fn recover(&mut self) -> Result<AvailableData, RecoveryError> {
+		// we might hold the full data already, e.g. because we backed the candidate.
+		if let Some(available_data) = self.query_full_data().await? {
+			return Ok(available_data);
+		}
+
+		// the connections are kept for as long as the receiver is alive.
+		let (connected_tx, mut connected_rx) = mpsc::unbounded();
+		self.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::ConnectToValidators(
+			PeerSet::Validation,
+			self.validators.clone(),
+			connected_tx,
+		))).await?;
+
+		let mut requesting_chunks = FuturesUnordered::new();
+		let mut timeout = Delay::new(RECOVERY_TIMEOUT).fuse();
+
+		loop {
+			if self.received_chunks.len() >= self.threshold {
+				return self.reconstruct();
+			}
+
+			let not_yet_connected = self.validators.len() - self.connected.len();
+			let pending = self.to_request.len() + requesting_chunks.len();
+			if self.received_chunks.len() + not_yet_connected + pending < self.threshold {
+				log::debug!(
+					target: LOG_TARGET,
+					"Recovered only {} of {} needed chunks of candidate {:?}",
+					self.received_chunks.len(),
+					self.threshold,
+					self.candidate_hash,
+				);
+
+				return Err(RecoveryError::Unavailable);
+			}
+
+			while requesting_chunks.len() < N_PARALLEL {
+				let (validator_index, peer) = match self.to_request.pop_front() {
+					Some(next) => next,
+					None => break,
+				};
+
+				requesting_chunks.push(self.request_chunk(validator_index, peer).await?);
+			}
+
+			select! {
+				connected = connected_rx.next() => {
+					if let Some((validator_id, peer)) = connected {
+						self.note_connected(validator_id, peer);
+					}
+				}
+				response = requesting_chunks.select_next_some() => {
+					let (validator_index, peer, chunk) = response;
+					self.handle_response(validator_index, peer, chunk).await?;
+				}
+				_ = timeout => {
+					log::debug!(
+						target: LOG_TARGET,
+						"Timed out recovering candidate {:?}",
+						self.candidate_hash,
+					);
+
+					return Err(RecoveryError::Unavailable);
+				}
+			}
+		}
+	}
+
+	fn note_connected(&mut self, validator_id: ValidatorId, peer: PeerId) {
+		let validator_index = match self.validators.iter().position(|v| v == &validator_id) {
+			Some(index) => index as ValidatorIndex,
+			None => return,
+		};
+
+		if self.connected.insert(validator_index) {
+			self.to_request.push_back((validator_index, peer));
+		}
+	}
+
+	async fn request_chunk(
+		&mut self,
+		validator_index: ValidatorIndex,
+		peer: PeerId,
+	) -> Result<BoxFuture<'static, (ValidatorIndex, PeerId, Option<ErasureChunk>)>, RecoveryError> {
+		let (tx, rx) = oneshot::channel();
+		self.to_state.send(FromInteraction::MakeRequest(
+			peer.clone(),
+			self.candidate_hash,
+			validator_index,
+			tx,
+		)).await.map_err(|_| RecoveryError::Unavailable)?;
+
+		Ok(async move {
+			// a peer disconnecting drops the request, which counts as not having the chunk.
+			let chunk = match future::select(rx, Delay::new(CHUNK_REQUEST_TIMEOUT)).await {
+				future::Either::Left((Ok(chunk), _)) => chunk,
+				_ => None,
+			};
+
+			(validator_index, peer, chunk)
+		}.boxed())
+	}
+
+	async fn handle_response(
+		&mut self,
+		validator_index: ValidatorIndex,
+		peer: PeerId,
+		chunk: Option<ErasureChunk>,
+	) -> Result<(), RecoveryError> {
+		let chunk = match chunk {
+			Some(chunk) => chunk,
+			None => return Ok(()),
+		};
+
+		if chunk.index == validator_index && is_chunk_valid(&self.erasure_root, &chunk) {
+			self.received_chunks.insert(validator_index, chunk);
+		} else {
+			self.send_message(AllMessages::NetworkBridge(
+				NetworkBridgeMessage::ReportPeer(peer, COST_MERKLE_PROOF_INVALID),
+			)).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Reconstruct the data from the received chunks and check that it is encoded to the
+	/// erasure root of the candidate.
+	fn reconstruct(&self) -> Result<AvailableData, RecoveryError> {
+		let n_validators = self.validators.len();
+
+		let available_data = reconstruct_v1(
+			n_validators,
+			self.received_chunks.values().map(|c| (&c.chunk[..], c.index as usize)),
+		).map_err(|e| {
+			log::debug!(
+				target: LOG_TARGET,
+				"Failed to reconstruct the data of candidate {:?}: {:?}",
+				self.candidate_hash,
+				e,
+			);
+
+			RecoveryError::Invalid
+		})?;
+
+		// every chunk is in the tree of the erasure root, but only a correct encoding of
+		// the data reproduces the whole tree.
+		let chunks = obtain_chunks_v1(n_validators, &available_data)
+			.map_err(|_| RecoveryError::Invalid)?;
+
+		if branches(&chunks).root() == self.erasure_root {
+			Ok(available_data)
+		} else {
+			Err(RecoveryError::Invalid)
+		}
+	}
+
+	async fn query_full_data(&mut self) -> Result<Option<AvailableData>, RecoveryError> {
+		let (tx, rx) = oneshot::channel();
+		self.send_message(AllMessages::AvailabilityStore(
+			AvailabilityStoreMessage::QueryAvailableData(self.candidate_hash, tx),
+		)).await?;
+
+		Ok(rx.await.ok().flatten())
+	}
+
+	async fn send_message(&mut self, msg: AllMessages) -> Result<(), RecoveryError> {
+		self.to_state.send(FromInteraction::Message(msg)).await.map_err(|_| RecoveryError::Unavailable)
+	}
+}
+
+/// Check the Merkle proof of a chunk against the erasure root.
+fn is_chunk_valid(erasure_root: &Hash, chunk: &ErasureChunk) -> bool {
+	match branch_hash(erasure_root, &chunk.proof, chunk.index as usize) {
+		Ok(anticipated_hash) => anticipated_hash == BlakeTwo256::hash(&chunk.chunk),
+		Err(_) => false,
+	}
+}
+
+async fn run<Context>(mut ctx: Context) -> Result<(), Error>
+where
+	Context: SubsystemContext<Message = AvailabilityRecoveryMessage>,
+{
+	let (from_interaction_tx, mut from_interaction_rx) = mpsc::channel(INTERACTION_CHANNEL_CAPACITY);
+	let mut state = State::default();
+
+	loop {
+		select! {
+			incoming = ctx.recv().fuse() => {
+				match incoming? {
+					FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+					FromOverseer::Signal(_) => {}
+					FromOverseer::Communication {
+						msg: AvailabilityRecoveryMessage::RecoverAvailableData(receipt, session, response),
+					} => {
+						handle_recover(
+							&mut ctx,
+							&mut state,
+							&from_interaction_tx,
+							receipt,
+							session,
+							response,
+						).await?;
+					}
+					FromOverseer::Communication {
+						msg: AvailabilityRecoveryMessage::NetworkBridgeUpdateV1(event),
+					} => {
+						handle_network_update(&mut ctx, &mut state, event).await?;
+					}
+				}
+			}
+			from_interaction = from_interaction_rx.next() => {
+				match from_interaction {
+					Some(FromInteraction::Message(msg)) => ctx.send_message(msg).await?,
+					Some(FromInteraction::MakeRequest(peer, candidate_hash, validator_index, response)) => {
+						make_request(&mut ctx, &mut state, peer, candidate_hash, validator_index, response).await?;
+					}
+					Some(FromInteraction::Concluded(candidate_hash, result)) => {
+						for response in state.interactions.remove(&candidate_hash).into_iter().flatten() {
+							let _ = response.send(result.clone());
+						}
+					}
+					None => return Ok(()),
+				}
+			}
+		}
+	}
+}
+
+async fn handle_recover<Context>(
+	ctx: &mut Context,
+	state: &mut State,
+	from_interaction_tx: &mpsc::Sender<FromInteraction>,
+	receipt: CandidateReceipt,
+	session: SessionIndex,
+	response: oneshot::Sender<Result<AvailableData, RecoveryError>>,
+) -> Result<(), Error>
+where
+	Context: SubsystemContext<Message = AvailabilityRecoveryMessage>,
+{
+	let candidate_hash = receipt.hash();
+
+	if let Some(awaiting) = state.interactions.get_mut(&candidate_hash) {
+		awaiting.push(response);
+		return Ok(());
+	}
+
+	let relay_parent = receipt.descriptor.relay_parent;
+	let validators = match query_session_validators(ctx, relay_parent, session).await {
+		Ok(Some(validators)) => validators,
+		Ok(None) => {
+			log::debug!(
+				target: LOG_TARGET,
+				"Candidate {:?} wasn't backed in session {}",
+				candidate_hash,
+				session,
+			);
+
+			let _ = response.send(Err(RecoveryError::Unavailable));
+			return Ok(());
+		}
+		Err(e) => {
+			log::warn!(
+				target: LOG_TARGET,
+				"Failed to fetch the validators of session {}: {:?}",
+				session,
+				e,
+			);
+
+			let _ = response.send(Err(RecoveryError::Unavailable));
+			return Ok(());
+		}
+	};
+
+	let threshold = match recovery_threshold(validators.len()) {
+		Ok(threshold) => threshold,
+		Err(e) => {
+			log::warn!(target: LOG_TARGET, "Can't recover data for {} validators: {:?}", validators.len(), e);
+
+			let _ = response.send(Err(RecoveryError::Unavailable));
+			return Ok(());
+		}
+	};
+
+	let interaction = Interaction {
+		to_state: from_interaction_tx.clone(),
+		candidate_hash,
+		erasure_root: receipt.descriptor.erasure_root,
+		validators,
+		threshold,
+		connected: HashSet::new(),
+		to_request: VecDeque::new(),
+		received_chunks: HashMap::new(),
+	};
+
+	state.interactions.insert(candidate_hash, vec![response]);
+	ctx.spawn("recovery-interaction", interaction.run().boxed()).await?;
+
+	Ok(())
+}
+
+async fn make_request<Context>(
+	ctx: &mut Context,
+	state: &mut State,
+	peer: PeerId,
+	candidate_hash: Hash,
+	validator_index: ValidatorIndex,
+	response: oneshot::Sender<Option<ErasureChunk>>,
+) -> Result<(), Error>
+where
+	Context: SubsystemContext<Message = AvailabilityRecoveryMessage>,
+{
+	// forget about the requests the interactions stopped waiting for.
+	state.live_requests.retain(|_, awaited| !awaited.response.is_canceled());
+
+	let request_id = state.next_request_id;
+	state.next_request_id += 1;
+	state.live_requests.insert(request_id, AwaitedChunk { peer: peer.clone(), response });
+
+	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+		vec![peer],
+		protocol_v1::ValidationProtocol::AvailabilityRecovery(
+			protocol_v1::AvailabilityRecoveryMessage::RequestChunk(request_id, candidate_hash, validator_index),
+		),
+	))).await?;
+
+	Ok(())
+}
+
+async fn handle_network_update<Context>(
+	ctx: &mut Context,
+	state: &mut State,
+	event: NetworkBridgeEvent<protocol_v1::AvailabilityRecoveryMessage>,
+) -> Result<(), Error>
+where
+	Context: SubsystemContext<Message = AvailabilityRecoveryMessage>,
+{
+	match event {
+		NetworkBridgeEvent::PeerDisconnected(peer) => {
+			// dropping the response channels lets the interactions know the chunks won't arrive.
+			state.live_requests.retain(|_, awaited| awaited.peer != peer);
+		}
+		NetworkBridgeEvent::PeerMessage(peer, message) => match message {
+			protocol_v1::AvailabilityRecoveryMessage::RequestChunk(request_id, candidate_hash, validator_index) => {
+				let chunk = query_chunk(ctx, candidate_hash, validator_index).await?;
+
+				ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+					vec![peer],
+					protocol_v1::ValidationProtocol::AvailabilityRecovery(
+						protocol_v1::AvailabilityRecoveryMessage::Chunk(request_id, chunk),
+					),
+				))).await?;
+			}
+			protocol_v1::AvailabilityRecoveryMessage::Chunk(request_id, chunk) => {
+				match state.live_requests.entry(request_id) {
+					Entry::Occupied(entry) if entry.get().peer == peer => {
+						let _ = entry.remove().response.send(chunk);
+					}
+					_ => {
+						ctx.send_message(AllMessages::NetworkBridge(
+							NetworkBridgeMessage::ReportPeer(peer, COST_UNEXPECTED_CHUNK),
+						)).await?;
+					}
+				}
+			}
+		},
+		NetworkBridgeEvent::PeerConnected(_, _)
+			| NetworkBridgeEvent::PeerViewChange(_, _)
+			| NetworkBridgeEvent::OurViewChange(_) => {}
+	}
+
+	Ok(())
+}
+
+/// Get the validators of the given session, as of the relay-parent of a candidate backed in it.
+///
+/// Returns `None` if the candidate can't have been backed in the session.
+async fn query_session_validators<Context>(
+	ctx: &mut Context,
+	relay_parent: Hash,
+	session: SessionIndex,
+) -> Result<Option<Vec<ValidatorId>>, Error>
+where
+	Context: SubsystemContext<Message = AvailabilityRecoveryMessage>,
+{
+	let backed_in = request_runtime(ctx, relay_parent, RuntimeApiRequest::SessionIndexForChild).await?;
+	if backed_in != session {
+		return Ok(None);
+	}
+
+	Ok(Some(request_runtime(ctx, relay_parent, RuntimeApiRequest::Validators).await?))
+}
+
+async fn query_chunk<Context>(
+	ctx: &mut Context,
+	candidate_hash: Hash,
+	validator_index: ValidatorIndex,
+) -> Result<Option<ErasureChunk>, Error>
+where
+	Context: SubsystemContext<Message = AvailabilityRecoveryMessage>,
+{
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::AvailabilityStore(
+		AvailabilityStoreMessage::QueryChunk(candidate_hash, validator_index, tx),
+	)).await?;
+
+	Ok(rx.await?)
+}
+
+async fn request_runtime<Context, T>(
+	ctx: &mut Context,
+	relay_parent: Hash,
+	request: impl FnOnce(RuntimeApiSender<T>) -> RuntimeApiRequest,
+) -> Result<T, Error>
+where
+	Context: SubsystemContext<Message = AvailabilityRecoveryMessage>,
+{
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		relay_parent,
+		request(tx),
+	))).await?;
+
+	Ok(rx.await??)
+}
@@ -0,0 +1,337 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::*;
+
+use assert_matches::assert_matches;
+use futures::executor;
+use sp_keyring::Sr25519Keyring;
+
+use polkadot_node_subsystem_test_helpers as test_helpers;
+use polkadot_primitives::v1::{BlockData, CandidateDescriptor, PersistedValidationData, PoV};
+
+type VirtualOverseer = test_helpers::TestSubsystemContextHandle<AvailabilityRecoveryMessage>;
+
+const SESSION: SessionIndex = 1;
+
+fn validators() -> Vec<ValidatorId> {
+	vec![
+		Sr25519Keyring::Alice.public().into(),
+		Sr25519Keyring::Bob.public().into(),
+		Sr25519Keyring::Charlie.public().into(),
+		Sr25519Keyring::Dave.public().into(),
+	]
+}
+
+fn available_data() -> AvailableData {
+	AvailableData {
+		pov: PoV { block_data: BlockData(vec![42; 64]) },
+		validation_data: PersistedValidationData::default(),
+	}
+}
+
+fn chunks_with_proofs(chunks: &[Vec<u8>]) -> Vec<ErasureChunk> {
+	branches(chunks)
+		.enumerate()
+		.map(|(index, (proof, chunk))| ErasureChunk {
+			chunk: chunk.to_vec(),
+			index: index as _,
+			proof,
+		})
+		.collect()
+}
+
+fn make_receipt(erasure_root: Hash) -> CandidateReceipt {
+	CandidateReceipt {
+		descriptor: CandidateDescriptor {
+			relay_parent: Hash::repeat_byte(1),
+			erasure_root,
+			..Default::default()
+		},
+		commitments_hash: Default::default(),
+	}
+}
+
+fn test_harness<T: Future<Output = ()>>(test: impl FnOnce(VirtualOverseer) -> T) {
+	let _ = env_logger::builder()
+		.is_test(true)
+		.filter(Some(LOG_TARGET), log::LevelFilter::Trace)
+		.try_init();
+
+	let pool = sp_core::testing::TaskExecutor::new();
+	let (context, virtual_overseer) = test_helpers::make_subsystem_context(pool.clone());
+
+	let subsystem = run(context).map(|_| ());
+	let test_fut = test(virtual_overseer);
+
+	futures::pin_mut!(test_fut);
+	futures::pin_mut!(subsystem);
+
+	executor::block_on(future::select(test_fut, subsystem));
+}
+
+/// Request the recovery of the candidate and answer the requests of the subsystem up to the
+/// chunk requests, which are returned along with the peers they were sent to.
+async fn start_recovery(
+	virtual_overseer: &mut VirtualOverseer,
+	receipt: &CandidateReceipt,
+) -> (oneshot::Receiver<Result<AvailableData, RecoveryError>>, Vec<(PeerId, RequestId, ValidatorIndex)>) {
+	let (tx, rx) = oneshot::channel();
+	virtual_overseer.send(FromOverseer::Communication {
+		msg: AvailabilityRecoveryMessage::RecoverAvailableData(receipt.clone(), SESSION, tx),
+	}).await;
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			relay_parent,
+			RuntimeApiRequest::SessionIndexForChild(tx),
+		)) => {
+			assert_eq!(relay_parent, receipt.descriptor.relay_parent);
+			tx.send(Ok(SESSION)).unwrap();
+		}
+	);
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			relay_parent,
+			RuntimeApiRequest::Validators(tx),
+		)) => {
+			assert_eq!(relay_parent, receipt.descriptor.relay_parent);
+			tx.send(Ok(validators())).unwrap();
+		}
+	);
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::AvailabilityStore(AvailabilityStoreMessage::QueryAvailableData(hash, tx)) => {
+			assert_eq!(hash, receipt.hash());
+			tx.send(None).unwrap();
+		}
+	);
+
+	let peers: Vec<_> = validators().iter().map(|_| PeerId::random()).collect();
+
+	assert_matches!(
+		virtual_overseer.recv().await,
+		AllMessages::NetworkBridge(NetworkBridgeMessage::ConnectToValidators(
+			PeerSet::Validation,
+			connect_to,
+			connected,
+		)) => {
+			assert_eq!(connect_to, validators());
+			for (validator, peer) in connect_to.into_iter().zip(peers.iter()) {
+				connected.unbounded_send((validator, peer.clone())).unwrap();
+			}
+		}
+	);
+
+	let mut requests = Vec::new();
+	for _ in 0..peers.len() {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+				to,
+				protocol_v1::ValidationProtocol::AvailabilityRecovery(
+					protocol_v1::AvailabilityRecoveryMessage::RequestChunk(request_id, hash, validator_index),
+				),
+			)) => {
+				assert_eq!(to.len(), 1);
+				assert_eq!(to[0], peers[validator_index as usize]);
+				assert_eq!(hash, receipt.hash());
+				requests.push((to[0].clone(), request_id, validator_index));
+			}
+		);
+	}
+
+	(rx, requests)
+}
+
+async fn respond(
+	virtual_overseer: &mut VirtualOverseer,
+	peer: PeerId,
+	request_id: RequestId,
+	chunk: Option<ErasureChunk>,
+) {
+	virtual_overseer.send(FromOverseer::Communication {
+		msg: AvailabilityRecoveryMessage::NetworkBridgeUpdateV1(NetworkBridgeEvent::PeerMessage(
+			peer,
+			protocol_v1::AvailabilityRecoveryMessage::Chunk(request_id, chunk),
+		)),
+	}).await;
+}
+
+#[test]
+fn data_is_recovered_from_chunks() {
+	test_harness(|mut virtual_overseer| async move {
+		let chunks = obtain_chunks_v1(validators().len(), &available_data()).unwrap();
+		let receipt = make_receipt(branches(&chunks).root());
+		let chunks = chunks_with_proofs(&chunks);
+
+		let (rx, requests) = start_recovery(&mut virtual_overseer, &receipt).await;
+		for (peer, request_id, validator_index) in requests {
+			respond(&mut virtual_overseer, peer, request_id, Some(chunks[validator_index as usize].clone())).await;
+		}
+
+		assert_eq!(rx.await.unwrap(), Ok(available_data()));
+	});
+}
+
+#[test]
+fn data_is_unavailable_when_validators_lack_chunks() {
+	test_harness(|mut virtual_overseer| async move {
+		let chunks = obtain_chunks_v1(validators().len(), &available_data()).unwrap();
+		let receipt = make_receipt(branches(&chunks).root());
+		let chunks = chunks_with_proofs(&chunks);
+
+		let (rx, requests) = start_recovery(&mut virtual_overseer, &receipt).await;
+		for (i, (peer, request_id, validator_index)) in requests.into_iter().enumerate() {
+			// only a single validator has its chunk, which is below the threshold.
+			let chunk = if i == 0 { Some(chunks[validator_index as usize].clone()) } else { None };
+			respond(&mut virtual_overseer, peer, request_id, chunk).await;
+		}
+
+		assert_eq!(rx.await.unwrap(), Err(RecoveryError::Unavailable));
+	});
+}
+
+#[test]
+fn chunks_with_invalid_proofs_are_reported() {
+	test_harness(|mut virtual_overseer| async move {
+		let chunks = obtain_chunks_v1(validators().len(), &available_data()).unwrap();
+		let receipt = make_receipt(branches(&chunks).root());
+		let chunks = chunks_with_proofs(&chunks);
+
+		let (rx, requests) = start_recovery(&mut virtual_overseer, &receipt).await;
+
+		// the recovery is given up once the remaining request can't reach the threshold.
+		for (peer, request_id, validator_index) in requests.into_iter().take(3) {
+			let mut chunk = chunks[validator_index as usize].clone();
+			chunk.chunk[0] ^= 0xff;
+			respond(&mut virtual_overseer, peer.clone(), request_id, Some(chunk)).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep)) => {
+					assert_eq!(p, peer);
+					assert_eq!(rep, COST_MERKLE_PROOF_INVALID);
+				}
+			);
+		}
+
+		assert_eq!(rx.await.unwrap(), Err(RecoveryError::Unavailable));
+	});
+}
+
+#[test]
+fn data_not_matching_the_erasure_root_is_invalid() {
+	test_harness(|mut virtual_overseer| async move {
+		// chunks which don't encode the data, but are in the tree of the erasure root.
+		let mut garbage = obtain_chunks_v1(validators().len(), &available_data()).unwrap();
+		for chunk in &mut garbage {
+			chunk.iter_mut().for_each(|b| *b = b.wrapping_add(1));
+		}
+		let receipt = make_receipt(branches(&garbage).root());
+		let chunks = chunks_with_proofs(&garbage);
+
+		let (rx, requests) = start_recovery(&mut virtual_overseer, &receipt).await;
+		for (peer, request_id, validator_index) in requests {
+			respond(&mut virtual_overseer, peer, request_id, Some(chunks[validator_index as usize].clone())).await;
+		}
+
+		assert_eq!(rx.await.unwrap(), Err(RecoveryError::Invalid));
+	});
+}
+
+#[test]
+fn recovery_in_another_session_is_unavailable() {
+	test_harness(|mut virtual_overseer| async move {
+		let receipt = make_receipt(Default::default());
+
+		let (tx, rx) = oneshot::channel();
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: AvailabilityRecoveryMessage::RecoverAvailableData(receipt.clone(), SESSION, tx),
+		}).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				_,
+				RuntimeApiRequest::SessionIndexForChild(tx),
+			)) => {
+				tx.send(Ok(SESSION + 1)).unwrap();
+			}
+		);
+
+		assert_eq!(rx.await.unwrap(), Err(RecoveryError::Unavailable));
+	});
+}
+
+#[test]
+fn chunk_requests_are_served_from_the_store() {
+	test_harness(|mut virtual_overseer| async move {
+		let chunks = obtain_chunks_v1(validators().len(), &available_data()).unwrap();
+		let chunk = chunks_with_proofs(&chunks)[2].clone();
+		let candidate_hash = Hash::repeat_byte(2);
+		let peer = PeerId::random();
+
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: AvailabilityRecoveryMessage::NetworkBridgeUpdateV1(NetworkBridgeEvent::PeerMessage(
+				peer.clone(),
+				protocol_v1::AvailabilityRecoveryMessage::RequestChunk(7, candidate_hash, 2),
+			)),
+		}).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::AvailabilityStore(AvailabilityStoreMessage::QueryChunk(hash, 2, tx)) => {
+				assert_eq!(hash, candidate_hash);
+				tx.send(Some(chunk.clone())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
+				to,
+				protocol_v1::ValidationProtocol::AvailabilityRecovery(
+					protocol_v1::AvailabilityRecoveryMessage::Chunk(7, Some(c)),
+				),
+			)) => {
+				assert_eq!(to, vec![peer]);
+				assert_eq!(c, chunk);
+			}
+		);
+	});
+}
+
+#[test]
+fn unrequested_chunks_are_reported() {
+	test_harness(|mut virtual_overseer| async move {
+		let peer = PeerId::random();
+
+		respond(&mut virtual_overseer, peer.clone(), 1, None).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep)) => {
+				assert_eq!(p, peer);
+				assert_eq!(rep, COST_UNEXPECTED_CHUNK);
+			}
+		);
+	});
+}
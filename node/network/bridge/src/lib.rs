@@ -20,9 +20,10 @@ use parity_scale_codec::{Encode, Decode};
 use futures::prelude::*;
 use futures::future::BoxFuture;
 use futures::stream::BoxStream;
-use futures::channel::oneshot;
+use futures::channel::{mpsc, oneshot};
 
 use sc_network::Event as NetworkEvent;
+use sc_network::multiaddr::Multiaddr;
 use sp_runtime::ConsensusEngineId;
 
 use polkadot_subsystem::{
@@ -32,17 +33,26 @@ use polkadot_subsystem::{
 use polkadot_subsystem::messages::{
 	NetworkBridgeMessage, AllMessages, AvailabilityDistributionMessage,
 	BitfieldDistributionMessage, PoVDistributionMessage, StatementDistributionMessage,
-	CollatorProtocolMessage,
+	CollatorProtocolMessage, RuntimeApiMessage, RuntimeApiRequest, ApprovalDistributionMessage,
+	AvailabilityRecoveryMessage, PeerSetManagerMessage,
 };
-use polkadot_primitives::v1::{Block, Hash, ValidatorId};
+use polkadot_primitives::v1::{AuthorityDiscoveryId, Block, Hash, ValidatorId};
 use polkadot_node_network_protocol::{
-	ObservedRole, ReputationChange, PeerId, PeerSet, View, NetworkBridgeEvent, v1 as protocol_v1
+	ObservedRole, ReputationChange, PeerId, PeerSet, View, NetworkBridgeEvent, v1 as protocol_v1,
+	request_response::{OutgoingRequest, Requests},
 };
 
-use std::collections::hash_map::{HashMap, Entry as HEntry};
+use std::collections::{HashSet, hash_map::{HashMap, Entry as HEntry}};
 use std::iter::ExactSizeIterator;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
+
+mod multiplexer;
+mod validator_discovery;
+
+pub use multiplexer::{RequestMultiplexer, RequestMultiplexError};
+pub use validator_discovery::AuthorityDiscovery;
 
 /// The maximum amount of heads a peer is allowed to have in their view at any time.
 ///
@@ -64,10 +74,20 @@ const UNCONNECTED_PEERSET_COST: ReputationChange
 	= ReputationChange::new(-50, "Message sent to un-connected peer-set");
 const MALFORMED_VIEW_COST: ReputationChange
 	= ReputationChange::new(-500, "Malformed view");
+const BANNED_COLLATOR_COST: ReputationChange
+	= ReputationChange::new(i32::MIN, "Banned collator");
+const BENEFIT_ALLOWED_COLLATOR: ReputationChange
+	= ReputationChange::new(100, "Collator in good standing");
 
 // network bridge log target
 const TARGET: &'static str = "network_bridge";
 
+/// How often to query the addresses of requested validators unknown to authority discovery again.
+#[cfg(not(test))]
+const DISCOVERY_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+#[cfg(test)]
+const DISCOVERY_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Messages received on the network.
 #[derive(Debug, Encode, Decode, Clone)]
 pub enum WireMessage<M> {
@@ -95,6 +115,18 @@ pub enum NetworkAction {
 	ReputationChange(PeerId, ReputationChange),
 	/// Write a notification to a given peer on the given peer-set.
 	WriteNotification(PeerId, PeerSet, Vec<u8>),
+	/// Add the given addresses to the reserved peers of the given peer-set.
+	AddToPeerSet(PeerSet, HashSet<Multiaddr>),
+	/// Remove the given addresses from the reserved peers of the given peer-set.
+	RemoveFromPeerSet(PeerSet, HashSet<Multiaddr>),
+}
+
+/// The name of the priority group used to keep the validators requested on a peer-set.
+fn priority_group_name(peer_set: PeerSet) -> String {
+	match peer_set {
+		PeerSet::Validation => format!("{}/validators", VALIDATION_PROTOCOL_NAME),
+		PeerSet::Collation => format!("{}/validators", COLLATION_PROTOCOL_NAME),
+	}
 }
 
 /// An abstraction over networking for the purposes of this subsystem.
@@ -127,6 +159,12 @@ pub trait Network: Send + 'static {
 			self.action_sink().send(NetworkAction::WriteNotification(who, peer_set, message)).await
 		}.boxed()
 	}
+
+	/// Send a request to a peer on the request's protocol.
+	///
+	/// The response, or the reason for not receiving one, is sent on the channel contained in the
+	/// request.
+	fn start_request(&self, request: Requests);
 }
 
 impl Network for Arc<sc_network::NetworkService<Block, Hash>> {
@@ -169,6 +207,24 @@ impl Network for Arc<sc_network::NetworkService<Block, Hash>> {
 							),
 						}
 					}
+					NetworkAction::AddToPeerSet(peer_set, addresses) => {
+						let res = self.0.add_to_priority_group(
+							priority_group_name(peer_set),
+							addresses,
+						);
+						if let Err(e) = res {
+							log::warn!(target: TARGET, "Failed to add validators to {:?}: {}", peer_set, e);
+						}
+					}
+					NetworkAction::RemoveFromPeerSet(peer_set, addresses) => {
+						let res = self.0.remove_from_priority_group(
+							priority_group_name(peer_set),
+							addresses,
+						);
+						if let Err(e) = res {
+							log::warn!(target: TARGET, "Failed to remove validators from {:?}: {}", peer_set, e);
+						}
+					}
 				}
 
 				Ok(())
@@ -185,24 +241,40 @@ impl Network for Arc<sc_network::NetworkService<Block, Hash>> {
 
 		Box::pin(ActionSink(&**self))
 	}
+
+	fn start_request(&self, request: Requests) {
+		let (protocol, OutgoingRequest { peer, payload, pending_response }) = request.encode_request();
+
+		sc_network::NetworkService::start_request(
+			&**self,
+			peer,
+			protocol.into_protocol_name(),
+			payload,
+			pending_response,
+		);
+	}
 }
 
 /// The network bridge subsystem.
-pub struct NetworkBridge<N>(N);
+pub struct NetworkBridge<N, AD>(N, AD, RequestMultiplexer);
 
-impl<N> NetworkBridge<N> {
-	/// Create a new network bridge subsystem with underlying network service.
+impl<N, AD> NetworkBridge<N, AD> {
+	/// Create a new network bridge subsystem with underlying network service, authority
+	/// discovery service and multiplexer of incoming requests.
 	///
 	/// This assumes that the network service has had the notifications protocol for the network
 	/// bridge already registered. See [`notifications_protocol_info`](notifications_protocol_info).
-	pub fn new(net_service: N) -> Self {
-		NetworkBridge(net_service)
+	/// The same goes for the request/response protocols, whose configurations are obtained with
+	/// the multiplexer. See [`RequestMultiplexer::new`](RequestMultiplexer::new).
+	pub fn new(net_service: N, authority_discovery: AD, request_multiplexer: RequestMultiplexer) -> Self {
+		NetworkBridge(net_service, authority_discovery, request_multiplexer)
 	}
 }
 
-impl<Net, Context> Subsystem<Context> for NetworkBridge<Net>
+impl<Net, AD, Context> Subsystem<Context> for NetworkBridge<Net, AD>
 	where
 		Net: Network,
+		AD: AuthorityDiscovery,
 		Context: SubsystemContext<Message=NetworkBridgeMessage>,
 {
 	type Metrics = ();
@@ -212,7 +284,7 @@ impl<Net, Context> Subsystem<Context> for NetworkBridge<Net>
 		// within `run_network`.
 		SpawnedSubsystem {
 			name: "network-bridge-subsystem",
-			future: run_network(self.0, ctx).map(|_| ()).boxed(),
+			future: run_network(self.0, self.1, self.2, ctx).map(|_| ()).boxed(),
 		}
 	}
 }
@@ -226,8 +298,10 @@ struct PeerData {
 enum Action {
 	SendValidationMessage(Vec<PeerId>, protocol_v1::ValidationProtocol),
 	SendCollationMessage(Vec<PeerId>, protocol_v1::CollationProtocol),
-	ConnectToValidators(PeerSet, Vec<ValidatorId>, oneshot::Sender<Vec<(ValidatorId, PeerId)>>),
+	ConnectToValidators(PeerSet, Vec<ValidatorId>, mpsc::UnboundedSender<(ValidatorId, PeerId)>),
 	ReportPeer(PeerId, ReputationChange),
+	SendRequests(Vec<Requests>),
+	SetCollatorPeers(Vec<PeerId>, Vec<PeerId>),
 
 	ActiveLeaves(ActiveLeavesUpdate),
 
@@ -238,6 +312,8 @@ enum Action {
 		Vec<WireMessage<protocol_v1::ValidationProtocol>>,
 		Vec<WireMessage<protocol_v1::CollationProtocol>>,
 	),
+	DispatchRequest(AllMessages),
+	RetryValidatorDiscovery,
 
 	Abort,
 	Nop,
@@ -258,6 +334,9 @@ fn action_from_overseer_message(
 				=> Action::SendCollationMessage(peers, msg),
 			NetworkBridgeMessage::ConnectToValidators(peer_set, validators, res)
 				=> Action::ConnectToValidators(peer_set, validators, res),
+			NetworkBridgeMessage::SendRequests(requests) => Action::SendRequests(requests),
+			NetworkBridgeMessage::SetCollatorPeers(allowed, banned)
+				=> Action::SetCollatorPeers(allowed, banned),
 		},
 		Ok(FromOverseer::Signal(OverseerSignal::BlockFinalized(_)))
 			=> Action::Nop,
@@ -322,6 +401,18 @@ fn action_from_network_message(event: Option<NetworkEvent>) -> Action {
 	}
 }
 
+fn action_from_incoming_request(
+	request: Result<AllMessages, RequestMultiplexError>,
+) -> Action {
+	match request {
+		Ok(msg) => Action::DispatchRequest(msg),
+		Err(RequestMultiplexError { peer, error }) => {
+			log::debug!(target: TARGET, "Failed to decode request from {}: {:?}", peer, error);
+			Action::ReportPeer(peer, MALFORMED_MESSAGE_COST)
+		}
+	}
+}
+
 fn construct_view(live_heads: &[Hash]) -> View {
 	View(live_heads.iter().rev().take(MAX_VIEW_HEADS).cloned().collect())
 }
@@ -517,7 +608,15 @@ async fn dispatch_validation_events_to_all<I>(
 			StatementDistributionMessage::NetworkBridgeUpdateV1(m)
 		)));
 
-		a.chain(b).chain(p).chain(s).filter_map(|x| x)
+		let ap = std::iter::once(event.focus().ok().map(|m| AllMessages::ApprovalDistribution(
+			ApprovalDistributionMessage::NetworkBridgeUpdateV1(m)
+		)));
+
+		let ar = std::iter::once(event.focus().ok().map(|m| AllMessages::AvailabilityRecovery(
+			AvailabilityRecoveryMessage::NetworkBridgeUpdateV1(m)
+		)));
+
+		a.chain(b).chain(p).chain(s).chain(ap).chain(ar).filter_map(|x| x)
 	};
 
 	ctx.send_messages(events.into_iter().flat_map(messages_for)).await
@@ -532,19 +631,61 @@ async fn dispatch_collation_events_to_all<I>(
 		I::IntoIter: Send,
 {
 	let messages_for = |event: NetworkBridgeEvent<protocol_v1::CollationProtocol>| {
-		event.focus().ok().map(|m| AllMessages::CollatorProtocol(
+		let c = std::iter::once(event.focus().ok().map(|m| AllMessages::CollatorProtocol(
 			CollatorProtocolMessage::NetworkBridgeUpdateV1(m)
-		))
+		)));
+
+		let psm = std::iter::once(event.focus().ok().map(|m| AllMessages::PeerSetManager(
+			PeerSetManagerMessage::NetworkBridgeUpdateV1(m)
+		)));
+
+		c.chain(psm).filter_map(|x| x)
 	};
 
 	ctx.send_messages(events.into_iter().flat_map(messages_for)).await
 }
 
-async fn run_network<N: Network>(
+/// Resolve the authority discovery keys of the given validators at the given relay-parent.
+///
+/// Validators without a known key are omitted.
+async fn validator_discovery_keys(
+	ctx: &mut impl SubsystemContext<Message=NetworkBridgeMessage>,
+	relay_parent: Hash,
+	validators: Vec<ValidatorId>,
+) -> SubsystemResult<Vec<(ValidatorId, AuthorityDiscoveryId)>> {
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		relay_parent,
+		RuntimeApiRequest::ValidatorDiscovery(validators.clone(), tx),
+	))).await?;
+
+	let keys = match rx.await? {
+		Ok(keys) => keys,
+		Err(e) => {
+			log::debug!(target: TARGET, "Failed to fetch authority discovery keys: {:?}", e);
+			return Ok(Vec::new());
+		}
+	};
+
+	Ok(validators.into_iter()
+		.zip(keys)
+		.filter_map(|(validator, key)| key.map(|k| (validator, k)))
+		.collect())
+}
+
+async fn run_network<N: Network, AD: AuthorityDiscovery>(
 	mut net: N,
+	authority_discovery: AD,
+	request_multiplexer: RequestMultiplexer,
 	mut ctx: impl SubsystemContext<Message=NetworkBridgeMessage>,
 ) -> SubsystemResult<()> {
 	let mut event_stream = net.event_stream().fuse();
+	// the multiplexer only concludes along with the network, so the event stream concluding
+	// is what shuts the bridge down.
+	let mut request_multiplexer = request_multiplexer.fuse();
+
+	let mut validator_discovery = validator_discovery::Service::new(authority_discovery);
+	let mut discovery_retry = futures_timer::Delay::new(DISCOVERY_RETRY_INTERVAL).fuse();
 
 	// Most recent heads are at the back.
 	let mut live_heads: Vec<Hash> = Vec::with_capacity(MAX_VIEW_HEADS);
@@ -553,6 +694,10 @@ async fn run_network<N: Network>(
 	let mut validation_peers: HashMap<PeerId, PeerData> = HashMap::new();
 	let mut collation_peers: HashMap<PeerId, PeerData> = HashMap::new();
 
+	// The collator peers as determined by the peer set manager.
+	let mut allowed_collators: HashSet<PeerId> = HashSet::new();
+	let mut banned_collators: HashSet<PeerId> = HashSet::new();
+
 	loop {
 		let action = {
 			let subsystem_next = ctx.recv().fuse();
@@ -562,9 +707,18 @@ async fn run_network<N: Network>(
 			futures::select! {
 				subsystem_msg = subsystem_next => action_from_overseer_message(subsystem_msg),
 				net_event = net_event_next => action_from_network_message(net_event),
+				request = request_multiplexer.select_next_some()
+					=> action_from_incoming_request(request),
+				_ = discovery_retry => {
+					discovery_retry = futures_timer::Delay::new(DISCOVERY_RETRY_INTERVAL).fuse();
+					Action::RetryValidatorDiscovery
+				}
 			}
 		};
 
+		// drop the reserved peers of the requests whose issuers are no longer interested.
+		validator_discovery.revoke_requests(&mut net).await?;
+
 		match action {
 			Action::Nop => {}
 			Action::Abort => return Ok(()),
@@ -583,12 +737,57 @@ async fn run_network<N: Network>(
 					WireMessage::ProtocolMessage(msg),
 			).await?,
 
-			Action::ConnectToValidators(_peer_set, _validators, _res) => {
-				// TODO: https://github.com/paritytech/polkadot/issues/1461
+			Action::ConnectToValidators(peer_set, validators, connected) => {
+				let relay_parent = match live_heads.last() {
+					Some(head) => *head,
+					None => {
+						log::debug!(
+							target: TARGET,
+							"Dropping a request to connect to validators: no active leaves",
+						);
+						continue
+					}
+				};
+
+				let validators = validator_discovery_keys(&mut ctx, relay_parent, validators).await?;
+				validator_discovery.on_request(
+					&mut net,
+					peer_set,
+					validators,
+					connected,
+				).await?;
 			}
 
 			Action::ReportPeer(peer, rep) => net.report_peer(peer, rep).await?,
 
+			Action::SendRequests(requests) => {
+				for request in requests {
+					net.start_request(request);
+				}
+			}
+
+			Action::DispatchRequest(msg) => ctx.send_message(msg).await?,
+
+			Action::RetryValidatorDiscovery => validator_discovery.retry_unresolved(&mut net).await?,
+
+			Action::SetCollatorPeers(allowed, banned) => {
+				let allowed: HashSet<_> = allowed.into_iter().collect();
+				let banned: HashSet<_> = banned.into_iter().collect();
+
+				// the network disconnects newly banned collators and keeps them from
+				// reconnecting for a while. Once they do, they are reported again.
+				for peer in banned.difference(&banned_collators) {
+					net.report_peer(peer.clone(), BANNED_COLLATOR_COST).await?;
+				}
+
+				for peer in allowed.difference(&allowed_collators) {
+					net.report_peer(peer.clone(), BENEFIT_ALLOWED_COLLATOR).await?;
+				}
+
+				allowed_collators = allowed;
+				banned_collators = banned;
+			}
+
 			Action::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated }) => {
 				live_heads.extend(activated);
 				live_heads.retain(|h| !deactivated.contains(h));
@@ -604,6 +803,11 @@ async fn run_network<N: Network>(
 			}
 
 			Action::PeerConnected(peer_set, peer, role) => {
+				if peer_set == PeerSet::Collation && banned_collators.contains(&peer) {
+					net.report_peer(peer, BANNED_COLLATOR_COST).await?;
+					continue
+				}
+
 				let peer_map = match peer_set {
 					PeerSet::Validation => &mut validation_peers,
 					PeerSet::Collation => &mut collation_peers,
@@ -694,7 +898,7 @@ async fn run_network<N: Network>(
 					}
 				}
 
-				if !c_messages.is_empty() {
+				if !c_messages.is_empty() && !banned_collators.contains(&peer) {
 					let events = handle_peer_messages(
 						peer.clone(),
 						&mut collation_peers,
@@ -729,6 +933,9 @@ mod tests {
 	use assert_matches::assert_matches;
 
 	use polkadot_subsystem::messages::{StatementDistributionMessage, BitfieldDistributionMessage};
+	use polkadot_node_network_protocol::request_response::{
+		network, v1 as req_res_v1, Protocol, RequestResponseConfig,
+	};
 	use polkadot_node_subsystem_test_helpers::{
 		SingleItemSink, SingleItemStream, TestSubsystemContextHandle,
 	};
@@ -738,12 +945,14 @@ mod tests {
 	struct TestNetwork {
 		net_events: Arc<Mutex<Option<SingleItemStream<NetworkEvent>>>>,
 		action_tx: mpsc::UnboundedSender<NetworkAction>,
+		request_tx: mpsc::UnboundedSender<Requests>,
 	}
 
 	// The test's view of the network. This receives updates from the subsystem in the form
 	// of `NetworkAction`s.
 	struct TestNetworkHandle {
 		action_rx: mpsc::UnboundedReceiver<NetworkAction>,
+		request_rx: mpsc::UnboundedReceiver<Requests>,
 		net_tx: SingleItemSink<NetworkEvent>,
 	}
 
@@ -753,14 +962,17 @@ mod tests {
 	) {
 		let (net_tx, net_rx) = polkadot_node_subsystem_test_helpers::single_item_sink();
 		let (action_tx, action_rx) = mpsc::unbounded();
+		let (request_tx, request_rx) = mpsc::unbounded();
 
 		(
 			TestNetwork {
 				net_events: Arc::new(Mutex::new(Some(net_rx))),
 				action_tx,
+				request_tx,
 			},
 			TestNetworkHandle {
 				action_rx,
+				request_rx,
 				net_tx,
 			},
 		)
@@ -786,6 +998,10 @@ mod tests {
 		{
 			Box::pin((&mut self.action_tx).sink_map_err(Into::into))
 		}
+
+		fn start_request(&self, request: Requests) {
+			let _ = self.request_tx.unbounded_send(request);
+		}
 	}
 
 	impl TestNetworkHandle {
@@ -826,6 +1042,11 @@ mod tests {
 			}).await;
 		}
 
+		// Get the next request sent out to the network.
+		async fn next_request(&mut self) -> Requests {
+			self.request_rx.next().await.expect("subsystem concluded early")
+		}
+
 		async fn send_network_event(&mut self, event: NetworkEvent) {
 			self.net_tx.send(event).await.expect("subsystem concluded early");
 		}
@@ -837,18 +1058,45 @@ mod tests {
 		actions.iter().find(|&x| x == action).is_some()
 	}
 
+	// An authority discovery service with a set of known addresses shared with the test.
+	#[derive(Default, Clone)]
+	struct TestAuthorityDiscovery {
+		addresses: Arc<Mutex<HashMap<AuthorityDiscoveryId, Vec<Multiaddr>>>>,
+	}
+
+	#[async_trait::async_trait]
+	impl AuthorityDiscovery for TestAuthorityDiscovery {
+		async fn get_addresses_by_authority_id(
+			&mut self,
+			authority: AuthorityDiscoveryId,
+		) -> Option<Vec<Multiaddr>> {
+			self.addresses.lock().get(&authority).cloned()
+		}
+	}
+
 	struct TestHarness {
 		network_handle: TestNetworkHandle,
 		virtual_overseer: TestSubsystemContextHandle<NetworkBridgeMessage>,
+		request_configs: Vec<RequestResponseConfig>,
 	}
 
 	fn test_harness<T: Future<Output=()>>(test: impl FnOnce(TestHarness) -> T) {
+		test_harness_with_authority_discovery(TestAuthorityDiscovery::default(), test)
+	}
+
+	fn test_harness_with_authority_discovery<T: Future<Output=()>>(
+		authority_discovery: TestAuthorityDiscovery,
+		test: impl FnOnce(TestHarness) -> T,
+	) {
 		let pool = sp_core::testing::TaskExecutor::new();
 		let (network, network_handle) = new_test_network();
 		let (context, virtual_overseer) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+		let (request_multiplexer, request_configs) = RequestMultiplexer::new();
 
 		let network_bridge = run_network(
 			network,
+			authority_discovery,
+			request_multiplexer,
 			context,
 		)
 			.map_err(|_| panic!("subsystem execution failed"))
@@ -857,6 +1105,7 @@ mod tests {
 		let test_fut = test(TestHarness {
 			network_handle,
 			virtual_overseer,
+			request_configs,
 		});
 
 		futures::pin_mut!(test_fut);
@@ -896,6 +1145,20 @@ mod tests {
 				StatementDistributionMessage::NetworkBridgeUpdateV1(e)
 			) if e == event.focus().expect("could not focus message")
 		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::ApprovalDistribution(
+				ApprovalDistributionMessage::NetworkBridgeUpdateV1(e)
+			) if e == event.focus().expect("could not focus message")
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::AvailabilityRecovery(
+				AvailabilityRecoveryMessage::NetworkBridgeUpdateV1(e)
+			) if e == event.focus().expect("could not focus message")
+		);
 	}
 
 	async fn assert_sends_collation_event_to_all(
@@ -907,13 +1170,20 @@ mod tests {
 			AllMessages::CollatorProtocol(
 				CollatorProtocolMessage::NetworkBridgeUpdateV1(e)
 			) if e == event.focus().expect("could not focus message")
-		)
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::PeerSetManager(
+				PeerSetManagerMessage::NetworkBridgeUpdateV1(e)
+			) if e == event.focus().expect("could not focus message")
+		);
 	}
 
 	#[test]
 	fn sends_view_updates_to_peers() {
 		test_harness(|test_harness| async move {
-			let TestHarness { mut network_handle, mut virtual_overseer } = test_harness;
+			let TestHarness { mut network_handle, mut virtual_overseer, .. } = test_harness;
 
 			let peer_a = PeerId::random();
 			let peer_b = PeerId::random();
@@ -966,6 +1236,7 @@ mod tests {
 			let TestHarness {
 				mut network_handle,
 				mut virtual_overseer,
+				..
 			} = test_harness;
 
 			let peer = PeerId::random();
@@ -1008,6 +1279,7 @@ mod tests {
 			let TestHarness {
 				mut network_handle,
 				mut virtual_overseer,
+				..
 			} = test_harness;
 
 			let peer = PeerId::random();
@@ -1031,13 +1303,12 @@ mod tests {
 				).await;
 			}
 
-			let pov_distribution_message = protocol_v1::PoVDistributionMessage::Awaiting(
-				[0; 32].into(),
-				vec![[1; 32].into()],
+			let approval_distribution_message = protocol_v1::ApprovalDistributionMessage::Approvals(
+				Vec::new(),
 			);
 
-			let message = protocol_v1::ValidationProtocol::PoVDistribution(
-				pov_distribution_message.clone(),
+			let message = protocol_v1::ValidationProtocol::ApprovalDistribution(
+				approval_distribution_message.clone(),
 			);
 
 			network_handle.peer_message(
@@ -1048,18 +1319,18 @@ mod tests {
 
 			network_handle.disconnect_peer(peer.clone(), PeerSet::Validation).await;
 
-			// PoV distribution message comes first, and the message is only sent to that subsystem.
+			// Approval distribution message comes first, and the message is only sent to that subsystem.
 			// then a disconnection event arises that is sent to all validation networking subsystems.
 
 			assert_matches!(
 				virtual_overseer.recv().await,
-				AllMessages::PoVDistribution(
-					PoVDistributionMessage::NetworkBridgeUpdateV1(
+				AllMessages::ApprovalDistribution(
+					ApprovalDistributionMessage::NetworkBridgeUpdateV1(
 						NetworkBridgeEvent::PeerMessage(p, m)
 					)
 				) => {
 					assert_eq!(p, peer);
-					assert_eq!(m, pov_distribution_message);
+					assert_eq!(m, approval_distribution_message);
 				}
 			);
 
@@ -1076,6 +1347,7 @@ mod tests {
 			let TestHarness {
 				mut network_handle,
 				mut virtual_overseer,
+				..
 			} = test_harness;
 
 			let peer = PeerId::random();
@@ -1145,6 +1417,7 @@ mod tests {
 			let TestHarness {
 				mut network_handle,
 				mut virtual_overseer,
+				..
 			} = test_harness;
 
 			let peer_a = PeerId::random();
@@ -1222,6 +1495,91 @@ mod tests {
 					assert_eq!(m, collator_protocol_message);
 				}
 			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::PeerSetManager(
+					PeerSetManagerMessage::NetworkBridgeUpdateV1(
+						NetworkBridgeEvent::PeerMessage(p, m)
+					)
+				) => {
+					assert_eq!(p, peer_b);
+					assert_eq!(m, collator_protocol_message);
+				}
+			);
+		});
+	}
+
+	#[test]
+	fn banned_collators_are_kept_off_the_collation_peer_set() {
+		test_harness(|test_harness| async move {
+			let TestHarness {
+				mut network_handle,
+				mut virtual_overseer,
+				..
+			} = test_harness;
+
+			let peer_a = PeerId::random();
+			let peer_b = PeerId::random();
+
+			network_handle.connect_peer(peer_a.clone(), PeerSet::Collation, ObservedRole::Full).await;
+
+			assert_sends_collation_event_to_all(
+				NetworkBridgeEvent::PeerConnected(peer_a.clone(), ObservedRole::Full),
+				&mut virtual_overseer,
+			).await;
+
+			assert_sends_collation_event_to_all(
+				NetworkBridgeEvent::PeerViewChange(peer_a.clone(), View(Default::default())),
+				&mut virtual_overseer,
+			).await;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::SetCollatorPeers(vec![peer_b.clone()], vec![peer_a.clone()]),
+			}).await;
+
+			let actions = network_handle.next_network_actions(2).await;
+			assert!(network_actions_contains(
+				&actions,
+				&NetworkAction::ReputationChange(peer_a.clone(), BANNED_COLLATOR_COST),
+			));
+			assert!(network_actions_contains(
+				&actions,
+				&NetworkAction::ReputationChange(peer_b.clone(), BENEFIT_ALLOWED_COLLATOR),
+			));
+
+			// messages of the banned collator are dropped.
+			let message = protocol_v1::CollationProtocol::CollatorProtocol(
+				protocol_v1::CollatorProtocolMessage::Declare(Sr25519Keyring::Alice.public().into()),
+			);
+
+			network_handle.peer_message(
+				peer_a.clone(),
+				PeerSet::Collation,
+				WireMessage::ProtocolMessage(message).encode(),
+			).await;
+
+			network_handle.disconnect_peer(peer_a.clone(), PeerSet::Collation).await;
+
+			assert_sends_collation_event_to_all(
+				NetworkBridgeEvent::PeerDisconnected(peer_a.clone()),
+				&mut virtual_overseer,
+			).await;
+
+			// the banned collator is reported again when reconnecting.
+			network_handle.connect_peer(peer_a.clone(), PeerSet::Collation, ObservedRole::Full).await;
+
+			assert_eq!(
+				network_handle.next_network_action().await,
+				NetworkAction::ReputationChange(peer_a.clone(), BANNED_COLLATOR_COST),
+			);
+
+			network_handle.connect_peer(peer_b.clone(), PeerSet::Collation, ObservedRole::Full).await;
+
+			assert_sends_collation_event_to_all(
+				NetworkBridgeEvent::PeerConnected(peer_b.clone(), ObservedRole::Full),
+				&mut virtual_overseer,
+			).await;
 		});
 	}
 
@@ -1231,6 +1589,7 @@ mod tests {
 			let TestHarness {
 				mut network_handle,
 				mut virtual_overseer,
+				..
 			} = test_harness;
 
 			let peer = PeerId::random();
@@ -1296,6 +1655,7 @@ mod tests {
 			let TestHarness {
 				mut network_handle,
 				mut virtual_overseer,
+				..
 			} = test_harness;
 
 			let peer = PeerId::random();
@@ -1331,13 +1691,12 @@ mod tests {
 			// send a validation protocol message.
 
 			{
-				let pov_distribution_message = protocol_v1::PoVDistributionMessage::Awaiting(
-					[0; 32].into(),
-					vec![[1; 32].into()],
+				let approval_distribution_message = protocol_v1::ApprovalDistributionMessage::Approvals(
+					Vec::new(),
 				);
 
-				let message = protocol_v1::ValidationProtocol::PoVDistribution(
-					pov_distribution_message.clone(),
+				let message = protocol_v1::ValidationProtocol::ApprovalDistribution(
+					approval_distribution_message.clone(),
 				);
 
 				virtual_overseer.send(FromOverseer::Communication {
@@ -1386,4 +1745,231 @@ mod tests {
 			}
 		});
 	}
+
+	#[test]
+	fn connects_to_validators_until_request_is_revoked() {
+		let alice_peer = PeerId::random();
+		let alice_address: Multiaddr = format!("/ip4/127.0.0.1/tcp/30333/p2p/{}", alice_peer)
+			.parse()
+			.unwrap();
+		let alice_authority: AuthorityDiscoveryId = Sr25519Keyring::Alice.public().into();
+
+		let authority_discovery = TestAuthorityDiscovery::default();
+		authority_discovery.addresses.lock().insert(alice_authority.clone(), vec![alice_address.clone()]);
+
+		test_harness_with_authority_discovery(authority_discovery, |test_harness| async move {
+			let TestHarness {
+				mut network_handle,
+				mut virtual_overseer,
+				..
+			} = test_harness;
+
+			let hash_a = Hash::from([1; 32]);
+			let alice: ValidatorId = Sr25519Keyring::Alice.public().into();
+			let bob: ValidatorId = Sr25519Keyring::Bob.public().into();
+
+			virtual_overseer.send(
+				FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::start_work(hash_a)))
+			).await;
+
+			let (connected_tx, mut connected_rx) = mpsc::unbounded();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::ConnectToValidators(
+					PeerSet::Validation,
+					vec![alice.clone(), bob.clone()],
+					connected_tx,
+				),
+			}).await;
+
+			// skip the view updates sent to the other subsystems.
+			loop {
+				match virtual_overseer.recv().await {
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						relay_parent,
+						RuntimeApiRequest::ValidatorDiscovery(validators, tx),
+					)) => {
+						assert_eq!(relay_parent, hash_a);
+						assert_eq!(validators, vec![alice.clone(), bob.clone()]);
+						// bob's authority discovery key is unknown.
+						tx.send(Ok(vec![Some(alice_authority.clone()), None])).unwrap();
+						break
+					}
+					_ => continue,
+				}
+			}
+
+			assert_eq!(
+				network_handle.next_network_action().await,
+				NetworkAction::AddToPeerSet(
+					PeerSet::Validation,
+					vec![alice_address.clone()].into_iter().collect(),
+				),
+			);
+			assert_eq!(connected_rx.next().await, Some((alice, alice_peer.clone())));
+
+			// dropping the receiver revokes the request before handling the next message.
+			drop(connected_rx);
+
+			let peer = PeerId::random();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::ReportPeer(peer.clone(), UNCONNECTED_PEERSET_COST),
+			}).await;
+
+			assert_eq!(
+				network_handle.next_network_actions(2).await,
+				vec![
+					NetworkAction::RemoveFromPeerSet(
+						PeerSet::Validation,
+						vec![alice_address].into_iter().collect(),
+					),
+					NetworkAction::ReputationChange(peer, UNCONNECTED_PEERSET_COST),
+				],
+			);
+		});
+	}
+
+	#[test]
+	fn retries_discovery_of_unresolved_validators() {
+		let alice_peer = PeerId::random();
+		let alice_address: Multiaddr = format!("/ip4/127.0.0.1/tcp/30333/p2p/{}", alice_peer)
+			.parse()
+			.unwrap();
+		let alice_authority: AuthorityDiscoveryId = Sr25519Keyring::Alice.public().into();
+
+		// authority discovery doesn't know alice's addresses yet.
+		let authority_discovery = TestAuthorityDiscovery::default();
+		let known_addresses = authority_discovery.addresses.clone();
+
+		test_harness_with_authority_discovery(authority_discovery, |test_harness| async move {
+			let TestHarness {
+				mut network_handle,
+				mut virtual_overseer,
+				..
+			} = test_harness;
+
+			let hash_a = Hash::from([1; 32]);
+			let alice: ValidatorId = Sr25519Keyring::Alice.public().into();
+
+			virtual_overseer.send(
+				FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::start_work(hash_a)))
+			).await;
+
+			let (connected_tx, mut connected_rx) = mpsc::unbounded();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::ConnectToValidators(
+					PeerSet::Validation,
+					vec![alice.clone()],
+					connected_tx,
+				),
+			}).await;
+
+			// skip the view updates sent to the other subsystems.
+			loop {
+				match virtual_overseer.recv().await {
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						_,
+						RuntimeApiRequest::ValidatorDiscovery(_, tx),
+					)) => {
+						tx.send(Ok(vec![Some(alice_authority.clone())])).unwrap();
+						break
+					}
+					_ => continue,
+				}
+			}
+
+			// make sure the connection request has been handled before alice becomes known.
+			let peer = PeerId::random();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::ReportPeer(peer.clone(), UNCONNECTED_PEERSET_COST),
+			}).await;
+			assert_eq!(
+				network_handle.next_network_action().await,
+				NetworkAction::ReputationChange(peer, UNCONNECTED_PEERSET_COST),
+			);
+
+			// once authority discovery learns about alice, the bridge picks her up without
+			// any further requests.
+			known_addresses.lock().insert(alice_authority, vec![alice_address.clone()]);
+
+			assert_eq!(
+				network_handle.next_network_action().await,
+				NetworkAction::AddToPeerSet(
+					PeerSet::Validation,
+					vec![alice_address].into_iter().collect(),
+				),
+			);
+			assert_eq!(connected_rx.next().await, Some((alice, alice_peer)));
+		});
+	}
+
+	#[test]
+	fn requests_are_sent_to_the_network() {
+		test_harness(|test_harness| async move {
+			let TestHarness {
+				mut network_handle,
+				mut virtual_overseer,
+				..
+			} = test_harness;
+
+			let peer = PeerId::random();
+			let payload = req_res_v1::PoVFetchingRequest {
+				relay_parent: Hash::repeat_byte(1),
+				pov_hash: Hash::repeat_byte(2),
+			};
+			let (request, response) = OutgoingRequest::new(peer.clone(), payload.clone());
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::SendRequests(vec![Requests::PoVFetching(request)]),
+			}).await;
+
+			let request = assert_matches!(
+				network_handle.next_request().await,
+				Requests::PoVFetching(request) => request
+			);
+			assert_eq!(request.peer, peer);
+			assert_eq!(request.payload, payload);
+
+			request.pending_response
+				.send(Ok(req_res_v1::PoVFetchingResponse::NoSuchPoV.encode()))
+				.unwrap();
+
+			assert_matches!(response.await, Ok(req_res_v1::PoVFetchingResponse::NoSuchPoV));
+		});
+	}
+
+	#[test]
+	fn incoming_requests_are_dispatched_to_subsystems() {
+		test_harness(|test_harness| async move {
+			let TestHarness {
+				mut virtual_overseer,
+				mut request_configs,
+				..
+			} = test_harness;
+
+			let peer = PeerId::random();
+			let payload = req_res_v1::CollationFetchingRequest {
+				relay_parent: Hash::repeat_byte(1),
+				para_id: 1.into(),
+			};
+
+			let collation_config = request_configs.iter_mut()
+				.find(|config| config.name == Protocol::CollationFetching.into_protocol_name())
+				.expect("all protocols are configured");
+
+			let (tx, _rx) = oneshot::channel();
+			collation_config.inbound_queue.as_mut().unwrap().send(network::IncomingRequest {
+				peer: peer.clone(),
+				payload: payload.encode(),
+				pending_response: tx,
+			}).await.unwrap();
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CollatorProtocol(CollatorProtocolMessage::CollationFetchingRequest(req)) => {
+					assert_eq!(req.peer, peer);
+					assert_eq!(req.payload, payload);
+				}
+			);
+		});
+	}
 }
@@ -0,0 +1,170 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Multiplexing of the incoming requests of all request/response protocols.
+
+use std::pin::Pin;
+
+use futures::stream::{self, BoxStream, SelectAll};
+use futures::task::{Context, Poll};
+use futures::{Stream, StreamExt};
+use parity_scale_codec::{Decode, Error as DecodingError};
+
+use polkadot_node_network_protocol::PeerId;
+use polkadot_node_network_protocol::request_response::{
+	network, IncomingRequest, Protocol, RequestResponseConfig, v1,
+};
+use polkadot_subsystem::messages::{AllMessages, CollatorProtocolMessage, PoVDistributionMessage};
+
+/// Turns the incoming requests of all protocols into a single stream of messages to the
+/// subsystems responsible for answering them.
+pub struct RequestMultiplexer {
+	receivers: SelectAll<BoxStream<'static, (Protocol, network::IncomingRequest)>>,
+}
+
+/// An incoming request which could not be decoded.
+#[derive(Debug)]
+pub struct RequestMultiplexError {
+	/// The peer which sent the request.
+	pub peer: PeerId,
+	/// The decoding error.
+	pub error: DecodingError,
+}
+
+impl RequestMultiplexer {
+	/// Create a new multiplexer for all the protocols in `Protocol`.
+	///
+	/// The returned configurations have to be registered with the network, which will then
+	/// send the incoming requests to the multiplexer.
+	pub fn new() -> (Self, Vec<RequestResponseConfig>) {
+		let (receivers, configs): (Vec<_>, Vec<_>) = Protocol::ALL.iter()
+			.map(|protocol| {
+				let protocol = *protocol;
+				let (rx, config) = protocol.get_config();
+				(rx.map(move |request| (protocol, request)).boxed(), config)
+			})
+			.unzip();
+
+		(
+			RequestMultiplexer { receivers: stream::select_all(receivers) },
+			configs,
+		)
+	}
+}
+
+impl Stream for RequestMultiplexer {
+	type Item = Result<AllMessages, RequestMultiplexError>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+		self.receivers.poll_next_unpin(cx)
+			.map(|next| next.map(|(protocol, request)| multiplex_single(protocol, request)))
+	}
+}
+
+/// Decode an incoming request and wrap it in a message to the subsystem answering it.
+fn multiplex_single(
+	protocol: Protocol,
+	network::IncomingRequest { peer, payload, pending_response }: network::IncomingRequest,
+) -> Result<AllMessages, RequestMultiplexError> {
+	let message = match protocol {
+		Protocol::CollationFetching => AllMessages::CollatorProtocol(
+			CollatorProtocolMessage::CollationFetchingRequest(IncomingRequest::new(
+				peer.clone(),
+				decode_with_peer::<v1::CollationFetchingRequest>(peer, payload)?,
+				pending_response,
+			))
+		),
+		Protocol::PoVFetching => AllMessages::PoVDistribution(
+			PoVDistributionMessage::PoVFetchingRequest(IncomingRequest::new(
+				peer.clone(),
+				decode_with_peer::<v1::PoVFetchingRequest>(peer, payload)?,
+				pending_response,
+			))
+		),
+	};
+
+	Ok(message)
+}
+
+fn decode_with_peer<Req: Decode>(
+	peer: PeerId,
+	payload: Vec<u8>,
+) -> Result<Req, RequestMultiplexError> {
+	Req::decode(&mut payload.as_ref()).map_err(|error| RequestMultiplexError { peer, error })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor;
+	use futures::channel::oneshot;
+	use futures::SinkExt;
+	use parity_scale_codec::Encode;
+	use polkadot_primitives::v1::Hash;
+	use assert_matches::assert_matches;
+
+	#[test]
+	fn requests_are_routed_to_their_subsystems() {
+		let (mut multiplexer, mut configs) = RequestMultiplexer::new();
+
+		let peer = PeerId::random();
+		let request = v1::PoVFetchingRequest {
+			relay_parent: Hash::repeat_byte(1),
+			pov_hash: Hash::repeat_byte(2),
+		};
+
+		let pov_config = configs.iter_mut()
+			.find(|config| config.name == Protocol::PoVFetching.into_protocol_name())
+			.expect("all protocols are configured");
+
+		let (tx, _rx) = oneshot::channel();
+		executor::block_on(pov_config.inbound_queue.as_mut().unwrap().send(network::IncomingRequest {
+			peer: peer.clone(),
+			payload: request.encode(),
+			pending_response: tx,
+		})).unwrap();
+
+		assert_matches!(
+			executor::block_on(multiplexer.next()),
+			Some(Ok(AllMessages::PoVDistribution(PoVDistributionMessage::PoVFetchingRequest(req)))) => {
+				assert_eq!(req.peer, peer);
+				assert_eq!(req.payload, request);
+			}
+		);
+	}
+
+	#[test]
+	fn undecodable_requests_are_reported() {
+		let (mut multiplexer, mut configs) = RequestMultiplexer::new();
+
+		let peer = PeerId::random();
+		let collation_config = configs.iter_mut()
+			.find(|config| config.name == Protocol::CollationFetching.into_protocol_name())
+			.expect("all protocols are configured");
+
+		let (tx, _rx) = oneshot::channel();
+		executor::block_on(collation_config.inbound_queue.as_mut().unwrap().send(network::IncomingRequest {
+			peer: peer.clone(),
+			payload: vec![1, 2, 3],
+			pending_response: tx,
+		})).unwrap();
+
+		assert_matches!(
+			executor::block_on(multiplexer.next()),
+			Some(Err(RequestMultiplexError { peer: p, .. })) => assert_eq!(p, peer)
+		);
+	}
+}
@@ -0,0 +1,261 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A validator discovery service for the Network Bridge.
+//!
+//! Resolves the addresses of validators via authority discovery and keeps them among the
+//! reserved peers of the requested peer-set for as long as somebody is interested in them.
+//! Validators whose addresses aren't known to authority discovery yet are periodically queried
+//! again.
+
+use std::collections::{HashMap, HashSet, hash_map::Entry};
+
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::prelude::*;
+
+use sc_network::multiaddr::{Multiaddr, Protocol};
+use sc_authority_discovery::Service as AuthorityDiscoveryService;
+use polkadot_node_network_protocol::{PeerId, PeerSet};
+use polkadot_primitives::v1::{AuthorityDiscoveryId, ValidatorId};
+use polkadot_subsystem::SubsystemResult;
+
+use super::{Network, NetworkAction};
+
+/// An abstraction over the authority discovery service.
+#[async_trait]
+pub trait AuthorityDiscovery: Send + 'static {
+	/// Get the addresses for the given [`AuthorityDiscoveryId`] from the local address cache.
+	async fn get_addresses_by_authority_id(
+		&mut self,
+		authority: AuthorityDiscoveryId,
+	) -> Option<Vec<Multiaddr>>;
+}
+
+#[async_trait]
+impl AuthorityDiscovery for AuthorityDiscoveryService {
+	async fn get_addresses_by_authority_id(
+		&mut self,
+		authority: AuthorityDiscoveryId,
+	) -> Option<Vec<Multiaddr>> {
+		AuthorityDiscoveryService::get_addresses_by_authority_id(self, authority).await
+	}
+}
+
+#[async_trait]
+impl<AD: AuthorityDiscovery> AuthorityDiscovery for Option<AD> {
+	async fn get_addresses_by_authority_id(
+		&mut self,
+		authority: AuthorityDiscoveryId,
+	) -> Option<Vec<Multiaddr>> {
+		match self {
+			Some(ad) => ad.get_addresses_by_authority_id(authority).await,
+			None => None,
+		}
+	}
+}
+
+/// A request that hasn't been revoked yet.
+struct NonRevokedRequest {
+	peer_set: PeerSet,
+	validators: Vec<(ValidatorId, AuthorityDiscoveryId)>,
+	connected: mpsc::UnboundedSender<(ValidatorId, PeerId)>,
+}
+
+/// The addresses of a requested validator on a peer-set along with the number of requests
+/// that are interested in it.
+struct RequestedValidator {
+	ref_count: usize,
+	addresses: HashSet<Multiaddr>,
+}
+
+/// Keeps track of the validators requested via `ConnectToValidators` and of the addresses that
+/// were added to the reserved peers on their behalf.
+pub(super) struct Service<AD> {
+	authority_discovery: AD,
+	requested_validators: HashMap<(PeerSet, AuthorityDiscoveryId), RequestedValidator>,
+	non_revoked_requests: Vec<NonRevokedRequest>,
+}
+
+impl<AD: AuthorityDiscovery> Service<AD> {
+	pub(super) fn new(authority_discovery: AD) -> Self {
+		Service {
+			authority_discovery,
+			requested_validators: HashMap::new(),
+			non_revoked_requests: Vec::new(),
+		}
+	}
+
+	/// Handle a new connection request.
+	///
+	/// Resolves the addresses of all validators, adds the ones that aren't reserved yet to the
+	/// peer-set and sends the resolved `(ValidatorId, PeerId)` pairs over `connected`.
+	pub(super) async fn on_request(
+		&mut self,
+		net: &mut impl Network,
+		peer_set: PeerSet,
+		validators: Vec<(ValidatorId, AuthorityDiscoveryId)>,
+		connected: mpsc::UnboundedSender<(ValidatorId, PeerId)>,
+	) -> SubsystemResult<()> {
+		let mut to_reserve = HashSet::new();
+		let mut requested_validators = Vec::with_capacity(validators.len());
+
+		for (validator_id, authority) in validators {
+			let key = (peer_set, authority.clone());
+
+			// we only query the addresses if we don't have any for this validator yet.
+			let known = self.requested_validators.get(&key)
+				.map_or(false, |v| !v.addresses.is_empty());
+			let resolved: HashSet<Multiaddr> = if known {
+				HashSet::new()
+			} else {
+				self.authority_discovery.get_addresses_by_authority_id(authority.clone())
+					.await
+					.unwrap_or_default()
+					.into_iter()
+					.collect()
+			};
+
+			let requested = match self.requested_validators.entry(key) {
+				Entry::Occupied(entry) => {
+					let requested = entry.into_mut();
+					requested.ref_count += 1;
+					requested
+				}
+				Entry::Vacant(entry) => entry.insert(RequestedValidator {
+					ref_count: 1,
+					addresses: HashSet::new(),
+				}),
+			};
+
+			for address in resolved {
+				if requested.addresses.insert(address.clone()) {
+					to_reserve.insert(address);
+				}
+			}
+
+			for peer_id in requested.addresses.iter().filter_map(peer_id_from_multiaddr) {
+				// the requester may have gone away already, which is handled on revocation.
+				let _ = connected.unbounded_send((validator_id.clone(), peer_id));
+			}
+
+			requested_validators.push((validator_id, authority));
+		}
+
+		self.non_revoked_requests.push(NonRevokedRequest {
+			peer_set,
+			validators: requested_validators,
+			connected,
+		});
+
+		if !to_reserve.is_empty() {
+			net.action_sink().send(NetworkAction::AddToPeerSet(peer_set, to_reserve)).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Query the addresses of the requested validators that authority discovery didn't know
+	/// about so far.
+	///
+	/// Adds the newly resolved addresses to the peer-sets and sends the resolved
+	/// `(ValidatorId, PeerId)` pairs to all requests interested in these validators.
+	pub(super) async fn retry_unresolved(&mut self, net: &mut impl Network) -> SubsystemResult<()> {
+		let mut to_reserve: HashMap<PeerSet, HashSet<Multiaddr>> = HashMap::new();
+		let authority_discovery = &mut self.authority_discovery;
+		let non_revoked_requests = &self.non_revoked_requests;
+
+		for ((peer_set, authority), requested) in self.requested_validators.iter_mut() {
+			if !requested.addresses.is_empty() {
+				continue
+			}
+
+			let resolved = authority_discovery.get_addresses_by_authority_id(authority.clone())
+				.await
+				.unwrap_or_default();
+			if resolved.is_empty() {
+				continue
+			}
+
+			let peer_ids: Vec<PeerId> = resolved.iter().filter_map(peer_id_from_multiaddr).collect();
+			let interested = non_revoked_requests.iter()
+				.filter(|request| request.peer_set == *peer_set)
+				.flat_map(|request| request.validators.iter()
+					.filter(|(_, a)| a == authority)
+					.map(move |(validator_id, _)| (validator_id, &request.connected))
+				);
+			for (validator_id, connected) in interested {
+				for peer_id in &peer_ids {
+					let _ = connected.unbounded_send((validator_id.clone(), peer_id.clone()));
+				}
+			}
+
+			requested.addresses.extend(resolved.iter().cloned());
+			to_reserve.entry(*peer_set).or_default().extend(resolved);
+		}
+
+		for (peer_set, addresses) in to_reserve {
+			net.action_sink().send(NetworkAction::AddToPeerSet(peer_set, addresses)).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Revoke all requests whose issuers are no longer interested in them, i.e. dropped the
+	/// receiving side of the channel.
+	///
+	/// Removes the addresses of validators that aren't requested anymore from the reserved peers.
+	pub(super) async fn revoke_requests(&mut self, net: &mut impl Network) -> SubsystemResult<()> {
+		let mut to_release: HashMap<PeerSet, HashSet<Multiaddr>> = HashMap::new();
+		let requested_validators = &mut self.requested_validators;
+
+		self.non_revoked_requests.retain(|request| {
+			if !request.connected.is_closed() {
+				return true;
+			}
+
+			for (_, authority) in &request.validators {
+				let key = (request.peer_set, authority.clone());
+				if let Entry::Occupied(mut entry) = requested_validators.entry(key) {
+					entry.get_mut().ref_count -= 1;
+					if entry.get().ref_count == 0 {
+						to_release.entry(request.peer_set)
+							.or_default()
+							.extend(entry.remove().addresses);
+					}
+				}
+			}
+
+			false
+		});
+
+		for (peer_set, addresses) in to_release {
+			if !addresses.is_empty() {
+				net.action_sink().send(NetworkAction::RemoveFromPeerSet(peer_set, addresses)).await?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Extract the `PeerId` from the trailing `/p2p/` component of the address, if any.
+fn peer_id_from_multiaddr(address: &Multiaddr) -> Option<PeerId> {
+	address.iter().last().and_then(|protocol| match protocol {
+		Protocol::P2p(multihash) => PeerId::from_multihash(multihash).ok(),
+		_ => None,
+	})
+}
@@ -15,8 +15,16 @@
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::HashMap;
-
-use futures::channel::oneshot;
+use std::pin::Pin;
+
+use codec::Encode;
+use futures::{
+	channel::{mpsc, oneshot},
+	select_biased,
+	stream::{FusedStream, Stream, StreamExt},
+	task::{Context as TaskContext, Poll},
+	FutureExt,
+};
 use log::{trace, warn};
 use polkadot_primitives::v1::{
 	CollatorId, CoreIndex, CoreState, Hash, Id as ParaId, CandidateReceipt,
@@ -31,13 +39,54 @@ use polkadot_subsystem::{
 	},
 };
 use polkadot_node_network_protocol::{
-	v1 as protocol_v1, View, PeerId, PeerSet, NetworkBridgeEvent, RequestId,
+	v1 as protocol_v1, View, PeerId, PeerSet, NetworkBridgeEvent,
+	request_response::{v1 as req_res_v1, IncomingRequest},
 };
 use polkadot_node_subsystem_util::{
 	request_validators_ctx,
 	request_validator_groups_ctx,
 };
 
+/// The pending connection requests, keyed by the relay-parent they were issued for.
+///
+/// Dropping a receiver revokes the corresponding request in the network bridge.
+#[derive(Default)]
+struct ConnectionRequests(HashMap<Hash, mpsc::UnboundedReceiver<(ValidatorId, PeerId)>>);
+
+impl ConnectionRequests {
+	/// Insert a new connection request, revoking any previous one for the same relay-parent.
+	fn put(&mut self, relay_parent: Hash, receiver: mpsc::UnboundedReceiver<(ValidatorId, PeerId)>) {
+		self.0.insert(relay_parent, receiver);
+	}
+
+	/// Remove (and thereby revoke) the connection request for the given relay-parent.
+	fn remove(&mut self, relay_parent: &Hash) {
+		self.0.remove(relay_parent);
+	}
+}
+
+impl Stream for ConnectionRequests {
+	type Item = (ValidatorId, PeerId);
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Option<Self::Item>> {
+		// closed receivers are skipped; the stream itself never terminates as new requests
+		// may be added at any time.
+		for receiver in self.0.values_mut() {
+			if let Poll::Ready(Some(pair)) = receiver.poll_next_unpin(cx) {
+				return Poll::Ready(Some(pair));
+			}
+		}
+
+		Poll::Pending
+	}
+}
+
+impl FusedStream for ConnectionRequests {
+	fn is_terminated(&self) -> bool {
+		false
+	}
+}
+
 #[derive(Default)]
 struct State {
 	/// Our id.
@@ -69,6 +118,9 @@ struct State {
 	/// Entries in this map will be cleared as validator groups in `our_validator_groups`
 	/// go out of scope with their respective deactivated leafs.
 	known_validators: HashMap<PeerId, ValidatorId>,
+
+	/// The connection requests to validators issued per relay-parent.
+	connection_requests: ConnectionRequests,
 }
 
 /// Distribute a collation.
@@ -136,7 +188,7 @@ where
 	state.our_validators_groups.insert(relay_parent, our_validators.clone());
 
 	// Issue a discovery request for the validators of the current group and the next group.
-	connect_to_validators(ctx, state, our_validators).await?;
+	connect_to_validators(ctx, state, relay_parent, our_validators).await?;
 
 	state.collations.insert(relay_parent, (receipt, pov));
 
@@ -238,25 +290,24 @@ where
 }
 
 /// Issue a connection request to a set of validators.
+///
+/// The request is kept alive until the relay-parent goes out of our view.
 async fn connect_to_validators<Context>(
 	ctx: &mut Context,
 	state: &mut State,
+	relay_parent: Hash,
 	validators: Vec<ValidatorId>,
 ) -> Result<()>
 where
 	Context: SubsystemContext<Message = CollatorProtocolMessage>
 {
-	let (tx, rx) = oneshot::channel();
+	let (tx, rx) = mpsc::unbounded();
 
 	ctx.send_message(AllMessages::NetworkBridge(
 		NetworkBridgeMessage::ConnectToValidators(PeerSet::Collation, validators, tx),
 	)).await?;
 
-	let mut validators_ids = rx.await?;
-
-	for id in validators_ids.drain(..) {
-		state.known_validators.insert(id.1, id.0);
-	}
+	state.connection_requests.put(relay_parent, rx);
 
 	Ok(())
 }
@@ -278,7 +329,18 @@ where
 		}
 	};
 
-	let wire_message = protocol_v1::CollatorProtocolMessage::AdvertiseCollation(relay_parent, collating_on);
+	let pov_size = match state.collations.get(&relay_parent) {
+		Some((_, pov)) => pov.encoded_size() as u32,
+		None => {
+			return Ok(());
+		}
+	};
+
+	let wire_message = protocol_v1::CollatorProtocolMessage::AdvertiseCollation(
+		relay_parent,
+		collating_on,
+		pov_size,
+	);
 
 	ctx.send_message(AllMessages::NetworkBridge(
 		NetworkBridgeMessage::SendCollationMessage(
@@ -329,6 +391,9 @@ where
 				}
 			}
 		}
+		CollationFetchingRequest(request) => {
+			handle_collation_request(state, request);
+		}
 		FetchCollation(_, _, _, _) => {
 			warn!(
 				target: TARGET,
@@ -364,91 +429,71 @@ where
 	Ok(())
 }
 
-/// Issue a response to a previously requested collation.
-async fn send_collation<Context>(
-	ctx: &mut Context,
-	request_id: RequestId,
-	origin: PeerId,
-	receipt: CandidateReceipt,
-	pov: PoV,
-) -> Result<()>
-where
-	Context: SubsystemContext<Message = CollatorProtocolMessage>
-{
-	let wire_message = protocol_v1::CollatorProtocolMessage::Collation(
-		request_id,
-		receipt,
-		pov,
-	);
-
-	ctx.send_message(AllMessages::NetworkBridge(
-		NetworkBridgeMessage::SendCollationMessage(
-			vec![origin],
-			protocol_v1::CollationProtocol::CollatorProtocol(wire_message),
-		)
-	)).await?;
-
-	Ok(())
+/// Respond to a collation request of a validator.
+///
+/// Requests we can't serve are dropped, which the requester observes as a refusal.
+fn handle_collation_request(
+	state: &State,
+	request: IncomingRequest<req_res_v1::CollationFetchingRequest>,
+) {
+	let req_res_v1::CollationFetchingRequest { relay_parent, para_id } = request.payload;
+
+	match state.collating_on {
+		Some(our_para_id) if our_para_id == para_id => {
+			if let Some((receipt, pov)) = state.collations.get(&relay_parent).cloned() {
+				let response = req_res_v1::CollationFetchingResponse::Collation(receipt, pov);
+				if request.send_response(response).is_err() {
+					trace!(
+						target: TARGET,
+						"Failed to send collation on {:?}, the request has been canceled",
+						relay_parent,
+					);
+				}
+			}
+		}
+		Some(our_para_id) => {
+			warn!(
+				target: TARGET,
+				"Received a CollationFetchingRequest for {:?} while collating on {:?}",
+				para_id, our_para_id,
+			);
+		}
+		None => {
+			warn!(
+				target: TARGET,
+				"Received a CollationFetchingRequest for {:?} while not collating on any para",
+				para_id,
+			);
+		}
+	}
 }
 
 /// A networking messages switch.
-async fn handle_incoming_peer_message<Context>(
-	ctx: &mut Context,
-	state: &mut State,
+///
+/// Collations are requested on the request/response protocol, so none of the
+/// notifications are expected on the collator side.
+fn handle_incoming_peer_message(
 	origin: PeerId,
 	msg: protocol_v1::CollatorProtocolMessage,
-) -> Result<()>
-where
-	Context: SubsystemContext<Message = CollatorProtocolMessage>
-{
+) {
 	use protocol_v1::CollatorProtocolMessage::*;
 
 	match msg {
 	    Declare(_) => {
 			warn!(
 				target: TARGET,
-				"Declare message is not expected on the collator side of the protocol",
+				"Declare message from {} is not expected on the collator side of the protocol",
+				origin,
 			);
 		}
-	    AdvertiseCollation(_, _) => {
+	    AdvertiseCollation(..) => {
 			warn!(
 				target: TARGET,
-				"AdvertiseCollation message is not expected on the collator side of the protocol",
-			);
-		}
-	    RequestCollation(request_id, relay_parent, para_id) => {
-			match state.collating_on {
-				Some(our_para_id) => {
-					if our_para_id == para_id {
-						if let Some(collation) = state.collations.get(&relay_parent).cloned() {
-							send_collation(ctx, request_id, origin, collation.0, collation.1).await?;
-						}
-					} else {
-						warn!(
-							target: TARGET,
-							"Received a RequestCollation for {:?} while collating on {:?}",
-							para_id, our_para_id,
-						);
-					}
-				}
-				None => {
-					warn!(
-						target: TARGET,
-						"Received a RequestCollation for {:?} while not collating on any para",
-						para_id,
-					);
-				}
-			}
-		}
-	    Collation(_, _, _) => {
-			warn!(
-				target: TARGET,
-				"Collation message is not expected on the collator side of the protocol",
+				"AdvertiseCollation message from {} is not expected on the collator side of the protocol",
+				origin,
 			);
 		}
 	}
-
-	Ok(())
 }
 
 /// Our view has changed.
@@ -526,7 +571,7 @@ where
 			handle_our_view_change(state, view).await?;
 		}
 	    PeerMessage(remote, msg) => {
-			handle_incoming_peer_message(ctx, state, remote, msg).await?;
+			handle_incoming_peer_message(remote, msg);
 		}
 	}
 
@@ -546,6 +591,7 @@ async fn handle_our_view_change(
 
 	for removed in removed.into_iter() {
 		state.collations.remove(&removed);
+		state.connection_requests.remove(&removed);
 		if let Some(group) = state.our_validators_groups.remove(&removed) {
 			state.known_validators.retain(|_, v| !group.contains(v));
 		}
@@ -567,7 +613,21 @@ where
 	state.our_id = our_id;
 
 	loop {
-		match ctx.recv().await? {
+		let msg = {
+			let connection_requests = &mut state.connection_requests;
+			select_biased! {
+				pair = connection_requests.next() => match pair {
+					Some((validator_id, peer_id)) => {
+						state.known_validators.insert(peer_id, validator_id);
+						continue
+					}
+					None => continue,
+				},
+				msg = ctx.recv().fuse() => msg?,
+			}
+		};
+
+		match msg {
 			Communication { msg } => process_msg(&mut ctx, &mut state, msg).await?,
 			Signal(ActiveLeaves(_update)) => {}
 			Signal(BlockFinalized(_)) => {}
@@ -597,6 +657,7 @@ mod tests {
 	};
 	use polkadot_subsystem::ActiveLeavesUpdate;
 	use polkadot_node_subsystem_util::TimeoutExt;
+	use codec::Decode;
 	use polkadot_subsystem_testhelpers::{self as test_helpers};
 	use polkadot_node_network_protocol::ObservedRole;
 
@@ -878,12 +939,12 @@ mod tests {
 					assert!(validators.contains(&test_state.validator_public[4]));
 					assert!(validators.contains(&test_state.validator_public[1]));
 
-					tx.send(vec![
-						(test_state.validator_public[2].clone(), test_state.validator_peer_id[2].clone()),
-						(test_state.validator_public[0].clone(), test_state.validator_peer_id[0].clone()),
-						(test_state.validator_public[4].clone(), test_state.validator_peer_id[4].clone()),
-						(test_state.validator_public[1].clone(), test_state.validator_peer_id[1].clone()),
-					]).unwrap();
+					for i in [2, 0, 4, 1].iter() {
+						tx.unbounded_send((
+							test_state.validator_public[*i].clone(),
+							test_state.validator_peer_id[*i].clone(),
+						)).unwrap();
+					}
 				}
 			);
 
@@ -944,49 +1005,39 @@ mod tests {
 						protocol_v1::CollatorProtocolMessage::AdvertiseCollation(
 							relay_parent,
 							collating_on,
+							pov_size,
 						) => {
 							assert_eq!(relay_parent, current);
 							assert_eq!(collating_on, test_state.chain_ids[0]);
+							assert_eq!(pov_size, pov_block.encoded_size() as u32);
 						}
 					);
 				}
 			);
 
-			let request_id = 42;
-
 			// Request a collation.
+			let (tx, rx) = oneshot::channel();
 			overseer_send(
 				&mut virtual_overseer,
-				CollatorProtocolMessage::NetworkBridgeUpdateV1(
-					NetworkBridgeEvent::PeerMessage(
+				CollatorProtocolMessage::CollationFetchingRequest(
+					IncomingRequest::new(
 						test_state.validator_peer_id[2].clone(),
-						protocol_v1::CollatorProtocolMessage::RequestCollation(
-							request_id,
-							current,
-							test_state.chain_ids[0],
-						)
+						req_res_v1::CollationFetchingRequest {
+							relay_parent: current,
+							para_id: test_state.chain_ids[0],
+						},
+						tx,
 					)
 				)
 			).await;
 
 			// Wait for the reply.
+			let response = rx.await.expect("the collation is sent");
 			assert_matches!(
-				overseer_recv(&mut virtual_overseer).await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::SendCollationMessage(
-						to,
-						protocol_v1::CollationProtocol::CollatorProtocol(wire_message),
-					)
-				) => {
-					assert_eq!(to, vec![test_state.validator_peer_id[2].clone()]);
-					assert_matches!(
-						wire_message,
-						protocol_v1::CollatorProtocolMessage::Collation(req_id, receipt, pov) => {
-							assert_eq!(req_id, request_id);
-							assert_eq!(receipt, candidate);
-							assert_eq!(pov, pov_block);
-						}
-					);
+				req_res_v1::CollationFetchingResponse::decode(&mut response.as_slice()),
+				Ok(req_res_v1::CollationFetchingResponse::Collation(receipt, pov)) => {
+					assert_eq!(receipt, candidate);
+					assert_eq!(pov, pov_block);
 				}
 			);
 
@@ -1000,23 +1051,24 @@ mod tests {
 				),
 			).await;
 
-			let request_id = 43;
-
 			// Re-request a collation.
+			let (tx, rx) = oneshot::channel();
 			overseer_send(
 				&mut virtual_overseer,
-				CollatorProtocolMessage::NetworkBridgeUpdateV1(
-					NetworkBridgeEvent::PeerMessage(
+				CollatorProtocolMessage::CollationFetchingRequest(
+					IncomingRequest::new(
 						test_state.validator_peer_id[2].clone(),
-						protocol_v1::CollatorProtocolMessage::RequestCollation(
-							request_id,
-							current,
-							test_state.chain_ids[0],
-						)
+						req_res_v1::CollationFetchingRequest {
+							relay_parent: current,
+							para_id: test_state.chain_ids[0],
+						},
+						tx,
 					)
 				)
 			).await;
 
+			// The collation for the old relay parent is gone, so the request is dropped.
+			assert!(rx.await.is_err());
 			assert!(overseer_recv_with_timeout(&mut virtual_overseer, TIMEOUT).await.is_none());
 		});
 	}
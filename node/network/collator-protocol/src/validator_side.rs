@@ -14,10 +14,10 @@
 // You should have received a copy of the GNU General Public License
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::{HashMap, HashSet};
-use std::time::Duration;
+use std::collections::HashMap;
 use std::task::Poll;
 
+use codec::Encode;
 use futures::{
 	StreamExt,
 	channel::oneshot,
@@ -32,68 +32,28 @@ use polkadot_primitives::v1::{
 use polkadot_subsystem::{
 	FromOverseer, OverseerSignal, SubsystemContext,
 	messages::{
-		AllMessages, CandidateSelectionMessage, CollatorProtocolMessage, NetworkBridgeMessage,
+		AllMessages, CandidateSelectionMessage, CollatorProtocolMessage, CollatorReport,
+		NetworkBridgeMessage, PeerSetManagerMessage,
 	},
 };
 use polkadot_node_network_protocol::{
-	v1 as protocol_v1, View, PeerId, ReputationChange as Rep, RequestId,
-	NetworkBridgeEvent,
+	v1 as protocol_v1, View, PeerId, ReputationChange as Rep, NetworkBridgeEvent,
+	request_response::{
+		v1 as req_res_v1, OutgoingRequest, OutgoingResult, RequestError, Requests,
+	},
 };
-use polkadot_node_subsystem_util::TimeoutExt;
 
 use super::{modify_reputation, TARGET, Result};
 
 const COST_UNEXPECTED_MESSAGE: Rep = Rep::new(-10, "An unexpected message");
-const COST_REQUEST_TIMED_OUT: Rep = Rep::new(-20, "A collation request has timed out");
+const COST_CORRUPTED_MESSAGE: Rep = Rep::new(-10, "Message was corrupt");
+const COST_REQUEST_FAILED: Rep = Rep::new(-20, "A collation request has failed");
 const COST_REPORT_BAD: Rep = Rep::new(-50, "A collator was reported by another subsystem");
 const BENEFIT_NOTIFY_GOOD: Rep = Rep::new(50, "A collator was noted good by another subsystem");
 
-#[derive(Debug)]
-enum CollationRequestResult {
-	Received(RequestId),
-	Timeout(RequestId),
-}
-
-/// A Future representing an ongoing collation request.
-/// It may timeout or end in a graceful fashion if a requested
-/// collation has been received sucessfully or chain has moved on.
-struct CollationRequest {
-	// The response for this request has been received successfully or 
-	// chain has moved forward and this request is no longer relevant.
-	received: oneshot::Receiver<()>,
-
-	// The timeout of this request.
-	timeout: Duration,
-
-	// The id of this request.
-	request_id: RequestId,
-}
-
-impl CollationRequest {
-	async fn wait(self) -> CollationRequestResult {
-		use CollationRequestResult::*;
-
-		let CollationRequest {
-			received,
-			timeout,
-			request_id,
-		} = self;
-
-
-		match received.timeout(timeout).await {
-			None => Timeout(request_id),
-			Some(_) => Received(request_id),
-		}
-	}
-}
-
-struct PerRequest {
-	// The sender side to signal the `CollationRequest` to resolve successfully.
-	received: oneshot::Sender<()>,
-
-	// Send result here.
-	result: oneshot::Sender<(CandidateReceipt, PoV)>,
-}
+/// The outcome of a collation request, along with the relay parent, para and collator
+/// it was made for.
+type CollationResponse = (Hash, ParaId, PeerId, OutgoingResult<req_res_v1::CollationFetchingResponse>);
 
 /// All state relevant for the validator side of the protocol lives here.
 #[derive(Default)]
@@ -107,30 +67,22 @@ struct State {
 	/// Peers that have declared themselves as collators.
 	known_collators: HashMap<PeerId, CollatorId>,
 
-	/// Advertisments received from collators. We accept one advertisment
-	/// per collator per source per relay-parent.
-	advertisments: HashMap<PeerId, HashSet<(ParaId, Hash)>>,
-
-	/// Derive RequestIds from this.
-	next_request_id: RequestId,
+	/// Advertisments received from collators, along with the advertised PoV sizes.
+	/// We accept one advertisment per collator per source per relay-parent.
+	advertisments: HashMap<PeerId, HashMap<(ParaId, Hash), u32>>,
 
-	/// The collations we have requested by relay parent and para id.
+	/// The collations we have requested by relay parent and para id, along with
+	/// the channels to reply with the collations to other subsystems.
 	///
 	/// For each relay parent and para id we may be connected to a number
 	/// of collators each of those may have advertised a different collation.
 	/// So we group such cases here.
-	requested_collations: HashMap<(Hash, ParaId, PeerId), RequestId>,
-
-	/// Housekeeping handles we need to have per request to:
-	///  - cancel ongoing requests
-	///  - reply with collations to other subsystems.
-	requests_info: HashMap<RequestId, PerRequest>,
+	requested_collations: HashMap<(Hash, ParaId, PeerId), oneshot::Sender<(CandidateReceipt, PoV)>>,
 
 	/// Collation requests that are currently in progress.
-	requests_in_progress: FuturesUnordered<BoxFuture<'static, CollationRequestResult>>,
-
-	/// Delay after which a collation request would time out.
-	request_timeout: Duration, 
+	///
+	/// Requests time out on the network level, so every request eventually resolves.
+	requests_in_progress: FuturesUnordered<BoxFuture<'static, CollationResponse>>,
 
 	/// Possessed collations.
 	collations: HashMap<(Hash, ParaId), Vec<(CollatorId, CandidateReceipt, PoV)>>,
@@ -170,7 +122,7 @@ where
 
 	// Has the collator in question advertised a relevant collation?
 	for (k, v) in state.advertisments.iter() {
-		if v.contains(&(para_id, relay_parent)) {
+		if v.contains_key(&(para_id, relay_parent)) {
 			if state.known_collators.get(k) == Some(&collator_id) {
 				relevant_advertiser = Some(k.clone());
 			}
@@ -186,6 +138,23 @@ where
 	Ok(())
 }
 
+/// Report the behavior of a collator to the peer set manager, which keeps track of it
+/// across restarts.
+async fn report_to_peer_set_manager<Context>(
+	ctx: &mut Context,
+	id: CollatorId,
+	report: CollatorReport,
+) -> Result<()>
+where
+	Context: SubsystemContext<Message = CollatorProtocolMessage>
+{
+	ctx.send_message(AllMessages::PeerSetManager(
+		PeerSetManagerMessage::ReportCollator(id, report)
+	)).await?;
+
+	Ok(())
+}
+
 /// Report a collator for some malicious actions.
 async fn report_collator<Context>(
 	ctx: &mut Context,
@@ -204,7 +173,7 @@ where
 		}
 	}
 
-	Ok(())
+	report_to_peer_set_manager(ctx, id, CollatorReport::InvalidCollation).await
 }
 
 /// Some other subsystem has reported a collator as a good one, bump reputation.
@@ -222,7 +191,7 @@ where
 		}
 	}
 
-	Ok(())
+	report_to_peer_set_manager(ctx, id, CollatorReport::GoodCollation).await
 }
 
 /// A peer's view has changed. A number of things should be done:
@@ -240,71 +209,106 @@ async fn handle_peer_view_change(
 	*current = view;
 
 	if let Some(advertisments) = state.advertisments.get_mut(&peer_id) {
-		advertisments.retain(|(_, relay_parent)| !removed.contains(relay_parent));
-	}
-
-	let mut requests_to_cancel = Vec::new();
-
-	for removed in removed.into_iter() {
-		state.requested_collations.retain(|k, v| {
-			if k.0 == removed {
-				requests_to_cancel.push(*v);
-				false
-			} else {
-				true
-			}
-		});
+		advertisments.retain(|(_, relay_parent), _| !removed.contains(relay_parent));
 	}
 
-	for r in requests_to_cancel.into_iter() {
-		if let Some(per_request) = state.requests_info.remove(&r) {
-			per_request.received.send(()).map_err(|_| oneshot::Canceled)?;
-		}
-	}
+	// Responses to canceled requests are ignored once they arrive.
+	state.requested_collations.retain(|k, _| !removed.contains(&k.0));
 
 	Ok(())
 }
 
-/// We have received a collation.
-///  - Cancel all ongoing requests
+/// A collation request has concluded.
+///  - Ignore it if the request has been canceled in the meantime
+///  - Punish the collator if the request failed, also reporting it to the peer set manager
 ///  - Reply to interested parties if any
 ///  - Store collation.
-async fn received_collation<Context>(
+async fn handle_collation_response<Context>(
 	ctx: &mut Context,
 	state: &mut State,
+	relay_parent: Hash,
+	para_id: ParaId,
 	origin: PeerId,
-	request_id: RequestId,
-	receipt: CandidateReceipt,
-	pov: PoV,
+	response: OutgoingResult<req_res_v1::CollationFetchingResponse>,
 ) -> Result<()>
 where
 	Context: SubsystemContext<Message = CollatorProtocolMessage>
 {
-	let relay_parent = receipt.descriptor.relay_parent;
-	let para_id = receipt.descriptor.para_id;
+	let result = match state.requested_collations.remove(&(relay_parent, para_id, origin.clone())) {
+		Some(result) => result,
+		None => {
+			// Our chain has moved on and the request has been canceled, so this response
+			// is no longer relevant. This is not the collator's fault.
+			trace!(
+				target: TARGET,
+				"Collation by {} on {} on relay parent {} is no longer requested",
+				origin, para_id, relay_parent,
+			);
+			return Ok(());
+		}
+	};
 
-	if let Some(id) = state.requested_collations.remove(
-		&(relay_parent, para_id, origin.clone())
-	) {
-		if id == request_id {
-			if let Some(per_request) = state.requests_info.remove(&id) {
-				let _ = per_request.received.send(());
+	let advertised_pov_size = state.advertisments.get(&origin)
+		.and_then(|advertisments| advertisments.get(&(para_id, relay_parent)))
+		.copied();
+
+	let failure = match response {
+		Ok(req_res_v1::CollationFetchingResponse::Collation(receipt, pov)) => {
+			if receipt.descriptor.relay_parent != relay_parent || receipt.descriptor.para_id != para_id {
+				// The collator answered with a collation we did not ask for.
+				Some(COST_UNEXPECTED_MESSAGE)
+			} else if advertised_pov_size.map_or(false, |size| pov.encoded_size() as u32 > size) {
+				// The collator understated the size of the PoV to get picked.
+				trace!(
+					target: TARGET,
+					"PoV by {} on {} on relay parent {} is larger than advertised",
+					origin, para_id, relay_parent,
+				);
+				Some(COST_UNEXPECTED_MESSAGE)
+			} else {
 				if let Some(collator_id) = state.known_collators.get(&origin) {
-					let _ = per_request.result.send((receipt.clone(), pov.clone()));
+					let _ = result.send((receipt.clone(), pov.clone()));
 
 					state.collations
 						.entry((relay_parent, para_id))
 						.or_default()
 						.push((collator_id.clone(), receipt, pov));
 				}
+
+				None
 			}
 		}
-	} else {
-		// TODO: https://github.com/paritytech/polkadot/issues/1694
-		// This is tricky. If our chain has moved on, we have already canceled
-		// the relevant request and removed it from the map; so and we are not expecting
-		// this reply although technically it is not a malicious behaviur.
-		modify_reputation(ctx, origin, COST_UNEXPECTED_MESSAGE).await?;
+		Err(RequestError::InvalidResponse(e)) => {
+			trace!(
+				target: TARGET,
+				"Collation response by {} could not be decoded: {:?}", origin, e,
+			);
+			Some(COST_CORRUPTED_MESSAGE)
+		}
+		Err(RequestError::NetworkError(e)) => {
+			trace!(
+				target: TARGET,
+				"Collation request to {} failed: {:?}", origin, e,
+			);
+			Some(COST_REQUEST_FAILED)
+		}
+		Err(RequestError::Canceled(_)) => {
+			trace!(
+				target: TARGET,
+				"Collation request to {} was canceled by the network", origin,
+			);
+			None
+		}
+	};
+
+	if let Some(cost) = failure {
+		let collator_id = state.known_collators.get(&origin).cloned();
+
+		modify_reputation(ctx, origin, cost).await?;
+
+		if let Some(collator_id) = collator_id {
+			report_to_peer_set_manager(ctx, collator_id, CollatorReport::RequestFailed).await?;
+		}
 	}
 
 	Ok(())
@@ -314,7 +318,6 @@ where
 /// This function will
 ///  - Check for duplicate requests.
 ///  - Check if the requested collation is in our view.
-///  - Update PerRequest records with the `result` field if necessary.
 /// And as such invocations of this function may rely on that.
 async fn request_collation<Context>(
 	ctx: &mut Context,
@@ -345,41 +348,22 @@ where
 		return Ok(());
 	}
 
-	let request_id = state.next_request_id;
-	state.next_request_id += 1;
-
-	let (tx, rx) = oneshot::channel();
-
-	let per_request = PerRequest {
-		received: tx,
-		result,
-	};
-
-	let request = CollationRequest {
-		received: rx,
-		timeout: state.request_timeout,
-		request_id,
-	};
-
-	state.requested_collations.insert((relay_parent, para_id.clone(), peer_id.clone()), request_id);
+	let (request, response) = OutgoingRequest::new(
+		peer_id.clone(),
+		req_res_v1::CollationFetchingRequest {
+			relay_parent,
+			para_id,
+		},
+	);
 
-	state.requests_info.insert(request_id, per_request);
+	state.requested_collations.insert((relay_parent, para_id, peer_id.clone()), result);
 
 	state.requests_in_progress.push(Box::pin(async move {
-		request.wait().await
+		(relay_parent, para_id, peer_id, response.await)
 	}));
 
-	let wire_message = protocol_v1::CollatorProtocolMessage::RequestCollation(
-		request_id,
-		relay_parent,
-		para_id,
-	);
-
 	ctx.send_message(AllMessages::NetworkBridge(
-		NetworkBridgeMessage::SendCollationMessage(
-			vec![peer_id],
-			protocol_v1::CollationProtocol::CollatorProtocol(wire_message),
-		)
+		NetworkBridgeMessage::SendRequests(vec![Requests::CollationFetching(request)])
 	)).await?;
 
 	Ok(())
@@ -391,6 +375,7 @@ async fn notify_candidate_selection<Context>(
 	collator: CollatorId,
 	relay_parent: Hash,
 	para_id: ParaId,
+	pov_size: u32,
 ) -> Result<()>
 where
 	Context: SubsystemContext<Message = CollatorProtocolMessage>
@@ -400,6 +385,7 @@ where
 			relay_parent,
 			para_id,
 			collator,
+			pov_size,
 		)
 	)).await?;
 
@@ -423,20 +409,13 @@ where
 			state.known_collators.insert(origin.clone(), id);
 			state.peer_views.entry(origin).or_default();
 		}
-	    AdvertiseCollation(relay_parent, para_id) => {
-			state.advertisments.entry(origin.clone()).or_default().insert((para_id, relay_parent));
+	    AdvertiseCollation(relay_parent, para_id, pov_size) => {
+			state.advertisments.entry(origin.clone()).or_default().insert((para_id, relay_parent), pov_size);
 
 			if let Some(collator) = state.known_collators.get(&origin) {
-				notify_candidate_selection(ctx, collator.clone(), relay_parent, para_id).await?;
+				notify_candidate_selection(ctx, collator.clone(), relay_parent, para_id, pov_size).await?;
 			}
 		}
-	    RequestCollation(_, _, _) => {
-			// This is a validator side of the protocol, collation requests are not expected here.
-			return modify_reputation(ctx, origin, COST_UNEXPECTED_MESSAGE).await;
-		}
-	    Collation(request_id, receipt, pov) => {
-			received_collation(ctx, state, origin, request_id, receipt, pov).await?;
-		}
 	}
 
 	Ok(())
@@ -449,20 +428,7 @@ async fn remove_relay_parent(
 	state: &mut State,
 	relay_parent: Hash,
 ) -> Result<()> {
-	let mut remove_these = Vec::new();
-
-	state.requested_collations.retain(|k, v| {
-		if k.0 == relay_parent {
-			remove_these.push(*v);
-		}
-		k.0 != relay_parent
-	});
-
-	for id in remove_these.into_iter() {
-		if let Some(info) = state.requests_info.remove(&id) {
-			info.received.send(()).map_err(|_| oneshot::Canceled)?;
-		}
-	}
+	state.requested_collations.retain(|k, _| k.0 != relay_parent);
 
 	state.collations.retain(|k, _| k.0 != relay_parent);
 
@@ -488,29 +454,6 @@ async fn handle_our_view_change(
 	Ok(())
 }
 
-/// A request has timed out.
-async fn request_timed_out<Context>(
-	ctx: &mut Context,
-	state: &mut State,
-	id: RequestId,
-) -> Result<()>
-where
-	Context: SubsystemContext<Message = CollatorProtocolMessage>
-{
-	// We have to go backwards in the map, again.
-	if let Some(key) = find_val_in_map(&state.requested_collations, &id) {
-		if let Some(_) = state.requested_collations.remove(&key) {
-			if let Some(_) = state.requests_info.remove(&id) {
-				let peer_id = key.2;
-
-				modify_reputation(ctx, peer_id, COST_REQUEST_TIMED_OUT).await?;
-			}
-		}
-	}
-
-	Ok(())
-}
-
 /// Bridge event switch.
 async fn handle_network_msg<Context>(
 	ctx: &mut Context,
@@ -568,6 +511,12 @@ where
 				"DistributeCollation message is not expected on the validator side of the protocol",
 			);
 		}
+		CollationFetchingRequest(_) => {
+			warn!(
+				target: TARGET,
+				"CollationFetchingRequest message is not expected on the validator side of the protocol",
+			);
+		}
 		FetchCollation(relay_parent, collator_id, para_id, tx) => {
 			fetch_collation(ctx, state, relay_parent, collator_id, para_id, tx).await?;
 		}
@@ -595,17 +544,14 @@ where
 }
 
 /// The main run loop.
-pub(crate) async fn run<Context>(mut ctx: Context, request_timeout: Duration) -> Result<()>
+pub(crate) async fn run<Context>(mut ctx: Context) -> Result<()>
 where
 	Context: SubsystemContext<Message = CollatorProtocolMessage>
 {
 	use FromOverseer::*;
 	use OverseerSignal::*;
 
-	let mut state = State {
-		request_timeout,
-		..Default::default()
-	};
+	let mut state = State::default();
 
 	loop {
 		if let Poll::Ready(msg) = futures::poll!(ctx.recv()) {
@@ -621,18 +567,10 @@ where
 			continue;
 		}
 
-		while let Poll::Ready(Some(request)) = futures::poll!(state.requests_in_progress.next()) {
-			// Request has timed out, we need to penalize the collator and re-send the request
-			// if the chain has not moved on yet.
-			match request {
-				CollationRequestResult::Timeout(id) => {
-					trace!(target: TARGET, "Request timed out {}", id);
-					request_timed_out(&mut ctx, &mut state, id).await?;
-				}
-				CollationRequestResult::Received(id) => {
-					state.requests_info.remove(&id);
-				}
-			}
+		while let Poll::Ready(Some((relay_parent, para_id, peer_id, response))) =
+			futures::poll!(state.requests_in_progress.next())
+		{
+			handle_collation_response(&mut ctx, &mut state, relay_parent, para_id, peer_id, response).await?;
 		}
 
 		futures::pending!();
@@ -641,20 +579,16 @@ where
 	Ok(())
 }
 
-fn find_val_in_map<K: Clone, V: Eq>(map: &HashMap<K, V>, val: &V) -> Option<K> {
-	map
-		.iter()
-		.find_map(|(k, v)| if v == val { Some(k.clone()) } else { None })
-}
-
 #[cfg(test)]
 mod tests {
 	use super::*;
 	use std::iter;
+	use std::time::Duration;
 	use futures::{executor, future, Future};
 	use sp_core::crypto::Pair;
 	use assert_matches::assert_matches;
-	use futures_timer::Delay;
+
+	use polkadot_node_subsystem_util::TimeoutExt;
 
 	use polkadot_primitives::v1::{BlockData, CollatorPair};
 	use polkadot_subsystem_testhelpers as test_helpers;
@@ -707,7 +641,7 @@ mod tests {
 
 		let (context, virtual_overseer) = test_helpers::make_subsystem_context(pool.clone());
 
-		let subsystem = run(context, Duration::from_millis(50));
+		let subsystem = run(context);
 
 		let test_fut = test(TestHarness { virtual_overseer });
 
@@ -754,6 +688,27 @@ mod tests {
 			.await
 	}
 
+	async fn assert_collation_request(
+		overseer: &mut test_helpers::TestSubsystemContextHandle<CollatorProtocolMessage>,
+		relay_parent: Hash,
+		para_id: ParaId,
+	) -> OutgoingRequest<req_res_v1::CollationFetchingRequest> {
+		assert_matches!(
+			overseer_recv(overseer).await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::SendRequests(mut requests)) => {
+				assert_eq!(requests.len(), 1);
+				assert_matches!(
+					requests.pop().unwrap(),
+					Requests::CollationFetching(request) => {
+						assert_eq!(request.payload.relay_parent, relay_parent);
+						assert_eq!(request.payload.para_id, para_id);
+						request
+					}
+				)
+			}
+		)
+	}
+
 	// As we receive a relevan advertisment act on it and issue a collation request.
 	#[test]
 	fn act_on_advertisment() {
@@ -795,6 +750,7 @@ mod tests {
 						protocol_v1::CollatorProtocolMessage::AdvertiseCollation(
 							test_state.relay_parent,
 							test_state.chain_ids[0],
+							1024,
 						)
 					)
 				)
@@ -806,18 +762,20 @@ mod tests {
 					relay_parent,
 					para_id,
 					collator,
+					pov_size,
 				)
 			) => {
 				assert_eq!(relay_parent, test_state.relay_parent);
 				assert_eq!(para_id, test_state.chain_ids[0]);
 				assert_eq!(collator, pair.public());
+				assert_eq!(pov_size, 1024);
 			});
 		});
 	}
 
-	// Test that an issued request times out a number of times until our view moves on.
+	// Test that a collator answering a request with garbage is punished.
 	#[test]
-	fn collation_request_times_out() {
+	fn corrupted_collation_response_is_punished() {
 		let test_state = TestState::default();
 
 		test_harness(|test_harness| async move {
@@ -854,6 +812,7 @@ mod tests {
 						protocol_v1::CollatorProtocolMessage::AdvertiseCollation(
 							test_state.relay_parent,
 							test_state.chain_ids[0],
+							1024,
 						)
 					)
 				)
@@ -865,10 +824,12 @@ mod tests {
 					relay_parent,
 					para_id,
 					collator,
+					pov_size,
 				)) => {
 					assert_eq!(relay_parent, test_state.relay_parent);
 					assert_eq!(para_id, test_state.chain_ids[0]);
 					assert_eq!(collator, test_state.collators[0].public());
+					assert_eq!(pov_size, 1024);
 				}
 			);
 
@@ -884,37 +845,209 @@ mod tests {
 				)
 			).await;
 
+			let request = assert_collation_request(
+				&mut virtual_overseer,
+				test_state.relay_parent,
+				test_state.chain_ids[0],
+			).await;
+			assert_eq!(request.peer, peer_b);
+
+			request.pending_response.send(Ok(vec![42, 42])).unwrap();
+
 			assert_matches!(
 				overseer_recv(&mut virtual_overseer).await,
-				AllMessages::NetworkBridge(NetworkBridgeMessage::SendCollationMessage(
-					peers,
-					protocol_v1::CollationProtocol::CollatorProtocol(
-						protocol_v1::CollatorProtocolMessage::RequestCollation(
-							_id,
-							relay_parent,
-							para_id,
+				AllMessages::NetworkBridge(
+					NetworkBridgeMessage::ReportPeer(peer, rep)
+				) => {
+					assert_eq!(peer, peer_b);
+					assert_eq!(rep, COST_CORRUPTED_MESSAGE);
+				}
+			);
+
+			assert_matches!(
+				overseer_recv(&mut virtual_overseer).await,
+				AllMessages::PeerSetManager(
+					PeerSetManagerMessage::ReportCollator(collator, report)
+				) => {
+					assert_eq!(collator, test_state.collators[0].public());
+					assert_eq!(report, CollatorReport::RequestFailed);
+				}
+			);
+		});
+	}
+
+	// Test that a collator sending a larger PoV than it advertised is punished.
+	#[test]
+	fn understated_pov_size_is_punished() {
+		let test_state = TestState::default();
+
+		test_harness(|test_harness| async move {
+			let TestHarness {
+				mut virtual_overseer,
+			} = test_harness;
+
+			overseer_send(
+				&mut virtual_overseer,
+				CollatorProtocolMessage::NetworkBridgeUpdateV1(
+					NetworkBridgeEvent::OurViewChange(View(vec![test_state.relay_parent]))
+				)
+			).await;
+
+			let peer_b = PeerId::random();
+
+			overseer_send(
+				&mut virtual_overseer,
+				CollatorProtocolMessage::NetworkBridgeUpdateV1(
+					NetworkBridgeEvent::PeerMessage(
+						peer_b.clone(),
+						protocol_v1::CollatorProtocolMessage::Declare(
+							test_state.collators[0].public(),
+						),
+					)
+				)
+			).await;
+
+			overseer_send(
+				&mut virtual_overseer,
+				CollatorProtocolMessage::NetworkBridgeUpdateV1(
+					NetworkBridgeEvent::PeerMessage(
+						peer_b.clone(),
+						protocol_v1::CollatorProtocolMessage::AdvertiseCollation(
+							test_state.relay_parent,
+							test_state.chain_ids[0],
+							1,
 						)
 					)
 				)
-			) => {
-				assert_eq!(relay_parent, test_state.relay_parent);
-				assert_eq!(peers, vec![peer_b.clone()]);
-				assert_eq!(para_id, test_state.chain_ids[0]);
-			});
+			).await;
+
+			assert_matches!(
+				overseer_recv(&mut virtual_overseer).await,
+				AllMessages::CandidateSelection(CandidateSelectionMessage::Collation(..))
+			);
+
+			let (tx, rx) = oneshot::channel();
+
+			overseer_send(
+				&mut virtual_overseer,
+				CollatorProtocolMessage::FetchCollation(
+					test_state.relay_parent,
+					test_state.collators[0].public(),
+					test_state.chain_ids[0],
+					tx,
+				)
+			).await;
+
+			let request = assert_collation_request(
+				&mut virtual_overseer,
+				test_state.relay_parent,
+				test_state.chain_ids[0],
+			).await;
+
+			let mut candidate = CandidateReceipt::default();
+			candidate.descriptor.para_id = test_state.chain_ids[0];
+			candidate.descriptor.relay_parent = test_state.relay_parent;
+
+			request.pending_response.send(Ok(
+				req_res_v1::CollationFetchingResponse::Collation(
+					candidate,
+					PoV { block_data: BlockData(vec![1, 2, 3]) },
+				).encode()
+			)).unwrap();
 
-			// Don't send a response and we shoud see reputation penalties to the
-			// collator.
-			Delay::new(Duration::from_millis(50)).await;
 			assert_matches!(
 				overseer_recv(&mut virtual_overseer).await,
 				AllMessages::NetworkBridge(
 					NetworkBridgeMessage::ReportPeer(peer, rep)
 				) => {
 					assert_eq!(peer, peer_b);
-					assert_eq!(rep, COST_REQUEST_TIMED_OUT);
+					assert_eq!(rep, COST_UNEXPECTED_MESSAGE);
+				}
+			);
+
+			assert_matches!(
+				overseer_recv(&mut virtual_overseer).await,
+				AllMessages::PeerSetManager(
+					PeerSetManagerMessage::ReportCollator(collator, report)
+				) => {
+					assert_eq!(collator, test_state.collators[0].public());
+					assert_eq!(report, CollatorReport::RequestFailed);
 				}
 			);
 
+			// The collation is not handed out.
+			assert!(rx.await.is_err());
+		});
+	}
+
+	// Test that responses to requests which were canceled by our view moving on are ignored.
+	#[test]
+	fn responses_to_canceled_requests_are_ignored() {
+		let test_state = TestState::default();
+
+		test_harness(|test_harness| async move {
+			let TestHarness {
+				mut virtual_overseer,
+			} = test_harness;
+
+			overseer_send(
+				&mut virtual_overseer,
+				CollatorProtocolMessage::NetworkBridgeUpdateV1(
+					NetworkBridgeEvent::OurViewChange(View(vec![test_state.relay_parent]))
+				)
+			).await;
+
+			let peer_b = PeerId::random();
+
+			overseer_send(
+				&mut virtual_overseer,
+				CollatorProtocolMessage::NetworkBridgeUpdateV1(
+					NetworkBridgeEvent::PeerMessage(
+						peer_b.clone(),
+						protocol_v1::CollatorProtocolMessage::Declare(
+							test_state.collators[0].public(),
+						),
+					)
+				)
+			).await;
+
+			overseer_send(
+				&mut virtual_overseer,
+				CollatorProtocolMessage::NetworkBridgeUpdateV1(
+					NetworkBridgeEvent::PeerMessage(
+						peer_b.clone(),
+						protocol_v1::CollatorProtocolMessage::AdvertiseCollation(
+							test_state.relay_parent,
+							test_state.chain_ids[0],
+							1024,
+						)
+					)
+				)
+			).await;
+
+			assert_matches!(
+				overseer_recv(&mut virtual_overseer).await,
+				AllMessages::CandidateSelection(CandidateSelectionMessage::Collation(..))
+			);
+
+			let (tx, rx) = oneshot::channel();
+
+			overseer_send(
+				&mut virtual_overseer,
+				CollatorProtocolMessage::FetchCollation(
+					test_state.relay_parent,
+					test_state.collators[0].public(),
+					test_state.chain_ids[0],
+					tx,
+				)
+			).await;
+
+			let request = assert_collation_request(
+				&mut virtual_overseer,
+				test_state.relay_parent,
+				test_state.chain_ids[0],
+			).await;
+
 			// Deactivate the relay parent in question.
 			overseer_send(
 				&mut virtual_overseer,
@@ -923,8 +1056,21 @@ mod tests {
 				)
 			).await;
 
-			// After we've deactivated it we are not expecting any more requests
-			// for timed out collations.
+			// The request has been canceled.
+			assert!(rx.await.is_err());
+
+			let mut candidate = CandidateReceipt::default();
+			candidate.descriptor.para_id = test_state.chain_ids[0];
+			candidate.descriptor.relay_parent = test_state.relay_parent;
+
+			request.pending_response.send(Ok(
+				req_res_v1::CollationFetchingResponse::Collation(
+					candidate,
+					PoV { block_data: BlockData(vec![]) },
+				).encode()
+			)).unwrap();
+
+			// The late response is neither delivered nor punished.
 			assert!(
 				overseer_recv_with_timeout(
 					&mut virtual_overseer,
@@ -993,6 +1139,16 @@ mod tests {
 				}
 			);
 
+			assert_matches!(
+				overseer_recv(&mut virtual_overseer).await,
+				AllMessages::PeerSetManager(
+					PeerSetManagerMessage::ReportCollator(collator, report)
+				) => {
+					assert_eq!(collator, test_state.collators[0].public());
+					assert_eq!(report, CollatorReport::InvalidCollation);
+				}
+			);
+
 			overseer_send(
 				&mut virtual_overseer,
 				CollatorProtocolMessage::NoteGoodCollation(test_state.collators[1].public()),
@@ -1007,6 +1163,16 @@ mod tests {
 					assert_eq!(rep, BENEFIT_NOTIFY_GOOD);
 				}
 			);
+
+			assert_matches!(
+				overseer_recv(&mut virtual_overseer).await,
+				AllMessages::PeerSetManager(
+					PeerSetManagerMessage::ReportCollator(collator, report)
+				) => {
+					assert_eq!(collator, test_state.collators[1].public());
+					assert_eq!(report, CollatorReport::GoodCollation);
+				}
+			);
 		});
 	}
 
@@ -1070,6 +1236,7 @@ mod tests {
 						protocol_v1::CollatorProtocolMessage::AdvertiseCollation(
 							test_state.relay_parent,
 							test_state.chain_ids[0],
+							1024,
 						)
 					)
 				)
@@ -1081,11 +1248,13 @@ mod tests {
 					relay_parent,
 					para_id,
 					collator,
+					pov_size,
 				)
 			) => {
 				assert_eq!(relay_parent, test_state.relay_parent);
 				assert_eq!(para_id, test_state.chain_ids[0]);
 				assert_eq!(collator, test_state.collators[0].public());
+				assert_eq!(pov_size, 1024);
 			});
 
 			overseer_send(
@@ -1096,6 +1265,7 @@ mod tests {
 						protocol_v1::CollatorProtocolMessage::AdvertiseCollation(
 							test_state.relay_parent,
 							test_state.chain_ids[0],
+							1024,
 						)
 					)
 				)
@@ -1107,11 +1277,13 @@ mod tests {
 					relay_parent,
 					para_id,
 					collator,
+					pov_size,
 				)
 			) => {
 				assert_eq!(relay_parent, test_state.relay_parent);
 				assert_eq!(para_id, test_state.chain_ids[0]);
 				assert_eq!(collator, test_state.collators[1].public());
+				assert_eq!(pov_size, 1024);
 			});
 
 			let (tx_0, rx_0) = oneshot::channel();
@@ -1138,81 +1310,46 @@ mod tests {
 				)
 			).await;
 
-			let (request_id, peer_id) = assert_matches!(
-				overseer_recv(&mut virtual_overseer).await,
-				AllMessages::NetworkBridge(NetworkBridgeMessage::SendCollationMessage(
-					peers,
-					protocol_v1::CollationProtocol::CollatorProtocol(
-						protocol_v1::CollatorProtocolMessage::RequestCollation(
-							id,
-							relay_parent,
-							para_id,
-						)
-					)
-				)
-			) => {
-				assert_eq!(relay_parent, test_state.relay_parent);
-				assert_eq!(para_id, test_state.chain_ids[0]);
-				(id, peers[0].clone())
-			});
+			let request_0 = assert_collation_request(
+				&mut virtual_overseer,
+				test_state.relay_parent,
+				test_state.chain_ids[0],
+			).await;
+
+			let request_1 = assert_collation_request(
+				&mut virtual_overseer,
+				test_state.relay_parent,
+				test_state.chain_ids[0],
+			).await;
+
+			assert_eq!(request_0.peer, peer_b);
+			assert_eq!(request_1.peer, peer_c);
 
 			let mut candidate_a = CandidateReceipt::default();
 			candidate_a.descriptor.para_id = test_state.chain_ids[0];
 			candidate_a.descriptor.relay_parent = test_state.relay_parent;
 
-			overseer_send(
-				&mut virtual_overseer,
-				CollatorProtocolMessage::NetworkBridgeUpdateV1(
-					NetworkBridgeEvent::PeerMessage(
-						peer_id,
-						protocol_v1::CollatorProtocolMessage::Collation(
-							request_id,
-							candidate_a.clone(),
-							PoV {
-								block_data: BlockData(vec![]),
-							},
-						)
-					)
-				)
-			).await;
-
-			let (request_id, peer_id) = assert_matches!(
-				overseer_recv(&mut virtual_overseer).await,
-				AllMessages::NetworkBridge(NetworkBridgeMessage::SendCollationMessage(
-					peers,
-					protocol_v1::CollationProtocol::CollatorProtocol(
-						protocol_v1::CollatorProtocolMessage::RequestCollation(
-							id,
-							relay_parent,
-							para_id,
-						)
-					)
-				)
-			) => {
-				assert_eq!(relay_parent, test_state.relay_parent);
-				assert_eq!(para_id, test_state.chain_ids[0]);
-				(id, peers[0].clone())
-			});
+			request_0.pending_response.send(Ok(
+				req_res_v1::CollationFetchingResponse::Collation(
+					candidate_a.clone(),
+					PoV {
+						block_data: BlockData(vec![]),
+					},
+				).encode()
+			)).unwrap();
 
 			let mut candidate_b = CandidateReceipt::default();
 			candidate_b.descriptor.para_id = test_state.chain_ids[0];
 			candidate_b.descriptor.relay_parent = test_state.relay_parent;
 
-			overseer_send(
-				&mut virtual_overseer,
-				CollatorProtocolMessage::NetworkBridgeUpdateV1(
-					NetworkBridgeEvent::PeerMessage(
-						peer_id,
-						protocol_v1::CollatorProtocolMessage::Collation(
-							request_id,
-							candidate_b.clone(),
-							PoV {
-								block_data: BlockData(vec![1, 2, 3]),
-							},
-						)
-					)
-				)
-			).await;
+			request_1.pending_response.send(Ok(
+				req_res_v1::CollationFetchingResponse::Collation(
+					candidate_b.clone(),
+					PoV {
+						block_data: BlockData(vec![1, 2, 3]),
+					},
+				).encode()
+			)).unwrap();
 
 			let collation_0 = rx_0.await.unwrap();
 			let collation_1 = rx_1.await.unwrap();
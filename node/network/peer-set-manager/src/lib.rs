@@ -0,0 +1,717 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Peer Set Manager subsystem.
+//!
+//! Keeps a score for every collator we have dealt with, which survives restarts of the node,
+//! and bans collators whose score drops too low for a while. The network bridge is told which
+//! peers on the collation peer-set belong to collators in good standing and which ones belong
+//! to banned collators.
+
+#![warn(missing_docs)]
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use codec::{Encode, Decode};
+use futures::{select, FutureExt};
+use futures_timer::Delay;
+use kvdb_rocksdb::{Database, DatabaseConfig};
+use kvdb::{KeyValueDB, DBTransaction};
+
+use polkadot_primitives::v1::CollatorId;
+use polkadot_subsystem::{
+	FromOverseer, OverseerSignal, Subsystem, SubsystemContext, SubsystemResult, SpawnedSubsystem,
+	messages::{AllMessages, CollatorReport, NetworkBridgeMessage, PeerSetManagerMessage},
+};
+use polkadot_node_network_protocol::{v1 as protocol_v1, NetworkBridgeEvent, PeerId};
+
+const LOG_TARGET: &str = "peer_set_manager";
+
+mod columns {
+	pub const COLLATORS: u32 = 0;
+	pub const NUM_COLUMNS: u32 = 1;
+}
+
+/// The score change of a collator for providing an invalid collation.
+const COST_INVALID_COLLATION: i32 = -500;
+/// The score change of a collator for a failed collation request.
+const COST_REQUEST_FAILED: i32 = -100;
+/// The score change of a collator for providing a good collation.
+const BENEFIT_GOOD_COLLATION: i32 = 50;
+
+/// The highest score a collator can have. This keeps collators from building up goodwill
+/// which they could then spend on spamming us.
+const MAX_SCORE: i32 = 1000;
+
+/// Ban collators once their score drops to this.
+const BAN_THRESHOLD: i32 = -1000;
+
+/// Ban collators for 1 day.
+const BAN_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Halve the scores of collators every hour.
+const SCORE_HALF_LIFE: Duration = Duration::from_secs(60 * 60);
+
+/// Check for bans that have expired every minute.
+const UNBAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Configuration of how collators are scored and banned.
+#[derive(Debug, Clone)]
+pub struct ReputationConfig {
+	/// Ban collators once their score drops to this.
+	pub ban_threshold: i32,
+	/// How long to ban collators for.
+	pub ban_duration: Duration,
+	/// The time after which half of the score of a collator has decayed.
+	pub score_half_life: Duration,
+	/// How often to check for bans that have expired.
+	pub unban_interval: Duration,
+}
+
+impl Default for ReputationConfig {
+	fn default() -> Self {
+		Self {
+			ban_threshold: BAN_THRESHOLD,
+			ban_duration: BAN_DURATION,
+			score_half_life: SCORE_HALF_LIFE,
+			unban_interval: UNBAN_INTERVAL,
+		}
+	}
+}
+
+/// Configuration for the peer set manager.
+pub struct Config {
+	/// Path to the database.
+	pub path: PathBuf,
+	/// The scoring and banning policy.
+	pub reputation: ReputationConfig,
+}
+
+/// The record of a collator kept in the database.
+#[derive(Debug, Clone, Default, Encode, Decode, PartialEq, Eq)]
+struct CollatorRecord {
+	/// The score of the collator as of `updated_at`.
+	score: i32,
+	/// The UNIX time in seconds at which the score was last updated.
+	updated_at: u64,
+	/// The UNIX time in seconds at which the last ban of the collator ends, if it was ever banned.
+	banned_until: Option<u64>,
+}
+
+impl CollatorRecord {
+	/// The score of the collator at the given time, after the decay since the last update.
+	fn score_at(&self, now: u64, half_life: Duration) -> i32 {
+		let elapsed = now.saturating_sub(self.updated_at) as f64;
+		let half_lives = elapsed / half_life.as_secs().max(1) as f64;
+
+		(self.score as f64 * 0.5f64.powf(half_lives)) as i32
+	}
+
+	/// The time at which the ban of the collator ends, if it is banned at the given time.
+	fn ban_end(&self, now: u64) -> Option<u64> {
+		self.banned_until.filter(|until| *until > now)
+	}
+}
+
+fn collator_key(collator: &CollatorId) -> Vec<u8> {
+	collator.encode()
+}
+
+fn load_record(db: &dyn KeyValueDB, collator: &CollatorId) -> CollatorRecord {
+	match db.get(columns::COLLATORS, &collator_key(collator)) {
+		Ok(Some(raw)) => CollatorRecord::decode(&mut &raw[..]).unwrap_or_else(|e| {
+			log::warn!(target: LOG_TARGET, "Corrupted record of collator {:?}: {:?}", collator, e);
+			CollatorRecord::default()
+		}),
+		Ok(None) => CollatorRecord::default(),
+		Err(e) => {
+			log::warn!(target: LOG_TARGET, "Failed to load the record of collator {:?}: {:?}", collator, e);
+			CollatorRecord::default()
+		}
+	}
+}
+
+fn store_record(db: &dyn KeyValueDB, collator: &CollatorId, record: &CollatorRecord) -> io::Result<()> {
+	let mut tx = DBTransaction::new();
+	tx.put_vec(columns::COLLATORS, &collator_key(collator), record.encode());
+
+	db.write(tx)
+}
+
+/// The current UNIX time in seconds.
+#[cfg(not(test))]
+fn time_now() -> u64 {
+	std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
+
+#[cfg(test)]
+fn time_now() -> u64 {
+	tests::TIME_NOW.with(|now| now.borrow().unwrap_or_default())
+}
+
+/// An implementation of the Peer Set Manager subsystem.
+pub struct PeerSetManagerSubsystem {
+	config: ReputationConfig,
+	inner: Arc<dyn KeyValueDB>,
+}
+
+impl PeerSetManagerSubsystem {
+	/// Create a new `PeerSetManagerSubsystem` with a given config on disk.
+	pub fn new_on_disk(config: Config) -> io::Result<Self> {
+		let db_config = DatabaseConfig::with_columns(columns::NUM_COLUMNS);
+
+		let path = config.path.to_str().ok_or_else(|| io::Error::new(
+			io::ErrorKind::Other,
+			format!("Bad database path: {:?}", config.path),
+		))?;
+
+		let db = Database::open(&db_config, &path)?;
+
+		Ok(Self {
+			config: config.reputation,
+			inner: Arc::new(db),
+		})
+	}
+
+	#[cfg(test)]
+	fn new_in_memory(inner: Arc<dyn KeyValueDB>, config: ReputationConfig) -> Self {
+		Self {
+			config,
+			inner,
+		}
+	}
+}
+
+#[derive(Default)]
+struct State {
+	/// The collators declared by the peers connected on the collation peer-set.
+	collators: HashMap<PeerId, CollatorId>,
+	/// The peers of banned collators, along with the UNIX time in seconds at which the bans end.
+	///
+	/// Peers are kept here after they disconnect, so they are kept off the peer-set until the
+	/// ban ends.
+	banned: HashMap<PeerId, u64>,
+	/// The peers of collators in good standing last sent to the network bridge.
+	sent_allowed: HashSet<PeerId>,
+	/// The peers of banned collators last sent to the network bridge.
+	sent_banned: HashSet<PeerId>,
+}
+
+/// Adjust the score of a collator according to the report and ban it, if the score drops too low.
+fn handle_report(
+	subsystem: &PeerSetManagerSubsystem,
+	state: &mut State,
+	collator: CollatorId,
+	report: CollatorReport,
+) -> io::Result<()> {
+	let now = time_now();
+	let mut record = load_record(&*subsystem.inner, &collator);
+
+	if record.ban_end(now).is_some() {
+		// Whatever banned collators do has no effect until the ban ends.
+		return Ok(());
+	}
+
+	let change = match report {
+		CollatorReport::InvalidCollation => COST_INVALID_COLLATION,
+		CollatorReport::RequestFailed => COST_REQUEST_FAILED,
+		CollatorReport::GoodCollation => BENEFIT_GOOD_COLLATION,
+	};
+
+	let score = record.score_at(now, subsystem.config.score_half_life)
+		.saturating_add(change)
+		.min(MAX_SCORE);
+
+	if score <= subsystem.config.ban_threshold {
+		let until = now.saturating_add(subsystem.config.ban_duration.as_secs());
+
+		log::info!(
+			target: LOG_TARGET,
+			"Banning collator {:?} until {} for a score of {}",
+			collator,
+			until,
+			score,
+		);
+
+		// The collator starts over once the ban ends.
+		record = CollatorRecord {
+			score: 0,
+			updated_at: now,
+			banned_until: Some(until),
+		};
+
+		for (peer, _) in state.collators.iter().filter(|(_, c)| **c == collator) {
+			state.banned.insert(peer.clone(), until);
+		}
+	} else {
+		record.score = score;
+		record.updated_at = now;
+	}
+
+	store_record(&*subsystem.inner, &collator, &record)
+}
+
+fn handle_network_update(
+	subsystem: &PeerSetManagerSubsystem,
+	state: &mut State,
+	event: NetworkBridgeEvent<protocol_v1::CollatorProtocolMessage>,
+) {
+	match event {
+		NetworkBridgeEvent::PeerDisconnected(peer) => {
+			state.collators.remove(&peer);
+		}
+		NetworkBridgeEvent::PeerMessage(peer, protocol_v1::CollatorProtocolMessage::Declare(collator)) => {
+			let record = load_record(&*subsystem.inner, &collator);
+
+			if let Some(until) = record.ban_end(time_now()) {
+				log::debug!(
+					target: LOG_TARGET,
+					"Peer {} declared banned collator {:?}",
+					peer,
+					collator,
+				);
+
+				state.banned.insert(peer.clone(), until);
+			}
+
+			state.collators.insert(peer, collator);
+		}
+		NetworkBridgeEvent::PeerMessage(_, protocol_v1::CollatorProtocolMessage::AdvertiseCollation(..)) |
+		NetworkBridgeEvent::PeerConnected(..) |
+		NetworkBridgeEvent::PeerViewChange(..) |
+		NetworkBridgeEvent::OurViewChange(_) => {}
+	}
+}
+
+/// The current scores of the given collators, with banned collators scoring `i32::MIN`.
+fn collator_scores(subsystem: &PeerSetManagerSubsystem, collators: &[CollatorId]) -> Vec<i32> {
+	let now = time_now();
+
+	collators.iter()
+		.map(|collator| {
+			let record = load_record(&*subsystem.inner, collator);
+
+			if record.ban_end(now).is_some() {
+				i32::MIN
+			} else {
+				record.score_at(now, subsystem.config.score_half_life)
+			}
+		})
+		.collect()
+}
+
+fn unban_expired(state: &mut State) {
+	let now = time_now();
+
+	state.banned.retain(|_, until| *until > now);
+}
+
+/// Inform the network bridge about the collator peers, if they have changed since we last did.
+async fn update_bridge<Context>(ctx: &mut Context, state: &mut State) -> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message=PeerSetManagerMessage>,
+{
+	let allowed: HashSet<_> = state.collators.keys()
+		.filter(|peer| !state.banned.contains_key(peer))
+		.cloned()
+		.collect();
+	let banned: HashSet<_> = state.banned.keys().cloned().collect();
+
+	if allowed == state.sent_allowed && banned == state.sent_banned {
+		return Ok(());
+	}
+
+	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SetCollatorPeers(
+		allowed.iter().cloned().collect(),
+		banned.iter().cloned().collect(),
+	))).await?;
+
+	state.sent_allowed = allowed;
+	state.sent_banned = banned;
+
+	Ok(())
+}
+
+fn process_message(
+	subsystem: &PeerSetManagerSubsystem,
+	state: &mut State,
+	msg: PeerSetManagerMessage,
+) {
+	match msg {
+		PeerSetManagerMessage::ReportCollator(collator, report) => {
+			if let Err(e) = handle_report(subsystem, state, collator.clone(), report) {
+				log::warn!(
+					target: LOG_TARGET,
+					"Failed to store the report {:?} of collator {:?}: {:?}",
+					report,
+					collator,
+					e,
+				);
+			}
+		}
+		PeerSetManagerMessage::NetworkBridgeUpdateV1(event) => {
+			handle_network_update(subsystem, state, event);
+		}
+		PeerSetManagerMessage::CollatorScores(collators, tx) => {
+			let _ = tx.send(collator_scores(subsystem, &collators));
+		}
+	}
+}
+
+async fn run<Context>(subsystem: PeerSetManagerSubsystem, mut ctx: Context)
+	-> SubsystemResult<()>
+where
+	Context: SubsystemContext<Message=PeerSetManagerMessage>,
+{
+	let ctx = &mut ctx;
+	let mut state = State::default();
+	let mut next_unban = Delay::new(subsystem.config.unban_interval).fuse();
+
+	loop {
+		select! {
+			incoming = ctx.recv().fuse() => {
+				match incoming? {
+					FromOverseer::Signal(OverseerSignal::Conclude) => break,
+					FromOverseer::Signal(_) => {}
+					FromOverseer::Communication { msg } => {
+						process_message(&subsystem, &mut state, msg);
+					}
+				}
+			}
+			_ = next_unban => {
+				unban_expired(&mut state);
+				next_unban = Delay::new(subsystem.config.unban_interval).fuse();
+			}
+		}
+
+		update_bridge(ctx, &mut state).await?;
+	}
+
+	Ok(())
+}
+
+impl<Context> Subsystem<Context> for PeerSetManagerSubsystem
+	where
+		Context: SubsystemContext<Message=PeerSetManagerMessage>,
+{
+	type Metrics = ();
+
+	fn start(self, ctx: Context) -> SpawnedSubsystem {
+		let future = Box::pin(async move {
+			if let Err(e) = run(self, ctx).await {
+				log::error!(target: LOG_TARGET, "Subsystem exited with an error {:?}", e);
+			}
+		});
+
+		SpawnedSubsystem {
+			name: "peer-set-manager-subsystem",
+			future,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{future, executor, channel::oneshot, Future};
+	use std::cell::RefCell;
+	use assert_matches::assert_matches;
+	use sp_keyring::Sr25519Keyring;
+	use polkadot_node_subsystem_test_helpers as test_helpers;
+
+	thread_local! {
+		pub(super) static TIME_NOW: RefCell<Option<u64>> = RefCell::new(None);
+	}
+
+	type VirtualOverseer = test_helpers::TestSubsystemContextHandle<PeerSetManagerMessage>;
+
+	fn set_time(now: u64) {
+		TIME_NOW.with(|t| *t.borrow_mut() = Some(now));
+	}
+
+	fn test_config() -> ReputationConfig {
+		ReputationConfig {
+			ban_threshold: -1000,
+			ban_duration: Duration::from_secs(100),
+			score_half_life: Duration::from_secs(10),
+			unban_interval: Duration::from_millis(10),
+		}
+	}
+
+	fn test_harness<T: Future<Output=()>>(
+		store: Arc<dyn KeyValueDB>,
+		test: impl FnOnce(VirtualOverseer) -> T,
+	) {
+		let pool = sp_core::testing::TaskExecutor::new();
+		let (context, virtual_overseer) = test_helpers::make_subsystem_context(pool);
+
+		let subsystem = PeerSetManagerSubsystem::new_in_memory(store, test_config());
+		let subsystem = run(subsystem, context);
+
+		let test_fut = test(virtual_overseer);
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	async fn declare(virtual_overseer: &mut VirtualOverseer, peer: &PeerId, collator: &CollatorId) {
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: PeerSetManagerMessage::NetworkBridgeUpdateV1(NetworkBridgeEvent::PeerMessage(
+				peer.clone(),
+				protocol_v1::CollatorProtocolMessage::Declare(collator.clone()),
+			)),
+		}).await;
+	}
+
+	async fn report(virtual_overseer: &mut VirtualOverseer, collator: &CollatorId, report: CollatorReport) {
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: PeerSetManagerMessage::ReportCollator(collator.clone(), report),
+		}).await;
+	}
+
+	async fn assert_collator_peers(
+		virtual_overseer: &mut VirtualOverseer,
+		expected_allowed: Vec<PeerId>,
+		expected_banned: Vec<PeerId>,
+	) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::SetCollatorPeers(allowed, banned)) => {
+				assert_eq!(
+					allowed.into_iter().collect::<HashSet<_>>(),
+					expected_allowed.into_iter().collect::<HashSet<_>>(),
+				);
+				assert_eq!(
+					banned.into_iter().collect::<HashSet<_>>(),
+					expected_banned.into_iter().collect::<HashSet<_>>(),
+				);
+			}
+		);
+	}
+
+	#[test]
+	fn scores_decay_over_time() {
+		let record = CollatorRecord {
+			score: -800,
+			updated_at: 1000,
+			banned_until: None,
+		};
+		let half_life = Duration::from_secs(10);
+
+		assert_eq!(record.score_at(1000, half_life), -800);
+		assert_eq!(record.score_at(1010, half_life), -400);
+		assert_eq!(record.score_at(1020, half_life), -200);
+		assert_eq!(record.score_at(2000, half_life), 0);
+	}
+
+	#[test]
+	fn invalid_collations_lead_to_a_ban() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let collator: CollatorId = Sr25519Keyring::Alice.public().into();
+		let peer = PeerId::random();
+
+		set_time(1000);
+
+		test_harness(store.clone(), |mut virtual_overseer| async move {
+			declare(&mut virtual_overseer, &peer, &collator).await;
+			assert_collator_peers(&mut virtual_overseer, vec![peer.clone()], vec![]).await;
+
+			report(&mut virtual_overseer, &collator, CollatorReport::InvalidCollation).await;
+			report(&mut virtual_overseer, &collator, CollatorReport::InvalidCollation).await;
+
+			assert_collator_peers(&mut virtual_overseer, vec![], vec![peer.clone()]).await;
+
+			assert_eq!(
+				load_record(&*store, &collator),
+				CollatorRecord {
+					score: 0,
+					updated_at: 1000,
+					banned_until: Some(1100),
+				},
+			);
+		});
+	}
+
+	#[test]
+	fn penalties_decay_before_a_ban() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let collator: CollatorId = Sr25519Keyring::Alice.public().into();
+		let peer = PeerId::random();
+		let other_peer = PeerId::random();
+
+		set_time(1000);
+
+		test_harness(store.clone(), |mut virtual_overseer| async move {
+			declare(&mut virtual_overseer, &peer, &collator).await;
+			assert_collator_peers(&mut virtual_overseer, vec![peer.clone()], vec![]).await;
+
+			report(&mut virtual_overseer, &collator, CollatorReport::InvalidCollation).await;
+
+			// half of the penalty has decayed by now.
+			set_time(1010);
+			report(&mut virtual_overseer, &collator, CollatorReport::InvalidCollation).await;
+
+			// the collator is still in good standing.
+			declare(&mut virtual_overseer, &other_peer, &Sr25519Keyring::Bob.public().into()).await;
+			assert_collator_peers(
+				&mut virtual_overseer,
+				vec![peer.clone(), other_peer.clone()],
+				vec![],
+			).await;
+
+			assert_eq!(
+				load_record(&*store, &collator),
+				CollatorRecord {
+					score: -750,
+					updated_at: 1010,
+					banned_until: None,
+				},
+			);
+		});
+	}
+
+	#[test]
+	fn collator_scores_are_decayed_and_banned_collators_score_lowest() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let alice: CollatorId = Sr25519Keyring::Alice.public().into();
+		let bob: CollatorId = Sr25519Keyring::Bob.public().into();
+		let charlie: CollatorId = Sr25519Keyring::Charlie.public().into();
+
+		set_time(1000);
+
+		store_record(&*store, &alice, &CollatorRecord {
+			score: 400,
+			updated_at: 990,
+			banned_until: None,
+		}).unwrap();
+		store_record(&*store, &bob, &CollatorRecord {
+			score: 0,
+			updated_at: 990,
+			banned_until: Some(1050),
+		}).unwrap();
+
+		test_harness(store, |mut virtual_overseer| async move {
+			let (tx, rx) = oneshot::channel();
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: PeerSetManagerMessage::CollatorScores(vec![alice, bob, charlie], tx),
+			}).await;
+
+			assert_eq!(rx.await.unwrap(), vec![200, i32::MIN, 0]);
+		});
+	}
+
+	#[test]
+	fn bans_persist_across_restarts() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let collator: CollatorId = Sr25519Keyring::Alice.public().into();
+		let peer = PeerId::random();
+
+		set_time(1000);
+
+		store_record(&*store, &collator, &CollatorRecord {
+			score: 0,
+			updated_at: 950,
+			banned_until: Some(1050),
+		}).unwrap();
+
+		test_harness(store, |mut virtual_overseer| async move {
+			declare(&mut virtual_overseer, &peer, &collator).await;
+			assert_collator_peers(&mut virtual_overseer, vec![], vec![peer.clone()]).await;
+		});
+	}
+
+	#[test]
+	fn banned_peers_stay_banned_after_disconnecting() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let collator: CollatorId = Sr25519Keyring::Alice.public().into();
+		let peer = PeerId::random();
+		let other_peer = PeerId::random();
+
+		set_time(1000);
+
+		store_record(&*store, &collator, &CollatorRecord {
+			score: 0,
+			updated_at: 950,
+			banned_until: Some(1050),
+		}).unwrap();
+
+		test_harness(store, |mut virtual_overseer| async move {
+			declare(&mut virtual_overseer, &peer, &collator).await;
+			assert_collator_peers(&mut virtual_overseer, vec![], vec![peer.clone()]).await;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: PeerSetManagerMessage::NetworkBridgeUpdateV1(
+					NetworkBridgeEvent::PeerDisconnected(peer.clone()),
+				),
+			}).await;
+
+			declare(&mut virtual_overseer, &other_peer, &Sr25519Keyring::Bob.public().into()).await;
+			assert_collator_peers(
+				&mut virtual_overseer,
+				vec![other_peer.clone()],
+				vec![peer.clone()],
+			).await;
+		});
+	}
+
+	#[test]
+	fn bans_expire() {
+		let store = Arc::new(kvdb_memorydb::create(columns::NUM_COLUMNS));
+		let collator: CollatorId = Sr25519Keyring::Alice.public().into();
+		let peer = PeerId::random();
+
+		set_time(1000);
+
+		store_record(&*store, &collator, &CollatorRecord {
+			score: 0,
+			updated_at: 950,
+			banned_until: Some(1050),
+		}).unwrap();
+
+		test_harness(store.clone(), |mut virtual_overseer| async move {
+			declare(&mut virtual_overseer, &peer, &collator).await;
+			assert_collator_peers(&mut virtual_overseer, vec![], vec![peer.clone()]).await;
+
+			set_time(1050);
+			assert_collator_peers(&mut virtual_overseer, vec![peer.clone()], vec![]).await;
+
+			// the collator starts over with a clean slate.
+			report(&mut virtual_overseer, &collator, CollatorReport::RequestFailed).await;
+			report(&mut virtual_overseer, &collator, CollatorReport::InvalidCollation).await;
+			declare(&mut virtual_overseer, &PeerId::random(), &Sr25519Keyring::Bob.public().into()).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SetCollatorPeers(allowed, banned)) => {
+					assert!(allowed.contains(&peer));
+					assert!(banned.is_empty());
+				}
+			);
+
+			assert_eq!(load_record(&*store, &collator).score, -600);
+		});
+	}
+}
@@ -16,8 +16,9 @@
 
 //! PoV Distribution Subsystem of Polkadot.
 //!
-//! This is a gossip implementation of code that is responsible for distributing PoVs
-//! among validators.
+//! This subsystem is responsible for fetching PoVs from validators and for serving the PoVs
+//! we know about to validators requesting them, using the PoV fetching request/response
+//! protocol.
 
 use polkadot_primitives::v1::{Hash, PoV, CandidateDescriptor};
 use polkadot_subsystem::{
@@ -28,22 +29,23 @@ use polkadot_subsystem::messages::{
 };
 use polkadot_node_network_protocol::{
 	v1 as protocol_v1, ReputationChange as Rep, NetworkBridgeEvent, PeerId, View,
+	request_response::{
+		v1 as req_res_v1, IncomingRequest, OutgoingRequest, OutgoingResult, RequestError, Requests,
+	},
 };
 
 use futures::prelude::*;
 use futures::channel::oneshot;
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
 
-use std::collections::{hash_map::{Entry, HashMap}, HashSet};
+use std::collections::hash_map::{Entry, HashMap};
 use std::sync::Arc;
 
-const COST_APPARENT_FLOOD: Rep = Rep::new(-500, "Peer appears to be flooding us with PoV requests");
-const COST_UNEXPECTED_POV: Rep = Rep::new(-500, "Peer sent us an unexpected PoV");
-const COST_AWAITED_NOT_IN_VIEW: Rep
-	= Rep::new(-100, "Peer claims to be awaiting something outside of its view");
+const COST_INVALID_POV: Rep = Rep::new(-500, "Peer sent us a PoV not matching the requested hash");
+const COST_CORRUPTED_RESPONSE: Rep = Rep::new(-100, "Peer sent us an undecodable PoV response");
 
 const BENEFIT_FRESH_POV: Rep = Rep::new(25, "Peer supplied us with an awaited PoV");
-const BENEFIT_LATE_POV: Rep = Rep::new(10, "Peer supplied us with an awaited PoV, \
-	but was not the first to do so");
 
 /// The PoV Distribution Subsystem.
 pub struct PoVDistribution;
@@ -63,42 +65,34 @@ impl<C> Subsystem<C> for PoVDistribution
 	}
 }
 
+/// The outcome of a PoV request, along with the relay-parent, PoV hash and peer it was made for.
+type PoVResponse = (Hash, Hash, PeerId, OutgoingResult<req_res_v1::PoVFetchingResponse>);
+
 struct State {
 	relay_parent_state: HashMap<Hash, BlockBasedState>,
-	peer_state: HashMap<PeerId, PeerState>,
-	our_view: View,
+	/// The views of all connected peers.
+	peer_views: HashMap<PeerId, View>,
+	/// PoV requests which are currently in flight.
+	///
+	/// Requests time out on the network level, so every request eventually resolves.
+	requests_in_progress: FuturesUnordered<BoxFuture<'static, PoVResponse>>,
 }
 
 struct BlockBasedState {
 	known: HashMap<Hash, Arc<PoV>>,
-	/// All the PoVs we are or were fetching, coupled with channels expecting the data.
-	///
-	/// This may be an empty list, which indicates that we were once awaiting this PoV but have
-	/// received it already.
-	fetching: HashMap<Hash, Vec<oneshot::Sender<Arc<PoV>>>>,
+	/// All the PoVs we are fetching.
+	fetching: HashMap<Hash, Fetch>,
 	n_validators: usize,
 }
 
-#[derive(Default)]
-struct PeerState {
-	/// A set of awaited PoV-hashes for each relay-parent in the peer's view.
-	awaited: HashMap<Hash, HashSet<Hash>>,
-}
-
-fn awaiting_message(relay_parent: Hash, awaiting: Vec<Hash>)
-	-> protocol_v1::ValidationProtocol
-{
-	protocol_v1::ValidationProtocol::PoVDistribution(
-		protocol_v1::PoVDistributionMessage::Awaiting(relay_parent, awaiting)
-	)
-}
-
-fn send_pov_message(relay_parent: Hash, pov_hash: Hash, pov: PoV)
-	-> protocol_v1::ValidationProtocol
-{
-	protocol_v1::ValidationProtocol::PoVDistribution(
-		protocol_v1::PoVDistributionMessage::SendPoV(relay_parent, pov_hash, pov)
-	)
+/// An ongoing fetch of a PoV.
+struct Fetch {
+	/// Channels expecting the data.
+	response_senders: Vec<oneshot::Sender<Arc<PoV>>>,
+	/// The peer we are currently requesting the PoV from, if any.
+	requested_from: Option<PeerId>,
+	/// Peers with the relay-parent in their view which we haven't requested the PoV from yet.
+	untried: Vec<PeerId>,
 }
 
 /// Handles the signal. If successful, returns `true` if the subsystem should conclude,
@@ -143,6 +137,7 @@ async fn handle_signal(
 			}
 
 			for relay_parent in deactivated {
+				// Responses to requests on this relay-parent are ignored once they arrive.
 				state.relay_parent_state.remove(&relay_parent);
 			}
 
@@ -152,87 +147,40 @@ async fn handle_signal(
 	}
 }
 
-/// Notify peers that we are awaiting a given PoV hash.
+/// Request the PoV from the next peer we haven't tried yet, unless a request is in flight.
 ///
-/// This only notifies peers who have the relay parent in their view.
-async fn notify_all_we_are_awaiting(
-	peers: &mut HashMap<PeerId, PeerState>,
+/// If there are no peers left to try, the fetch stays pending until a peer with the
+/// relay-parent in view shows up.
+async fn request_from_next_peer(
 	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	requests_in_progress: &mut FuturesUnordered<BoxFuture<'static, PoVResponse>>,
 	relay_parent: Hash,
 	pov_hash: Hash,
+	fetch: &mut Fetch,
 ) -> SubsystemResult<()> {
-	// We use `awaited` as a proxy for which heads are in the peer's view.
-	let peers_to_send: Vec<_> = peers.iter()
-		.filter_map(|(peer, state)| if state.awaited.contains_key(&relay_parent) {
-			Some(peer.clone())
-		} else {
-			None
-		})
-		.collect();
-
-	if peers_to_send.is_empty() { return Ok(()) }
-
-	let payload = awaiting_message(relay_parent, vec![pov_hash]);
+	if fetch.requested_from.is_some() { return Ok(()) }
 
-	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
-		peers_to_send,
-		payload,
-	))).await
-}
-
-/// Notify one peer about everything we're awaiting at a given relay-parent.
-async fn notify_one_we_are_awaiting_many(
-	peer: &PeerId,
-	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
-	relay_parent_state: &HashMap<Hash, BlockBasedState>,
-	relay_parent: Hash,
-) -> SubsystemResult<()> {
-	let awaiting_hashes = relay_parent_state.get(&relay_parent).into_iter().flat_map(|s| {
-		// Send the peer everything we are fetching at this relay-parent
-		s.fetching.iter()
-			.filter(|(_, senders)| !senders.is_empty()) // that has not been completed already.
-			.map(|(pov_hash, _)| *pov_hash)
-	}).collect::<Vec<_>>();
-
-	if awaiting_hashes.is_empty() { return Ok(()) }
-
-	let payload = awaiting_message(relay_parent, awaiting_hashes);
-
-	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
-		vec![peer.clone()],
-		payload,
-	))).await
-}
-
-/// Distribute a PoV to peers who are awaiting it.
-async fn distribute_to_awaiting(
-	peers: &mut HashMap<PeerId, PeerState>,
-	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
-	relay_parent: Hash,
-	pov_hash: Hash,
-	pov: &PoV,
-) -> SubsystemResult<()> {
-	// Send to all peers who are awaiting the PoV and have that relay-parent in their view.
-	//
-	// Also removes it from their awaiting set.
-	let peers_to_send: Vec<_> = peers.iter_mut()
-		.filter_map(|(peer, state)| state.awaited.get_mut(&relay_parent).and_then(|awaited| {
-			if awaited.remove(&pov_hash) {
-				Some(peer.clone())
-			} else {
-				None
-			}
-		}))
-		.collect();
+	let peer = match fetch.untried.pop() {
+		Some(peer) => peer,
+		None => return Ok(()),
+	};
 
-	if peers_to_send.is_empty() { return Ok(()) }
+	let (request, response) = OutgoingRequest::new(
+		peer.clone(),
+		req_res_v1::PoVFetchingRequest {
+			relay_parent,
+			pov_hash,
+		},
+	);
 
-	let payload = send_pov_message(relay_parent, pov_hash, pov.clone());
+	fetch.requested_from = Some(peer.clone());
+	requests_in_progress.push(Box::pin(async move {
+		(relay_parent, pov_hash, peer, response.await)
+	}));
 
-	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendValidationMessage(
-		peers_to_send,
-		payload,
-	))).await
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::SendRequests(vec![Requests::PoVFetching(request)])
+	)).await
 }
 
 /// Handles a `FetchPoV` message.
@@ -253,66 +201,61 @@ async fn handle_fetch(
 		return Ok(());
 	}
 
-	{
-		match relay_parent_state.fetching.entry(descriptor.pov_hash) {
-			Entry::Occupied(mut e) => {
-				// we are already awaiting this PoV if there is an entry.
-				e.get_mut().push(response_sender);
-				return Ok(());
-			}
-			Entry::Vacant(e) => {
-				e.insert(vec![response_sender]);
-			}
-		}
+	if let Some(fetch) = relay_parent_state.fetching.get_mut(&descriptor.pov_hash) {
+		// we are already fetching this PoV if there is an entry.
+		fetch.response_senders.push(response_sender);
+		return Ok(());
 	}
 
-	if relay_parent_state.fetching.len() > 2 * relay_parent_state.n_validators {
+	if relay_parent_state.fetching.len() >= 2 * relay_parent_state.n_validators {
 		log::warn!("Other subsystems have requested PoV distribution to \
 			fetch more PoVs than reasonably expected: {}", relay_parent_state.fetching.len());
 		return Ok(());
 	}
 
-	// Issue an `Awaiting` message to all peers with this in their view.
-	notify_all_we_are_awaiting(
-		&mut state.peer_state,
+	// Fetch the PoV from the peers with this in their view.
+	let untried = state.peer_views.iter()
+		.filter(|(_, view)| view.0.contains(&relay_parent))
+		.map(|(peer, _)| peer.clone())
+		.collect();
+
+	let fetch = relay_parent_state.fetching.entry(descriptor.pov_hash).or_insert(Fetch {
+		response_senders: vec![response_sender],
+		requested_from: None,
+		untried,
+	});
+
+	request_from_next_peer(
 		ctx,
+		&mut state.requests_in_progress,
 		relay_parent,
-		descriptor.pov_hash
+		descriptor.pov_hash,
+		fetch,
 	).await
 }
 
 /// Handles a `DistributePoV` message.
-async fn handle_distribute(
+///
+/// The PoV is served to peers requesting it from now on.
+fn handle_distribute(
 	state: &mut State,
-	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
 	relay_parent: Hash,
 	descriptor: CandidateDescriptor,
 	pov: Arc<PoV>,
-) -> SubsystemResult<()> {
+) {
 	let relay_parent_state = match state.relay_parent_state.get_mut(&relay_parent) {
-		None => return Ok(()),
+		None => return,
 		Some(s) => s,
 	};
 
-	if let Some(our_awaited) = relay_parent_state.fetching.get_mut(&descriptor.pov_hash) {
-		// Drain all the senders, but keep the entry in the map around intentionally.
-		//
-		// It signals that we were at one point awaiting this, so we will be able to tell
-		// why peers are sending it to us.
-		for response_sender in our_awaited.drain(..) {
+	if let Some(fetch) = relay_parent_state.fetching.remove(&descriptor.pov_hash) {
+		// An in-flight request for the PoV is ignored once it resolves.
+		for response_sender in fetch.response_senders {
 			let _ = response_sender.send(pov.clone());
 		}
 	}
 
-	relay_parent_state.known.insert(descriptor.pov_hash, pov.clone());
-
-	distribute_to_awaiting(
-		&mut state.peer_state,
-		ctx,
-		relay_parent,
-		descriptor.pov_hash,
-		&*pov,
-	).await
+	relay_parent_state.known.insert(descriptor.pov_hash, pov);
 }
 
 /// Report a reputation change for a peer.
@@ -324,123 +267,84 @@ async fn report_peer(
 	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep))).await
 }
 
-/// Handle a notification from a peer that they are awaiting some PoVs.
-async fn handle_awaiting(
-	state: &mut State,
-	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
-	peer: PeerId,
-	relay_parent: Hash,
-	pov_hashes: Vec<Hash>,
-) -> SubsystemResult<()> {
-	if !state.our_view.0.contains(&relay_parent) {
-		report_peer(ctx, peer, COST_AWAITED_NOT_IN_VIEW).await?;
-		return Ok(());
-	}
-
-	let relay_parent_state = match state.relay_parent_state.get_mut(&relay_parent) {
-		None => {
-			log::warn!("PoV Distribution relay parent state out-of-sync with our view");
-			return Ok(());
-		}
-		Some(s) => s,
-	};
+/// Handles a request for a PoV by a peer, responding with the PoV if we know it.
+fn handle_pov_request(
+	state: &State,
+	request: IncomingRequest<req_res_v1::PoVFetchingRequest>,
+) {
+	let req_res_v1::PoVFetchingRequest { relay_parent, pov_hash } = request.payload;
 
-	let peer_awaiting = match
-		state.peer_state.get_mut(&peer).and_then(|s| s.awaited.get_mut(&relay_parent))
+	let response = match state.relay_parent_state.get(&relay_parent)
+		.and_then(|s| s.known.get(&pov_hash))
 	{
-		None => {
-			report_peer(ctx, peer, COST_AWAITED_NOT_IN_VIEW).await?;
-			return Ok(());
-		}
-		Some(a) => a,
+		Some(pov) => req_res_v1::PoVFetchingResponse::PoV((**pov).clone()),
+		None => req_res_v1::PoVFetchingResponse::NoSuchPoV,
 	};
 
-	let will_be_awaited = peer_awaiting.len() + pov_hashes.len();
-	if will_be_awaited <= 2 * relay_parent_state.n_validators {
-		for pov_hash in pov_hashes {
-			// For all requested PoV hashes, if we have it, we complete the request immediately.
-			// Otherwise, we note that the peer is awaiting the PoV.
-			if let Some(pov) = relay_parent_state.known.get(&pov_hash) {
-				let payload = send_pov_message(relay_parent, pov_hash, (&**pov).clone());
-				ctx.send_message(AllMessages::NetworkBridge(
-					NetworkBridgeMessage::SendValidationMessage(vec![peer.clone()], payload)
-				)).await?;
-			} else {
-				peer_awaiting.insert(pov_hash);
-			}
-		}
-	} else {
-		report_peer(ctx, peer, COST_APPARENT_FLOOD).await?;
+	if request.send_response(response).is_err() {
+		log::debug!(
+			target: "pov_distribution",
+			"Failed to respond to PoV request for {:?}, the request has been canceled",
+			pov_hash,
+		);
 	}
-
-	Ok(())
 }
 
-/// Handle an incoming PoV from our peer. Reports them if unexpected, rewards them if not.
+/// Handles the response to one of our PoV requests. Reports the peer if the response is
+/// invalid, rewards them if it completes the fetch.
 ///
-/// Completes any requests awaiting that PoV.
-async fn handle_incoming_pov(
+/// If the fetch is not complete, the PoV is requested from the next peer.
+async fn handle_pov_response(
 	state: &mut State,
 	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
-	peer: PeerId,
-	relay_parent: Hash,
-	pov_hash: Hash,
-	pov: PoV,
+	(relay_parent, pov_hash, peer, response): PoVResponse,
 ) -> SubsystemResult<()> {
+	// The relay-parent may have been deactivated or the PoV distributed in the meantime.
 	let relay_parent_state = match state.relay_parent_state.get_mut(&relay_parent) {
-		None =>	{
-			report_peer(ctx, peer, COST_UNEXPECTED_POV).await?;
-			return Ok(());
-		},
-		Some(r) => r,
+		None => return Ok(()),
+		Some(s) => s,
 	};
 
-	let pov = {
-		// Do validity checks and complete all senders awaiting this PoV.
-		let fetching = match relay_parent_state.fetching.get_mut(&pov_hash) {
-			None => {
-				report_peer(ctx, peer, COST_UNEXPECTED_POV).await?;
-				return Ok(());
-			}
-			Some(f) => f,
-		};
+	let mut fetch = match relay_parent_state.fetching.entry(pov_hash) {
+		Entry::Occupied(e) if e.get().requested_from.as_ref() == Some(&peer) => e,
+		_ => return Ok(()),
+	};
 
-		let hash = pov.hash();
-		if hash != pov_hash {
-			report_peer(ctx, peer, COST_UNEXPECTED_POV).await?;
-			return Ok(());
-		}
+	fetch.get_mut().requested_from = None;
 
-		let pov = Arc::new(pov);
+	match response {
+		Ok(req_res_v1::PoVFetchingResponse::PoV(pov)) => {
+			if pov.hash() != pov_hash {
+				report_peer(ctx, peer, COST_INVALID_POV).await?;
+			} else {
+				let pov = Arc::new(pov);
+				for response_sender in fetch.remove().response_senders {
+					let _ = response_sender.send(pov.clone());
+				}
 
-		if fetching.is_empty() {
-			// fetching is empty whenever we were awaiting something and
-			// it was completed afterwards.
-			report_peer(ctx, peer.clone(), BENEFIT_LATE_POV).await?;
-		} else {
-			// fetching is non-empty when the peer just provided us with data we needed.
-			report_peer(ctx, peer.clone(), BENEFIT_FRESH_POV).await?;
-		}
+				relay_parent_state.known.insert(pov_hash, pov);
 
-		for response_sender in fetching.drain(..) {
-			let _ = response_sender.send(pov.clone());
+				return report_peer(ctx, peer, BENEFIT_FRESH_POV).await;
+			}
+		}
+		Ok(req_res_v1::PoVFetchingResponse::NoSuchPoV) => {}
+		Err(RequestError::InvalidResponse(_)) => {
+			report_peer(ctx, peer, COST_CORRUPTED_RESPONSE).await?;
+		}
+		Err(e) => {
+			log::debug!(
+				target: "pov_distribution",
+				"PoV request to {:?} failed: {:?}", peer, e,
+			);
 		}
-
-		pov
-	};
-
-	// make sure we don't consider this peer as awaiting that PoV anymore.
-	if let Some(peer_state) = state.peer_state.get_mut(&peer) {
-		peer_state.awaited.remove(&pov_hash);
 	}
 
-	// distribute the PoV to all other peers who are awaiting it.
-	distribute_to_awaiting(
-		&mut state.peer_state,
+	request_from_next_peer(
 		ctx,
+		&mut state.requests_in_progress,
 		relay_parent,
 		pov_hash,
-		&*pov,
+		fetch.get_mut(),
 	).await
 }
 
@@ -452,61 +356,48 @@ async fn handle_network_update(
 ) -> SubsystemResult<()> {
 	match update {
 		NetworkBridgeEvent::PeerConnected(peer, _observed_role) => {
-			state.peer_state.insert(peer, PeerState { awaited: HashMap::new() });
+			state.peer_views.insert(peer, View(Vec::new()));
 			Ok(())
 		}
 		NetworkBridgeEvent::PeerDisconnected(peer) => {
-			state.peer_state.remove(&peer);
-			Ok(())
-		}
-		NetworkBridgeEvent::PeerViewChange(peer_id, view) => {
-			if let Some(peer_state) = state.peer_state.get_mut(&peer_id) {
-				// prune anything not in the new view.
-				peer_state.awaited.retain(|relay_parent, _| view.0.contains(&relay_parent));
-
-				// introduce things from the new view.
-				for relay_parent in view.0.iter() {
-					if let Entry::Vacant(entry) = peer_state.awaited.entry(*relay_parent) {
-						entry.insert(HashSet::new());
-
-						// Notify the peer about everything we're awaiting at the new relay-parent.
-						notify_one_we_are_awaiting_many(
-							&peer_id,
-							ctx,
-							&state.relay_parent_state,
-							*relay_parent,
-						).await?;
-					}
+			state.peer_views.remove(&peer);
+			for relay_parent_state in state.relay_parent_state.values_mut() {
+				for fetch in relay_parent_state.fetching.values_mut() {
+					fetch.untried.retain(|p| p != &peer);
 				}
 			}
-
 			Ok(())
 		}
-		NetworkBridgeEvent::PeerMessage(peer, message) => {
-			match message {
-				protocol_v1::PoVDistributionMessage::Awaiting(relay_parent, pov_hashes)
-					=> handle_awaiting(
-						state,
-						ctx,
-						peer,
-						relay_parent,
-						pov_hashes,
-					).await,
-				protocol_v1::PoVDistributionMessage::SendPoV(relay_parent, pov_hash, pov)
-					=> handle_incoming_pov(
-						state,
+		NetworkBridgeEvent::PeerViewChange(peer_id, view) => {
+			let old_view = match state.peer_views.get_mut(&peer_id) {
+				Some(v) => std::mem::replace(v, view.clone()),
+				None => return Ok(()),
+			};
+
+			// Fetch what we are still missing at the new relay-parents from the peer.
+			for relay_parent in view.difference(&old_view) {
+				let relay_parent_state = match state.relay_parent_state.get_mut(relay_parent) {
+					Some(s) => s,
+					None => continue,
+				};
+
+				for (pov_hash, fetch) in relay_parent_state.fetching.iter_mut() {
+					fetch.untried.push(peer_id.clone());
+
+					request_from_next_peer(
 						ctx,
-						peer,
-						relay_parent,
-						pov_hash,
-						pov,
-					).await,
+						&mut state.requests_in_progress,
+						*relay_parent,
+						*pov_hash,
+						fetch,
+					).await?;
+				}
 			}
-		}
-		NetworkBridgeEvent::OurViewChange(view) => {
-			state.our_view = view;
+
 			Ok(())
 		}
+		NetworkBridgeEvent::PeerMessage(_peer, message) => match message {},
+		NetworkBridgeEvent::OurViewChange(_view) => Ok(()),
 	}
 }
 
@@ -515,39 +406,44 @@ async fn run(
 ) -> SubsystemResult<()> {
 	let mut state = State {
 		relay_parent_state: HashMap::new(),
-		peer_state: HashMap::new(),
-		our_view: View(Vec::new()),
+		peer_views: HashMap::new(),
+		requests_in_progress: FuturesUnordered::new(),
 	};
 
 	loop {
-		match ctx.recv().await? {
-			FromOverseer::Signal(signal) => if handle_signal(&mut state, &mut ctx, signal).await? {
-				return Ok(());
-			},
-			FromOverseer::Communication { msg } => match msg {
-				PoVDistributionMessage::FetchPoV(relay_parent, descriptor, response_sender) =>
-					handle_fetch(
-						&mut state,
-						&mut ctx,
-						relay_parent,
-						descriptor,
-						response_sender,
-					).await?,
-				PoVDistributionMessage::DistributePoV(relay_parent, descriptor, pov) =>
-					handle_distribute(
-						&mut state,
-						&mut ctx,
-						relay_parent,
-						descriptor,
-						pov,
-					).await?,
-				PoVDistributionMessage::NetworkBridgeUpdateV1(event) =>
-					handle_network_update(
-						&mut state,
-						&mut ctx,
-						event,
-					).await?,
+		futures::select! {
+			msg = ctx.recv().fuse() => match msg? {
+				FromOverseer::Signal(signal) => if handle_signal(&mut state, &mut ctx, signal).await? {
+					return Ok(());
+				},
+				FromOverseer::Communication { msg } => match msg {
+					PoVDistributionMessage::FetchPoV(relay_parent, descriptor, response_sender) =>
+						handle_fetch(
+							&mut state,
+							&mut ctx,
+							relay_parent,
+							descriptor,
+							response_sender,
+						).await?,
+					PoVDistributionMessage::DistributePoV(relay_parent, descriptor, pov) =>
+						handle_distribute(
+							&mut state,
+							relay_parent,
+							descriptor,
+							pov,
+						),
+					PoVDistributionMessage::PoVFetchingRequest(request) =>
+						handle_pov_request(&state, request),
+					PoVDistributionMessage::NetworkBridgeUpdateV1(event) =>
+						handle_network_update(
+							&mut state,
+							&mut ctx,
+							event,
+						).await?,
+				},
 			},
+			response = state.requests_in_progress.select_next_some() =>
+				handle_pov_response(&mut state, &mut ctx, response).await?,
 		}
 	}
 }
@@ -556,251 +452,161 @@ async fn run(
 mod tests {
 	use super::*;
 	use futures::executor;
+	use parity_scale_codec::Encode;
 	use polkadot_primitives::v1::BlockData;
+	use polkadot_node_subsystem_test_helpers::TestSubsystemContextHandle;
 	use assert_matches::assert_matches;
 
 	fn make_pov(data: Vec<u8>) -> PoV {
 		PoV { block_data: BlockData(data) }
 	}
 
-	fn make_peer_state(awaited: Vec<(Hash, Vec<Hash>)>)
-		-> PeerState
-	{
-		PeerState {
-			awaited: awaited.into_iter().map(|(rp, h)| (rp, h.into_iter().collect())).collect()
+	fn make_state(relay_parents: Vec<Hash>, peer_views: Vec<(PeerId, Vec<Hash>)>) -> State {
+		State {
+			relay_parent_state: relay_parents.into_iter().map(|relay_parent| (
+				relay_parent,
+				BlockBasedState {
+					known: HashMap::new(),
+					fetching: HashMap::new(),
+					n_validators: 10,
+				},
+			)).collect(),
+			peer_views: peer_views.into_iter().map(|(peer, view)| (peer, View(view))).collect(),
+			requests_in_progress: FuturesUnordered::new(),
 		}
 	}
 
+	async fn assert_pov_request(
+		handle: &mut TestSubsystemContextHandle<PoVDistributionMessage>,
+		relay_parent: Hash,
+		pov_hash: Hash,
+	) -> OutgoingRequest<req_res_v1::PoVFetchingRequest> {
+		assert_matches!(
+			handle.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::SendRequests(mut requests)) => {
+				assert_eq!(requests.len(), 1);
+				assert_matches!(
+					requests.pop().unwrap(),
+					Requests::PoVFetching(request) => {
+						assert_eq!(request.payload.relay_parent, relay_parent);
+						assert_eq!(request.payload.pov_hash, pov_hash);
+						request
+					}
+				)
+			}
+		)
+	}
+
 	#[test]
-	fn distributes_to_those_awaiting_and_completes_local() {
+	fn distribute_completes_local_and_serves_requests() {
 		let hash_a: Hash = [0; 32].into();
-		let hash_b: Hash = [1; 32].into();
 
 		let peer_a = PeerId::random();
-		let peer_b = PeerId::random();
-		let peer_c = PeerId::random();
 
 		let (pov_send, pov_recv) = oneshot::channel();
 		let pov = make_pov(vec![1, 2, 3]);
 		let pov_hash = pov.hash();
 
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let mut b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
-
-				b.fetching.insert(pov_hash, vec![pov_send]);
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				// peer A has hash_a in its view and is awaiting the PoV.
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![pov_hash])]),
-				);
-
-				// peer B has hash_a in its view but is not awaiting.
-				s.insert(
-					peer_b.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				// peer C doesn't have hash_a in its view but is awaiting the PoV under hash_b.
-				s.insert(
-					peer_c.clone(),
-					make_peer_state(vec![(hash_b, vec![pov_hash])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a, hash_b]),
-		};
+		let mut state = make_state(vec![hash_a], vec![]);
+		state.relay_parent_state.get_mut(&hash_a).unwrap().fetching.insert(pov_hash, Fetch {
+			response_senders: vec![pov_send],
+			requested_from: None,
+			untried: Vec::new(),
+		});
 
-		let pool = sp_core::testing::TaskExecutor::new();
-		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
 		let mut descriptor = CandidateDescriptor::default();
 		descriptor.pov_hash = pov_hash;
 
 		executor::block_on(async move {
-			handle_distribute(
-				&mut state,
-				&mut ctx,
-				hash_a,
-				descriptor,
-				Arc::new(pov.clone()),
-			).await.unwrap();
+			handle_distribute(&mut state, hash_a, descriptor, Arc::new(pov.clone()));
 
-			assert!(!state.peer_state[&peer_a].awaited[&hash_a].contains(&pov_hash));
-			assert!(state.peer_state[&peer_c].awaited[&hash_b].contains(&pov_hash));
+			assert!(state.relay_parent_state[&hash_a].fetching.is_empty());
 
 			// our local sender also completed
 			assert_eq!(&*pov_recv.await.unwrap(), &pov);
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::SendValidationMessage(peers, message)
-				) => {
-					assert_eq!(peers, vec![peer_a.clone()]);
-					assert_eq!(
-						message,
-						send_pov_message(hash_a, pov_hash, pov.clone()),
-					);
-				}
-			)
+			let (response_send, response_recv) = oneshot::channel();
+			handle_pov_request(&state, IncomingRequest::new(
+				peer_a,
+				req_res_v1::PoVFetchingRequest { relay_parent: hash_a, pov_hash },
+				response_send,
+			));
+
+			assert_eq!(
+				response_recv.await.unwrap(),
+				req_res_v1::PoVFetchingResponse::PoV(pov).encode(),
+			);
 		});
 	}
 
 	#[test]
-	fn we_inform_peers_with_same_view_we_are_awaiting() {
+	fn requests_for_unknown_povs_are_answered() {
 		let hash_a: Hash = [0; 32].into();
 		let hash_b: Hash = [1; 32].into();
 
 		let peer_a = PeerId::random();
-		let peer_b = PeerId::random();
 
-		let (pov_send, _) = oneshot::channel();
 		let pov = make_pov(vec![1, 2, 3]);
 		let pov_hash = pov.hash();
 
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
-
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				// peer A has hash_a in its view.
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				// peer B doesn't have hash_a in its view.
-				s.insert(
-					peer_b.clone(),
-					make_peer_state(vec![(hash_b, vec![])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
-
-		let pool = sp_core::testing::TaskExecutor::new();
-		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
-		let mut descriptor = CandidateDescriptor::default();
-		descriptor.pov_hash = pov_hash;
+		let mut state = make_state(vec![hash_a], vec![]);
+		state.relay_parent_state.get_mut(&hash_a).unwrap().known.insert(pov_hash, Arc::new(pov));
 
 		executor::block_on(async move {
-			handle_fetch(
-				&mut state,
-				&mut ctx,
-				hash_a,
-				descriptor,
-				pov_send,
-			).await.unwrap();
-
-			assert_eq!(state.relay_parent_state[&hash_a].fetching[&pov_hash].len(), 1);
-
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::SendValidationMessage(peers, message)
-				) => {
-					assert_eq!(peers, vec![peer_a.clone()]);
-					assert_eq!(
-						message,
-						awaiting_message(hash_a, vec![pov_hash]),
-					);
-				}
-			)
+			// the PoV is known, but not at the requested relay-parent.
+			let (response_send, response_recv) = oneshot::channel();
+			handle_pov_request(&state, IncomingRequest::new(
+				peer_a,
+				req_res_v1::PoVFetchingRequest { relay_parent: hash_b, pov_hash },
+				response_send,
+			));
+
+			assert_eq!(
+				response_recv.await.unwrap(),
+				req_res_v1::PoVFetchingResponse::NoSuchPoV.encode(),
+			);
 		});
 	}
 
 	#[test]
-	fn peer_view_change_leads_to_us_informing() {
+	fn we_request_from_peers_with_same_view() {
 		let hash_a: Hash = [0; 32].into();
 		let hash_b: Hash = [1; 32].into();
 
 		let peer_a = PeerId::random();
+		let peer_b = PeerId::random();
 
-		let (pov_a_send, _) = oneshot::channel();
-
-		let pov_a = make_pov(vec![1, 2, 3]);
-		let pov_a_hash = pov_a.hash();
-
-		let pov_b = make_pov(vec![4, 5, 6]);
-		let pov_b_hash = pov_b.hash();
-
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let mut b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
-
-				// pov_a is still being fetched, whereas the fetch of pov_b has already
-				// completed, as implied by the empty vector.
-				b.fetching.insert(pov_a_hash, vec![pov_a_send]);
-				b.fetching.insert(pov_b_hash, vec![]);
-
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				// peer A doesn't yet have hash_a in its view.
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_b, vec![])]),
-				);
+		let (pov_send, _) = oneshot::channel();
+		let pov = make_pov(vec![1, 2, 3]);
+		let pov_hash = pov.hash();
 
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
+		// peer A has hash_a in its view, peer B doesn't.
+		let mut state = make_state(
+			vec![hash_a],
+			vec![(peer_a.clone(), vec![hash_a]), (peer_b.clone(), vec![hash_b])],
+		);
 
 		let pool = sp_core::testing::TaskExecutor::new();
 		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov_hash;
 
 		executor::block_on(async move {
-			handle_network_update(
+			handle_fetch(
 				&mut state,
 				&mut ctx,
-				NetworkBridgeEvent::PeerViewChange(peer_a.clone(), View(vec![hash_a, hash_b])),
+				hash_a,
+				descriptor,
+				pov_send,
 			).await.unwrap();
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::SendValidationMessage(peers, message)
-				) => {
-					assert_eq!(peers, vec![peer_a.clone()]);
-					assert_eq!(
-						message,
-						awaiting_message(hash_a, vec![pov_a_hash]),
-					);
-				}
-			)
+			let fetch = &state.relay_parent_state[&hash_a].fetching[&pov_hash];
+			assert_eq!(fetch.response_senders.len(), 1);
+			assert_eq!(fetch.requested_from, Some(peer_a.clone()));
+			assert!(fetch.untried.is_empty());
+
+			let request = assert_pov_request(&mut handle, hash_a, pov_hash).await;
+			assert_eq!(request.peer, peer_a);
 		});
 	}
 
@@ -809,71 +615,32 @@ mod tests {
 		let hash_a: Hash = [0; 32].into();
 
 		let peer_a = PeerId::random();
-		let peer_b = PeerId::random();
 
 		let (pov_send, pov_recv) = oneshot::channel();
-
 		let pov = make_pov(vec![1, 2, 3]);
 		let pov_hash = pov.hash();
 
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let mut b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
-
-				// pov is being fetched.
-				b.fetching.insert(pov_hash, vec![pov_send]);
-
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				// peers A and B are functionally the same.
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				s.insert(
-					peer_b.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
+		let mut state = make_state(vec![hash_a], vec![(peer_a.clone(), vec![hash_a])]);
 
 		let pool = sp_core::testing::TaskExecutor::new();
 		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov_hash;
 
 		executor::block_on(async move {
-			// Peer A answers our request before peer B.
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					send_pov_message(hash_a, pov_hash, pov.clone()),
-				).focus().unwrap(),
-			).await.unwrap();
+			handle_fetch(&mut state, &mut ctx, hash_a, descriptor, pov_send).await.unwrap();
 
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_b.clone(),
-					send_pov_message(hash_a, pov_hash, pov.clone()),
-				).focus().unwrap(),
-			).await.unwrap();
+			let request = assert_pov_request(&mut handle, hash_a, pov_hash).await;
+			request.pending_response
+				.send(Ok(req_res_v1::PoVFetchingResponse::PoV(pov.clone()).encode()))
+				.unwrap();
+
+			let response = state.requests_in_progress.next().await.unwrap();
+			handle_pov_response(&mut state, &mut ctx, response).await.unwrap();
 
 			assert_eq!(&*pov_recv.await.unwrap(), &pov);
+			assert!(state.relay_parent_state[&hash_a].fetching.is_empty());
+			assert!(state.relay_parent_state[&hash_a].known.contains_key(&pov_hash));
 
 			assert_matches!(
 				handle.recv().await,
@@ -884,575 +651,173 @@ mod tests {
 					assert_eq!(rep, BENEFIT_FRESH_POV);
 				}
 			);
-
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::ReportPeer(peer, rep)
-				) => {
-					assert_eq!(peer, peer_b);
-					assert_eq!(rep, BENEFIT_LATE_POV);
-				}
-			);
 		});
 	}
 
 	#[test]
-	fn peer_punished_for_sending_bad_pov() {
+	fn peer_punished_for_sending_bad_pov_and_next_peer_is_tried() {
 		let hash_a: Hash = [0; 32].into();
 
 		let peer_a = PeerId::random();
+		let peer_b = PeerId::random();
 
 		let (pov_send, _) = oneshot::channel();
-
 		let pov = make_pov(vec![1, 2, 3]);
 		let pov_hash = pov.hash();
 
 		let bad_pov = make_pov(vec![6, 6, 6]);
 
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let mut b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
-
-				// pov is being fetched.
-				b.fetching.insert(pov_hash, vec![pov_send]);
-
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
+		let mut state = make_state(
+			vec![hash_a],
+			vec![(peer_a.clone(), vec![hash_a]), (peer_b.clone(), vec![hash_a])],
+		);
 
 		let pool = sp_core::testing::TaskExecutor::new();
 		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov_hash;
 
 		executor::block_on(async move {
-			// Peer A answers our request: right relay parent, awaited hash, wrong PoV.
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					send_pov_message(hash_a, pov_hash, bad_pov.clone()),
-				).focus().unwrap(),
-			).await.unwrap();
+			handle_fetch(&mut state, &mut ctx, hash_a, descriptor, pov_send).await.unwrap();
+
+			let request = assert_pov_request(&mut handle, hash_a, pov_hash).await;
+			let first_peer = request.peer.clone();
+			request.pending_response
+				.send(Ok(req_res_v1::PoVFetchingResponse::PoV(bad_pov).encode()))
+				.unwrap();
 
-			// didn't complete our sender.
-			assert_eq!(state.relay_parent_state[&hash_a].fetching[&pov_hash].len(), 1);
+			let response = state.requests_in_progress.next().await.unwrap();
+			handle_pov_response(&mut state, &mut ctx, response).await.unwrap();
 
 			assert_matches!(
 				handle.recv().await,
 				AllMessages::NetworkBridge(
 					NetworkBridgeMessage::ReportPeer(peer, rep)
 				) => {
-					assert_eq!(peer, peer_a);
-					assert_eq!(rep, COST_UNEXPECTED_POV);
+					assert_eq!(peer, first_peer);
+					assert_eq!(rep, COST_INVALID_POV);
 				}
 			);
-		});
-	}
-
-	#[test]
-	fn peer_punished_for_sending_unexpected_pov() {
-		let hash_a: Hash = [0; 32].into();
-
-		let peer_a = PeerId::random();
-
-		let pov = make_pov(vec![1, 2, 3]);
-		let pov_hash = pov.hash();
-
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
 
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
+			// The PoV is requested from the other peer.
+			let request = assert_pov_request(&mut handle, hash_a, pov_hash).await;
+			assert_ne!(request.peer, first_peer);
+			assert!(request.peer == peer_a || request.peer == peer_b);
 
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
-
-		let pool = sp_core::testing::TaskExecutor::new();
-		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
-
-		executor::block_on(async move {
-			// Peer A answers our request: right relay parent, awaited hash, wrong PoV.
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					send_pov_message(hash_a, pov_hash, pov.clone()),
-				).focus().unwrap(),
-			).await.unwrap();
-
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::ReportPeer(peer, rep)
-				) => {
-					assert_eq!(peer, peer_a);
-					assert_eq!(rep, COST_UNEXPECTED_POV);
-				}
-			);
+			assert!(state.relay_parent_state[&hash_a].fetching.contains_key(&pov_hash));
 		});
 	}
 
 	#[test]
-	fn peer_punished_for_sending_pov_out_of_our_view() {
+	fn no_such_pov_leads_to_next_peer_without_punishment() {
 		let hash_a: Hash = [0; 32].into();
-		let hash_b: Hash = [1; 32].into();
 
 		let peer_a = PeerId::random();
+		let peer_b = PeerId::random();
 
+		let (pov_send, _) = oneshot::channel();
 		let pov = make_pov(vec![1, 2, 3]);
 		let pov_hash = pov.hash();
 
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
-
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
-
-		let pool = sp_core::testing::TaskExecutor::new();
-		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
-
-		executor::block_on(async move {
-			// Peer A answers our request: right relay parent, awaited hash, wrong PoV.
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					send_pov_message(hash_b, pov_hash, pov.clone()),
-				).focus().unwrap(),
-			).await.unwrap();
-
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::ReportPeer(peer, rep)
-				) => {
-					assert_eq!(peer, peer_a);
-					assert_eq!(rep, COST_UNEXPECTED_POV);
-				}
-			);
-		});
-	}
-
-	#[test]
-	fn peer_reported_for_awaiting_too_much() {
-		let hash_a: Hash = [0; 32].into();
-
-		let peer_a = PeerId::random();
-		let n_validators = 10;
-
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators,
-				};
-
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
+		let mut state = make_state(
+			vec![hash_a],
+			vec![(peer_a.clone(), vec![hash_a]), (peer_b.clone(), vec![hash_a])],
+		);
 
 		let pool = sp_core::testing::TaskExecutor::new();
 		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov_hash;
 
 		executor::block_on(async move {
-			let max_plausibly_awaited = n_validators * 2;
-
-			// The peer awaits a plausible (albeit unlikely) amount of PoVs.
-			for i in 0..max_plausibly_awaited {
-				let pov_hash = make_pov(vec![i as u8; 32]).hash();
-				handle_network_update(
-					&mut state,
-					&mut ctx,
-					NetworkBridgeEvent::PeerMessage(
-						peer_a.clone(),
-						awaiting_message(hash_a, vec![pov_hash]),
-					).focus().unwrap(),
-				).await.unwrap();
-			}
+			handle_fetch(&mut state, &mut ctx, hash_a, descriptor, pov_send).await.unwrap();
 
-			assert_eq!(state.peer_state[&peer_a].awaited[&hash_a].len(), max_plausibly_awaited);
+			let request = assert_pov_request(&mut handle, hash_a, pov_hash).await;
+			let first_peer = request.peer.clone();
+			request.pending_response
+				.send(Ok(req_res_v1::PoVFetchingResponse::NoSuchPoV.encode()))
+				.unwrap();
 
-			// The last straw:
-			let last_pov_hash = make_pov(vec![max_plausibly_awaited as u8; 32]).hash();
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					awaiting_message(hash_a, vec![last_pov_hash]),
-				).focus().unwrap(),
-			).await.unwrap();
+			let response = state.requests_in_progress.next().await.unwrap();
+			handle_pov_response(&mut state, &mut ctx, response).await.unwrap();
 
-			// No more bookkeeping for you!
-			assert_eq!(state.peer_state[&peer_a].awaited[&hash_a].len(), max_plausibly_awaited);
-
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::ReportPeer(peer, rep)
-				) => {
-					assert_eq!(peer, peer_a);
-					assert_eq!(rep, COST_APPARENT_FLOOD);
-				}
-			);
+			// No report, just a request to the other peer.
+			let request = assert_pov_request(&mut handle, hash_a, pov_hash).await;
+			assert_ne!(request.peer, first_peer);
 		});
 	}
 
 	#[test]
-	fn peer_reported_for_awaiting_outside_their_view() {
+	fn peer_view_change_leads_to_us_requesting() {
 		let hash_a: Hash = [0; 32].into();
 		let hash_b: Hash = [1; 32].into();
 
 		let peer_a = PeerId::random();
 
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				s.insert(hash_a, BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				});
-
-				s.insert(hash_b, BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				});
-
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				// Peer has only hash A in its view.
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
+		let (pov_send, _) = oneshot::channel();
+		let pov = make_pov(vec![1, 2, 3]);
+		let pov_hash = pov.hash();
 
-				s
-			},
-			our_view: View(vec![hash_a, hash_b]),
-		};
+		// Nobody has hash_a in view yet.
+		let mut state = make_state(vec![hash_a], vec![(peer_a.clone(), vec![hash_b])]);
 
 		let pool = sp_core::testing::TaskExecutor::new();
 		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov_hash;
 
 		executor::block_on(async move {
-			let pov_hash = make_pov(vec![1, 2, 3]).hash();
+			handle_fetch(&mut state, &mut ctx, hash_a, descriptor, pov_send).await.unwrap();
 
-			// Hash B is in our view but not the peer's
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					awaiting_message(hash_b, vec![pov_hash]),
-				).focus().unwrap(),
-			).await.unwrap();
-
-			assert!(state.peer_state[&peer_a].awaited.get(&hash_b).is_none());
+			assert!(state.requests_in_progress.is_empty());
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::ReportPeer(peer, rep)
-				) => {
-					assert_eq!(peer, peer_a);
-					assert_eq!(rep, COST_AWAITED_NOT_IN_VIEW);
-				}
-			);
-		});
-	}
-
-	#[test]
-	fn peer_reported_for_awaiting_outside_our_view() {
-		let hash_a: Hash = [0; 32].into();
-		let hash_b: Hash = [1; 32].into();
-
-		let peer_a = PeerId::random();
-
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				s.insert(hash_a, BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				});
-
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				// Peer has hashes A and B in their view.
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![]), (hash_b, vec![])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
-
-		let pool = sp_core::testing::TaskExecutor::new();
-		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
-
-		executor::block_on(async move {
-			let pov_hash = make_pov(vec![1, 2, 3]).hash();
-
-			// Hash B is in peer's view but not ours.
 			handle_network_update(
 				&mut state,
 				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					awaiting_message(hash_b, vec![pov_hash]),
-				).focus().unwrap(),
+				NetworkBridgeEvent::PeerViewChange(peer_a.clone(), View(vec![hash_a, hash_b])),
 			).await.unwrap();
 
-			// Illegal `awaited` is ignored.
-			assert!(state.peer_state[&peer_a].awaited[&hash_b].is_empty());
-
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::ReportPeer(peer, rep)
-				) => {
-					assert_eq!(peer, peer_a);
-					assert_eq!(rep, COST_AWAITED_NOT_IN_VIEW);
-				}
-			);
+			let request = assert_pov_request(&mut handle, hash_a, pov_hash).await;
+			assert_eq!(request.peer, peer_a);
 		});
 	}
 
 	#[test]
-	fn peer_complete_fetch_leads_to_us_completing_others() {
+	fn responses_to_completed_fetches_are_ignored() {
 		let hash_a: Hash = [0; 32].into();
 
 		let peer_a = PeerId::random();
-		let peer_b = PeerId::random();
 
 		let (pov_send, pov_recv) = oneshot::channel();
-
 		let pov = make_pov(vec![1, 2, 3]);
 		let pov_hash = pov.hash();
 
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let mut b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
-
-				// pov is being fetched.
-				b.fetching.insert(pov_hash, vec![pov_send]);
-
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![])]),
-				);
-
-				// peer B is awaiting peer A's request.
-				s.insert(
-					peer_b.clone(),
-					make_peer_state(vec![(hash_a, vec![pov_hash])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
+		let mut state = make_state(vec![hash_a], vec![(peer_a.clone(), vec![hash_a])]);
 
 		let pool = sp_core::testing::TaskExecutor::new();
 		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
+		let mut descriptor = CandidateDescriptor::default();
+		descriptor.pov_hash = pov_hash;
 
 		executor::block_on(async move {
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					send_pov_message(hash_a, pov_hash, pov.clone()),
-				).focus().unwrap(),
-			).await.unwrap();
+			handle_fetch(&mut state, &mut ctx, hash_a, descriptor.clone(), pov_send).await.unwrap();
 
-			assert_eq!(&*pov_recv.await.unwrap(), &pov);
-
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::ReportPeer(peer, rep)
-				) => {
-					assert_eq!(peer, peer_a);
-					assert_eq!(rep, BENEFIT_FRESH_POV);
-				}
-			);
-
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::SendValidationMessage(peers, message)
-				) => {
-					assert_eq!(peers, vec![peer_b.clone()]);
-					assert_eq!(
-						message,
-						send_pov_message(hash_a, pov_hash, pov.clone()),
-					);
-				}
-			);
-
-			assert!(!state.peer_state[&peer_b].awaited[&hash_a].contains(&pov_hash));
-		});
-	}
-
-	#[test]
-	fn peer_completing_request_no_longer_awaiting() {
-		let hash_a: Hash = [0; 32].into();
-
-		let peer_a = PeerId::random();
-
-		let (pov_send, pov_recv) = oneshot::channel();
-
-		let pov = make_pov(vec![1, 2, 3]);
-		let pov_hash = pov.hash();
-
-		let mut state = State {
-			relay_parent_state: {
-				let mut s = HashMap::new();
-				let mut b = BlockBasedState {
-					known: HashMap::new(),
-					fetching: HashMap::new(),
-					n_validators: 10,
-				};
-
-				// pov is being fetched.
-				b.fetching.insert(pov_hash, vec![pov_send]);
-
-				s.insert(hash_a, b);
-				s
-			},
-			peer_state: {
-				let mut s = HashMap::new();
-
-				// peer A is registered as awaiting.
-				s.insert(
-					peer_a.clone(),
-					make_peer_state(vec![(hash_a, vec![pov_hash])]),
-				);
-
-				s
-			},
-			our_view: View(vec![hash_a]),
-		};
-
-		let pool = sp_core::testing::TaskExecutor::new();
-		let (mut ctx, mut handle) = polkadot_node_subsystem_test_helpers::make_subsystem_context(pool);
-
-		executor::block_on(async move {
-			handle_network_update(
-				&mut state,
-				&mut ctx,
-				NetworkBridgeEvent::PeerMessage(
-					peer_a.clone(),
-					send_pov_message(hash_a, pov_hash, pov.clone()),
-				).focus().unwrap(),
-			).await.unwrap();
+			let request = assert_pov_request(&mut handle, hash_a, pov_hash).await;
 
+			// We get the PoV from elsewhere in the meantime.
+			handle_distribute(&mut state, hash_a, descriptor, Arc::new(pov.clone()));
 			assert_eq!(&*pov_recv.await.unwrap(), &pov);
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::NetworkBridge(
-					NetworkBridgeMessage::ReportPeer(peer, rep)
-				) => {
-					assert_eq!(peer, peer_a);
-					assert_eq!(rep, BENEFIT_FRESH_POV);
-				}
-			);
+			request.pending_response
+				.send(Ok(req_res_v1::PoVFetchingResponse::NoSuchPoV.encode()))
+				.unwrap();
+
+			let response = state.requests_in_progress.next().await.unwrap();
+			handle_pov_response(&mut state, &mut ctx, response).await.unwrap();
 
-			// We received the PoV from peer A, so we do not consider it awaited by peer A anymore.
-			assert!(!state.peer_state[&peer_a].awaited[&hash_a].contains(&pov_hash));
+			// Neither a report nor a new request.
+			drop(ctx);
+			assert!(handle.try_recv().await.is_none());
 		});
 	}
 }
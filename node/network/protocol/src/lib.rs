@@ -22,6 +22,8 @@ use std::convert::TryFrom;
 
 pub use sc_network::{ReputationChange, PeerId};
 
+pub mod request_response;
+
 /// A unique identifier of a request.
 pub type RequestId = u64;
 
@@ -172,10 +174,12 @@ impl View {
 /// v1 protocol types.
 pub mod v1 {
 	use polkadot_primitives::v1::{
-		Hash, CollatorId, Id as ParaId, ErasureChunk, CandidateReceipt,
-		SignedAvailabilityBitfield, PoV,
+		Hash, CollatorId, Id as ParaId, ErasureChunk, SignedAvailabilityBitfield, ValidatorIndex,
 	};
 	use polkadot_node_primitives::SignedFullStatement;
+	use polkadot_node_primitives::approval::{
+		CandidateIndex, IndirectAssignmentCert, IndirectSignedApprovalVote,
+	};
 	use parity_scale_codec::{Encode, Decode};
 	use std::convert::TryFrom;
 	use super::RequestId;
@@ -188,6 +192,18 @@ pub mod v1 {
 		Chunk(Hash, ErasureChunk),
 	}
 
+	/// Network messages used by the availability recovery subsystem.
+	#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+	pub enum AvailabilityRecoveryMessage {
+		/// Request the erasure chunk of a candidate (by hash) held by the validator with the
+		/// given index.
+		#[codec(index = "0")]
+		RequestChunk(RequestId, Hash, ValidatorIndex),
+		/// The response to a chunk request. `None` if the chunk isn't held by the peer.
+		#[codec(index = "1")]
+		Chunk(RequestId, Option<ErasureChunk>),
+	}
+
 	/// Network messages used by the bitfield distribution subsystem.
 	#[derive(Debug, Clone, Encode, Decode, PartialEq)]
 	pub enum BitfieldDistributionMessage {
@@ -197,17 +213,11 @@ pub mod v1 {
 	}
 
 	/// Network messages used by the PoV distribution subsystem.
+	///
+	/// PoVs are fetched with requests on the `PoVFetching` protocol, so there are no
+	/// notifications at the moment. The subsystem still follows the views of its peers.
 	#[derive(Debug, Clone, Encode, Decode, PartialEq)]
-	pub enum PoVDistributionMessage {
-		/// Notification that we are awaiting the given PoVs (by hash) against a
-		/// specific relay-parent hash.
-		#[codec(index = "0")]
-		Awaiting(Hash, Vec<Hash>),
-		/// Notification of an awaited PoV, in a given relay-parent context.
-		/// (relay_parent, pov_hash, pov)
-		#[codec(index = "1")]
-		SendPoV(Hash, Hash, PoV),
-	}
+	pub enum PoVDistributionMessage { }
 
 	/// Network messages used by the statement distribution subsystem.
 	#[derive(Debug, Clone, Encode, Decode, PartialEq)]
@@ -217,22 +227,27 @@ pub mod v1 {
 		Statement(Hash, SignedFullStatement)
 	}
 
+	/// Network messages used by the approval distribution subsystem.
+	#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+	pub enum ApprovalDistributionMessage {
+		/// Assignments for candidates in recent, unfinalized blocks.
+		#[codec(index = "0")]
+		Assignments(Vec<(IndirectAssignmentCert, CandidateIndex)>),
+		/// Approvals for candidates in some recent, unfinalized block.
+		#[codec(index = "1")]
+		Approvals(Vec<IndirectSignedApprovalVote>),
+	}
+
 	/// Network messages used by the collator protocol subsystem
 	#[derive(Debug, Clone, Encode, Decode, PartialEq)]
 	pub enum CollatorProtocolMessage {
 		/// Declare the intent to advertise collations under a collator ID.
 		#[codec(index = "0")]
 		Declare(CollatorId),
-		/// Advertise a collation to a validator. Can only be sent once the peer has declared
-		/// that they are a collator with given ID.
+		/// Advertise a collation to a validator, along with the encoded size of its PoV in bytes.
+		/// Can only be sent once the peer has declared that they are a collator with given ID.
 		#[codec(index = "1")]
-		AdvertiseCollation(Hash, ParaId),
-		/// Request the advertised collation at that relay-parent.
-		#[codec(index = "2")]
-		RequestCollation(RequestId, Hash, ParaId),
-		/// A requested collation.
-		#[codec(index = "3")]
-		Collation(RequestId, CandidateReceipt, PoV),
+		AdvertiseCollation(Hash, ParaId, u32),
 	}
 
 	/// All network messages on the validation peer-set.
@@ -250,12 +265,20 @@ pub mod v1 {
 		/// Statement distribution messages
 		#[codec(index = "3")]
 		StatementDistribution(StatementDistributionMessage),
+		/// Approval distribution messages
+		#[codec(index = "4")]
+		ApprovalDistribution(ApprovalDistributionMessage),
+		/// Availability recovery messages
+		#[codec(index = "5")]
+		AvailabilityRecovery(AvailabilityRecoveryMessage),
 	}
 
 	impl_try_from!(ValidationProtocol, AvailabilityDistribution, AvailabilityDistributionMessage);
 	impl_try_from!(ValidationProtocol, BitfieldDistribution, BitfieldDistributionMessage);
 	impl_try_from!(ValidationProtocol, PoVDistribution, PoVDistributionMessage);
 	impl_try_from!(ValidationProtocol, StatementDistribution, StatementDistributionMessage);
+	impl_try_from!(ValidationProtocol, ApprovalDistribution, ApprovalDistributionMessage);
+	impl_try_from!(ValidationProtocol, AvailabilityRecovery, AvailabilityRecoveryMessage);
 
 	/// All network messages on the collation peer-set.
 	#[derive(Debug, Clone, Encode, Decode, PartialEq)]
@@ -0,0 +1,118 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Request/response protocols used by Polkadot.
+//!
+//! Unlike the notification protocols of the peer-sets, every request is sent on a dedicated
+//! substream and answered by exactly one response. The network enforces size limits on both and
+//! a timeout on the response, so subsystems don't have to track their requests themselves.
+//!
+//! Adding a protocol requires:
+//! - A variant in `Protocol`, along with its configuration in `Protocol::get_config`.
+//! - Request and response types in `v1`, with an `IsRequest` impl for the request.
+//! - A variant in `Requests`, so that requests can be sent via the network bridge.
+//! - Routing of the incoming requests to the responsible subsystem in the network bridge.
+
+use std::borrow::Cow;
+use std::time::Duration;
+
+use futures::channel::mpsc;
+
+pub use sc_network::config as network;
+pub use sc_network::config::RequestResponseConfig;
+
+/// Everything related to sending and receiving requests.
+pub mod request;
+pub use request::{
+	IncomingRequest, IsRequest, OutgoingRequest, OutgoingResult, RequestError, Requests,
+};
+
+/// The v1 requests and responses, as they are sent over the wire.
+pub mod v1;
+
+/// The request/response protocols.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+	/// Protocol for fetching collations from collators.
+	CollationFetching,
+	/// Protocol for fetching PoVs from validators.
+	PoVFetching,
+}
+
+/// The maximum size of a PoV sent in a response.
+const MAX_POV_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Room for everything in a response which isn't the PoV, e.g. the candidate receipt.
+const RESPONSE_OVERHEAD: u64 = 64 * 1024;
+
+/// Requests only carry a few hashes and identifiers.
+const MAX_REQUEST_SIZE: u64 = 1_000;
+
+/// Collators are not required to be well-connected, so the timeout is conservative.
+const COLLATION_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// PoVs are requested from validators, which are expected to have plenty of bandwidth.
+const POV_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+impl Protocol {
+	/// All the request/response protocols.
+	pub const ALL: [Protocol; 2] = [Protocol::CollationFetching, Protocol::PoVFetching];
+
+	/// Get the configuration of the protocol, to be registered with the network.
+	///
+	/// Incoming requests on the protocol are sent to the returned receiver.
+	pub fn get_config(self) -> (mpsc::Receiver<network::IncomingRequest>, RequestResponseConfig) {
+		let (tx, rx) = mpsc::channel(self.get_channel_size());
+
+		let cfg = match self {
+			Protocol::CollationFetching => RequestResponseConfig {
+				name: Cow::Borrowed(self.into_protocol_name()),
+				max_request_size: MAX_REQUEST_SIZE,
+				max_response_size: MAX_POV_SIZE + RESPONSE_OVERHEAD,
+				request_timeout: COLLATION_REQUEST_TIMEOUT,
+				inbound_queue: Some(tx),
+			},
+			Protocol::PoVFetching => RequestResponseConfig {
+				name: Cow::Borrowed(self.into_protocol_name()),
+				max_request_size: MAX_REQUEST_SIZE,
+				max_response_size: MAX_POV_SIZE + RESPONSE_OVERHEAD,
+				request_timeout: POV_REQUEST_TIMEOUT,
+				inbound_queue: Some(tx),
+			},
+		};
+
+		(rx, cfg)
+	}
+
+	/// The size of the queue of incoming requests. Requests are dropped by the network when it
+	/// is full.
+	fn get_channel_size(self) -> usize {
+		match self {
+			// A collator serves the validators assigned to its para, a few dozen at most.
+			Protocol::CollationFetching => 64,
+			// Every validator may ask us for the PoVs of the candidates we backed.
+			Protocol::PoVFetching => 1024,
+		}
+	}
+
+	/// The name of the protocol on the wire.
+	pub const fn into_protocol_name(self) -> &'static str {
+		match self {
+			Protocol::CollationFetching => "/polkadot/req_collation/1",
+			Protocol::PoVFetching => "/polkadot/req_pov/1",
+		}
+	}
+}
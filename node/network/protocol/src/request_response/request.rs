@@ -0,0 +1,182 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+use futures::channel::oneshot;
+use futures::prelude::Future;
+use parity_scale_codec::{Decode, Encode, Error as DecodingError};
+use sc_network::{PeerId, RequestFailure};
+
+use super::{v1, Protocol};
+
+/// Common properties of all requests.
+pub trait IsRequest {
+	/// The response to the request.
+	type Response;
+
+	/// The protocol the request is sent on.
+	const PROTOCOL: Protocol;
+}
+
+/// All requests that can be sent via the network bridge.
+#[derive(Debug)]
+pub enum Requests {
+	/// Request a collation from a collator.
+	CollationFetching(OutgoingRequest<v1::CollationFetchingRequest>),
+	/// Request a PoV from a validator.
+	PoVFetching(OutgoingRequest<v1::PoVFetchingRequest>),
+}
+
+impl Requests {
+	/// Get the protocol the request is sent on.
+	pub fn get_protocol(&self) -> Protocol {
+		match self {
+			Self::CollationFetching(_) => Protocol::CollationFetching,
+			Self::PoVFetching(_) => Protocol::PoVFetching,
+		}
+	}
+
+	/// Encode the request, for it to be sent on the returned protocol.
+	pub fn encode_request(self) -> (Protocol, OutgoingRequest<Vec<u8>>) {
+		match self {
+			Self::CollationFetching(r) => r.encode_request(),
+			Self::PoVFetching(r) => r.encode_request(),
+		}
+	}
+}
+
+/// A request to be sent to a peer, along with the channel its response is sent on.
+#[derive(Debug)]
+pub struct OutgoingRequest<Req> {
+	/// The peer to send the request to.
+	pub peer: PeerId,
+	/// The request itself.
+	pub payload: Req,
+	/// The network sends the raw response, or the reason for not receiving one, on this channel.
+	pub pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+}
+
+/// The reasons for an outgoing request not to result in a response.
+#[derive(Debug)]
+pub enum RequestError {
+	/// The response could not be decoded.
+	InvalidResponse(DecodingError),
+	/// The network failed to deliver the request or response, e.g. because the peer is not
+	/// connected, refused to answer or timed out.
+	NetworkError(RequestFailure),
+	/// The network dropped the request without reporting a failure.
+	Canceled(oneshot::Canceled),
+}
+
+impl From<DecodingError> for RequestError {
+	fn from(err: DecodingError) -> Self {
+		RequestError::InvalidResponse(err)
+	}
+}
+
+impl From<RequestFailure> for RequestError {
+	fn from(err: RequestFailure) -> Self {
+		RequestError::NetworkError(err)
+	}
+}
+
+impl From<oneshot::Canceled> for RequestError {
+	fn from(err: oneshot::Canceled) -> Self {
+		RequestError::Canceled(err)
+	}
+}
+
+/// The outcome of an `OutgoingRequest`.
+pub type OutgoingResult<Res> = Result<Res, RequestError>;
+
+impl<Req> OutgoingRequest<Req>
+where
+	Req: IsRequest + Encode,
+	Req::Response: Decode,
+{
+	/// Create a new request to the given peer.
+	///
+	/// Returns the request, to be sent via the network bridge, and a future resolving to the
+	/// decoded response.
+	pub fn new(
+		peer: PeerId,
+		payload: Req,
+	) -> (Self, impl Future<Output = OutgoingResult<Req::Response>>) {
+		let (tx, rx) = oneshot::channel();
+		let request = Self {
+			peer,
+			payload,
+			pending_response: tx,
+		};
+
+		(request, receive_response::<Req>(rx))
+	}
+
+	fn encode_request(self) -> (Protocol, OutgoingRequest<Vec<u8>>) {
+		let OutgoingRequest { peer, payload, pending_response } = self;
+		let encoded = OutgoingRequest {
+			peer,
+			payload: payload.encode(),
+			pending_response,
+		};
+
+		(Req::PROTOCOL, encoded)
+	}
+}
+
+async fn receive_response<Req>(
+	rx: oneshot::Receiver<Result<Vec<u8>, RequestFailure>>,
+) -> OutgoingResult<Req::Response>
+where
+	Req: IsRequest,
+	Req::Response: Decode,
+{
+	let raw = rx.await??;
+	Ok(Decode::decode(&mut raw.as_ref())?)
+}
+
+/// A request received from a peer, along with the channel to respond on.
+#[derive(Debug)]
+pub struct IncomingRequest<Req> {
+	/// The peer which sent the request.
+	pub peer: PeerId,
+	/// The request itself.
+	pub payload: Req,
+	pending_response: oneshot::Sender<Vec<u8>>,
+}
+
+impl<Req> IncomingRequest<Req>
+where
+	Req: IsRequest,
+	Req::Response: Encode,
+{
+	/// Create a new incoming request.
+	pub fn new(peer: PeerId, payload: Req, pending_response: oneshot::Sender<Vec<u8>>) -> Self {
+		Self {
+			peer,
+			payload,
+			pending_response,
+		}
+	}
+
+	/// Send the response to the request.
+	///
+	/// Returns the response if it could not be sent, i.e. if the network is no longer waiting
+	/// for it.
+	pub fn send_response(self, response: Req::Response) -> Result<(), Req::Response> {
+		let encoded = response.encode();
+		self.pending_response.send(encoded).map_err(|_| response)
+	}
+}
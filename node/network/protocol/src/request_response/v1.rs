@@ -0,0 +1,71 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Requests and responses as sent over the wire for the individual protocols.
+
+use parity_scale_codec::{Decode, Encode};
+
+use polkadot_primitives::v1::{CandidateReceipt, Hash, Id as ParaId, PoV};
+
+use super::request::IsRequest;
+use super::Protocol;
+
+/// Request the advertised collation at a relay-parent.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct CollationFetchingRequest {
+	/// The relay-parent of the collation.
+	pub relay_parent: Hash,
+	/// The para of the collation.
+	pub para_id: ParaId,
+}
+
+/// The response of a collator to a `CollationFetchingRequest`.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub enum CollationFetchingResponse {
+	/// The requested collation.
+	#[codec(index = "0")]
+	Collation(CandidateReceipt, PoV),
+}
+
+impl IsRequest for CollationFetchingRequest {
+	type Response = CollationFetchingResponse;
+	const PROTOCOL: Protocol = Protocol::CollationFetching;
+}
+
+/// Request a PoV (by hash) in the context of a relay-parent.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct PoVFetchingRequest {
+	/// The relay-parent the PoV is needed at.
+	pub relay_parent: Hash,
+	/// The hash of the PoV.
+	pub pov_hash: Hash,
+}
+
+/// The response of a validator to a `PoVFetchingRequest`.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub enum PoVFetchingResponse {
+	/// The requested PoV.
+	#[codec(index = "0")]
+	PoV(PoV),
+	/// The validator doesn't have the requested PoV.
+	#[codec(index = "1")]
+	NoSuchPoV,
+}
+
+impl IsRequest for PoVFetchingRequest {
+	type Response = PoVFetchingResponse;
+	const PROTOCOL: Protocol = Protocol::PoVFetching;
+}
@@ -161,6 +161,10 @@ fn main() {
 			chain_api: DummySubsystem,
 			collation_generation: DummySubsystem,
 			collator_protocol: DummySubsystem,
+			approval_voting: DummySubsystem,
+			approval_distribution: DummySubsystem,
+			availability_recovery: DummySubsystem,
+			peer_set_manager: DummySubsystem,
 		};
 		let (overseer, _handler) = Overseer::new(
 			vec![],
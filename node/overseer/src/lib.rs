@@ -80,6 +80,8 @@ use polkadot_subsystem::messages::{
 	AvailabilityDistributionMessage, BitfieldSigningMessage, BitfieldDistributionMessage,
 	ProvisionerMessage, PoVDistributionMessage, RuntimeApiMessage,
 	AvailabilityStoreMessage, NetworkBridgeMessage, AllMessages, CollationGenerationMessage, CollatorProtocolMessage,
+	ApprovalVotingMessage, ApprovalDistributionMessage, AvailabilityRecoveryMessage,
+	PeerSetManagerMessage,
 };
 pub use polkadot_subsystem::{
 	Subsystem, SubsystemContext, OverseerSignal, FromOverseer, SubsystemError, SubsystemResult,
@@ -394,6 +396,18 @@ pub struct Overseer<S: SpawnNamed> {
 	/// A Collator Protocol subsystem.
 	collator_protocol_subsystem: OverseenSubsystem<CollatorProtocolMessage>,
 
+	/// An Approval Voting subsystem.
+	approval_voting_subsystem: OverseenSubsystem<ApprovalVotingMessage>,
+
+	/// An Approval Distribution subsystem.
+	approval_distribution_subsystem: OverseenSubsystem<ApprovalDistributionMessage>,
+
+	/// An Availability Recovery subsystem.
+	availability_recovery_subsystem: OverseenSubsystem<AvailabilityRecoveryMessage>,
+
+	/// A Peer Set Manager subsystem.
+	peer_set_manager_subsystem: OverseenSubsystem<PeerSetManagerMessage>,
+
 	/// Spawner to spawn tasks to.
 	s: S,
 
@@ -432,7 +446,7 @@ pub struct Overseer<S: SpawnNamed> {
 ///
 /// [`Subsystem`]: trait.Subsystem.html
 /// [`DummySubsystem`]: struct.DummySubsystem.html
-pub struct AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP> {
+pub struct AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP, AV, APD, AR, PSM> {
 	/// A candidate validation subsystem.
 	pub candidate_validation: CV,
 	/// A candidate backing subsystem.
@@ -463,6 +477,14 @@ pub struct AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG
 	pub collation_generation: CG,
 	/// A Collator Protocol subsystem.
 	pub collator_protocol: CP,
+	/// An Approval Voting subsystem.
+	pub approval_voting: AV,
+	/// An Approval Distribution subsystem.
+	pub approval_distribution: APD,
+	/// An Availability Recovery subsystem.
+	pub availability_recovery: AR,
+	/// A Peer Set Manager subsystem.
+	pub peer_set_manager: PSM,
 }
 
 /// Overseer Prometheus metrics.
@@ -595,6 +617,10 @@ where
 	///     chain_api: DummySubsystem,
 	///     collation_generation: DummySubsystem,
 	///     collator_protocol: DummySubsystem,
+	///     approval_voting: DummySubsystem,
+	///     approval_distribution: DummySubsystem,
+	///     availability_recovery: DummySubsystem,
+	///     peer_set_manager: DummySubsystem,
 	/// };
 	/// let (overseer, _handler) = Overseer::new(
 	///     vec![],
@@ -616,9 +642,9 @@ where
 	/// #
 	/// # }); }
 	/// ```
-	pub fn new<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>(
+	pub fn new<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP, AV, APD, AR, PSM>(
 		leaves: impl IntoIterator<Item = BlockInfo>,
-		all_subsystems: AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP>,
+		all_subsystems: AllSubsystems<CV, CB, CS, SD, AD, BS, BD, P, PoVD, RA, AS, NB, CA, CG, CP, AV, APD, AR, PSM>,
 		prometheus_registry: Option<&prometheus::Registry>,
 		mut s: S,
 	) -> SubsystemResult<(Self, OverseerHandler)>
@@ -638,6 +664,10 @@ where
 		CA: Subsystem<OverseerSubsystemContext<ChainApiMessage>> + Send,
 		CG: Subsystem<OverseerSubsystemContext<CollationGenerationMessage>> + Send,
 		CP: Subsystem<OverseerSubsystemContext<CollatorProtocolMessage>> + Send,
+		AV: Subsystem<OverseerSubsystemContext<ApprovalVotingMessage>> + Send,
+		APD: Subsystem<OverseerSubsystemContext<ApprovalDistributionMessage>> + Send,
+		AR: Subsystem<OverseerSubsystemContext<AvailabilityRecoveryMessage>> + Send,
+		PSM: Subsystem<OverseerSubsystemContext<PeerSetManagerMessage>> + Send,
 	{
 		let (events_tx, events_rx) = mpsc::channel(CHANNEL_CAPACITY);
 
@@ -754,6 +784,34 @@ where
 			all_subsystems.collator_protocol,
 		)?;
 
+		let approval_voting_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			all_subsystems.approval_voting,
+		)?;
+
+		let approval_distribution_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			all_subsystems.approval_distribution,
+		)?;
+
+		let availability_recovery_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			all_subsystems.availability_recovery,
+		)?;
+
+		let peer_set_manager_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			all_subsystems.peer_set_manager,
+		)?;
+
 		let leaves = leaves
 			.into_iter()
 			.map(|BlockInfo { hash, parent_hash: _, number }| (hash, number))
@@ -780,6 +838,10 @@ where
 			chain_api_subsystem,
 			collation_generation_subsystem,
 			collator_protocol_subsystem,
+			approval_voting_subsystem,
+			approval_distribution_subsystem,
+			availability_recovery_subsystem,
+			peer_set_manager_subsystem,
 			s,
 			running_subsystems,
 			running_subsystems_rx,
@@ -855,6 +917,22 @@ where
 			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
 		}
 
+		if let Some(ref mut s) = self.approval_voting_subsystem.instance {
+			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		}
+
+		if let Some(ref mut s) = self.approval_distribution_subsystem.instance {
+			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		}
+
+		if let Some(ref mut s) = self.availability_recovery_subsystem.instance {
+			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		}
+
+		if let Some(ref mut s) = self.peer_set_manager_subsystem.instance {
+			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		}
+
 		let mut stop_delay = Delay::new(Duration::from_secs(STOP_DELAY)).fuse();
 
 		loop {
@@ -1044,6 +1122,22 @@ where
 			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
 		}
 
+		if let Some(ref mut s) = self.approval_voting_subsystem.instance {
+			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+		}
+
+		if let Some(ref mut s) = self.approval_distribution_subsystem.instance {
+			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+		}
+
+		if let Some(ref mut s) = self.availability_recovery_subsystem.instance {
+			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+		}
+
+		if let Some(ref mut s) = self.peer_set_manager_subsystem.instance {
+			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+		}
+
 		Ok(())
 	}
 
@@ -1124,6 +1218,26 @@ where
 					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
 				}
 			}
+			AllMessages::ApprovalVoting(msg) => {
+				if let Some(ref mut s) = self.approval_voting_subsystem.instance {
+					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+				}
+			}
+			AllMessages::ApprovalDistribution(msg) => {
+				if let Some(ref mut s) = self.approval_distribution_subsystem.instance {
+					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+				}
+			}
+			AllMessages::AvailabilityRecovery(msg) => {
+				if let Some(ref mut s) = self.availability_recovery_subsystem.instance {
+					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+				}
+			}
+			AllMessages::PeerSetManager(msg) => {
+				if let Some(ref mut s) = self.peer_set_manager_subsystem.instance {
+					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
+				}
+			}
 		}
 	}
 
@@ -1217,6 +1331,7 @@ mod tests {
 	use polkadot_subsystem::DummySubsystem;
 	use polkadot_subsystem::messages::RuntimeApiRequest;
 	use polkadot_node_primitives::{Collation, CollationGenerationConfig};
+	use polkadot_node_primitives::approval::IndirectSignedApprovalVote;
 	use polkadot_node_network_protocol::{PeerId, ReputationChange, NetworkBridgeEvent};
 
 	use sp_core::crypto::Pair as _;
@@ -1345,6 +1460,10 @@ mod tests {
 				chain_api: DummySubsystem,
 				collation_generation: DummySubsystem,
 				collator_protocol: DummySubsystem,
+				approval_voting: DummySubsystem,
+				approval_distribution: DummySubsystem,
+				availability_recovery: DummySubsystem,
+				peer_set_manager: DummySubsystem,
 			};
 			let (overseer, mut handler) = Overseer::new(
 				vec![],
@@ -1419,6 +1538,10 @@ mod tests {
 				candidate_backing: DummySubsystem,
 				candidate_selection: DummySubsystem,
 				collator_protocol: DummySubsystem,
+				approval_voting: DummySubsystem,
+				approval_distribution: DummySubsystem,
+				availability_recovery: DummySubsystem,
+				peer_set_manager: DummySubsystem,
 				statement_distribution: DummySubsystem,
 				availability_distribution: DummySubsystem,
 				bitfield_signing: DummySubsystem,
@@ -1491,6 +1614,10 @@ mod tests {
 				chain_api: DummySubsystem,
 				collation_generation: DummySubsystem,
 				collator_protocol: DummySubsystem,
+				approval_voting: DummySubsystem,
+				approval_distribution: DummySubsystem,
+				availability_recovery: DummySubsystem,
+				peer_set_manager: DummySubsystem,
 			};
 			let (overseer, _handle) = Overseer::new(
 				vec![],
@@ -1615,6 +1742,10 @@ mod tests {
 				chain_api: DummySubsystem,
 				collation_generation: DummySubsystem,
 				collator_protocol: DummySubsystem,
+				approval_voting: DummySubsystem,
+				approval_distribution: DummySubsystem,
+				availability_recovery: DummySubsystem,
+				peer_set_manager: DummySubsystem,
 			};
 			let (overseer, mut handler) = Overseer::new(
 				vec![first_block],
@@ -1720,6 +1851,10 @@ mod tests {
 				chain_api: DummySubsystem,
 				collation_generation: DummySubsystem,
 				collator_protocol: DummySubsystem,
+				approval_voting: DummySubsystem,
+				approval_distribution: DummySubsystem,
+				availability_recovery: DummySubsystem,
+				peer_set_manager: DummySubsystem,
 			};
 			// start with two forks of different height.
 			let (overseer, mut handler) = Overseer::new(
@@ -1925,6 +2060,29 @@ mod tests {
 		NetworkBridgeMessage::ReportPeer(PeerId::random(), ReputationChange::new(42, ""))
 	}
 
+	fn test_approval_voting_msg() -> ApprovalVotingMessage {
+		let (sender, _) = oneshot::channel();
+		let vote = IndirectSignedApprovalVote {
+			block_hash: Default::default(),
+			candidate_index: 0,
+			validator: 0,
+			signature: sp_core::sr25519::Signature([0u8; 64]).into(),
+		};
+		ApprovalVotingMessage::CheckAndImportApproval(vote, sender)
+	}
+
+	fn test_approval_distribution_msg() -> ApprovalDistributionMessage {
+		ApprovalDistributionMessage::NetworkBridgeUpdateV1(test_network_bridge_event())
+	}
+
+	fn test_availability_recovery_msg() -> AvailabilityRecoveryMessage {
+		AvailabilityRecoveryMessage::NetworkBridgeUpdateV1(test_network_bridge_event())
+	}
+
+	fn test_peer_set_manager_msg() -> PeerSetManagerMessage {
+		PeerSetManagerMessage::NetworkBridgeUpdateV1(test_network_bridge_event())
+	}
+
 	// Checks that `stop`, `broadcast_signal` and `broadcast_message` are implemented correctly.
 	#[test]
 	fn overseer_all_subsystems_receive_signals_and_messages() {
@@ -1957,6 +2115,10 @@ mod tests {
 				availability_store: subsystem.clone(),
 				network_bridge: subsystem.clone(),
 				chain_api: subsystem.clone(),
+				approval_voting: subsystem.clone(),
+				approval_distribution: subsystem.clone(),
+				availability_recovery: subsystem.clone(),
+				peer_set_manager: subsystem.clone(),
 			};
 			let (overseer, mut handler) = Overseer::new(
 				vec![],
@@ -1992,13 +2154,17 @@ mod tests {
 			handler.send_msg(AllMessages::AvailabilityStore(test_availability_store_msg())).await.unwrap();
 			handler.send_msg(AllMessages::NetworkBridge(test_network_bridge_msg())).await.unwrap();
 			handler.send_msg(AllMessages::ChainApi(test_chain_api_msg())).await.unwrap();
+			handler.send_msg(AllMessages::ApprovalVoting(test_approval_voting_msg())).await.unwrap();
+			handler.send_msg(AllMessages::ApprovalDistribution(test_approval_distribution_msg())).await.unwrap();
+			handler.send_msg(AllMessages::AvailabilityRecovery(test_availability_recovery_msg())).await.unwrap();
+			handler.send_msg(AllMessages::PeerSetManager(test_peer_set_manager_msg())).await.unwrap();
 
 			// send a stop signal to each subsystems
 			handler.stop().await.unwrap();
 
 			select! {
 				res = overseer_fut => {
-					const NUM_SUBSYSTEMS: usize = 15;
+					const NUM_SUBSYSTEMS: usize = 19;
 
 					assert_eq!(stop_signals_received.load(atomic::Ordering::SeqCst), NUM_SUBSYSTEMS);
 					// x2 because of broadcast_signal on startup
@@ -26,7 +26,7 @@ use polkadot_primitives::v1::{
 	Hash, CommittedCandidateReceipt, CandidateReceipt, CompactStatement,
 	EncodeAs, Signed, SigningContext, ValidatorIndex, ValidatorId,
 	UpwardMessage, Balance, ValidationCode, PersistedValidationData, ValidationData,
-	HeadData, PoV, CollatorPair, Id as ParaId,
+	HeadData, PoV, CollatorPair, Id as ParaId, OutboundHrmpMessage, BlockNumber,
 };
 use polkadot_statement_table::{
 	generic::{
@@ -38,6 +38,8 @@ use polkadot_statement_table::{
 
 pub use sp_core::traits::SpawnNamed;
 
+pub mod approval;
+
 /// A statement, where the candidate receipt is included in the `Seconded` variant.
 ///
 /// This is the committed candidate receipt instead of the bare candidate receipt. As such,
@@ -126,6 +128,12 @@ pub struct ValidationOutputs {
 	pub fees: Balance,
 	/// The new validation code submitted by the execution, if any.
 	pub new_validation_code: Option<ValidationCode>,
+	/// The number of messages processed from the DMQ.
+	pub processed_downward_messages: u32,
+	/// The horizontal messages sent by the parachain.
+	pub horizontal_messages: Vec<OutboundHrmpMessage<ParaId>>,
+	/// The mark which specifies the block number up to which all inbound HRMP messages are processed.
+	pub hrmp_watermark: BlockNumber,
 }
 
 /// Candidate invalidity details
@@ -153,6 +161,12 @@ pub enum InvalidCandidate {
 	HeadDataTooLarge(u64),
 	/// Code upgrade triggered but not allowed.
 	CodeUpgradeNotAllowed,
+	/// Execution exceeded the memory limit.
+	MemoryLimitExceeded,
+	/// Execution exceeded the stack limit.
+	StackLimitExceeded,
+	/// Execution ran out of fuel.
+	FuelExhausted,
 }
 
 /// Result of the validation of the candidate.
@@ -278,6 +292,12 @@ pub struct Collation {
 	pub head_data: HeadData,
 	/// Proof that this block is valid.
 	pub proof_of_validity: PoV,
+	/// The number of messages processed from the DMQ.
+	pub processed_downward_messages: u32,
+	/// The horizontal messages sent by the parachain.
+	pub horizontal_messages: Vec<OutboundHrmpMessage<ParaId>>,
+	/// The mark which specifies the block number up to which all inbound HRMP messages are processed.
+	pub hrmp_watermark: BlockNumber,
 }
 
 /// Configuration for the collation generator
@@ -30,6 +30,14 @@ use sp_blockchain::HeaderBackend;
 use polkadot_overseer::{AllSubsystems, BlockInfo, Overseer, OverseerHandler};
 use polkadot_subsystem::DummySubsystem;
 use polkadot_node_core_proposer::ProposerFactory;
+pub use polkadot_node_core_av_store::Config as AvailabilityConfig;
+pub use polkadot_node_core_candidate_validation::Config as CandidateValidationConfig;
+pub use polkadot_peer_set_manager::Config as PeerSetManagerConfig;
+pub use polkadot_parachain::wasm_executor::run_worker as run_validation_worker;
+use sc_keystore::KeyStorePtr;
+use polkadot_primitives::v1::Hash;
+use polkadot_primitives::v1::ParachainHost;
+use polkadot_network_bridge::RequestMultiplexer;
 use sp_trie::PrefixedMemoryDB;
 use sp_core::traits::SpawnNamed;
 use sc_client_api::ExecutorProvider;
@@ -249,11 +257,22 @@ fn new_partial<RuntimeApi, Executor>(config: &mut Configuration) -> Result<
 	})
 }
 
-fn real_overseer<S: SpawnNamed>(
+/// The configuration of the subsystems which keep their data on disk.
+struct SubsystemsConfig {
+	availability: AvailabilityConfig,
+	candidate_validation: CandidateValidationConfig,
+	peer_set_manager: PeerSetManagerConfig,
+}
+
+/// Build an overseer running none of the parachain subsystems.
+fn dummy_overseer<Spawner>(
 	leaves: impl IntoIterator<Item = BlockInfo>,
-	prometheus_registry: Option<&Registry>,
-	s: S,
-) -> Result<(Overseer<S>, OverseerHandler), ServiceError> {
+	registry: Option<&Registry>,
+	spawner: Spawner,
+) -> Result<(Overseer<Spawner>, OverseerHandler), ServiceError>
+where
+	Spawner: 'static + SpawnNamed + Clone + Unpin,
+{
 	let all_subsystems = AllSubsystems {
 		candidate_validation: DummySubsystem,
 		candidate_backing: DummySubsystem,
@@ -270,22 +289,204 @@ fn real_overseer<S: SpawnNamed>(
 		chain_api: DummySubsystem,
 		collation_generation: DummySubsystem,
 		collator_protocol: DummySubsystem,
+		approval_voting: DummySubsystem,
+		approval_distribution: DummySubsystem,
+		availability_recovery: DummySubsystem,
+		peer_set_manager: DummySubsystem,
+	};
+
+	Overseer::new(
+		leaves,
+		all_subsystems,
+		registry,
+		spawner,
+	).map_err(|e| ServiceError::Other(format!("Failed to create an Overseer: {:?}", e)))
+}
+
+/// Build an overseer running all of the parachain subsystems.
+fn real_overseer<Spawner, RuntimeClient>(
+	leaves: impl IntoIterator<Item = BlockInfo>,
+	keystore: KeyStorePtr,
+	runtime_client: Arc<RuntimeClient>,
+	subsystems_config: SubsystemsConfig,
+	network_service: Arc<sc_network::NetworkService<Block, Hash>>,
+	authority_discovery: Option<authority_discovery::Service>,
+	request_multiplexer: RequestMultiplexer,
+	registry: Option<&Registry>,
+	spawner: Spawner,
+	collator_id: Option<CollatorId>,
+	slot_duration_millis: u64,
+) -> Result<(Overseer<Spawner>, OverseerHandler), ServiceError>
+where
+	RuntimeClient: 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync,
+	RuntimeClient::Api: ParachainHost<Block>,
+	Spawner: 'static + SpawnNamed + Clone + Unpin,
+{
+	use polkadot_subsystem::metrics::Metrics;
+
+	use polkadot_approval_distribution::ApprovalDistribution as ApprovalDistributionSubsystem;
+	use polkadot_availability_distribution::AvailabilityDistributionSubsystem;
+	use polkadot_availability_recovery::AvailabilityRecoverySubsystem;
+	use polkadot_node_core_approval_voting::{
+		ApprovalVotingSubsystem, Config as ApprovalVotingConfig,
+	};
+	use polkadot_node_core_av_store::AvailabilityStoreSubsystem;
+	use polkadot_availability_bitfield_distribution::BitfieldDistribution as BitfieldDistributionSubsystem;
+	use polkadot_node_core_bitfield_signing::BitfieldSigningSubsystem;
+	use polkadot_node_core_backing::CandidateBackingSubsystem;
+	use polkadot_node_core_candidate_selection::CandidateSelectionSubsystem;
+	use polkadot_node_core_candidate_validation::CandidateValidationSubsystem;
+	use polkadot_node_core_chain_api::ChainApiSubsystem;
+	use polkadot_node_collation_generation::CollationGenerationSubsystem;
+	use polkadot_collator_protocol::CollatorProtocolSubsystem;
+	use polkadot_network_bridge::NetworkBridge as NetworkBridgeSubsystem;
+	use polkadot_peer_set_manager::PeerSetManagerSubsystem;
+	use polkadot_pov_distribution::PoVDistribution as PoVDistributionSubsystem;
+	use polkadot_node_core_provisioner::ProvisioningSubsystem;
+	use polkadot_node_core_runtime_api::RuntimeApiSubsystem;
+	use polkadot_statement_distribution::StatementDistribution as StatementDistributionSubsystem;
+
+	let all_subsystems = AllSubsystems {
+		approval_distribution: ApprovalDistributionSubsystem,
+		approval_voting: ApprovalVotingSubsystem::new(
+			keystore.clone(),
+			ApprovalVotingConfig {
+				slot_duration_millis,
+				..Default::default()
+			},
+			Metrics::register(registry),
+		),
+		availability_distribution: AvailabilityDistributionSubsystem::new(
+			keystore.clone(),
+		),
+		availability_recovery: AvailabilityRecoverySubsystem,
+		availability_store: AvailabilityStoreSubsystem::new_on_disk(
+			subsystems_config.availability,
+			Metrics::register(registry),
+		)?,
+		bitfield_distribution: BitfieldDistributionSubsystem,
+		bitfield_signing: BitfieldSigningSubsystem::new(
+			spawner.clone(),
+			keystore.clone(),
+			Metrics::register(registry),
+		),
+		candidate_backing: CandidateBackingSubsystem::new(
+			spawner.clone(),
+			keystore.clone(),
+			Metrics::register(registry),
+		),
+		candidate_selection: CandidateSelectionSubsystem::new(
+			spawner.clone(),
+			(),
+			Metrics::register(registry),
+		),
+		candidate_validation: CandidateValidationSubsystem::new(
+			spawner.clone(),
+			subsystems_config.candidate_validation,
+			Metrics::register(registry),
+		),
+		chain_api: ChainApiSubsystem::new(
+			runtime_client.clone(),
+			Metrics::register(registry),
+		),
+		collation_generation: CollationGenerationSubsystem::new(
+			Metrics::register(registry),
+		),
+		collator_protocol: CollatorProtocolSubsystem::new(
+			collator_id,
+		),
+		network_bridge: NetworkBridgeSubsystem::new(
+			network_service,
+			authority_discovery,
+			request_multiplexer,
+		),
+		peer_set_manager: PeerSetManagerSubsystem::new_on_disk(
+			subsystems_config.peer_set_manager,
+		)?,
+		pov_distribution: PoVDistributionSubsystem,
+		provisioner: ProvisioningSubsystem::new(
+			spawner.clone(),
+			(),
+			Metrics::register(registry),
+		),
+		runtime_api: RuntimeApiSubsystem::new(
+			runtime_client,
+			Metrics::register(registry),
+		),
+		statement_distribution: StatementDistributionSubsystem,
 	};
 
 	Overseer::new(
 		leaves,
 		all_subsystems,
-		prometheus_registry,
-		s,
+		registry,
+		spawner,
 	).map_err(|e| ServiceError::Other(format!("Failed to create an Overseer: {:?}", e)))
 }
 
+/// Derive the availability store configuration from the node's database configuration.
+///
+/// The availability store lives in a sub-directory of the substrate database path, so that
+/// its column numbers don't conflict with substrate and commands like `purge-chain` work
+/// without further changes.
+#[cfg(feature = "full-node")]
+fn availability_config(config: &Configuration) -> Result<AvailabilityConfig, ServiceError> {
+	let path = config.database.path().ok_or_else(|| ServiceError::Other(
+		"The availability store requires a database path".into(),
+	))?;
+
+	Ok(AvailabilityConfig {
+		// substrate cache size is improper here; just use the default.
+		cache_size: None,
+		path: path.join("parachains").join("av-store"),
+		pruning: Default::default(),
+	})
+}
+
+/// Derive the candidate validation configuration from the node's database configuration and the
+/// number of validation workers, if set.
+///
+/// The PVF artifacts are kept in a sub-directory of the substrate database path as well, so that
+/// they are removed by `purge-chain`.
+#[cfg(feature = "full-node")]
+fn candidate_validation_config(
+	config: &Configuration,
+	validation_workers: Option<usize>,
+) -> Result<CandidateValidationConfig, ServiceError> {
+	let path = config.database.path().ok_or_else(|| ServiceError::Other(
+		"Candidate validation requires a database path".into(),
+	))?;
+
+	Ok(CandidateValidationConfig {
+		artifact_cache_path: path.join("parachains").join("pvf-artifacts"),
+		validation_workers,
+	})
+}
+
+/// Derive the peer set manager configuration from the node's database configuration.
+///
+/// Like the availability store, the peer set manager keeps its database in a sub-directory of
+/// the substrate database path.
+#[cfg(feature = "full-node")]
+fn peer_set_manager_config(config: &Configuration) -> Result<PeerSetManagerConfig, ServiceError> {
+	let path = config.database.path().ok_or_else(|| ServiceError::Other(
+		"The peer set manager requires a database path".into(),
+	))?;
+
+	Ok(PeerSetManagerConfig {
+		path: path.join("parachains").join("peer-set"),
+		reputation: Default::default(),
+	})
+}
+
 #[cfg(feature = "full-node")]
 fn new_full<RuntimeApi, Executor>(
 	mut config: Configuration,
 	collating_for: Option<(CollatorId, ParaId)>,
 	authority_discovery_enabled: bool,
 	grandpa_pause: Option<(u32, u32)>,
+	real_overseer: bool,
+	validation_workers: Option<usize>,
 ) -> Result<(
 	TaskManager,
 	Arc<FullClient<RuntimeApi, Executor>>,
@@ -321,6 +522,23 @@ fn new_full<RuntimeApi, Executor>(
 	} = new_partial::<RuntimeApi, Executor>(&mut config)?;
 
 	let prometheus_registry = config.prometheus_registry().cloned();
+	// only the real subsystems keep data on disk and speak the parachain network protocols, so
+	// nodes without a database path can still run the dummy overseer.
+	let real_subsystems = if real_overseer {
+		let subsystems_config = SubsystemsConfig {
+			availability: availability_config(&config)?,
+			candidate_validation: candidate_validation_config(&config, validation_workers)?,
+			peer_set_manager: peer_set_manager_config(&config)?,
+		};
+
+		config.network.notifications_protocols.extend(polkadot_network_bridge::notifications_protocol_info());
+		let (request_multiplexer, configs) = RequestMultiplexer::new();
+		config.network.request_response_protocols.extend(configs);
+
+		Some((subsystems_config, request_multiplexer))
+	} else {
+		None
+	};
 
 	let (shared_voter_state, finality_proof_provider) = rpc_setup;
 
@@ -380,7 +598,62 @@ fn new_full<RuntimeApi, Executor>(
 		})
 		.collect();
 
-	let (overseer, handler) = real_overseer(leaves, prometheus_registry.as_ref(), spawner)?;
+	let authority_discovery_service = if authority_discovery_enabled
+		&& (is_collator || matches!(role, Role::Authority{..} | Role::Sentry{..}))
+	{
+		use sc_network::Event;
+		use futures::StreamExt;
+
+		// collators only look up the addresses of validators, they don't publish their own.
+		let (sentries, authority_discovery_role) = match role {
+			Role::Authority { ref sentry_nodes } if !is_collator => (
+				sentry_nodes.clone(),
+				authority_discovery::Role::Authority (
+					keystore.clone(),
+				),
+			),
+			_ => (
+				vec![],
+				authority_discovery::Role::Sentry,
+			),
+		};
+
+		let network_event_stream = network.event_stream("authority-discovery");
+		let dht_event_stream = network_event_stream.filter_map(|e| async move { match e {
+			Event::Dht(e) => Some(e),
+			_ => None,
+		}}).boxed();
+		let (authority_discovery_worker, service) = authority_discovery::new_worker_and_service(
+			client.clone(),
+			network.clone(),
+			sentries,
+			dht_event_stream,
+			authority_discovery_role,
+			prometheus_registry.clone(),
+		);
+
+		task_manager.spawn_handle().spawn("authority-discovery-worker", authority_discovery_worker);
+		Some(service)
+	} else {
+		None
+	};
+
+	let (overseer, handler) = match real_subsystems {
+		Some((subsystems_config, request_multiplexer)) => real_overseer(
+			leaves,
+			keystore.clone(),
+			overseer_client.clone(),
+			subsystems_config,
+			network.clone(),
+			authority_discovery_service,
+			request_multiplexer,
+			prometheus_registry.as_ref(),
+			spawner,
+			collating_for.as_ref().map(|(collator_id, _)| collator_id.clone()),
+			babe_link.config().slot_duration,
+		)?,
+		None => dummy_overseer(leaves, prometheus_registry.as_ref(), spawner)?,
+	};
 	let handler_clone = handler.clone();
 
 	task_manager.spawn_essential_handle().spawn_blocking("overseer", Box::pin(async move {
@@ -499,44 +772,6 @@ fn new_full<RuntimeApi, Executor>(
 		)?;
 	}
 
-	if matches!(role, Role::Authority{..} | Role::Sentry{..}) {
-		use sc_network::Event;
-		use futures::StreamExt;
-
-		if authority_discovery_enabled {
-			let (sentries, authority_discovery_role) = match role {
-				Role::Authority { ref sentry_nodes } => (
-					sentry_nodes.clone(),
-					authority_discovery::Role::Authority (
-						keystore.clone(),
-					),
-				),
-				Role::Sentry {..} => (
-					vec![],
-					authority_discovery::Role::Sentry,
-				),
-				_ => unreachable!("Due to outer matches! constraint; qed."),
-			};
-
-			let network_event_stream = network.event_stream("authority-discovery");
-			let dht_event_stream = network_event_stream.filter_map(|e| async move { match e {
-				Event::Dht(e) => Some(e),
-				_ => None,
-			}}).boxed();
-			let (authority_discovery_worker, _service) = authority_discovery::new_worker_and_service(
-				client.clone(),
-				network.clone(),
-				sentries,
-				dht_event_stream,
-				authority_discovery_role,
-				prometheus_registry.clone(),
-			);
-
-			task_manager.spawn_handle().spawn("authority-discovery-worker", authority_discovery_worker);
-		}
-	}
-
-
 	network_starter.start_network();
 
 	Ok((task_manager, client, network, rpc_handlers, handler))
@@ -689,6 +924,8 @@ pub fn build_full(
 	collating_for: Option<(CollatorId, ParaId)>,
 	authority_discovery_enabled: bool,
 	grandpa_pause: Option<(u32, u32)>,
+	real_overseer: bool,
+	validation_workers: Option<usize>,
 ) -> Result<(TaskManager, Client, OverseerHandler), ServiceError> {
 	if config.chain_spec.is_kusama() {
 		new_full::<kusama_runtime::RuntimeApi, KusamaExecutor>(
@@ -696,6 +933,8 @@ pub fn build_full(
 			collating_for,
 			authority_discovery_enabled,
 			grandpa_pause,
+			real_overseer,
+			validation_workers,
 		).map(|(task_manager, client, _, _, handler)| (task_manager, Client::Kusama(client), handler))
 	} else if config.chain_spec.is_westend() {
 		new_full::<westend_runtime::RuntimeApi, WestendExecutor>(
@@ -703,6 +942,8 @@ pub fn build_full(
 			collating_for,
 			authority_discovery_enabled,
 			grandpa_pause,
+			real_overseer,
+			validation_workers,
 		).map(|(task_manager, client, _, _, handler)| (task_manager, Client::Westend(client), handler))
 	} else {
 		new_full::<polkadot_runtime::RuntimeApi, PolkadotExecutor>(
@@ -710,6 +951,8 @@ pub fn build_full(
 			collating_for,
 			authority_discovery_enabled,
 			grandpa_pause,
+			real_overseer,
+			validation_workers,
 		).map(|(task_manager, client, _, _, handler)| (task_manager, Client::Polkadot(client), handler))
 	}
 }
@@ -39,10 +39,11 @@ use keystore::KeyStorePtr;
 use parity_scale_codec::Encode;
 use pin_project::{pin_project, pinned_drop};
 use polkadot_primitives::v1::{
-	CandidateEvent, CommittedCandidateReceipt, CoreState, EncodeAs, PersistedValidationData,
+	CandidateEvent, CommittedCandidateReceipt, CoreState, EncodeAs, ExecutionLimits,
+	ExecutionTimeouts, PersistedValidationData,
 	GroupRotationInfo, Hash, Id as ParaId, ValidationData, OccupiedCoreAssumption,
-	SessionIndex, Signed, SigningContext, ValidationCode, ValidatorId, ValidatorIndex,
-	ValidatorPair,
+	SessionIndex, SessionInfo, Signed, SigningContext, ValidationCode, ValidatorId,
+	ValidatorIndex, ValidatorPair,
 };
 use sp_core::{Pair, traits::SpawnNamed};
 use std::{
@@ -188,9 +189,13 @@ specialize_requests! {
 	fn request_full_validation_data(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<ValidationData>; FullValidationData;
 	fn request_persisted_validation_data(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<PersistedValidationData>; PersistedValidationData;
 	fn request_session_index_for_child() -> SessionIndex; SessionIndexForChild;
+	fn request_session_info(index: SessionIndex) -> Option<SessionInfo>; SessionInfo;
 	fn request_validation_code(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<ValidationCode>; ValidationCode;
+	fn request_future_validation_code(para_id: ParaId) -> Option<ValidationCode>; FutureValidationCode;
 	fn request_candidate_pending_availability(para_id: ParaId) -> Option<CommittedCandidateReceipt>; CandidatePendingAvailability;
 	fn request_candidate_events() -> Vec<CandidateEvent>; CandidateEvents;
+	fn request_execution_limits() -> ExecutionLimits; ExecutionLimits;
+	fn request_execution_timeouts() -> ExecutionTimeouts; ExecutionTimeouts;
 }
 
 /// Request some data from the `RuntimeApi` via a SubsystemContext.
@@ -271,9 +276,13 @@ specialize_requests_ctx! {
 	fn request_full_validation_data_ctx(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<ValidationData>; FullValidationData;
 	fn request_persisted_validation_data_ctx(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<PersistedValidationData>; PersistedValidationData;
 	fn request_session_index_for_child_ctx() -> SessionIndex; SessionIndexForChild;
+	fn request_session_info_ctx(index: SessionIndex) -> Option<SessionInfo>; SessionInfo;
 	fn request_validation_code_ctx(para_id: ParaId, assumption: OccupiedCoreAssumption) -> Option<ValidationCode>; ValidationCode;
+	fn request_future_validation_code_ctx(para_id: ParaId) -> Option<ValidationCode>; FutureValidationCode;
 	fn request_candidate_pending_availability_ctx(para_id: ParaId) -> Option<CommittedCandidateReceipt>; CandidatePendingAvailability;
 	fn request_candidate_events_ctx() -> Vec<CandidateEvent>; CandidateEvents;
+	fn request_execution_limits_ctx() -> ExecutionLimits; ExecutionLimits;
+	fn request_execution_timeouts_ctx() -> ExecutionTimeouts; ExecutionTimeouts;
 }
 
 /// From the given set of validators, find the first key we can sign with, if any.
@@ -1020,7 +1029,7 @@ impl<F: Future> Future for Timeout<F> {
 mod tests {
 	use super::{Error as UtilError, JobManager, JobTrait, JobsError, TimeoutExt, ToJobTrait};
 	use polkadot_node_subsystem::{
-		messages::{AllMessages, CandidateSelectionMessage},
+		messages::{AllMessages, CandidateSelectionMessage, CollatorReport, PeerSetManagerMessage},
 		ActiveLeavesUpdate, FromOverseer, OverseerSignal, SpawnedSubsystem, Subsystem,
 	};
 	use assert_matches::assert_matches;
@@ -1030,7 +1039,7 @@ mod tests {
 		stream::{self, StreamExt},
 		future, Future, FutureExt, SinkExt,
 	};
-	use polkadot_primitives::v1::Hash;
+	use polkadot_primitives::v1::{CandidateReceipt, CollatorId, Hash};
 	use polkadot_node_subsystem_test_helpers::{self as test_helpers, make_subsystem_context};
 	use std::{collections::HashMap, convert::TryFrom, pin::Pin, time::Duration};
 
@@ -1044,6 +1053,7 @@ mod tests {
 	// most will want to retain the sender and receiver, as well as whatever other data they like
 	struct FakeCandidateSelectionJob {
 		receiver: mpsc::Receiver<ToJob>,
+		sender: mpsc::Sender<FromJob>,
 	}
 
 	// ToJob implementations require the following properties:
@@ -1096,12 +1106,16 @@ mod tests {
 	#[derive(Clone)]
 	enum FromJob {
 		Test,
+		ReportCollator(CollatorId),
 	}
 
 	impl From<FromJob> for AllMessages {
 		fn from(from_job: FromJob) -> AllMessages {
 			match from_job {
 				FromJob::Test => AllMessages::CandidateSelection(CandidateSelectionMessage::default()),
+				FromJob::ReportCollator(collator) => AllMessages::PeerSetManager(
+					PeerSetManagerMessage::ReportCollator(collator, CollatorReport::InvalidCollation),
+				),
 			}
 		}
 	}
@@ -1142,15 +1156,14 @@ mod tests {
 			mut sender: mpsc::Sender<FromJob>,
 		) -> Pin<Box<dyn Future<Output = Result<(), Self::Error>> + Send>> {
 			async move {
-				let job = FakeCandidateSelectionJob { receiver };
-
 				// most jobs will have a request-response cycle at the heart of their run loop.
-				// however, in this case, we never receive valid messages, so we may as well
-				// just send all of our (mock) output messages now
+				// however, in this case, we just send all of our (mock) output messages now
 				let mock_output = run_args.remove(&parent).unwrap_or_default();
 				let mut stream = stream::iter(mock_output.into_iter().map(Ok));
 				sender.send_all(&mut stream).await?;
 
+				let job = FakeCandidateSelectionJob { receiver, sender };
+
 				// it isn't necessary to break run_loop into its own function,
 				// but it's convenient to separate the concerns in this way
 				job.run_loop().await
@@ -1163,9 +1176,10 @@ mod tests {
 		async fn run_loop(mut self) -> Result<(), Error> {
 			while let Some(msg) = self.receiver.next().await {
 				match msg {
-					ToJob::CandidateSelection(_csm) => {
-						unimplemented!("we'd report the collator to the peer set manager here, but that's not implemented yet");
+					ToJob::CandidateSelection(CandidateSelectionMessage::Invalid(_, receipt)) => {
+						self.sender.send(FromJob::ReportCollator(receipt.descriptor.collator)).await?;
 					}
+					ToJob::CandidateSelection(CandidateSelectionMessage::Collation(..)) => {}
 					ToJob::Stop => break,
 				}
 			}
@@ -1304,6 +1318,52 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn jobs_report_invalid_collations() {
+		let relay_parent = Hash::repeat_byte(0x01);
+		let mut run_args = HashMap::new();
+		run_args.insert(
+			relay_parent.clone(),
+			vec![FromJob::Test],
+		);
+
+		test_harness(run_args, |mut overseer_handle, err_rx| async move {
+			overseer_handle
+				.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+					ActiveLeavesUpdate::start_work(relay_parent),
+				)))
+				.await;
+			assert_matches!(
+				overseer_handle.recv().await,
+				AllMessages::CandidateSelection(_)
+			);
+
+			let receipt = CandidateReceipt::default();
+			overseer_handle
+				.send(FromOverseer::Communication {
+					msg: CandidateSelectionMessage::Invalid(relay_parent, receipt.clone()),
+				})
+				.await;
+
+			assert_matches!(
+				overseer_handle.recv().await,
+				AllMessages::PeerSetManager(PeerSetManagerMessage::ReportCollator(
+					collator,
+					CollatorReport::InvalidCollation,
+				)) => {
+					assert_eq!(collator, receipt.descriptor.collator);
+				}
+			);
+
+			overseer_handle
+				.send(FromOverseer::Signal(OverseerSignal::Conclude))
+				.await;
+
+			let errs: Vec<_> = err_rx.collect().await;
+			assert_eq!(errs.len(), 0);
+		});
+	}
+
 	#[test]
 	fn test_subsystem_impl_and_name_derivation() {
 		let pool = sp_core::testing::TaskExecutor::new();
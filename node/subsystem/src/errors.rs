@@ -55,3 +55,21 @@ impl core::fmt::Display for ChainApiError {
 		write!(f, "{}", self.msg)
 	}
 }
+
+/// An error which can be returned when recovering the available data of a candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryError {
+	/// Enough chunks were recovered, but they don't encode data matching the erasure root.
+	Invalid,
+	/// Not enough chunks could be recovered.
+	Unavailable,
+}
+
+impl core::fmt::Display for RecoveryError {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+		match self {
+			RecoveryError::Invalid => write!(f, "the recovered data is invalid"),
+			RecoveryError::Unavailable => write!(f, "the data is unavailable"),
+		}
+	}
+}
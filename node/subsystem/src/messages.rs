@@ -26,19 +26,24 @@ use futures::channel::{mpsc, oneshot};
 
 use polkadot_node_network_protocol::{
 	v1 as protocol_v1, NetworkBridgeEvent, ReputationChange, PeerId, PeerSet,
+	request_response::{v1 as req_res_v1, IncomingRequest, Requests},
 };
 use polkadot_node_primitives::{
 	CollationGenerationConfig, MisbehaviorReport, SignedFullStatement, ValidationResult,
+	approval::{CandidateIndex, IndirectAssignmentCert, IndirectSignedApprovalVote},
 };
 use polkadot_primitives::v1::{
-	AvailableData, BackedCandidate, BlockNumber, CandidateDescriptor, CandidateEvent,
+	AuthorityDiscoveryId, AvailableData, BackedCandidate, BlockNumber, CandidateDescriptor, CandidateEvent,
 	CandidateReceipt, CollatorId, CommittedCandidateReceipt,
-	CoreState, ErasureChunk, GroupRotationInfo, Hash, Id as ParaId,
-	OccupiedCoreAssumption, PersistedValidationData, PoV, SessionIndex, SignedAvailabilityBitfield,
-	TransientValidationData, ValidationCode, ValidatorId, ValidationData, ValidatorIndex,
-	ValidatorSignature,
+	CoreState, ErasureChunk, ExecutionLimits, ExecutionTimeouts, GroupRotationInfo, Hash, Header,
+	Id as ParaId,
+	InboundDownwardMessage,
+	InboundHrmpMessage,
+	OccupiedCoreAssumption, PersistedValidationData, PoV, SessionIndex, SessionInfo,
+	SignedAvailabilityBitfield, TransientValidationData, ValidationCode, ValidatorId, ValidationData, ValidatorIndex,
+	DisputeStatementSet,
 };
-use std::sync::Arc;
+use std::{sync::Arc, collections::btree_map::BTreeMap, time::Duration};
 
 /// A notification of a new backed candidate.
 #[derive(Debug)]
@@ -48,7 +53,8 @@ pub struct NewBackedCandidate(pub BackedCandidate);
 #[derive(Debug)]
 pub enum CandidateSelectionMessage {
 	/// A candidate collation can be fetched from a collator and should be considered for seconding.
-	Collation(Hash, ParaId, CollatorId),
+	/// The last field is the size of its PoV in bytes, as advertised by the collator.
+	Collation(Hash, ParaId, CollatorId, u32),
 	/// We recommended a particular candidate to be seconded, but it was invalid; penalize the collator.
 	/// The hash is the relay parent.
 	Invalid(Hash, CandidateReceipt),
@@ -126,12 +132,16 @@ pub enum CandidateValidationMessage {
 	/// Explicitly provide the `PersistedValidationData` and `ValidationCode` so this can do full
 	/// validation without needing to access the state of the relay-chain. Optionally provide the
 	/// `TransientValidationData` for further checks on the outputs.
+	///
+	/// Optionally provide the timeout of execution. Otherwise, the backing timeout as of the
+	/// `relay_parent` of the `CandidateDescriptor` is used.
 	ValidateFromExhaustive(
 		PersistedValidationData,
 		Option<TransientValidationData>,
 		ValidationCode,
 		CandidateDescriptor,
 		Arc<PoV>,
+		Option<Duration>,
 		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
 	),
 }
@@ -141,7 +151,7 @@ impl CandidateValidationMessage {
 	pub fn relay_parent(&self) -> Option<Hash> {
 		match self {
 			Self::ValidateFromChainState(_, _, _) => None,
-			Self::ValidateFromExhaustive(_, _, _, _, _, _) => None,
+			Self::ValidateFromExhaustive(_, _, _, _, _, _, _) => None,
 		}
 	}
 }
@@ -168,6 +178,8 @@ pub enum CollatorProtocolMessage {
 	NoteGoodCollation(CollatorId),
 	/// Get a network bridge update.
 	NetworkBridgeUpdateV1(NetworkBridgeEvent<protocol_v1::CollatorProtocolMessage>),
+	/// A validator requests a collation from us.
+	CollationFetchingRequest(IncomingRequest<req_res_v1::CollationFetchingRequest>),
 }
 
 impl CollatorProtocolMessage {
@@ -180,6 +192,7 @@ impl CollatorProtocolMessage {
 			Self::ReportCollator(_) => None,
 			Self::NoteGoodCollation(_) => None,
 			Self::NetworkBridgeUpdateV1(_) => None,
+			Self::CollationFetchingRequest(req) => Some(req.payload.relay_parent),
 		}
 	}
 }
@@ -196,11 +209,21 @@ pub enum NetworkBridgeMessage {
 	/// Send a message to one or more peers on the collation peer-set.
 	SendCollationMessage(Vec<PeerId>, protocol_v1::CollationProtocol),
 
-	/// Connect to peers who represent the given `ValidatorId`s at the given relay-parent.
+	/// Connect to peers who represent the given `ValidatorId`s on the given peer-set.
 	///
-	/// Also accepts a response channel by which the issuer can learn the `PeerId`s of those
-	/// validators.
-	ConnectToValidators(PeerSet, Vec<ValidatorId>, oneshot::Sender<Vec<(ValidatorId, PeerId)>>),
+	/// Also accepts a channel by which the issuer can learn the `PeerId`s of those validators.
+	/// The connections are kept for as long as the receiving side of the channel is alive; once
+	/// it is dropped, the request is revoked.
+	ConnectToValidators(PeerSet, Vec<ValidatorId>, mpsc::UnboundedSender<(ValidatorId, PeerId)>),
+
+	/// Send requests on the request/response protocols. The responses are delivered on the
+	/// channels included in the requests.
+	SendRequests(Vec<Requests>),
+
+	/// Inform the bridge about the collators on the collation peer-set, as determined by the
+	/// peer set manager. The first set are the peers of collators in good standing, the second
+	/// one those of banned collators. Each message replaces the sets sent before.
+	SetCollatorPeers(Vec<PeerId>, Vec<PeerId>),
 }
 
 impl NetworkBridgeMessage {
@@ -211,6 +234,8 @@ impl NetworkBridgeMessage {
 			Self::SendValidationMessage(_, _) => None,
 			Self::SendCollationMessage(_, _) => None,
 			Self::ConnectToValidators(_, _, _) => None,
+			Self::SendRequests(_) => None,
+			Self::SetCollatorPeers(_, _) => None,
 		}
 	}
 }
@@ -231,6 +256,30 @@ impl AvailabilityDistributionMessage {
 	}
 }
 
+/// Availability Recovery Message.
+#[derive(Debug)]
+pub enum AvailabilityRecoveryMessage {
+	/// Recover the available data of a candidate from the chunks held by the validators of
+	/// the session the candidate was backed in.
+	RecoverAvailableData(
+		CandidateReceipt,
+		SessionIndex,
+		oneshot::Sender<Result<AvailableData, crate::errors::RecoveryError>>,
+	),
+	/// Event from the network bridge.
+	NetworkBridgeUpdateV1(NetworkBridgeEvent<protocol_v1::AvailabilityRecoveryMessage>),
+}
+
+impl AvailabilityRecoveryMessage {
+	/// If the current variant contains the relay parent hash, return it.
+	pub fn relay_parent(&self) -> Option<Hash> {
+		match self {
+			Self::RecoverAvailableData(_, _, _) => None,
+			Self::NetworkBridgeUpdateV1(_) => None,
+		}
+	}
+}
+
 /// Bitfield distribution message.
 #[derive(Debug)]
 pub enum BitfieldDistributionMessage {
@@ -322,6 +371,9 @@ pub enum ChainApiMessage {
 	/// Request the block number by hash.
 	/// Returns `None` if a block with the given hash is not present in the db.
 	BlockNumber(Hash, ChainApiResponseChannel<Option<BlockNumber>>),
+	/// Request the block header by hash.
+	/// Returns `None` if a block with the given hash is not present in the db.
+	BlockHeader(Hash, ChainApiResponseChannel<Option<Header>>),
 	/// Request the finalized block hash by number.
 	/// Returns `None` if a block with the given number is not present in the db.
 	/// Note: the caller must ensure the block is finalized.
@@ -380,15 +432,36 @@ pub enum RuntimeApiRequest {
 	),
 	/// Get the session index that a child of the block will have.
 	SessionIndexForChild(RuntimeApiSender<SessionIndex>),
+	/// Get the session info for the given session, if stored.
+	SessionInfo(SessionIndex, RuntimeApiSender<Option<SessionInfo>>),
 	/// Get the validation code for a para, taking the given `OccupiedCoreAssumption`, which
 	/// will inform on how the validation data should be computed if the para currently
 	/// occupies a core.
 	ValidationCode(ParaId, OccupiedCoreAssumption, RuntimeApiSender<Option<ValidationCode>>),
+	/// Get the validation code a para is scheduled to upgrade to, if any.
+	FutureValidationCode(ParaId, RuntimeApiSender<Option<ValidationCode>>),
 	/// Get a the candidate pending availability for a particular parachain by parachain / core index
 	CandidatePendingAvailability(ParaId, RuntimeApiSender<Option<CommittedCandidateReceipt>>),
 	/// Get all events concerning candidates (backing, inclusion, time-out) in the parent of
 	/// the block in whose state this request is executed.
 	CandidateEvents(RuntimeApiSender<Vec<CandidateEvent>>),
+	/// Get all the pending inbound messages in the downward message queue for a para.
+	DmqContents(
+		ParaId,
+		RuntimeApiSender<Vec<InboundDownwardMessage<BlockNumber>>>,
+	),
+	/// Get the contents of all channels addressed to the given recipient. Channels that have no
+	/// messages in them are also included.
+	InboundHrmpChannelsContents(
+		ParaId,
+		RuntimeApiSender<BTreeMap<ParaId, Vec<InboundHrmpMessage<BlockNumber>>>>,
+	),
+	/// Get the authority discovery keys of the given validators, in the same order.
+	ValidatorDiscovery(Vec<ValidatorId>, RuntimeApiSender<Vec<Option<AuthorityDiscoveryId>>>),
+	/// Get the limits validation functions are executed under.
+	ExecutionLimits(RuntimeApiSender<ExecutionLimits>),
+	/// Get the timeouts of the execution of validation functions.
+	ExecutionTimeouts(RuntimeApiSender<ExecutionTimeouts>),
 }
 
 /// A message to the Runtime API subsystem.
@@ -437,14 +510,19 @@ pub enum ProvisionableData {
 	BackedCandidate(BackedCandidate),
 	/// Misbehavior reports are self-contained proofs of validator misbehavior.
 	MisbehaviorReport(Hash, MisbehaviorReport),
-	/// Disputes trigger a broad dispute resolution process.
-	Dispute(Hash, ValidatorSignature),
+	/// Disputes trigger a broad dispute resolution process. These are signed statements about the
+	/// validity of a candidate, which are imported by the disputes module of the runtime.
+	Dispute(Hash, DisputeStatementSet),
 }
 
 /// This data needs to make its way from the provisioner into the InherentData.
 ///
 /// There, it is used to construct the InclusionInherent.
-pub type ProvisionerInherentData = (Vec<SignedAvailabilityBitfield>, Vec<BackedCandidate>);
+pub type ProvisionerInherentData = (
+	Vec<SignedAvailabilityBitfield>,
+	Vec<BackedCandidate>,
+	Vec<DisputeStatementSet>,
+);
 
 /// Message to the Provisioner.
 ///
@@ -488,6 +566,8 @@ pub enum PoVDistributionMessage {
 	DistributePoV(Hash, CandidateDescriptor, Arc<PoV>),
 	/// An update from the network bridge.
 	NetworkBridgeUpdateV1(NetworkBridgeEvent<protocol_v1::PoVDistributionMessage>),
+	/// A peer requests a PoV from us.
+	PoVFetchingRequest(IncomingRequest<req_res_v1::PoVFetchingRequest>),
 }
 
 impl PoVDistributionMessage {
@@ -497,6 +577,7 @@ impl PoVDistributionMessage {
 			Self::FetchPoV(hash, _, _) => Some(*hash),
 			Self::DistributePoV(hash, _, _) => Some(*hash),
 			Self::NetworkBridgeUpdateV1(_) => None,
+			Self::PoVFetchingRequest(req) => Some(req.payload.relay_parent),
 		}
 	}
 }
@@ -515,6 +596,105 @@ impl CollationGenerationMessage {
 	}
 }
 
+/// The result type of [`ApprovalVotingMessage::CheckAndImportAssignment`] request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssignmentCheckResult {
+	/// The vote was accepted and should be propagated onwards.
+	Accepted,
+	/// The vote was valid but duplicate and should not be propagated onwards.
+	AcceptedDuplicate,
+	/// The vote was valid but too far in the future to accept right now.
+	TooFarInFuture,
+	/// The vote was bad and should be ignored, reporting the peer who propagated it.
+	Bad,
+}
+
+/// The result type of [`ApprovalVotingMessage::CheckAndImportApproval`] request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalCheckResult {
+	/// The vote was accepted and should be propagated onwards.
+	Accepted,
+	/// The vote was bad and should be ignored, reporting the peer who propagated it.
+	Bad,
+}
+
+/// Message to the Approval Voting subsystem.
+#[derive(Debug)]
+pub enum ApprovalVotingMessage {
+	/// Check if the assignment is valid and can be accepted by our view of the protocol.
+	/// Should not be sent unless the block hash is known.
+	CheckAndImportAssignment(
+		IndirectAssignmentCert,
+		CandidateIndex,
+		oneshot::Sender<AssignmentCheckResult>,
+	),
+	/// Check if the approval vote is valid and can be accepted by our view of the
+	/// protocol.
+	///
+	/// Should not be sent unless the block hash within the indirect vote is known.
+	CheckAndImportApproval(
+		IndirectSignedApprovalVote,
+		oneshot::Sender<ApprovalCheckResult>,
+	),
+}
+
+impl ApprovalVotingMessage {
+	/// If the current variant contains the relay parent hash, return it.
+	pub fn relay_parent(&self) -> Option<Hash> {
+		None
+	}
+}
+
+/// Message to the Approval Distribution subsystem.
+#[derive(Debug)]
+pub enum ApprovalDistributionMessage {
+	/// Distribute an assignment cert from the local validator. The cert is assumed
+	/// to be valid, relevant, and for the given relay-parent and validator index.
+	DistributeAssignment(IndirectAssignmentCert, CandidateIndex),
+	/// Distribute an approval vote for the local validator. The approval vote is assumed to be
+	/// valid, relevant, and the corresponding approval already issued.
+	DistributeApproval(IndirectSignedApprovalVote),
+	/// An update from the network bridge.
+	NetworkBridgeUpdateV1(NetworkBridgeEvent<protocol_v1::ApprovalDistributionMessage>),
+}
+
+impl ApprovalDistributionMessage {
+	/// If the current variant contains the relay parent hash, return it.
+	pub fn relay_parent(&self) -> Option<Hash> {
+		None
+	}
+}
+
+/// The behavior of a collator, as reported to the peer set manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollatorReport {
+	/// The collator provided a collation which turned out to be invalid.
+	InvalidCollation,
+	/// A request to the collator failed, timed out or was answered with garbage.
+	RequestFailed,
+	/// The collator provided a collation which turned out to be valid.
+	GoodCollation,
+}
+
+/// Message to the Peer Set Manager subsystem.
+#[derive(Debug)]
+pub enum PeerSetManagerMessage {
+	/// Report the behavior of a collator, adjusting its persistent score.
+	ReportCollator(CollatorId, CollatorReport),
+	/// An update from the network bridge on the collation peer-set.
+	NetworkBridgeUpdateV1(NetworkBridgeEvent<protocol_v1::CollatorProtocolMessage>),
+	/// Get the current scores of the given collators, in the same order.
+	/// Banned collators have a score of `i32::MIN`.
+	CollatorScores(Vec<CollatorId>, oneshot::Sender<Vec<i32>>),
+}
+
+impl PeerSetManagerMessage {
+	/// If the current variant contains the relay parent hash, return it.
+	pub fn relay_parent(&self) -> Option<Hash> {
+		None
+	}
+}
+
 /// A message type tying together all message types that are used across Subsystems.
 #[derive(Debug)]
 pub enum AllMessages {
@@ -532,6 +712,8 @@ pub enum AllMessages {
 	StatementDistribution(StatementDistributionMessage),
 	/// Message for the availability distribution subsystem.
 	AvailabilityDistribution(AvailabilityDistributionMessage),
+	/// Message for the availability recovery subsystem.
+	AvailabilityRecovery(AvailabilityRecoveryMessage),
 	/// Message for the bitfield distribution subsystem.
 	BitfieldDistribution(BitfieldDistributionMessage),
 	/// Message for the bitfield signing subsystem.
@@ -548,4 +730,10 @@ pub enum AllMessages {
 	NetworkBridge(NetworkBridgeMessage),
 	/// Message for the Collation Generation subsystem
 	CollationGeneration(CollationGenerationMessage),
+	/// Message for the Approval Voting subsystem.
+	ApprovalVoting(ApprovalVotingMessage),
+	/// Message for the Approval Distribution subsystem.
+	ApprovalDistribution(ApprovalDistributionMessage),
+	/// Message for the Peer Set Manager subsystem.
+	PeerSetManager(PeerSetManagerMessage),
 }
@@ -28,7 +28,7 @@ use serde::{Serialize, Deserialize};
 #[cfg(feature = "std")]
 use sp_core::bytes;
 
-use polkadot_core_primitives::Hash;
+use polkadot_core_primitives::{Hash, OutboundHrmpMessage};
 
 /// Block number type used by the relay chain.
 pub use polkadot_core_primitives::BlockNumber as RelayChainBlockNumber;
@@ -55,6 +55,38 @@ impl From<Vec<u8>> for ValidationCode {
 	}
 }
 
+/// The default maximum number of 64 KiB pages of linear memory a validation function may use.
+pub const DEFAULT_MAX_PVF_MEMORY_PAGES: u32 = 16 * 1024; // 1 GiB
+/// The default maximum logical stack height of a validation function.
+pub const DEFAULT_MAX_PVF_STACK_HEIGHT: u32 = 64 * 1024;
+
+/// Deterministic limits a validation function is executed under.
+///
+/// All validators execute a candidate under the same limits, so a validation function exceeding
+/// them is rejected by all validators alike, regardless of their hardware.
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode, RuntimeDebug)]
+pub struct ExecutionLimits {
+	/// The maximum number of 64 KiB pages of linear memory, including the pages the host provides
+	/// for the heap.
+	pub max_memory_pages: u32,
+	/// The maximum logical stack height. The height of a function call is the number of values it
+	/// keeps on the stack, including its locals and arguments.
+	pub max_stack_height: u32,
+	/// The amount of fuel execution may consume, if it is limited. One unit of fuel is consumed
+	/// per executed instruction.
+	pub max_fuel: Option<u64>,
+}
+
+impl Default for ExecutionLimits {
+	fn default() -> Self {
+		ExecutionLimits {
+			max_memory_pages: DEFAULT_MAX_PVF_MEMORY_PAGES,
+			max_stack_height: DEFAULT_MAX_PVF_STACK_HEIGHT,
+			max_fuel: None,
+		}
+	}
+}
+
 /// Parachain block data.
 ///
 /// Contains everything required to validate para-block, may contain block and witness data.
@@ -135,6 +167,21 @@ impl sp_std::ops::Add<u32> for Id {
 	}
 }
 
+/// A type that uniquely identifies an HRMP channel. An HRMP channel is established between two paras.
+/// In text, we use the notation `(A, B)` to specify a channel between A and B. The channels are
+/// unidirectional, meaning that `(A, B)` and `(B, A)` refer to different channels. The convention is
+/// that we use the first item tuple for the sender and the second for the recipient. Only one channel
+/// is allowed between two participants in one direction, i.e. there cannot be 2 different channels
+/// identified by `(A, B)`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Hash))]
+pub struct HrmpChannelId {
+	/// The para that acts as the sender in this channel.
+	pub sender: Id,
+	/// The para that acts as the recipient in this channel.
+	pub recipient: Id,
+}
+
 /// This type can be converted into and possibly from an AccountId (which itself is generic).
 pub trait AccountIdConversion<AccountId>: Sized {
 	/// Convert into an account ID. This is infallible.
@@ -240,6 +287,11 @@ pub struct ValidationParams {
 	/// vector is sorted ascending by the para id and doesn't contain multiple entries with the same
 	/// sender.
 	pub hrmp_mqc_heads: Vec<(Id, Hash)>,
+	/// The MQC head for the DMQ.
+	///
+	/// The DMQ MQC head will be used by the validation function to authorize the downward messages
+	/// passed by the collator.
+	pub dmq_mqc_head: Hash,
 }
 
 /// The result of parachain validation.
@@ -257,4 +309,8 @@ pub struct ValidationResult {
 	///
 	/// It is expected that the Parachain processes them from first to last.
 	pub processed_downward_messages: u32,
+	/// The horizontal messages sent by the parachain.
+	pub horizontal_messages: Vec<OutboundHrmpMessage<Id>>,
+	/// The mark which specifies the block number up to which all inbound HRMP messages are processed.
+	pub hrmp_watermark: RelayChainBlockNumber,
 }
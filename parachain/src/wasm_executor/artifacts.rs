@@ -0,0 +1,346 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An on-disk cache of PVF artifacts, keyed by the blake2-256 hash of the validation code and
+//! the execution limits the artifact enforces.
+//!
+//! An artifact is validation code which has been checked against the limits of the host,
+//! instrumented to enforce the execution limits and compiled to machine code. Executors load the
+//! compiled module from the artifact the first time they execute a candidate under it and keep it
+//! in memory keyed by the id of the artifact, so the compilation cost is paid once per code and
+//! limits rather than once per candidate or process.
+//!
+//! Compiled artifacts can only be loaded by the version of the executor which compiled them, so
+//! the node purges the cache when it starts.
+
+use std::{fs, io, path::{Path, PathBuf}, sync::atomic::{AtomicUsize, Ordering}};
+use codec::Encode;
+use log::debug;
+use sp_core::{bytes, hashing::blake2_256, hexdisplay::HexDisplay};
+use polkadot_core_primitives::Hash;
+use crate::primitives::ExecutionLimits;
+use super::{ValidationError, InternalError, compile_validation_code, prepare_validation_code};
+
+const ARTIFACT_EXTENSION: &str = "pvf";
+/// The number of bytes of the hash of the execution limits in the name of an artifact.
+const LIMITS_HASH_LEN: usize = 8;
+
+/// Distinguishes the temporary files of artifacts prepared concurrently within this process.
+static NEXT_TMP_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A prepared artifact in the cache.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Artifact {
+	/// The blake2-256 hash of the validation code the artifact was prepared from.
+	pub code_hash: Hash,
+	/// The execution limits the artifact enforces.
+	pub limits: ExecutionLimits,
+	/// The path of the artifact.
+	pub path: PathBuf,
+}
+
+impl Artifact {
+	/// The id of the artifact, which is unique for each pair of code hash and limits.
+	pub fn id(&self) -> Hash {
+		artifact_id(&self.code_hash, &self.limits)
+	}
+}
+
+fn artifact_id(code_hash: &Hash, limits: &ExecutionLimits) -> Hash {
+	Hash::from(blake2_256(&(code_hash, limits).encode()))
+}
+
+/// A directory of prepared artifacts.
+///
+/// The cache may be shared by several processes: artifacts are written to a temporary file
+/// first and then moved into place, so an artifact is either complete or absent.
+#[derive(Clone, Debug)]
+pub struct ArtifactCache {
+	dir: PathBuf,
+}
+
+impl ArtifactCache {
+	/// Create a cache keeping its artifacts in the given directory, creating it if it doesn't
+	/// exist.
+	///
+	/// Candidates are executed from whatever the directory contains, so it must be owned by the
+	/// user of this process and be inaccessible to all others.
+	pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+		let dir = dir.into();
+		create_private_dir(&dir)?;
+		Ok(ArtifactCache { dir })
+	}
+
+	/// The directory the artifacts are kept in.
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Artifacts are named `<code hash>-<limits hash>.pvf`, with the hash of the limits truncated.
+	fn artifact_path(&self, code_hash: &Hash, limits: &ExecutionLimits) -> PathBuf {
+		let limits_hash = blake2_256(&limits.encode());
+		self.dir
+			.join(format!(
+				"{}-{}",
+				HexDisplay::from(&code_hash.0),
+				HexDisplay::from(&&limits_hash[..LIMITS_HASH_LEN]),
+			))
+			.with_extension(ARTIFACT_EXTENSION)
+	}
+
+	/// Get the artifact prepared from the code with the given hash for the given limits, if any.
+	pub fn get(&self, code_hash: &Hash, limits: &ExecutionLimits) -> Option<Artifact> {
+		let path = self.artifact_path(code_hash, limits);
+		if path.is_file() {
+			Some(Artifact { code_hash: *code_hash, limits: *limits, path })
+		} else {
+			None
+		}
+	}
+
+	/// Whether an artifact was prepared from the code with the given hash for the given limits.
+	pub fn contains(&self, code_hash: &Hash, limits: &ExecutionLimits) -> bool {
+		self.get(code_hash, limits).is_some()
+	}
+
+	/// Get the artifact of the given validation code for the given limits, preparing it if it is
+	/// not in the cache yet.
+	///
+	/// The returned flag is `true` if the artifact was found in the cache.
+	pub fn get_or_prepare(
+		&self,
+		validation_code: &[u8],
+		limits: &ExecutionLimits,
+	) -> Result<(Artifact, bool), ValidationError> {
+		let code_hash = Hash::from(blake2_256(validation_code));
+		match self.get(&code_hash, limits) {
+			Some(artifact) => Ok((artifact, true)),
+			None => self.prepare(validation_code, limits).map(|artifact| (artifact, false)),
+		}
+	}
+
+	/// Prepare the artifact of the given validation code for the given limits and put it into the
+	/// cache, replacing any artifact prepared from the same code for the same limits before.
+	pub fn prepare(
+		&self,
+		validation_code: &[u8],
+		limits: &ExecutionLimits,
+	) -> Result<Artifact, ValidationError> {
+		let instrumented_code = prepare_validation_code(validation_code, limits)?;
+		let compiled_code = compile_validation_code(&instrumented_code)?;
+
+		let code_hash = Hash::from(blake2_256(validation_code));
+		let path = self.artifact_path(&code_hash, limits);
+
+		let tmp_path = path.with_extension(format!(
+			"{}.{}-{}.tmp",
+			ARTIFACT_EXTENSION,
+			std::process::id(),
+			NEXT_TMP_ID.fetch_add(1, Ordering::Relaxed),
+		));
+		fs::write(&tmp_path, compiled_code)
+			.and_then(|_| fs::rename(&tmp_path, &path))
+			.map_err(|e| {
+				let _ = fs::remove_file(&tmp_path);
+				InternalError::from(e)
+			})?;
+
+		debug!("Prepared PVF artifact {:?}", path);
+
+		Ok(Artifact { code_hash, limits: *limits, path })
+	}
+
+	/// The code hashes and paths of all artifacts in the cache.
+	fn entries(&self) -> io::Result<Vec<(Hash, PathBuf)>> {
+		let entries = match fs::read_dir(&self.dir) {
+			Ok(entries) => entries,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+			Err(e) => return Err(e),
+		};
+
+		let mut artifacts = Vec::new();
+		for entry in entries {
+			let path = entry?.path();
+			if path.extension().map_or(true, |ext| ext != ARTIFACT_EXTENSION) {
+				continue;
+			}
+
+			let code_hash = path.file_stem()
+				.and_then(|stem| stem.to_str())
+				.and_then(|stem| stem.split('-').next())
+				.and_then(|code_hash| bytes::from_hex(code_hash).ok())
+				.filter(|raw| raw.len() == Hash::len_bytes())
+				.map(|raw| Hash::from_slice(&raw));
+
+			if let Some(code_hash) = code_hash {
+				artifacts.push((code_hash, path));
+			}
+		}
+
+		Ok(artifacts)
+	}
+
+	/// The hashes of the code of all artifacts in the cache.
+	pub fn code_hashes(&self) -> io::Result<Vec<Hash>> {
+		let mut hashes: Vec<_> = self.entries()?.into_iter().map(|(code_hash, _)| code_hash).collect();
+		hashes.sort();
+		hashes.dedup();
+		Ok(hashes)
+	}
+
+	/// Remove all artifacts whose code hash is not to be kept, whatever limits they were prepared
+	/// for. Returns the code hashes of the removed artifacts.
+	pub fn prune(&self, keep: impl Fn(&Hash) -> bool) -> io::Result<Vec<Hash>> {
+		let mut pruned = Vec::new();
+		for (code_hash, path) in self.entries()? {
+			if keep(&code_hash) {
+				continue;
+			}
+
+			match fs::remove_file(path) {
+				Ok(()) => pruned.push(code_hash),
+				// another process might have pruned it already.
+				Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(pruned)
+	}
+}
+
+/// Create the given directory and its parents, accessible only to the user of this process, and
+/// check that an existing directory is too.
+#[cfg(all(unix, not(target_os = "android")))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+	use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+	fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+
+	let metadata = fs::symlink_metadata(dir)?;
+	let refuse = |reason: &str| Err(io::Error::new(
+		io::ErrorKind::PermissionDenied,
+		format!("Refusing to keep PVF artifacts in {:?}: {}", dir, reason),
+	));
+
+	if !metadata.is_dir() {
+		return refuse("not a directory");
+	}
+	if metadata.uid() != unsafe { libc::geteuid() } {
+		return refuse("owned by another user");
+	}
+	if metadata.mode() & 0o077 != 0 {
+		return refuse("accessible to other users");
+	}
+
+	Ok(())
+}
+
+#[cfg(any(not(unix), target_os = "android"))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+	fs::create_dir_all(dir)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::{InvalidCandidate, MAX_CODE_MEM};
+
+	fn cache_in_temp_dir(name: &str) -> ArtifactCache {
+		let dir = std::env::temp_dir()
+			.join(format!("polkadot-pvf-artifacts-test-{}-{}", name, std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		ArtifactCache::new(dir).unwrap()
+	}
+
+	/// A module without functions, whose memory has the given number of initial pages.
+	fn code(memory_pages: u32) -> Vec<u8> {
+		let module = parity_wasm::builder::module()
+			.memory().with_min(memory_pages).build()
+			.build();
+		parity_wasm::elements::serialize(module).unwrap()
+	}
+
+	#[test]
+	fn prepared_artifacts_are_found_by_code_hash_and_limits() {
+		let cache = cache_in_temp_dir("prepare");
+		let code = code(1);
+		let code_hash = Hash::from(blake2_256(&code));
+		let limits = ExecutionLimits::default();
+
+		assert!(!cache.contains(&code_hash, &limits));
+
+		let (artifact, hit) = cache.get_or_prepare(&code, &limits).unwrap();
+		assert!(!hit);
+		assert_eq!(artifact.code_hash, code_hash);
+		let instrumented_code = prepare_validation_code(&code, &limits).unwrap();
+		assert_eq!(fs::read(&artifact.path).unwrap(), compile_validation_code(&instrumented_code).unwrap());
+
+		let (artifact_again, hit) = cache.get_or_prepare(&code, &limits).unwrap();
+		assert!(hit);
+		assert_eq!(artifact_again, artifact);
+
+		let other_limits = ExecutionLimits { max_fuel: Some(1_000_000), ..limits };
+		let (other_artifact, hit) = cache.get_or_prepare(&code, &other_limits).unwrap();
+		assert!(!hit);
+		assert_ne!(other_artifact.path, artifact.path);
+		assert_ne!(other_artifact.id(), artifact.id());
+
+		assert_eq!(cache.code_hashes().unwrap(), vec![code_hash]);
+
+		fs::remove_dir_all(cache.dir()).unwrap();
+	}
+
+	#[test]
+	fn unreferenced_artifacts_are_pruned() {
+		let cache = cache_in_temp_dir("prune");
+		let limits = ExecutionLimits::default();
+		let kept = cache.prepare(&code(1), &limits).unwrap();
+		let pruned = cache.prepare(&code(2), &limits).unwrap();
+
+		assert_eq!(cache.prune(|h| h == &kept.code_hash).unwrap(), vec![pruned.code_hash]);
+		assert!(cache.contains(&kept.code_hash, &limits));
+		assert!(!cache.contains(&pruned.code_hash, &limits));
+
+		fs::remove_dir_all(cache.dir()).unwrap();
+	}
+
+	#[test]
+	#[cfg(all(unix, not(target_os = "android")))]
+	fn directories_accessible_to_others_are_refused() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let cache = cache_in_temp_dir("permissions");
+		fs::set_permissions(cache.dir(), fs::Permissions::from_mode(0o755)).unwrap();
+
+		let err = ArtifactCache::new(cache.dir()).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+		fs::remove_dir_all(cache.dir()).unwrap();
+	}
+
+	#[test]
+	fn too_large_code_is_rejected() {
+		let cache = cache_in_temp_dir("too-large");
+
+		match cache.prepare(&vec![0; MAX_CODE_MEM + 1], &Default::default()) {
+			Err(ValidationError::InvalidCandidate(InvalidCandidate::CodeTooLarge(_))) => {},
+			r => panic!("unexpected result: {:?}", r),
+		}
+
+		fs::remove_dir_all(cache.dir()).unwrap();
+	}
+}
@@ -0,0 +1,494 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Enforcement of the deterministic [`ExecutionLimits`] of validation functions.
+//!
+//! The limits are enforced by instrumenting the validation code before it is executed:
+//!
+//! - The maximum of the linear memory is lowered to the memory limit, so that the memory can't
+//!   grow beyond it. Code whose initial memory together with the heap exceeds the limit is
+//!   rejected.
+//! - Allocations on the heap go through the `ext_pvf_malloc` host function instead of
+//!   `ext_allocator_malloc`, recording the exhaustion of the heap.
+//! - Every function call is preceded by a check of the logical stack height, which doesn't depend
+//!   on the stack usage of the executor's host.
+//! - Every metered block of instructions is preceded by a call to the `ext_pvf_charge_fuel` host
+//!   function, charging the cost of the block.
+//!
+//! Breaches of the stack limit are reported through `ext_pvf_charge_fuel` as well, so fuel is
+//! metered even if it is not limited. The breaches are recorded in the [`FuelMeter`] of the
+//! execution, so that a trap can be told apart from others without inspecting the error of the
+//! executor.
+
+use parity_wasm::elements::{self, External, ImportEntry, Instruction, MemoryType, Module};
+use pwasm_utils::{rules, stack_height};
+use sp_wasm_interface::{Function, FunctionContext, Signature, Value, ValueType};
+use crate::primitives::ExecutionLimits;
+use super::InvalidCandidate;
+
+/// The number of 64 KiB pages the executor adds to the memory of a validation function for its
+/// heap.
+pub(super) const HEAP_PAGES: u32 = 1024;
+
+/// The name of the host function charging fuel.
+const CHARGE_FUEL: &str = "ext_pvf_charge_fuel_version_1";
+/// The fuel charged to report that the stack limit was exceeded.
+const STACK_LIMIT_EXCEEDED: i32 = -1;
+/// The name of the host function allocating memory on the heap, recording its exhaustion.
+const MALLOC: &str = "ext_pvf_malloc_version_1";
+/// The name of the allocating host function of `sp-io`, which `MALLOC` replaces.
+const ALLOCATOR_MALLOC: &str = "ext_allocator_malloc_version_1";
+
+/// A limit which execution exceeded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Breach {
+	Memory,
+	Stack,
+	Fuel,
+}
+
+/// Meters the fuel consumed by execution and records the limit execution exceeded, if any.
+#[derive(Debug)]
+pub(super) struct FuelMeter {
+	fuel_left: Option<u64>,
+	breach: Option<Breach>,
+}
+
+impl FuelMeter {
+	pub(super) fn new(limits: &ExecutionLimits) -> Self {
+		FuelMeter {
+			fuel_left: limits.max_fuel,
+			breach: None,
+		}
+	}
+
+	fn charge(&mut self, amount: i32) -> Result<(), String> {
+		if amount == STACK_LIMIT_EXCEEDED {
+			self.breach = Some(Breach::Stack);
+			return Err("Stack limit exceeded".into());
+		}
+
+		if let Some(ref mut fuel_left) = self.fuel_left {
+			match fuel_left.checked_sub(amount as u32 as u64) {
+				Some(left) => *fuel_left = left,
+				None => {
+					self.breach = Some(Breach::Fuel);
+					return Err("Fuel exhausted".into());
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	fn note_heap_exhausted(&mut self) {
+		self.breach = Some(Breach::Memory);
+	}
+
+	/// Turn an error of the executor into the `InvalidCandidate` describing it, taking the limit
+	/// exceeded by the execution into account.
+	pub(super) fn invalid_candidate(&self, err: String) -> InvalidCandidate {
+		match self.breach {
+			Some(Breach::Memory) => InvalidCandidate::MemoryLimitExceeded,
+			Some(Breach::Stack) => InvalidCandidate::StackLimitExceeded,
+			Some(Breach::Fuel) => InvalidCandidate::FuelExhausted,
+			None => err.into(),
+		}
+	}
+}
+
+sp_externalities::decl_extension! {
+	/// The extension giving the host functions access to the fuel meter of the execution.
+	pub(super) struct FuelMeterExt(FuelMeter);
+}
+
+impl FuelMeterExt {
+	pub(super) fn new(meter: FuelMeter) -> Self {
+		FuelMeterExt(meter)
+	}
+}
+
+/// The `ext_pvf_charge_fuel` host function.
+struct ChargeFuel;
+
+impl Function for ChargeFuel {
+	fn name(&self) -> &str {
+		CHARGE_FUEL
+	}
+
+	fn signature(&self) -> Signature {
+		Signature::new(&[ValueType::I32][..], None)
+	}
+
+	fn execute(
+		&self,
+		_: &mut dyn FunctionContext,
+		args: &mut dyn Iterator<Item = Value>,
+	) -> sp_wasm_interface::Result<Option<Value>> {
+		use sp_externalities::ExternalitiesExt;
+
+		let amount = match args.next() {
+			Some(Value::I32(amount)) => amount,
+			_ => return Err(format!("{} expects a single i32 argument", CHARGE_FUEL)),
+		};
+
+		sp_externalities::with_externalities(|ext| match ext.extension::<FuelMeterExt>() {
+			Some(meter) => meter.charge(amount),
+			None => Err("No fuel meter registered".into()),
+		})
+			.unwrap_or_else(|| Err("No externalities available".into()))
+			.map(|()| None)
+	}
+}
+
+/// The `ext_pvf_malloc` host function.
+struct Malloc;
+
+impl Function for Malloc {
+	fn name(&self) -> &str {
+		MALLOC
+	}
+
+	fn signature(&self) -> Signature {
+		Signature::new(&[ValueType::I32][..], Some(ValueType::I32))
+	}
+
+	fn execute(
+		&self,
+		context: &mut dyn FunctionContext,
+		args: &mut dyn Iterator<Item = Value>,
+	) -> sp_wasm_interface::Result<Option<Value>> {
+		use sp_externalities::ExternalitiesExt;
+
+		let size = match args.next() {
+			Some(Value::I32(size)) => size as u32,
+			_ => return Err(format!("{} expects a single i32 argument", MALLOC)),
+		};
+
+		match context.allocate_memory(size) {
+			Ok(ptr) => Ok(Some(Value::I32(u32::from(ptr) as i32))),
+			Err(e) => {
+				sp_externalities::with_externalities(|ext| {
+					if let Some(meter) = ext.extension::<FuelMeterExt>() {
+						meter.note_heap_exhausted();
+					}
+				});
+
+				Err(format!("Failed to allocate memory: {}", e))
+			}
+		}
+	}
+}
+
+static CHARGE_FUEL_FUNCTION: ChargeFuel = ChargeFuel;
+static MALLOC_FUNCTION: Malloc = Malloc;
+
+/// The host functions enforcing the limits.
+pub(super) fn host_functions() -> Vec<&'static dyn Function> {
+	vec![&CHARGE_FUEL_FUNCTION, &MALLOC_FUNCTION]
+}
+
+/// Instrument validation code to enforce the given limits during execution.
+pub(super) fn instrument(validation_code: &[u8], limits: &ExecutionLimits) -> Result<Vec<u8>, InvalidCandidate> {
+	let module: Module = elements::deserialize_buffer(validation_code)
+		.map_err(|e| InvalidCandidate::InvalidCode(format!("Failed to decode the code: {}", e)))?;
+
+	let mut module = limit_memory(module, limits.max_memory_pages)?;
+	rename_function_import(&mut module, ALLOCATOR_MALLOC, MALLOC);
+
+	let mut module = pwasm_utils::inject_gas_counter(module, &rules::Set::default())
+		.map_err(|_| InvalidCandidate::InvalidCode("Failed to inject the fuel meter".into()))?;
+	let charge_fuel_index = rename_function_import(&mut module, "gas", CHARGE_FUEL)
+		.ok_or_else(|| InvalidCandidate::InvalidCode("The fuel meter import is missing".into()))?;
+
+	// `inject_limiter` appends the global keeping the stack height to the global section and
+	// refers to it by its position within the section.
+	let stack_height_global = module.global_section().map_or(0, |globals| globals.entries().len() as u32);
+	let module = stack_height::inject_limiter(module, limits.max_stack_height)
+		.map_err(|e| InvalidCandidate::InvalidCode(format!("Failed to inject the stack limiter: {:?}", e)))?;
+	let module = report_stack_limit(module, stack_height_global, limits.max_stack_height, charge_fuel_index)?;
+
+	elements::serialize(module)
+		.map_err(|e| InvalidCandidate::InvalidCode(format!("Failed to encode the code: {}", e)))
+}
+
+/// Lower the maximum of the linear memory to the limit, whether the memory is imported or
+/// defined by the module.
+fn limit_memory(mut module: Module, max_memory_pages: u32) -> Result<Module, InvalidCandidate> {
+	let limit = |memory: &MemoryType| -> Result<MemoryType, InvalidCandidate> {
+		let initial = memory.limits().initial();
+		if initial.saturating_add(HEAP_PAGES) > max_memory_pages {
+			return Err(InvalidCandidate::MemoryLimitExceeded);
+		}
+
+		let maximum = memory.limits().maximum().map_or(max_memory_pages, |max| max.min(max_memory_pages));
+		Ok(MemoryType::new(initial, Some(maximum)))
+	};
+
+	if let Some(imports) = module.import_section_mut() {
+		for entry in imports.entries_mut() {
+			if let External::Memory(ref memory) = *entry.external() {
+				let memory = limit(memory)?;
+				*entry = ImportEntry::new(
+					entry.module().into(),
+					entry.field().into(),
+					External::Memory(memory),
+				);
+			}
+		}
+	}
+
+	if let Some(memories) = module.memory_section_mut() {
+		for memory in memories.entries_mut() {
+			*memory = limit(memory)?;
+		}
+	}
+
+	Ok(module)
+}
+
+/// Rename the function imported from `env` with the given name, returning the function index of
+/// the import, if any.
+fn rename_function_import(module: &mut Module, field: &str, new_field: &str) -> Option<u32> {
+	let imports = module.import_section_mut()?;
+
+	let mut function_index = 0;
+	for entry in imports.entries_mut() {
+		let type_index = match *entry.external() {
+			External::Function(type_index) => type_index,
+			_ => continue,
+		};
+
+		if entry.module() == "env" && entry.field() == field {
+			*entry = ImportEntry::new("env".into(), new_field.into(), External::Function(type_index));
+			return Some(function_index);
+		}
+
+		function_index += 1;
+	}
+
+	None
+}
+
+/// The stack limiter traps with `unreachable` once the stack limit is exceeded. Report the breach
+/// to the host right before, so that it can be told apart from other traps.
+///
+/// The stack height is kept in the global with the given index, which the limiter adds to the
+/// module, so the validation code itself never writes it. Every write of the limiter is followed
+/// by a check reporting the breach, ahead of the limiter's own check.
+fn report_stack_limit(
+	mut module: Module,
+	stack_height_global: u32,
+	max_stack_height: u32,
+	charge_fuel_index: u32,
+) -> Result<Module, InvalidCandidate> {
+	let is_stack_height = module.global_section()
+		.and_then(|globals| globals.entries().get(stack_height_global as usize))
+		.map_or(false, |global| {
+			let global_type = global.global_type();
+			global_type.is_mutable() && global_type.content_type() == elements::ValueType::I32
+		});
+	if !is_stack_height {
+		return Err(InvalidCandidate::InvalidCode("The stack height global is missing".into()));
+	}
+
+	let bodies = match module.code_section_mut() {
+		Some(code) => code.bodies_mut(),
+		None => return Ok(module),
+	};
+
+	for body in bodies {
+		let instructions = body.code_mut().elements_mut();
+		let mut instrumented = Vec::with_capacity(instructions.len());
+
+		for instruction in instructions.drain(..) {
+			let sets_stack_height = matches!(
+				instruction,
+				Instruction::SetGlobal(index) if index == stack_height_global
+			);
+
+			instrumented.push(instruction);
+
+			if sets_stack_height {
+				instrumented.extend_from_slice(&[
+					Instruction::GetGlobal(stack_height_global),
+					Instruction::I32Const(max_stack_height as i32),
+					Instruction::I32GtU,
+					Instruction::If(elements::BlockType::NoResult),
+					Instruction::I32Const(STACK_LIMIT_EXCEEDED),
+					Instruction::Call(charge_fuel_index),
+					Instruction::End,
+				]);
+			}
+		}
+
+		*instructions = instrumented;
+	}
+
+	Ok(module)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn limits(max_fuel: Option<u64>) -> ExecutionLimits {
+		ExecutionLimits { max_fuel, ..Default::default() }
+	}
+
+	#[test]
+	fn fuel_is_only_limited_with_a_budget() {
+		let mut meter = FuelMeter::new(&limits(None));
+		assert!(meter.charge(i32::max_value()).is_ok());
+		assert!(meter.charge(i32::max_value()).is_ok());
+		assert_eq!(meter.breach, None);
+
+		let mut meter = FuelMeter::new(&limits(Some(10)));
+		assert!(meter.charge(6).is_ok());
+		assert!(meter.charge(4).is_ok());
+		assert!(meter.charge(1).is_err());
+		assert_eq!(meter.breach, Some(Breach::Fuel));
+	}
+
+	#[test]
+	fn stack_limit_breaches_are_recorded() {
+		let mut meter = FuelMeter::new(&limits(None));
+		assert!(meter.charge(STACK_LIMIT_EXCEEDED).is_err());
+		assert_eq!(meter.breach, Some(Breach::Stack));
+	}
+
+	#[test]
+	fn heap_exhaustion_is_recorded() {
+		let mut meter = FuelMeter::new(&limits(None));
+		meter.note_heap_exhausted();
+		match meter.invalid_candidate("trap".into()) {
+			InvalidCandidate::MemoryLimitExceeded => {},
+			r => panic!("unexpected result: {:?}", r),
+		}
+	}
+
+	#[test]
+	fn allocations_are_recorded_by_the_host() {
+		let module = parity_wasm::builder::module()
+			.memory().with_min(16).build()
+			.import()
+				.module("env")
+				.field(ALLOCATOR_MALLOC)
+				.external().func(0)
+				.build()
+			.function()
+				.signature().param().i32().return_type().i32().build()
+				.body().with_instructions(elements::Instructions::new(vec![
+					Instruction::GetLocal(0),
+					Instruction::Call(0),
+					Instruction::End,
+				])).build()
+				.build()
+			.build();
+
+		let limits = ExecutionLimits { max_memory_pages: HEAP_PAGES + 16, ..Default::default() };
+		let instrumented: Module = elements::deserialize_buffer(
+			&instrument(&elements::serialize(module).unwrap(), &limits).unwrap()
+		).unwrap();
+
+		let imports: Vec<_> = instrumented.import_section().unwrap().entries().iter()
+			.map(|entry| entry.field().to_owned())
+			.collect();
+		assert!(imports.contains(&MALLOC.to_owned()));
+		assert!(!imports.contains(&ALLOCATOR_MALLOC.to_owned()));
+	}
+
+	#[test]
+	fn every_write_of_the_stack_height_is_checked() {
+		let module = parity_wasm::builder::module()
+			.global().value_type().i32().mutable().init_expr(Instruction::I32Const(0)).build()
+			.function()
+				.signature().build()
+				.body().with_instructions(elements::Instructions::new(vec![
+					// writes the global of the validation code, which must not be checked.
+					Instruction::I32Const(1),
+					Instruction::SetGlobal(0),
+					Instruction::End,
+				])).build()
+				.build()
+			.function()
+				.signature().build()
+				.body().with_instructions(elements::Instructions::new(vec![
+					Instruction::Call(0),
+					Instruction::End,
+				])).build()
+				.build()
+			.build();
+
+		let limits = ExecutionLimits { max_memory_pages: HEAP_PAGES, ..Default::default() };
+		let instrumented: Module = elements::deserialize_buffer(
+			&instrument(&elements::serialize(module).unwrap(), &limits).unwrap()
+		).unwrap();
+
+		let report = [
+			Instruction::GetGlobal(1),
+			Instruction::I32Const(limits.max_stack_height as i32),
+			Instruction::I32GtU,
+			Instruction::If(elements::BlockType::NoResult),
+			Instruction::I32Const(STACK_LIMIT_EXCEEDED),
+			Instruction::Call(0),
+			Instruction::End,
+		];
+
+		let mut writes = 0;
+		for body in instrumented.code_section().unwrap().bodies() {
+			let instructions = body.code().elements();
+			for (i, instruction) in instructions.iter().enumerate() {
+				match instruction {
+					Instruction::SetGlobal(1) => {
+						writes += 1;
+						assert_eq!(&instructions[i + 1..i + 1 + report.len()], &report[..]);
+					}
+					Instruction::SetGlobal(0) => {
+						assert_ne!(instructions.get(i + 1), Some(&report[0]));
+					}
+					_ => {}
+				}
+			}
+		}
+
+		// the increment and decrement of the stack height around the call.
+		assert_eq!(writes, 2);
+	}
+
+	#[test]
+	fn memory_beyond_the_limit_is_rejected() {
+		let memory = |initial| {
+			let module = parity_wasm::builder::module()
+				.memory().with_min(initial).build()
+				.build();
+			elements::serialize(module).unwrap()
+		};
+
+		let limits = ExecutionLimits { max_memory_pages: HEAP_PAGES + 16, ..Default::default() };
+
+		let instrumented: Module = elements::deserialize_buffer(
+			&instrument(&memory(16), &limits).unwrap()
+		).unwrap();
+		let memory_limits = instrumented.memory_section().unwrap().entries()[0].limits();
+		assert_eq!(memory_limits.maximum(), Some(HEAP_PAGES + 16));
+
+		match instrument(&memory(17), &limits) {
+			Err(InvalidCandidate::MemoryLimitExceeded) => {},
+			r => panic!("unexpected result: {:?}", r),
+		}
+	}
+}
@@ -20,16 +20,26 @@
 //! Assuming the parameters are correct, this module provides a wrapper around
 //! a WASM VM for re-execution of a parachain candidate.
 
-use std::{any::{TypeId, Any}, path::PathBuf};
-use crate::primitives::{ValidationParams, ValidationResult};
+use std::{any::{TypeId, Any}, fmt, fs, panic::AssertUnwindSafe, path::{Path, PathBuf}, sync::Arc, time::Duration};
+use crate::primitives::{ExecutionLimits, ValidationParams, ValidationResult};
 use codec::{Decode, Encode};
-use sp_core::{storage::{ChildInfo, TrackedStorageKey}, traits::{CallInWasm, SpawnNamed}};
+use parking_lot::Mutex;
+use polkadot_core_primitives::Hash;
+use sc_executor_common::{runtime_blob::RuntimeBlob, wasm_runtime::WasmModule};
+use sc_executor_wasmtime::{Config as WasmtimeConfig, Semantics};
+use sp_core::{storage::{ChildInfo, TrackedStorageKey}, traits::SpawnNamed};
 use sp_externalities::Extensions;
-use sp_wasm_interface::HostFunctions as _;
+use sp_wasm_interface::{Function, HostFunctions as _};
 
-#[cfg(not(any(target_os = "android", target_os = "unknown")))]
-pub use validation_host::{run_worker, ValidationPool, EXECUTION_TIMEOUT_SEC, WORKER_ARGS};
+#[cfg(all(unix, not(target_os = "android")))]
+pub use validation_host::{run_worker, run_worker_with, ValidationPool, WORKER_ARGS};
 
+pub use artifacts::{Artifact, ArtifactCache};
+
+mod artifacts;
+mod limits;
+#[cfg(all(unix, not(target_os = "android")))]
+mod sandbox;
 mod validation_host;
 
 // maximum memory in bytes
@@ -38,44 +48,61 @@ const MAX_CODE_MEM: usize = 16 * 1024 * 1024; // 16 MiB
 const MAX_VALIDATION_RESULT_HEADER_MEM: usize = MAX_CODE_MEM + 1024; // 16.001 MiB
 
 /// A stub validation-pool defined when compiling for Android or WASM.
-#[cfg(any(target_os = "android", target_os = "unknown"))]
+#[cfg(any(not(unix), target_os = "android"))]
 #[derive(Clone)]
 pub struct ValidationPool {
 	_inner: (), // private field means not publicly-instantiable
 }
 
-#[cfg(any(target_os = "android", target_os = "unknown"))]
+#[cfg(any(not(unix), target_os = "android"))]
 impl ValidationPool {
-	/// Create a new `ValidationPool`.
-	pub fn new() -> Self {
+	/// Create a new `ValidationPool` using the given artifact cache.
+	pub fn with_artifact_cache(_: ArtifactCache) -> Self {
 		ValidationPool { _inner: () }
 	}
+
+	/// Create a new `ValidationPool` using the given artifact cache and number of workers.
+	pub fn with_num_workers(artifact_cache: ArtifactCache, _: usize) -> Self {
+		Self::with_artifact_cache(artifact_cache)
+	}
 }
 
 /// A stub function defined when compiling for Android or WASM.
-#[cfg(any(target_os = "android", target_os = "unknown"))]
+#[cfg(any(not(unix), target_os = "android"))]
 pub fn run_worker(_: &str) -> Result<(), String> {
 	Err("Cannot run validation worker on this platform".to_string())
 }
 
+/// A stub function defined when compiling for Android or WASM.
+#[cfg(any(not(unix), target_os = "android"))]
+#[doc(hidden)]
+pub fn run_worker_with(_: &str, _: impl FnOnce()) -> Result<(), String> {
+	run_worker("")
+}
+
 /// The execution mode for the `ValidationPool`.
 #[derive(Clone)]
-#[cfg_attr(not(any(target_os = "android", target_os = "unknown")), derive(Debug))]
+#[cfg_attr(all(unix, not(target_os = "android")), derive(Debug))]
 pub enum ExecutionMode {
 	/// The validation worker is ran in a thread inside the same process.
-	InProcess,
+	InProcess {
+		/// The artifact cache candidates are executed from.
+		artifact_cache: ArtifactCache,
+		/// The executor keeping the modules loaded from the artifacts.
+		executor: ValidationExecutor,
+	},
 	/// The validation worker is ran using the process' executable and the subcommand `validation-worker` is passed
-	/// following by the address of the shared memory.
+	/// followed by the file descriptor of the socket it inherits.
 	ExternalProcessSelfHost(ValidationPool),
-	/// The validation worker is ran using the command provided and the argument provided. The address of the shared
-	/// memory is added at the end of the arguments.
+	/// The validation worker is ran using the command provided and the argument provided. The file descriptor of the
+	/// socket the worker inherits is added at the end of the arguments.
 	ExternalProcessCustomHost {
 		/// Validation pool.
 		pool: ValidationPool,
 		/// Path to the validation worker. The file must exists and be executable.
 		binary: PathBuf,
-		/// List of arguments passed to the validation worker. The address of the shared memory will be automatically
-		/// added after the arguments.
+		/// List of arguments passed to the validation worker. The file descriptor of the socket the worker inherits
+		/// will be automatically added after the arguments.
 		args: Vec<String>,
 	},
 }
@@ -110,6 +137,19 @@ pub enum InvalidCandidate {
 	Timeout,
 	#[display(fmt = "External WASM execution error: {}", _0)]
 	ExternalWasmExecutor(String),
+	/// The validation code could not be prepared for execution.
+	#[display(fmt = "Invalid validation code: {}", _0)]
+	#[from(ignore)]
+	InvalidCode(String),
+	/// Execution exceeded the memory limit.
+	#[display(fmt = "Memory limit exceeded.")]
+	MemoryLimitExceeded,
+	/// Execution exceeded the stack limit.
+	#[display(fmt = "Stack limit exceeded.")]
+	StackLimitExceeded,
+	/// Execution ran out of fuel.
+	#[display(fmt = "Fuel exhausted.")]
+	FuelExhausted,
 }
 
 /// Host error during candidate validation. This does not indicate an invalid candidate.
@@ -119,11 +159,11 @@ pub enum InternalError {
 	Io(std::io::Error),
 	#[display(fmt = "System error: {}", _0)]
 	System(Box<dyn std::error::Error + Send>),
-	#[display(fmt = "Shared memory error: {}", _0)]
-	#[cfg(not(any(target_os = "android", target_os = "unknown")))]
-	SharedMem(shared_memory::SharedMemError),
 	#[display(fmt = "WASM worker error: {}", _0)]
 	WasmWorker(String),
+	/// The sandbox of the validation worker killed it for attempting a disallowed system call.
+	#[display(fmt = "WASM worker attempted a disallowed system call")]
+	DisallowedSyscall,
 }
 
 impl std::error::Error for ValidationError {
@@ -131,37 +171,40 @@ impl std::error::Error for ValidationError {
 		match self {
 			ValidationError::Internal(InternalError::Io(ref err)) => Some(err),
 			ValidationError::Internal(InternalError::System(ref err)) => Some(&**err),
-			#[cfg(not(any(target_os = "android", target_os = "unknown")))]
-			ValidationError::Internal(InternalError::SharedMem(ref err)) => Some(err),
 			ValidationError::InvalidCandidate(InvalidCandidate::WasmExecutor(ref err)) => Some(err),
 			_ => None,
 		}
 	}
 }
 
-/// Validate a candidate under the given validation code.
+/// Validate a candidate under the given validation code, within the given limits.
+///
+/// Execution is aborted once the timeout elapses. The timeout is only enforced when executing
+/// in an external process.
 ///
 /// This will fail if the validation code is not a proper parachain validation module.
 pub fn validate_candidate(
 	validation_code: &[u8],
 	params: ValidationParams,
+	limits: &ExecutionLimits,
+	timeout: Duration,
 	execution_mode: &ExecutionMode,
 	spawner: impl SpawnNamed + 'static,
 ) -> Result<ValidationResult, ValidationError> {
 	match execution_mode {
-		ExecutionMode::InProcess => {
-			validate_candidate_internal(validation_code, &params.encode(), spawner)
+		ExecutionMode::InProcess { artifact_cache, executor } => {
+			validate_candidate_internal(artifact_cache, executor, validation_code, &params.encode(), limits, spawner)
 		},
-		#[cfg(not(any(target_os = "android", target_os = "unknown")))]
+		#[cfg(all(unix, not(target_os = "android")))]
 		ExecutionMode::ExternalProcessSelfHost(pool) => {
-			pool.validate_candidate(validation_code, params)
+			pool.validate_candidate(validation_code, params, limits, timeout)
 		},
-		#[cfg(not(any(target_os = "android", target_os = "unknown")))]
+		#[cfg(all(unix, not(target_os = "android")))]
 		ExecutionMode::ExternalProcessCustomHost { pool, binary, args } => {
 			let args: Vec<&str> = args.iter().map(|x| x.as_str()).collect();
-			pool.validate_candidate_custom(validation_code, params, binary, &args)
+			pool.validate_candidate_custom(validation_code, params, limits, timeout, binary, &args)
 		},
-		#[cfg(any(target_os = "android", target_os = "unknown"))]
+		#[cfg(any(not(unix), target_os = "android"))]
 		ExecutionMode::ExternalProcessSelfHost(_) | ExecutionMode::ExternalProcessCustomHost { .. } =>
 			Err(ValidationError::Internal(InternalError::System(
 				Box::<dyn std::error::Error + Send + Sync>::from(
@@ -174,39 +217,179 @@ pub fn validate_candidate(
 /// The host functions provided by the wasm executor to the parachain wasm blob.
 type HostFunctions = sp_io::SubstrateHostFunctions;
 
-/// Validate a candidate under the given validation code.
+/// The number of modules an executor keeps loaded.
+const MAX_LOADED_MODULES: usize = 8;
+
+fn host_functions() -> Vec<&'static dyn Function> {
+	let mut host_functions = HostFunctions::host_functions();
+	host_functions.extend(limits::host_functions());
+	host_functions
+}
+
+/// The semantics validation code is compiled with. The limits are enforced by instrumenting the
+/// code, so only the ones keeping execution deterministic are set here.
+fn semantics() -> Semantics {
+	Semantics {
+		fast_instance_reuse: false,
+		deterministic_stack_limit: None,
+		canonicalize_nans: true,
+	}
+}
+
+/// A wasm executor for the validation of candidates.
+///
+/// The executor loads the modules compiled into artifacts from disk the first time it executes a
+/// candidate under them, and keeps the most recently used ones loaded, keyed by the id of their
+/// artifact. Cloning the executor shares the loaded modules.
+#[derive(Clone)]
+pub struct ValidationExecutor {
+	/// Executes the calls of validation code into other code, through `CallInWasmExt`.
+	call_in_wasm: sc_executor::WasmExecutor,
+	/// The loaded modules, the most recently used first.
+	modules: Arc<Mutex<Vec<(Hash, Arc<dyn WasmModule>)>>>,
+}
+
+impl fmt::Debug for ValidationExecutor {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("ValidationExecutor")
+			.field("modules", &self.modules.lock().iter().map(|(id, _)| *id).collect::<Vec<_>>())
+			.finish()
+	}
+}
+
+impl ValidationExecutor {
+	/// Create a new executor without any loaded modules.
+	pub fn new() -> Self {
+		ValidationExecutor {
+			call_in_wasm: sc_executor::WasmExecutor::new(
+				sc_executor::WasmExecutionMethod::Interpreted,
+				Some(limits::HEAP_PAGES as u64),
+				host_functions(),
+				8
+			),
+			modules: Arc::new(Mutex::new(Vec::new())),
+		}
+	}
+
+	/// Get the module compiled into the artifact with the given id and path, loading it if it is
+	/// not loaded yet.
+	fn module(
+		&self,
+		artifact_id: Hash,
+		artifact_path: &Path,
+		limits: &ExecutionLimits,
+	) -> Result<Arc<dyn WasmModule>, ValidationError> {
+		{
+			let mut modules = self.modules.lock();
+			if let Some(index) = modules.iter().position(|(id, _)| *id == artifact_id) {
+				let entry = modules.remove(index);
+				let module = entry.1.clone();
+				modules.insert(0, entry);
+				return Ok(module);
+			}
+		}
+
+		let compiled = fs::read(artifact_path).map_err(InternalError::from)?;
+		let config = WasmtimeConfig {
+			heap_pages: limits::HEAP_PAGES,
+			max_memory_pages: Some(limits.max_memory_pages),
+			allow_missing_func_imports: true,
+			cache_path: None,
+			semantics: semantics(),
+		};
+		// artifacts are only ever written by `compile_validation_code`, into a directory private
+		// to the node, which purges it when it starts. So the artifact was compiled by this very
+		// version of the executor.
+		let module: Arc<dyn WasmModule> = unsafe {
+			sc_executor_wasmtime::create_runtime_from_artifact(&compiled, config, host_functions())
+		}
+			.map(Arc::new)
+			.map_err(|e| InternalError::WasmWorker(format!("Failed to load artifact {:?}: {:?}", artifact_path, e)))?;
+
+		let mut modules = self.modules.lock();
+		modules.insert(0, (artifact_id, module.clone()));
+		modules.truncate(MAX_LOADED_MODULES);
+
+		Ok(module)
+	}
+
+	/// Validate a candidate under the artifact with the given id and path, which was prepared for
+	/// the given limits.
+	///
+	/// This will fail if the validation code is not a proper parachain validation module.
+	pub fn validate_candidate(
+		&self,
+		artifact_id: Hash,
+		artifact_path: &Path,
+		encoded_call_data: &[u8],
+		limits: &ExecutionLimits,
+		spawner: impl SpawnNamed + 'static,
+	) -> Result<ValidationResult, ValidationError> {
+		let module = self.module(artifact_id, artifact_path, limits)?;
+
+		let mut extensions = Extensions::new();
+		extensions.register(sp_core::traits::TaskExecutorExt::new(spawner));
+		extensions.register(sp_core::traits::CallInWasmExt::new(self.call_in_wasm.clone()));
+		extensions.register(limits::FuelMeterExt::new(limits::FuelMeter::new(limits)));
+
+		let mut ext = ValidationExternalities(extensions);
+
+		let res = sc_executor::with_externalities_safe(&mut ext, AssertUnwindSafe(|| {
+			module.new_instance()
+				.and_then(|instance| instance.call_export("validate_block", encoded_call_data))
+		}))
+			.and_then(|res| res)
+			.map_err(|e| {
+				let invalid = ext.0.get_mut(TypeId::of::<limits::FuelMeterExt>())
+					.and_then(|meter| meter.downcast_mut::<limits::FuelMeterExt>())
+					.map(|meter| meter.invalid_candidate(e.to_string()))
+					.expect("the fuel meter was registered above; qed");
+
+				ValidationError::InvalidCandidate(invalid)
+			})?;
+
+		ValidationResult::decode(&mut &res[..])
+			.map_err(|_| ValidationError::InvalidCandidate(InvalidCandidate::BadReturn).into())
+	}
+}
+
+/// Instrument the given validation code to enforce the given limits.
+pub fn prepare_validation_code(
+	validation_code: &[u8],
+	limits: &ExecutionLimits,
+) -> Result<Vec<u8>, ValidationError> {
+	if validation_code.len() > MAX_CODE_MEM {
+		return Err(ValidationError::InvalidCandidate(InvalidCandidate::CodeTooLarge(validation_code.len())));
+	}
+
+	limits::instrument(validation_code, limits).map_err(ValidationError::InvalidCandidate)
+}
+
+/// Compile validation code instrumented by [`prepare_validation_code`] into the artifact the
+/// executor loads it from.
+pub fn compile_validation_code(instrumented_code: &[u8]) -> Result<Vec<u8>, ValidationError> {
+	let invalid_code = |e| ValidationError::InvalidCandidate(
+		InvalidCandidate::InvalidCode(format!("Failed to compile the code: {:?}", e)),
+	);
+
+	let blob = RuntimeBlob::new(instrumented_code).map_err(invalid_code)?;
+	sc_executor_wasmtime::prepare_runtime_artifact(blob, &semantics()).map_err(invalid_code)
+}
+
+/// Validate a candidate under the given validation code, within the given limits, executing it
+/// from its artifact in the given cache.
 ///
 /// This will fail if the validation code is not a proper parachain validation module.
 pub fn validate_candidate_internal(
+	artifact_cache: &ArtifactCache,
+	executor: &ValidationExecutor,
 	validation_code: &[u8],
 	encoded_call_data: &[u8],
+	limits: &ExecutionLimits,
 	spawner: impl SpawnNamed + 'static,
 ) -> Result<ValidationResult, ValidationError> {
-	let executor = sc_executor::WasmExecutor::new(
-		sc_executor::WasmExecutionMethod::Interpreted,
-		// TODO: Make sure we don't use more than 1GB: https://github.com/paritytech/polkadot/issues/699
-		Some(1024),
-		HostFunctions::host_functions(),
-		8
-	);
-
-	let mut extensions = Extensions::new();
-	extensions.register(sp_core::traits::TaskExecutorExt::new(spawner));
-	extensions.register(sp_core::traits::CallInWasmExt::new(executor.clone()));
-
-	let mut ext = ValidationExternalities(extensions);
-
-	let res = executor.call_in_wasm(
-		validation_code,
-		None,
-		"validate_block",
-		encoded_call_data,
-		&mut ext,
-		sp_core::traits::MissingHostFunctions::Allow,
-	).map_err(|e| ValidationError::InvalidCandidate(e.into()))?;
-
-	ValidationResult::decode(&mut &res[..])
-		.map_err(|_| ValidationError::InvalidCandidate(InvalidCandidate::BadReturn).into())
+	let (artifact, _) = artifact_cache.get_or_prepare(validation_code, limits)?;
+	executor.validate_candidate(artifact.id(), &artifact.path, encoded_call_data, limits, spawner)
 }
 
 /// The validation externalities that will panic on any storage related access. They just provide
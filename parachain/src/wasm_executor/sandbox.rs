@@ -0,0 +1,795 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sandboxing of validation workers.
+//!
+//! On Linux, a host starts its worker in new user and PID namespaces, so that the worker can
+//! neither see nor signal any other process. Once the worker is connected to its host, it drops
+//! its remaining privileges before it executes any validation code, in two steps:
+//!
+//! 1. [`isolate`] lowers the resource limits of the worker, including the memory it may use for
+//!    the validation code it executes, and moves it into new user, mount, network and IPC
+//!    namespaces. Its root directory is changed to an empty, read-only directory, into which only
+//!    the directory of the artifact cache is mounted, read-only as well. So it sees none of the
+//!    other files of the host, including the keystore, and it can reach neither the network nor
+//!    the IPC objects of the host. This has to happen while the worker is single-threaded.
+//! 2. [`restrict_syscalls`] keeps the worker from starting any more processes or threads and
+//!    installs a seccomp-bpf filter on all of its threads, which only allows the system calls
+//!    needed to receive candidates, load the artifacts they are executed from, execute them, send
+//!    back the results and abort. Files may only be opened for reading. Any other system call
+//!    kills the worker with `SIGSYS`, which the host reports as
+//!    [`InternalError::DisallowedSyscall`](super::InternalError::DisallowedSyscall).
+//!
+//! Before each candidate, [`limit_cpu_time`] bounds the CPU time the worker may use, as a backstop
+//! should the host fail to kill the worker once the timeout of the candidate elapses.
+//!
+//! All of these are best-effort. If the kernel does not support one of them, or the worker is not
+//! permitted to use it, a warning is logged and the worker carries on without it.
+
+use std::{
+	io, path::{Path, PathBuf}, process,
+	os::unix::{io::RawFd, process::{CommandExt, ExitStatusExt}},
+	time::Duration,
+};
+use log::warn;
+#[cfg(target_os = "linux")]
+use log::info;
+
+/// The signal a worker is killed with when it attempts a disallowed system call, if system calls
+/// are filtered on this platform.
+#[cfg(target_os = "linux")]
+pub const DISALLOWED_SYSCALL_SIGNAL: Option<i32> = Some(libc::SIGSYS);
+#[cfg(not(target_os = "linux"))]
+pub const DISALLOWED_SYSCALL_SIGNAL: Option<i32> = None;
+
+/// The exit code of a worker which exceeded its CPU time limit.
+pub const CPU_TIME_EXCEEDED_EXIT_CODE: i32 = 152;
+
+/// A worker process started by [`spawn_worker`].
+#[derive(Debug)]
+pub struct WorkerProcess {
+	pid: libc::pid_t,
+	status: Option<process::ExitStatus>,
+}
+
+impl WorkerProcess {
+	/// The process id of the worker, in the PID namespace of the host.
+	pub fn id(&self) -> u32 {
+		self.pid as u32
+	}
+
+	/// The exit status of the worker, if it exited.
+	pub fn try_wait(&mut self) -> io::Result<Option<process::ExitStatus>> {
+		if self.status.is_none() {
+			let mut status = 0;
+			match unsafe { libc::waitpid(self.pid, &mut status, libc::WNOHANG) } {
+				0 => {},
+				-1 => return Err(io::Error::last_os_error()),
+				_ => self.status = Some(process::ExitStatus::from_raw(status)),
+			}
+		}
+
+		Ok(self.status)
+	}
+
+	/// Wait for the worker to exit.
+	pub fn wait(&mut self) -> io::Result<process::ExitStatus> {
+		while self.status.is_none() {
+			let mut status = 0;
+			if unsafe { libc::waitpid(self.pid, &mut status, 0) } == -1 {
+				let e = io::Error::last_os_error();
+				if e.kind() != io::ErrorKind::Interrupted {
+					return Err(e);
+				}
+			} else {
+				self.status = Some(process::ExitStatus::from_raw(status));
+			}
+		}
+
+		Ok(self.status.expect("the loop only ends once the status is set; qed"))
+	}
+
+	/// Kill the worker, unless it exited already.
+	pub fn kill(&mut self) -> io::Result<()> {
+		// the id of a worker which was waited for may have been reused by another process.
+		if self.status.is_some() {
+			return Ok(());
+		}
+
+		if unsafe { libc::kill(self.pid, libc::SIGKILL) } != 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(())
+	}
+}
+
+/// Start a worker with the given command and arguments, passing it the given socket. The socket
+/// is inherited by the worker, and its file descriptor appended to the arguments.
+///
+/// On Linux, the worker is started in new user and PID namespaces where they are available.
+pub fn spawn_worker(cmd: &Path, args: &[&str], socket_fd: RawFd) -> io::Result<WorkerProcess> {
+	let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+	args.push(socket_fd.to_string());
+
+	#[cfg(target_os = "linux")]
+	match linux::spawn_in_namespaces(cmd, &args, socket_fd) {
+		Ok(pid) => return Ok(WorkerProcess { pid, status: None }),
+		Err(e) => warn!(
+			"User and PID namespaces are not supported by the kernel or not permitted, starting the validation \
+			worker without them: {}",
+			e,
+		),
+	}
+
+	let mut command = process::Command::new(cmd);
+	command.args(&args);
+	// like all sockets of the host, the socket is closed on exec. Only the worker itself inherits
+	// it, so the workers started concurrently by other hosts don't.
+	unsafe {
+		command.pre_exec(move || {
+			if libc::fcntl(socket_fd, libc::F_SETFD, 0) == -1 {
+				return Err(io::Error::last_os_error());
+			}
+			Ok(())
+		});
+	}
+
+	// the worker is waited for by its pid, which the handle of the child doesn't do on drop.
+	let child = command.spawn()?;
+	Ok(WorkerProcess { pid: child.id() as libc::pid_t, status: None })
+}
+
+/// Lower the resource limits of the worker, allowing the validation code it executes the given
+/// number of 64 KiB pages of memory, and move it into new namespaces with an empty root directory
+/// into which only the given artifact directory is mounted.
+///
+/// Returns the path of the artifact directory within the new root directory, if it was changed.
+///
+/// This must be called while the worker is single-threaded, or the namespaces cannot be entered.
+pub fn isolate(max_memory_pages: u32, artifact_dir: &Path) -> PathBuf {
+	#[cfg(target_os = "linux")]
+	{
+		if let Err(e) = linux::set_rlimits(max_memory_pages) {
+			warn!("Failed to lower the resource limits of the validation worker, running without them: {}", e);
+		}
+
+		match linux::unshare_namespaces() {
+			Ok(()) => match linux::change_root(artifact_dir) {
+				Ok(artifact_dir) => {
+					info!(
+						"Validation worker entered new user, mount, network and IPC namespaces with an empty root \
+						directory",
+					);
+					return artifact_dir;
+				}
+				Err(e) => warn!(
+					"Failed to change the root directory of the validation worker, running it with access to the \
+					filesystem: {}",
+					e,
+				),
+			},
+			Err(e) => warn!(
+				"Namespaces are not supported by the kernel or not permitted, running the validation worker \
+				without them: {}",
+				e,
+			),
+		}
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	{
+		let _ = max_memory_pages;
+		warn!("Sandboxing is only supported on Linux, running the validation worker without namespaces and rlimits");
+	}
+
+	artifact_dir.to_path_buf()
+}
+
+/// Keep the worker from starting any more processes or threads, and restrict the system calls of
+/// all of its threads to the ones it needs.
+pub fn restrict_syscalls() {
+	#[cfg(target_os = "linux")]
+	{
+		if let Err(e) = linux::forbid_new_processes() {
+			warn!("Failed to keep the validation worker from starting processes, running without that limit: {}", e);
+		}
+
+		match linux::install_seccomp_filter() {
+			Ok(()) => info!("Validation worker restricted its system calls with seccomp"),
+			Err(e) => warn!(
+				"Seccomp is not supported by the kernel or not permitted, running the validation worker without \
+				system call filtering: {}",
+				e,
+			),
+		}
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	warn!("Sandboxing is only supported on Linux, running the validation worker without system call filtering");
+}
+
+/// Allow the worker the given CPU time from now on. A worker exceeding it exits with
+/// [`CPU_TIME_EXCEEDED_EXIT_CODE`].
+pub fn limit_cpu_time(limit: Duration) {
+	#[cfg(target_os = "linux")]
+	if let Err(e) = linux::limit_cpu_time(limit) {
+		warn!("Failed to limit the CPU time of the validation worker: {}", e);
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	let _ = limit;
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use std::{
+		env, ffi::CString, fs, io, iter, ptr, time::Duration,
+		os::unix::{ffi::OsStrExt, io::RawFd},
+		path::{Path, PathBuf},
+	};
+	use super::super::MAX_RUNTIME_MEM;
+	use super::CPU_TIME_EXCEEDED_EXIT_CODE;
+
+	/// The maximum number of file descriptors the worker may have open.
+	const MAX_OPEN_FILES: libc::rlim_t = 64;
+
+	/// The size of a page of linear memory.
+	const WASM_PAGE_SIZE: libc::rlim_t = 64 * 1024;
+
+	/// The memory a worker needs besides the linear memory of the validation code it executes: a
+	/// request with the largest params allowed while it is decoded, and the executor with the
+	/// modules it keeps.
+	const MEMORY_HEADROOM: libc::rlim_t = 2 * MAX_RUNTIME_MEM as libc::rlim_t + 512 * 1024 * 1024;
+
+	#[cfg(target_env = "gnu")]
+	type Resource = libc::__rlimit_resource_t;
+	#[cfg(not(target_env = "gnu"))]
+	type Resource = libc::c_int;
+
+	/// The name of the directory the old root is moved to while changing the root directory.
+	const OLD_ROOT: &str = "old-root";
+
+	/// The name of the directory the artifact directory is mounted at in the new root directory.
+	const ARTIFACTS: &str = "artifacts";
+
+	fn check(result: libc::c_int) -> io::Result<()> {
+		if result != 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(())
+	}
+
+	fn c_path(path: &Path) -> io::Result<CString> {
+		CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+	}
+
+	/// Start the worker in new user and PID namespaces, returning its pid.
+	///
+	/// The worker is mapped to the user and group of the host in its user namespace, so that it
+	/// can create further namespaces itself.
+	pub fn spawn_in_namespaces(cmd: &Path, args: &[String], socket_fd: RawFd) -> io::Result<libc::pid_t> {
+		// everything the child needs is prepared up front: the child of a multi-threaded process
+		// may only make async-signal-safe calls.
+		let program = c_path(cmd)?;
+		let args = iter::once(Ok(program.clone()))
+			.chain(args.iter().map(|arg| CString::new(arg.as_bytes())))
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+		let argv: Vec<*const libc::c_char> = args.iter()
+			.map(|arg| arg.as_ptr())
+			.chain(iter::once(ptr::null()))
+			.collect();
+		let mut no_signals = unsafe { std::mem::zeroed::<libc::sigset_t>() };
+		check(unsafe { libc::sigemptyset(&mut no_signals) })?;
+
+		// the child reports a failure to execute the worker through this pipe, whose writing end
+		// is closed once the worker is executed.
+		let mut pipe = [0; 2];
+		check(unsafe { libc::pipe2(pipe.as_mut_ptr(), libc::O_CLOEXEC) })?;
+		let (read_end, write_end) = (pipe[0], pipe[1]);
+
+		let flags = libc::CLONE_NEWUSER | libc::CLONE_NEWPID | libc::SIGCHLD;
+		// without a new stack, the child continues on a copy of the stack of the host, like a
+		// forked process.
+		let no_arg: libc::c_ulong = 0;
+		let pid = unsafe { libc::syscall(libc::SYS_clone, flags as libc::c_ulong, no_arg, no_arg, no_arg, no_arg) };
+		if pid == 0 {
+			unsafe {
+				libc::sigprocmask(libc::SIG_SETMASK, &no_signals, ptr::null_mut());
+				if libc::fcntl(socket_fd, libc::F_SETFD, 0) == 0 {
+					libc::execv(program.as_ptr(), argv.as_ptr());
+				}
+
+				let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+				libc::write(write_end, &errno as *const i32 as *const libc::c_void, std::mem::size_of::<i32>());
+				libc::_exit(127);
+			}
+		}
+
+		let spawned = if pid < 0 { Err(io::Error::last_os_error()) } else { Ok(pid as libc::pid_t) };
+		unsafe { libc::close(write_end) };
+
+		let result = spawned.and_then(|pid| {
+			let mut errno = 0i32;
+			let read = unsafe {
+				libc::read(read_end, &mut errno as *mut i32 as *mut libc::c_void, std::mem::size_of::<i32>())
+			};
+
+			// the worker was executed if the pipe was closed without an error written to it.
+			let result = match read {
+				0 => write_id_maps(pid),
+				-1 => Err(io::Error::last_os_error()),
+				_ => Err(io::Error::from_raw_os_error(errno)),
+			};
+
+			result.map(|()| pid).map_err(|e| {
+				unsafe {
+					libc::kill(pid, libc::SIGKILL);
+					libc::waitpid(pid, ptr::null_mut(), 0);
+				}
+				e
+			})
+		});
+
+		unsafe { libc::close(read_end) };
+		result
+	}
+
+	/// Map the user and group of the worker in its user namespace to the ones of the host.
+	fn write_id_maps(pid: libc::pid_t) -> io::Result<()> {
+		let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+		let proc_dir = Path::new("/proc").join(pid.to_string());
+
+		// an unprivileged process may only map the group after giving up on supplementary groups.
+		fs::write(proc_dir.join("setgroups"), "deny")?;
+		fs::write(proc_dir.join("uid_map"), format!("{} {} 1", uid, uid))?;
+		fs::write(proc_dir.join("gid_map"), format!("{} {} 1", gid, gid))
+	}
+
+	/// Lower the given resource limits. Limits can only be lowered by an unprivileged process.
+	fn lower_rlimits(limits: &[(Resource, libc::rlim_t)]) -> io::Result<()> {
+		for (resource, limit) in limits.iter().cloned() {
+			let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+			check(unsafe { libc::getrlimit(resource, &mut current) })?;
+
+			let new = libc::rlimit {
+				rlim_cur: current.rlim_cur.min(limit),
+				rlim_max: current.rlim_max.min(limit),
+			};
+			check(unsafe { libc::setrlimit(resource, &new) })?;
+		}
+
+		Ok(())
+	}
+
+	pub fn set_rlimits(max_memory_pages: u32) -> io::Result<()> {
+		lower_rlimits(&[
+			// no core dumps of workers killed by the seccomp filter and no memory locked.
+			(libc::RLIMIT_CORE, 0),
+			(libc::RLIMIT_MEMLOCK, 0),
+			(libc::RLIMIT_NOFILE, MAX_OPEN_FILES),
+			// the worker only writes to its socket, never to a file.
+			(libc::RLIMIT_FSIZE, 0),
+			// unlike the address space, the data of a process doesn't include memory which is
+			// reserved but not accessible, like the guard pages of linear memory.
+			(libc::RLIMIT_DATA, max_memory_pages as libc::rlim_t * WASM_PAGE_SIZE + MEMORY_HEADROOM),
+		])?;
+
+		// the worker is the init process of its PID namespace, which signals with the default
+		// action don't terminate.
+		let handler = cpu_time_exceeded as extern "C" fn(libc::c_int) as libc::sighandler_t;
+		if unsafe { libc::signal(libc::SIGXCPU, handler) } == libc::SIG_ERR {
+			return Err(io::Error::last_os_error());
+		}
+
+		Ok(())
+	}
+
+	extern "C" fn cpu_time_exceeded(_: libc::c_int) {
+		unsafe { libc::_exit(CPU_TIME_EXCEEDED_EXIT_CODE) }
+	}
+
+	pub fn limit_cpu_time(limit: Duration) -> io::Result<()> {
+		let mut used = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+		check(unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut used) })?;
+
+		let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+		check(unsafe { libc::getrlimit(libc::RLIMIT_CPU, &mut current) })?;
+
+		// the limit is counted in whole seconds of the CPU time of the process so far.
+		let limit = used.tv_sec as libc::rlim_t + 1 + limit.as_secs() + (limit.subsec_nanos() > 0) as libc::rlim_t;
+		let new = libc::rlimit { rlim_cur: limit.min(current.rlim_max), rlim_max: current.rlim_max };
+		check(unsafe { libc::setrlimit(libc::RLIMIT_CPU, &new) })
+	}
+
+	pub fn forbid_new_processes() -> io::Result<()> {
+		// threads count as processes, so neither can be started once the worker's threads are.
+		lower_rlimits(&[(libc::RLIMIT_NPROC, 0)])
+	}
+
+	pub fn unshare_namespaces() -> io::Result<()> {
+		// a new user namespace gives the unprivileged worker the capabilities to create the other
+		// namespaces, without granting it any privileges outside of them.
+		let flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET | libc::CLONE_NEWIPC;
+		check(unsafe { libc::unshare(flags) })
+	}
+
+	/// Change the root directory of the worker to an empty, read-only file system with the given
+	/// artifact directory mounted into it read-only, detaching the file systems of the host from its
+	/// mount namespace. Returns the path of the artifact directory in the new root.
+	pub fn change_root(artifact_dir: &Path) -> io::Result<PathBuf> {
+		let none: *const libc::c_char = ptr::null();
+		let root = CString::new("/").expect("no nul byte in the root path; qed");
+		// the empty root is mounted over the temporary directory, which exists on any system.
+		let new_root = c_path(&env::temp_dir())?;
+		let tmpfs = CString::new("tmpfs").expect("no nul byte in the file system type; qed");
+
+		unsafe {
+			// keep the mounts below from propagating to the mount namespace of the host.
+			check(libc::mount(none, root.as_ptr(), none, libc::MS_REC | libc::MS_PRIVATE, ptr::null()))?;
+		}
+
+		// the artifact directory might be within the temporary directory, which the new root hides.
+		let artifact_dir = c_path(artifact_dir)?;
+		let artifact_dir_fd = unsafe {
+			libc::open(artifact_dir.as_ptr(), libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC)
+		};
+		if artifact_dir_fd == -1 {
+			return Err(io::Error::last_os_error());
+		}
+
+		let result = unsafe {
+			check(libc::mount(
+				tmpfs.as_ptr(),
+				new_root.as_ptr(),
+				tmpfs.as_ptr(),
+				libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+				ptr::null(),
+			))
+		}.and_then(|()| {
+			let result = enter_new_root(&new_root, artifact_dir_fd);
+			if result.is_err() {
+				// reveal the temporary directory again.
+				unsafe {
+					libc::chdir(root.as_ptr());
+					libc::umount2(new_root.as_ptr(), libc::MNT_DETACH);
+				}
+			}
+			result
+		});
+
+		unsafe { libc::close(artifact_dir_fd) };
+		result.map(|()| Path::new("/").join(ARTIFACTS))
+	}
+
+	/// Mount the artifact directory open as the given file descriptor into the new root, make it
+	/// the root directory and detach the old one.
+	fn enter_new_root(new_root: &CString, artifact_dir_fd: RawFd) -> io::Result<()> {
+		let none: *const libc::c_char = ptr::null();
+		let root = CString::new("/").expect("no nul byte in the root path; qed");
+		let dot = CString::new(".").expect("no nul byte in the current directory; qed");
+		let old_root = CString::new(OLD_ROOT).expect("no nul byte in the name of the old root; qed");
+		let artifacts = CString::new(ARTIFACTS).expect("no nul byte in the name of the artifact directory; qed");
+		let artifact_dir = CString::new(format!("/proc/self/fd/{}", artifact_dir_fd))
+			.expect("no nul byte in the path of a file descriptor; qed");
+		let read_only = libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+
+		unsafe {
+			check(libc::chdir(new_root.as_ptr()))?;
+
+			check(libc::mkdir(artifacts.as_ptr(), 0o700))?;
+			check(libc::mount(artifact_dir.as_ptr(), artifacts.as_ptr(), none, libc::MS_BIND, ptr::null()))?;
+			// a bind mount only becomes read-only once it is remounted.
+			check(libc::mount(none, artifacts.as_ptr(), none, libc::MS_REMOUNT | libc::MS_BIND | read_only, ptr::null()))?;
+
+			check(libc::mkdir(old_root.as_ptr(), 0o700))?;
+			if libc::syscall(libc::SYS_pivot_root, dot.as_ptr(), old_root.as_ptr()) != 0 {
+				return Err(io::Error::last_os_error());
+			}
+			check(libc::umount2(old_root.as_ptr(), libc::MNT_DETACH))?;
+			check(libc::rmdir(old_root.as_ptr()))?;
+			check(libc::chdir(root.as_ptr()))?;
+
+			check(libc::mount(none, root.as_ptr(), none, libc::MS_REMOUNT | libc::MS_BIND | read_only, ptr::null()))?;
+		}
+
+		Ok(())
+	}
+
+	// BPF instruction classes and fields, from `linux/bpf_common.h`.
+	const BPF_LD: u16 = 0x00;
+	const BPF_JMP: u16 = 0x05;
+	const BPF_RET: u16 = 0x06;
+	const BPF_W: u16 = 0x00;
+	const BPF_ABS: u16 = 0x20;
+	const BPF_JEQ: u16 = 0x10;
+	const BPF_JGE: u16 = 0x30;
+	const BPF_JSET: u16 = 0x40;
+	const BPF_K: u16 = 0x00;
+
+	// Seccomp filter return values and modes, from `linux/seccomp.h`.
+	const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+	const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+	const SECCOMP_SET_MODE_FILTER: libc::c_ulong = 1;
+	const SECCOMP_FILTER_FLAG_TSYNC: libc::c_ulong = 1;
+
+	// Offsets of the fields of `struct seccomp_data`.
+	const SECCOMP_DATA_NR_OFFSET: u32 = 0;
+	const SECCOMP_DATA_ARCH_OFFSET: u32 = 4;
+	const SECCOMP_DATA_ARGS_OFFSET: u32 = 16;
+
+	/// The flags of `open` and `openat` which let a file be written or created.
+	const WRITE_FLAGS: u32 = (libc::O_WRONLY | libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC | libc::O_APPEND) as u32;
+
+	/// The system calls opening files, along with the index of their argument holding the flags.
+	#[cfg(target_arch = "x86_64")]
+	const OPEN_SYSCALLS: &[(libc::c_long, u32)] = &[(libc::SYS_open, 1), (libc::SYS_openat, 2)];
+	#[cfg(target_arch = "aarch64")]
+	const OPEN_SYSCALLS: &[(libc::c_long, u32)] = &[(libc::SYS_openat, 2)];
+
+	#[cfg(target_arch = "x86_64")]
+	const AUDIT_ARCH: u32 = 0xc000_003e;
+	#[cfg(target_arch = "aarch64")]
+	const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+	/// System calls of the x32 ABI have this bit set on x86_64.
+	#[cfg(target_arch = "x86_64")]
+	const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+	/// The system calls needed to exchange messages with the host, execute candidates on the
+	/// threads of the worker, limit its CPU time and exit. Raising a signal is allowed too, so that
+	/// a panicking or aborting worker is told apart from one attempting a disallowed system call.
+	///
+	/// Neither `clone` nor `clone3` is allowed: no threads or processes can be started once the
+	/// system calls are restricted, so the threads executing candidates are started before.
+	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+	const ALLOWED_SYSCALLS: &[libc::c_long] = &[
+		libc::SYS_read,
+		libc::SYS_write,
+		libc::SYS_readv,
+		libc::SYS_writev,
+		libc::SYS_recvfrom,
+		libc::SYS_sendto,
+		libc::SYS_recvmsg,
+		libc::SYS_sendmsg,
+		libc::SYS_close,
+		libc::SYS_fstat,
+		libc::SYS_statx,
+		libc::SYS_mmap,
+		libc::SYS_munmap,
+		libc::SYS_mremap,
+		libc::SYS_mprotect,
+		libc::SYS_madvise,
+		libc::SYS_brk,
+		libc::SYS_futex,
+		libc::SYS_sched_yield,
+		libc::SYS_sched_getaffinity,
+		libc::SYS_rt_sigreturn,
+		libc::SYS_rt_sigaction,
+		libc::SYS_rt_sigprocmask,
+		libc::SYS_tgkill,
+		libc::SYS_tkill,
+		libc::SYS_sigaltstack,
+		libc::SYS_clock_gettime,
+		libc::SYS_clock_nanosleep,
+		libc::SYS_nanosleep,
+		libc::SYS_getrandom,
+		libc::SYS_getrlimit,
+		libc::SYS_setrlimit,
+		libc::SYS_getpid,
+		libc::SYS_gettid,
+		libc::SYS_exit,
+		libc::SYS_exit_group,
+	];
+
+	/// A BPF instruction, `struct sock_filter` in `linux/filter.h`.
+	#[repr(C)]
+	#[derive(Clone, Copy, Debug, PartialEq)]
+	struct SockFilter {
+		code: u16,
+		jt: u8,
+		jf: u8,
+		k: u32,
+	}
+
+	/// A BPF program, `struct sock_fprog` in `linux/filter.h`.
+	#[repr(C)]
+	struct SockFprog {
+		len: libc::c_ushort,
+		filter: *const SockFilter,
+	}
+
+	fn stmt(code: u16, k: u32) -> SockFilter {
+		SockFilter { code, jt: 0, jf: 0, k }
+	}
+
+	fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+		SockFilter { code, jt, jf, k }
+	}
+
+	/// Build the filter program: system calls of another architecture and all system calls which
+	/// are not allowed are trapped.
+	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+	fn filter() -> Vec<SockFilter> {
+		let mut filter = vec![
+			stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARCH_OFFSET),
+			jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+			stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP),
+			stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_NR_OFFSET),
+		];
+
+		#[cfg(target_arch = "x86_64")]
+		filter.extend_from_slice(&[
+			jump(BPF_JMP | BPF_JGE | BPF_K, X32_SYSCALL_BIT, 0, 1),
+			stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP),
+		]);
+
+		for syscall in ALLOWED_SYSCALLS {
+			filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, *syscall as u32, 0, 1));
+			filter.push(stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+		}
+
+		// artifacts are loaded from files, which may only be opened for reading.
+		for (syscall, flags_arg) in OPEN_SYSCALLS {
+			filter.extend_from_slice(&[
+				jump(BPF_JMP | BPF_JEQ | BPF_K, *syscall as u32, 0, 4),
+				stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARGS_OFFSET + 8 * flags_arg),
+				jump(BPF_JMP | BPF_JSET | BPF_K, WRITE_FLAGS, 1, 0),
+				stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+				stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP),
+			]);
+		}
+
+		// the resource limits are read and changed with `prlimit64`, which is only allowed for the
+		// worker itself, that is with a pid of 0.
+		filter.extend_from_slice(&[
+			jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_prlimit64 as u32, 0, 6),
+			stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARGS_OFFSET),
+			jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 0, 2),
+			stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARGS_OFFSET + 4),
+			jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 1, 0),
+			stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP),
+			stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+		]);
+
+		filter.push(stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP));
+		filter
+	}
+
+	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+	pub fn install_seccomp_filter() -> io::Result<()> {
+		let filter = filter();
+		let program = SockFprog { len: filter.len() as libc::c_ushort, filter: filter.as_ptr() };
+
+		// required to install a filter without `CAP_SYS_ADMIN`, and keeps the worker from gaining
+		// privileges through `execve` of a set-user-ID binary.
+		if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+			return Err(io::Error::last_os_error());
+		}
+
+		// the filter is synchronized to all threads, including the ones of the task executor which
+		// were started before.
+		let result = unsafe {
+			libc::syscall(
+				libc::SYS_seccomp,
+				SECCOMP_SET_MODE_FILTER,
+				SECCOMP_FILTER_FLAG_TSYNC,
+				&program as *const SockFprog,
+			)
+		};
+		if result < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		if result > 0 {
+			return Err(io::Error::new(
+				io::ErrorKind::Other,
+				format!("the filter could not be synchronized to thread {}", result),
+			));
+		}
+
+		Ok(())
+	}
+
+	#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+	pub fn install_seccomp_filter() -> io::Result<()> {
+		Err(io::Error::new(io::ErrorKind::Other, "the system call filter is not available on this architecture"))
+	}
+
+	#[cfg(all(test, any(target_arch = "x86_64", target_arch = "aarch64")))]
+	mod tests {
+		use super::*;
+
+		#[test]
+		fn filter_checks_arch_first_and_traps_by_default() {
+			let filter = filter();
+
+			assert_eq!(filter[0], stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARCH_OFFSET));
+			assert_eq!(filter[1].k, AUDIT_ARCH);
+			assert_eq!(filter[2], stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP));
+			assert_eq!(filter.last(), Some(&stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP)));
+			// the length of a BPF program is limited.
+			assert!(filter.len() <= 4096);
+		}
+
+		#[test]
+		fn allowed_syscalls_are_allowed() {
+			let filter = filter();
+
+			for syscall in ALLOWED_SYSCALLS {
+				let check = filter.iter()
+					.position(|i| i.code == BPF_JMP | BPF_JEQ | BPF_K && i.k == *syscall as u32)
+					.expect("every allowed system call is checked");
+				assert_eq!(filter[check + 1], stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+			}
+
+			assert!(!filter.iter().any(|i| i.k == libc::SYS_socket as u32 && i.code == BPF_JMP | BPF_JEQ | BPF_K));
+		}
+
+		#[test]
+		fn threads_cannot_be_started() {
+			let filter = filter();
+
+			// `clone3` has the same number on all architectures.
+			const SYS_CLONE3: libc::c_long = 435;
+
+			for syscall in &[libc::SYS_clone, SYS_CLONE3] {
+				assert!(!filter.iter().any(|i| i.k == *syscall as u32 && i.code == BPF_JMP | BPF_JEQ | BPF_K));
+			}
+		}
+
+		#[test]
+		fn resource_limits_can_only_be_changed_for_the_worker_itself() {
+			let filter = filter();
+
+			let check = filter.iter()
+				.position(|i| i.code == BPF_JMP | BPF_JEQ | BPF_K && i.k == libc::SYS_prlimit64 as u32)
+				.expect("prlimit64 is checked");
+			assert_eq!(filter[check..check + 7], [
+				jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_prlimit64 as u32, 0, 6),
+				stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARGS_OFFSET),
+				jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 0, 2),
+				stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARGS_OFFSET + 4),
+				jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 1, 0),
+				stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP),
+				stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+			]);
+		}
+
+		#[test]
+		fn files_can_only_be_opened_for_reading() {
+			let filter = filter();
+
+			for (syscall, flags_arg) in OPEN_SYSCALLS {
+				let check = filter.iter()
+					.position(|i| i.code == BPF_JMP | BPF_JEQ | BPF_K && i.k == *syscall as u32)
+					.expect("every system call opening files is checked");
+				assert_eq!(filter[check + 1], stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARGS_OFFSET + 8 * flags_arg));
+				assert_eq!(filter[check + 2], jump(BPF_JMP | BPF_JSET | BPF_K, WRITE_FLAGS, 1, 0));
+				assert_eq!(filter[check + 3], stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+				assert_eq!(filter[check + 4], stmt(BPF_RET | BPF_K, SECCOMP_RET_TRAP));
+			}
+		}
+
+		#[test]
+		fn signals_can_be_raised() {
+			for syscall in &[libc::SYS_rt_sigaction, libc::SYS_rt_sigprocmask, libc::SYS_tgkill, libc::SYS_getpid] {
+				assert!(ALLOWED_SYSCALLS.contains(syscall));
+			}
+		}
+	}
+}
@@ -14,18 +14,49 @@
 // You should have received a copy of the GNU General Public License
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
-#![cfg(not(any(target_os = "android", target_os = "unknown")))]
-
-use std::{process, env, sync::Arc, sync::atomic, path::PathBuf};
+//! Out-of-process validation of candidates.
+//!
+//! Each host of the pool owns a worker process, which it talks to through a pair of connected Unix
+//! sockets. The worker inherits its end of the pair when it is started and is told the file
+//! descriptor on the command line, so the socket has no name any other process could connect to.
+//! Both sides then exchange the version of the protocol they speak, and the host tells the worker
+//! the directory of the artifact cache and how much memory the validation code it executes may
+//! use, so that the worker can [sandbox](super::sandbox) itself accordingly. After that, the host
+//! sends one validation request at a time and waits for its response. All messages are
+//! SCALE-encoded and prefixed with their length.
+//!
+//! A request only names the artifact the candidate is validated under. The worker, whose sandbox
+//! only lets it read the directory of the artifact cache, loads the compiled module from the
+//! artifact the first time it is named and keeps it for the following candidates.
+//!
+//! A worker which crashes or does not respond in time is killed and started again the next time
+//! its host is used, after a delay which grows with the number of consecutive failures. Candidates
+//! are meanwhile validated by the other idle hosts; if the workers of all of them failed, waiting
+//! for one to be started again counts towards the timeout of the candidate.
+
+#![cfg(all(unix, not(target_os = "android")))]
+
+use std::{
+	env, io::{self, Read, Write}, process, sync::Arc,
+	ffi::OsStr,
+	os::unix::{
+		ffi::OsStrExt,
+		io::{AsRawFd, FromRawFd, RawFd},
+		net::UnixStream,
+		process::ExitStatusExt,
+	},
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
 use codec::{Decode, Encode};
-use crate::primitives::{ValidationParams, ValidationResult};
+use polkadot_core_primitives::Hash;
+use crate::primitives::{ExecutionLimits, ValidationParams, ValidationResult};
 use super::{
-	validate_candidate_internal, ValidationError, InvalidCandidate, InternalError,
-	MAX_CODE_MEM, MAX_RUNTIME_MEM, MAX_VALIDATION_RESULT_HEADER_MEM,
+	ValidationExecutor, ValidationError, InvalidCandidate, InternalError, Artifact, ArtifactCache,
+	MAX_RUNTIME_MEM, MAX_VALIDATION_RESULT_HEADER_MEM, sandbox,
 };
-use shared_memory::{SharedMem, SharedMemConf, EventState, WriteLockable, EventWait, EventSet};
-use parking_lot::Mutex;
-use log::{debug, trace};
+use parking_lot::{Condvar, Mutex};
+use log::debug;
 use futures::executor::ThreadPool;
 use sp_core::traits::SpawnNamed;
 
@@ -33,18 +64,28 @@ const WORKER_ARG: &'static str = "validation-worker";
 /// CLI Argument to start in validation worker mode.
 pub const WORKER_ARGS: &[&'static str] = &[WORKER_ARG];
 
-/// Execution timeout in seconds;
-#[cfg(debug_assertions)]
-pub const EXECUTION_TIMEOUT_SEC: u64 =  30;
+/// The version of the protocol spoken between a host and its worker. It must be bumped whenever
+/// the messages exchanged change.
+const PROTOCOL_VERSION: u32 = 4;
 
-#[cfg(not(debug_assertions))]
-pub const EXECUTION_TIMEOUT_SEC: u64 =  5;
+/// The time a worker is given to start up and complete the handshake.
+const WORKER_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
 
-enum Event {
-	CandidateReady = 0,
-	ResultReady = 1,
-	WorkerReady = 2,
-}
+/// The delay before restarting a worker which failed once. It doubles with every further
+/// consecutive failure.
+const WORKER_RESTART_BACKOFF_BASE: Duration = Duration::from_millis(100);
+
+/// The maximum delay before restarting a failed worker.
+const WORKER_RESTART_BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+/// The maximum size of a validation request, apart from the encoded params.
+const VALIDATION_HEADER_MEM: usize = 1024;
+
+/// The maximum size of a validation request.
+const MAX_VALIDATION_REQUEST_MEM: usize = VALIDATION_HEADER_MEM + MAX_RUNTIME_MEM;
+
+/// The maximum size of the setup message, which carries the path of the artifact directory.
+const MAX_SETUP_MEM: usize = 8 * 1024;
 
 #[derive(Clone)]
 struct TaskExecutor(ThreadPool);
@@ -66,22 +107,49 @@ impl SpawnNamed for TaskExecutor {
 }
 
 /// A pool of hosts.
+///
+/// The hosts execute candidates from the artifacts in the pool's artifact cache, preparing the
+/// artifact of a validation code the first time a candidate is validated under it. Each candidate
+/// is validated by the next idle host, so at most as many candidates are validated in parallel
+/// as there are hosts.
 #[derive(Clone, Debug)]
 pub struct ValidationPool {
-	hosts: Arc<Vec<Mutex<ValidationHost>>>,
+	hosts: Arc<Hosts>,
+	artifact_cache: ArtifactCache,
 }
 
-const DEFAULT_NUM_HOSTS: usize = 8;
+#[derive(Debug)]
+struct Hosts {
+	idle: Mutex<Vec<ValidationHost>>,
+	available: Condvar,
+}
 
 impl ValidationPool {
-	/// Creates a validation pool with the default configuration.
-	pub fn new() -> ValidationPool {
+	/// Creates a validation pool using the given artifact cache, with one host per CPU.
+	pub fn with_artifact_cache(artifact_cache: ArtifactCache) -> ValidationPool {
+		Self::with_num_workers(artifact_cache, num_cpus::get())
+	}
+
+	/// Creates a validation pool using the given artifact cache, with the given number of hosts.
+	///
+	/// The pool has at least one host.
+	pub fn with_num_workers(artifact_cache: ArtifactCache, num_workers: usize) -> ValidationPool {
 		ValidationPool {
-			hosts: Arc::new((0..DEFAULT_NUM_HOSTS).map(|_| Default::default()).collect()),
+			hosts: Arc::new(Hosts {
+				idle: Mutex::new((0..num_workers.max(1)).map(|_| Default::default()).collect()),
+				available: Condvar::new(),
+			}),
+			artifact_cache,
 		}
 	}
 
-	/// Validate a candidate under the given validation code using the next free validation host.
+	/// The artifact cache the hosts execute candidates from.
+	pub fn artifact_cache(&self) -> &ArtifactCache {
+		&self.artifact_cache
+	}
+
+	/// Validate a candidate under the given validation code within the given limits, using the next
+	/// idle validation host. Execution is aborted once the timeout elapses.
 	///
 	/// This will fail if the validation code is not a proper parachain validation module.
 	///
@@ -90,16 +158,21 @@ impl ValidationPool {
 		&self,
 		validation_code: &[u8],
 		params: ValidationParams,
+		limits: &ExecutionLimits,
+		timeout: Duration,
 	) -> Result<ValidationResult, ValidationError> {
 		self.validate_candidate_custom(
 			validation_code,
 			params,
+			limits,
+			timeout,
 			&env::current_exe().map_err(|err| ValidationError::Internal(err.into()))?,
 			WORKER_ARGS,
 		)
 	}
 
-	/// Validate a candidate under the given validation code using the next free validation host.
+	/// Validate a candidate under the given validation code within the given limits, using the next
+	/// idle validation host. Execution is aborted once the timeout elapses.
 	///
 	/// This will fail if the validation code is not a proper parachain validation module.
 	///
@@ -108,281 +181,586 @@ impl ValidationPool {
 		&self,
 		validation_code: &[u8],
 		params: ValidationParams,
+		limits: &ExecutionLimits,
+		timeout: Duration,
 		command: &PathBuf,
 		args: &[&str],
 	) -> Result<ValidationResult, ValidationError> {
-		for host in self.hosts.iter() {
-			if let Some(mut host) = host.try_lock() {
-				return host.validate_candidate(validation_code, params, command, args)
+		let (artifact, _) = self.artifact_cache.get_or_prepare(validation_code, limits)?;
+
+		let (mut host, timeout) = self.take_host(timeout)?;
+		let result = host.validate_candidate(self.artifact_cache.dir(), &artifact, params, timeout, command, args);
+		self.return_host(host);
+
+		result
+	}
+
+	/// Take an idle host which can start its worker right away, waiting for one to become idle if
+	/// all of them are busy.
+	///
+	/// If the workers of all idle hosts failed recently, this waits until the first of them may be
+	/// started again or another host becomes idle. Unlike waiting for a busy host, this counts
+	/// towards the given timeout, and the time left of it is returned along with the host.
+	fn take_host(&self, timeout: Duration) -> Result<(ValidationHost, Duration), InternalError> {
+		let mut idle = self.hosts.idle.lock();
+		let mut left = timeout;
+
+		loop {
+			let now = Instant::now();
+			for host in idle.iter_mut() {
+				host.reap_worker(now);
+			}
+
+			let next = idle.iter()
+				.enumerate()
+				.map(|(index, host)| (index, host.restart_delay(now)))
+				.min_by_key(|(_, delay)| *delay);
+
+			match next {
+				None => self.hosts.available.wait(&mut idle),
+				Some(_) if left == Duration::from_secs(0) => return Err(InternalError::WasmWorker(
+					"No validation worker could be started again in time".into(),
+				)),
+				Some((index, delay)) if delay == Duration::from_secs(0) => return Ok((idle.swap_remove(index), left)),
+				Some((_, delay)) => {
+					debug!("Waiting {:?} for a validation worker to be started again", delay);
+					self.hosts.available.wait_for(&mut idle, delay.min(left));
+					left = left.checked_sub(now.elapsed()).unwrap_or_else(|| Duration::from_secs(0));
+				}
 			}
 		}
+	}
 
-		// all workers are busy, just wait for the first one
-		self.hosts[0].lock().validate_candidate(validation_code, params, command, args)
+	fn return_host(&self, host: ValidationHost) {
+		self.hosts.idle.lock().push(host);
+		self.hosts.available.notify_one();
 	}
 }
 
-/// Validation worker process entry point. Runs a loop waiting for candidates to validate
-/// and sends back results via shared memory.
-pub fn run_worker(mem_id: &str) -> Result<(), String> {
-	let mut memory = match SharedMem::open(mem_id) {
-		Ok(memory) => memory,
+/// Validation worker process entry point. Talks to the host through the socket with the given file
+/// descriptor, which the worker inherited from the host, sandboxes itself and validates the
+/// candidates the host sends until it closes the connection.
+pub fn run_worker(socket_fd: &str) -> Result<(), String> {
+	run_worker_with(socket_fd, || ())
+}
+
+/// Like [`run_worker`], but calls the given function once the worker is sandboxed, before it
+/// validates any candidate. This lets tests check what the sandbox prevents.
+#[doc(hidden)]
+pub fn run_worker_with(socket_fd: &str, sandboxed: impl FnOnce()) -> Result<(), String> {
+	let mut stream = match socket_fd.parse::<RawFd>() {
+		// the host started the worker with its end of the socket pair, which nothing else owns.
+		Ok(fd) => unsafe { UnixStream::from_raw_fd(fd) },
 		Err(e) => {
-			debug!("{} Error opening shared memory: {:?}", process::id(), e);
-			return Err(format!("Error opening shared memory: {:?}", e));
+			debug!("{} Invalid socket descriptor {:?}: {:?}", process::id(), socket_fd, e);
+			return Err(format!("Invalid socket descriptor {:?}: {:?}", socket_fd, e));
 		}
 	};
 
-	let exit = Arc::new(atomic::AtomicBool::new(false));
+	send_message(&mut stream, &Handshake { version: PROTOCOL_VERSION })
+		.map_err(|e| format!("{} Error sending handshake: {:?}", process::id(), e))?;
+	let handshake: Handshake = recv_message(&mut stream, VALIDATION_HEADER_MEM)
+		.map_err(|e| format!("{} Error receiving handshake: {:?}", process::id(), e))?;
+	if handshake.version != PROTOCOL_VERSION {
+		return Err(format!(
+			"Validation host speaks protocol version {}, expected {}",
+			handshake.version,
+			PROTOCOL_VERSION,
+		));
+	}
+
+	let setup: Setup = recv_message(&mut stream, MAX_SETUP_MEM)
+		.map_err(|e| format!("{} Error receiving setup: {:?}", process::id(), e))?;
+
+	// the user namespace can only be entered while the worker is single-threaded, so before the
+	// task executor starts its threads.
+	let artifact_dir = sandbox::isolate(setup.max_memory_pages, Path::new(OsStr::from_bytes(&setup.artifact_dir)));
+
 	let task_executor = TaskExecutor::new()?;
-	// spawn parent monitor thread
-	let watch_exit = exit.clone();
-	std::thread::spawn(move || {
-		use std::io::Read;
-		let mut in_data = Vec::new();
-		// pipe terminates when parent process exits
-		std::io::stdin().read_to_end(&mut in_data).ok();
-		debug!("{} Parent process is dead. Exiting", process::id());
-		exit.store(true, atomic::Ordering::Relaxed);
-	});
-
-	memory.set(Event::WorkerReady as usize, EventState::Signaled)
-		.map_err(|e| format!("{} Error setting shared event: {:?}", process::id(), e))?;
+	// the executor lives as long as the worker, so that the modules loaded from an artifact are
+	// reused for all candidates validated under the same code and limits.
+	let executor = ValidationExecutor::new();
 
-	loop {
-		if watch_exit.load(atomic::Ordering::Relaxed) {
-			break;
-		}
+	sandbox::restrict_syscalls();
+	sandboxed();
 
+	loop {
 		debug!("{} Waiting for candidate", process::id());
-		match memory.wait(Event::CandidateReady as usize, shared_memory::Timeout::Sec(3)) {
-			Err(e) => {
-				// Timeout
-				trace!("{} Timeout waiting for candidate: {:?}", process::id(), e);
-				continue;
+		let request: ValidationRequest = match recv_message(&mut stream, MAX_VALIDATION_REQUEST_MEM) {
+			Ok(request) => request,
+			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+				debug!("{} Validation host is gone. Exiting", process::id());
+				return Ok(());
 			}
-			Ok(()) => {}
-		}
-
-		{
-			debug!("{} Processing candidate", process::id());
-			// we have candidate data
-			let mut slice = memory.wlock_as_slice(0)
-				.map_err(|e| format!("Error locking shared memory: {:?}", e))?;
-
-			let result = {
-				let data: &mut[u8] = &mut **slice;
-				let (header_buf, rest) = data.split_at_mut(1024);
-				let mut header_buf: &[u8] = header_buf;
-				let header = ValidationHeader::decode(&mut header_buf)
-					.map_err(|_| format!("Error decoding validation request."))?;
-				debug!("{} Candidate header: {:?}", process::id(), header);
-				let (code, rest) = rest.split_at_mut(MAX_CODE_MEM);
-				let (code, _) = code.split_at_mut(header.code_size as usize);
-				let (call_data, _) = rest.split_at_mut(MAX_RUNTIME_MEM);
-				let (call_data, _) = call_data.split_at_mut(header.params_size as usize);
-
-				let result = validate_candidate_internal(code, call_data, task_executor.clone());
-				debug!("{} Candidate validated: {:?}", process::id(), result);
-
-				match result {
-					Ok(r) => ValidationResultHeader::Ok(r),
-					Err(ValidationError::Internal(e)) =>
-						ValidationResultHeader::Error(WorkerValidationError::InternalError(e.to_string())),
-					Err(ValidationError::InvalidCandidate(e)) =>
-						ValidationResultHeader::Error(WorkerValidationError::ValidationError(e.to_string())),
-				}
-			};
-			let mut data: &mut[u8] = &mut **slice;
-			result.encode_to(&mut data);
-		}
-		debug!("{} Signaling result", process::id());
-		memory.set(Event::ResultReady as usize, EventState::Signaled)
-			.map_err(|e| format!("Error setting shared event: {:?}", e))?;
+			Err(e) => return Err(format!("Error receiving validation request: {:?}", e)),
+		};
+
+		debug!("{} Processing candidate of artifact {:?}", process::id(), request.artifact_id);
+
+		// the host kills a worker which doesn't respond in time, so the worker only exceeds this
+		// if the host fails to.
+		sandbox::limit_cpu_time(Duration::from_millis(request.timeout_millis) * 2);
+
+		let result = executor.validate_candidate(
+			request.artifact_id,
+			&artifact_dir.join(OsStr::from_bytes(&request.artifact_name)),
+			&request.params,
+			&request.limits,
+			task_executor.clone(),
+		);
+		debug!("{} Candidate validated: {:?}", process::id(), result);
+
+		let response = match result {
+			Ok(r) => ValidationResponse::Ok(r),
+			Err(ValidationError::Internal(e)) =>
+				ValidationResponse::Error(WorkerValidationError::InternalError(e.to_string())),
+			Err(ValidationError::InvalidCandidate(InvalidCandidate::MemoryLimitExceeded)) =>
+				ValidationResponse::Error(WorkerValidationError::MemoryLimitExceeded),
+			Err(ValidationError::InvalidCandidate(InvalidCandidate::StackLimitExceeded)) =>
+				ValidationResponse::Error(WorkerValidationError::StackLimitExceeded),
+			Err(ValidationError::InvalidCandidate(InvalidCandidate::FuelExhausted)) =>
+				ValidationResponse::Error(WorkerValidationError::FuelExhausted),
+			Err(ValidationError::InvalidCandidate(e)) =>
+				ValidationResponse::Error(WorkerValidationError::ValidationError(e.to_string())),
+		};
+
+		debug!("{} Sending result", process::id());
+		send_message(&mut stream, &response)
+			.map_err(|e| format!("Error sending validation result: {:?}", e))?;
 	}
-	Ok(())
 }
 
-/// Params header in shared memory. All offsets should be aligned to WASM page size.
+/// The first message sent by either side of a connection.
 #[derive(Encode, Decode, Debug)]
-struct ValidationHeader {
-	code_size: u64,
-	params_size: u64,
+struct Handshake {
+	/// The version of the protocol spoken by the sender.
+	version: u32,
+}
+
+/// The message sent by the host after the handshake, telling the worker how to sandbox itself.
+#[derive(Encode, Decode, Debug)]
+struct Setup {
+	/// The path of the directory of the artifact cache.
+	artifact_dir: Vec<u8>,
+	/// The maximum number of 64 KiB pages of linear memory of any candidate the worker will be
+	/// sent.
+	max_memory_pages: u32,
+}
+
+/// A request to validate a candidate.
+#[derive(Encode, Decode, Debug)]
+struct ValidationRequest {
+	/// The id of the artifact.
+	artifact_id: Hash,
+	/// The file name of the artifact to execute, within the directory of the artifact cache.
+	artifact_name: Vec<u8>,
+	/// The limits the artifact enforces.
+	limits: ExecutionLimits,
+	/// The encoded validation params.
+	params: Vec<u8>,
+	/// The time the host waits for the response, in milliseconds.
+	timeout_millis: u64,
 }
 
 #[derive(Encode, Decode, Debug)]
 enum WorkerValidationError {
 	InternalError(String),
 	ValidationError(String),
+	MemoryLimitExceeded,
+	StackLimitExceeded,
+	FuelExhausted,
 }
 
 #[derive(Encode, Decode, Debug)]
-enum ValidationResultHeader {
+enum ValidationResponse {
 	Ok(ValidationResult),
 	Error(WorkerValidationError),
 }
 
-unsafe impl Send for ValidationHost {}
+/// Send a message, prefixed with its length.
+fn send_message(stream: &mut impl Write, message: &impl Encode) -> io::Result<()> {
+	let message = message.encode();
+	stream.write_all(&(message.len() as u32).to_le_bytes())?;
+	stream.write_all(&message)?;
+	stream.flush()
+}
 
-struct ValidationHostMemory(SharedMem);
+/// Receive a message sent with [`send_message`], refusing messages larger than `max_len`.
+fn recv_message<T: Decode>(stream: &mut impl Read, max_len: usize) -> io::Result<T> {
+	let mut len = [0u8; 4];
+	stream.read_exact(&mut len)?;
+	let len = u32::from_le_bytes(len) as usize;
+	if len > max_len {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("Message of {} bytes exceeds the maximum of {}", len, max_len),
+		));
+	}
 
-impl std::fmt::Debug for ValidationHostMemory {
-	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-		write!(f, "ValidationHostMemory")
+	let mut message = vec![0u8; len];
+	stream.read_exact(&mut message)?;
+	T::decode(&mut &message[..]).map_err(|e| io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!("Error decoding message: {:?}", e),
+	))
+}
+
+/// Tracks the consecutive failures of the workers of a host.
+#[derive(Default, Debug)]
+struct Backoff {
+	failures: u32,
+	last_failure: Option<Instant>,
+}
+
+impl Backoff {
+	fn note_failure(&mut self, now: Instant) {
+		self.failures = self.failures.saturating_add(1);
+		self.last_failure = Some(now);
+	}
+
+	fn reset(&mut self) {
+		self.failures = 0;
+		self.last_failure = None;
+	}
+
+	/// The time left before a new worker may be started.
+	fn remaining(&self, now: Instant) -> Duration {
+		let last_failure = match self.last_failure {
+			Some(last_failure) => last_failure,
+			None => return Duration::from_secs(0),
+		};
+
+		let delay = WORKER_RESTART_BACKOFF_BASE
+			.checked_mul(1 << (self.failures - 1).min(16))
+			.map_or(WORKER_RESTART_BACKOFF_MAX, |delay| delay.min(WORKER_RESTART_BACKOFF_MAX));
+
+		(last_failure + delay).saturating_duration_since(now)
 	}
 }
 
-impl std::ops::Deref for ValidationHostMemory {
-	type Target = SharedMem;
+/// A running worker and the connection to it.
+#[derive(Debug)]
+struct Worker {
+	process: sandbox::WorkerProcess,
+	stream: UnixStream,
+	/// The directory the worker reads the artifacts from.
+	artifact_dir: PathBuf,
+	/// The maximum number of pages of linear memory the worker allows the code it executes.
+	max_memory_pages: u32,
+}
 
-	fn deref(&self) -> &Self::Target {
-		&self.0
+impl Drop for Worker {
+	fn drop(&mut self) {
+		self.process.kill().ok();
+		self.process.wait().ok();
 	}
 }
 
-impl std::ops::DerefMut for ValidationHostMemory {
-	fn deref_mut(&mut self) -> &mut Self::Target {
-		&mut self.0
+impl Worker {
+	/// Start a worker which executes code from the artifacts in the given directory using up to the
+	/// given number of pages of linear memory, and complete the handshake with it.
+	fn start(cmd: &PathBuf, args: &[&str], artifact_dir: &Path, max_memory_pages: u32) -> Result<Self, InternalError> {
+		let (stream, worker_stream) = UnixStream::pair()?;
+		let worker_fd = worker_stream.as_raw_fd();
+
+		debug!("Starting worker at {:?} with arguments: {:?} and socket {}", cmd, args, worker_fd);
+		let process = sandbox::spawn_worker(cmd, args, worker_fd)?;
+
+		// once the worker is the only owner of its end, the host notices when it exits.
+		drop(worker_stream);
+
+		// a failed handshake drops the worker, which kills it.
+		let mut worker = Worker { process, stream, artifact_dir: artifact_dir.to_path_buf(), max_memory_pages };
+		worker.handshake()?;
+		let artifact_dir = artifact_dir.as_os_str().as_bytes().to_vec();
+		send_message(&mut worker.stream, &Setup { artifact_dir, max_memory_pages })?;
+		Ok(worker)
 	}
+
+	fn handshake(&mut self) -> Result<(), InternalError> {
+		self.stream.set_read_timeout(Some(WORKER_STARTUP_TIMEOUT))?;
+		let handshake: Handshake = recv_message(&mut self.stream, VALIDATION_HEADER_MEM)?;
+		if handshake.version != PROTOCOL_VERSION {
+			return Err(InternalError::WasmWorker(format!(
+				"Worker speaks protocol version {}, expected {}",
+				handshake.version,
+				PROTOCOL_VERSION,
+			)));
+		}
+
+		send_message(&mut self.stream, &Handshake { version: PROTOCOL_VERSION })?;
+		Ok(())
+	}
+
+	/// Kill the worker, returning how it exited if the sandbox had stopped it before.
+	fn kill(mut self) -> Option<SandboxExit> {
+		self.process.kill().ok();
+		let status = self.process.wait().ok()?;
+
+		if status.signal().is_some() && status.signal() == sandbox::DISALLOWED_SYSCALL_SIGNAL {
+			Some(SandboxExit::DisallowedSyscall)
+		} else if status.code() == Some(sandbox::CPU_TIME_EXCEEDED_EXIT_CODE) {
+			Some(SandboxExit::CpuTimeExceeded)
+		} else {
+			None
+		}
+	}
+
+	/// Send the request to the worker and wait for the response until the timeout elapses.
+	fn validate(&mut self, request: &ValidationRequest, timeout: Duration) -> io::Result<ValidationResponse> {
+		let deadline = Instant::now() + timeout;
+		self.stream.set_write_timeout(Some(timeout))?;
+		send_message(&mut self.stream, request)?;
+
+		let remaining = deadline.saturating_duration_since(Instant::now());
+		// a zero timeout would block forever.
+		self.stream.set_read_timeout(Some(remaining.max(Duration::from_millis(1))))?;
+		recv_message(&mut self.stream, MAX_VALIDATION_RESULT_HEADER_MEM)
+	}
+}
+
+/// The ways the sandbox stops a worker.
+enum SandboxExit {
+	DisallowedSyscall,
+	CpuTimeExceeded,
 }
 
 #[derive(Default, Debug)]
 struct ValidationHost {
-	worker: Option<process::Child>,
-	memory: Option<ValidationHostMemory>,
-	id: u32,
+	worker: Option<Worker>,
+	backoff: Backoff,
 }
 
-impl Drop for ValidationHost {
-	fn drop(&mut self) {
-		if let Some(ref mut worker) = &mut self.worker {
-			worker.kill().ok();
+impl ValidationHost {
+	/// The time left before the worker of this host may be started, if it is not running.
+	fn restart_delay(&self, now: Instant) -> Duration {
+		match self.worker {
+			Some(_) => Duration::from_secs(0),
+			None => self.backoff.remaining(now),
 		}
 	}
-}
 
-impl ValidationHost {
-	fn create_memory() -> Result<SharedMem, InternalError> {
-		let mem_size = MAX_RUNTIME_MEM + MAX_CODE_MEM + MAX_VALIDATION_RESULT_HEADER_MEM;
-		let mem_config = SharedMemConf::default()
-			.set_size(mem_size)
-			.add_lock(shared_memory::LockType::Mutex, 0, mem_size)?
-			.add_event(shared_memory::EventType::Auto)?  // Event::CandidateReady
-			.add_event(shared_memory::EventType::Auto)?  // Event::ResultReady
-			.add_event(shared_memory::EventType::Auto)?; // Event::WorkerReady
-
-		Ok(mem_config.create()?)
+	/// Note the failure of the worker of this host if it exited while the host was idle.
+	fn reap_worker(&mut self, now: Instant) {
+		let exited = match self.worker {
+			Some(ref mut worker) => !matches!(worker.process.try_wait(), Ok(None)),
+			None => false,
+		};
+
+		if exited {
+			debug!("Worker {} exited while idle", self.worker.as_ref().map_or(0, |w| w.process.id()));
+			self.worker = None;
+			self.backoff.note_failure(now);
+		}
 	}
 
-	fn start_worker(&mut self, cmd: &PathBuf, args: &[&str]) -> Result<(), InternalError> {
-		if let Some(ref mut worker) = self.worker {
-			// Check if still alive
-			if let Ok(None) = worker.try_wait() {
-				// Still running
-				return Ok(());
-			}
+	/// Start the worker of this host, unless it is running already, reads the artifacts from the
+	/// given directory and allows the code it executes the given number of pages of linear memory.
+	/// The host must have been taken from the pool once its restart delay elapsed.
+	fn start_worker(
+		&mut self,
+		cmd: &PathBuf,
+		args: &[&str],
+		artifact_dir: &Path,
+		max_memory_pages: u32,
+	) -> Result<(), InternalError> {
+		match self.worker {
+			Some(ref worker) if worker.artifact_dir == artifact_dir && worker.max_memory_pages >= max_memory_pages =>
+				return Ok(()),
+			// neither the directory nor the memory limit of a sandboxed worker can be changed, so it
+			// is replaced.
+			Some(_) => self.worker = None,
+			None => {},
 		}
 
-		let memory = Self::create_memory()?;
-
-		debug!("Starting worker at {:?} with arguments: {:?} and {:?}", cmd, args, memory.get_os_path());
-		let worker = process::Command::new(cmd)
-			.args(args)
-			.arg(memory.get_os_path())
-			.stdin(process::Stdio::piped())
-			.spawn()?;
-		self.id = worker.id();
-		self.worker = Some(worker);
-
-		memory.wait(
-			Event::WorkerReady as usize,
-			shared_memory::Timeout::Sec(EXECUTION_TIMEOUT_SEC as usize),
-		)?;
-		self.memory = Some(ValidationHostMemory(memory));
-		Ok(())
+		match Worker::start(cmd, args, artifact_dir, max_memory_pages) {
+			Ok(worker) => {
+				self.worker = Some(worker);
+				Ok(())
+			}
+			Err(e) => {
+				self.backoff.note_failure(Instant::now());
+				Err(e)
+			}
+		}
 	}
 
-	/// Validate a candidate under the given validation code.
+	/// Validate a candidate under the validation code of the given artifact in the given directory,
+	/// killing the worker once the timeout elapses.
 	///
 	/// This will fail if the validation code is not a proper parachain validation module.
 	pub fn validate_candidate(
 		&mut self,
-		validation_code: &[u8],
+		artifact_dir: &Path,
+		artifact: &Artifact,
 		params: ValidationParams,
+		timeout: Duration,
 		binary: &PathBuf,
 		args: &[&str],
 	) -> Result<ValidationResult, ValidationError> {
-		if validation_code.len() > MAX_CODE_MEM {
-			return Err(ValidationError::InvalidCandidate(InvalidCandidate::CodeTooLarge(validation_code.len())));
+		let artifact_name = artifact.path.file_name()
+			.expect("artifacts are files in the directory of the cache; qed")
+			.as_bytes()
+			.to_vec();
+
+		let encoded_params = params.encode();
+		if encoded_params.len() >= MAX_RUNTIME_MEM {
+			return Err(ValidationError::InvalidCandidate(InvalidCandidate::ParamsTooLarge(MAX_RUNTIME_MEM)));
 		}
+
+		let request = ValidationRequest {
+			artifact_id: artifact.id(),
+			artifact_name,
+			limits: artifact.limits,
+			params: encoded_params,
+			timeout_millis: timeout.as_millis() as u64,
+		};
+
 		// First, check if need to spawn the child process
-		self.start_worker(binary, args)?;
-		let memory = self.memory.as_mut()
-			.expect("memory is always `Some` after `start_worker` completes successfully");
-		{
-			// Put data in shared mem
-			let data: &mut[u8] = &mut **memory.wlock_as_slice(0)
-				.map_err(|e|ValidationError::Internal(e.into()))?;
-			let (mut header_buf, rest) = data.split_at_mut(1024);
-			let (code, rest) = rest.split_at_mut(MAX_CODE_MEM);
-			let (code, _) = code.split_at_mut(validation_code.len());
-			let (call_data, _) = rest.split_at_mut(MAX_RUNTIME_MEM);
-			code[..validation_code.len()].copy_from_slice(validation_code);
-			let encoded_params = params.encode();
-			if encoded_params.len() >= MAX_RUNTIME_MEM {
-				return Err(ValidationError::InvalidCandidate(InvalidCandidate::ParamsTooLarge(MAX_RUNTIME_MEM)));
+		self.start_worker(binary, args, artifact_dir, artifact.limits.max_memory_pages)?;
+		let worker = self.worker.as_mut()
+			.expect("worker is always `Some` after `start_worker` completes successfully");
+		let id = worker.process.id();
+
+		debug!("{} Sending candidate", id);
+		let response = match worker.validate(&request, timeout) {
+			Ok(response) => {
+				self.backoff.reset();
+				response
 			}
-			call_data[..encoded_params.len()].copy_from_slice(&encoded_params);
+			Err(e) => {
+				// the worker either hung or crashed, so it is killed and started again with the
+				// next candidate.
+				let sandbox_exit = self.worker.take().and_then(Worker::kill);
+				self.backoff.note_failure(Instant::now());
+
+				return match (sandbox_exit, e.kind()) {
+					(Some(SandboxExit::DisallowedSyscall), _) => {
+						debug!("{} Worker attempted a disallowed system call", id);
+						Err(ValidationError::Internal(InternalError::DisallowedSyscall))
+					}
+					(Some(SandboxExit::CpuTimeExceeded), _) |
+					(None, io::ErrorKind::WouldBlock) |
+					(None, io::ErrorKind::TimedOut) => {
+						debug!("{} Worker timeout: {:?}", id, e);
+						Err(ValidationError::InvalidCandidate(InvalidCandidate::Timeout))
+					}
+					_ => {
+						debug!("{} Worker failed: {:?}", id, e);
+						Err(ValidationError::Internal(e.into()))
+					}
+				};
+			}
+		};
+
+		match response {
+			ValidationResponse::Ok(result) => Ok(result),
+			ValidationResponse::Error(WorkerValidationError::InternalError(e)) => {
+				debug!("{} Internal validation error: {}", id, e);
+				Err(ValidationError::Internal(InternalError::WasmWorker(e)))
+			},
+			ValidationResponse::Error(WorkerValidationError::ValidationError(e)) => {
+				debug!("{} External validation error: {}", id, e);
+				Err(ValidationError::InvalidCandidate(InvalidCandidate::ExternalWasmExecutor(e)))
+			}
+			ValidationResponse::Error(WorkerValidationError::MemoryLimitExceeded) =>
+				Err(ValidationError::InvalidCandidate(InvalidCandidate::MemoryLimitExceeded)),
+			ValidationResponse::Error(WorkerValidationError::StackLimitExceeded) =>
+				Err(ValidationError::InvalidCandidate(InvalidCandidate::StackLimitExceeded)),
+			ValidationResponse::Error(WorkerValidationError::FuelExhausted) =>
+				Err(ValidationError::InvalidCandidate(InvalidCandidate::FuelExhausted)),
+		}
+	}
+}
 
-			let header = ValidationHeader {
-				code_size: validation_code.len() as u64,
-				params_size: encoded_params.len() as u64,
-			};
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	#[test]
+	fn messages_are_framed() {
+		let mut buf = Vec::new();
+		send_message(&mut buf, &Handshake { version: 7 }).unwrap();
+		send_message(&mut buf, &vec![1u8; 100]).unwrap();
+
+		let mut stream = &buf[..];
+		let handshake: Handshake = recv_message(&mut stream, 16).unwrap();
+		assert_eq!(handshake.version, 7);
+		let message: Vec<u8> = recv_message(&mut stream, 128).unwrap();
+		assert_eq!(message, vec![1u8; 100]);
+
+		let err = recv_message::<Handshake>(&mut stream, 16).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+	}
 
-			header.encode_to(&mut header_buf);
-		}
+	#[test]
+	fn oversized_messages_are_refused() {
+		let mut buf = Vec::new();
+		send_message(&mut buf, &vec![1u8; 100]).unwrap();
 
-		debug!("{} Signaling candidate", self.id);
-		memory.set(Event::CandidateReady as usize, EventState::Signaled)
-			.map_err(|e| ValidationError::Internal(e.into()))?;
+		let err = recv_message::<Vec<u8>>(&mut &buf[..], 64).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+	}
 
-		debug!("{} Waiting for results", self.id);
-		match memory.wait(Event::ResultReady as usize, shared_memory::Timeout::Sec(EXECUTION_TIMEOUT_SEC as usize)) {
-			Err(e) => {
-				debug!("Worker timeout: {:?}", e);
-				if let Some(mut worker) = self.worker.take() {
-					worker.kill().ok();
-				}
-				return Err(ValidationError::InvalidCandidate(InvalidCandidate::Timeout));
-			}
-			Ok(()) => {}
+	#[test]
+	fn restart_delay_grows_with_consecutive_failures() {
+		let now = Instant::now();
+		let mut backoff = Backoff::default();
+		assert_eq!(backoff.remaining(now), Duration::from_secs(0));
+
+		backoff.note_failure(now);
+		assert_eq!(backoff.remaining(now), WORKER_RESTART_BACKOFF_BASE);
+		assert_eq!(backoff.remaining(now + WORKER_RESTART_BACKOFF_BASE), Duration::from_secs(0));
+
+		backoff.note_failure(now);
+		assert_eq!(backoff.remaining(now), WORKER_RESTART_BACKOFF_BASE * 2);
+
+		for _ in 0..100 {
+			backoff.note_failure(now);
 		}
+		assert_eq!(backoff.remaining(now), WORKER_RESTART_BACKOFF_MAX);
 
-		{
-			debug!("{} Reading results", self.id);
-			let data: &[u8] = &**memory.wlock_as_slice(0)
-				.map_err(|e| ValidationError::Internal(e.into()))?;
-			let (header_buf, _) = data.split_at(MAX_VALIDATION_RESULT_HEADER_MEM);
-			let mut header_buf: &[u8] = header_buf;
-			let header = ValidationResultHeader::decode(&mut header_buf)
-				.map_err(|e|
-					InternalError::System(
-						Box::<dyn std::error::Error + Send + Sync>::from(
-							format!("Failed to decode `ValidationResultHeader`: {:?}", e)
-						) as Box<_>
-					)
-				)?;
-			match header {
-				ValidationResultHeader::Ok(result) => Ok(result),
-				ValidationResultHeader::Error(WorkerValidationError::InternalError(e)) => {
-					debug!("{} Internal validation error: {}", self.id, e);
-					Err(ValidationError::Internal(InternalError::WasmWorker(e)))
-				},
-				ValidationResultHeader::Error(WorkerValidationError::ValidationError(e)) => {
-					debug!("{} External validation error: {}", self.id, e);
-					Err(ValidationError::InvalidCandidate(InvalidCandidate::ExternalWasmExecutor(e)))
-				}
-			}
+		backoff.reset();
+		assert_eq!(backoff.remaining(now), Duration::from_secs(0));
+	}
+
+	fn pool_in_temp_dir(name: &str, num_workers: usize) -> ValidationPool {
+		let dir = env::temp_dir()
+			.join(format!("polkadot-pvf-pool-test-{}-{}", name, process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		ValidationPool::with_num_workers(ArtifactCache::new(dir).unwrap(), num_workers)
+	}
+
+	#[test]
+	fn hosts_whose_worker_failed_are_skipped() {
+		let pool = pool_in_temp_dir("failover", 2);
+		pool.hosts.idle.lock()[0].backoff.note_failure(Instant::now());
+
+		let (host, left) = pool.take_host(Duration::from_secs(1)).unwrap();
+		assert_eq!(host.backoff.failures, 0);
+		assert_eq!(left, Duration::from_secs(1));
+
+		fs::remove_dir_all(pool.artifact_cache().dir()).unwrap();
+	}
+
+	#[test]
+	fn waiting_for_a_restart_counts_towards_the_timeout() {
+		let pool = pool_in_temp_dir("restart", 1);
+		pool.hosts.idle.lock()[0].backoff.note_failure(Instant::now());
+
+		let (host, left) = pool.take_host(Duration::from_secs(1)).unwrap();
+		assert!(left <= Duration::from_secs(1) - WORKER_RESTART_BACKOFF_BASE);
+		pool.return_host(host);
+
+		for _ in 0..10 {
+			pool.hosts.idle.lock()[0].backoff.note_failure(Instant::now());
+		}
+		match pool.take_host(WORKER_RESTART_BACKOFF_BASE) {
+			Err(InternalError::WasmWorker(_)) => {},
+			r => panic!("unexpected result: {:?}", r.map(|(_, left)| left)),
 		}
+
+		fs::remove_dir_all(pool.artifact_cache().dir()).unwrap();
 	}
 }
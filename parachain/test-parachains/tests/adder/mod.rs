@@ -16,7 +16,9 @@
 
 //! Basic parachain that adds a number as part of its state.
 
-const WORKER_ARGS_TEST: &[&'static str] = &["--nocapture", "validation_worker"];
+// see `wasm_executor::WORKER_ARGS_TEST`.
+const WORKER_ARGS_TEST: &[&'static str] = &["--nocapture", "--test-threads=1", "validation_worker"];
+const EXECUTION_TIMEOUT: Duration = Duration::from_secs(30);
 
 use parachain::{
 	primitives::{
@@ -25,9 +27,10 @@ use parachain::{
 		HeadData as GenericHeadData,
 		ValidationParams,
 	},
-	wasm_executor::{ValidationPool, ExecutionMode}
+	wasm_executor::{ExecutionMode, ValidationExecutor},
 };
 use codec::{Decode, Encode};
+use std::time::Duration;
 
 /// Head data for this parachain.
 #[derive(Default, Clone, Encode, Decode)]
@@ -59,7 +62,7 @@ fn hash_head(head: &HeadData) -> [u8; 32] {
 
 fn execution_mode() -> ExecutionMode {
 	ExecutionMode::ExternalProcessCustomHost {
-		pool: ValidationPool::new(),
+		pool: crate::validation_pool(),
 		binary: std::env::current_exe().unwrap(),
 		args: WORKER_ARGS_TEST.iter().map(|x| x.to_string()).collect(),
 	}
@@ -67,7 +70,10 @@ fn execution_mode() -> ExecutionMode {
 
 #[test]
 fn execute_good_on_parent_with_inprocess_validation() {
-	let execution_mode = ExecutionMode::InProcess;
+	let execution_mode = ExecutionMode::InProcess {
+		artifact_cache: crate::artifact_cache(),
+		executor: ValidationExecutor::new(),
+	};
 	execute_good_on_parent(execution_mode);
 }
 
@@ -97,7 +103,10 @@ fn execute_good_on_parent(execution_mode: ExecutionMode) {
 			block_data: GenericBlockData(block_data.encode()),
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
+			dmq_mqc_head: Default::default(),
 		},
+		&Default::default(),
+		EXECUTION_TIMEOUT,
 		&execution_mode,
 		sp_core::testing::TaskExecutor::new(),
 	).unwrap();
@@ -135,7 +144,10 @@ fn execute_good_chain_on_parent() {
 				block_data: GenericBlockData(block_data.encode()),
 				relay_chain_height: number as RelayChainBlockNumber + 1,
 				hrmp_mqc_heads: Vec::new(),
+				dmq_mqc_head: Default::default(),
 			},
+			&Default::default(),
+			EXECUTION_TIMEOUT,
 			&execution_mode,
 			sp_core::testing::TaskExecutor::new(),
 		).unwrap();
@@ -174,7 +186,10 @@ fn execute_bad_on_parent() {
 			block_data: GenericBlockData(block_data.encode()),
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
+			dmq_mqc_head: Default::default(),
 		},
+		&Default::default(),
+		EXECUTION_TIMEOUT,
 		&execution_mode,
 		sp_core::testing::TaskExecutor::new(),
 	).unwrap_err();
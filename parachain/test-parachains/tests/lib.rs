@@ -17,14 +17,42 @@
 mod adder;
 mod wasm_executor;
 
-use parachain::wasm_executor::run_worker;
+use parachain::wasm_executor::{run_worker, run_worker_with, ArtifactCache, ValidationPool};
+
+/// An artifact cache in a private directory of this test binary.
+fn artifact_cache() -> ArtifactCache {
+	let dir = std::env::temp_dir().join(format!("polkadot-pvf-test-parachains-{}", std::process::id()));
+	ArtifactCache::new(dir).unwrap()
+}
+
+/// A validation pool keeping its artifacts in the artifact cache of this test binary.
+fn validation_pool() -> ValidationPool {
+	ValidationPool::with_artifact_cache(artifact_cache())
+}
+
+/// The file descriptor of the socket to the validation host, which the host passes to the test
+/// binary right after the name of the test acting as the worker.
+fn socket_fd(worker_test: &str) -> Option<String> {
+	std::env::args().skip_while(|arg| arg != worker_test).nth(1)
+}
 
 // This is not an actual test, but rather an entry point for out-of process WASM executor.
 // When executing tests the executor spawns currently executing binary, which happens to be test binary.
 // It then passes "validation_worker" on CLI effectivly making rust test executor to run this single test.
 #[test]
 fn validation_worker() {
-	if let Some(id) = std::env::args().find(|a| a.starts_with("/shmem_rs_")) {
-		run_worker(&id).unwrap()
+	if let Some(socket_fd) = socket_fd("validation_worker") {
+		run_worker(&socket_fd).unwrap()
+	}
+}
+
+// Like `validation_worker`, but the worker opens a network socket once it is sandboxed, which the
+// sandbox doesn't allow.
+#[test]
+fn disallowed_syscall_worker() {
+	if let Some(socket_fd) = socket_fd("disallowed_syscall_worker") {
+		run_worker_with(&socket_fd, || {
+			let _ = std::net::TcpListener::bind("127.0.0.1:0");
+		}).unwrap()
 	}
 }
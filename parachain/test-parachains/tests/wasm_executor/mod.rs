@@ -16,22 +16,64 @@
 
 //! Basic parachain that adds a number as part of its state.
 
-const WORKER_ARGS_TEST: &[&'static str] = &["--nocapture", "validation_worker"];
+// the worker entry points run on the main thread of the test binary, so that the worker is still
+// single-threaded when it sandboxes itself.
+const WORKER_ARGS_TEST: &[&'static str] = &["--nocapture", "--test-threads=1", "validation_worker"];
+const DISALLOWED_SYSCALL_WORKER_ARGS_TEST: &[&'static str] =
+	&["--nocapture", "--test-threads=1", "disallowed_syscall_worker"];
+const EXECUTION_TIMEOUT: Duration = Duration::from_secs(5);
 
 use crate::adder;
 use parachain::{
-	primitives::{BlockData, ValidationParams},
-	wasm_executor::{ValidationError, InvalidCandidate, EXECUTION_TIMEOUT_SEC, ExecutionMode, ValidationPool},
+	primitives::{BlockData, ExecutionLimits, ValidationParams},
+	wasm_executor::{ValidationError, InvalidCandidate, InternalError, ExecutionMode},
 };
+use std::time::Duration;
 
 fn execution_mode() -> ExecutionMode {
 	ExecutionMode::ExternalProcessCustomHost {
-		pool: ValidationPool::new(),
+		pool: crate::validation_pool(),
 		binary: std::env::current_exe().unwrap(),
 		args: WORKER_ARGS_TEST.iter().map(|x| x.to_string()).collect(),
 	}
 }
 
+#[test]
+fn sandboxed_worker_validates_candidates() {
+	// the worker sandboxes itself before the first candidate and is reused for the second one.
+	adder::execute_good_on_parent_with_external_process_validation();
+	adder::execute_good_on_parent_with_external_process_validation();
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn disallowed_syscall_is_reported() {
+	let execution_mode = ExecutionMode::ExternalProcessCustomHost {
+		pool: crate::validation_pool(),
+		binary: std::env::current_exe().unwrap(),
+		args: DISALLOWED_SYSCALL_WORKER_ARGS_TEST.iter().map(|x| x.to_string()).collect(),
+	};
+
+	let result = parachain::wasm_executor::validate_candidate(
+		halt::wasm_binary_unwrap(),
+		ValidationParams {
+			block_data: BlockData(Vec::new()),
+			parent_head: Default::default(),
+			relay_chain_height: 1,
+			hrmp_mqc_heads: Vec::new(),
+			dmq_mqc_head: Default::default(),
+		},
+		&Default::default(),
+		EXECUTION_TIMEOUT,
+		&execution_mode,
+		sp_core::testing::TaskExecutor::new(),
+	);
+	match result {
+		Err(ValidationError::Internal(InternalError::DisallowedSyscall)) => {},
+		r => panic!("{:?}", r),
+	}
+}
+
 #[test]
 fn terminates_on_timeout() {
 	let execution_mode = execution_mode();
@@ -43,7 +85,10 @@ fn terminates_on_timeout() {
 			parent_head: Default::default(),
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
+			dmq_mqc_head: Default::default(),
 		},
+		&Default::default(),
+		EXECUTION_TIMEOUT,
 		&execution_mode,
 		sp_core::testing::TaskExecutor::new(),
 	);
@@ -56,6 +101,30 @@ fn terminates_on_timeout() {
 	adder::execute_good_on_parent_with_external_process_validation();
 }
 
+#[test]
+fn terminates_once_fuel_is_exhausted() {
+	let execution_mode = execution_mode();
+
+	let result = parachain::wasm_executor::validate_candidate(
+		halt::wasm_binary_unwrap(),
+		ValidationParams {
+			block_data: BlockData(Vec::new()),
+			parent_head: Default::default(),
+			relay_chain_height: 1,
+			hrmp_mqc_heads: Vec::new(),
+			dmq_mqc_head: Default::default(),
+		},
+		&ExecutionLimits { max_fuel: Some(1_000_000), ..Default::default() },
+		EXECUTION_TIMEOUT,
+		&execution_mode,
+		sp_core::testing::TaskExecutor::new(),
+	);
+	match result {
+		Err(ValidationError::InvalidCandidate(InvalidCandidate::FuelExhausted)) => {},
+		r => panic!("{:?}", r),
+	}
+}
+
 #[test]
 fn parallel_execution() {
 	let execution_mode = execution_mode();
@@ -71,7 +140,10 @@ fn parallel_execution() {
 			parent_head: Default::default(),
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
+			dmq_mqc_head: Default::default(),
 		},
+		&Default::default(),
+		EXECUTION_TIMEOUT,
 		&execution_mode,
 		sp_core::testing::TaskExecutor::new(),
 	).ok());
@@ -82,14 +154,14 @@ fn parallel_execution() {
 			parent_head: Default::default(),
 			relay_chain_height: 1,
 			hrmp_mqc_heads: Vec::new(),
+			dmq_mqc_head: Default::default(),
 		},
+		&Default::default(),
+		EXECUTION_TIMEOUT,
 		&execution_mode2,
 		sp_core::testing::TaskExecutor::new(),
 	);
 	thread.join().unwrap();
-	// total time should be < 2 x EXECUTION_TIMEOUT_SEC
-	assert!(
-		std::time::Instant::now().duration_since(start)
-		< std::time::Duration::from_secs(EXECUTION_TIMEOUT_SEC * 2)
-	);
+	// total time should be < 2 x EXECUTION_TIMEOUT
+	assert!(std::time::Instant::now().duration_since(start) < EXECUTION_TIMEOUT * 2);
 }
@@ -17,6 +17,7 @@
 //! V1 Primitives.
 
 use sp_std::prelude::*;
+use sp_std::collections::btree_map::BTreeMap;
 use parity_scale_codec::{Encode, Decode};
 use bitvec::vec::BitVec;
 
@@ -31,13 +32,13 @@ pub use runtime_primitives::traits::{BlakeTwo256, Hash as HashT};
 pub use polkadot_core_primitives::v1::{
 	BlockNumber, Moment, Signature, AccountPublic, AccountId, AccountIndex,
 	ChainId, Hash, Nonce, Balance, Header, Block, BlockId, UncheckedExtrinsic,
-	Remark, DownwardMessage,
+	Remark, DownwardMessage, InboundDownwardMessage, InboundHrmpMessage, OutboundHrmpMessage,
 };
 
 // Export some polkadot-parachain primitives
 pub use polkadot_parachain::primitives::{
 	Id, ParachainDispatchOrigin, LOWEST_USER_ID, UpwardMessage, HeadData, BlockData,
-	ValidationCode,
+	ValidationCode, AccountIdConversion, HrmpChannelId, ExecutionLimits,
 };
 
 // Export some basic parachain primitives from v0.
@@ -52,10 +53,42 @@ pub use crate::v0::{
 pub use crate::v0::{ValidatorPair, CollatorPair};
 
 pub use sp_staking::SessionIndex;
+pub use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
 
 /// Unique identifier for the Inclusion Inherent
 pub const INCLUSION_INHERENT_IDENTIFIER: InherentIdentifier = *b"inclusn0";
 
+/// The `ConsensusEngineId` under which the parachains runtime posts digest items.
+pub const POLKADOT_ENGINE_ID: runtime_primitives::ConsensusEngineId = *b"POL1";
+
+/// A consensus log item posted to the header digest by the parachains runtime.
+#[derive(Decode, Encode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum ConsensusLog<N = BlockNumber> {
+	/// A candidate included in the block with the given number has been concluded invalid by a
+	/// supermajority of validators. The chain should be reverted to the parent of that block.
+	#[codec(index = "1")]
+	Revert(N),
+}
+
+impl<N: Decode> ConsensusLog<N> {
+	/// Attempt to convert a reference to a generic digest item into a consensus log.
+	pub fn from_digest_item<H>(digest_item: &runtime_primitives::DigestItem<H>)
+		-> Result<Option<Self>, parity_scale_codec::Error>
+	{
+		match digest_item {
+			runtime_primitives::DigestItem::Consensus(id, encoded) if id == &POLKADOT_ENGINE_ID =>
+				Ok(Some(Self::decode(&mut &encoded[..])?)),
+			_ => Ok(None),
+		}
+	}
+}
+
+impl<H, N: Encode> From<ConsensusLog<N>> for runtime_primitives::DigestItem<H> {
+	fn from(log: ConsensusLog<N>) -> runtime_primitives::DigestItem<H> {
+		Self::Consensus(POLKADOT_ENGINE_ID, log.encode())
+	}
+}
+
 /// Get a collator signature payload on a relay-parent, block-data combo.
 pub fn collator_signature_payload<H: AsRef<[u8]>>(
 	relay_parent: &H,
@@ -112,6 +145,9 @@ pub struct CandidateDescriptor<H = Hash> {
 	pub persisted_validation_data_hash: Hash,
 	/// The blake2-256 hash of the pov.
 	pub pov_hash: Hash,
+	/// The root of the Merkle tree of the erasure-coded chunks of the available data.
+	/// This must be equal to the erasure root in the commitments of the candidate.
+	pub erasure_root: Hash,
 	/// Signature on blake2-256 of components of this receipt:
 	/// The parachain index, the relay parent, the validation data hash, and the pov_hash.
 	pub signature: CollatorSignature,
@@ -265,6 +301,11 @@ pub struct PersistedValidationData<N = BlockNumber> {
 	/// vector is sorted ascending by the para id and doesn't contain multiple entries with the same
 	/// sender.
 	pub hrmp_mqc_heads: Vec<(Id, Hash)>,
+	/// The MQC head for the DMQ.
+	///
+	/// The DMQ MQC head will be used by the validation function to authorize the downward messages
+	/// passed by the collator.
+	pub dmq_mqc_head: Hash,
 }
 
 impl<N: Encode> PersistedValidationData<N> {
@@ -310,12 +351,18 @@ pub struct CandidateCommitments {
 	pub fees: Balance,
 	/// Messages destined to be interpreted by the Relay chain itself.
 	pub upward_messages: Vec<UpwardMessage>,
+	/// Horizontal messages sent by the parachain.
+	pub horizontal_messages: Vec<OutboundHrmpMessage<Id>>,
 	/// The root of a block's erasure encoding Merkle tree.
 	pub erasure_root: Hash,
 	/// New validation code.
 	pub new_validation_code: Option<ValidationCode>,
 	/// The head-data produced as a result of execution.
 	pub head_data: HeadData,
+	/// The number of messages processed from the DMQ.
+	pub processed_downward_messages: u32,
+	/// The mark which specifies the block number up to which all inbound HRMP messages are processed.
+	pub hrmp_watermark: BlockNumber,
 }
 
 impl CandidateCommitments {
@@ -425,6 +472,36 @@ pub fn check_candidate_backing<H: AsRef<[u8]> + Clone + Encode>(
 	Ok(signed)
 }
 
+/// A set of signed statements about the validity of a single candidate, disputing it.
+///
+/// The statements are `Valid` or `Invalid` compact statements by validators of the session the
+/// candidate was included in, signed under the context given by `signing_context`.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct DisputeStatementSet {
+	/// The hash of the disputed candidate.
+	pub candidate_hash: Hash,
+	/// The session the candidate was included in.
+	pub session: SessionIndex,
+	/// The statements about the validity of the candidate.
+	pub statements: Vec<SignedStatement>,
+}
+
+impl DisputeStatementSet {
+	/// The signing context of the statements in this set.
+	///
+	/// This is the session of the candidate along with its hash, so that statements are
+	/// bound to the dispute and can be replayed onto any fork of the relay chain.
+	pub fn signing_context(&self) -> SigningContext {
+		SigningContext {
+			session_index: self.session,
+			parent_hash: self.candidate_hash,
+		}
+	}
+}
+
+/// A set of dispute statement sets, each about a different candidate.
+pub type DisputeStatementSets = Vec<DisputeStatementSet>;
+
 /// The unique (during session) index of a core.
 #[derive(Encode, Decode, Default, PartialOrd, Ord, Eq, PartialEq, Clone, Copy)]
 #[cfg_attr(feature = "std", derive(Debug))]
@@ -625,13 +702,76 @@ pub enum OccupiedCoreAssumption {
 pub enum CandidateEvent<H = Hash> {
 	/// This candidate receipt was backed in the most recent block.
 	#[codec(index = "0")]
-	CandidateBacked(CandidateReceipt<H>, HeadData),
+	CandidateBacked(CandidateReceipt<H>, HeadData, CoreIndex),
 	/// This candidate receipt was included and became a parablock at the most recent block.
 	#[codec(index = "1")]
-	CandidateIncluded(CandidateReceipt<H>, HeadData),
+	CandidateIncluded(CandidateReceipt<H>, HeadData, CoreIndex),
 	/// This candidate receipt was not made available in time and timed out.
 	#[codec(index = "2")]
-	CandidateTimedOut(CandidateReceipt<H>, HeadData),
+	CandidateTimedOut(CandidateReceipt<H>, HeadData, CoreIndex),
+}
+
+impl<H> CandidateEvent<H> {
+	/// Get the receipt of the candidate this event concerns.
+	pub fn receipt(&self) -> &CandidateReceipt<H> {
+		match self {
+			Self::CandidateBacked(receipt, _, _) => receipt,
+			Self::CandidateIncluded(receipt, _, _) => receipt,
+			Self::CandidateTimedOut(receipt, _, _) => receipt,
+		}
+	}
+}
+
+/// Information about the validators of a session and the parameters of approval checking
+/// in the session.
+#[derive(Clone, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(PartialEq, Default))]
+pub struct SessionInfo {
+	/// The validators of the session, in canonical order.
+	pub validators: Vec<ValidatorId>,
+	/// The authority discovery keys of the validators, in the same order as `validators`.
+	pub discovery_keys: Vec<AuthorityDiscoveryId>,
+	/// The validator groups of the session, as produced by the scheduler, which are referred to
+	/// by `GroupIndex`.
+	pub validator_groups: Vec<Vec<ValidatorIndex>>,
+	/// The number of availability cores during the session.
+	pub n_cores: u32,
+	/// The width of the zeroth delay tranche of approval assignments.
+	pub zeroth_delay_tranche_width: u32,
+	/// The number of `RelayVRFModulo` samples each validator takes.
+	pub relay_vrf_modulo_samples: u32,
+	/// The number of delay tranches in total.
+	pub n_delay_tranches: u32,
+	/// The number of slots after receiving their assignment that validators are given to
+	/// approve a candidate before they are considered no-shows.
+	pub no_show_slots: u32,
+	/// The number of validators which need to approve a candidate.
+	pub needed_approvals: u32,
+}
+
+/// The default timeout of the execution of a validation function during backing, in milliseconds.
+pub const DEFAULT_BACKING_EXECUTION_TIMEOUT_MS: u32 = 5_000;
+/// The default timeout of the execution of a validation function during approval checking, in
+/// milliseconds. Approval checkers are more lenient than backers, so that a candidate which was
+/// backed in time isn't rejected by approval checkers on slower hardware.
+pub const DEFAULT_APPROVAL_EXECUTION_TIMEOUT_MS: u32 = 15_000;
+
+/// The timeouts of the execution of validation functions, in milliseconds.
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub struct ExecutionTimeouts {
+	/// The timeout of execution during backing.
+	pub backing: u32,
+	/// The timeout of execution during approval checking.
+	pub approval: u32,
+}
+
+impl Default for ExecutionTimeouts {
+	fn default() -> Self {
+		ExecutionTimeouts {
+			backing: DEFAULT_BACKING_EXECUTION_TIMEOUT_MS,
+			approval: DEFAULT_APPROVAL_EXECUTION_TIMEOUT_MS,
+		}
+	}
 }
 
 sp_api::decl_runtime_apis! {
@@ -670,6 +810,11 @@ sp_api::decl_runtime_apis! {
 		/// This can be used to instantiate a `SigningContext`.
 		fn session_index_for_child() -> SessionIndex;
 
+		/// Get the session info for the given session, if stored.
+		///
+		/// Only the sessions within the dispute period of the current one are stored.
+		fn session_info(index: SessionIndex) -> Option<SessionInfo>;
+
 		/// Fetch the validation code used by a para, making the given `OccupiedCoreAssumption`.
 		///
 		/// Returns `None` if either the para is not registered or the assumption is `Freed`
@@ -677,6 +822,9 @@ sp_api::decl_runtime_apis! {
 		fn validation_code(para_id: Id, assumption: OccupiedCoreAssumption)
 			-> Option<ValidationCode>;
 
+		/// Fetch the validation code a para is scheduled to upgrade to, if any.
+		fn future_validation_code(para_id: Id) -> Option<ValidationCode>;
+
 		/// Get the receipt of a candidate pending availability. This returns `Some` for any paras
 		/// assigned to occupied cores in `availability_cores` and `None` otherwise.
 		fn candidate_pending_availability(para_id: Id) -> Option<CommittedCandidateReceipt<H>>;
@@ -686,6 +834,23 @@ sp_api::decl_runtime_apis! {
 		// initialization.
 		#[skip_initialize_block]
 		fn candidate_events() -> Vec<CandidateEvent<H>>;
+
+		/// Get all the pending inbound messages in the downward message queue for a para.
+		fn dmq_contents(recipient: Id) -> Vec<InboundDownwardMessage<N>>;
+
+		/// Get the contents of all channels addressed to the given recipient. Channels that have no
+		/// messages in them are also included.
+		fn inbound_hrmp_channels_contents(recipient: Id) -> BTreeMap<Id, Vec<InboundHrmpMessage<N>>>;
+
+		/// Get the authority discovery keys of the given validators. The keys are returned in the
+		/// same order as the validators and are `None` for validators that aren't known.
+		fn validator_discovery(validators: Vec<ValidatorId>) -> Vec<Option<AuthorityDiscoveryId>>;
+
+		/// Get the limits validation functions are executed under.
+		fn execution_limits() -> ExecutionLimits;
+
+		/// Get the timeouts of the execution of validation functions.
+		fn execution_timeouts() -> ExecutionTimeouts;
 	}
 }
 
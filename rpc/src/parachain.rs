@@ -0,0 +1,585 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The `parachain_*` RPCs, exposing the state of the parachain host through the
+//! `ParachainHost` runtime API.
+
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result, futures::{Future, Sink, Stream}};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId, manager::SubscriptionManager};
+use log::warn;
+use polkadot_primitives::v1::{
+	Block, BlockId, BlakeTwo256, Hash, HashT, HeadData, Id as ParaId, OccupiedCoreAssumption,
+	ParachainHost, ValidationCode,
+};
+use sc_client_api::BlockchainEvents;
+use sc_rpc::SubscriptionTaskExecutor;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+
+pub mod types;
+
+use types::{
+	BlockStatus, CandidateEvent, CandidateEventNotification, CommittedCandidateReceipt, CoreState,
+	ValidatorGroups,
+};
+
+/// The error code of a failed runtime API call.
+const RUNTIME_ERROR: i64 = 1;
+
+/// Parachain RPC methods.
+#[rpc]
+pub trait ParachainApi<BlockHash> {
+	/// RPC metadata.
+	type Metadata;
+
+	/// Get the head data of a para, as included in the given block.
+	///
+	/// Candidates pending availability are not taken into account.
+	#[rpc(name = "parachain_headData")]
+	fn head_data(&self, para_id: ParaId, at: Option<BlockHash>) -> Result<Option<HeadData>>;
+
+	/// Get the current validation code of a para at the given block.
+	#[rpc(name = "parachain_validationCode")]
+	fn validation_code(&self, para_id: ParaId, at: Option<BlockHash>) -> Result<Option<ValidationCode>>;
+
+	/// Get the blake2-256 hash of the current validation code of a para at the given block.
+	#[rpc(name = "parachain_validationCodeHash")]
+	fn validation_code_hash(&self, para_id: ParaId, at: Option<BlockHash>) -> Result<Option<Hash>>;
+
+	/// Get information on all availability cores at the given block.
+	#[rpc(name = "parachain_availabilityCores")]
+	fn availability_cores(&self, at: Option<BlockHash>) -> Result<Vec<CoreState>>;
+
+	/// Get the candidate of a para pending availability at the given block, if any.
+	#[rpc(name = "parachain_candidatePendingAvailability")]
+	fn candidate_pending_availability(
+		&self,
+		para_id: ParaId,
+		at: Option<BlockHash>,
+	) -> Result<Option<CommittedCandidateReceipt>>;
+
+	/// Get the validator groups and group rotation info at the given block.
+	#[rpc(name = "parachain_validatorGroups")]
+	fn validator_groups(&self, at: Option<BlockHash>) -> Result<ValidatorGroups>;
+
+	/// Get all events concerning candidates (backing, inclusion, time-out) in the given block.
+	#[rpc(name = "parachain_candidateEvents")]
+	fn candidate_events(&self, at: Option<BlockHash>) -> Result<Vec<CandidateEvent>>;
+
+	/// Subscribe to the events concerning candidates of a para, as blocks are imported and
+	/// finalized.
+	#[pubsub(
+		subscription = "parachain_candidateEvents",
+		subscribe,
+		name = "parachain_subscribeCandidateEvents"
+	)]
+	fn subscribe_candidate_events(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<CandidateEventNotification>,
+		para_id: ParaId,
+	);
+
+	/// Unsubscribe from the events concerning candidates of a para.
+	#[pubsub(
+		subscription = "parachain_candidateEvents",
+		unsubscribe,
+		name = "parachain_unsubscribeCandidateEvents"
+	)]
+	fn unsubscribe_candidate_events(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool>;
+}
+
+/// An implementation of the parachain RPCs on top of a client with access to the
+/// `ParachainHost` runtime API.
+pub struct Parachain<C> {
+	client: Arc<C>,
+	manager: SubscriptionManager,
+}
+
+impl<C> Parachain<C> {
+	/// Create a new instance of the parachain RPCs.
+	pub fn new(client: Arc<C>, executor: SubscriptionTaskExecutor) -> Self {
+		Parachain {
+			client,
+			manager: SubscriptionManager::new(Arc::new(executor)),
+		}
+	}
+}
+
+impl<C> Parachain<C> where C: HeaderBackend<Block> {
+	fn block_id(&self, at: Option<Hash>) -> BlockId {
+		BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash))
+	}
+}
+
+fn runtime_error(err: impl std::fmt::Debug) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: "Runtime API call failed.".into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+/// Fetch the candidate events of the given block which concern the given para.
+///
+/// Errors are logged and treated as if there were no events.
+fn para_candidate_events<C>(client: &C, block_hash: Hash, para_id: ParaId) -> Vec<CandidateEvent> where
+	C: ProvideRuntimeApi<Block>,
+	C::Api: ParachainHost<Block>,
+{
+	match client.runtime_api().candidate_events(&BlockId::hash(block_hash)) {
+		Ok(events) => events.into_iter()
+			.filter(|event| event.receipt().descriptor.para_id == para_id)
+			.map(Into::into)
+			.collect(),
+		Err(e) => {
+			warn!("Failed to fetch the candidate events of block {}: {:?}", block_hash, e);
+			Vec::new()
+		}
+	}
+}
+
+impl<C> ParachainApi<Hash> for Parachain<C> where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + BlockchainEvents<Block>,
+	C: Send + Sync + 'static,
+	C::Api: ParachainHost<Block>,
+{
+	type Metadata = sc_rpc::Metadata;
+
+	fn head_data(&self, para_id: ParaId, at: Option<Hash>) -> Result<Option<HeadData>> {
+		// the head as of the given block is the parent head of the next candidate, if we don't
+		// enact the candidate pending availability.
+		self.client.runtime_api()
+			.persisted_validation_data(&self.block_id(at), para_id, OccupiedCoreAssumption::TimedOut)
+			.map(|data| data.map(|data| data.parent_head))
+			.map_err(runtime_error)
+	}
+
+	fn validation_code(&self, para_id: ParaId, at: Option<Hash>) -> Result<Option<ValidationCode>> {
+		self.client.runtime_api()
+			.validation_code(&self.block_id(at), para_id, OccupiedCoreAssumption::TimedOut)
+			.map_err(runtime_error)
+	}
+
+	fn validation_code_hash(&self, para_id: ParaId, at: Option<Hash>) -> Result<Option<Hash>> {
+		self.validation_code(para_id, at)
+			.map(|code| code.map(|code| BlakeTwo256::hash(&code.0[..])))
+	}
+
+	fn availability_cores(&self, at: Option<Hash>) -> Result<Vec<CoreState>> {
+		self.client.runtime_api()
+			.availability_cores(&self.block_id(at))
+			.map(|cores| cores.into_iter().map(Into::into).collect())
+			.map_err(runtime_error)
+	}
+
+	fn candidate_pending_availability(
+		&self,
+		para_id: ParaId,
+		at: Option<Hash>,
+	) -> Result<Option<CommittedCandidateReceipt>> {
+		self.client.runtime_api()
+			.candidate_pending_availability(&self.block_id(at), para_id)
+			.map(|candidate| candidate.map(Into::into))
+			.map_err(runtime_error)
+	}
+
+	fn validator_groups(&self, at: Option<Hash>) -> Result<ValidatorGroups> {
+		self.client.runtime_api()
+			.validator_groups(&self.block_id(at))
+			.map(Into::into)
+			.map_err(runtime_error)
+	}
+
+	fn candidate_events(&self, at: Option<Hash>) -> Result<Vec<CandidateEvent>> {
+		self.client.runtime_api()
+			.candidate_events(&self.block_id(at))
+			.map(|events| events.into_iter().map(Into::into).collect())
+			.map_err(runtime_error)
+	}
+
+	fn subscribe_candidate_events(
+		&self,
+		_metadata: Self::Metadata,
+		subscriber: Subscriber<CandidateEventNotification>,
+		para_id: ParaId,
+	) {
+		let imported = self.client.import_notification_stream().map(|notification| {
+			let status = if notification.is_new_best { BlockStatus::Best } else { BlockStatus::Imported };
+			(notification.hash, status)
+		});
+		let finalized = self.client.finality_notification_stream()
+			.map(|notification| (notification.hash, BlockStatus::Finalized));
+
+		let client = self.client.clone();
+		let stream = futures::stream::select(imported, finalized)
+			.flat_map(move |(block_hash, block_status)| {
+				let notifications = para_candidate_events(&*client, block_hash, para_id)
+					.into_iter()
+					.map(move |event| CandidateEventNotification { block_hash, block_status, event });
+
+				futures::stream::iter(notifications)
+			})
+			.map(|notification| Ok::<_, ()>(notification))
+			.compat();
+
+		self.manager.add(subscriber, |sink| {
+			sink
+				.sink_map_err(|e| warn!("Error sending candidate event notifications: {:?}", e))
+				.send_all(stream.map(Ok))
+				// the stream only ends once the client is gone or we are unsubscribed.
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_candidate_events(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool> {
+		Ok(self.manager.cancel(id))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::collections::{BTreeMap, HashMap};
+
+	use polkadot_primitives::v1::{
+		self, AuthorityDiscoveryId, BlockNumber, CandidateDescriptor, CandidateReceipt,
+		CommittedCandidateReceipt as V1CommittedCandidateReceipt, CoreIndex, ExecutionLimits,
+		ExecutionTimeouts, GroupRotationInfo, Header, InboundDownwardMessage,
+		InboundHrmpMessage, PersistedValidationData, ScheduledCore, SessionIndex, SessionInfo,
+		ValidationData, ValidatorId, ValidatorIndex,
+	};
+	use sc_client_api::{FinalityNotifications, ImportNotifications, StorageEventStream};
+	use sp_blockchain::{BlockStatus as ChainBlockStatus, Info as BlockInfo};
+	use sp_core::{storage::StorageKey, testing::TaskExecutor};
+	use sp_utils::mpsc::tracing_unbounded;
+
+	const BEST_HASH: Hash = Hash::repeat_byte(1);
+
+	#[derive(Default, Clone)]
+	struct MockRuntimeApi {
+		heads: HashMap<ParaId, HeadData>,
+		validation_code: HashMap<ParaId, ValidationCode>,
+		validator_groups: Vec<Vec<ValidatorIndex>>,
+		availability_cores: Vec<v1::CoreState>,
+		candidate_pending_availability: HashMap<ParaId, V1CommittedCandidateReceipt>,
+		candidate_events: Vec<v1::CandidateEvent>,
+	}
+
+	sp_api::mock_impl_runtime_apis! {
+		impl ParachainHost<Block> for MockRuntimeApi {
+			type Error = String;
+
+			fn validators(&self) -> Vec<ValidatorId> {
+				Vec::new()
+			}
+
+			fn validator_groups(&self) -> (Vec<Vec<ValidatorIndex>>, GroupRotationInfo) {
+				(
+					self.validator_groups.clone(),
+					GroupRotationInfo {
+						session_start_block: 1,
+						group_rotation_frequency: 100,
+						now: 10,
+					},
+				)
+			}
+
+			fn availability_cores(&self) -> Vec<v1::CoreState> {
+				self.availability_cores.clone()
+			}
+
+			fn persisted_validation_data(
+				&self,
+				para: ParaId,
+				_assumption: OccupiedCoreAssumption,
+			) -> Option<PersistedValidationData> {
+				self.heads.get(&para).map(|head| PersistedValidationData {
+					parent_head: head.clone(),
+					..Default::default()
+				})
+			}
+
+			fn full_validation_data(
+				&self,
+				_para: ParaId,
+				_assumption: OccupiedCoreAssumption,
+			) -> Option<ValidationData> {
+				None
+			}
+
+			fn session_index_for_child(&self) -> SessionIndex {
+				0
+			}
+
+			fn session_info(&self, _index: SessionIndex) -> Option<SessionInfo> {
+				None
+			}
+
+			fn validation_code(
+				&self,
+				para: ParaId,
+				_assumption: OccupiedCoreAssumption,
+			) -> Option<ValidationCode> {
+				self.validation_code.get(&para).cloned()
+			}
+
+			fn future_validation_code(&self, _para: ParaId) -> Option<ValidationCode> {
+				None
+			}
+
+			fn candidate_pending_availability(
+				&self,
+				para: ParaId,
+			) -> Option<V1CommittedCandidateReceipt> {
+				self.candidate_pending_availability.get(&para).cloned()
+			}
+
+			fn candidate_events(&self) -> Vec<v1::CandidateEvent> {
+				self.candidate_events.clone()
+			}
+
+			fn dmq_contents(
+				&self,
+				_recipient: ParaId,
+			) -> Vec<InboundDownwardMessage<BlockNumber>> {
+				Vec::new()
+			}
+
+			fn inbound_hrmp_channels_contents(
+				&self,
+				_recipient: ParaId,
+			) -> BTreeMap<ParaId, Vec<InboundHrmpMessage>> {
+				BTreeMap::new()
+			}
+
+			fn validator_discovery(
+				&self,
+				validators: Vec<ValidatorId>,
+			) -> Vec<Option<AuthorityDiscoveryId>> {
+				vec![None; validators.len()]
+			}
+
+			fn execution_limits(&self) -> ExecutionLimits {
+				ExecutionLimits::default()
+			}
+
+			fn execution_timeouts(&self) -> ExecutionTimeouts {
+				ExecutionTimeouts::default()
+			}
+		}
+	}
+
+	// A client serving the runtime API from the mock, with a single best block.
+	struct MockClient {
+		api: MockRuntimeApi,
+	}
+
+	impl ProvideRuntimeApi<Block> for MockClient {
+		type Api = MockRuntimeApi;
+
+		fn runtime_api<'a>(&'a self) -> sp_api::ApiRef<'a, Self::Api> {
+			self.api.clone().into()
+		}
+	}
+
+	impl HeaderBackend<Block> for MockClient {
+		fn info(&self) -> BlockInfo<Block> {
+			BlockInfo {
+				best_hash: BEST_HASH,
+				best_number: 1,
+				genesis_hash: Hash::zero(),
+				finalized_hash: Hash::zero(),
+				finalized_number: 0,
+				number_leaves: 1,
+			}
+		}
+
+		fn header(&self, _id: BlockId) -> sp_blockchain::Result<Option<Header>> {
+			Ok(None)
+		}
+
+		fn status(&self, _id: BlockId) -> sp_blockchain::Result<ChainBlockStatus> {
+			Ok(ChainBlockStatus::Unknown)
+		}
+
+		fn number(&self, _hash: Hash) -> sp_blockchain::Result<Option<BlockNumber>> {
+			Ok(None)
+		}
+
+		fn hash(&self, _number: BlockNumber) -> sp_blockchain::Result<Option<Hash>> {
+			Ok(None)
+		}
+	}
+
+	impl BlockchainEvents<Block> for MockClient {
+		fn import_notification_stream(&self) -> ImportNotifications<Block> {
+			tracing_unbounded("mock_import_notification_stream").1
+		}
+
+		fn finality_notification_stream(&self) -> FinalityNotifications<Block> {
+			tracing_unbounded("mock_finality_notification_stream").1
+		}
+
+		fn storage_changes_notification_stream(
+			&self,
+			_filter_keys: Option<&[StorageKey]>,
+			_child_filter_keys: Option<&[(StorageKey, Option<Vec<StorageKey>>)]>,
+		) -> sp_blockchain::Result<StorageEventStream<Hash>> {
+			unimplemented!("not used by the parachain RPCs")
+		}
+	}
+
+	fn parachain(api: MockRuntimeApi) -> Parachain<MockClient> {
+		Parachain::new(Arc::new(MockClient { api }), SubscriptionTaskExecutor::new(TaskExecutor::new()))
+	}
+
+	fn candidate(para_id: ParaId) -> V1CommittedCandidateReceipt {
+		let mut candidate = V1CommittedCandidateReceipt::default();
+		candidate.descriptor.para_id = para_id;
+		candidate.commitments.head_data = vec![1, 2, 3].into();
+		candidate
+	}
+
+	#[test]
+	fn head_data_and_validation_code() {
+		let para_a = ParaId::from(1);
+		let para_b = ParaId::from(2);
+
+		let mut api = MockRuntimeApi::default();
+		api.heads.insert(para_a, vec![1, 2, 3].into());
+		api.validation_code.insert(para_a, vec![4, 5, 6].into());
+		let parachain = parachain(api);
+
+		assert_eq!(parachain.head_data(para_a, None).unwrap(), Some(vec![1, 2, 3].into()));
+		assert_eq!(parachain.head_data(para_b, Some(BEST_HASH)).unwrap(), None);
+
+		assert_eq!(parachain.validation_code(para_a, None).unwrap(), Some(vec![4, 5, 6].into()));
+		assert_eq!(parachain.validation_code(para_b, None).unwrap(), None);
+
+		assert_eq!(
+			parachain.validation_code_hash(para_a, None).unwrap(),
+			Some(BlakeTwo256::hash(&[4, 5, 6])),
+		);
+		assert_eq!(parachain.validation_code_hash(para_b, None).unwrap(), None);
+	}
+
+	#[test]
+	fn availability_cores_and_pending_candidates() {
+		let para_a = ParaId::from(1);
+		let para_b = ParaId::from(2);
+
+		let mut api = MockRuntimeApi::default();
+		api.availability_cores = vec![
+			v1::CoreState::Scheduled(ScheduledCore { para_id: para_a, collator: None }),
+			v1::CoreState::Free,
+		];
+		api.candidate_pending_availability.insert(para_a, candidate(para_a));
+		let parachain = parachain(api);
+
+		assert_eq!(
+			parachain.availability_cores(None).unwrap(),
+			vec![
+				CoreState::Scheduled(types::ScheduledCore { para_id: para_a, collator: None }),
+				CoreState::Free,
+			],
+		);
+
+		let pending = parachain.candidate_pending_availability(para_a, None).unwrap().unwrap();
+		assert_eq!(pending.descriptor.para_id, para_a);
+		assert_eq!(pending.commitments.head_data, vec![1, 2, 3].into());
+		assert_eq!(parachain.candidate_pending_availability(para_b, None).unwrap(), None);
+	}
+
+	#[test]
+	fn validator_groups() {
+		let mut api = MockRuntimeApi::default();
+		api.validator_groups = vec![vec![0, 1], vec![2]];
+		let parachain = parachain(api);
+
+		let groups = parachain.validator_groups(None).unwrap();
+		assert_eq!(groups.groups, vec![vec![0, 1], vec![2]]);
+		assert_eq!(groups.rotation_info.session_start_block, 1);
+		assert_eq!(groups.rotation_info.group_rotation_frequency, 100);
+		assert_eq!(groups.rotation_info.now, 10);
+	}
+
+	#[test]
+	fn requests_over_json_rpc() {
+		let mut api = MockRuntimeApi::default();
+		api.heads.insert(ParaId::from(1), vec![1, 2, 3].into());
+		api.availability_cores = vec![
+			v1::CoreState::Scheduled(ScheduledCore { para_id: ParaId::from(1), collator: None }),
+			v1::CoreState::Free,
+		];
+
+		let mut io = jsonrpc_core::MetaIoHandler::default();
+		io.extend_with(ParachainApi::to_delegate(parachain(api)));
+
+		let request = r#"{"jsonrpc":"2.0","method":"parachain_headData","params":[1],"id":1}"#;
+		let response = r#"{"jsonrpc":"2.0","result":"0x010203","id":1}"#;
+		assert_eq!(io.handle_request_sync(request, Default::default()), Some(response.into()));
+
+		let request = r#"{"jsonrpc":"2.0","method":"parachain_headData","params":[2],"id":2}"#;
+		let response = r#"{"jsonrpc":"2.0","result":null,"id":2}"#;
+		assert_eq!(io.handle_request_sync(request, Default::default()), Some(response.into()));
+
+		let request = r#"{"jsonrpc":"2.0","method":"parachain_availabilityCores","params":[],"id":3}"#;
+		let response = r#"{"jsonrpc":"2.0","result":[{"scheduled":{"paraId":1,"collator":null}},"free"],"id":3}"#;
+		assert_eq!(io.handle_request_sync(request, Default::default()), Some(response.into()));
+	}
+
+	#[test]
+	fn candidate_events_are_filtered_by_para() {
+		let para_a = ParaId::from(1);
+		let para_b = ParaId::from(2);
+
+		let receipt = |para_id| CandidateReceipt {
+			descriptor: CandidateDescriptor { para_id, ..Default::default() },
+			commitments_hash: Hash::zero(),
+		};
+
+		let mut api = MockRuntimeApi::default();
+		api.candidate_events = vec![
+			v1::CandidateEvent::CandidateBacked(receipt(para_a), vec![1].into(), CoreIndex(0)),
+			v1::CandidateEvent::CandidateIncluded(receipt(para_b), vec![2].into(), CoreIndex(1)),
+		];
+		let client = MockClient { api };
+
+		let parachain = parachain(client.api.clone());
+		assert_eq!(parachain.candidate_events(None).unwrap().len(), 2);
+
+		let events = para_candidate_events(&client, BEST_HASH, para_b);
+		assert_eq!(
+			events,
+			vec![CandidateEvent::CandidateIncluded(receipt(para_b).into(), vec![2].into(), 1)],
+		);
+		assert!(para_candidate_events(&client, BEST_HASH, ParaId::from(3)).is_empty());
+	}
+}
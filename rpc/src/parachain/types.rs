@@ -0,0 +1,407 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! JSON representations of the v1 parachain primitives returned by the parachain RPCs.
+//!
+//! These mirror the types in `polkadot_primitives::v1` field by field. Raw byte strings and
+//! signatures are represented as hex-encoded bytes and bitfields as lists of booleans.
+
+use codec::Encode;
+use serde::{Serialize, Deserialize};
+use sp_core::Bytes;
+use polkadot_primitives::v1::{
+	self, Balance, BlockNumber, CollatorId, Hash, HeadData, Id as ParaId, ValidationCode,
+	ValidatorIndex,
+};
+
+/// A unique descriptor of the candidate receipt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateDescriptor {
+	/// The ID of the para this is a candidate for.
+	pub para_id: ParaId,
+	/// The hash of the relay-chain block this is executed in the context of.
+	pub relay_parent: Hash,
+	/// The collator's sr25519 public key.
+	pub collator: CollatorId,
+	/// The blake2-256 hash of the persisted validation data.
+	pub persisted_validation_data_hash: Hash,
+	/// The blake2-256 hash of the pov.
+	pub pov_hash: Hash,
+	/// The root of the Merkle tree of the erasure-coded chunks of the available data.
+	pub erasure_root: Hash,
+	/// The SCALE-encoded signature of the collator on the descriptor.
+	pub signature: Bytes,
+}
+
+impl From<v1::CandidateDescriptor> for CandidateDescriptor {
+	fn from(descriptor: v1::CandidateDescriptor) -> Self {
+		CandidateDescriptor {
+			para_id: descriptor.para_id,
+			relay_parent: descriptor.relay_parent,
+			collator: descriptor.collator,
+			persisted_validation_data_hash: descriptor.persisted_validation_data_hash,
+			pov_hash: descriptor.pov_hash,
+			erasure_root: descriptor.erasure_root,
+			signature: descriptor.signature.encode().into(),
+		}
+	}
+}
+
+/// A candidate-receipt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateReceipt {
+	/// The descriptor of the candidate.
+	pub descriptor: CandidateDescriptor,
+	/// The hash of the encoded commitments made as a result of candidate execution.
+	pub commitments_hash: Hash,
+}
+
+impl From<v1::CandidateReceipt> for CandidateReceipt {
+	fn from(receipt: v1::CandidateReceipt) -> Self {
+		CandidateReceipt {
+			descriptor: receipt.descriptor.into(),
+			commitments_hash: receipt.commitments_hash,
+		}
+	}
+}
+
+/// An HRMP message seen from the perspective of a sender.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboundHrmpMessage {
+	/// The para that will get this message in its downward message queue.
+	pub recipient: ParaId,
+	/// The message payload.
+	pub data: Bytes,
+}
+
+/// Commitments made in a `CandidateReceipt`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateCommitments {
+	/// Fees paid from the chain to the relay chain validators.
+	pub fees: Balance,
+	/// Messages destined to be interpreted by the Relay chain itself.
+	pub upward_messages: Vec<Bytes>,
+	/// Horizontal messages sent by the parachain.
+	pub horizontal_messages: Vec<OutboundHrmpMessage>,
+	/// The root of a block's erasure encoding Merkle tree.
+	pub erasure_root: Hash,
+	/// New validation code.
+	pub new_validation_code: Option<ValidationCode>,
+	/// The head-data produced as a result of execution.
+	pub head_data: HeadData,
+	/// The number of messages processed from the DMQ.
+	pub processed_downward_messages: u32,
+	/// The mark which specifies the block number up to which all inbound HRMP messages are processed.
+	pub hrmp_watermark: BlockNumber,
+}
+
+impl From<v1::CandidateCommitments> for CandidateCommitments {
+	fn from(commitments: v1::CandidateCommitments) -> Self {
+		CandidateCommitments {
+			fees: commitments.fees,
+			upward_messages: commitments.upward_messages.into_iter().map(Into::into).collect(),
+			horizontal_messages: commitments.horizontal_messages
+				.into_iter()
+				.map(|m| OutboundHrmpMessage { recipient: m.recipient, data: m.data.into() })
+				.collect(),
+			erasure_root: commitments.erasure_root,
+			new_validation_code: commitments.new_validation_code,
+			head_data: commitments.head_data,
+			processed_downward_messages: commitments.processed_downward_messages,
+			hrmp_watermark: commitments.hrmp_watermark,
+		}
+	}
+}
+
+/// A candidate-receipt with commitments directly included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommittedCandidateReceipt {
+	/// The descriptor of the candidate.
+	pub descriptor: CandidateDescriptor,
+	/// The commitments of the candidate receipt.
+	pub commitments: CandidateCommitments,
+}
+
+impl From<v1::CommittedCandidateReceipt> for CommittedCandidateReceipt {
+	fn from(receipt: v1::CommittedCandidateReceipt) -> Self {
+		CommittedCandidateReceipt {
+			descriptor: receipt.descriptor.into(),
+			commitments: receipt.commitments.into(),
+		}
+	}
+}
+
+/// Information about a core which is currently occupied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OccupiedCore {
+	/// The ID of the para occupying the core.
+	pub para_id: ParaId,
+	/// If this core is freed by availability, this is the assignment that is next up on this
+	/// core, if any.
+	pub next_up_on_available: Option<ScheduledCore>,
+	/// The relay-chain block number this began occupying the core at.
+	pub occupied_since: BlockNumber,
+	/// The relay-chain block this will time-out at, if any.
+	pub time_out_at: BlockNumber,
+	/// If this core is freed by being timed-out, this is the assignment that is next up on this
+	/// core, if any.
+	pub next_up_on_time_out: Option<ScheduledCore>,
+	/// One entry for each validator in the set, `true` if the validator has attested to
+	/// availability on-chain.
+	pub availability: Vec<bool>,
+	/// The group assigned to distribute availability pieces of this candidate.
+	pub group_responsible: u32,
+}
+
+/// Information about a core which is currently free, with a para scheduled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledCore {
+	/// The ID of a para scheduled.
+	pub para_id: ParaId,
+	/// The collator required to author the block, if any.
+	pub collator: Option<CollatorId>,
+}
+
+impl From<v1::ScheduledCore> for ScheduledCore {
+	fn from(core: v1::ScheduledCore) -> Self {
+		ScheduledCore {
+			para_id: core.para_id,
+			collator: core.collator,
+		}
+	}
+}
+
+/// The state of a particular availability core.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CoreState {
+	/// The core is currently occupied.
+	Occupied(OccupiedCore),
+	/// The core is currently free, with a para scheduled and given the opportunity
+	/// to occupy.
+	Scheduled(ScheduledCore),
+	/// The core is currently free and there is nothing scheduled.
+	Free,
+}
+
+impl From<v1::CoreState> for CoreState {
+	fn from(core: v1::CoreState) -> Self {
+		match core {
+			v1::CoreState::Occupied(core) => CoreState::Occupied(OccupiedCore {
+				para_id: core.para_id,
+				next_up_on_available: core.next_up_on_available.map(Into::into),
+				occupied_since: core.occupied_since,
+				time_out_at: core.time_out_at,
+				next_up_on_time_out: core.next_up_on_time_out.map(Into::into),
+				availability: core.availability.iter().map(|b| *b).collect(),
+				group_responsible: core.group_responsible.0,
+			}),
+			v1::CoreState::Scheduled(core) => CoreState::Scheduled(core.into()),
+			v1::CoreState::Free => CoreState::Free,
+		}
+	}
+}
+
+/// A helper data-type for tracking validator-group rotations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupRotationInfo {
+	/// The block number where the session started.
+	pub session_start_block: BlockNumber,
+	/// How often groups rotate. 0 means never.
+	pub group_rotation_frequency: BlockNumber,
+	/// The current block number.
+	pub now: BlockNumber,
+}
+
+/// The validator groups of a block together with their rotation info.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorGroups {
+	/// The validators of each group, by index.
+	pub groups: Vec<Vec<ValidatorIndex>>,
+	/// Information on the rotation of the groups across the availability cores.
+	pub rotation_info: GroupRotationInfo,
+}
+
+impl From<(Vec<Vec<ValidatorIndex>>, v1::GroupRotationInfo)> for ValidatorGroups {
+	fn from((groups, rotation_info): (Vec<Vec<ValidatorIndex>>, v1::GroupRotationInfo)) -> Self {
+		ValidatorGroups {
+			groups,
+			rotation_info: GroupRotationInfo {
+				session_start_block: rotation_info.session_start_block,
+				group_rotation_frequency: rotation_info.group_rotation_frequency,
+				now: rotation_info.now,
+			},
+		}
+	}
+}
+
+/// An event concerning a candidate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CandidateEvent {
+	/// This candidate receipt was backed in the most recent block. [candidate, head_data, core_index]
+	CandidateBacked(CandidateReceipt, HeadData, u32),
+	/// This candidate receipt was included and became a parablock at the most recent block.
+	/// [candidate, head_data, core_index]
+	CandidateIncluded(CandidateReceipt, HeadData, u32),
+	/// This candidate receipt was not made available in time and timed out.
+	/// [candidate, head_data, core_index]
+	CandidateTimedOut(CandidateReceipt, HeadData, u32),
+}
+
+impl From<v1::CandidateEvent> for CandidateEvent {
+	fn from(event: v1::CandidateEvent) -> Self {
+		match event {
+			v1::CandidateEvent::CandidateBacked(receipt, head, core) =>
+				CandidateEvent::CandidateBacked(receipt.into(), head, core.0),
+			v1::CandidateEvent::CandidateIncluded(receipt, head, core) =>
+				CandidateEvent::CandidateIncluded(receipt.into(), head, core.0),
+			v1::CandidateEvent::CandidateTimedOut(receipt, head, core) =>
+				CandidateEvent::CandidateTimedOut(receipt.into(), head, core.0),
+		}
+	}
+}
+
+/// The status of a block in which candidate events occurred, as of the notification.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockStatus {
+	/// The block was imported, but is not on the best chain.
+	Imported,
+	/// The block was imported as the new best block.
+	Best,
+	/// The block was finalized.
+	Finalized,
+}
+
+/// A notification about an event concerning a candidate of a subscribed para.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateEventNotification {
+	/// The hash of the block the event occurred in.
+	pub block_hash: Hash,
+	/// The status of that block.
+	pub block_status: BlockStatus,
+	/// The event itself.
+	pub event: CandidateEvent,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde::de::DeserializeOwned;
+	use polkadot_primitives::v1::{CoreIndex, GroupIndex};
+
+	fn round_trip<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(value: T) {
+		let json = serde_json::to_string(&value).unwrap();
+		assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value);
+	}
+
+	fn committed_candidate(para_id: ParaId) -> v1::CommittedCandidateReceipt {
+		let mut candidate = v1::CommittedCandidateReceipt::default();
+		candidate.descriptor.para_id = para_id;
+		candidate.commitments.upward_messages = vec![vec![1, 2]];
+		candidate.commitments.horizontal_messages = vec![v1::OutboundHrmpMessage {
+			recipient: ParaId::from(2),
+			data: vec![3, 4],
+		}];
+		candidate.commitments.new_validation_code = Some(vec![5, 6].into());
+		candidate.commitments.head_data = vec![7, 8].into();
+		candidate.commitments.hrmp_watermark = 10;
+		candidate
+	}
+
+	#[test]
+	fn committed_candidate_receipt_round_trip() {
+		let candidate = CommittedCandidateReceipt::from(committed_candidate(ParaId::from(1)));
+		round_trip(candidate.clone());
+
+		let json = serde_json::to_value(&candidate).unwrap();
+		assert_eq!(json["descriptor"]["paraId"], 1);
+		assert_eq!(json["commitments"]["upwardMessages"][0], "0x0102");
+		assert_eq!(json["commitments"]["horizontalMessages"][0]["recipient"], 2);
+		assert_eq!(json["commitments"]["horizontalMessages"][0]["data"], "0x0304");
+		assert_eq!(json["commitments"]["newValidationCode"], "0x0506");
+		assert_eq!(json["commitments"]["headData"], "0x0708");
+		assert_eq!(json["commitments"]["hrmpWatermark"], 10);
+	}
+
+	#[test]
+	fn core_state_round_trip() {
+		let occupied = CoreState::from(v1::CoreState::Occupied(v1::OccupiedCore {
+			para_id: ParaId::from(1),
+			next_up_on_available: Some(v1::ScheduledCore { para_id: ParaId::from(2), collator: None }),
+			occupied_since: 5,
+			time_out_at: 15,
+			next_up_on_time_out: None,
+			availability: bitvec::bitvec![bitvec::order::Lsb0, u8; 0, 1, 0],
+			group_responsible: GroupIndex(3),
+		}));
+
+		let json = serde_json::to_value(&occupied).unwrap();
+		assert_eq!(json["occupied"]["availability"], serde_json::json!([false, true, false]));
+		assert_eq!(json["occupied"]["nextUpOnAvailable"]["paraId"], 2);
+		assert_eq!(json["occupied"]["groupResponsible"], 3);
+
+		round_trip(occupied);
+		round_trip(CoreState::from(v1::CoreState::Scheduled(v1::ScheduledCore {
+			para_id: ParaId::from(1),
+			collator: None,
+		})));
+		round_trip(CoreState::from(v1::CoreState::Free));
+		assert_eq!(serde_json::to_string(&CoreState::Free).unwrap(), r#""free""#);
+	}
+
+	#[test]
+	fn validator_groups_round_trip() {
+		let groups = ValidatorGroups::from((
+			vec![vec![0, 1], vec![2]],
+			v1::GroupRotationInfo { session_start_block: 1, group_rotation_frequency: 10, now: 5 },
+		));
+
+		let json = serde_json::to_value(&groups).unwrap();
+		assert_eq!(json["rotationInfo"]["sessionStartBlock"], 1);
+		assert_eq!(json["rotationInfo"]["groupRotationFrequency"], 10);
+
+		round_trip(groups);
+	}
+
+	#[test]
+	fn candidate_event_notification_round_trip() {
+		let receipt = committed_candidate(ParaId::from(1)).to_plain();
+		let notification = CandidateEventNotification {
+			block_hash: Hash::repeat_byte(1),
+			block_status: BlockStatus::Best,
+			event: v1::CandidateEvent::CandidateIncluded(receipt, vec![1].into(), CoreIndex(2)).into(),
+		};
+
+		let json = serde_json::to_value(&notification).unwrap();
+		assert_eq!(json["blockStatus"], "best");
+		assert_eq!(json["event"]["candidateIncluded"][1], "0x01");
+		assert_eq!(json["event"]["candidateIncluded"][2], 2);
+
+		round_trip(notification);
+	}
+}
@@ -25,6 +25,7 @@ pub mod crowdfund;
 pub mod purchase;
 pub mod impls;
 pub mod paras_sudo_wrapper;
+pub mod paras_registrar;
 
 pub mod dummy;
 
@@ -0,0 +1,491 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Module to handle the registration of parathreads and parachains on top of the v1 `paras`
+//! module.
+//!
+//! Anyone may register a parathread by reserving a deposit, which is returned when the
+//! parathread is deregistered. Parachains are registered through the [`Registrar`] interface,
+//! e.g. by the `slots` module for auction winners, and may swap places with parathreads.
+
+use sp_std::prelude::*;
+use sp_runtime::{RuntimeDebug, traits::{AccountIdConversion, Saturating}};
+use codec::{Encode, Decode};
+use frame_support::{
+	decl_storage, decl_module, decl_event, decl_error, ensure,
+	dispatch::DispatchResult,
+	traits::{Currency, ReservableCurrency, Get},
+};
+use frame_system::ensure_signed;
+use primitives::v1::{Id as ParaId, ValidationCode, HeadData, LOWEST_USER_ID};
+use runtime_parachains::{
+	configuration,
+	paras::{self, ParaGenesisArgs},
+	router,
+};
+use crate::slots::{Registrar, SwapAux};
+
+type BalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+
+/// Information about a parathread registered with a deposit.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, RuntimeDebug)]
+pub struct ParaInfo<AccountId, Balance> {
+	/// The account that registered the parathread and may deregister it.
+	pub manager: AccountId,
+	/// The amount reserved from the manager for the registration.
+	pub deposit: Balance,
+}
+
+/// The module's configuration trait.
+pub trait Trait: paras::Trait + router::Trait {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+	/// The currency used for the registration deposits.
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The base deposit for registering a parathread.
+	type ParathreadDeposit: Get<BalanceOf<Self>>;
+
+	/// The deposit for every byte of the validation code and initial head data of a parathread.
+	type DataDepositPerByte: Get<BalanceOf<Self>>;
+
+	/// The handler for the other properties of paras swapping places, e.g. their leases.
+	type SwapAux: SwapAux;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Registrar {
+		/// The next free para ID.
+		NextFreeId get(fn next_free_id): ParaId = LOWEST_USER_ID;
+
+		/// The paras registered with a deposit, along with their managers.
+		Paras get(fn paras): map hasher(twox_64_concat) ParaId => Option<ParaInfo<T::AccountId, BalanceOf<T>>>;
+
+		/// The swaps requested on behalf of one para, mapped to the para to swap with. The swap
+		/// happens once the other para requests it as well.
+		PendingSwap get(fn pending_swap): map hasher(twox_64_concat) ParaId => Option<ParaId>;
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where
+		AccountId = <T as frame_system::Trait>::AccountId,
+	{
+		/// A parathread was registered. [para_id, manager]
+		Registered(ParaId, AccountId),
+		/// A para was deregistered. [para_id]
+		Deregistered(ParaId),
+		/// Two paras are to swap places at the start of the next session. [one, other]
+		Swapped(ParaId, ParaId),
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The para ID is already registered.
+		AlreadyRegistered,
+		/// The para ID is not registered.
+		NotRegistered,
+		/// The validation code is larger than allowed.
+		CodeTooLarge,
+		/// The head data is larger than allowed.
+		HeadDataTooLarge,
+		/// The caller doesn't control the para.
+		NotManager,
+		/// The para is a parachain, which must be deregistered through its lease ending.
+		IsParachain,
+		/// A swap must be between a parachain and a parathread.
+		CannotSwap,
+	}
+}
+
+decl_module! {
+	/// The paras registrar module.
+	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Register a parathread with the given initial head data and validation code. It is
+		/// initialized at the start of the next session.
+		///
+		/// A deposit of `ParathreadDeposit` and `DataDepositPerByte` for every byte of code and
+		/// head data is reserved from the caller, who becomes the manager of the parathread.
+		#[weight = 100_000_000]
+		pub fn register(
+			origin,
+			genesis_head: HeadData,
+			validation_code: ValidationCode,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			ensure!(
+				Self::head_data_size_allowed(genesis_head.0.len() as _),
+				Error::<T>::HeadDataTooLarge,
+			);
+			ensure!(
+				Self::code_size_allowed(validation_code.0.len() as _),
+				Error::<T>::CodeTooLarge,
+			);
+
+			let data_len = (genesis_head.0.len() + validation_code.0.len()) as u32;
+			let deposit = T::ParathreadDeposit::get()
+				.saturating_add(T::DataDepositPerByte::get().saturating_mul(data_len.into()));
+			<T as Trait>::Currency::reserve(&who, deposit)?;
+
+			let id = Self::new_id();
+			<Paras<T>>::insert(id, ParaInfo { manager: who.clone(), deposit });
+			paras::Module::<T>::schedule_para_initialize(id, ParaGenesisArgs {
+				genesis_head,
+				validation_code,
+				parachain: false,
+			});
+
+			Self::deposit_event(RawEvent::Registered(id, who));
+			Ok(())
+		}
+
+		/// Deregister a parathread registered by the caller. It is cleaned up at the start of the
+		/// next session and the deposit is returned.
+		#[weight = 100_000_000]
+		pub fn deregister(origin, id: ParaId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			let info = Self::paras(id).ok_or(Error::<T>::NotRegistered)?;
+			ensure!(info.manager == who, Error::<T>::NotManager);
+			ensure!(!paras::Module::<T>::parachains().contains(&id), Error::<T>::IsParachain);
+
+			Self::do_deregister(id);
+			Ok(())
+		}
+
+		/// Swap a parachain with a parathread, swapping their leases, deposits and managers.
+		///
+		/// The caller must control `id`, i.e. be its manager or its sovereign account. The swap
+		/// happens once it is requested on behalf of both paras, and the paras change places at
+		/// the start of the next session.
+		#[weight = 100_000_000]
+		pub fn swap(origin, id: ParaId, other: ParaId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			ensure!(Self::is_controller(&who, id), Error::<T>::NotManager);
+
+			if PendingSwap::get(other) == Some(id) {
+				let parachains = paras::Module::<T>::parachains();
+				ensure!(
+					parachains.contains(&id) != parachains.contains(&other),
+					Error::<T>::CannotSwap,
+				);
+				ensure!(
+					paras::Module::<T>::is_known_para(id) && paras::Module::<T>::is_known_para(other),
+					Error::<T>::NotRegistered,
+				);
+
+				T::SwapAux::ensure_can_swap(id, other)?;
+				T::SwapAux::on_swap(id, other)?;
+
+				<Paras<T>>::swap(id, other);
+				PendingSwap::remove(other);
+				paras::Module::<T>::schedule_para_swap(id, other);
+
+				Self::deposit_event(RawEvent::Swapped(id, other));
+			} else {
+				PendingSwap::insert(id, other);
+			}
+
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Whether the account may act on behalf of the para.
+	fn is_controller(who: &T::AccountId, id: ParaId) -> bool {
+		let sovereign: T::AccountId = id.into_account();
+		&sovereign == who || Self::paras(id).map_or(false, |info| &info.manager == who)
+	}
+
+	/// Schedule the para to be cleaned up and return the deposit held for it, if any.
+	fn do_deregister(id: ParaId) {
+		paras::Module::<T>::schedule_para_cleanup(id);
+		router::Module::<T>::schedule_para_cleanup(id);
+
+		if let Some(info) = <Paras<T>>::take(id) {
+			<T as Trait>::Currency::unreserve(&info.manager, info.deposit);
+		}
+		PendingSwap::remove(id);
+
+		Self::deposit_event(RawEvent::Deregistered(id));
+	}
+}
+
+impl<T: Trait> Registrar<T::AccountId> for Module<T> {
+	fn new_id() -> ParaId {
+		NextFreeId::mutate(|next| {
+			let id = *next;
+			*next = id + 1;
+			id
+		})
+	}
+
+	fn head_data_size_allowed(head_data_size: u32) -> bool {
+		head_data_size <= <configuration::Module<T>>::config().max_head_data_size
+	}
+
+	fn code_size_allowed(code_size: u32) -> bool {
+		code_size <= <configuration::Module<T>>::config().max_code_size
+	}
+
+	fn register_para(
+		id: ParaId,
+		parachain: bool,
+		code: ValidationCode,
+		initial_head_data: HeadData,
+	) -> DispatchResult {
+		ensure!(!paras::Module::<T>::is_known_para(id), Error::<T>::AlreadyRegistered);
+
+		paras::Module::<T>::schedule_para_initialize(id, ParaGenesisArgs {
+			genesis_head: initial_head_data,
+			validation_code: code,
+			parachain,
+		});
+
+		Ok(())
+	}
+
+	fn deregister_para(id: ParaId) -> DispatchResult {
+		ensure!(paras::Module::<T>::is_known_para(id), Error::<T>::NotRegistered);
+
+		Self::do_deregister(id);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+	use sp_runtime::{
+		Perbill,
+		traits::{BlakeTwo256, IdentityLookup},
+	};
+	use frame_support::{impl_outer_origin, parameter_types, assert_ok, assert_noop};
+	use primitives::v1::{BlockNumber, Header};
+	use runtime_parachains::configuration::HostConfiguration;
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u32 = 250;
+		pub const MaximumBlockWeight: u32 = 4 * 1024 * 1024;
+		pub const MaximumBlockLength: u32 = 4 * 1024 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	}
+	impl frame_system::Trait for Test {
+		type BaseCallFilter = ();
+		type Origin = Origin;
+		type Call = ();
+		type Index = u64;
+		type BlockNumber = BlockNumber;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+		type AccountData = pallet_balances::AccountData<u64>;
+		type OnNewAccount = ();
+		type OnKilledAccount = Balances;
+		type SystemWeightInfo = ();
+	}
+
+	parameter_types! {
+		pub const ExistentialDeposit: u64 = 1;
+	}
+
+	impl pallet_balances::Trait for Test {
+		type Balance = u64;
+		type Event = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type AccountStore = System;
+		type MaxLocks = ();
+		type WeightInfo = ();
+	}
+
+	impl configuration::Trait for Test { }
+
+	impl paras::Trait for Test { }
+
+	impl router::Trait for Test {
+		type UmpSink = ();
+		type Currency = Balances;
+	}
+
+	parameter_types! {
+		pub const ParathreadDeposit: u64 = 10;
+		pub const DataDepositPerByte: u64 = 1;
+	}
+
+	impl Trait for Test {
+		type Event = ();
+		type Currency = Balances;
+		type ParathreadDeposit = ParathreadDeposit;
+		type DataDepositPerByte = DataDepositPerByte;
+		type SwapAux = ();
+	}
+
+	type System = frame_system::Module<Test>;
+	type Balances = pallet_balances::Module<Test>;
+	type Paras = paras::Module<Test>;
+	type ParasRegistrar = Module<Test>;
+
+	const MAX_CODE_SIZE: u32 = 100;
+	const MAX_HEAD_DATA_SIZE: u32 = 10;
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+
+		pallet_balances::GenesisConfig::<Test> {
+			balances: vec![(1, 100), (2, 100)],
+		}.assimilate_storage(&mut t).unwrap();
+
+		configuration::GenesisConfig::<Test> {
+			config: HostConfiguration {
+				max_code_size: MAX_CODE_SIZE,
+				max_head_data_size: MAX_HEAD_DATA_SIZE,
+				..Default::default()
+			},
+		}.assimilate_storage(&mut t).unwrap();
+
+		t.into()
+	}
+
+	#[test]
+	fn register_reserves_deposit_and_schedules_parathread() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(ParasRegistrar::register(Origin::signed(1), vec![1; 5].into(), vec![1; 20].into()));
+
+			let id = LOWEST_USER_ID;
+			assert_eq!(ParasRegistrar::next_free_id(), id + 1);
+			assert_eq!(ParasRegistrar::paras(id), Some(ParaInfo { manager: 1, deposit: 10 + 25 }));
+			assert_eq!(Balances::reserved_balance(1), 35);
+			assert!(Paras::is_known_para(id));
+		});
+	}
+
+	#[test]
+	fn register_checks_sizes() {
+		new_test_ext().execute_with(|| {
+			assert_noop!(
+				ParasRegistrar::register(
+					Origin::signed(1),
+					vec![1; MAX_HEAD_DATA_SIZE as usize + 1].into(),
+					vec![1].into(),
+				),
+				Error::<Test>::HeadDataTooLarge,
+			);
+			assert_noop!(
+				ParasRegistrar::register(
+					Origin::signed(1),
+					vec![1].into(),
+					vec![1; MAX_CODE_SIZE as usize + 1].into(),
+				),
+				Error::<Test>::CodeTooLarge,
+			);
+		});
+	}
+
+	#[test]
+	fn register_requires_deposit() {
+		new_test_ext().execute_with(|| {
+			assert!(ParasRegistrar::register(Origin::signed(3), vec![1].into(), vec![1].into()).is_err());
+			assert_eq!(ParasRegistrar::next_free_id(), LOWEST_USER_ID);
+		});
+	}
+
+	#[test]
+	fn deregister_refunds_deposit() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(ParasRegistrar::register(Origin::signed(1), vec![1].into(), vec![1].into()));
+			let id = LOWEST_USER_ID;
+
+			assert_noop!(ParasRegistrar::deregister(Origin::signed(2), id), Error::<Test>::NotManager);
+			assert_noop!(ParasRegistrar::deregister(Origin::signed(1), id + 1), Error::<Test>::NotRegistered);
+
+			assert_ok!(ParasRegistrar::deregister(Origin::signed(1), id));
+			assert_eq!(ParasRegistrar::paras(id), None);
+			assert_eq!(Balances::reserved_balance(1), 0);
+			assert_eq!(Balances::free_balance(1), 100);
+		});
+	}
+
+	#[test]
+	fn register_para_rejects_known_ids() {
+		new_test_ext().execute_with(|| {
+			let id = ParasRegistrar::new_id();
+			assert_ok!(ParasRegistrar::register_para(id, true, vec![1].into(), vec![1].into()));
+			assert!(Paras::is_known_para(id));
+
+			assert_noop!(
+				ParasRegistrar::register_para(id, false, vec![2].into(), vec![2].into()),
+				Error::<Test>::AlreadyRegistered,
+			);
+
+			assert_ok!(ParasRegistrar::deregister_para(id));
+			assert_noop!(ParasRegistrar::deregister_para(id + 1), Error::<Test>::NotRegistered);
+		});
+	}
+
+	#[test]
+	fn swap_requires_both_sides() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(ParasRegistrar::register(Origin::signed(1), vec![1].into(), vec![1].into()));
+			let thread = LOWEST_USER_ID;
+			let chain = ParasRegistrar::new_id();
+			assert_ok!(ParasRegistrar::register_para(chain, true, vec![2].into(), vec![2].into()));
+
+			assert_noop!(ParasRegistrar::swap(Origin::signed(2), thread, chain), Error::<Test>::NotManager);
+
+			assert_ok!(ParasRegistrar::swap(Origin::signed(1), thread, chain));
+			assert_eq!(ParasRegistrar::pending_swap(thread), Some(chain));
+			assert_eq!(ParasRegistrar::paras(thread).map(|i| i.manager), Some(1));
+
+			// the chain isn't live yet, so both are treated as parathreads.
+			let chain_account: u64 = chain.into_account();
+			assert_noop!(
+				ParasRegistrar::swap(Origin::signed(chain_account), chain, thread),
+				Error::<Test>::CannotSwap,
+			);
+		});
+	}
+}
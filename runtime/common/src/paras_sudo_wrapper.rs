@@ -17,22 +17,28 @@
 //! A simple wrapper allowing `Sudo` to call into `paras` routines.
 
 use frame_support::{
-	decl_error, decl_module,
+	decl_error, decl_module, ensure,
 	dispatch::DispatchResult,
 	weights::DispatchClass,
 };
 use frame_system::ensure_root;
 use runtime_parachains::{
-	router,
+	configuration, router,
 	paras::{self, ParaGenesisArgs},
 };
-use primitives::v1::Id as ParaId;
+use primitives::v1::{Id as ParaId, DownwardMessage};
 
 /// The module's configuration trait.
 pub trait Trait: paras::Trait + router::Trait { }
 
 decl_error! {
-	pub enum Error for Module<T: Trait> { }
+	pub enum Error for Module<T: Trait> {
+		/// The specified parachain or parathread is not registered.
+		ParaDoesntExist,
+		/// A DMP message couldn't be sent because it exceeds the maximum size allowed for a downward
+		/// message.
+		ExceedsMaxMessageSize,
+	}
 }
 
 decl_module! {
@@ -60,5 +66,192 @@ decl_module! {
 			router::Module::<T>::schedule_para_cleanup(id);
 			Ok(())
 		}
+
+		/// Send a downward message to the given para.
+		///
+		/// The given parachain should exist and the payload should not exceed the preconfigured size
+		/// `config.critical_downward_message_size`.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn sudo_queue_downward_message(origin, id: ParaId, msg: DownwardMessage) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(paras::Module::<T>::is_known_para(id), Error::<T>::ParaDoesntExist);
+			let config = <configuration::Module<T>>::config();
+			<router::Module<T>>::queue_downward_message(&config, id, msg)
+				.map_err(|e| match e {
+					router::QueueDownwardMessageError::ExceedsCriticalMessageSize =>
+						Error::<T>::ExceedsMaxMessageSize.into(),
+				})
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+	use sp_runtime::{
+		Perbill, DispatchError,
+		traits::{BlakeTwo256, IdentityLookup},
+	};
+	use frame_support::{impl_outer_origin, parameter_types, assert_ok, assert_noop};
+	use primitives::v1::{BlockNumber, Header};
+	use runtime_parachains::configuration::HostConfiguration;
+
+	impl_outer_origin! {
+		pub enum Origin for Test {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u32 = 250;
+		pub const MaximumBlockWeight: u32 = 4 * 1024 * 1024;
+		pub const MaximumBlockLength: u32 = 4 * 1024 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	}
+	impl frame_system::Trait for Test {
+		type BaseCallFilter = ();
+		type Origin = Origin;
+		type Call = ();
+		type Index = u64;
+		type BlockNumber = BlockNumber;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+		type AccountData = pallet_balances::AccountData<u64>;
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+	}
+
+	parameter_types! {
+		pub const ExistentialDeposit: u64 = 1;
+	}
+
+	impl pallet_balances::Trait for Test {
+		type Balance = u64;
+		type Event = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type AccountStore = frame_system::Module<Test>;
+		type MaxLocks = ();
+		type WeightInfo = ();
+	}
+
+	impl configuration::Trait for Test { }
+
+	impl paras::Trait for Test { }
+
+	impl router::Trait for Test {
+		type UmpSink = ();
+		type Currency = pallet_balances::Module<Test>;
+	}
+
+	impl Trait for Test { }
+
+	type Router = router::Module<Test>;
+	type ParasSudoWrapper = Module<Test>;
+
+	const CRITICAL_DOWNWARD_MESSAGE_SIZE: u32 = 16;
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+
+		configuration::GenesisConfig::<Test> {
+			config: HostConfiguration {
+				critical_downward_message_size: CRITICAL_DOWNWARD_MESSAGE_SIZE,
+				..Default::default()
+			},
+		}.assimilate_storage(&mut t).unwrap();
+
+		t.into()
+	}
+
+	fn schedule_para(id: ParaId) {
+		assert_ok!(ParasSudoWrapper::sudo_schedule_para_initialize(
+			Origin::root(),
+			id,
+			ParaGenesisArgs {
+				genesis_head: vec![1].into(),
+				validation_code: vec![1].into(),
+				parachain: true,
+			},
+		));
+	}
+
+	#[test]
+	fn queue_downward_message_lands_in_the_dmq() {
+		new_test_ext().execute_with(|| {
+			let para = ParaId::from(100);
+			schedule_para(para);
+
+			assert_ok!(ParasSudoWrapper::sudo_queue_downward_message(
+				Origin::root(),
+				para,
+				DownwardMessage::Opaque(vec![1, 2, 3]),
+			));
+
+			let contents = Router::dmq_contents(para);
+			assert_eq!(contents.len(), 1);
+			assert_eq!(contents[0].msg, DownwardMessage::Opaque(vec![1, 2, 3]));
+		});
+	}
+
+	#[test]
+	fn queue_downward_message_requires_root() {
+		new_test_ext().execute_with(|| {
+			let para = ParaId::from(100);
+			schedule_para(para);
+
+			assert_noop!(
+				ParasSudoWrapper::sudo_queue_downward_message(
+					Origin::signed(1),
+					para,
+					DownwardMessage::Opaque(vec![1, 2, 3]),
+				),
+				DispatchError::BadOrigin,
+			);
+		});
+	}
+
+	#[test]
+	fn queue_downward_message_checks_recipient_and_size() {
+		new_test_ext().execute_with(|| {
+			let para = ParaId::from(100);
+
+			assert_noop!(
+				ParasSudoWrapper::sudo_queue_downward_message(
+					Origin::root(),
+					para,
+					DownwardMessage::Opaque(vec![1, 2, 3]),
+				),
+				Error::<Test>::ParaDoesntExist,
+			);
+
+			schedule_para(para);
+
+			assert_noop!(
+				ParasSudoWrapper::sudo_queue_downward_message(
+					Origin::root(),
+					para,
+					DownwardMessage::Opaque(vec![0; CRITICAL_DOWNWARD_MESSAGE_SIZE as usize]),
+				),
+				Error::<Test>::ExceedsMaxMessageSize,
+			);
+			assert!(Router::dmq_contents(para).is_empty());
+		});
 	}
 }
@@ -101,6 +101,17 @@ pub trait SwapAux {
 	fn on_swap(one: ParaId, other: ParaId) -> Result<(), &'static str>;
 }
 
+/// For runtimes without leases, there is nothing else to swap.
+impl SwapAux for () {
+	fn ensure_can_swap(_: ParaId, _: ParaId) -> Result<(), &'static str> {
+		Ok(())
+	}
+
+	fn on_swap(_: ParaId, _: ParaId) -> Result<(), &'static str> {
+		Ok(())
+	}
+}
+
 /// A sub-bidder identifier. Used to distinguish between different logical bidders coming from the
 /// same account ID.
 pub type SubId = u32;
@@ -19,7 +19,7 @@
 //! Configuration can change only at session boundaries and is buffered until then.
 
 use sp_std::prelude::*;
-use primitives::v1::ValidatorId;
+use primitives::v1::{Balance, SessionIndex, ValidatorId};
 use frame_support::{
 	decl_storage, decl_module, decl_error,
 	dispatch::DispatchResult,
@@ -39,6 +39,9 @@ pub struct HostConfiguration<BlockNumber> {
 	/// The acceptance period, in blocks. This is the amount of blocks after availability that validators
 	/// and fishermen have to perform secondary checks or issue reports.
 	pub acceptance_period: BlockNumber,
+	/// The dispute period, in sessions. This is the amount of sessions after the session a candidate
+	/// was included in during which disputes about the candidate are accepted.
+	pub dispute_period: SessionIndex,
 	/// The maximum validation code size, in bytes.
 	pub max_code_size: u32,
 	/// The maximum head-data size, in bytes.
@@ -47,6 +50,9 @@ pub struct HostConfiguration<BlockNumber> {
 	pub parathread_cores: u32,
 	/// The number of retries that a parathread author has to submit their block.
 	pub parathread_retries: u32,
+	/// The fee for submitting a parathread claim. It is burned once a candidate is backed for
+	/// the claim or the claim runs out of retries, and refunded otherwise.
+	pub parathread_claim_fee: Balance,
 	/// How often parachain groups should be rotated across parachains. Must be non-zero.
 	pub group_rotation_frequency: BlockNumber,
 	/// The availability period, in blocks, for parachains. This is the amount of blocks
@@ -58,6 +64,82 @@ pub struct HostConfiguration<BlockNumber> {
 	pub thread_availability_period: BlockNumber,
 	/// The amount of blocks ahead to schedule parachains and parathreads.
 	pub scheduling_lookahead: u32,
+	/// Total number of individual messages allowed in the parachain -> relay-chain message queue.
+	pub max_upward_queue_count: u32,
+	/// Total size of messages allowed in the parachain -> relay-chain message queue before which
+	/// no further messages may be added to it.
+	pub max_upward_queue_size: u32,
+	/// The maximum size of an upward message that can be sent by a candidate.
+	pub max_upward_message_size: u32,
+	/// The maximum number of messages that a candidate can contain.
+	pub max_upward_message_num_per_candidate: u32,
+	/// The amount of weight we wish to devote to the processing the dispatchable upward messages
+	/// stage.
+	///
+	/// NOTE that this is a soft limit and could be exceeded.
+	pub preferred_dispatchable_upward_messages_step_weight: Weight,
+	/// Any dispatchable upward message that requests more than the critical amount is rejected.
+	///
+	/// The parameter value is picked up so that no dispatchable can make the block weight exceed
+	/// the total budget. I.e. that the sum of `preferred_dispatchable_upward_messages_step_weight`
+	/// and `dispatchable_upward_message_critical_weight` doesn't exceed the amount of weight left
+	/// under a typical worst case (e.g. no upgrades, etc) weight consumed by the required phases of
+	/// block execution (i.e. initialization, finalization and inherents).
+	pub dispatchable_upward_message_critical_weight: Weight,
+	/// The maximum size of a message that can be put in a downward message queue.
+	///
+	/// Since we require receiving at least one DMP message the obvious upper bound of the size is
+	/// the PoV size. Of course, there is a lot of other different things that a parachain may
+	/// decide to do with its PoV so this value in practice will be picked as a fraction of the PoV
+	/// size.
+	pub critical_downward_message_size: u32,
+	/// Number of sessions after which an HRMP open channel request expires.
+	pub hrmp_open_request_ttl: u32,
+	/// The deposit that the sender should provide for opening an HRMP channel.
+	pub hrmp_sender_deposit: Balance,
+	/// The deposit that the recipient should provide for accepting opening an HRMP channel.
+	pub hrmp_recipient_deposit: Balance,
+	/// The maximum number of messages allowed in an HRMP channel at once.
+	pub hrmp_channel_max_places: u32,
+	/// The maximum total size of messages in bytes allowed in an HRMP channel at once.
+	pub hrmp_channel_max_size: u32,
+	/// The maximum number of inbound HRMP channels a parachain is allowed to accept.
+	pub hrmp_max_parachain_inbound_channels: u32,
+	/// The maximum number of inbound HRMP channels a parathread is allowed to accept.
+	pub hrmp_max_parathread_inbound_channels: u32,
+	/// The maximum size of a message that could ever be put into an HRMP channel.
+	pub hrmp_channel_max_message_size: u32,
+	/// The maximum number of outbound HRMP channels a parachain is allowed to open.
+	pub hrmp_max_parachain_outbound_channels: u32,
+	/// The maximum number of outbound HRMP channels a parathread is allowed to open.
+	pub hrmp_max_parathread_outbound_channels: u32,
+	/// The maximum number of outbound HRMP messages can be sent by a candidate.
+	pub hrmp_max_message_num_per_candidate: u32,
+	/// The number of validators which need to approve a candidate.
+	pub needed_approvals: u32,
+	/// The number of `RelayVRFModulo` samples each validator takes for approval assignments.
+	pub relay_vrf_modulo_samples: u32,
+	/// The number of delay tranches of approval assignments in total.
+	pub n_delay_tranches: u32,
+	/// The width of the zeroth delay tranche of approval assignments.
+	pub zeroth_delay_tranche_width: u32,
+	/// The number of slots after receiving their assignment that validators are given to
+	/// approve a candidate before they are considered no-shows.
+	pub no_show_slots: u32,
+	/// The maximum number of 64 KiB memory pages a validation function may use, including its
+	/// heap. If `None`, `DEFAULT_MAX_PVF_MEMORY_PAGES` is used.
+	pub max_pvf_memory_pages: Option<u32>,
+	/// The maximum logical stack height of a validation function. If `None`,
+	/// `DEFAULT_MAX_PVF_STACK_HEIGHT` is used.
+	pub max_pvf_stack_height: Option<u32>,
+	/// The fuel a validation function may consume. If `None`, fuel is not limited.
+	pub max_pvf_fuel: Option<u64>,
+	/// The timeout of the execution of a validation function during backing, in milliseconds.
+	/// If `None`, `DEFAULT_BACKING_EXECUTION_TIMEOUT_MS` is used.
+	pub pvf_backing_timeout: Option<u32>,
+	/// The timeout of the execution of a validation function during approval checking, in
+	/// milliseconds. If `None`, `DEFAULT_APPROVAL_EXECUTION_TIMEOUT_MS` is used.
+	pub pvf_approval_timeout: Option<u32>,
 }
 
 pub trait Trait: frame_system::Trait { }
@@ -110,6 +192,16 @@ decl_module! {
 			Ok(())
 		}
 
+		/// Set the dispute period, in number of sessions to keep for disputes.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_dispute_period(origin, new: SessionIndex) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.dispute_period, new) != new
+			});
+			Ok(())
+		}
+
 		/// Set the max validation code size for incoming upgrades.
 		#[weight = (1_000, DispatchClass::Operational)]
 		pub fn set_max_code_size(origin, new: u32) -> DispatchResult {
@@ -150,6 +242,16 @@ decl_module! {
 			Ok(())
 		}
 
+		/// Set the fee for submitting a parathread claim.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_parathread_claim_fee(origin, new: Balance) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.parathread_claim_fee, new) != new
+			});
+			Ok(())
+		}
+
 
 		/// Set the parachain validator-group rotation frequency
 		#[weight = (1_000, DispatchClass::Operational)]
@@ -190,6 +292,286 @@ decl_module! {
 			});
 			Ok(())
 		}
+
+		/// Sets the maximum items that can present in a upward dispatch queue at once.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_upward_queue_count(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_upward_queue_count, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum total size of items that can present in a upward dispatch queue at once.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_upward_queue_size(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_upward_queue_size, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum size of an upward message that can be sent by a candidate.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_upward_message_size(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_upward_message_size, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum number of messages that a candidate can contain.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_upward_message_num_per_candidate(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_upward_message_num_per_candidate, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the soft limit for the phase of dispatching dispatchable upward messages.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_preferred_dispatchable_upward_messages_step_weight(origin, new: Weight) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.preferred_dispatchable_upward_messages_step_weight, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum weight that a single dispatchable upward message is allowed to consume.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_dispatchable_upward_message_critical_weight(origin, new: Weight) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.dispatchable_upward_message_critical_weight, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum size of a message that can be put in a downward message queue.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_critical_downward_message_size(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.critical_downward_message_size, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the number of sessions after which an HRMP open channel request expires.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_open_request_ttl(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_open_request_ttl, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the amount of funds that the sender should provide for opening an HRMP channel.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_sender_deposit(origin, new: Balance) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_sender_deposit, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the amount of funds that the recipient should provide for accepting opening an HRMP channel.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_recipient_deposit(origin, new: Balance) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_recipient_deposit, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum number of messages allowed in an HRMP channel at once.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_channel_max_places(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_channel_max_places, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum total size of messages in bytes allowed in an HRMP channel at once.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_channel_max_size(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_channel_max_size, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum number of inbound HRMP channels a parachain is allowed to accept.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_max_parachain_inbound_channels(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_max_parachain_inbound_channels, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum number of inbound HRMP channels a parathread is allowed to accept.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_max_parathread_inbound_channels(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_max_parathread_inbound_channels, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum size of a message that could ever be put into an HRMP channel.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_channel_max_message_size(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_channel_max_message_size, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum number of outbound HRMP channels a parachain is allowed to open.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_max_parachain_outbound_channels(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_max_parachain_outbound_channels, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum number of outbound HRMP channels a parathread is allowed to open.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_max_parathread_outbound_channels(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_max_parathread_outbound_channels, new) != new
+			});
+			Ok(())
+		}
+
+		/// Sets the maximum number of outbound HRMP messages can be sent by a candidate.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_max_message_num_per_candidate(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_max_message_num_per_candidate, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the number of validators which need to approve a candidate.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_needed_approvals(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.needed_approvals, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the number of `RelayVRFModulo` samples each validator takes.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_relay_vrf_modulo_samples(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.relay_vrf_modulo_samples, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the total number of delay tranches of approval assignments.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_n_delay_tranches(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.n_delay_tranches, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the width of the zeroth delay tranche of approval assignments.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_zeroth_delay_tranche_width(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.zeroth_delay_tranche_width, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the number of slots after which validators who don't approve are no-shows.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_no_show_slots(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.no_show_slots, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum number of memory pages a validation function may use.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_pvf_memory_pages(origin, new: Option<u32>) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_pvf_memory_pages, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum logical stack height of a validation function.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_pvf_stack_height(origin, new: Option<u32>) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_pvf_stack_height, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the fuel a validation function may consume.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_pvf_fuel(origin, new: Option<u64>) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_pvf_fuel, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the timeout of the execution of a validation function during backing.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_pvf_backing_timeout(origin, new: Option<u32>) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.pvf_backing_timeout, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the timeout of the execution of a validation function during approval checking.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_pvf_approval_timeout(origin, new: Option<u32>) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.pvf_approval_timeout, new) != new
+			});
+			Ok(())
+		}
 	}
 }
 
@@ -260,14 +642,44 @@ mod tests {
 				validation_upgrade_frequency: 100,
 				validation_upgrade_delay: 10,
 				acceptance_period: 5,
+				dispute_period: 2,
 				max_code_size: 100_000,
 				max_head_data_size: 1_000,
 				parathread_cores: 2,
 				parathread_retries: 5,
+				parathread_claim_fee: 50,
 				group_rotation_frequency: 20,
 				chain_availability_period: 10,
 				thread_availability_period: 8,
 				scheduling_lookahead: 3,
+				max_upward_queue_count: 1337,
+				max_upward_queue_size: 228,
+				max_upward_message_size: 64,
+				max_upward_message_num_per_candidate: 5,
+				preferred_dispatchable_upward_messages_step_weight: 20000,
+				dispatchable_upward_message_critical_weight: 6000,
+				critical_downward_message_size: 1024,
+				hrmp_open_request_ttl: 3,
+				hrmp_sender_deposit: 100,
+				hrmp_recipient_deposit: 80,
+				hrmp_channel_max_places: 10,
+				hrmp_channel_max_size: 1024,
+				hrmp_max_parachain_inbound_channels: 30,
+				hrmp_max_parathread_inbound_channels: 2,
+				hrmp_channel_max_message_size: 8,
+				hrmp_max_parachain_outbound_channels: 20,
+				hrmp_max_parathread_outbound_channels: 1,
+				hrmp_max_message_num_per_candidate: 5,
+				needed_approvals: 10,
+				relay_vrf_modulo_samples: 3,
+				n_delay_tranches: 40,
+				zeroth_delay_tranche_width: 1,
+				no_show_slots: 2,
+				max_pvf_memory_pages: Some(2048),
+				max_pvf_stack_height: Some(1024),
+				max_pvf_fuel: Some(1_000_000_000),
+				pvf_backing_timeout: Some(2_000),
+				pvf_approval_timeout: Some(6_000),
 			};
 
 			assert!(<Configuration as Store>::PendingConfig::get().is_none());
@@ -281,6 +693,9 @@ mod tests {
 			Configuration::set_acceptance_period(
 				Origin::root(), new_config.acceptance_period,
 			).unwrap();
+			Configuration::set_dispute_period(
+				Origin::root(), new_config.dispute_period,
+			).unwrap();
 			Configuration::set_max_code_size(
 				Origin::root(), new_config.max_code_size,
 			).unwrap();
@@ -293,6 +708,9 @@ mod tests {
 			Configuration::set_parathread_retries(
 				Origin::root(), new_config.parathread_retries,
 			).unwrap();
+			Configuration::set_parathread_claim_fee(
+				Origin::root(), new_config.parathread_claim_fee,
+			).unwrap();
 			Configuration::set_group_rotation_frequency(
 				Origin::root(), new_config.group_rotation_frequency,
 			).unwrap();
@@ -305,6 +723,90 @@ mod tests {
 			Configuration::set_scheduling_lookahead(
 				Origin::root(), new_config.scheduling_lookahead,
 			).unwrap();
+			Configuration::set_max_upward_queue_count(
+				Origin::root(), new_config.max_upward_queue_count,
+			).unwrap();
+			Configuration::set_max_upward_queue_size(
+				Origin::root(), new_config.max_upward_queue_size,
+			).unwrap();
+			Configuration::set_max_upward_message_size(
+				Origin::root(), new_config.max_upward_message_size,
+			).unwrap();
+			Configuration::set_max_upward_message_num_per_candidate(
+				Origin::root(), new_config.max_upward_message_num_per_candidate,
+			).unwrap();
+			Configuration::set_preferred_dispatchable_upward_messages_step_weight(
+				Origin::root(), new_config.preferred_dispatchable_upward_messages_step_weight,
+			).unwrap();
+			Configuration::set_dispatchable_upward_message_critical_weight(
+				Origin::root(), new_config.dispatchable_upward_message_critical_weight,
+			).unwrap();
+			Configuration::set_critical_downward_message_size(
+				Origin::root(), new_config.critical_downward_message_size,
+			).unwrap();
+			Configuration::set_hrmp_open_request_ttl(
+				Origin::root(), new_config.hrmp_open_request_ttl,
+			).unwrap();
+			Configuration::set_hrmp_sender_deposit(
+				Origin::root(), new_config.hrmp_sender_deposit,
+			).unwrap();
+			Configuration::set_hrmp_recipient_deposit(
+				Origin::root(), new_config.hrmp_recipient_deposit,
+			).unwrap();
+			Configuration::set_hrmp_channel_max_places(
+				Origin::root(), new_config.hrmp_channel_max_places,
+			).unwrap();
+			Configuration::set_hrmp_channel_max_size(
+				Origin::root(), new_config.hrmp_channel_max_size,
+			).unwrap();
+			Configuration::set_hrmp_max_parachain_inbound_channels(
+				Origin::root(), new_config.hrmp_max_parachain_inbound_channels,
+			).unwrap();
+			Configuration::set_hrmp_max_parathread_inbound_channels(
+				Origin::root(), new_config.hrmp_max_parathread_inbound_channels,
+			).unwrap();
+			Configuration::set_hrmp_channel_max_message_size(
+				Origin::root(), new_config.hrmp_channel_max_message_size,
+			).unwrap();
+			Configuration::set_hrmp_max_parachain_outbound_channels(
+				Origin::root(), new_config.hrmp_max_parachain_outbound_channels,
+			).unwrap();
+			Configuration::set_hrmp_max_parathread_outbound_channels(
+				Origin::root(), new_config.hrmp_max_parathread_outbound_channels,
+			).unwrap();
+			Configuration::set_hrmp_max_message_num_per_candidate(
+				Origin::root(), new_config.hrmp_max_message_num_per_candidate,
+			).unwrap();
+			Configuration::set_needed_approvals(
+				Origin::root(), new_config.needed_approvals,
+			).unwrap();
+			Configuration::set_relay_vrf_modulo_samples(
+				Origin::root(), new_config.relay_vrf_modulo_samples,
+			).unwrap();
+			Configuration::set_n_delay_tranches(
+				Origin::root(), new_config.n_delay_tranches,
+			).unwrap();
+			Configuration::set_zeroth_delay_tranche_width(
+				Origin::root(), new_config.zeroth_delay_tranche_width,
+			).unwrap();
+			Configuration::set_no_show_slots(
+				Origin::root(), new_config.no_show_slots,
+			).unwrap();
+			Configuration::set_max_pvf_memory_pages(
+				Origin::root(), new_config.max_pvf_memory_pages,
+			).unwrap();
+			Configuration::set_max_pvf_stack_height(
+				Origin::root(), new_config.max_pvf_stack_height,
+			).unwrap();
+			Configuration::set_max_pvf_fuel(
+				Origin::root(), new_config.max_pvf_fuel,
+			).unwrap();
+			Configuration::set_pvf_backing_timeout(
+				Origin::root(), new_config.pvf_backing_timeout,
+			).unwrap();
+			Configuration::set_pvf_approval_timeout(
+				Origin::root(), new_config.pvf_approval_timeout,
+			).unwrap();
 
 			assert_eq!(<Configuration as Store>::PendingConfig::get(), Some(new_config));
 		})
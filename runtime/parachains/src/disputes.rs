@@ -0,0 +1,831 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The disputes module is responsible for resolving disputes about the validity of candidates.
+//!
+//! Signed statements about the validity of candidates are submitted through the inclusion
+//! inherent. There is at most one dispute per candidate per session, which concludes once a
+//! supermajority of the session's validators agrees on the validity of the candidate. The losing
+//! side is punished, and if a candidate included on this fork is concluded invalid, a signal to
+//! revert the chain is issued in the header digest.
+//!
+//! Concluded disputes stay open until their session leaves the dispute period, so that late
+//! voters on the losing side can be punished as well.
+
+use sp_std::prelude::*;
+use sp_std::collections::btree_set::BTreeSet;
+use primitives::v1::{
+	CompactStatement, ConsensusLog, DisputeStatementSets, Hash, SessionIndex, ValidatorId,
+	ValidatorIndex,
+};
+use frame_support::{
+	decl_storage, decl_module, decl_error, decl_event, ensure, weights::Weight,
+};
+use codec::{Encode, Decode};
+use bitvec::{order::Lsb0 as BitOrderLsb0, vec::BitVec};
+use sp_runtime::{DispatchError, Perbill, RuntimeDebug, traits::Convert};
+use sp_staking::offence::{Kind, Offence, ReportOffence};
+use pallet_session::historical::IdentificationTuple;
+
+use crate::{configuration, initializer::SessionChangeNotification};
+
+/// The result of a concluded dispute.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum DisputeResult {
+	/// A supermajority of validators attested to the validity of the candidate.
+	Valid,
+	/// A supermajority of validators attested to the invalidity of the candidate.
+	Invalid,
+}
+
+/// The state of a dispute about a single candidate.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct DisputeState<N> {
+	/// The validators that attested to the validity of the candidate. One bit per validator of
+	/// the session.
+	validators_for: BitVec<BitOrderLsb0, u8>,
+	/// The validators that attested to the invalidity of the candidate. One bit per validator of
+	/// the session.
+	validators_against: BitVec<BitOrderLsb0, u8>,
+	/// The block number at which the dispute was initiated.
+	start: N,
+	/// The block number at which the dispute was concluded and its result, if it has concluded.
+	concluded: Option<(N, DisputeResult)>,
+}
+
+impl<N> DisputeState<N> {
+	/// The validators on the losing side of the dispute, given its result.
+	fn losers(&self, result: DisputeResult) -> &BitVec<BitOrderLsb0, u8> {
+		match result {
+			DisputeResult::Valid => &self.validators_against,
+			DisputeResult::Invalid => &self.validators_for,
+		}
+	}
+}
+
+/// Punishment of the validators on the losing side of concluded disputes.
+pub trait PunishValidators<AccountId> {
+	/// Punish validators that attested to the validity of a candidate which was concluded invalid.
+	fn punish_for_invalid(
+		session: SessionIndex,
+		candidate_hash: Hash,
+		validator_set_count: u32,
+		losers: Vec<AccountId>,
+	);
+
+	/// Punish validators that attested to the invalidity of a candidate which was concluded valid.
+	fn punish_against_valid(
+		session: SessionIndex,
+		candidate_hash: Hash,
+		validator_set_count: u32,
+		losers: Vec<AccountId>,
+	);
+}
+
+/// An implementation which doesn't punish anyone.
+impl<AccountId> PunishValidators<AccountId> for () {
+	fn punish_for_invalid(_: SessionIndex, _: Hash, _: u32, _: Vec<AccountId>) { }
+
+	fn punish_against_valid(_: SessionIndex, _: Hash, _: u32, _: Vec<AccountId>) { }
+}
+
+/// The time slot of the offences committed by losing a dispute. Every validator can commit at most
+/// one such offence per candidate.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, PartialOrd, Ord, RuntimeDebug)]
+pub struct DisputeTimeSlot {
+	/// The session the disputed candidate was included in.
+	pub session_index: SessionIndex,
+	/// The hash of the disputed candidate.
+	pub candidate_hash: Hash,
+}
+
+/// The offence of attesting to the validity of a candidate which was concluded invalid.
+#[derive(RuntimeDebug)]
+pub struct ForInvalidOffence<Offender> {
+	/// The time slot of the offence.
+	pub time_slot: DisputeTimeSlot,
+	/// The size of the validator set in the session of the offence.
+	pub validator_set_count: u32,
+	/// The validators on the losing side.
+	pub offenders: Vec<Offender>,
+}
+
+impl<Offender: Clone> Offence<Offender> for ForInvalidOffence<Offender> {
+	const ID: Kind = *b"disputes:invalid";
+	type TimeSlot = DisputeTimeSlot;
+
+	fn offenders(&self) -> Vec<Offender> {
+		self.offenders.clone()
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.time_slot.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> DisputeTimeSlot {
+		self.time_slot.clone()
+	}
+
+	fn slash_fraction(_offenders_count: u32, _validator_set_count: u32) -> Perbill {
+		// attesting to an invalid candidate is the worst thing a validator can do.
+		Perbill::from_percent(100)
+	}
+}
+
+/// The offence of attesting to the invalidity of a candidate which was concluded valid.
+#[derive(RuntimeDebug)]
+pub struct AgainstValidOffence<Offender> {
+	/// The time slot of the offence.
+	pub time_slot: DisputeTimeSlot,
+	/// The size of the validator set in the session of the offence.
+	pub validator_set_count: u32,
+	/// The validators on the losing side.
+	pub offenders: Vec<Offender>,
+}
+
+impl<Offender: Clone> Offence<Offender> for AgainstValidOffence<Offender> {
+	const ID: Kind = *b"disputes:against";
+	type TimeSlot = DisputeTimeSlot;
+
+	fn offenders(&self) -> Vec<Offender> {
+		self.offenders.clone()
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.time_slot.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> DisputeTimeSlot {
+		self.time_slot.clone()
+	}
+
+	fn slash_fraction(_offenders_count: u32, _validator_set_count: u32) -> Perbill {
+		// a small fee for wasting the time of all other validators.
+		Perbill::from_percent(1)
+	}
+}
+
+/// Punishes the losing side of disputes by reporting offences to `R`, which is expected to be the
+/// offences module. The offences are in turn handed to `pallet_staking` for slashing.
+pub struct SlashValidatorsForDisputes<C, R>(sp_std::marker::PhantomData<(C, R)>);
+
+impl<C, R> SlashValidatorsForDisputes<C, R> where C: pallet_session::historical::Trait {
+	fn identify(validators: Vec<C::ValidatorId>) -> Vec<IdentificationTuple<C>> {
+		validators.into_iter()
+			.filter_map(|v| C::FullIdentificationOf::convert(v.clone()).map(|full| (v, full)))
+			.collect()
+	}
+}
+
+impl<C, R> PunishValidators<C::ValidatorId> for SlashValidatorsForDisputes<C, R> where
+	C: pallet_session::historical::Trait,
+	R: ReportOffence<C::AccountId, IdentificationTuple<C>, ForInvalidOffence<IdentificationTuple<C>>>
+		+ ReportOffence<C::AccountId, IdentificationTuple<C>, AgainstValidOffence<IdentificationTuple<C>>>,
+{
+	fn punish_for_invalid(
+		session: SessionIndex,
+		candidate_hash: Hash,
+		validator_set_count: u32,
+		losers: Vec<C::ValidatorId>,
+	) {
+		let offence = ForInvalidOffence {
+			time_slot: DisputeTimeSlot { session_index: session, candidate_hash },
+			validator_set_count,
+			offenders: Self::identify(losers),
+		};
+
+		// the only possible error is a duplicate report, which is fine to ignore.
+		let _ = R::report_offence(Vec::new(), offence);
+	}
+
+	fn punish_against_valid(
+		session: SessionIndex,
+		candidate_hash: Hash,
+		validator_set_count: u32,
+		losers: Vec<C::ValidatorId>,
+	) {
+		let offence = AgainstValidOffence {
+			time_slot: DisputeTimeSlot { session_index: session, candidate_hash },
+			validator_set_count,
+			offenders: Self::identify(losers),
+		};
+
+		let _ = R::report_offence(Vec::new(), offence);
+	}
+}
+
+pub trait Trait: frame_system::Trait + configuration::Trait {
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+
+	/// The punishment of the validators on the losing side of concluded disputes.
+	type PunishValidators: PunishValidators<Self::AccountId>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ParaDisputes {
+		/// The validators of every session within the dispute period, along with their accounts.
+		SessionValidators: map hasher(twox_64_concat) SessionIndex
+			=> Option<Vec<(T::AccountId, ValidatorId)>>;
+
+		/// The earliest session for which validators are stored.
+		EarliestStoredSession: SessionIndex;
+
+		/// The numbers of the blocks in which candidates were included on this fork, by the
+		/// session of their inclusion and their hash.
+		Included: double_map hasher(twox_64_concat) SessionIndex, hasher(twox_64_concat) Hash
+			=> Option<T::BlockNumber>;
+
+		/// All ongoing and concluded disputes, by session and candidate hash.
+		Disputes: double_map hasher(twox_64_concat) SessionIndex, hasher(twox_64_concat) Hash
+			=> Option<DisputeState<T::BlockNumber>>;
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// Multiple dispute statement sets about the same candidate.
+		DuplicateDisputeStatementSet,
+		/// The session of the dispute is unknown or outside of the dispute period.
+		AncientDisputeStatement,
+		/// Validator index out of bounds.
+		ValidatorIndexOutOfBounds,
+		/// The statement isn't a validity or invalidity statement about the disputed candidate.
+		InvalidDisputeStatement,
+		/// Invalid signature of a dispute statement.
+		InvalidDisputeSignature,
+		/// A new dispute must have statements on both sides.
+		SingleSidedDispute,
+	}
+}
+
+decl_event! {
+	pub enum Event<T> where <T as frame_system::Trait>::BlockNumber {
+		/// A dispute about a candidate has been initiated. [session, candidate_hash]
+		DisputeInitiated(SessionIndex, Hash),
+		/// A dispute about a candidate has concluded. [session, candidate_hash, result]
+		DisputeConcluded(SessionIndex, Hash, DisputeResult),
+		/// The chain should be reverted to before the given block, which included a candidate
+		/// concluded invalid. [block_number]
+		Revert(BlockNumber),
+	}
+}
+
+decl_module! {
+	/// The parachain disputes module.
+	pub struct Module<T: Trait>
+		for enum Call where origin: <T as frame_system::Trait>::Origin
+	{
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+	}
+}
+
+/// A dispute with the statements of a dispute statement set imported, but not yet written to
+/// storage.
+struct ImportedDispute<N> {
+	session: SessionIndex,
+	candidate_hash: Hash,
+	state: DisputeState<N>,
+	is_new: bool,
+	/// The validators whose statements were imported, along with whether they attested validity.
+	new_statements: Vec<(ValidatorIndex, bool)>,
+}
+
+impl<T: Trait> Module<T> {
+	/// Block initialization logic, called by initializer.
+	pub(crate) fn initializer_initialize(_now: T::BlockNumber) -> Weight { 0 }
+
+	/// Block finalization logic, called by initializer.
+	pub(crate) fn initializer_finalize() { }
+
+	/// Handle an incoming session change. `validator_accounts` are the accounts of the new
+	/// validators, in the same order as the validators.
+	pub(crate) fn initializer_on_new_session(
+		notification: &SessionChangeNotification<T::BlockNumber>,
+		validator_accounts: Vec<T::AccountId>,
+	) {
+		let session_index = notification.session_index;
+		let validators: Vec<_> = validator_accounts.into_iter()
+			.zip(notification.validators.iter().cloned())
+			.collect();
+
+		<SessionValidators<T>>::insert(session_index, validators);
+
+		// prune all sessions which left the dispute period.
+		let earliest_kept = session_index.saturating_sub(notification.new_config.dispute_period);
+		let earliest_stored = EarliestStoredSession::get();
+		for session in earliest_stored..earliest_kept {
+			<SessionValidators<T>>::remove(session);
+			<Included<T>>::remove_prefix(session);
+			<Disputes<T>>::remove_prefix(session);
+		}
+
+		if earliest_kept > earliest_stored {
+			EarliestStoredSession::set(earliest_kept);
+		}
+	}
+
+	/// Note that candidates with the given hashes were included in the current block, in the
+	/// given session.
+	pub(crate) fn note_included(session: SessionIndex, candidate_hashes: impl IntoIterator<Item = Hash>) {
+		let now = <frame_system::Module<T>>::block_number();
+		for candidate_hash in candidate_hashes {
+			<Included<T>>::insert(session, candidate_hash, now);
+		}
+	}
+
+	/// Process a set of dispute statement sets, each about a different candidate.
+	///
+	/// Either all of the statements are imported or, if any of them fail the checks, none are.
+	/// Disputes reaching a supermajority are concluded, punishing the losing side.
+	pub(crate) fn process_dispute_statements(
+		statement_sets: DisputeStatementSets,
+	) -> Result<(), DispatchError> {
+		let now = <frame_system::Module<T>>::block_number();
+
+		// do all checks before writing storage.
+		let mut seen = BTreeSet::new();
+		let mut imported = Vec::with_capacity(statement_sets.len());
+		for set in &statement_sets {
+			ensure!(
+				seen.insert((set.session, set.candidate_hash)),
+				Error::<T>::DuplicateDisputeStatementSet,
+			);
+
+			let validators = <SessionValidators<T>>::get(set.session)
+				.ok_or(Error::<T>::AncientDisputeStatement)?;
+
+			let existing = <Disputes<T>>::get(set.session, set.candidate_hash);
+			let is_new = existing.is_none();
+			let mut state = existing.unwrap_or_else(|| DisputeState {
+				validators_for: bitvec::bitvec![BitOrderLsb0, u8; 0; validators.len()],
+				validators_against: bitvec::bitvec![BitOrderLsb0, u8; 0; validators.len()],
+				start: now,
+				concluded: None,
+			});
+
+			let signing_context = set.signing_context();
+			let mut new_statements = Vec::with_capacity(set.statements.len());
+			for statement in &set.statements {
+				let validator_index = statement.validator_index();
+				let (_, validator_public) = validators.get(validator_index as usize)
+					.ok_or(Error::<T>::ValidatorIndexOutOfBounds)?;
+
+				let valid = match statement.payload() {
+					CompactStatement::Valid(h) if h == &set.candidate_hash => true,
+					CompactStatement::Invalid(h) if h == &set.candidate_hash => false,
+					_ => return Err(Error::<T>::InvalidDisputeStatement.into()),
+				};
+
+				statement.check_signature(&signing_context, validator_public)
+					.map_err(|_| Error::<T>::InvalidDisputeSignature)?;
+
+				// statements which are already known are skipped, so that block authors don't need
+				// to track which statements have been imported on this fork already.
+				let votes = if valid { &mut state.validators_for } else { &mut state.validators_against };
+				let mut bit = votes.get_mut(validator_index as usize)
+					.ok_or(Error::<T>::ValidatorIndexOutOfBounds)?;
+				if *bit {
+					continue;
+				}
+				*bit = true;
+
+				new_statements.push((validator_index, valid));
+			}
+
+			if is_new {
+				ensure!(
+					state.validators_for.any() && state.validators_against.any(),
+					Error::<T>::SingleSidedDispute,
+				);
+			}
+
+			imported.push(ImportedDispute {
+				session: set.session,
+				candidate_hash: set.candidate_hash,
+				state,
+				is_new,
+				new_statements,
+			});
+		}
+
+		// all checks passed; import the statements.
+		for ImportedDispute { session, candidate_hash, mut state, is_new, new_statements } in imported {
+			if is_new {
+				Self::deposit_event(Event::<T>::DisputeInitiated(session, candidate_hash));
+			}
+
+			let validators = <SessionValidators<T>>::get(session).unwrap_or_default();
+			let accounts = |indices: Vec<ValidatorIndex>| -> Vec<T::AccountId> {
+				indices.into_iter()
+					.filter_map(|i| validators.get(i as usize).map(|(account, _)| account.clone()))
+					.collect()
+			};
+
+			match state.concluded {
+				None => {
+					let threshold = supermajority_threshold(validators.len());
+					let result = if state.validators_for.count_ones() >= threshold {
+						Some(DisputeResult::Valid)
+					} else if state.validators_against.count_ones() >= threshold {
+						Some(DisputeResult::Invalid)
+					} else {
+						None
+					};
+
+					if let Some(result) = result {
+						state.concluded = Some((now, result));
+						Self::deposit_event(Event::<T>::DisputeConcluded(session, candidate_hash, result));
+
+						let losers = accounts(state.losers(result).iter()
+							.enumerate()
+							.filter(|(_, lost)| **lost)
+							.map(|(i, _)| i as ValidatorIndex)
+							.collect()
+						);
+						Self::punish(session, candidate_hash, result, validators.len(), losers);
+
+						if result == DisputeResult::Invalid {
+							Self::revert(session, candidate_hash);
+						}
+					}
+				}
+				Some((_, result)) => {
+					// the dispute has already concluded, so only late voters on the losing side
+					// are punished.
+					let losers_attested_validity = result == DisputeResult::Invalid;
+					let losers = accounts(new_statements.iter()
+						.filter(|(_, valid)| *valid == losers_attested_validity)
+						.map(|(i, _)| *i)
+						.collect()
+					);
+
+					if !losers.is_empty() {
+						Self::punish(session, candidate_hash, result, validators.len(), losers);
+					}
+				}
+			}
+
+			<Disputes<T>>::insert(session, candidate_hash, state);
+		}
+
+		Ok(())
+	}
+
+	fn punish(
+		session: SessionIndex,
+		candidate_hash: Hash,
+		result: DisputeResult,
+		validator_set_count: usize,
+		losers: Vec<T::AccountId>,
+	) {
+		let validator_set_count = validator_set_count as u32;
+		match result {
+			DisputeResult::Valid => T::PunishValidators::punish_against_valid(
+				session,
+				candidate_hash,
+				validator_set_count,
+				losers,
+			),
+			DisputeResult::Invalid => T::PunishValidators::punish_for_invalid(
+				session,
+				candidate_hash,
+				validator_set_count,
+				losers,
+			),
+		}
+	}
+
+	/// Signal that the chain should be reverted to before the inclusion of the given candidate,
+	/// if it was included on this fork.
+	fn revert(session: SessionIndex, candidate_hash: Hash) {
+		if let Some(included_in) = <Included<T>>::get(session, candidate_hash) {
+			<frame_system::Module<T>>::deposit_log(ConsensusLog::Revert(included_in).into());
+			Self::deposit_event(Event::<T>::Revert(included_in));
+		}
+	}
+
+	/// Get the state of the dispute about the given candidate in the given session, if any.
+	pub(crate) fn dispute(
+		session: SessionIndex,
+		candidate_hash: Hash,
+	) -> Option<DisputeState<T::BlockNumber>> {
+		<Disputes<T>>::get(session, candidate_hash)
+	}
+}
+
+/// The number of votes needed for a supermajority among `n_validators`, i.e. more than two
+/// thirds of them.
+fn supermajority_threshold(n_validators: usize) -> usize {
+	n_validators - n_validators.saturating_sub(1) / 3
+}
+
+#[cfg(test)]
+pub(crate) mod mock_punishment {
+	//! An implementation of a mock punishment handler that records all punished validators.
+
+	use super::{PunishValidators, DisputeResult, Hash, SessionIndex};
+	use std::cell::RefCell;
+
+	std::thread_local! {
+		static PUNISHED: RefCell<Vec<(SessionIndex, Hash, DisputeResult, Vec<u64>)>>
+			= RefCell::new(Vec::new());
+	}
+
+	/// A punishment handler that records the punished validators along with the result of the
+	/// dispute they lost.
+	pub struct MockPunishValidators;
+
+	impl PunishValidators<u64> for MockPunishValidators {
+		fn punish_for_invalid(session: SessionIndex, candidate_hash: Hash, _: u32, losers: Vec<u64>) {
+			PUNISHED.with(|p| p.borrow_mut().push((session, candidate_hash, DisputeResult::Invalid, losers)));
+		}
+
+		fn punish_against_valid(session: SessionIndex, candidate_hash: Hash, _: u32, losers: Vec<u64>) {
+			PUNISHED.with(|p| p.borrow_mut().push((session, candidate_hash, DisputeResult::Valid, losers)));
+		}
+	}
+
+	/// Take all the punishments since the last call.
+	pub fn take_punished() -> Vec<(SessionIndex, Hash, DisputeResult, Vec<u64>)> {
+		PUNISHED.with(|p| sp_std::mem::take(&mut *p.borrow_mut()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::mock_punishment::take_punished;
+
+	use primitives::v1::{BlockNumber, DisputeStatementSet, SignedStatement, SigningContext};
+	use keyring::Sr25519Keyring;
+
+	use crate::mock::{new_test_ext, Disputes, System, Test};
+	use crate::configuration::HostConfiguration;
+
+	const VALIDATORS: [Sr25519Keyring; 7] = [
+		Sr25519Keyring::Alice,
+		Sr25519Keyring::Bob,
+		Sr25519Keyring::Charlie,
+		Sr25519Keyring::Dave,
+		Sr25519Keyring::Eve,
+		Sr25519Keyring::Ferdie,
+		Sr25519Keyring::One,
+	];
+
+	fn start_session(session_index: SessionIndex, dispute_period: SessionIndex) {
+		let notification = SessionChangeNotification {
+			validators: VALIDATORS.iter().map(|k| k.public().into()).collect(),
+			new_config: HostConfiguration {
+				dispute_period,
+				..Default::default()
+			},
+			session_index,
+			..Default::default()
+		};
+
+		Disputes::initializer_on_new_session(&notification, (0..VALIDATORS.len() as u64).collect());
+	}
+
+	fn statement_set(
+		session: SessionIndex,
+		candidate_hash: Hash,
+		statements: &[(ValidatorIndex, bool)],
+	) -> DisputeStatementSet {
+		let mut set = DisputeStatementSet { candidate_hash, session, statements: Vec::new() };
+		let signing_context = set.signing_context();
+
+		set.statements = statements.iter().map(|&(validator_index, valid)| {
+			let statement = if valid {
+				CompactStatement::Valid(candidate_hash)
+			} else {
+				CompactStatement::Invalid(candidate_hash)
+			};
+
+			SignedStatement::sign(
+				statement,
+				&signing_context,
+				validator_index,
+				&VALIDATORS[validator_index as usize].pair().into(),
+			)
+		}).collect();
+
+		set
+	}
+
+	fn revert_logs() -> Vec<ConsensusLog<BlockNumber>> {
+		System::digest().logs.iter()
+			.filter_map(|item| ConsensusLog::from_digest_item(item).unwrap())
+			.collect()
+	}
+
+	#[test]
+	fn supermajority_threshold_is_more_than_two_thirds() {
+		assert_eq!(supermajority_threshold(1), 1);
+		assert_eq!(supermajority_threshold(3), 3);
+		assert_eq!(supermajority_threshold(4), 3);
+		assert_eq!(supermajority_threshold(5), 4);
+		assert_eq!(supermajority_threshold(7), 5);
+		assert_eq!(supermajority_threshold(10), 7);
+	}
+
+	#[test]
+	fn new_disputes_need_statements_on_both_sides() {
+		new_test_ext(Default::default()).execute_with(|| {
+			let candidate_hash = Hash::repeat_byte(1);
+			start_session(1, 0);
+
+			assert_eq!(
+				Disputes::process_dispute_statements(vec![
+					statement_set(1, candidate_hash, &[(0, true), (1, true)]),
+				]),
+				Err(Error::<Test>::SingleSidedDispute.into()),
+			);
+			assert!(Disputes::dispute(1, candidate_hash).is_none());
+
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(0, true), (1, false)]),
+			]).is_ok());
+
+			let dispute = Disputes::dispute(1, candidate_hash).unwrap();
+			assert_eq!(dispute.start, System::block_number());
+			assert_eq!(dispute.validators_for.count_ones(), 1);
+			assert_eq!(dispute.validators_against.count_ones(), 1);
+			assert!(dispute.concluded.is_none());
+
+			// further statements can be single-sided and known statements are skipped.
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(0, true), (2, true), (2, true)]),
+			]).is_ok());
+			assert_eq!(Disputes::dispute(1, candidate_hash).unwrap().validators_for.count_ones(), 2);
+		});
+	}
+
+	#[test]
+	fn bad_statements_are_rejected() {
+		new_test_ext(Default::default()).execute_with(|| {
+			let candidate_hash = Hash::repeat_byte(1);
+			start_session(1, 0);
+
+			// unknown session.
+			assert_eq!(
+				Disputes::process_dispute_statements(vec![
+					statement_set(2, candidate_hash, &[(0, true), (1, false)]),
+				]),
+				Err(Error::<Test>::AncientDisputeStatement.into()),
+			);
+
+			// statement about another candidate.
+			let mut set = statement_set(1, candidate_hash, &[(0, true)]);
+			set.statements.extend(statement_set(1, Hash::repeat_byte(2), &[(1, false)]).statements);
+			assert_eq!(
+				Disputes::process_dispute_statements(vec![set]),
+				Err(Error::<Test>::InvalidDisputeStatement.into()),
+			);
+
+			// statement signed under the wrong context.
+			let mut set = statement_set(1, candidate_hash, &[(0, true), (1, false)]);
+			set.statements[1] = SignedStatement::sign(
+				CompactStatement::Invalid(candidate_hash),
+				&SigningContext { session_index: 1, parent_hash: Hash::repeat_byte(3) },
+				1,
+				&VALIDATORS[1].pair().into(),
+			);
+			assert_eq!(
+				Disputes::process_dispute_statements(vec![set]),
+				Err(Error::<Test>::InvalidDisputeSignature.into()),
+			);
+
+			// duplicate set.
+			assert_eq!(
+				Disputes::process_dispute_statements(vec![
+					statement_set(1, candidate_hash, &[(0, true), (1, false)]),
+					statement_set(1, candidate_hash, &[(2, true)]),
+				]),
+				Err(Error::<Test>::DuplicateDisputeStatementSet.into()),
+			);
+
+			// nothing was imported by any of the failed calls.
+			assert!(Disputes::dispute(1, candidate_hash).is_none());
+		});
+	}
+
+	#[test]
+	fn dispute_concluding_invalid_reverts_and_punishes_backers() {
+		new_test_ext(Default::default()).execute_with(|| {
+			let candidate_hash = Hash::repeat_byte(1);
+			start_session(1, 0);
+
+			System::set_block_number(5);
+			Disputes::note_included(1, vec![candidate_hash]);
+
+			System::set_block_number(7);
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(0, true), (1, false), (2, false), (3, false), (4, false)]),
+			]).is_ok());
+
+			assert!(Disputes::dispute(1, candidate_hash).unwrap().concluded.is_none());
+			assert!(take_punished().is_empty());
+			assert!(revert_logs().is_empty());
+
+			System::set_block_number(8);
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(5, false)]),
+			]).is_ok());
+
+			assert_eq!(
+				Disputes::dispute(1, candidate_hash).unwrap().concluded,
+				Some((8, DisputeResult::Invalid)),
+			);
+			assert_eq!(take_punished(), vec![(1, candidate_hash, DisputeResult::Invalid, vec![0])]);
+			assert_eq!(revert_logs(), vec![ConsensusLog::Revert(5)]);
+		});
+	}
+
+	#[test]
+	fn dispute_concluding_valid_punishes_late_disputers() {
+		new_test_ext(Default::default()).execute_with(|| {
+			let candidate_hash = Hash::repeat_byte(1);
+			start_session(1, 0);
+			Disputes::note_included(1, vec![candidate_hash]);
+
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(0, true), (1, true), (2, true), (3, true), (4, false)]),
+			]).is_ok());
+			assert!(Disputes::dispute(1, candidate_hash).unwrap().concluded.is_none());
+
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(5, true)]),
+			]).is_ok());
+
+			assert_eq!(
+				Disputes::dispute(1, candidate_hash).unwrap().concluded,
+				Some((System::block_number(), DisputeResult::Valid)),
+			);
+			assert_eq!(take_punished(), vec![(1, candidate_hash, DisputeResult::Valid, vec![4])]);
+
+			// the dispute is still open, so a late vote against the candidate is punished.
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(6, false)]),
+			]).is_ok());
+			assert_eq!(take_punished(), vec![(1, candidate_hash, DisputeResult::Valid, vec![6])]);
+
+			// candidates concluded valid don't revert the chain.
+			assert!(revert_logs().is_empty());
+		});
+	}
+
+	#[test]
+	fn sessions_outside_of_dispute_period_are_pruned() {
+		new_test_ext(Default::default()).execute_with(|| {
+			let candidate_hash = Hash::repeat_byte(1);
+			start_session(1, 1);
+
+			Disputes::note_included(1, vec![candidate_hash]);
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(0, true), (1, false)]),
+			]).is_ok());
+
+			// session 1 is still within the dispute period of session 2.
+			start_session(2, 1);
+			assert!(Disputes::dispute(1, candidate_hash).is_some());
+			assert!(Disputes::process_dispute_statements(vec![
+				statement_set(1, candidate_hash, &[(2, true)]),
+			]).is_ok());
+
+			start_session(3, 1);
+			assert!(Disputes::dispute(1, candidate_hash).is_none());
+			assert!(<Included<Test>>::get(1, candidate_hash).is_none());
+			assert_eq!(
+				Disputes::process_dispute_statements(vec![
+					statement_set(1, candidate_hash, &[(3, true)]),
+				]),
+				Err(Error::<Test>::AncientDisputeStatement.into()),
+			);
+			assert_eq!(EarliestStoredSession::get(), 2);
+		});
+	}
+}
@@ -25,7 +25,7 @@ use primitives::v1::{
 	ValidatorId, CandidateCommitments, CandidateDescriptor, ValidatorIndex, Id as ParaId,
 	AvailabilityBitfield as AvailabilityBitfield, SignedAvailabilityBitfields, SigningContext,
 	BackedCandidate, CoreIndex, GroupIndex, CommittedCandidateReceipt,
-	CandidateReceipt, HeadData,
+	CandidateReceipt, HeadData, Hash,
 };
 use frame_support::{
 	decl_storage, decl_module, decl_error, decl_event, ensure, debug,
@@ -36,7 +36,7 @@ use bitvec::{order::Lsb0 as BitOrderLsb0, vec::BitVec};
 use sp_staking::SessionIndex;
 use sp_runtime::{DispatchError, traits::{One, Saturating}};
 
-use crate::{configuration, paras, scheduler::CoreAssignment};
+use crate::{configuration, paras, router, scheduler::CoreAssignment};
 
 /// A bitfield signed by a validator indicating that it is keeping its piece of the erasure-coding
 /// for any backed candidates referred to by a `1` bit available.
@@ -86,7 +86,7 @@ impl<H, N> CandidatePendingAvailability<H, N> {
 }
 
 pub trait Trait:
-	frame_system::Trait + paras::Trait + configuration::Trait
+	frame_system::Trait + paras::Trait + router::Trait + configuration::Trait
 {
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
 }
@@ -147,6 +147,17 @@ decl_error! {
 		NotCollatorSigned,
 		/// The validation data hash does not match expected.
 		ValidationDataHashMismatch,
+		/// The erasure root in the descriptor does not match the one in the commitments.
+		ErasureRootMismatch,
+		/// At least one upward message sent does not pass the acceptance criteria.
+		IncorrectUpwardMessages,
+		/// The candidate didn't follow the rules of processing downward messages.
+		IncorrectDownwardMessageHandling,
+		/// The HRMP watermark is not advanced or points to a block without inbound messages.
+		IncorrectHrmpWatermark,
+		/// The outbound HRMP messages are not sorted, are too many or one of them doesn't fit into its
+		/// channel.
+		IncorrectOutboundHrmp,
 		/// Internal error only returned when compiled with debug assertions.
 		InternalError,
 	}
@@ -154,12 +165,12 @@ decl_error! {
 
 decl_event! {
 	pub enum Event<T> where <T as frame_system::Trait>::Hash {
-		/// A candidate was backed. [candidate, head_data]
-		CandidateBacked(CandidateReceipt<Hash>, HeadData),
-		/// A candidate was included. [candidate, head_data]
-		CandidateIncluded(CandidateReceipt<Hash>, HeadData),
-		/// A candidate timed out. [candidate, head_data]
-		CandidateTimedOut(CandidateReceipt<Hash>, HeadData),
+		/// A candidate was backed. [candidate, head_data, core_index]
+		CandidateBacked(CandidateReceipt<Hash>, HeadData, CoreIndex),
+		/// A candidate was included. [candidate, head_data, core_index]
+		CandidateIncluded(CandidateReceipt<Hash>, HeadData, CoreIndex),
+		/// A candidate timed out. [candidate, head_data, core_index]
+		CandidateTimedOut(CandidateReceipt<Hash>, HeadData, CoreIndex),
 	}
 }
 
@@ -197,11 +208,11 @@ impl<T: Trait> Module<T> {
 	}
 
 	/// Process a set of incoming bitfields. Return a vec of cores freed by candidates
-	/// becoming available.
+	/// becoming available, along with the hashes of those candidates.
 	pub(crate) fn process_bitfields(
 		signed_bitfields: SignedAvailabilityBitfields,
 		core_lookup: impl Fn(CoreIndex) -> Option<ParaId>,
-	) -> Result<Vec<CoreIndex>, DispatchError> {
+	) -> Result<Vec<(CoreIndex, Hash)>, DispatchError> {
 		let validators = Validators::get();
 		let session_index = CurrentSessionIndex::get();
 		let config = <configuration::Module<T>>::config();
@@ -320,20 +331,19 @@ impl<T: Trait> Module<T> {
 					descriptor: pending_availability.descriptor,
 					commitments,
 				};
+				let candidate_hash = receipt.hash();
 				Self::enact_candidate(
 					pending_availability.relay_parent_number,
 					receipt,
+					pending_availability.core,
 				);
 
-				freed_cores.push(pending_availability.core);
+				freed_cores.push((pending_availability.core, candidate_hash));
 			} else {
 				<PendingAvailability<T>>::insert(&para_id, &pending_availability);
 			}
 		}
 
-		// TODO: pass available candidates onwards to validity module once implemented.
-		// https://github.com/paritytech/polkadot/issues/1251
-
 		Ok(freed_cores)
 	}
 
@@ -420,6 +430,41 @@ impl<T: Trait> Module<T> {
 					candidate.descriptor().check_collator_signature().is_ok(),
 					Error::<T>::NotCollatorSigned,
 				);
+				ensure!(
+					candidate.descriptor().erasure_root == candidate.candidate.commitments.erasure_root,
+					Error::<T>::ErasureRootMismatch,
+				);
+				ensure!(
+					<router::Module<T>>::check_upward_messages(
+						&config,
+						para_id,
+						&candidate.candidate.commitments.upward_messages,
+					),
+					Error::<T>::IncorrectUpwardMessages,
+				);
+				ensure!(
+					<router::Module<T>>::check_processed_downward_messages(
+						para_id,
+						candidate.candidate.commitments.processed_downward_messages,
+					),
+					Error::<T>::IncorrectDownwardMessageHandling,
+				);
+				ensure!(
+					<router::Module<T>>::check_hrmp_watermark(
+						para_id,
+						relay_parent_number,
+						candidate.candidate.commitments.hrmp_watermark.into(),
+					),
+					Error::<T>::IncorrectHrmpWatermark,
+				);
+				ensure!(
+					<router::Module<T>>::check_outbound_hrmp(
+						&config,
+						para_id,
+						&candidate.candidate.commitments.horizontal_messages,
+					),
+					Error::<T>::IncorrectOutboundHrmp,
+				);
 
 				for (i, assignment) in scheduled[skip..].iter().enumerate() {
 					check_assignment_in_order(assignment)?;
@@ -519,6 +564,7 @@ impl<T: Trait> Module<T> {
 			Self::deposit_event(Event::<T>::CandidateBacked(
 				candidate.candidate.to_plain(),
 				candidate.candidate.commitments.head_data.clone(),
+				core,
 			));
 
 			let (descriptor, commitments) = (
@@ -542,6 +588,7 @@ impl<T: Trait> Module<T> {
 	fn enact_candidate(
 		relay_parent_number: T::BlockNumber,
 		receipt: CommittedCandidateReceipt<T::Hash>,
+		core_index: CoreIndex,
 	) -> Weight {
 		let plain = receipt.to_plain();
 		let commitments = receipt.commitments;
@@ -557,8 +604,25 @@ impl<T: Trait> Module<T> {
 			);
 		}
 
+		weight += <router::Module<T>>::enact_upward_messages(
+			receipt.descriptor.para_id,
+			commitments.upward_messages,
+		);
+		weight += <router::Module<T>>::prune_dmq(
+			receipt.descriptor.para_id,
+			commitments.processed_downward_messages,
+		);
+		weight += <router::Module<T>>::prune_hrmp(
+			receipt.descriptor.para_id,
+			commitments.hrmp_watermark.into(),
+		);
+		weight += <router::Module<T>>::queue_outbound_hrmp(
+			receipt.descriptor.para_id,
+			commitments.horizontal_messages,
+		);
+
 		Self::deposit_event(
-			Event::<T>::CandidateIncluded(plain, commitments.head_data.clone())
+			Event::<T>::CandidateIncluded(plain, commitments.head_data.clone(), core_index)
 		);
 
 		weight + <paras::Module<T>>::note_new_head(
@@ -599,6 +663,7 @@ impl<T: Trait> Module<T> {
 				Self::deposit_event(Event::<T>::CandidateTimedOut(
 					candidate,
 					commitments.head_data,
+					pending.core,
 				));
 			}
 		}
@@ -625,6 +690,7 @@ impl<T: Trait> Module<T> {
 			Self::enact_candidate(
 				pending.relay_parent_number,
 				candidate,
+				pending.core,
 			);
 		}
 	}
@@ -839,6 +905,9 @@ mod tests {
 		)
 	}
 
+	/// The number of the relay parent block used by the candidates in the tests below.
+	const RELAY_PARENT_NUM: BlockNumber = 4;
+
 	#[derive(Default)]
 	struct TestCandidateBuilder {
 		para_id: ParaId,
@@ -847,6 +916,8 @@ mod tests {
 		relay_parent: Hash,
 		persisted_validation_data_hash: Hash,
 		new_validation_code: Option<ValidationCode>,
+		processed_downward_messages: u32,
+		hrmp_watermark: BlockNumber,
 	}
 
 	impl TestCandidateBuilder {
@@ -862,6 +933,8 @@ mod tests {
 				commitments: CandidateCommitments {
 					head_data: self.head_data,
 					new_validation_code: self.new_validation_code,
+					processed_downward_messages: self.processed_downward_messages,
+					hrmp_watermark: self.hrmp_watermark,
 					..Default::default()
 				},
 			}
@@ -1298,6 +1371,7 @@ mod tests {
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
@@ -1330,6 +1404,7 @@ mod tests {
 			{
 				let mut candidate_a = TestCandidateBuilder {
 					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
@@ -1337,6 +1412,7 @@ mod tests {
 				}.build();
 				let mut candidate_b = TestCandidateBuilder {
 					para_id: chain_b,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([2; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_b).unwrap(),
@@ -1384,6 +1460,7 @@ mod tests {
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
@@ -1419,6 +1496,7 @@ mod tests {
 
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: wrong_parent_hash,
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
@@ -1451,6 +1529,7 @@ mod tests {
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: thread_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(thread_a).unwrap(),
@@ -1489,6 +1568,7 @@ mod tests {
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: thread_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(thread_a).unwrap(),
@@ -1522,10 +1602,47 @@ mod tests {
 				);
 			}
 
+			// erasure root in the descriptor doesn't match the commitments - reject.
+			{
+				let mut candidate = TestCandidateBuilder {
+					para_id: chain_a,
+					relay_parent: System::parent_hash(),
+					pov_hash: Hash::from([1; 32]),
+					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
+					hrmp_watermark: RELAY_PARENT_NUM,
+					..Default::default()
+				}.build();
+
+				candidate.descriptor.erasure_root = Hash::repeat_byte(1);
+
+				collator_sign_candidate(
+					Sr25519Keyring::One,
+					&mut candidate,
+				);
+
+				let backed = back_candidate(
+					candidate,
+					&validators,
+					group_validators(GroupIndex::from(0)).unwrap().as_ref(),
+					&signing_context,
+					BackingKind::Threshold,
+				);
+
+				assert_eq!(
+					Inclusion::process_candidates(
+						vec![backed],
+						vec![chain_a_assignment.clone()],
+						&group_validators,
+					),
+					Err(Error::<Test>::ErasureRootMismatch.into()),
+				);
+			}
+
 			// para occupied - reject.
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
@@ -1572,6 +1689,7 @@ mod tests {
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
@@ -1606,10 +1724,80 @@ mod tests {
 				<PendingAvailabilityCommitments>::remove(&chain_a);
 			}
 
+			// claims to have processed more downward messages than there are - reject.
+			{
+				let mut candidate = TestCandidateBuilder {
+					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
+					relay_parent: System::parent_hash(),
+					pov_hash: Hash::from([1; 32]),
+					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
+					processed_downward_messages: 1,
+					..Default::default()
+				}.build();
+
+				collator_sign_candidate(
+					Sr25519Keyring::One,
+					&mut candidate,
+				);
+
+				let backed = back_candidate(
+					candidate,
+					&validators,
+					group_validators(GroupIndex::from(0)).unwrap().as_ref(),
+					&signing_context,
+					BackingKind::Threshold,
+				);
+
+				assert_eq!(
+					Inclusion::process_candidates(
+						vec![backed],
+						vec![chain_a_assignment.clone()],
+						&group_validators,
+					),
+					Err(Error::<Test>::IncorrectDownwardMessageHandling.into()),
+				);
+			}
+
+			// the HRMP watermark points to a block without any inbound messages - reject.
+			{
+				let mut candidate = TestCandidateBuilder {
+					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM - 1,
+					relay_parent: System::parent_hash(),
+					pov_hash: Hash::from([1; 32]),
+					persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
+					..Default::default()
+				}.build();
+
+				collator_sign_candidate(
+					Sr25519Keyring::One,
+					&mut candidate,
+				);
+
+				let backed = back_candidate(
+					candidate,
+					&validators,
+					group_validators(GroupIndex::from(0)).unwrap().as_ref(),
+					&signing_context,
+					BackingKind::Threshold,
+				);
+
+				assert_eq!(
+					Inclusion::process_candidates(
+						vec![backed],
+						vec![chain_a_assignment.clone()],
+						&group_validators,
+					),
+					Err(Error::<Test>::IncorrectHrmpWatermark.into()),
+				);
+			}
+
 			// interfering code upgrade - reject
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					new_validation_code: Some(vec![5, 6, 7, 8].into()),
@@ -1652,6 +1840,7 @@ mod tests {
 			{
 				let mut candidate = TestCandidateBuilder {
 					para_id: chain_a,
+					hrmp_watermark: RELAY_PARENT_NUM,
 					relay_parent: System::parent_hash(),
 					pov_hash: Hash::from([1; 32]),
 					persisted_validation_data_hash: [42u8; 32].into(),
@@ -1742,6 +1931,7 @@ mod tests {
 
 			let mut candidate_a = TestCandidateBuilder {
 				para_id: chain_a,
+				hrmp_watermark: RELAY_PARENT_NUM,
 				relay_parent: System::parent_hash(),
 				pov_hash: Hash::from([1; 32]),
 				persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
@@ -1754,6 +1944,7 @@ mod tests {
 
 			let mut candidate_b = TestCandidateBuilder {
 				para_id: chain_b,
+				hrmp_watermark: RELAY_PARENT_NUM,
 				relay_parent: System::parent_hash(),
 				pov_hash: Hash::from([2; 32]),
 				persisted_validation_data_hash: make_vdata_hash(chain_b).unwrap(),
@@ -1766,6 +1957,7 @@ mod tests {
 
 			let mut candidate_c = TestCandidateBuilder {
 				para_id: thread_a,
+				hrmp_watermark: RELAY_PARENT_NUM,
 				relay_parent: System::parent_hash(),
 				pov_hash: Hash::from([3; 32]),
 				persisted_validation_data_hash: make_vdata_hash(thread_a).unwrap(),
@@ -1898,6 +2090,7 @@ mod tests {
 
 			let mut candidate_a = TestCandidateBuilder {
 				para_id: chain_a,
+				hrmp_watermark: RELAY_PARENT_NUM,
 				relay_parent: System::parent_hash(),
 				pov_hash: Hash::from([1; 32]),
 				persisted_validation_data_hash: make_vdata_hash(chain_a).unwrap(),
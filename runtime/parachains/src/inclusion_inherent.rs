@@ -14,8 +14,8 @@
 // You should have received a copy of the GNU General Public License
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
-//! Provides glue code over the scheduler and inclusion modules, and accepting
-//! one inherent per block that can include new para candidates and bitfields.
+//! Provides glue code over the scheduler, inclusion and disputes modules, and accepting
+//! one inherent per block that can include new para candidates, bitfields and dispute statements.
 //!
 //! Unlike other modules in this crate, it does not need to be initialized by the initializer,
 //! as it has no initialization logic and its finalization logic depends only on the details of
@@ -23,7 +23,8 @@
 
 use sp_std::prelude::*;
 use primitives::v1::{
-	BackedCandidate, SignedAvailabilityBitfields, INCLUSION_INHERENT_IDENTIFIER,
+	BackedCandidate, DisputeStatementSets, SignedAvailabilityBitfields,
+	INCLUSION_INHERENT_IDENTIFIER,
 };
 use frame_support::{
 	decl_error, decl_module, decl_storage, ensure,
@@ -33,12 +34,13 @@ use frame_support::{
 };
 use frame_system::ensure_none;
 use crate::{
+	disputes,
 	inclusion,
 	scheduler::{self, FreedReason},
 };
 use inherents::{InherentIdentifier, InherentData, MakeFatalError, ProvideInherent};
 
-pub trait Trait: inclusion::Trait + scheduler::Trait {}
+pub trait Trait: inclusion::Trait + scheduler::Trait + disputes::Trait {}
 
 decl_storage! {
 	trait Store for Module<T: Trait> as ParaInclusionInherent {
@@ -74,12 +76,13 @@ decl_module! {
 			}
 		}
 
-		/// Include backed candidates and bitfields.
+		/// Include backed candidates, bitfields and dispute statements.
 		#[weight = (1_000_000_000, DispatchClass::Mandatory)]
 		pub fn inclusion(
 			origin,
 			signed_bitfields: SignedAvailabilityBitfields,
 			backed_candidates: Vec<BackedCandidate<T::Hash>>,
+			dispute_statements: DisputeStatementSets,
 		) -> DispatchResult {
 			ensure_none(origin)?;
 			ensure!(!<Included>::exists(), Error::<T>::TooManyInclusionInherents);
@@ -91,6 +94,17 @@ decl_module! {
 				<scheduler::Module<T>>::core_para,
 			)?;
 
+			// Note the inclusion of the available candidates, so that they can be reverted if
+			// they are disputed and concluded invalid.
+			<disputes::Module<T>>::note_included(
+				<inclusion::Module<T>>::session_index(),
+				freed_concluded.iter().map(|(_, candidate_hash)| *candidate_hash),
+			);
+
+			// Import the dispute statements, concluding disputes and punishing the losing side
+			// where a supermajority is reached.
+			<disputes::Module<T>>::process_dispute_statements(dispute_statements)?;
+
 			// Handle timeouts for any availability core work.
 			let availability_pred = <scheduler::Module<T>>::availability_timeout_predicate();
 			let freed_timeout = if let Some(pred) = availability_pred {
@@ -100,7 +114,7 @@ decl_module! {
 			};
 
 			// Schedule paras again, given freed cores, and reasons for freeing.
-			let freed = freed_concluded.into_iter().map(|c| (c, FreedReason::Concluded))
+			let freed = freed_concluded.into_iter().map(|(c, _)| (c, FreedReason::Concluded))
 				.chain(freed_timeout.into_iter().map(|c| (c, FreedReason::TimedOut)));
 
 			<scheduler::Module<T>>::schedule(freed.collect());
@@ -131,13 +145,22 @@ impl<T: Trait> ProvideInherent for Module<T> {
 	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
 		data.get_data(&Self::INHERENT_IDENTIFIER)
 			.expect("inclusion inherent data failed to decode")
-			.map(|(signed_bitfields, backed_candidates): (SignedAvailabilityBitfields, Vec<BackedCandidate<T::Hash>>)| {
+			.map(|(signed_bitfields, backed_candidates, dispute_statements): (
+				SignedAvailabilityBitfields,
+				Vec<BackedCandidate<T::Hash>>,
+				DisputeStatementSets,
+			)| {
 				// Sanity check: session changes can invalidate an inherent, and we _really_ don't want that to happen.
 				// See github.com/paritytech/polkadot/issues/1327
-				if Self::inclusion(frame_system::RawOrigin::None.into(), signed_bitfields.clone(), backed_candidates.clone()).is_ok() {
-					Call::inclusion(signed_bitfields, backed_candidates)
+				if Self::inclusion(
+					frame_system::RawOrigin::None.into(),
+					signed_bitfields.clone(),
+					backed_candidates.clone(),
+					dispute_statements.clone(),
+				).is_ok() {
+					Call::inclusion(signed_bitfields, backed_candidates, dispute_statements)
 				} else {
-					Call::inclusion(Vec::new().into(), Vec::new())
+					Call::inclusion(Vec::new().into(), Vec::new(), Vec::new())
 				}
 			})
 	}
@@ -29,7 +29,7 @@ use sp_runtime::traits::One;
 use codec::{Encode, Decode};
 use crate::{
 	configuration::{self, HostConfiguration},
-	paras, router, scheduler, inclusion,
+	paras, router, scheduler, inclusion, disputes, session_info,
 };
 
 /// Information about a session change that has just occurred.
@@ -50,10 +50,12 @@ pub struct SessionChangeNotification<BlockNumber> {
 }
 
 #[derive(Encode, Decode)]
-struct BufferedSessionChange<N> {
+struct BufferedSessionChange<N, AccountId> {
 	apply_at: N,
 	validators: Vec<ValidatorId>,
 	queued: Vec<ValidatorId>,
+	/// The accounts of the new validators, in the same order as `validators`.
+	validator_accounts: Vec<AccountId>,
 	session_index: sp_staking::SessionIndex,
 }
 
@@ -63,7 +65,9 @@ pub trait Trait:
 	+ paras::Trait
 	+ scheduler::Trait
 	+ inclusion::Trait
+	+ disputes::Trait
 	+ router::Trait
+	+ session_info::Trait
 {
 	/// A randomness beacon.
 	type Randomness: Randomness<Self::Hash>;
@@ -87,7 +91,7 @@ decl_storage! {
 		///
 		/// However this is a `Vec` regardless to handle various edge cases that may occur at runtime
 		/// upgrade boundaries or if governance intervenes.
-		BufferedSessionChanges: Vec<BufferedSessionChange<T::BlockNumber>>;
+		BufferedSessionChanges: Vec<BufferedSessionChange<T::BlockNumber, T::AccountId>>;
 	}
 }
 
@@ -112,6 +116,7 @@ decl_module! {
 						buffered.session_index,
 						buffered.validators,
 						buffered.queued,
+						buffered.validator_accounts,
 					);
 				}
 			});
@@ -121,13 +126,16 @@ decl_module! {
 			// - Paras
 			// - Scheduler
 			// - Inclusion
-			// - Validity
+			// - Disputes
 			// - Router
+			// - SessionInfo
 			let total_weight = configuration::Module::<T>::initializer_initialize(now) +
 				paras::Module::<T>::initializer_initialize(now) +
 				scheduler::Module::<T>::initializer_initialize(now) +
 				inclusion::Module::<T>::initializer_initialize(now) +
-				router::Module::<T>::initializer_initialize(now);
+				disputes::Module::<T>::initializer_initialize(now) +
+				router::Module::<T>::initializer_initialize(now) +
+				session_info::Module::<T>::initializer_initialize(now);
 
 			HasInitialized::set(Some(()));
 
@@ -137,7 +145,9 @@ decl_module! {
 		fn on_finalize() {
 			// reverse initialization order.
 
+			session_info::Module::<T>::initializer_finalize();
 			router::Module::<T>::initializer_finalize();
+			disputes::Module::<T>::initializer_finalize();
 			inclusion::Module::<T>::initializer_finalize();
 			scheduler::Module::<T>::initializer_finalize();
 			paras::Module::<T>::initializer_finalize();
@@ -152,6 +162,7 @@ impl<T: Trait> Module<T> {
 		session_index: sp_staking::SessionIndex,
 		validators: Vec<ValidatorId>,
 		queued: Vec<ValidatorId>,
+		validator_accounts: Vec<T::AccountId>,
 	) {
 		let prev_config = <configuration::Module<T>>::config();
 
@@ -181,7 +192,9 @@ impl<T: Trait> Module<T> {
 		paras::Module::<T>::initializer_on_new_session(&notification);
 		scheduler::Module::<T>::initializer_on_new_session(&notification);
 		inclusion::Module::<T>::initializer_on_new_session(&notification);
+		disputes::Module::<T>::initializer_on_new_session(&notification, validator_accounts);
 		router::Module::<T>::initializer_on_new_session(&notification);
+		session_info::Module::<T>::initializer_on_new_session(&notification);
 	}
 
 	/// Should be called when a new session occurs. Buffers the session notification to be applied
@@ -194,7 +207,9 @@ impl<T: Trait> Module<T> {
 	)
 		where I: Iterator<Item=(&'a T::AccountId, ValidatorId)>
 	{
-		let validators: Vec<_> = validators.map(|(_, v)| v).collect();
+		let (validator_accounts, validators): (Vec<_>, Vec<_>) = validators
+			.map(|(a, v)| (a.clone(), v))
+			.unzip();
 		let queued: Vec<_> = if let Some(queued) = queued {
 			queued.map(|(_, v)| v).collect()
 		} else {
@@ -205,6 +220,7 @@ impl<T: Trait> Module<T> {
 			apply_at: <frame_system::Module<T>>::block_number() + One::one(),
 			validators,
 			queued,
+			validator_accounts,
 			session_index,
 		}));
 	}
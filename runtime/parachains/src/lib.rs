@@ -28,13 +28,14 @@ use primitives::v1::Id as ParaId;
 use codec::{Decode, Encode};
 
 pub mod configuration;
+pub mod disputes;
 pub mod inclusion;
 pub mod inclusion_inherent;
 pub mod initializer;
 pub mod paras;
 pub mod router;
 pub mod scheduler;
-pub mod validity;
+pub mod session_info;
 
 pub mod runtime_api_impl;
 
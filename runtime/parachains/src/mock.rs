@@ -24,12 +24,12 @@ use sp_runtime::{
 		BlakeTwo256, IdentityLookup,
 	},
 };
-use primitives::v1::{BlockNumber, Header};
+use primitives::v1::{AuthorityDiscoveryId, BlockNumber, Header};
 use frame_support::{
 	impl_outer_origin, impl_outer_dispatch, impl_outer_event, parameter_types,
 	weights::Weight, traits::Randomness as RandomnessT,
 };
-use crate::inclusion;
+use crate::{inclusion, disputes};
 
 /// A test runtime struct.
 #[derive(Clone, Eq, PartialEq)]
@@ -48,7 +48,9 @@ impl_outer_dispatch! {
 impl_outer_event! {
 	pub enum TestEvent for Test {
 		frame_system<T>,
+		pallet_balances<T>,
 		inclusion<T>,
+		disputes<T>,
 	}
 }
 
@@ -95,6 +97,20 @@ impl frame_system::Trait for Test {
 	type SystemWeightInfo = ();
 }
 
+parameter_types! {
+	pub const ExistentialDeposit: u128 = 1;
+}
+
+impl pallet_balances::Trait for Test {
+	type Balance = u128;
+	type Event = TestEvent;
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type MaxLocks = ();
+	type WeightInfo = ();
+}
+
 impl crate::initializer::Trait for Test {
 	type Randomness = TestRandomness;
 }
@@ -103,16 +119,37 @@ impl crate::configuration::Trait for Test { }
 
 impl crate::paras::Trait for Test { }
 
-impl crate::router::Trait for Test { }
+impl crate::router::Trait for Test {
+	type UmpSink = crate::router::MockUmpSink;
+	type Currency = Balances;
+}
 
-impl crate::scheduler::Trait for Test { }
+impl crate::scheduler::Trait for Test {
+	type Currency = Balances;
+}
 
 impl crate::inclusion::Trait for Test {
 	type Event = TestEvent;
 }
 
+impl crate::disputes::Trait for Test {
+	type Event = TestEvent;
+	type PunishValidators = crate::disputes::mock_punishment::MockPunishValidators;
+}
+
+impl crate::session_info::Trait for Test { }
+
+impl crate::session_info::AuthorityDiscoveryTrait for Test {
+	fn authorities() -> Vec<AuthorityDiscoveryId> {
+		Vec::new()
+	}
+}
+
 pub type System = frame_system::Module<Test>;
 
+/// Mocked balances.
+pub type Balances = pallet_balances::Module<Test>;
+
 /// Mocked initializer.
 pub type Initializer = crate::initializer::Module<Test>;
 
@@ -123,8 +160,6 @@ pub type Configuration = crate::configuration::Module<Test>;
 pub type Paras = crate::paras::Module<Test>;
 
 /// Mocked router.
-// TODO: Will be used in the follow ups.
-#[allow(dead_code)]
 pub type Router = crate::router::Module<Test>;
 
 /// Mocked scheduler.
@@ -133,9 +168,16 @@ pub type Scheduler = crate::scheduler::Module<Test>;
 /// Mocked inclusion module.
 pub type Inclusion = crate::inclusion::Module<Test>;
 
+/// Mocked disputes module.
+pub type Disputes = crate::disputes::Module<Test>;
+
+/// Mocked session info module.
+pub type ParaSessionInfo = crate::session_info::Module<Test>;
+
 /// Create a new set of test externalities.
 pub fn new_test_ext(state: GenesisConfig) -> TestExternalities {
 	let mut t = state.system.build_storage::<Test>().unwrap();
+	state.balances.assimilate_storage(&mut t).unwrap();
 	state.configuration.assimilate_storage(&mut t).unwrap();
 	state.paras.assimilate_storage(&mut t).unwrap();
 
@@ -145,6 +187,7 @@ pub fn new_test_ext(state: GenesisConfig) -> TestExternalities {
 #[derive(Default)]
 pub struct GenesisConfig {
 	pub system: frame_system::GenesisConfig,
+	pub balances: pallet_balances::GenesisConfig<Test>,
 	pub configuration: crate::configuration::GenesisConfig<Test>,
 	pub paras: crate::paras::GenesisConfig<Test>,
 }
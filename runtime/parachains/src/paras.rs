@@ -198,7 +198,7 @@ decl_storage! {
 		/// in the context of a relay chain block with a number >= `expected_at`.
 		FutureCodeUpgrades get(fn future_code_upgrade_at): map hasher(twox_64_concat) ParaId => Option<T::BlockNumber>;
 		/// The actual future code of a para.
-		FutureCode: map hasher(twox_64_concat) ParaId => Option<ValidationCode>;
+		FutureCode get(fn future_code): map hasher(twox_64_concat) ParaId => Option<ValidationCode>;
 
 		/// Upcoming paras (chains and threads). These are only updated on session change. Corresponds to an
 		/// entry in the upcoming-genesis map.
@@ -207,6 +207,9 @@ decl_storage! {
 		UpcomingParasGenesis: map hasher(twox_64_concat) ParaId => Option<ParaGenesisArgs>;
 		/// Paras that are to be cleaned up at the end of the session.
 		OutgoingParas: Vec<ParaId>;
+		/// Pairs of a parachain and a parathread whose kinds are to be swapped at the end of the
+		/// session.
+		UpcomingSwaps: Vec<(ParaId, ParaId)>;
 
 	}
 	add_extra_genesis {
@@ -261,6 +264,7 @@ impl<T: Trait> Module<T> {
 		let now = <frame_system::Module<T>>::block_number();
 		let mut parachains = Self::clean_up_outgoing(now);
 		Self::apply_incoming(&mut parachains);
+		Self::apply_swaps(&mut parachains);
 		<Self as Store>::Parachains::set(parachains);
 	}
 
@@ -311,6 +315,35 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	/// Applies all upcoming swaps, turning the parachain of every pair into a parathread and
+	/// the parathread into a parachain. Pairs which aren't a live parachain and a live
+	/// parathread anymore are ignored.
+	fn apply_swaps(parachains: &mut Vec<ParaId>) {
+		let swaps = <Self as Store>::UpcomingSwaps::take();
+		for (one, other) in swaps {
+			let (chain, thread) = if parachains.binary_search(&one).is_ok() {
+				(one, other)
+			} else {
+				(other, one)
+			};
+
+			let chain_index = match parachains.binary_search(&chain) {
+				Ok(i) => i,
+				Err(_) => continue,
+			};
+
+			if <Self as Store>::Parathreads::take(&thread).is_none() {
+				continue;
+			}
+
+			parachains.remove(chain_index);
+			<Self as Store>::Parathreads::insert(&chain, ());
+			if let Err(i) = parachains.binary_search(&thread) {
+				parachains.insert(i, thread);
+			}
+		}
+	}
+
 	// note replacement of the code of para with given `id`, which occured in the
 	// context of the given relay-chain block number. provide the replaced code.
 	//
@@ -437,6 +470,20 @@ impl<T: Trait> Module<T> {
 		outgoing_weight + upcoming_weight
 	}
 
+	/// Schedule the kinds of a parachain and a parathread to be swapped at the start of the
+	/// next session: the parachain becomes a parathread and vice versa. The code and head data
+	/// of both paras stay the same.
+	pub fn schedule_para_swap(one: ParaId, other: ParaId) -> Weight {
+		UpcomingSwaps::mutate(|v| v.push((one, other)));
+
+		T::DbWeight::get().writes(1)
+	}
+
+	/// Whether a para ID is live or scheduled to be initialized at the start of the next session.
+	pub fn is_known_para(id: ParaId) -> bool {
+		<Self as Store>::Heads::contains_key(&id) || <Self as Store>::UpcomingParasGenesis::contains_key(&id)
+	}
+
 	/// Schedule a future code upgrade of the given parachain, to be applied after inclusion
 	/// of a block of the same parachain executed in the context of a relay-chain block
 	/// with number >= `expected_at`
@@ -539,6 +586,11 @@ impl<T: Trait> Module<T> {
 		Parathreads::get(&id).is_some()
 	}
 
+	/// Whether a para ID corresponds to any live parachain or parathread.
+	pub(crate) fn is_valid_para(id: ParaId) -> bool {
+		Self::parachains().binary_search(&id).is_ok() || Self::is_parathread(id)
+	}
+
 	/// The block number of the last scheduled upgrade of the requested para. Includes future upgrades
 	/// if the flag is set. This is the `expected_at` number, not the `activated_at` number.
 	pub(crate) fn last_code_upgrade(id: ParaId, include_future: bool) -> Option<T::BlockNumber> {
@@ -1197,6 +1249,60 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn para_swap_at_session() {
+		new_test_ext(Default::default()).execute_with(|| {
+			run_to_block(1, None);
+
+			let chain = ParaId::from(525);
+			let thread = ParaId::from(999);
+
+			Paras::schedule_para_initialize(
+				chain,
+				ParaGenesisArgs {
+					parachain: true,
+					genesis_head: vec![1].into(),
+					validation_code: vec![1].into(),
+				},
+			);
+
+			Paras::schedule_para_initialize(
+				thread,
+				ParaGenesisArgs {
+					parachain: false,
+					genesis_head: vec![2].into(),
+					validation_code: vec![2].into(),
+				},
+			);
+
+			assert!(Paras::is_known_para(chain));
+			assert!(Paras::is_known_para(thread));
+			assert!(!Paras::is_known_para(ParaId::from(333)));
+
+			run_to_block(2, Some(vec![2]));
+
+			assert_eq!(Paras::parachains(), vec![chain]);
+			assert!(<Paras as Store>::Parathreads::get(&thread).is_some());
+
+			Paras::schedule_para_swap(thread, chain);
+
+			// run to block without session change.
+			run_to_block(3, None);
+
+			assert_eq!(Paras::parachains(), vec![chain]);
+
+			run_to_block(4, Some(vec![4]));
+
+			assert_eq!(Paras::parachains(), vec![thread]);
+			assert!(<Paras as Store>::Parathreads::get(&chain).is_some());
+			assert!(<Paras as Store>::Parathreads::get(&thread).is_none());
+			assert!(<Paras as Store>::UpcomingSwaps::get().is_empty());
+
+			assert_eq!(Paras::current_code(&chain), Some(vec![1].into()));
+			assert_eq!(Paras::current_code(&thread), Some(vec![2].into()));
+		});
+	}
+
 	#[test]
 	fn code_at_with_intermediate() {
 		let acceptance_period = 10;
@@ -0,0 +1,295 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{Trait, Module, Store};
+use crate::configuration::HostConfiguration;
+use sp_std::prelude::*;
+use sp_runtime::traits::{BlakeTwo256, Hash as HashT, SaturatedConversion};
+use frame_support::{StorageMap, weights::Weight, traits::Get};
+use codec::Encode;
+use primitives::v1::{Id as ParaId, DownwardMessage, InboundDownwardMessage, Hash};
+
+/// An error sending a downward message.
+#[cfg_attr(test, derive(Debug))]
+pub enum QueueDownwardMessageError {
+	/// The message being sent exceeds the configured critical message size.
+	ExceedsCriticalMessageSize,
+}
+
+/// Routines and getters related to downward message passing.
+impl<T: Trait> Module<T> {
+	pub(super) fn clean_dmp_after_outgoing(outgoing_para: ParaId) {
+		<Self as Store>::DownwardMessageQueues::remove(&outgoing_para);
+		<Self as Store>::DownwardMessageQueueHeads::remove(&outgoing_para);
+	}
+
+	/// Enqueue a downward message to a specific recipient para.
+	///
+	/// When encoded, the message should not exceed the `config.critical_downward_message_size`.
+	/// Otherwise, the message won't be sent and `Err` will be returned.
+	pub fn queue_downward_message(
+		config: &HostConfiguration<T::BlockNumber>,
+		para: ParaId,
+		msg: DownwardMessage,
+	) -> Result<(), QueueDownwardMessageError> {
+		let serialized_len = msg.encoded_size() as u32;
+		if serialized_len > config.critical_downward_message_size {
+			return Err(QueueDownwardMessageError::ExceedsCriticalMessageSize);
+		}
+
+		let inbound = InboundDownwardMessage {
+			msg,
+			sent_at: <frame_system::Module<T>>::block_number(),
+		};
+
+		// obtain the new link in the MQC and update the head.
+		<Self as Store>::DownwardMessageQueueHeads::mutate(para, |head| {
+			let prev_head = *head;
+			*head = BlakeTwo256::hash_of(&(
+				prev_head,
+				inbound.sent_at,
+				BlakeTwo256::hash_of(&inbound.msg),
+			));
+		});
+
+		<Self as Store>::DownwardMessageQueues::mutate(para, |v| {
+			v.push(inbound);
+		});
+
+		Ok(())
+	}
+
+	/// Checks if the number of processed downward messages is valid, i.e.:
+	///
+	/// - if there are pending messages then `processed_downward_messages` should be at least 1,
+	/// - `processed_downward_messages` should not be greater than the number of pending messages.
+	///
+	/// Returns true if all checks have been passed.
+	pub(crate) fn check_processed_downward_messages(
+		para: ParaId,
+		processed_downward_messages: u32,
+	) -> bool {
+		let dmq_length = Self::dmq_length(para);
+
+		if dmq_length > 0 && processed_downward_messages == 0 {
+			return false;
+		}
+		if dmq_length < processed_downward_messages {
+			return false;
+		}
+
+		true
+	}
+
+	/// Prunes the specified number of messages from the downward message queue of the given para.
+	pub(crate) fn prune_dmq(para: ParaId, processed_downward_messages: u32) -> Weight {
+		if processed_downward_messages == 0 {
+			return 0;
+		}
+
+		let mut queue = <Self as Store>::DownwardMessageQueues::take(&para);
+		let processed_downward_messages = processed_downward_messages as usize;
+		if processed_downward_messages >= queue.len() {
+			// reaching the `>` case is unexpected due to the constraint established by
+			// `check_processed_downward_messages`. But better be safe than sorry.
+			queue.clear();
+		} else {
+			queue.drain(..processed_downward_messages);
+		}
+
+		if !queue.is_empty() {
+			<Self as Store>::DownwardMessageQueues::insert(&para, queue);
+		}
+
+		T::DbWeight::get().reads_writes(1, 1)
+	}
+
+	/// Returns the Head of Message Queue Chain for the given para or all zeros if there is none
+	/// associated with it.
+	pub(crate) fn dmq_mqc_head(para: ParaId) -> Hash {
+		<Self as Store>::DownwardMessageQueueHeads::get(&para)
+	}
+
+	/// Returns the number of pending downward messages addressed to the given para.
+	///
+	/// Returns 0 if the para doesn't have an associated downward message queue.
+	pub(crate) fn dmq_length(para: ParaId) -> u32 {
+		<Self as Store>::DownwardMessageQueues::decode_len(&para)
+			.unwrap_or(0)
+			.saturated_into::<u32>()
+	}
+
+	/// Returns the downward message queue contents for the given para.
+	///
+	/// The most recent messages are the latest in the vector.
+	pub fn dmq_contents(recipient: ParaId) -> Vec<InboundDownwardMessage<T::BlockNumber>> {
+		<Self as Store>::DownwardMessageQueues::get(&recipient)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::router::tests::default_genesis_config;
+	use crate::mock::{Configuration, Router, System, new_test_ext};
+	use frame_support::StorageValue;
+
+	fn queue_downward_message(
+		para_id: ParaId,
+		msg: DownwardMessage,
+	) -> Result<(), QueueDownwardMessageError> {
+		Router::queue_downward_message(&Configuration::config(), para_id, msg)
+	}
+
+	fn opaque_msg(data: Vec<u8>) -> DownwardMessage {
+		DownwardMessage::Opaque(data)
+	}
+
+	#[test]
+	fn dmq_length_and_head_updated_properly() {
+		let a = ParaId::from(1312);
+		let b = ParaId::from(228);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			assert_eq!(Router::dmq_length(a), 0);
+			assert_eq!(Router::dmq_length(b), 0);
+
+			queue_downward_message(a, opaque_msg(vec![1, 2, 3])).unwrap();
+
+			assert_eq!(Router::dmq_length(a), 1);
+			assert_eq!(Router::dmq_length(b), 0);
+			assert!(!Router::dmq_mqc_head(a).is_zero());
+			assert!(Router::dmq_mqc_head(b).is_zero());
+		});
+	}
+
+	#[test]
+	fn dmq_mqc_head_fixture() {
+		let a = ParaId::from(2000);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			System::set_block_number(1);
+			queue_downward_message(a, opaque_msg(vec![1, 2, 3])).unwrap();
+			System::set_block_number(2);
+			queue_downward_message(a, opaque_msg(vec![4, 5, 6])).unwrap();
+
+			// recompute the chain by hand and compare it against the stored head.
+			let contents = Router::dmq_contents(a);
+			assert_eq!(contents.len(), 2);
+			let expected_head = contents.iter().fold(Hash::zero(), |head, m| {
+				BlakeTwo256::hash_of(&(head, m.sent_at, BlakeTwo256::hash_of(&m.msg)))
+			});
+			assert_eq!(Router::dmq_mqc_head(a), expected_head);
+			assert_eq!(contents[0].sent_at, 1);
+			assert_eq!(contents[1].sent_at, 2);
+		});
+	}
+
+	#[test]
+	fn check_processed_downward_messages() {
+		let a = ParaId::from(1312);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			// processed_downward_messages=0 is allowed when the DMQ is empty.
+			assert!(Router::check_processed_downward_messages(a, 0));
+
+			queue_downward_message(a, opaque_msg(vec![1, 2, 3])).unwrap();
+			queue_downward_message(a, opaque_msg(vec![4, 5, 6])).unwrap();
+			queue_downward_message(a, opaque_msg(vec![7, 8, 9])).unwrap();
+
+			// 0 doesn't pass if the DMQ has msgs.
+			assert!(!Router::check_processed_downward_messages(a, 0));
+			// a candidate can consume up to 3 messages
+			assert!(Router::check_processed_downward_messages(a, 1));
+			assert!(Router::check_processed_downward_messages(a, 2));
+			assert!(Router::check_processed_downward_messages(a, 3));
+			// there is no 4 messages in the queue
+			assert!(!Router::check_processed_downward_messages(a, 4));
+		});
+	}
+
+	#[test]
+	fn dmq_pruning() {
+		let a = ParaId::from(1312);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			assert_eq!(Router::dmq_length(a), 0);
+
+			queue_downward_message(a, opaque_msg(vec![1, 2, 3])).unwrap();
+			queue_downward_message(a, opaque_msg(vec![4, 5, 6])).unwrap();
+			queue_downward_message(a, opaque_msg(vec![7, 8, 9])).unwrap();
+			assert_eq!(Router::dmq_length(a), 3);
+
+			// pruning 0 elements shouldn't change anything.
+			Router::prune_dmq(a, 0);
+			assert_eq!(Router::dmq_length(a), 3);
+
+			Router::prune_dmq(a, 2);
+			assert_eq!(Router::dmq_length(a), 1);
+			assert_eq!(Router::dmq_contents(a)[0].msg, opaque_msg(vec![7, 8, 9]));
+
+			// pruning the rest removes the queue but keeps the MQC head.
+			let head = Router::dmq_mqc_head(a);
+			Router::prune_dmq(a, 1);
+			assert_eq!(Router::dmq_length(a), 0);
+			assert!(!<Router as Store>::DownwardMessageQueues::contains_key(&a));
+			assert_eq!(Router::dmq_mqc_head(a), head);
+		});
+	}
+
+	#[test]
+	fn queue_downward_message_critical() {
+		let a = ParaId::from(1312);
+
+		let mut genesis = default_genesis_config();
+		genesis.configuration.config.critical_downward_message_size = 7;
+
+		new_test_ext(genesis).execute_with(|| {
+			let smol = opaque_msg([0; 3].to_vec());
+			let big = opaque_msg([1; 8].to_vec());
+
+			// still within limits
+			assert_eq!(smol.encode().len(), 5);
+			assert!(queue_downward_message(a, smol).is_ok());
+
+			// that's too big
+			assert_eq!(big.encode().len(), 10);
+			assert!(queue_downward_message(a, big).is_err());
+		});
+	}
+
+	#[test]
+	fn queues_of_outgoing_paras_are_cleaned_up() {
+		let a = ParaId::from(1312);
+		let b = ParaId::from(228);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			queue_downward_message(a, opaque_msg(vec![1, 2, 3])).unwrap();
+			queue_downward_message(b, opaque_msg(vec![4, 5, 6])).unwrap();
+
+			Router::schedule_para_cleanup(a);
+			Router::initializer_on_new_session(&Default::default());
+
+			assert_eq!(Router::dmq_length(a), 0);
+			assert!(Router::dmq_mqc_head(a).is_zero());
+			assert_eq!(Router::dmq_length(b), 1);
+			assert!(!Router::dmq_mqc_head(b).is_zero());
+
+			// the list of outgoing paras is drained at the session boundary.
+			assert!(<Router as Store>::OutgoingParas::get().is_empty());
+		});
+	}
+}
@@ -0,0 +1,1174 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{Trait, Module, Store, Error};
+use crate::{configuration::{self, HostConfiguration}, paras};
+use codec::{Decode, Encode};
+use frame_support::{
+	ensure, StorageMap, StorageValue,
+	dispatch::{DispatchError, DispatchResult},
+	traits::{Get, ReservableCurrency},
+	weights::Weight,
+};
+use frame_system::ensure_signed;
+use primitives::v1::{
+	AccountIdConversion, Balance, Hash, HrmpChannelId, Id as ParaId, InboundHrmpMessage,
+	OutboundHrmpMessage, SessionIndex,
+};
+use sp_runtime::traits::{BlakeTwo256, Hash as HashT, UniqueSaturatedInto};
+use sp_std::{
+	prelude::*,
+	collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+};
+
+/// A description of a request to open an HRMP channel.
+#[derive(Encode, Decode)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct HrmpOpenChannelRequest {
+	/// Indicates if this request was confirmed by the recipient.
+	pub confirmed: bool,
+	/// How many session boundaries ago this request was seen.
+	pub age: SessionIndex,
+	/// The amount that the sender supplied at the time of creation of this request.
+	pub sender_deposit: Balance,
+	/// The maximum number of messages that can be pending in the channel at once.
+	pub limit_used_places: u32,
+	/// The maximum total size of the messages that can be pending in the channel at once.
+	pub limit_used_bytes: u32,
+	/// The maximum message size that could be put into the channel.
+	pub limit_message_size: u32,
+}
+
+/// A metadata of an HRMP channel.
+#[derive(Encode, Decode)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct HrmpChannel {
+	/// The amount that the sender supplied as a deposit when opening this channel.
+	pub sender_deposit: Balance,
+	/// The amount that the recipient supplied as a deposit when accepting opening this channel.
+	pub recipient_deposit: Balance,
+	/// The maximum number of messages that can be pending in the channel at once.
+	pub limit_used_places: u32,
+	/// The maximum total size of the messages that can be pending in the channel at once.
+	pub limit_used_bytes: u32,
+	/// The maximum message size that could be put into the channel.
+	pub limit_message_size: u32,
+	/// The current number of messages pending in the channel.
+	/// Invariant: should be less or equal to `limit_used_places`.
+	pub used_places: u32,
+	/// The total size in bytes of all message payloads in the channel.
+	/// Invariant: should be less or equal to `limit_used_bytes`.
+	pub used_bytes: u32,
+	/// A head of the Message Queue Chain for this channel. Each link in this chain has a form:
+	/// `(prev_head, B, H(M))`, where
+	/// - `prev_head`: is the previous value of `mqc_head`.
+	/// - `B`: is the [relay-chain] block number in which a message was appended
+	/// - `H(M)`: is the hash of the message being appended.
+	/// This value is initialized to a special value that consists of all zeroes which indicates
+	/// that no messages were previously added.
+	pub mqc_head: Hash,
+}
+
+/// Routines and getters related to HRMP.
+impl<T: Trait> Module<T> {
+	/// Ensures that the given origin is the sovereign account of some para and returns the id of
+	/// that para.
+	pub(super) fn ensure_para_origin(origin: T::Origin) -> Result<ParaId, DispatchError> {
+		let who = ensure_signed(origin)?;
+		ParaId::try_from_account(&who).ok_or_else(|| Error::<T>::NotParaOrigin.into())
+	}
+
+	/// Remove all storage entries associated with the given para.
+	pub(super) fn clean_hrmp_after_outgoing(outgoing_para: ParaId) {
+		Self::clean_open_channel_requests(outgoing_para);
+
+		<Self as Store>::HrmpOpenChannelRequestCount::remove(&outgoing_para);
+		<Self as Store>::HrmpAcceptedChannelRequestCount::remove(&outgoing_para);
+
+		// close all channels where the outgoing para acts as the recipient.
+		for sender in <Self as Store>::HrmpIngressChannelsIndex::take(&outgoing_para) {
+			Self::close_hrmp_channel(&HrmpChannelId {
+				sender,
+				recipient: outgoing_para,
+			});
+		}
+		// close all channels where the outgoing para acts as the sender.
+		for recipient in <Self as Store>::HrmpEgressChannelsIndex::take(&outgoing_para) {
+			Self::close_hrmp_channel(&HrmpChannelId {
+				sender: outgoing_para,
+				recipient,
+			});
+		}
+
+		<Self as Store>::HrmpWatermarks::remove(&outgoing_para);
+		<Self as Store>::HrmpChannelDigests::remove(&outgoing_para);
+	}
+
+	/// Remove all open channel requests in which the given para participates either as the sender
+	/// or as the recipient, refunding the deposits and adjusting the request counters of the
+	/// counterparties.
+	fn clean_open_channel_requests(outgoing_para: ParaId) {
+		let config = <configuration::Module<T>>::config();
+		let mut open_req_channels = <Self as Store>::HrmpOpenChannelRequestsList::get();
+		let len_before = open_req_channels.len();
+
+		open_req_channels.retain(|channel_id| {
+			if channel_id.sender != outgoing_para && channel_id.recipient != outgoing_para {
+				return true;
+			}
+
+			if let Some(request) = <Self as Store>::HrmpOpenChannelRequests::take(channel_id) {
+				T::Currency::unreserve(
+					&channel_id.sender.into_account(),
+					request.sender_deposit.unique_saturated_into(),
+				);
+				Self::decrement_count::<<Self as Store>::HrmpOpenChannelRequestCount>(
+					&channel_id.sender,
+				);
+
+				if request.confirmed {
+					T::Currency::unreserve(
+						&channel_id.recipient.into_account(),
+						config.hrmp_recipient_deposit.unique_saturated_into(),
+					);
+					Self::decrement_count::<<Self as Store>::HrmpAcceptedChannelRequestCount>(
+						&channel_id.recipient,
+					);
+				}
+			}
+
+			false
+		});
+
+		if open_req_channels.len() != len_before {
+			<Self as Store>::HrmpOpenChannelRequestsList::put(open_req_channels);
+		}
+	}
+
+	/// Iterate over all open channel requests and:
+	///
+	/// - prune the stale requests
+	/// - enact the confirmed requests
+	pub(super) fn process_hrmp_open_channel_requests(config: &HostConfiguration<T::BlockNumber>) {
+		let mut open_req_channels = <Self as Store>::HrmpOpenChannelRequestsList::get();
+		if open_req_channels.is_empty() {
+			return;
+		}
+
+		// iterate the vector starting from the end making our way to the beginning. This way we
+		// can leverage `swap_remove` to efficiently remove an item during iteration.
+		let mut idx = open_req_channels.len();
+		loop {
+			// bail if we've iterated over all items.
+			if idx == 0 {
+				break;
+			}
+
+			idx -= 1;
+			let channel_id = open_req_channels[idx].clone();
+			let mut request = <Self as Store>::HrmpOpenChannelRequests::get(&channel_id)
+				.expect(
+					"can't be `None` due to the invariant that the list contains the same items as the set; qed"
+				);
+
+			if request.confirmed {
+				if <paras::Module<T>>::is_valid_para(channel_id.sender)
+					&& <paras::Module<T>>::is_valid_para(channel_id.recipient)
+				{
+					<Self as Store>::HrmpChannels::insert(
+						&channel_id,
+						HrmpChannel {
+							sender_deposit: request.sender_deposit,
+							recipient_deposit: config.hrmp_recipient_deposit,
+							limit_used_places: request.limit_used_places,
+							limit_used_bytes: request.limit_used_bytes,
+							limit_message_size: request.limit_message_size,
+							used_places: 0,
+							used_bytes: 0,
+							mqc_head: Default::default(),
+						},
+					);
+
+					<Self as Store>::HrmpIngressChannelsIndex::mutate(&channel_id.recipient, |v| {
+						if let Err(i) = v.binary_search(&channel_id.sender) {
+							v.insert(i, channel_id.sender);
+						}
+					});
+					<Self as Store>::HrmpEgressChannelsIndex::mutate(&channel_id.sender, |v| {
+						if let Err(i) = v.binary_search(&channel_id.recipient) {
+							v.insert(i, channel_id.recipient);
+						}
+					});
+				} else {
+					// one of the parties went away in the meantime. Return the deposits.
+					T::Currency::unreserve(
+						&channel_id.sender.into_account(),
+						request.sender_deposit.unique_saturated_into(),
+					);
+					T::Currency::unreserve(
+						&channel_id.recipient.into_account(),
+						config.hrmp_recipient_deposit.unique_saturated_into(),
+					);
+				}
+
+				Self::decrement_count::<<Self as Store>::HrmpOpenChannelRequestCount>(
+					&channel_id.sender,
+				);
+				Self::decrement_count::<<Self as Store>::HrmpAcceptedChannelRequestCount>(
+					&channel_id.recipient,
+				);
+
+				<Self as Store>::HrmpOpenChannelRequests::remove(&channel_id);
+				open_req_channels.swap_remove(idx);
+			} else {
+				request.age += 1;
+				if request.age >= config.hrmp_open_request_ttl {
+					// got stale
+
+					Self::decrement_count::<<Self as Store>::HrmpOpenChannelRequestCount>(
+						&channel_id.sender,
+					);
+
+					T::Currency::unreserve(
+						&channel_id.sender.into_account(),
+						request.sender_deposit.unique_saturated_into(),
+					);
+
+					<Self as Store>::HrmpOpenChannelRequests::remove(&channel_id);
+					open_req_channels.swap_remove(idx);
+				} else {
+					<Self as Store>::HrmpOpenChannelRequests::insert(&channel_id, request);
+				}
+			}
+		}
+
+		<Self as Store>::HrmpOpenChannelRequestsList::put(open_req_channels);
+	}
+
+	/// Iterate over all close channel requests unconditionally closing the channels.
+	pub(super) fn process_hrmp_close_channel_requests() {
+		let close_reqs = <Self as Store>::HrmpCloseChannelRequestsList::take();
+		for condemned_ch_id in close_reqs {
+			<Self as Store>::HrmpCloseChannelRequests::remove(&condemned_ch_id);
+			Self::close_hrmp_channel(&condemned_ch_id);
+		}
+	}
+
+	/// Close and remove the designated HRMP channel.
+	///
+	/// This includes returning the deposits. However, it doesn't include updating the ingress/egress
+	/// indicies.
+	fn close_hrmp_channel(channel_id: &HrmpChannelId) {
+		if let Some(HrmpChannel {
+			sender_deposit,
+			recipient_deposit,
+			..
+		}) = <Self as Store>::HrmpChannels::take(channel_id)
+		{
+			T::Currency::unreserve(
+				&channel_id.sender.into_account(),
+				sender_deposit.unique_saturated_into(),
+			);
+			T::Currency::unreserve(
+				&channel_id.recipient.into_account(),
+				recipient_deposit.unique_saturated_into(),
+			);
+		}
+
+		<Self as Store>::HrmpChannelContents::remove(channel_id);
+
+		Self::remove_from_index::<<Self as Store>::HrmpEgressChannelsIndex>(
+			&channel_id.sender,
+			&channel_id.recipient,
+		);
+		Self::remove_from_index::<<Self as Store>::HrmpIngressChannelsIndex>(
+			&channel_id.recipient,
+			&channel_id.sender,
+		);
+	}
+
+	/// Check that the candidate of the given recipient controls the HRMP watermark properly.
+	pub(crate) fn check_hrmp_watermark(
+		recipient: ParaId,
+		relay_chain_parent_number: T::BlockNumber,
+		new_hrmp_watermark: T::BlockNumber,
+	) -> bool {
+		// First, check where the watermark CANNOT legally land.
+		//
+		// (a) For ensuring that messages are eventually, a rule requires each parablock new
+		//     watermark should be greater than the last one.
+		//
+		// (b) However, a parachain cannot read into "the future", therefore the watermark should
+		//     not be greater than the relay-chain context block which the parablock refers to.
+		if let Some(last_watermark) = <Self as Store>::HrmpWatermarks::get(&recipient) {
+			if new_hrmp_watermark <= last_watermark {
+				return false;
+			}
+		}
+		if new_hrmp_watermark > relay_chain_parent_number {
+			return false;
+		}
+
+		// Second, check where the watermark CAN land. It's one of the following:
+		//
+		// (a) The relay parent block number.
+		// (b) A relay-chain block in which this para received at least one message.
+		if new_hrmp_watermark == relay_chain_parent_number {
+			true
+		} else {
+			let digest = <Self as Store>::HrmpChannelDigests::get(&recipient);
+			digest
+				.binary_search_by_key(&new_hrmp_watermark, |(block_no, _)| *block_no)
+				.is_ok()
+		}
+	}
+
+	/// Check that all the horizontal messages sent by a candidate fit into the channels they are
+	/// addressed to.
+	///
+	/// The messages are expected to be sorted by ascending recipient with no two messages sharing
+	/// the same recipient, and their number shouldn't exceed
+	/// `config.hrmp_max_message_num_per_candidate`.
+	pub(crate) fn check_outbound_hrmp(
+		config: &HostConfiguration<T::BlockNumber>,
+		sender: ParaId,
+		out_hrmp_msgs: &[OutboundHrmpMessage<ParaId>],
+	) -> bool {
+		if out_hrmp_msgs.len() as u32 > config.hrmp_max_message_num_per_candidate {
+			return false;
+		}
+
+		let mut last_recipient = None::<ParaId>;
+		for out_msg in out_hrmp_msgs {
+			match last_recipient {
+				// the messages must be sorted in ascending order and there must be no two messages
+				// sent to the same recipient. Thus we can check that every recipient is strictly
+				// greater than the previous one.
+				Some(last_recipient) if out_msg.recipient <= last_recipient => return false,
+				_ => last_recipient = Some(out_msg.recipient),
+			}
+
+			let channel_id = HrmpChannelId {
+				sender,
+				recipient: out_msg.recipient,
+			};
+
+			let channel = match <Self as Store>::HrmpChannels::get(&channel_id) {
+				Some(channel) => channel,
+				None => return false,
+			};
+
+			let msg_size = out_msg.data.len() as u32;
+			if msg_size > channel.limit_message_size {
+				return false;
+			}
+
+			if channel.used_places + 1 > channel.limit_used_places
+				|| channel.used_bytes.saturating_add(msg_size) > channel.limit_used_bytes
+			{
+				return false;
+			}
+		}
+
+		true
+	}
+
+	/// Prunes the messages up to the new watermark from the channels that point to the given
+	/// recipient and moves the watermark.
+	pub(crate) fn prune_hrmp(recipient: ParaId, new_hrmp_watermark: T::BlockNumber) -> Weight {
+		let mut weight = 0;
+
+		// sift through the incoming messages digest to collect the paras that sent at least one
+		// message to this parachain between the old and new watermarks.
+		let senders = {
+			let mut senders = BTreeSet::new();
+			let mut leftover = Vec::new();
+			let digest = <Self as Store>::HrmpChannelDigests::take(&recipient);
+			for (block_no, paras_sent_msg) in digest {
+				if block_no <= new_hrmp_watermark {
+					senders.extend(paras_sent_msg);
+				} else {
+					leftover.push((block_no, paras_sent_msg));
+				}
+			}
+			if !leftover.is_empty() {
+				<Self as Store>::HrmpChannelDigests::insert(&recipient, leftover);
+			}
+
+			senders
+		};
+		weight += T::DbWeight::get().reads_writes(1, 1);
+
+		// having all senders we can trivially find out the channels which we need to prune.
+		for sender in senders {
+			let channel_id = HrmpChannelId { sender, recipient };
+
+			let (pruned_places, pruned_bytes) = {
+				let contents = <Self as Store>::HrmpChannelContents::take(&channel_id);
+				let (pruned, leftover): (Vec<_>, Vec<_>) = contents
+					.into_iter()
+					.partition(|msg| msg.sent_at <= new_hrmp_watermark);
+
+				if !leftover.is_empty() {
+					<Self as Store>::HrmpChannelContents::insert(&channel_id, leftover);
+				}
+
+				let pruned_bytes = pruned
+					.iter()
+					.fold(0u32, |acc, msg| acc.saturating_add(msg.data.len() as u32));
+				(pruned.len() as u32, pruned_bytes)
+			};
+
+			<Self as Store>::HrmpChannels::mutate(&channel_id, |channel| {
+				if let Some(ref mut channel) = channel {
+					channel.used_places = channel.used_places.saturating_sub(pruned_places);
+					channel.used_bytes = channel.used_bytes.saturating_sub(pruned_bytes);
+				}
+			});
+
+			weight += T::DbWeight::get().reads_writes(2, 2);
+		}
+
+		<Self as Store>::HrmpWatermarks::insert(&recipient, new_hrmp_watermark);
+		weight += T::DbWeight::get().reads_writes(0, 1);
+
+		weight
+	}
+
+	/// Process the outbound HRMP messages by putting them into the appropriate recipient queues.
+	///
+	/// Returns the amount of weight consumed.
+	pub(crate) fn queue_outbound_hrmp(
+		sender: ParaId,
+		out_hrmp_msgs: Vec<OutboundHrmpMessage<ParaId>>,
+	) -> Weight {
+		let mut weight = 0;
+		let now = <frame_system::Module<T>>::block_number();
+
+		for out_msg in out_hrmp_msgs {
+			let channel_id = HrmpChannelId {
+				sender,
+				recipient: out_msg.recipient,
+			};
+
+			let mut channel = match <Self as Store>::HrmpChannels::get(&channel_id) {
+				Some(channel) => channel,
+				None => {
+					// apparently, that since acceptance of this candidate the recipient was
+					// offboarded and the channel no longer exists.
+					continue;
+				}
+			};
+
+			let inbound = InboundHrmpMessage {
+				sent_at: now,
+				data: out_msg.data,
+			};
+
+			// book keeping
+			channel.used_places += 1;
+			channel.used_bytes += inbound.data.len() as u32;
+
+			// update the MQC head.
+			channel.mqc_head = BlakeTwo256::hash_of(&(
+				channel.mqc_head,
+				inbound.sent_at,
+				BlakeTwo256::hash_of(&inbound.data),
+			));
+
+			<Self as Store>::HrmpChannels::insert(&channel_id, channel);
+			<Self as Store>::HrmpChannelContents::append(&channel_id, inbound);
+
+			// The digests are sorted in ascending by block number order. Assuming absence of
+			// contextual execution, there are only two possible scenarios here:
+			//
+			// (a) It's the first time anybody sends a message to this recipient within this block.
+			//     In this case, the digest vector would be empty or the block number of the latest
+			//     entry is smaller than the current.
+			//
+			// (b) Somebody has already sent a message within the current block. That means that
+			//     the block number of the latest entry is equal to the current.
+			//
+			// Note that having the latest entry greater than the current block number is a logical
+			// error.
+			let mut recipient_digest =
+				<Self as Store>::HrmpChannelDigests::get(&channel_id.recipient);
+			if let Some(cur_block_digest) = recipient_digest
+				.last_mut()
+				.filter(|(block_no, _)| *block_no == now)
+				.map(|(_, ref mut d)| d)
+			{
+				if let Err(i) = cur_block_digest.binary_search(&sender) {
+					cur_block_digest.insert(i, sender);
+				}
+			} else {
+				recipient_digest.push((now, sp_std::vec![sender]));
+			}
+			<Self as Store>::HrmpChannelDigests::insert(&channel_id.recipient, recipient_digest);
+
+			weight += T::DbWeight::get().reads_writes(2, 3);
+		}
+
+		weight
+	}
+
+	pub(super) fn init_open_channel(
+		origin: ParaId,
+		recipient: ParaId,
+		max_places: u32,
+		max_message_size: u32,
+	) -> DispatchResult {
+		ensure!(origin != recipient, Error::<T>::OpenHrmpChannelToSelf);
+		ensure!(
+			<paras::Module<T>>::is_valid_para(recipient),
+			Error::<T>::OpenHrmpChannelInvalidRecipient,
+		);
+
+		let config = <configuration::Module<T>>::config();
+		ensure!(max_places > 0, Error::<T>::OpenHrmpChannelZeroCapacity);
+		ensure!(
+			max_places <= config.hrmp_channel_max_places,
+			Error::<T>::OpenHrmpChannelCapacityExceedsLimit,
+		);
+		ensure!(max_message_size > 0, Error::<T>::OpenHrmpChannelZeroMessageSize);
+		ensure!(
+			max_message_size <= config.hrmp_channel_max_message_size,
+			Error::<T>::OpenHrmpChannelMessageSizeExceedsLimit,
+		);
+
+		let channel_id = HrmpChannelId {
+			sender: origin,
+			recipient,
+		};
+		ensure!(
+			<Self as Store>::HrmpOpenChannelRequests::get(&channel_id).is_none(),
+			Error::<T>::OpenHrmpChannelAlreadyRequested,
+		);
+		ensure!(
+			<Self as Store>::HrmpChannels::get(&channel_id).is_none(),
+			Error::<T>::OpenHrmpChannelAlreadyExists,
+		);
+
+		let egress_cnt =
+			<Self as Store>::HrmpEgressChannelsIndex::decode_len(&origin).unwrap_or(0) as u32;
+		let open_req_cnt = <Self as Store>::HrmpOpenChannelRequestCount::get(&origin);
+		let channel_num_limit = if <paras::Module<T>>::is_parathread(origin) {
+			config.hrmp_max_parathread_outbound_channels
+		} else {
+			config.hrmp_max_parachain_outbound_channels
+		};
+		ensure!(
+			egress_cnt + open_req_cnt < channel_num_limit,
+			Error::<T>::OpenHrmpChannelLimitExceeded,
+		);
+
+		T::Currency::reserve(
+			&origin.into_account(),
+			config.hrmp_sender_deposit.unique_saturated_into(),
+		)?;
+
+		<Self as Store>::HrmpOpenChannelRequestCount::insert(&origin, open_req_cnt + 1);
+		<Self as Store>::HrmpOpenChannelRequests::insert(
+			&channel_id,
+			HrmpOpenChannelRequest {
+				confirmed: false,
+				age: 0,
+				sender_deposit: config.hrmp_sender_deposit,
+				limit_used_places: max_places,
+				limit_used_bytes: config.hrmp_channel_max_size,
+				limit_message_size: max_message_size,
+			},
+		);
+		<Self as Store>::HrmpOpenChannelRequestsList::append(channel_id);
+
+		Ok(())
+	}
+
+	pub(super) fn accept_open_channel(origin: ParaId, sender: ParaId) -> DispatchResult {
+		let channel_id = HrmpChannelId {
+			sender,
+			recipient: origin,
+		};
+		let mut channel_req = <Self as Store>::HrmpOpenChannelRequests::get(&channel_id)
+			.ok_or(Error::<T>::AcceptHrmpChannelDoesntExist)?;
+		ensure!(
+			!channel_req.confirmed,
+			Error::<T>::AcceptHrmpChannelAlreadyConfirmed,
+		);
+
+		// check if by accepting this open channel request, this parachain would exceed the
+		// number of inbound channels.
+		let config = <configuration::Module<T>>::config();
+		let channel_num_limit = if <paras::Module<T>>::is_parathread(origin) {
+			config.hrmp_max_parathread_inbound_channels
+		} else {
+			config.hrmp_max_parachain_inbound_channels
+		};
+		let ingress_cnt =
+			<Self as Store>::HrmpIngressChannelsIndex::decode_len(&origin).unwrap_or(0) as u32;
+		let accepted_cnt = <Self as Store>::HrmpAcceptedChannelRequestCount::get(&origin);
+		ensure!(
+			ingress_cnt + accepted_cnt < channel_num_limit,
+			Error::<T>::AcceptHrmpChannelLimitExceeded,
+		);
+
+		T::Currency::reserve(
+			&origin.into_account(),
+			config.hrmp_recipient_deposit.unique_saturated_into(),
+		)?;
+
+		// persist the updated open channel request and then increment the number of accepted
+		// channels.
+		channel_req.confirmed = true;
+		<Self as Store>::HrmpOpenChannelRequests::insert(&channel_id, channel_req);
+		<Self as Store>::HrmpAcceptedChannelRequestCount::insert(&origin, accepted_cnt + 1);
+
+		Ok(())
+	}
+
+	pub(super) fn close_channel(origin: ParaId, channel_id: HrmpChannelId) -> DispatchResult {
+		// check if the origin is allowed to close the channel.
+		ensure!(
+			origin == channel_id.sender || origin == channel_id.recipient,
+			Error::<T>::CloseHrmpChannelUnauthorized,
+		);
+
+		// check if the channel requested to close does exist.
+		ensure!(
+			<Self as Store>::HrmpChannels::get(&channel_id).is_some(),
+			Error::<T>::CloseHrmpChannelDoesntExist,
+		);
+
+		// check that there is no outstanding close request for this channel
+		ensure!(
+			<Self as Store>::HrmpCloseChannelRequests::get(&channel_id).is_none(),
+			Error::<T>::CloseHrmpChannelAlreadyUnderway,
+		);
+
+		<Self as Store>::HrmpCloseChannelRequests::insert(&channel_id, ());
+		<Self as Store>::HrmpCloseChannelRequestsList::append(channel_id);
+
+		Ok(())
+	}
+
+	/// Returns the list of MQC heads for the inbound channels of the given recipient para paired
+	/// with the sender para ids. This vector is sorted ascending by the para id and doesn't contain
+	/// multiple entries with the same sender.
+	pub(crate) fn hrmp_mqc_heads(recipient: ParaId) -> Vec<(ParaId, Hash)> {
+		let sender_set = <Self as Store>::HrmpIngressChannelsIndex::get(&recipient);
+
+		// The ingress channels vector is sorted, thus `mqc_heads` is sorted as well.
+		let mut mqc_heads = Vec::with_capacity(sender_set.len());
+		for sender in sender_set {
+			let channel_metadata =
+				<Self as Store>::HrmpChannels::get(&HrmpChannelId { sender, recipient });
+			let mqc_head = channel_metadata
+				.map(|metadata| metadata.mqc_head)
+				.unwrap_or_default();
+			mqc_heads.push((sender, mqc_head));
+		}
+
+		mqc_heads
+	}
+
+	/// Returns contents of all channels addressed to the given recipient. Channels that have no
+	/// messages in them are also included.
+	pub(crate) fn inbound_hrmp_channels_contents(
+		recipient: ParaId,
+	) -> BTreeMap<ParaId, Vec<InboundHrmpMessage<T::BlockNumber>>> {
+		let sender_set = <Self as Store>::HrmpIngressChannelsIndex::get(&recipient);
+
+		let mut inbound_hrmp_channels_contents = BTreeMap::new();
+		for sender in sender_set {
+			let channel_contents =
+				<Self as Store>::HrmpChannelContents::get(&HrmpChannelId { sender, recipient });
+			inbound_hrmp_channels_contents.insert(sender, channel_contents);
+		}
+
+		inbound_hrmp_channels_contents
+	}
+
+	/// Decrements the counter stored under the given key, removing the entry once it drops to
+	/// zero.
+	fn decrement_count<S: StorageMap<ParaId, u32, Query = u32>>(para: &ParaId) {
+		let count = S::get(para);
+		if count <= 1 {
+			S::remove(para);
+		} else {
+			S::insert(para, count - 1);
+		}
+	}
+
+	/// Removes the `item` from the sorted index stored under `key`, removing the entry altogether
+	/// if it becomes empty.
+	fn remove_from_index<S: StorageMap<ParaId, Vec<ParaId>, Query = Vec<ParaId>>>(
+		key: &ParaId,
+		item: &ParaId,
+	) {
+		let mut index = S::get(key);
+		if let Ok(i) = index.binary_search(item) {
+			index.remove(i);
+		}
+
+		if index.is_empty() {
+			S::remove(key);
+		} else {
+			S::insert(key, index);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mock::{
+		new_test_ext, Balances, Configuration, Origin, Router, System,
+		GenesisConfig as MockGenesisConfig,
+	};
+	use crate::initializer::SessionChangeNotification;
+	use crate::paras::ParaGenesisArgs;
+	use frame_support::{assert_noop, assert_ok, traits::Currency as _};
+	use primitives::v1::BlockNumber;
+
+	fn new_session() {
+		let config = Configuration::config();
+		Router::initializer_on_new_session(&SessionChangeNotification {
+			prev_config: config.clone(),
+			new_config: config,
+			..Default::default()
+		});
+	}
+
+	fn genesis_config(paras: Vec<(ParaId, bool)>) -> MockGenesisConfig {
+		let paras = paras
+			.into_iter()
+			.map(|(id, parachain)| (id, ParaGenesisArgs {
+				parachain,
+				genesis_head: Default::default(),
+				validation_code: Default::default(),
+			}))
+			.collect::<Vec<_>>();
+		let balances = paras
+			.iter()
+			.map(|(id, _)| (id.into_account(), 1000))
+			.collect();
+
+		MockGenesisConfig {
+			configuration: crate::configuration::GenesisConfig {
+				config: HostConfiguration {
+					hrmp_open_request_ttl: 2,
+					hrmp_sender_deposit: 100,
+					hrmp_recipient_deposit: 80,
+					hrmp_channel_max_places: 4,
+					hrmp_channel_max_size: 16,
+					hrmp_max_parachain_inbound_channels: 2,
+					hrmp_max_parathread_inbound_channels: 1,
+					hrmp_channel_max_message_size: 8,
+					hrmp_max_parachain_outbound_channels: 2,
+					hrmp_max_parathread_outbound_channels: 1,
+					hrmp_max_message_num_per_candidate: 2,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			paras: crate::paras::GenesisConfig {
+				paras,
+				..Default::default()
+			},
+			balances: pallet_balances::GenesisConfig { balances },
+			..Default::default()
+		}
+	}
+
+	fn para_origin(para: ParaId) -> Origin {
+		Origin::signed(para.into_account())
+	}
+
+	fn free_balance(para: ParaId) -> u128 {
+		Balances::free_balance(&para.into_account())
+	}
+
+	fn reserved_balance(para: ParaId) -> u128 {
+		Balances::reserved_balance(&para.into_account())
+	}
+
+	fn open_channel(sender: ParaId, recipient: ParaId) {
+		assert_ok!(Router::hrmp_init_open_channel(para_origin(sender), recipient, 4, 8));
+		assert_ok!(Router::hrmp_accept_open_channel(para_origin(recipient), sender));
+		new_session();
+	}
+
+	fn channel_exists(sender: ParaId, recipient: ParaId) -> bool {
+		<Router as Store>::HrmpChannels::get(&HrmpChannelId { sender, recipient }).is_some()
+	}
+
+	fn msg(recipient: ParaId, data: Vec<u8>) -> OutboundHrmpMessage<ParaId> {
+		OutboundHrmpMessage { recipient, data }
+	}
+
+	#[test]
+	fn open_channel_works() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true)])).execute_with(|| {
+			assert_ok!(Router::hrmp_init_open_channel(para_origin(a), b, 4, 8));
+			assert_eq!(reserved_balance(a), 100);
+			assert_ok!(Router::hrmp_accept_open_channel(para_origin(b), a));
+			assert_eq!(reserved_balance(b), 80);
+
+			// the channel is only opened at the session boundary.
+			assert!(!channel_exists(a, b));
+			new_session();
+			assert!(channel_exists(a, b));
+
+			assert_eq!(<Router as Store>::HrmpEgressChannelsIndex::get(&a), vec![b]);
+			assert_eq!(<Router as Store>::HrmpIngressChannelsIndex::get(&b), vec![a]);
+			assert!(<Router as Store>::HrmpOpenChannelRequestsList::get().is_empty());
+			assert!(!<Router as Store>::HrmpOpenChannelRequestCount::contains_key(&a));
+			assert!(!<Router as Store>::HrmpAcceptedChannelRequestCount::contains_key(&b));
+		});
+	}
+
+	#[test]
+	fn open_channel_checks() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+		let c = ParaId::from(3);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true), (c, false)])).execute_with(|| {
+			assert_noop!(
+				Router::hrmp_init_open_channel(Origin::signed(1), b, 4, 8),
+				Error::<crate::mock::Test>::NotParaOrigin,
+			);
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(a), a, 4, 8),
+				Error::<crate::mock::Test>::OpenHrmpChannelToSelf,
+			);
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(a), 42.into(), 4, 8),
+				Error::<crate::mock::Test>::OpenHrmpChannelInvalidRecipient,
+			);
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(a), b, 0, 8),
+				Error::<crate::mock::Test>::OpenHrmpChannelZeroCapacity,
+			);
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(a), b, 5, 8),
+				Error::<crate::mock::Test>::OpenHrmpChannelCapacityExceedsLimit,
+			);
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(a), b, 4, 0),
+				Error::<crate::mock::Test>::OpenHrmpChannelZeroMessageSize,
+			);
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(a), b, 4, 9),
+				Error::<crate::mock::Test>::OpenHrmpChannelMessageSizeExceedsLimit,
+			);
+
+			assert_ok!(Router::hrmp_init_open_channel(para_origin(a), b, 4, 8));
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(a), b, 4, 8),
+				Error::<crate::mock::Test>::OpenHrmpChannelAlreadyRequested,
+			);
+
+			// parathreads are allowed only one outbound channel.
+			assert_ok!(Router::hrmp_init_open_channel(para_origin(c), a, 4, 8));
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(c), b, 4, 8),
+				Error::<crate::mock::Test>::OpenHrmpChannelLimitExceeded,
+			);
+
+			assert_ok!(Router::hrmp_accept_open_channel(para_origin(b), a));
+			assert_noop!(
+				Router::hrmp_accept_open_channel(para_origin(b), a),
+				Error::<crate::mock::Test>::AcceptHrmpChannelAlreadyConfirmed,
+			);
+			assert_noop!(
+				Router::hrmp_accept_open_channel(para_origin(b), c),
+				Error::<crate::mock::Test>::AcceptHrmpChannelDoesntExist,
+			);
+
+			new_session();
+			assert_noop!(
+				Router::hrmp_init_open_channel(para_origin(a), b, 4, 8),
+				Error::<crate::mock::Test>::OpenHrmpChannelAlreadyExists,
+			);
+		});
+	}
+
+	#[test]
+	fn stale_open_requests_are_pruned() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true)])).execute_with(|| {
+			assert_ok!(Router::hrmp_init_open_channel(para_origin(a), b, 4, 8));
+			assert_eq!(free_balance(a), 900);
+
+			// the request survives until it reaches the configured ttl.
+			new_session();
+			assert!(<Router as Store>::HrmpOpenChannelRequests::get(
+				&HrmpChannelId { sender: a, recipient: b },
+			).is_some());
+
+			new_session();
+			assert!(<Router as Store>::HrmpOpenChannelRequests::get(
+				&HrmpChannelId { sender: a, recipient: b },
+			).is_none());
+			assert!(<Router as Store>::HrmpOpenChannelRequestsList::get().is_empty());
+			assert!(!<Router as Store>::HrmpOpenChannelRequestCount::contains_key(&a));
+			assert!(!channel_exists(a, b));
+			assert_eq!(free_balance(a), 1000);
+		});
+	}
+
+	#[test]
+	fn close_channel_works() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+		let c = ParaId::from(3);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true), (c, true)])).execute_with(|| {
+			open_channel(a, b);
+			let channel_id = HrmpChannelId { sender: a, recipient: b };
+
+			assert_noop!(
+				Router::hrmp_close_channel(para_origin(c), channel_id.clone()),
+				Error::<crate::mock::Test>::CloseHrmpChannelUnauthorized,
+			);
+			assert_noop!(
+				Router::hrmp_close_channel(para_origin(b), HrmpChannelId { sender: b, recipient: a }),
+				Error::<crate::mock::Test>::CloseHrmpChannelDoesntExist,
+			);
+
+			assert_ok!(Router::hrmp_close_channel(para_origin(b), channel_id.clone()));
+			assert_noop!(
+				Router::hrmp_close_channel(para_origin(a), channel_id.clone()),
+				Error::<crate::mock::Test>::CloseHrmpChannelAlreadyUnderway,
+			);
+
+			// the channel is only closed at the session boundary.
+			assert!(channel_exists(a, b));
+			new_session();
+			assert!(!channel_exists(a, b));
+
+			assert!(!<Router as Store>::HrmpEgressChannelsIndex::contains_key(&a));
+			assert!(!<Router as Store>::HrmpIngressChannelsIndex::contains_key(&b));
+			assert!(<Router as Store>::HrmpCloseChannelRequestsList::get().is_empty());
+			assert_eq!(free_balance(a), 1000);
+			assert_eq!(free_balance(b), 1000);
+		});
+	}
+
+	#[test]
+	fn channels_of_outgoing_paras_are_cleaned_up() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+		let c = ParaId::from(3);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true), (c, true)])).execute_with(|| {
+			open_channel(a, b);
+			open_channel(b, c);
+			open_channel(c, a);
+
+			Router::schedule_para_cleanup(b);
+			new_session();
+
+			assert!(!channel_exists(a, b));
+			assert!(!channel_exists(b, c));
+			assert!(channel_exists(c, a));
+			assert_eq!(<Router as Store>::HrmpEgressChannelsIndex::get(&c), vec![a]);
+			assert_eq!(<Router as Store>::HrmpIngressChannelsIndex::get(&a), vec![c]);
+			assert!(!<Router as Store>::HrmpEgressChannelsIndex::contains_key(&a));
+			assert!(!<Router as Store>::HrmpIngressChannelsIndex::contains_key(&c));
+			assert_eq!(free_balance(b), 1000);
+		});
+	}
+
+	#[test]
+	fn open_requests_of_outgoing_paras_are_cleaned_up() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+		let c = ParaId::from(3);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true), (c, true)])).execute_with(|| {
+			// a pending request where the outgoing para is the recipient.
+			assert_ok!(Router::hrmp_init_open_channel(para_origin(a), b, 4, 8));
+			// a confirmed request where the outgoing para is the sender.
+			assert_ok!(Router::hrmp_init_open_channel(para_origin(b), c, 4, 8));
+			assert_ok!(Router::hrmp_accept_open_channel(para_origin(c), b));
+			// a request not involving the outgoing para.
+			assert_ok!(Router::hrmp_init_open_channel(para_origin(c), a, 4, 8));
+
+			Router::schedule_para_cleanup(b);
+			new_session();
+
+			let remaining = HrmpChannelId { sender: c, recipient: a };
+			assert_eq!(<Router as Store>::HrmpOpenChannelRequestsList::get(), vec![remaining.clone()]);
+			assert!(<Router as Store>::HrmpOpenChannelRequests::contains_key(&remaining));
+			assert!(!<Router as Store>::HrmpOpenChannelRequests::contains_key(
+				&HrmpChannelId { sender: a, recipient: b },
+			));
+			assert!(!<Router as Store>::HrmpOpenChannelRequests::contains_key(
+				&HrmpChannelId { sender: b, recipient: c },
+			));
+
+			assert!(!<Router as Store>::HrmpOpenChannelRequestCount::contains_key(&a));
+			assert!(!<Router as Store>::HrmpOpenChannelRequestCount::contains_key(&b));
+			assert_eq!(<Router as Store>::HrmpOpenChannelRequestCount::get(&c), 1);
+			assert!(!<Router as Store>::HrmpAcceptedChannelRequestCount::contains_key(&c));
+
+			assert!(!channel_exists(b, c));
+			assert_eq!(free_balance(a), 1000);
+			assert_eq!(free_balance(b), 1000);
+			assert_eq!(free_balance(c), 900);
+			assert_eq!(reserved_balance(c), 100);
+		});
+	}
+
+	#[test]
+	fn check_outbound_hrmp() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+		let c = ParaId::from(3);
+		let d = ParaId::from(4);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true), (c, true), (d, true)])).execute_with(|| {
+			open_channel(a, b);
+			open_channel(a, c);
+
+			let config = Configuration::config();
+
+			assert!(Router::check_outbound_hrmp(&config, a, &[]));
+			assert!(Router::check_outbound_hrmp(&config, a, &[msg(b, vec![1, 2, 3])]));
+			assert!(Router::check_outbound_hrmp(&config, a, &[msg(b, vec![1]), msg(c, vec![2])]));
+
+			// there is no channel to `d`.
+			assert!(!Router::check_outbound_hrmp(&config, a, &[msg(d, vec![1, 2, 3])]));
+			// the message is too big.
+			assert!(!Router::check_outbound_hrmp(&config, a, &[msg(b, vec![0; 9])]));
+			// the messages are not sorted by recipient.
+			assert!(!Router::check_outbound_hrmp(&config, a, &[msg(c, vec![1]), msg(b, vec![2])]));
+			// two messages are sent to the same recipient.
+			assert!(!Router::check_outbound_hrmp(&config, a, &[msg(b, vec![1]), msg(b, vec![2])]));
+		});
+	}
+
+	#[test]
+	fn check_outbound_hrmp_enforces_message_num_per_candidate() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+		let c = ParaId::from(3);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true), (c, true)])).execute_with(|| {
+			open_channel(a, b);
+			open_channel(a, c);
+
+			let msgs = [msg(b, vec![1]), msg(c, vec![2])];
+			let config = HostConfiguration {
+				hrmp_max_message_num_per_candidate: 1,
+				..Configuration::config()
+			};
+			assert!(!Router::check_outbound_hrmp(&config, a, &msgs));
+			assert!(Router::check_outbound_hrmp(&config, a, &msgs[..1]));
+		});
+	}
+
+	#[test]
+	fn check_outbound_hrmp_accounts_for_queued() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+		let c = ParaId::from(3);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true), (c, true)])).execute_with(|| {
+			open_channel(a, b);
+			open_channel(a, c);
+
+			let config = Configuration::config();
+
+			// the channel size limit is 16 bytes.
+			Router::queue_outbound_hrmp(a, vec![msg(b, vec![0; 8])]);
+			Router::queue_outbound_hrmp(a, vec![msg(b, vec![0; 8])]);
+			assert!(!Router::check_outbound_hrmp(&config, a, &[msg(b, vec![0])]));
+
+			// the channel places limit is 4 messages.
+			for _ in 0..4 {
+				Router::queue_outbound_hrmp(a, vec![msg(c, vec![])]);
+			}
+			assert!(!Router::check_outbound_hrmp(&config, a, &[msg(c, vec![])]));
+		});
+	}
+
+	#[test]
+	fn queue_and_prune_hrmp() {
+		let a = ParaId::from(1);
+		let b = ParaId::from(2);
+		let c = ParaId::from(3);
+
+		new_test_ext(genesis_config(vec![(a, true), (b, true), (c, true)])).execute_with(|| {
+			open_channel(a, c);
+			open_channel(b, c);
+
+			System::set_block_number(2);
+			Router::queue_outbound_hrmp(a, vec![msg(c, vec![1, 2, 3])]);
+			System::set_block_number(3);
+			Router::queue_outbound_hrmp(b, vec![msg(c, vec![4, 5])]);
+			Router::queue_outbound_hrmp(a, vec![msg(c, vec![6])]);
+
+			assert_eq!(
+				<Router as Store>::HrmpChannelDigests::get(&c),
+				vec![(2, vec![a]), (3, vec![a, b])],
+			);
+
+			let contents = Router::inbound_hrmp_channels_contents(c);
+			assert_eq!(contents.len(), 2);
+			assert_eq!(contents[&a].len(), 2);
+			assert_eq!(contents[&b], vec![InboundHrmpMessage { sent_at: 3, data: vec![4, 5] }]);
+
+			// recompute the chain by hand and compare it against the stored head.
+			let expected_head = contents[&a].iter().fold(Hash::zero(), |head, m| {
+				BlakeTwo256::hash_of(&(head, m.sent_at, BlakeTwo256::hash_of(&m.data)))
+			});
+			let mqc_heads = Router::hrmp_mqc_heads(c);
+			assert_eq!(mqc_heads.len(), 2);
+			assert_eq!(mqc_heads[0], (a, expected_head));
+
+			// the watermark can land either on the relay parent or on a block with messages.
+			assert!(Router::check_hrmp_watermark(c, 4, 2));
+			assert!(!Router::check_hrmp_watermark(c, 4, 1));
+			assert!(Router::check_hrmp_watermark(c, 4, 4));
+			assert!(!Router::check_hrmp_watermark(c, 4, 5));
+
+			Router::prune_hrmp(c, 2);
+			assert_eq!(<Router as Store>::HrmpChannelDigests::get(&c), vec![(3, vec![a, b])]);
+			assert_eq!(Router::inbound_hrmp_channels_contents(c)[&a].len(), 1);
+			let channel = <Router as Store>::HrmpChannels::get(&HrmpChannelId { sender: a, recipient: c })
+				.unwrap();
+			assert_eq!(channel.used_places, 1);
+			assert_eq!(channel.used_bytes, 1);
+
+			// the watermark must advance.
+			assert!(!Router::check_hrmp_watermark(c, 4, 2));
+			assert!(Router::check_hrmp_watermark(c, 4, 3));
+
+			Router::prune_hrmp(c, 3);
+			assert!(<Router as Store>::HrmpChannelDigests::get(&c).is_empty());
+			assert!(Router::inbound_hrmp_channels_contents(c).values().all(|v| v.is_empty()));
+			assert_eq!(<Router as Store>::HrmpWatermarks::get(&c), Some(3 as BlockNumber));
+		});
+	}
+}
@@ -0,0 +1,533 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{Trait, Module, Store};
+use crate::configuration::{self, HostConfiguration};
+use sp_std::prelude::*;
+use sp_std::marker::PhantomData;
+use sp_runtime::traits::Dispatchable;
+use frame_support::{
+	StorageMap, StorageValue,
+	dispatch::PostDispatchInfo,
+	weights::{GetDispatchInfo, Weight, extract_actual_weight},
+	traits::Get,
+};
+use codec::Decode;
+use primitives::v1::{AccountIdConversion, Id as ParaId, ParachainDispatchOrigin, UpwardMessage};
+
+/// All upward messages coming from parachains will be funneled into an implementation of this trait.
+///
+/// The router only guarantees that the messages are handed over in the order they were sent by a
+/// given para. What happens to the message afterwards, including whether it is dispatched at all,
+/// is up to the implementation.
+pub trait UmpSink {
+	/// Process an incoming upward message and return the amount of weight it consumed.
+	///
+	/// See the trait docs for more details.
+	fn process_upward_message(origin: ParaId, msg: UpwardMessage) -> Weight;
+}
+
+/// An implementation of a sink that just swallows the message without consuming any weight.
+impl UmpSink for () {
+	fn process_upward_message(_: ParaId, _: UpwardMessage) -> Weight {
+		0
+	}
+}
+
+/// An [`UmpSink`] that decodes upward messages into calls of the runtime and dispatches them.
+///
+/// Only messages with the `Signed` dispatch origin are dispatched, using the account derived from
+/// the para id as the signer. Messages that cannot be decoded, request any other origin or declare
+/// more weight than `config.dispatchable_upward_message_critical_weight` are dropped.
+pub struct SignedDispatchUmpSink<T>(PhantomData<T>);
+
+impl<T> UmpSink for SignedDispatchUmpSink<T> where
+	T: configuration::Trait,
+	T::Call: Decode + GetDispatchInfo + Dispatchable<Origin = T::Origin, PostInfo = PostDispatchInfo>,
+{
+	fn process_upward_message(origin: ParaId, msg: UpwardMessage) -> Weight {
+		match msg.origin {
+			ParachainDispatchOrigin::Signed => {}
+			ParachainDispatchOrigin::Parachain | ParachainDispatchOrigin::Root => return 0,
+		}
+
+		let call = match T::Call::decode(&mut &msg.data[..]) {
+			Ok(call) => call,
+			Err(_) => return 0,
+		};
+
+		let dispatch_info = call.get_dispatch_info();
+		let critical_weight = <configuration::Module<T>>::config()
+			.dispatchable_upward_message_critical_weight;
+		if dispatch_info.weight > critical_weight {
+			return 0;
+		}
+
+		let signer: T::AccountId = origin.into_account();
+		let res = call.dispatch(frame_system::RawOrigin::Signed(signer).into());
+
+		extract_actual_weight(&res, &dispatch_info)
+	}
+}
+
+/// Routines related to the upward message passing.
+impl<T: Trait> Module<T> {
+	pub(super) fn clean_ump_after_outgoing(outgoing_para: ParaId) {
+		<Self as Store>::RelayDispatchQueueSize::remove(&outgoing_para);
+		<Self as Store>::RelayDispatchQueues::remove(&outgoing_para);
+
+		// Remove the outgoing para from the `NeedsDispatch` list and from
+		// `NextDispatchRoundStartWith`.
+		//
+		// That's needed for maintaining invariant that `NextDispatchRoundStartWith` points to an
+		// existing item in `NeedsDispatch`.
+		<Self as Store>::NeedsDispatch::mutate(|v| {
+			if let Ok(i) = v.binary_search(&outgoing_para) {
+				v.remove(i);
+			}
+		});
+		<Self as Store>::NextDispatchRoundStartWith::mutate(|v| {
+			*v = v.filter(|p| *p != outgoing_para)
+		});
+	}
+
+	/// Check that all the upward messages sent by a candidate pass the acceptance criteria. Returns
+	/// false, if any of the messages doesn't pass.
+	pub(crate) fn check_upward_messages(
+		config: &HostConfiguration<T::BlockNumber>,
+		para: ParaId,
+		upward_messages: &[UpwardMessage],
+	) -> bool {
+		if upward_messages.len() as u32 > config.max_upward_message_num_per_candidate {
+			return false;
+		}
+
+		let (mut para_queue_count, mut para_queue_size) =
+			<Self as Store>::RelayDispatchQueueSize::get(&para);
+
+		for msg in upward_messages {
+			// Only specially permissioned paras are allowed to request the root origin.
+			if msg.origin == ParachainDispatchOrigin::Root && !para.is_system() {
+				return false;
+			}
+
+			let msg_size = msg.data.len() as u32;
+			if msg_size > config.max_upward_message_size {
+				return false;
+			}
+
+			para_queue_count += 1;
+			para_queue_size = para_queue_size.saturating_add(msg_size);
+		}
+
+		// make sure that the queue is not overfilled.
+		// we do it here only once since returning false invalidates the whole relay-chain block.
+		para_queue_count <= config.max_upward_queue_count
+			&& para_queue_size <= config.max_upward_queue_size
+	}
+
+	/// Enacts all the upward messages sent by a candidate.
+	pub(crate) fn enact_upward_messages(
+		para: ParaId,
+		upward_messages: Vec<UpwardMessage>,
+	) -> Weight {
+		let mut weight = 0;
+
+		if !upward_messages.is_empty() {
+			let (extra_cnt, extra_size) = upward_messages
+				.iter()
+				.fold((0, 0), |(cnt, size), msg| (cnt + 1, size + msg.data.len() as u32));
+
+			<Self as Store>::RelayDispatchQueues::mutate(&para, |v| {
+				v.extend(upward_messages.into_iter())
+			});
+
+			<Self as Store>::RelayDispatchQueueSize::mutate(
+				&para,
+				|(ref mut cnt, ref mut size)| {
+					*cnt += extra_cnt;
+					*size += extra_size;
+				},
+			);
+
+			<Self as Store>::NeedsDispatch::mutate(|v| {
+				if let Err(i) = v.binary_search(&para) {
+					v.insert(i, para);
+				}
+			});
+
+			weight += T::DbWeight::get().reads_writes(3, 3);
+		}
+
+		weight
+	}
+
+	/// Devote some time into dispatching pending upward messages.
+	///
+	/// The paras are served in a round-robin fashion, one message at a time, starting with
+	/// `NextDispatchRoundStartWith`. Processing stops once the consumed weight reaches
+	/// `config.preferred_dispatchable_upward_messages_step_weight`.
+	pub(crate) fn process_pending_upward_messages() -> Weight {
+		let config = <configuration::Module<T>>::config();
+		let mut weight = T::DbWeight::get().reads(3);
+
+		let mut needs_dispatch = <Self as Store>::NeedsDispatch::get();
+		if needs_dispatch.is_empty() {
+			return weight;
+		}
+
+		let mut idx = <Self as Store>::NextDispatchRoundStartWith::get()
+			// well, it would be weird if the para is not found, since `NextDispatchRoundStartWith`
+			// is supposed to be reset once the para leaves `NeedsDispatch`. Let's select 0 as the
+			// starting index as a safe bet in that case.
+			.and_then(|para| needs_dispatch.binary_search(&para).ok())
+			.unwrap_or(0);
+
+		loop {
+			let dispatchee = match needs_dispatch.get(idx) {
+				Some(para) => *para,
+				// can only happen if `needs_dispatch` became empty.
+				None => break,
+			};
+
+			let (upward_message, became_empty) = Self::dequeue_upward_message(dispatchee);
+			weight += T::DbWeight::get().reads_writes(2, 2);
+
+			if let Some(upward_message) = upward_message {
+				weight += T::UmpSink::process_upward_message(dispatchee, upward_message);
+			}
+
+			if became_empty {
+				// the queue is drained and this para doesn't need attention anymore. The next para
+				// slides into the current index.
+				needs_dispatch.remove(idx);
+			} else {
+				idx += 1;
+			}
+
+			if idx >= needs_dispatch.len() {
+				idx = 0;
+			}
+
+			if weight >= config.preferred_dispatchable_upward_messages_step_weight {
+				break;
+			}
+		}
+
+		<Self as Store>::NextDispatchRoundStartWith::set(needs_dispatch.get(idx).cloned());
+		<Self as Store>::NeedsDispatch::put(needs_dispatch);
+
+		weight + T::DbWeight::get().writes(2)
+	}
+
+	/// Pop the first message from the dispatch queue of the given para.
+	///
+	/// Returns the message, if any, and whether the queue became empty. An empty queue is removed
+	/// from storage altogether along with its size entry.
+	fn dequeue_upward_message(para: ParaId) -> (Option<UpwardMessage>, bool) {
+		let mut queue = <Self as Store>::RelayDispatchQueues::get(&para);
+		let upward_message = queue.pop_front();
+
+		if queue.is_empty() {
+			<Self as Store>::RelayDispatchQueues::remove(&para);
+			<Self as Store>::RelayDispatchQueueSize::remove(&para);
+			return (upward_message, true);
+		}
+
+		if let Some(ref msg) = upward_message {
+			<Self as Store>::RelayDispatchQueueSize::mutate(&para, |(ref mut cnt, ref mut size)| {
+				*cnt = cnt.saturating_sub(1);
+				*size = size.saturating_sub(msg.data.len() as u32);
+			});
+		}
+		<Self as Store>::RelayDispatchQueues::insert(&para, queue);
+
+		(upward_message, false)
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod mock_sink {
+	//! An implementation of a mock UMP sink that records all messages it receives.
+
+	use super::{UmpSink, UpwardMessage, ParaId, Weight};
+	use std::cell::RefCell;
+
+	/// The amount of weight charged for every byte of the message payload.
+	pub const WEIGHT_PER_BYTE: Weight = 100;
+
+	std::thread_local! {
+		static PROCESSED: RefCell<Vec<(ParaId, UpwardMessage)>> = RefCell::new(Vec::new());
+	}
+
+	/// A sink that records the processed messages and charges `WEIGHT_PER_BYTE` for each byte of
+	/// the message payload.
+	pub struct MockUmpSink;
+
+	impl UmpSink for MockUmpSink {
+		fn process_upward_message(origin: ParaId, msg: UpwardMessage) -> Weight {
+			let weight = msg.data.len() as Weight * WEIGHT_PER_BYTE;
+			PROCESSED.with(|p| p.borrow_mut().push((origin, msg)));
+			weight
+		}
+	}
+
+	/// Take all the messages processed since the last call.
+	pub fn take_processed() -> Vec<(ParaId, UpwardMessage)> {
+		PROCESSED.with(|p| sp_std::mem::take(&mut *p.borrow_mut()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::mock_sink::take_processed;
+	use crate::router::tests::default_genesis_config;
+	use crate::mock::{Configuration, Router, new_test_ext};
+	use frame_support::IterableStorageMap;
+
+	fn signed_msg(data: Vec<u8>) -> UpwardMessage {
+		UpwardMessage {
+			origin: ParachainDispatchOrigin::Signed,
+			data,
+		}
+	}
+
+	fn queue_upward_msg(para: ParaId, msg: UpwardMessage) {
+		let msgs = vec![msg];
+		assert!(Router::check_upward_messages(&Configuration::config(), para, &msgs));
+		let _ = Router::enact_upward_messages(para, msgs);
+	}
+
+	fn assert_storage_consistency_exhaustive() {
+		// check that empty queues don't clutter the storage.
+		for (_para, queue) in <Router as Store>::RelayDispatchQueues::iter() {
+			assert!(!queue.is_empty());
+		}
+
+		// actually count the counts and sizes in queues and compare them to the bookkeeped version.
+		for (para, queue) in <Router as Store>::RelayDispatchQueues::iter() {
+			let (expected_count, expected_size) = <Router as Store>::RelayDispatchQueueSize::get(para);
+			let (actual_count, actual_size) = queue
+				.into_iter()
+				.fold((0, 0), |(acc_count, acc_size), x| (acc_count + 1, acc_size + x.data.len() as u32));
+
+			assert_eq!(expected_count, actual_count);
+			assert_eq!(expected_size, actual_size);
+		}
+
+		// since we wipe the empty queues the sets of paras in queue contents, queue sizes and
+		// need dispatch set should all be equal.
+		let queue_contents_set = <Router as Store>::RelayDispatchQueues::iter()
+			.map(|(k, _)| k)
+			.collect::<std::collections::HashSet<ParaId>>();
+		let queue_sizes_set = <Router as Store>::RelayDispatchQueueSize::iter()
+			.map(|(k, _)| k)
+			.collect::<std::collections::HashSet<ParaId>>();
+		let needs_dispatch_set = <Router as Store>::NeedsDispatch::get()
+			.into_iter()
+			.collect::<std::collections::HashSet<ParaId>>();
+		assert_eq!(queue_contents_set, queue_sizes_set);
+		assert_eq!(queue_contents_set, needs_dispatch_set);
+
+		// `NextDispatchRoundStartWith` should point into a para that is tracked.
+		if let Some(para) = <Router as Store>::NextDispatchRoundStartWith::get() {
+			assert!(queue_contents_set.contains(&para));
+		}
+
+		// `NeedsDispatch` is always sorted.
+		let needs_dispatch = <Router as Store>::NeedsDispatch::get();
+		assert!(needs_dispatch.windows(2).all(|xs| xs[0] <= xs[1]));
+	}
+
+	#[test]
+	fn check_upward_messages_enforces_limits() {
+		let para = ParaId::from(2000);
+		let system_para = ParaId::from(1);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			let config = Configuration::config();
+
+			// too many messages per candidate.
+			assert!(!Router::check_upward_messages(
+				&config,
+				para,
+				&[signed_msg(vec![1]), signed_msg(vec![2]), signed_msg(vec![3])],
+			));
+
+			// a message that is too big.
+			assert!(!Router::check_upward_messages(&config, para, &[signed_msg(vec![0; 9])]));
+			assert!(Router::check_upward_messages(&config, para, &[signed_msg(vec![0; 8])]));
+
+			// only system paras may request the root origin.
+			let root_msg = UpwardMessage { origin: ParachainDispatchOrigin::Root, data: vec![] };
+			assert!(!Router::check_upward_messages(&config, para, &[root_msg.clone()]));
+			assert!(Router::check_upward_messages(&config, system_para, &[root_msg]));
+		});
+	}
+
+	#[test]
+	fn check_upward_messages_accounts_for_queued() {
+		let para = ParaId::from(2000);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			// the queue size limit is 16 bytes.
+			queue_upward_msg(para, signed_msg(vec![0; 8]));
+			queue_upward_msg(para, signed_msg(vec![0; 8]));
+			assert!(!Router::check_upward_messages(
+				&Configuration::config(),
+				para,
+				&[signed_msg(vec![0; 1])],
+			));
+
+			// the queue count limit is 3 messages.
+			let para = ParaId::from(2001);
+			queue_upward_msg(para, signed_msg(vec![]));
+			queue_upward_msg(para, signed_msg(vec![]));
+			queue_upward_msg(para, signed_msg(vec![]));
+			assert!(!Router::check_upward_messages(
+				&Configuration::config(),
+				para,
+				&[signed_msg(vec![])],
+			));
+
+			assert_storage_consistency_exhaustive();
+		});
+	}
+
+	#[test]
+	fn dispatch_empty() {
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			assert_storage_consistency_exhaustive();
+
+			// make sure that the case with empty queues is handled properly
+			Router::process_pending_upward_messages();
+			assert!(take_processed().is_empty());
+
+			assert_storage_consistency_exhaustive();
+		});
+	}
+
+	#[test]
+	fn dispatch_single_message() {
+		let a = ParaId::from(228);
+		let msg = signed_msg(vec![1, 2, 3]);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			queue_upward_msg(a, msg.clone());
+			Router::process_pending_upward_messages();
+			assert_eq!(take_processed(), vec![(a, msg)]);
+
+			assert_storage_consistency_exhaustive();
+			assert!(<Router as Store>::NeedsDispatch::get().is_empty());
+			assert!(<Router as Store>::NextDispatchRoundStartWith::get().is_none());
+		});
+	}
+
+	#[test]
+	fn dispatch_round_robin_resumes_after_exceeding_step_weight() {
+		let a = ParaId::from(128);
+		let b = ParaId::from(256);
+
+		// every message consumes 400 weight, the preferred step weight is 1000. That means that
+		// 3 messages are dispatched per round.
+		let a_msg_1 = signed_msg(vec![1; 4]);
+		let a_msg_2 = signed_msg(vec![2; 4]);
+		let a_msg_3 = signed_msg(vec![3; 4]);
+		let b_msg_1 = signed_msg(vec![4; 4]);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			queue_upward_msg(a, a_msg_1.clone());
+			queue_upward_msg(a, a_msg_2.clone());
+			queue_upward_msg(a, a_msg_3.clone());
+			queue_upward_msg(b, b_msg_1.clone());
+
+			assert_storage_consistency_exhaustive();
+
+			Router::process_pending_upward_messages();
+			assert_eq!(
+				take_processed(),
+				vec![(a, a_msg_1), (b, b_msg_1), (a, a_msg_2)],
+			);
+			assert_eq!(<Router as Store>::NextDispatchRoundStartWith::get(), Some(a));
+
+			assert_storage_consistency_exhaustive();
+
+			Router::process_pending_upward_messages();
+			assert_eq!(take_processed(), vec![(a, a_msg_3)]);
+
+			assert_storage_consistency_exhaustive();
+			assert!(<Router as Store>::NextDispatchRoundStartWith::get().is_none());
+		});
+	}
+
+	#[test]
+	fn dispatch_starts_with_next_dispatch_round_para() {
+		let a = ParaId::from(128);
+		let b = ParaId::from(256);
+		let c = ParaId::from(512);
+
+		let msg = signed_msg(vec![1; 8]);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			queue_upward_msg(a, msg.clone());
+			queue_upward_msg(b, msg.clone());
+			queue_upward_msg(c, msg.clone());
+
+			// every message consumes 800 weight, so two are processed per round.
+			Router::process_pending_upward_messages();
+			assert_eq!(take_processed(), vec![(a, msg.clone()), (b, msg.clone())]);
+			assert_eq!(<Router as Store>::NextDispatchRoundStartWith::get(), Some(c));
+
+			assert_storage_consistency_exhaustive();
+
+			queue_upward_msg(a, msg.clone());
+
+			Router::process_pending_upward_messages();
+			assert_eq!(take_processed(), vec![(c, msg.clone()), (a, msg.clone())]);
+
+			assert_storage_consistency_exhaustive();
+		});
+	}
+
+	#[test]
+	fn queues_of_outgoing_paras_are_cleaned_up() {
+		let a = ParaId::from(128);
+		let b = ParaId::from(256);
+
+		let msg = signed_msg(vec![1; 8]);
+
+		new_test_ext(default_genesis_config()).execute_with(|| {
+			queue_upward_msg(a, msg.clone());
+			queue_upward_msg(a, msg.clone());
+			queue_upward_msg(b, msg.clone());
+
+			// this leaves `NextDispatchRoundStartWith` pointing at `a`.
+			Router::process_pending_upward_messages();
+			assert_eq!(take_processed(), vec![(a, msg.clone()), (b, msg.clone())]);
+			queue_upward_msg(b, msg.clone());
+			assert_eq!(<Router as Store>::NextDispatchRoundStartWith::get(), Some(a));
+
+			Router::schedule_para_cleanup(a);
+			Router::initializer_on_new_session(&Default::default());
+
+			assert_storage_consistency_exhaustive();
+			assert!(<Router as Store>::RelayDispatchQueues::get(&a).is_empty());
+			assert_eq!(<Router as Store>::NeedsDispatch::get(), vec![b]);
+			assert!(<Router as Store>::NextDispatchRoundStartWith::get().is_none());
+		});
+	}
+}
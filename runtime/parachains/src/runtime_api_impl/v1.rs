@@ -22,11 +22,13 @@ use primitives::v1::{
 	ValidatorId, ValidatorIndex, GroupRotationInfo, CoreState, ValidationData,
 	Id as ParaId, OccupiedCoreAssumption, SessionIndex, ValidationCode,
 	CommittedCandidateReceipt, ScheduledCore, OccupiedCore, CoreOccupied, CoreIndex,
-	GroupIndex, CandidateEvent, PersistedValidationData,
+	GroupIndex, CandidateEvent, PersistedValidationData, InboundDownwardMessage,
+	InboundHrmpMessage, AuthorityDiscoveryId, SessionInfo, ExecutionLimits, ExecutionTimeouts,
 };
+use sp_std::collections::btree_map::BTreeMap;
 use sp_runtime::traits::Zero;
 use frame_support::debug;
-use crate::{initializer, inclusion, scheduler, configuration, paras};
+use crate::{initializer, inclusion, scheduler, configuration, paras, router, session_info};
 
 /// Implementation for the `validators` function of the runtime API.
 pub fn validators<T: initializer::Trait>() -> Vec<ValidatorId> {
@@ -228,6 +230,11 @@ pub fn session_index_for_child<T: initializer::Trait>() -> SessionIndex {
 	<inclusion::Module<T>>::session_index()
 }
 
+/// Implementation for the `session_info` function of the runtime API.
+pub fn session_info<T: initializer::Trait>(index: SessionIndex) -> Option<SessionInfo> {
+	<session_info::Module<T>>::session_info(index)
+}
+
 /// Implementation for the `validation_code` function of the runtime API.
 pub fn validation_code<T: initializer::Trait>(
 	para_id: ParaId,
@@ -240,6 +247,11 @@ pub fn validation_code<T: initializer::Trait>(
 	)
 }
 
+/// Implementation for the `future_validation_code` function of the runtime API.
+pub fn future_validation_code<T: initializer::Trait>(para_id: ParaId) -> Option<ValidationCode> {
+	<paras::Module<T>>::future_code(&para_id)
+}
+
 /// Implementation for the `candidate_pending_availability` function of the runtime API.
 pub fn candidate_pending_availability<T: initializer::Trait>(para_id: ParaId)
 	-> Option<CommittedCandidateReceipt<T::Hash>>
@@ -260,9 +272,77 @@ where
 	<frame_system::Module<T>>::events().into_iter()
 		.filter_map(|record| extract_event(record.event))
 		.map(|event| match event {
-			RawEvent::<T>::CandidateBacked(c, h) => CandidateEvent::CandidateBacked(c, h),
-			RawEvent::<T>::CandidateIncluded(c, h) => CandidateEvent::CandidateIncluded(c, h),
-			RawEvent::<T>::CandidateTimedOut(c, h) => CandidateEvent::CandidateTimedOut(c, h),
+			RawEvent::<T>::CandidateBacked(c, h, core) => CandidateEvent::CandidateBacked(c, h, core),
+			RawEvent::<T>::CandidateIncluded(c, h, core) => CandidateEvent::CandidateIncluded(c, h, core),
+			RawEvent::<T>::CandidateTimedOut(c, h, core) => CandidateEvent::CandidateTimedOut(c, h, core),
 		})
 		.collect()
 }
+
+/// Implementation for the `dmq_contents` function of the runtime API.
+pub fn dmq_contents<T: initializer::Trait>(
+	recipient: ParaId,
+) -> Vec<InboundDownwardMessage<T::BlockNumber>> {
+	<router::Module<T>>::dmq_contents(recipient)
+}
+
+/// Implementation for the `inbound_hrmp_channels_contents` function of the runtime API.
+pub fn inbound_hrmp_channels_contents<T: initializer::Trait>(
+	recipient: ParaId,
+) -> BTreeMap<ParaId, Vec<InboundHrmpMessage<T::BlockNumber>>> {
+	<router::Module<T>>::inbound_hrmp_channels_contents(recipient)
+}
+
+/// Implementation for the `validator_discovery` function of the runtime API.
+///
+/// Every validator is resolved through the session keys registered by its owner, so validators
+/// which aren't known to the session module resolve to `None`.
+pub fn validator_discovery<T>(validators: Vec<ValidatorId>) -> Vec<Option<AuthorityDiscoveryId>>
+where
+	T: initializer::Trait + pallet_session::Trait,
+{
+	use codec::Encode;
+	use frame_support::{storage::migration::get_storage_value, StorageHasher, Twox64Concat};
+	use sp_runtime::{RuntimeAppPublic, traits::OpaqueKeys};
+
+	// `pallet_session` doesn't expose its `key_owner` and `load_keys` getters, so read the
+	// underlying `KeyOwner` and `NextKeys` maps directly.
+	fn key_owner<T: pallet_session::Trait>(id: &ValidatorId) -> Option<T::ValidatorId> {
+		let key = (ValidatorId::ID, id.to_raw_vec()).encode();
+		get_storage_value(b"Session", b"KeyOwner", &Twox64Concat::hash(&key))
+	}
+
+	fn load_keys<T: pallet_session::Trait>(owner: &T::ValidatorId) -> Option<T::Keys> {
+		get_storage_value(b"Session", b"NextKeys", &Twox64Concat::hash(&owner.encode()))
+	}
+
+	validators.iter()
+		.map(|id| {
+			let owner = key_owner::<T>(id)?;
+			load_keys::<T>(&owner)?.get(AuthorityDiscoveryId::ID)
+		})
+		.collect()
+}
+
+/// Implementation for the `execution_limits` function of the runtime API.
+pub fn execution_limits<T: initializer::Trait>() -> ExecutionLimits {
+	let config = <configuration::Module<T>>::config();
+	let defaults = ExecutionLimits::default();
+
+	ExecutionLimits {
+		max_memory_pages: config.max_pvf_memory_pages.unwrap_or(defaults.max_memory_pages),
+		max_stack_height: config.max_pvf_stack_height.unwrap_or(defaults.max_stack_height),
+		max_fuel: config.max_pvf_fuel,
+	}
+}
+
+/// Implementation for the `execution_timeouts` function of the runtime API.
+pub fn execution_timeouts<T: initializer::Trait>() -> ExecutionTimeouts {
+	let config = <configuration::Module<T>>::config();
+	let defaults = ExecutionTimeouts::default();
+
+	ExecutionTimeouts {
+		backing: config.pvf_backing_timeout.unwrap_or(defaults.backing),
+		approval: config.pvf_approval_timeout.unwrap_or(defaults.approval),
+	}
+}
@@ -42,11 +42,14 @@ use primitives::v1::{
 	GroupIndex, ParathreadClaim, ParathreadEntry, GroupRotationInfo, ScheduledCore,
 };
 use frame_support::{
-	decl_storage, decl_module, decl_error,
+	decl_storage, decl_module, decl_error, ensure,
+	dispatch::DispatchResult,
+	traits::{Currency, ReservableCurrency},
 	weights::Weight,
 };
+use frame_system::ensure_signed;
 use codec::{Encode, Decode};
-use sp_runtime::traits::{Saturating, Zero};
+use sp_runtime::traits::{Saturating, Zero, UniqueSaturatedInto};
 
 use rand::{SeedableRng, seq::SliceRandom};
 use rand_chacha::ChaCha20Rng;
@@ -153,7 +156,13 @@ impl CoreAssignment {
 	}
 }
 
-pub trait Trait: frame_system::Trait + configuration::Trait + paras::Trait { }
+type BalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+
+pub trait Trait: frame_system::Trait + configuration::Trait + paras::Trait {
+	/// The currency used to pay for parathread claims.
+	type Currency: ReservableCurrency<Self::AccountId>;
+}
 
 decl_storage! {
 	trait Store for Module<T: Trait> as ParaScheduler {
@@ -186,17 +195,54 @@ decl_storage! {
 		///
 		/// Bounded by the number of cores: one for each parachain and parathread multiplexer.
 		Scheduled get(fn scheduled): Vec<CoreAssignment>; // sorted ascending by CoreIndex.
+		/// The fees reserved for the claims in the claim index, along with the accounts which paid
+		/// them. Claims which already had a candidate backed have no fee left.
+		///
+		/// Bounded by the size of the claim index.
+		ParathreadClaimFees get(fn parathread_claim_fee):
+			map hasher(twox_64_concat) ParaId => Option<(T::AccountId, BalanceOf<T>)>;
 	}
 }
 
 decl_error! {
-	pub enum Error for Module<T: Trait> { }
+	pub enum Error for Module<T: Trait> {
+		/// The para is not a live parathread.
+		NotParathread,
+		/// The parathread claim queue is full.
+		QueueFull,
+		/// There is already a claim on the parathread.
+		ClaimExists,
+		/// The fee for the claim can't be paid.
+		InsufficientBalance,
+	}
 }
 
 decl_module! {
 	/// The scheduler module.
 	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
 		type Error = Error<T>;
+
+		/// Submit a claim on authoring the next block of a live parathread with the given collator.
+		///
+		/// The caller pays the `parathread_claim_fee` of the configuration. The fee is burned once
+		/// a candidate is backed for the claim or the claim is dropped after `parathread_retries`,
+		/// and refunded if the claim is dropped for any other reason before it is backed.
+		#[weight = 100_000_000]
+		pub fn submit_parathread_claim(origin, para_id: ParaId, collator: CollatorId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			let fee: BalanceOf<T> = <configuration::Module<T>>::config()
+				.parathread_claim_fee
+				.unique_saturated_into();
+			ensure!(T::Currency::can_reserve(&who, fee), Error::<T>::InsufficientBalance);
+
+			Self::add_parathread_claim(ParathreadClaim(para_id, collator))?;
+
+			T::Currency::reserve(&who, fee)?;
+			<ParathreadClaimFees<T>>::insert(para_id, (who, fee));
+
+			Ok(())
+		}
 	}
 }
 
@@ -222,6 +268,14 @@ impl<T: Trait> Module<T> {
 
 					if entry.retries <= config.parathread_retries {
 						queue.enqueue_entry(entry, config.parathread_cores);
+					} else {
+						// the claim had all of its attempts, so it is dropped and paid for.
+						ParathreadClaimIndex::mutate(|index| {
+							if let Ok(i) = index.binary_search(&core_assignment.para_id) {
+								index.remove(i);
+							}
+						});
+						Self::burn_claim_fee(core_assignment.para_id);
 					}
 				}
 			}
@@ -297,13 +351,16 @@ impl<T: Trait> Module<T> {
 					queue: Vec::new(),
 					next_core_offset: 0,
 				};
-				claim_index.clear();
+				for claim_para in claim_index.drain(..) {
+					Self::refund_claim_fee(claim_para);
+				}
 				return;
 			}
 
 			// prune out all entries beyond retry or that no longer correspond to live parathread.
 			thread_queue.queue.retain(|queued| {
-				let will_keep = queued.claim.retries <= config.parathread_retries
+				let beyond_retries = queued.claim.retries > config.parathread_retries;
+				let will_keep = !beyond_retries
 					&& <paras::Module<T>>::is_parathread(queued.claim.claim.0);
 
 				if !will_keep {
@@ -313,6 +370,12 @@ impl<T: Trait> Module<T> {
 					if let Ok(i) = claim_index.binary_search(&claim_para) {
 						claim_index.remove(i);
 					}
+
+					if beyond_retries {
+						Self::burn_claim_fee(claim_para);
+					} else {
+						Self::refund_claim_fee(claim_para);
+					}
 				}
 
 				will_keep
@@ -335,15 +398,14 @@ impl<T: Trait> Module<T> {
 	/// assigned to a core, this call will fail. This call will also fail if the queue is full.
 	///
 	/// Fails if the claim does not correspond to any live parathread.
-	#[allow(unused)]
-	pub fn add_parathread_claim(claim: ParathreadClaim) {
-		if !<paras::Module<T>>::is_parathread(claim.0) { return }
+	pub fn add_parathread_claim(claim: ParathreadClaim) -> DispatchResult {
+		ensure!(<paras::Module<T>>::is_parathread(claim.0), Error::<T>::NotParathread);
 
 		let config = <configuration::Module<T>>::config();
 		let queue_max_size = config.parathread_cores * config.scheduling_lookahead;
 
-		ParathreadQueue::mutate(|queue| {
-			if queue.queue.len() >= queue_max_size as usize { return }
+		ParathreadQueue::try_mutate(|queue| {
+			ensure!(queue.queue.len() < queue_max_size as usize, Error::<T>::QueueFull);
 
 			let para_id = claim.0;
 
@@ -357,13 +419,30 @@ impl<T: Trait> Module<T> {
 				}
 			});
 
-			if competes_with_another { return }
+			ensure!(!competes_with_another, Error::<T>::ClaimExists);
 
 			let entry = ParathreadEntry { claim, retries: 0 };
 			queue.enqueue_entry(entry, config.parathread_cores);
+
+			Ok(())
 		})
 	}
 
+	/// Burn the fee paid for the claim on the parathread, if any is left.
+	fn burn_claim_fee(para_id: ParaId) {
+		if let Some((who, fee)) = <ParathreadClaimFees<T>>::take(para_id) {
+			// dropping the imbalance reduces the total issuance.
+			let _ = T::Currency::slash_reserved(&who, fee);
+		}
+	}
+
+	/// Refund the fee paid for the claim on the parathread, if any is left.
+	fn refund_claim_fee(para_id: ParaId) {
+		if let Some((who, fee)) = <ParathreadClaimFees<T>>::take(para_id) {
+			T::Currency::unreserve(&who, fee);
+		}
+	}
+
 	/// Schedule all unassigned cores, where possible. Provide a list of cores that should be considered
 	/// newly-freed along with the reason for them being freed. The list is assumed to be sorted in
 	/// ascending order by core index.
@@ -522,6 +601,11 @@ impl<T: Trait> Module<T> {
 					let _ = occupied_iter.next();
 
 					availability_cores[assignment.core.0 as usize] = Some(assignment.to_core_occupied());
+
+					// a candidate was backed for the claim, so it is paid for.
+					if let AssignmentKind::Parathread(_, _) = assignment.kind {
+						Self::burn_claim_fee(assignment.para_id);
+					}
 				}
 
 				retain
@@ -727,10 +811,13 @@ mod tests {
 	use super::*;
 
 	use primitives::v1::{BlockNumber, ValidatorId, CollatorId};
-	use frame_support::traits::{OnFinalize, OnInitialize};
+	use frame_support::{assert_ok, assert_noop, traits::{OnFinalize, OnInitialize}};
 	use keyring::Sr25519Keyring;
 
-	use crate::mock::{new_test_ext, Configuration, Paras, System, Scheduler, GenesisConfig as MockGenesisConfig};
+	use crate::mock::{
+		new_test_ext, Balances, Configuration, Origin, Paras, System, Scheduler, Test,
+		GenesisConfig as MockGenesisConfig,
+	};
 	use crate::initializer::SessionChangeNotification;
 	use crate::configuration::HostConfiguration;
 	use crate::paras::ParaGenesisArgs;
@@ -799,7 +886,7 @@ mod tests {
 			assert!(Paras::is_parathread(thread_id));
 
 			{
-				Scheduler::add_parathread_claim(ParathreadClaim(thread_id, collator.clone()));
+				assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_id, collator.clone())));
 				let queue = ParathreadQueue::get();
 				assert_eq!(queue.next_core_offset, 1);
 				assert_eq!(queue.queue.len(), 1);
@@ -815,7 +902,10 @@ mod tests {
 			// due to the index, completing claims are not allowed.
 			{
 				let collator2 = CollatorId::from(Sr25519Keyring::Bob.public());
-				Scheduler::add_parathread_claim(ParathreadClaim(thread_id, collator2.clone()));
+				assert_noop!(
+					Scheduler::add_parathread_claim(ParathreadClaim(thread_id, collator2.clone())),
+					Error::<Test>::ClaimExists,
+				);
 				let queue = ParathreadQueue::get();
 				assert_eq!(queue.next_core_offset, 1);
 				assert_eq!(queue.queue.len(), 1);
@@ -828,10 +918,13 @@ mod tests {
 				});
 			}
 
-			// claims on non-live parathreads have no effect.
+			// claims on non-live parathreads are rejected.
 			{
 				let thread_id2 = ParaId::from(11);
-				Scheduler::add_parathread_claim(ParathreadClaim(thread_id2, collator.clone()));
+				assert_noop!(
+					Scheduler::add_parathread_claim(ParathreadClaim(thread_id2, collator.clone())),
+					Error::<Test>::NotParathread,
+				);
 				let queue = ParathreadQueue::get();
 				assert_eq!(queue.next_core_offset, 1);
 				assert_eq!(queue.queue.len(), 1);
@@ -877,7 +970,10 @@ mod tests {
 
 			assert!(Paras::is_parathread(thread_id));
 
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_id, collator.clone()));
+			assert_noop!(
+				Scheduler::add_parathread_claim(ParathreadClaim(thread_id, collator.clone())),
+				Error::<Test>::QueueFull,
+			);
 			assert_eq!(ParathreadQueue::get(), Default::default());
 		});
 	}
@@ -1127,8 +1223,8 @@ mod tests {
 			}
 
 			// add a couple of parathread claims.
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone()));
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_c, collator.clone()));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone())));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_c, collator.clone())));
 
 			run_to_block(2, |_| None);
 
@@ -1225,8 +1321,8 @@ mod tests {
 			});
 
 			// add a couple of parathread claims now that the parathreads are live.
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone()));
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_c, collator.clone()));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone())));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_c, collator.clone())));
 
 			run_to_block(2, |_| None);
 
@@ -1250,9 +1346,9 @@ mod tests {
 			// add a couple more parathread claims - the claim on `b` will go to the 3rd parathread core (4)
 			// and the claim on `d` will go back to the 1st parathread core (2). The claim on `e` then
 			// will go for core `3`.
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_b, collator.clone()));
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_d, collator.clone()));
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_e, collator.clone()));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_b, collator.clone())));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_d, collator.clone())));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_e, collator.clone())));
 
 			run_to_block(3, |_| None);
 
@@ -1383,8 +1479,8 @@ mod tests {
 			let session_start_block = <Scheduler as Store>::SessionStartBlock::get();
 			assert_eq!(session_start_block, 1);
 
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone()));
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_b, collator.clone()));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone())));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_b, collator.clone())));
 
 			run_to_block(2, |_| None);
 
@@ -1460,8 +1556,8 @@ mod tests {
 				_ => None,
 			});
 
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone()));
-			Scheduler::add_parathread_claim(ParathreadClaim(thread_b, collator.clone()));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_a, collator.clone())));
+			assert_ok!(Scheduler::add_parathread_claim(ParathreadClaim(thread_b, collator.clone())));
 
 			run_to_block(2, |_| None);
 			assert_eq!(Scheduler::scheduled().len(), 2);
@@ -1474,6 +1570,146 @@ mod tests {
 		});
 	}
 
+	const CLAIM_FEE: u128 = 10;
+
+	fn claim_fee_genesis_config() -> MockGenesisConfig {
+		MockGenesisConfig {
+			configuration: crate::configuration::GenesisConfig {
+				config: HostConfiguration {
+					parathread_claim_fee: CLAIM_FEE,
+					..default_config()
+				},
+				..Default::default()
+			},
+			balances: pallet_balances::GenesisConfig {
+				balances: vec![(1, 100)],
+			},
+			..Default::default()
+		}
+	}
+
+	// activate the parathread at the session change in block 1.
+	fn activate_parathread(thread: ParaId) {
+		Paras::schedule_para_initialize(thread, ParaGenesisArgs {
+			genesis_head: Vec::new().into(),
+			validation_code: Vec::new().into(),
+			parachain: false,
+		});
+
+		run_to_block(1, |number| match number {
+			1 => Some(SessionChangeNotification {
+				new_config: Configuration::config(),
+				validators: vec![
+					ValidatorId::from(Sr25519Keyring::Alice.public()),
+					ValidatorId::from(Sr25519Keyring::Eve.public()),
+				],
+				..Default::default()
+			}),
+			_ => None,
+		});
+	}
+
+	#[test]
+	fn submit_parathread_claim_reserves_fee() {
+		let thread = ParaId::from(1);
+		let collator = CollatorId::from(Sr25519Keyring::Alice.public());
+
+		new_test_ext(claim_fee_genesis_config()).execute_with(|| {
+			activate_parathread(thread);
+
+			assert_noop!(
+				Scheduler::submit_parathread_claim(Origin::signed(1), ParaId::from(2), collator.clone()),
+				Error::<Test>::NotParathread,
+			);
+			assert_noop!(
+				Scheduler::submit_parathread_claim(Origin::signed(2), thread, collator.clone()),
+				Error::<Test>::InsufficientBalance,
+			);
+
+			assert_ok!(Scheduler::submit_parathread_claim(Origin::signed(1), thread, collator.clone()));
+			assert_eq!(Balances::reserved_balance(1), CLAIM_FEE);
+			assert_eq!(Scheduler::parathread_claim_fee(thread), Some((1, CLAIM_FEE)));
+
+			assert_noop!(
+				Scheduler::submit_parathread_claim(Origin::signed(1), thread, collator.clone()),
+				Error::<Test>::ClaimExists,
+			);
+		});
+	}
+
+	#[test]
+	fn claim_fee_is_burned_when_backed() {
+		let thread = ParaId::from(1);
+		let collator = CollatorId::from(Sr25519Keyring::Alice.public());
+
+		new_test_ext(claim_fee_genesis_config()).execute_with(|| {
+			activate_parathread(thread);
+			let issuance = Balances::total_issuance();
+
+			assert_ok!(Scheduler::submit_parathread_claim(Origin::signed(1), thread, collator.clone()));
+
+			run_to_block(2, |_| None);
+			assert_eq!(Scheduler::scheduled().len(), 1);
+
+			Scheduler::occupied(&[CoreIndex(0)]);
+
+			assert_eq!(Balances::reserved_balance(1), 0);
+			assert_eq!(Balances::free_balance(1), 100 - CLAIM_FEE);
+			assert_eq!(Balances::total_issuance(), issuance - CLAIM_FEE);
+			assert!(Scheduler::parathread_claim_fee(thread).is_none());
+		});
+	}
+
+	#[test]
+	fn claim_fee_is_burned_after_retries() {
+		let max_retries = default_config().parathread_retries;
+		let thread = ParaId::from(1);
+		let collator = CollatorId::from(Sr25519Keyring::Alice.public());
+
+		new_test_ext(claim_fee_genesis_config()).execute_with(|| {
+			activate_parathread(thread);
+
+			assert_ok!(Scheduler::submit_parathread_claim(Origin::signed(1), thread, collator.clone()));
+
+			run_to_block(2 + max_retries, |_| None);
+			assert_eq!(Balances::reserved_balance(1), CLAIM_FEE);
+
+			run_to_block(2 + max_retries + 1, |_| None);
+			assert_eq!(Scheduler::scheduled().len(), 0);
+			assert_eq!(Balances::reserved_balance(1), 0);
+			assert_eq!(Balances::free_balance(1), 100 - CLAIM_FEE);
+
+			// the dropped claim doesn't block further claims.
+			assert_ok!(Scheduler::submit_parathread_claim(Origin::signed(1), thread, collator.clone()));
+		});
+	}
+
+	#[test]
+	fn claim_fee_is_refunded_when_parathread_is_offboarded() {
+		let thread = ParaId::from(1);
+		let collator = CollatorId::from(Sr25519Keyring::Alice.public());
+
+		new_test_ext(claim_fee_genesis_config()).execute_with(|| {
+			activate_parathread(thread);
+
+			assert_ok!(Scheduler::submit_parathread_claim(Origin::signed(1), thread, collator.clone()));
+			assert_eq!(Balances::reserved_balance(1), CLAIM_FEE);
+
+			Paras::schedule_para_cleanup(thread);
+			run_to_block(2, |number| match number {
+				2 => Some(SessionChangeNotification {
+					new_config: Configuration::config(),
+					..Default::default()
+				}),
+				_ => None,
+			});
+
+			assert_eq!(Balances::reserved_balance(1), 0);
+			assert_eq!(Balances::free_balance(1), 100);
+			assert!(Scheduler::parathread_claim_fee(thread).is_none());
+		});
+	}
+
 	#[test]
 	fn availability_predicate_works() {
 		let genesis_config = MockGenesisConfig {
@@ -1713,7 +1949,7 @@ mod tests {
 			let thread_claim_a = ParathreadClaim(thread_a, collator.clone());
 			let thread_claim_b = ParathreadClaim(thread_b, collator.clone());
 
-			Scheduler::add_parathread_claim(thread_claim_a.clone());
+			assert_ok!(Scheduler::add_parathread_claim(thread_claim_a.clone()));
 
 			run_to_block(2, |_| None);
 
@@ -1731,7 +1967,7 @@ mod tests {
 
 				assert!(Scheduler::next_up_on_available(CoreIndex(0)).is_none());
 
-				Scheduler::add_parathread_claim(thread_claim_b);
+				assert_ok!(Scheduler::add_parathread_claim(thread_claim_b));
 
 				let queue = ParathreadQueue::get();
 				assert_eq!(
@@ -1794,7 +2030,7 @@ mod tests {
 			let thread_claim_a = ParathreadClaim(thread_a, collator.clone());
 			let thread_claim_b = ParathreadClaim(thread_b, collator.clone());
 
-			Scheduler::add_parathread_claim(thread_claim_a.clone());
+			assert_ok!(Scheduler::add_parathread_claim(thread_claim_a.clone()));
 
 			run_to_block(2, |_| None);
 
@@ -1820,7 +2056,7 @@ mod tests {
 					}
 				);
 
-				Scheduler::add_parathread_claim(thread_claim_b);
+				assert_ok!(Scheduler::add_parathread_claim(thread_claim_b));
 
 				let queue = ParathreadQueue::get();
 				assert_eq!(
@@ -0,0 +1,196 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The session info module keeps information about the validators of past sessions, which is
+//! needed to check approvals and disputes of candidates included in those sessions.
+//!
+//! Information is kept for all sessions within the dispute period.
+
+use sp_std::prelude::*;
+use primitives::v1::{AuthorityDiscoveryId, SessionIndex, SessionInfo};
+use frame_support::{decl_storage, decl_module, decl_error, weights::Weight};
+
+use crate::{configuration, scheduler, initializer::SessionChangeNotification};
+
+pub trait Trait:
+	frame_system::Trait
+	+ configuration::Trait
+	+ scheduler::Trait
+	+ AuthorityDiscoveryTrait
+{ }
+
+/// The source of the authority discovery keys of the validators.
+pub trait AuthorityDiscoveryTrait {
+	/// The authority discovery keys of the current validators, in the same order as the validators.
+	fn authorities() -> Vec<AuthorityDiscoveryId>;
+}
+
+impl<T: pallet_authority_discovery::Trait> AuthorityDiscoveryTrait for T {
+	fn authorities() -> Vec<AuthorityDiscoveryId> {
+		<pallet_authority_discovery::Module<T>>::authorities()
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ParaSessionInfo {
+		/// The session info of every session within the dispute period.
+		Sessions get(fn session_info): map hasher(twox_64_concat) SessionIndex => Option<SessionInfo>;
+
+		/// The earliest session for which session info is stored.
+		EarliestStoredSession get(fn earliest_stored_session): SessionIndex;
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Trait> { }
+}
+
+decl_module! {
+	/// The session info module.
+	pub struct Module<T: Trait> for enum Call where origin: <T as frame_system::Trait>::Origin {
+		type Error = Error<T>;
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Called by the initializer to initialize the session info module.
+	pub(crate) fn initializer_initialize(_now: T::BlockNumber) -> Weight {
+		0
+	}
+
+	/// Called by the initializer to finalize the session info module.
+	pub(crate) fn initializer_finalize() { }
+
+	/// Called by the initializer to note that a new session has started.
+	///
+	/// This must be called after the scheduler, which determines the validator groups and the
+	/// availability cores of the session.
+	pub(crate) fn initializer_on_new_session(notification: &SessionChangeNotification<T::BlockNumber>) {
+		let config = &notification.new_config;
+		let session_index = notification.session_index;
+
+		let info = SessionInfo {
+			validators: notification.validators.clone(),
+			discovery_keys: <T as AuthorityDiscoveryTrait>::authorities(),
+			validator_groups: <scheduler::Module<T>>::validator_groups(),
+			n_cores: <scheduler::Module<T>>::availability_cores().len() as u32,
+			zeroth_delay_tranche_width: config.zeroth_delay_tranche_width,
+			relay_vrf_modulo_samples: config.relay_vrf_modulo_samples,
+			n_delay_tranches: config.n_delay_tranches,
+			no_show_slots: config.no_show_slots,
+			needed_approvals: config.needed_approvals,
+		};
+
+		Sessions::insert(session_index, info);
+
+		// prune all sessions which left the dispute period.
+		let earliest_kept = session_index.saturating_sub(config.dispute_period);
+		let earliest_stored = EarliestStoredSession::get();
+		for session in earliest_stored..earliest_kept {
+			Sessions::remove(session);
+		}
+
+		if earliest_kept > earliest_stored {
+			EarliestStoredSession::set(earliest_kept);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use primitives::v1::BlockNumber;
+	use keyring::Sr25519Keyring;
+
+	use crate::mock::{new_test_ext, ParaSessionInfo, Scheduler};
+	use crate::configuration::HostConfiguration;
+
+	const VALIDATORS: [Sr25519Keyring; 4] = [
+		Sr25519Keyring::Alice,
+		Sr25519Keyring::Bob,
+		Sr25519Keyring::Charlie,
+		Sr25519Keyring::Dave,
+	];
+
+	fn config() -> HostConfiguration<BlockNumber> {
+		HostConfiguration {
+			dispute_period: 2,
+			parathread_cores: 2,
+			group_rotation_frequency: 10,
+			needed_approvals: 3,
+			relay_vrf_modulo_samples: 2,
+			n_delay_tranches: 20,
+			zeroth_delay_tranche_width: 1,
+			no_show_slots: 4,
+			..Default::default()
+		}
+	}
+
+	fn start_session(session_index: SessionIndex) {
+		let notification = SessionChangeNotification {
+			validators: VALIDATORS.iter().map(|k| k.public().into()).collect(),
+			new_config: config(),
+			session_index,
+			..Default::default()
+		};
+
+		Scheduler::initializer_on_new_session(&notification);
+		ParaSessionInfo::initializer_on_new_session(&notification);
+	}
+
+	#[test]
+	fn session_info_is_taken_from_the_session_change() {
+		new_test_ext(Default::default()).execute_with(|| {
+			start_session(1);
+
+			let info = ParaSessionInfo::session_info(1).unwrap();
+			let config = config();
+
+			assert_eq!(
+				info.validators,
+				VALIDATORS.iter().map(|k| k.public().into()).collect::<Vec<_>>(),
+			);
+			assert_eq!(info.validator_groups, Scheduler::validator_groups());
+			assert_eq!(info.validator_groups.len(), 2);
+			assert_eq!(info.n_cores, config.parathread_cores);
+			assert_eq!(info.needed_approvals, config.needed_approvals);
+			assert_eq!(info.relay_vrf_modulo_samples, config.relay_vrf_modulo_samples);
+			assert_eq!(info.n_delay_tranches, config.n_delay_tranches);
+			assert_eq!(info.zeroth_delay_tranche_width, config.zeroth_delay_tranche_width);
+			assert_eq!(info.no_show_slots, config.no_show_slots);
+		});
+	}
+
+	#[test]
+	fn sessions_outside_the_dispute_period_are_pruned() {
+		new_test_ext(Default::default()).execute_with(|| {
+			for session in 1..=5 {
+				start_session(session);
+			}
+
+			assert_eq!(ParaSessionInfo::earliest_stored_session(), 3);
+
+			for session in 0..3 {
+				assert!(ParaSessionInfo::session_info(session).is_none());
+			}
+
+			for session in 3..=5 {
+				assert!(ParaSessionInfo::session_info(session).is_some());
+			}
+		});
+	}
+}
@@ -21,12 +21,14 @@
 #![recursion_limit="256"]
 
 use sp_std::prelude::*;
+use sp_std::collections::btree_map::BTreeMap;
 use codec::Encode;
 use primitives::v1::{
 	AccountId, AccountIndex, Balance, BlockNumber, Hash, Nonce, Signature, Moment,
 	GroupRotationInfo, CoreState, Id, ValidationData, ValidationCode, CandidateEvent,
 	ValidatorId, ValidatorIndex, CommittedCandidateReceipt, OccupiedCoreAssumption,
-	PersistedValidationData,
+	PersistedValidationData, InboundDownwardMessage, InboundHrmpMessage, SessionInfo,
+	ExecutionLimits, ExecutionTimeouts,
 };
 use runtime_common::{
 	SlowAdjustingFeeUpdate,
@@ -62,14 +64,17 @@ use sp_staking::SessionIndex;
 use pallet_session::historical as session_historical;
 use frame_system::EnsureRoot;
 use runtime_common::paras_sudo_wrapper as paras_sudo_wrapper;
+use runtime_common::paras_registrar;
 
 use runtime_parachains::configuration as parachains_configuration;
+use runtime_parachains::disputes as parachains_disputes;
 use runtime_parachains::inclusion as parachains_inclusion;
 use runtime_parachains::inclusion_inherent as parachains_inclusion_inherent;
 use runtime_parachains::initializer as parachains_initializer;
 use runtime_parachains::paras as parachains_paras;
 use runtime_parachains::router as parachains_router;
 use runtime_parachains::scheduler as parachains_scheduler;
+use runtime_parachains::session_info as parachains_session_info;
 
 pub use pallet_balances::Call as BalancesCall;
 
@@ -191,11 +196,19 @@ sp_api::impl_runtime_apis! {
 			runtime_api_impl::session_index_for_child::<Runtime>()
 		}
 
+		fn session_info(index: SessionIndex) -> Option<SessionInfo> {
+			runtime_api_impl::session_info::<Runtime>(index)
+		}
+
 		fn validation_code(para_id: Id, assumption: OccupiedCoreAssumption)
 			-> Option<ValidationCode> {
 			runtime_api_impl::validation_code::<Runtime>(para_id, assumption)
 		}
 
+		fn future_validation_code(para_id: Id) -> Option<ValidationCode> {
+			runtime_api_impl::future_validation_code::<Runtime>(para_id)
+		}
+
 		fn candidate_pending_availability(para_id: Id) -> Option<CommittedCandidateReceipt<Hash>> {
 			runtime_api_impl::candidate_pending_availability::<Runtime>(para_id)
 		}
@@ -210,6 +223,30 @@ sp_api::impl_runtime_apis! {
 				}
 			})
 		}
+
+		fn dmq_contents(
+			recipient: Id,
+		) -> Vec<InboundDownwardMessage<BlockNumber>> {
+			runtime_api_impl::dmq_contents::<Runtime>(recipient)
+		}
+
+		fn inbound_hrmp_channels_contents(
+			recipient: Id
+		) -> BTreeMap<Id, Vec<InboundHrmpMessage<BlockNumber>>> {
+			runtime_api_impl::inbound_hrmp_channels_contents::<Runtime>(recipient)
+		}
+
+		fn validator_discovery(validators: Vec<ValidatorId>) -> Vec<Option<AuthorityDiscoveryId>> {
+			runtime_api_impl::validator_discovery::<Runtime>(validators)
+		}
+
+		fn execution_limits() -> ExecutionLimits {
+			runtime_api_impl::execution_limits::<Runtime>()
+		}
+
+		fn execution_timeouts() -> ExecutionTimeouts {
+			runtime_api_impl::execution_timeouts::<Runtime>()
+		}
 	}
 
 	impl fg_primitives::GrandpaApi<Block> for Runtime {
@@ -369,12 +406,15 @@ construct_runtime! {
 		Config: parachains_configuration::{Module, Call, Storage},
 		Inclusion: parachains_inclusion::{Module, Call, Storage, Event<T>},
 		InclusionInherent: parachains_inclusion_inherent::{Module, Call, Storage},
+		Disputes: parachains_disputes::{Module, Call, Storage, Event<T>},
 		Scheduler: parachains_scheduler::{Module, Call, Storage},
 		Paras: parachains_paras::{Module, Call, Storage},
 		Initializer: parachains_initializer::{Module, Call, Storage},
 		Router: parachains_router::{Module, Call, Storage},
+		ParaSessionInfo: parachains_session_info::{Module, Call, Storage},
 
 		ParasSudoWrapper: paras_sudo_wrapper::{Module, Call},
+		Registrar: paras_registrar::{Module, Call, Storage, Event<T>},
 	}
 }
 
@@ -585,6 +625,7 @@ impl<C> frame_system::offchain::SendTransactionTypes<C> for Runtime where
 
 parameter_types! {
 	pub const ParathreadDeposit: Balance = 5 * DOLLARS;
+	pub const DataDepositPerByte: Balance = 10 * MILLICENTS;
 	pub const QueueSize: usize = 2;
 	pub const MaxRetries: u32 = 3;
 }
@@ -723,14 +764,34 @@ impl parachains_inclusion::Trait for Runtime {
 
 impl parachains_paras::Trait for Runtime { }
 
-impl parachains_router::Trait for Runtime { }
+impl parachains_router::Trait for Runtime {
+	type UmpSink = parachains_router::SignedDispatchUmpSink<Runtime>;
+	type Currency = Balances;
+}
+
+impl parachains_disputes::Trait for Runtime {
+	type Event = Event;
+	type PunishValidators = parachains_disputes::SlashValidatorsForDisputes<Runtime, Offences>;
+}
 
 impl parachains_inclusion_inherent::Trait for Runtime { }
 
-impl parachains_scheduler::Trait for Runtime { }
+impl parachains_scheduler::Trait for Runtime {
+	type Currency = Balances;
+}
 
 impl parachains_initializer::Trait for Runtime {
 	type Randomness = Babe;
 }
 
+impl parachains_session_info::Trait for Runtime { }
+
 impl paras_sudo_wrapper::Trait for Runtime { }
+
+impl paras_registrar::Trait for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+	type ParathreadDeposit = ParathreadDeposit;
+	type DataDepositPerByte = DataDepositPerByte;
+	type SwapAux = ();
+}
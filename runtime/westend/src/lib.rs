@@ -21,11 +21,13 @@
 #![recursion_limit="256"]
 
 use sp_std::prelude::*;
+use sp_std::collections::btree_map::BTreeMap;
 use codec::{Encode, Decode};
 use primitives::v1::{
 	AccountId, AccountIndex, Balance, BlockNumber, Hash, Nonce, Signature, Moment, ValidatorId,
 	ValidatorIndex, CoreState, Id, CandidateEvent, ValidationData, OccupiedCoreAssumption,
 	CommittedCandidateReceipt, PersistedValidationData, GroupRotationInfo, ValidationCode,
+	InboundDownwardMessage, InboundHrmpMessage, SessionInfo, ExecutionLimits, ExecutionTimeouts,
 };
 use runtime_common::{
 	dummy, purchase, SlowAdjustingFeeUpdate,
@@ -843,10 +845,18 @@ sp_api::impl_runtime_apis! {
 			0
 		}
 
+		fn session_info(_: SessionIndex) -> Option<SessionInfo> {
+			None
+		}
+
 		fn validation_code(_: Id, _: OccupiedCoreAssumption) -> Option<ValidationCode> {
 			None
 		}
 
+		fn future_validation_code(_: Id) -> Option<ValidationCode> {
+			None
+		}
+
 		fn candidate_pending_availability(_: Id) -> Option<CommittedCandidateReceipt<Hash>> {
 			None
 		}
@@ -854,6 +864,30 @@ sp_api::impl_runtime_apis! {
 		fn candidate_events() -> Vec<CandidateEvent<Hash>> {
 			Vec::new()
 		}
+
+		fn dmq_contents(
+			_recipient: Id,
+		) -> Vec<InboundDownwardMessage<BlockNumber>> {
+			Vec::new()
+		}
+
+		fn inbound_hrmp_channels_contents(
+			_recipient: Id
+		) -> BTreeMap<Id, Vec<InboundHrmpMessage<BlockNumber>>> {
+			BTreeMap::new()
+		}
+
+		fn validator_discovery(_: Vec<ValidatorId>) -> Vec<Option<AuthorityDiscoveryId>> {
+			Vec::new()
+		}
+
+		fn execution_limits() -> ExecutionLimits {
+			Default::default()
+		}
+
+		fn execution_timeouts() -> ExecutionTimeouts {
+			Default::default()
+		}
 	}
 
 	impl fg_primitives::GrandpaApi<Block> for Runtime {